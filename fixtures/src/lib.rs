@@ -0,0 +1,296 @@
+// Golden vectors for ZK Casino's cross-component encodings
+// The sequencer, prover and on-chain programs each encode the same batches, proofs and
+// accounts on their own. Their tests check those encoders against the values pinned
+// here, so a change on one side that the other sides would no longer understand fails
+// a test rather than a settlement. The values were produced by the encoders when they
+// were pinned; change one only together with every component that reads it.
+
+/// Decode a hex fixture
+pub fn hex(s: &str) -> Vec<u8> {
+    assert!(s.len().is_multiple_of(2), "odd-length hex fixture");
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).expect("invalid hex fixture"))
+        .collect()
+}
+
+/// Decode a 32-byte hex fixture
+pub fn hex32(s: &str) -> [u8; 32] {
+    hex(s).try_into().expect("fixture is not 32 bytes")
+}
+
+/// One bet of the canonical batch, with the VRF transcript its outcome came from
+pub struct Bet {
+    pub bet_id: u64,
+    pub user: [u8; 32],
+    pub player: &'static str, // Base58 of `user`, as the sequencer keys balances
+    pub bet_amount: u64,
+    pub user_guess: u8,
+    pub outcome: u8,
+    pub payout: u64,
+    pub vrf_message: &'static str, // Bytes the VRF key signs for this bet
+    pub vrf_proof: &'static str,   // Ed25519 signature of the VRF key over vrf_message
+    pub vrf_leaf: &'static str,    // Transcript leaf of the proof
+}
+
+impl Bet {
+    pub fn is_win(&self) -> bool {
+        self.outcome == self.user_guess
+    }
+
+    /// The bet's effect on the user's SOL balance
+    pub fn sol_delta(&self) -> i64 {
+        self.payout as i64 - self.bet_amount as i64
+    }
+}
+
+/// A settlement batch as the sequencer submits it and the verifier checks it
+pub struct Batch {
+    pub batch_id: u64,
+    pub sequencer_nonce: u64,
+    pub game_id: u8,
+    pub prev_state_root: &'static str, // Balance state root before the bets
+    pub new_state_root: &'static str,  // Balance state root after the bets
+    pub vrf_pubkey: &'static str,
+    pub vrf_outputs_root: &'static str, // Merkle root of the bets' transcript leaves
+    pub bets: &'static [Bet],
+}
+
+impl Batch {
+    /// Stakes minus payouts over the batch
+    pub fn house_delta(&self) -> i64 {
+        self.bets.iter().map(|bet| -bet.sol_delta()).sum()
+    }
+
+    /// Groth16 public inputs for the batch, as 32-byte big-endian scalars: the batch
+    /// id, the Poseidon commitment to the bets, then the state roots before and after
+    pub fn public_inputs(&self) -> [[u8; 32]; 4] {
+        let mut batch_id = [0u8; 32];
+        batch_id[24..].copy_from_slice(&self.batch_id.to_be_bytes());
+        [
+            batch_id,
+            hex32(BATCH_COMMITMENT),
+            hex32(self.prev_state_root),
+            hex32(self.new_state_root),
+        ]
+    }
+}
+
+/// Balance state root with no players: the Poseidon tree of all-zero leaves
+pub const EMPTY_STATE_ROOT: &str =
+    "2a7c7c9b6ce5880b9f6f228d72bf6a575a526f29c66ecceef8b753d38bba7323";
+
+/// Ed25519 seed of the VRF key the canonical batch's outcomes were drawn with
+pub const VRF_SEED: [u8; 32] = [7; 32];
+
+/// The canonical batch: a coin flip win and a loss, starting from the empty state
+pub const BATCH: Batch = Batch {
+    batch_id: 7,
+    sequencer_nonce: 7,
+    game_id: 0,
+    prev_state_root: EMPTY_STATE_ROOT,
+    new_state_root: "02296dee845ef7d7e1853dc2808a4ab9653e3e499e34f13089be3fffb450c832",
+    vrf_pubkey: "ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c",
+    vrf_outputs_root: "9cd53786f4f43bd08485a6fcfd3a821c96d7e79b7c95d02887493fe8b92a7e43",
+    bets: &[
+        Bet {
+            bet_id: 101,
+            user: [0x11; 32],
+            player: "29d2S7vB453rNYFdR5Ycwt7y9haRT5fwVwL9zTmBhfV2",
+            bet_amount: 1_000_000,
+            user_guess: 1,
+            outcome: 1,
+            payout: 2_000_000,
+            vrf_message:
+                "zkcasino-vrf-v1:101:29d2S7vB453rNYFdR5Ycwt7y9haRT5fwVwL9zTmBhfV2:1000000:true",
+            vrf_proof: "db656bf9729477cf1c51fe9385cb7866b8c8815447ff30dd22c237439ed320e3\
+                        5595ee92c16c6305a70d42b2836252323469460611f1afac28d55d1ff62e0403",
+            vrf_leaf: "bcf2f39b852aed00ec095d3c86c2d71f49679c9f9f9dfa70d880c9ca9d4e3f25",
+        },
+        Bet {
+            bet_id: 102,
+            user: [0x22; 32],
+            player: "3JF3sEqM796hk5WFqA6EtmEwJQ9quALszsfJyvXNQKy3",
+            bet_amount: 500_000,
+            user_guess: 0,
+            outcome: 1,
+            payout: 0,
+            vrf_message:
+                "zkcasino-vrf-v1:102:3JF3sEqM796hk5WFqA6EtmEwJQ9quALszsfJyvXNQKy3:500000:false",
+            vrf_proof: "56609ae9bbe652a622bc6c9a4ec5be37436e3dc7b1e2d4ecb1c65d0ec17e86e1\
+                        dc6c073a59795e586ddc2e509536e2f244a0609c889731441b9603840f258502",
+            vrf_leaf: "4b0be3c165844f87aa0b219b71ce6086c9d8b0c15ca3b5eece5b5a8982871379",
+        },
+    ],
+};
+
+/// Hash the sequencer signs over the batch
+pub const BATCH_HASH: &str = "03a8e58f91967e1871a09746ab95313ba5249eb9016f88cd437c0f02ad6cb6ea";
+
+/// Poseidon commitment to the batch's bets (big-endian), the proof's second public input
+pub const BATCH_COMMITMENT: &str =
+    "2e8e644f5f72006e70ad7eaff7c5f6921dfb9c90e640dbb5815268cdf4e18a35";
+
+/// Borsh encoding of the batch's bets (Vec<BetSettlement>)
+pub const BETS: &str = "02000000\
+    6500000000000000\
+    1111111111111111111111111111111111111111111111111111111111111111\
+    40420f0000000000\
+    0101\
+    80841e0000000000\
+    6600000000000000\
+    2222222222222222222222222222222222222222222222222222222222222222\
+    20a1070000000000\
+    0001\
+    0000000000000000";
+
+/// begin_batch instruction data for the batch, with an all-zero sequencer signature
+pub const BEGIN_BATCH: &str = "4c43870042f7f600\
+    0700000000000000\
+    0700000000000000\
+    00\
+    2a7c7c9b6ce5880b9f6f228d72bf6a575a526f29c66ecceef8b753d38bba7323\
+    02296dee845ef7d7e1853dc2808a4ab9653e3e499e34f13089be3fffb450c832\
+    ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c\
+    9cd53786f4f43bd08485a6fcfd3a821c96d7e79b7c95d02887493fe8b92a7e43\
+    02000000\
+    0000000000000000000000000000000000000000000000000000000000000000\
+    0000000000000000000000000000000000000000000000000000000000000000";
+
+/// append_batch_chunk instruction data staging all of the batch's bets at offset 0
+pub const APPEND_BATCH_CHUNK: &str = "c92334deb307bdef00000000\
+    02000000\
+    6500000000000000\
+    1111111111111111111111111111111111111111111111111111111111111111\
+    40420f0000000000\
+    0101\
+    80841e0000000000\
+    6600000000000000\
+    2222222222222222222222222222222222222222222222222222222222222222\
+    20a1070000000000\
+    0001\
+    0000000000000000";
+
+/// Timestamp of the proof envelope
+pub const PROOF_TIMESTAMP: u64 = 1_700_000_000;
+
+/// The prover's proof envelope for the batch, with the BN254 group generators standing
+/// in for the proof points: batch id, timestamp, length-prefixed compressed public
+/// inputs (little-endian scalars), then the length-prefixed compressed proof
+pub const PROOF_ENVELOPE: &str = "07000000\
+    00f1536500000000\
+    04000000\
+    20000000\
+    0700000000000000000000000000000000000000000000000000000000000000\
+    20000000\
+    358ae1f4cd685281b5db40e6909cfb1d92f6c5f7af7ead706e00725f4f648e2e\
+    20000000\
+    2373ba8bd353b7f8eecc6ec6296f525a576abf728d226f9f0b88e56c9b7c7c2a\
+    20000000\
+    32c850b4ff3fbe8930f1349e493e3e65b94a8a80c23d85e1d7f75e84ee6d2902\
+    80000000\
+    0100000000000000000000000000000000000000000000000000000000000000\
+    edf692d95cbdde46ddda5ef7d422436779445c5e66006a42761e1f12efde0018\
+    c212f3aeb785e49712e7a9353349aaf1255dfb31b7bf60723a480d9293938e19\
+    0100000000000000000000000000000000000000000000000000000000000000";
+
+/// The same proof points as the on-chain verifier takes them: A, B then C, uncompressed
+/// big-endian, G2 coordinates as (imaginary, real)
+pub const PROOF: &str = "\
+    0000000000000000000000000000000000000000000000000000000000000001\
+    0000000000000000000000000000000000000000000000000000000000000002\
+    198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+    1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+    090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+    12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa\
+    0000000000000000000000000000000000000000000000000000000000000001\
+    0000000000000000000000000000000000000000000000000000000000000002";
+
+/// Sequencer key the account fixtures are written for
+pub const SEQUENCER: [u8; 32] = [0x33; 32];
+
+/// Proof hash recorded in the batch record
+pub const PROOF_HASH: [u8; 32] = [0x44; 32];
+
+/// Slot the batch record says the batch settled in
+pub const SETTLED_SLOT: u64 = 4242;
+
+/// Root over the batch's bets as the verifier commits to them in its BatchRecord
+pub const BATCH_BETS_ROOT: &str =
+    "c8d84743919d3ee7f3dcbbf4a95a931fcf2caf9d66b113f19f95d7dc533539c5";
+
+/// BatchRecord account data for the batch, settled by SEQUENCER
+pub const BATCH_RECORD: &str = "ed9d97517f3b0df2\
+    0700000000000000\
+    3333333333333333333333333333333333333333333333333333333333333333\
+    4444444444444444444444444444444444444444444444444444444444444444\
+    02296dee845ef7d7e1853dc2808a4ab9653e3e499e34f13089be3fffb450c832\
+    02000000\
+    e05ef8ffffffffff\
+    9210000000000000\
+    00\
+    c8d84743919d3ee7f3dcbbf4a95a931fcf2caf9d66b113f19f95d7dc533539c5\
+    00000000000000000000000000\
+    0000000000000000";
+
+/// Registration window of SEQUENCER, which has been rotated out
+pub const REGISTERED_AT: i64 = 1_700_000_000;
+pub const VALID_UNTIL: i64 = 1_700_003_600;
+
+/// SequencerRegistration account data for SEQUENCER
+pub const SEQUENCER_REGISTRATION: &str = "a3f9349992b9db90\
+    3333333333333333333333333333333333333333333333333333333333333333\
+    00f1536500000000\
+    00f1536500000000\
+    0110ff536500000000";
+
+/// Vault update_balances instruction data for the batch's first bet
+pub const UPDATE_BALANCES: &str = "6ffa5651e2744122\
+    40420f0000000000\
+    0000000000000000\
+    01\
+    40420f0000000000";
+
+/// Vault update_balances_batch instruction data, one delta per bet of the batch
+pub const UPDATE_BALANCES_BATCH: &str = "6413484ee52520d0\
+    02000000\
+    1111111111111111111111111111111111111111111111111111111111111111\
+    40420f0000000000\
+    0000000000000000\
+    01\
+    40420f0000000000\
+    2222222222222222222222222222222222222222222222222222222222222222\
+    e05ef8ffffffffff\
+    0000000000000000\
+    00\
+    20a1070000000000";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex("00ff10"), vec![0x00, 0xff, 0x10]);
+        assert_eq!(hex32(BATCH_HASH)[0], 0x03);
+    }
+
+    #[test]
+    fn test_batch_is_consistent() {
+        assert_eq!(BATCH.house_delta(), -500_000);
+        assert!(BATCH.bets[0].is_win() && !BATCH.bets[1].is_win());
+
+        // Public inputs are BN254 scalars, below the field modulus
+        let modulus = hex32("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001");
+        for input in BATCH.public_inputs() {
+            assert!(input < modulus);
+        }
+        assert_eq!(hex(PROOF).len(), 256);
+
+        // The chunk and begin_batch data carry the bets and the batch header verbatim
+        assert_eq!(&hex(APPEND_BATCH_CHUNK)[12..], &hex(BETS)[..]);
+        let begin = hex(BEGIN_BATCH);
+        assert_eq!(begin.len(), 8 + 8 + 8 + 1 + 4 * 32 + 4 + 64);
+        assert_eq!(&begin[57..89], &hex32(BATCH.new_state_root));
+    }
+}
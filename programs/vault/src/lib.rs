@@ -1,7 +1,61 @@
+// Option::is_none_or needs Rust 1.82, newer than the SBF toolchain this builds with
+#![allow(clippy::unnecessary_map_or)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::poseidon::{self, Endianness, Parameters};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
 
 declare_id!("E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx");
 
+/// Maximum approved withdrawal destinations per user
+pub const MAX_WITHDRAWAL_DESTINATIONS: usize = 4;
+/// Delay before a withdrawal allowlist change takes effect (24 hours)
+pub const ALLOWLIST_CHANGE_DELAY: i64 = 24 * 60 * 60;
+/// After this long without settlement the player can reclaim an escrowed stake (1 hour)
+pub const ESCROW_REFUND_DELAY: i64 = 60 * 60;
+/// After this long without the verifier releasing a bet lock, the user can release it (1 hour)
+pub const BET_LOCK_TIMEOUT: i64 = 60 * 60;
+/// The verifier program, the only caller allowed to apply settlement to balances
+pub mod verifier_program {
+    anchor_lang::declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+}
+/// Seed of the verifier PDA that signs its update_balances calls
+pub const VERIFIER_CALLER_SEED: &[u8] = b"vault_caller";
+/// Most deltas in one update_balances_batch, matching the verifier's batch size
+pub const MAX_BATCH_DELTAS: usize = 100;
+/// Version of the TreasuryHealth layout returned by treasury_health
+pub const TREASURY_HEALTH_VERSION: u8 = 2;
+/// Length of the rolling window daily withdrawal limits apply over (24 hours)
+pub const WITHDRAWAL_WINDOW: i64 = 24 * 60 * 60;
+/// Highest protocol fee the authority can set, in basis points of winnings (10%)
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
+/// Basis points in a whole
+pub const BPS_DENOMINATOR: u64 = 10_000;
+/// Slots a forced exit gives the sequencer to settle the user's vault before the user
+/// can withdraw without it (about a day at 400ms slots)
+pub const FORCED_EXIT_DELAY_SLOTS: u64 = 216_000;
+
+/// Seed of a referrer's ReferralAccount PDA, followed by the referrer's key
+pub const REFERRAL_SEED: &[u8] = b"referral";
+/// Seed of a user's queued emergency withdrawal, see request_emergency_withdrawal
+pub const EMERGENCY_WITHDRAWAL_SEED: &[u8] = b"emergency_withdrawal";
+
+/// Depth of the settled-balance state tree (the settlement circuit's), so every
+/// balance inclusion proof has exactly this many steps
+pub const BALANCE_TREE_DEPTH: usize = 16;
+
+/// VaultState::pause_flags bits
+pub const PAUSE_DEPOSITS: u8 = 1 << 0; // SOL, USDC and token deposits
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 1; // SOL, USDC and token withdrawals
+pub const PAUSE_SETTLEMENTS: u8 = 1 << 2; // Balance updates, bet locks and escrowed bets
+pub const PAUSE_ALL: u8 = PAUSE_DEPOSITS | PAUSE_WITHDRAWALS | PAUSE_SETTLEMENTS;
+
 #[program]
 pub mod vault {
     use super::*;
@@ -13,7 +67,15 @@ pub mod vault {
         vault_state.total_users = 0;
         vault_state.total_sol_deposited = 0;
         vault_state.total_usdc_deposited = 0;
-        vault_state.is_paused = false;
+        vault_state.pause_flags = 0;
+        vault_state.paused_until_slot = None;
+        vault_state.pending_authority = None;
+        vault_state.usdc_mint = Pubkey::default();
+        vault_state.usdc_decimals = 0;
+        vault_state.protocol_fee_bps = 0;
+        vault_state.referral_share_bps = 0;
+        vault_state.balances_root = [0; 32]; // None committed yet
+        vault_state.balances_root_batch_id = 0;
 
         msg!(
             "Vault initialized with authority: {}",
@@ -22,8 +84,30 @@ pub mod vault {
         Ok(())
     }
 
-    /// Create a user vault account
-    pub fn initialize_user_vault(ctx: Context<InitializeUserVault>) -> Result<()> {
+    /// Create a user vault account, optionally recording the referrer who brought the
+    /// user in. A referrer needs a ReferralAccount, passed as `referral_account`.
+    pub fn initialize_user_vault(
+        ctx: Context<InitializeUserVault>,
+        referrer: Option<Pubkey>,
+    ) -> Result<()> {
+        if let Some(referrer) = referrer {
+            require_keys_neq!(referrer, ctx.accounts.user.key(), VaultError::SelfReferral);
+            let referral_account = ctx
+                .accounts
+                .referral_account
+                .as_mut()
+                .ok_or(VaultError::ReferralAccountRequired)?;
+            require_keys_eq!(
+                referral_account.referrer,
+                referrer,
+                VaultError::ReferralAccountMismatch
+            );
+            referral_account.referred_users = referral_account
+                .referred_users
+                .checked_add(1)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+
         let user_vault = &mut ctx.accounts.user_vault;
         user_vault.owner = ctx.accounts.user.key();
         user_vault.sol_balance = 0;
@@ -32,6 +116,19 @@ pub mod vault {
         user_vault.total_winnings = 0;
         user_vault.total_losses = 0;
         user_vault.created_at = Clock::get()?.unix_timestamp;
+        user_vault.last_settled_slot = 0;
+        user_vault.exit_requested_slot = None;
+        user_vault.deposit_nonce = 0;
+        user_vault.locked_balance = LockedBalance::default();
+        user_vault.open_escrows = 0;
+        user_vault.referrer = referrer;
+        user_vault.sol_wagered = 0;
+        user_vault.usdc_wagered = 0;
+        user_vault.sol_won = 0;
+        user_vault.usdc_won = 0;
+        user_vault.current_streak = 0;
+        user_vault.last_bet_at = 0;
+        user_vault.exits = 0;
 
         // Update global vault state
         let vault_state = &mut ctx.accounts.vault_state;
@@ -44,11 +141,25 @@ pub mod vault {
         Ok(())
     }
 
-    /// Deposit SOL into user vault (mocked for Phase 2)
+    /// Deposit SOL into user vault, moving the lamports into program custody: the vault
+    /// state account, above its rent
     pub fn deposit_sol(ctx: Context<DepositSol>, amount: u64) -> Result<()> {
-        require!(!ctx.accounts.vault_state.is_paused, VaultError::VaultPaused);
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_DEPOSITS, Clock::get()?.slot)?;
         require!(amount > 0, VaultError::InvalidAmount);
 
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: ctx.accounts.vault_state.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
         let user_vault = &mut ctx.accounts.user_vault;
         user_vault.sol_balance = user_vault
             .sol_balance
@@ -67,6 +178,7 @@ pub mod vault {
             token_type: TokenType::Sol,
             amount,
             new_balance: user_vault.sol_balance,
+            deposit_nonce: None,
             timestamp: Clock::get()?.unix_timestamp,
         });
 
@@ -78,11 +190,118 @@ pub mod vault {
         Ok(())
     }
 
-    /// Deposit USDC into user vault (mocked for Phase 2)
+    /// Credit the user's vault with everything sent to their deposit address, the system
+    /// account PDA ["deposit_address", user] a plain wallet transfer can fund. Anyone may
+    /// relay it: the credit can only land in the user's own vault. The event carries
+    /// the vault's deposit nonce so the sequencer credits each relayed deposit once.
+    ///
+    /// The swept lamports move into program custody, held by the vault state account
+    /// above its rent, so the credited balance is backed by lamports the user can no
+    /// longer spend elsewhere.
+    pub fn deposit_for(ctx: Context<DepositFor>) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_DEPOSITS, Clock::get()?.slot)?;
+        let amount = ctx.accounts.deposit_address.lamports();
+        require!(amount > 0, VaultError::InvalidAmount);
+
+        let user = ctx.accounts.user.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"deposit_address",
+            user.as_ref(),
+            &[ctx.bumps.deposit_address],
+        ]];
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.deposit_address.to_account_info(),
+                    to: ctx.accounts.vault_state.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        let user_vault = &mut ctx.accounts.user_vault;
+        user_vault.sol_balance = user_vault
+            .sol_balance
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        user_vault.deposit_nonce = user_vault
+            .deposit_nonce
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.total_sol_deposited = vault_state
+            .total_sol_deposited
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+
+        emit!(DepositEvent {
+            user,
+            token_type: TokenType::Sol,
+            amount,
+            new_balance: user_vault.sol_balance,
+            deposit_nonce: Some(user_vault.deposit_nonce),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Relayed SOL deposit #{}: {} lamports for user: {}",
+            user_vault.deposit_nonce,
+            amount,
+            user
+        );
+        Ok(())
+    }
+
+    /// Set the USDC mint and create the vault's custody account for it: the associated
+    /// token account of the vault authority PDA. The mint may be an SPL Token or a
+    /// Token-2022 mint, with the matching token program passed. It can only change while
+    /// no USDC is in custody (admin only).
+    pub fn configure_usdc(ctx: Context<ConfigureUsdc>) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        require!(
+            vault_state.total_usdc_deposited == 0,
+            VaultError::UsdcInCustody
+        );
+        vault_state.usdc_mint = ctx.accounts.usdc_mint.key();
+        vault_state.usdc_decimals = ctx.accounts.usdc_mint.decimals;
+
+        msg!(
+            "USDC custody configured: mint {} ({} decimals), custody account {}",
+            vault_state.usdc_mint,
+            vault_state.usdc_decimals,
+            ctx.accounts.vault_token_account.key()
+        );
+        Ok(())
+    }
+
+    /// Deposit USDC (in base units) into user vault, moving it into the vault's custody
     pub fn deposit_usdc(ctx: Context<DepositUsdc>, amount: u64) -> Result<()> {
-        require!(!ctx.accounts.vault_state.is_paused, VaultError::VaultPaused);
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_DEPOSITS, Clock::get()?.slot)?;
         require!(amount > 0, VaultError::InvalidAmount);
 
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.usdc_mint.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.usdc_mint.decimals,
+        )?;
+        // A Token-2022 transfer fee reaches custody short; credit what arrived
+        let amount = received_amount(&ctx.accounts.usdc_mint.to_account_info(), amount)?;
+
         let user_vault = &mut ctx.accounts.user_vault;
         user_vault.usdc_balance = user_vault
             .usdc_balance
@@ -101,6 +320,7 @@ pub mod vault {
             token_type: TokenType::Usdc,
             amount,
             new_balance: user_vault.usdc_balance,
+            deposit_nonce: None,
             timestamp: Clock::get()?.unix_timestamp,
         });
 
@@ -112,14 +332,29 @@ pub mod vault {
         Ok(())
     }
 
-    /// Withdraw SOL from user vault
+    /// Withdraw SOL from user vault, paid out of program custody
     pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
-        require!(!ctx.accounts.vault_state.is_paused, VaultError::VaultPaused);
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_WITHDRAWALS, Clock::get()?.slot)?;
         require!(amount > 0, VaultError::InvalidAmount);
+        enforce_withdrawal_allowlist(
+            &ctx.accounts.withdrawal_allowlist,
+            &ctx.accounts.destination.key(),
+            ctx.program_id,
+        )?;
+
+        enforce_withdrawal_limits(
+            &mut ctx.accounts.vault_state,
+            &mut ctx.accounts.user_vault,
+            TokenType::Sol,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )?;
 
         let user_vault = &mut ctx.accounts.user_vault;
         require!(
-            user_vault.sol_balance >= amount,
+            user_vault.available(&TokenType::Sol) >= amount,
             VaultError::InsufficientBalance
         );
 
@@ -135,6 +370,14 @@ pub mod vault {
             .checked_sub(amount)
             .ok_or(VaultError::MathUnderflow)?;
 
+        transfer_sol_from_custody(
+            &ctx.accounts.vault_state.to_account_info(),
+            &ctx.accounts.destination.to_account_info(),
+            &Rent::get()?,
+            0,
+            amount,
+        )?;
+
         emit!(WithdrawEvent {
             user: ctx.accounts.user.key(),
             token_type: TokenType::Sol,
@@ -151,14 +394,30 @@ pub mod vault {
         Ok(())
     }
 
-    /// Withdraw USDC from user vault
+    /// Withdraw USDC (in base units) from user vault out of the vault's custody. The
+    /// allowlist is checked against the wallet owning the destination token account.
     pub fn withdraw_usdc(ctx: Context<WithdrawUsdc>, amount: u64) -> Result<()> {
-        require!(!ctx.accounts.vault_state.is_paused, VaultError::VaultPaused);
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_WITHDRAWALS, Clock::get()?.slot)?;
         require!(amount > 0, VaultError::InvalidAmount);
+        enforce_withdrawal_allowlist(
+            &ctx.accounts.withdrawal_allowlist,
+            &ctx.accounts.destination.owner,
+            ctx.program_id,
+        )?;
+
+        enforce_withdrawal_limits(
+            &mut ctx.accounts.vault_state,
+            &mut ctx.accounts.user_vault,
+            TokenType::Usdc,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )?;
 
         let user_vault = &mut ctx.accounts.user_vault;
         require!(
-            user_vault.usdc_balance >= amount,
+            user_vault.available(&TokenType::Usdc) >= amount,
             VaultError::InsufficientBalance
         );
 
@@ -174,6 +433,22 @@ pub mod vault {
             .checked_sub(amount)
             .ok_or(VaultError::MathUnderflow)?;
 
+        let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    mint: ctx.accounts.usdc_mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.usdc_mint.decimals,
+        )?;
+
         emit!(WithdrawEvent {
             user: ctx.accounts.user.key(),
             token_type: TokenType::Usdc,
@@ -190,162 +465,3874 @@ pub mod vault {
         Ok(())
     }
 
-    /// Update user vault after settlement (called by verifier program)
-    pub fn update_balances(
-        ctx: Context<UpdateBalances>,
-        sol_delta: i64,
-        usdc_delta: i64,
-        is_win: bool,
-        bet_amount: u64,
+    /// Add a betting currency: register its mint with bet bounds and create the vault's
+    /// custody account for it, the associated token account of the vault authority PDA.
+    /// USDC keeps its dedicated accounting and cannot be registered (admin only).
+    pub fn register_token(ctx: Context<RegisterToken>, min_bet: u64, max_bet: u64) -> Result<()> {
+        require!(
+            ctx.accounts.mint.key() != ctx.accounts.vault_state.usdc_mint,
+            VaultError::UsdcNotRegistrable
+        );
+        SupportedToken::check_bet_limits(min_bet, max_bet)?;
+
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.mint = ctx.accounts.mint.key();
+        supported_token.decimals = ctx.accounts.mint.decimals;
+        supported_token.enabled = true;
+        supported_token.min_bet = min_bet;
+        supported_token.max_bet = max_bet;
+        supported_token.total_deposited = 0;
+        supported_token.limits = WithdrawalLimits::default();
+        supported_token.withdrawals = WithdrawalWindow::default();
+
+        emit!(TokenConfigUpdatedEvent {
+            mint: supported_token.mint,
+            enabled: true,
+            min_bet,
+            max_bet,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Token registered: mint {} ({} decimals), custody account {}",
+            supported_token.mint,
+            supported_token.decimals,
+            ctx.accounts.vault_token_account.key()
+        );
+        Ok(())
+    }
+
+    /// Enable or disable a registered token and change its bet bounds. A disabled token
+    /// takes no deposits; withdrawals stay open (admin only).
+    pub fn update_token(
+        ctx: Context<UpdateToken>,
+        enabled: bool,
+        min_bet: u64,
+        max_bet: u64,
     ) -> Result<()> {
-        require!(!ctx.accounts.vault_state.is_paused, VaultError::VaultPaused);
+        SupportedToken::check_bet_limits(min_bet, max_bet)?;
 
-        let user_vault = &mut ctx.accounts.user_vault;
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.enabled = enabled;
+        supported_token.min_bet = min_bet;
+        supported_token.max_bet = max_bet;
 
-        // Update SOL balance
-        if sol_delta >= 0 {
-            user_vault.sol_balance = user_vault
-                .sol_balance
-                .checked_add(sol_delta as u64)
-                .ok_or(VaultError::MathOverflow)?;
-        } else {
-            let abs_delta = (-sol_delta) as u64;
-            require!(
-                user_vault.sol_balance >= abs_delta,
-                VaultError::InsufficientBalance
-            );
-            user_vault.sol_balance = user_vault
-                .sol_balance
-                .checked_sub(abs_delta)
-                .ok_or(VaultError::MathUnderflow)?;
-        }
+        emit!(TokenConfigUpdatedEvent {
+            mint: supported_token.mint,
+            enabled,
+            min_bet,
+            max_bet,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
 
-        // Update USDC balance
-        if usdc_delta >= 0 {
-            user_vault.usdc_balance = user_vault
-                .usdc_balance
-                .checked_add(usdc_delta as u64)
-                .ok_or(VaultError::MathOverflow)?;
-        } else {
-            let abs_delta = (-usdc_delta) as u64;
-            require!(
-                user_vault.usdc_balance >= abs_delta,
-                VaultError::InsufficientBalance
-            );
-            user_vault.usdc_balance = user_vault
-                .usdc_balance
-                .checked_sub(abs_delta)
-                .ok_or(VaultError::MathUnderflow)?;
-        }
+    /// Set the withdrawal limits for a registered token; zero leaves a limit off
+    /// (admin only)
+    pub fn set_token_withdrawal_limits(
+        ctx: Context<UpdateToken>,
+        limits: WithdrawalLimits,
+    ) -> Result<()> {
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.limits = limits.clone();
 
-        // Update bet statistics
-        user_vault.bet_count = user_vault
-            .bet_count
-            .checked_add(1)
+        emit!(TokenWithdrawalLimitsUpdatedEvent {
+            mint: supported_token.mint,
+            limits: limits.clone(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Withdrawal limits for mint {} set: {} per transaction, {} per user daily, {} globally daily",
+            supported_token.mint,
+            limits.max_per_transaction,
+            limits.max_per_user_daily,
+            limits.max_global_daily
+        );
+        Ok(())
+    }
+
+    /// Create the user's balance account for a registered token
+    pub fn initialize_token_balance(ctx: Context<InitializeTokenBalance>) -> Result<()> {
+        let token_balance = &mut ctx.accounts.token_balance;
+        token_balance.owner = ctx.accounts.user.key();
+        token_balance.mint = ctx.accounts.supported_token.mint;
+        token_balance.balance = 0;
+        token_balance.withdrawals = WithdrawalWindow::default();
+
+        msg!(
+            "Token balance created for: {} (mint {})",
+            token_balance.owner,
+            token_balance.mint
+        );
+        Ok(())
+    }
+
+    /// Deposit a registered token (in base units), moving it into the vault's custody
+    pub fn deposit_token(ctx: Context<DepositToken>, amount: u64) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_DEPOSITS, Clock::get()?.slot)?;
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(
+            ctx.accounts.supported_token.enabled,
+            VaultError::TokenDisabled
+        );
+
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.supported_token.decimals,
+        )?;
+        // A Token-2022 transfer fee reaches custody short; credit what arrived
+        let amount = received_amount(&ctx.accounts.mint.to_account_info(), amount)?;
+
+        let token_balance = &mut ctx.accounts.token_balance;
+        token_balance.balance = token_balance
+            .balance
+            .checked_add(amount)
             .ok_or(VaultError::MathOverflow)?;
 
-        if is_win {
-            user_vault.total_winnings = user_vault
-                .total_winnings
-                .checked_add(bet_amount)
-                .ok_or(VaultError::MathOverflow)?;
-        } else {
-            user_vault.total_losses = user_vault
-                .total_losses
-                .checked_add(bet_amount)
-                .ok_or(VaultError::MathOverflow)?;
-        }
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.total_deposited = supported_token
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
 
-        emit!(BalanceUpdateEvent {
+        emit!(TokenDepositEvent {
+            user: ctx.accounts.user.key(),
+            mint: supported_token.mint,
+            amount,
+            new_balance: token_balance.balance,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Token deposit: {} of mint {} for user: {}",
+            amount,
+            supported_token.mint,
+            ctx.accounts.user.key()
+        );
+        Ok(())
+    }
+
+    /// Withdraw a registered token (in base units) out of the vault's custody. The
+    /// allowlist is checked against the wallet owning the destination token account.
+    pub fn withdraw_token(ctx: Context<WithdrawToken>, amount: u64) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_WITHDRAWALS, Clock::get()?.slot)?;
+        require!(amount > 0, VaultError::InvalidAmount);
+        enforce_withdrawal_allowlist(
+            &ctx.accounts.withdrawal_allowlist,
+            &ctx.accounts.destination.owner,
+            ctx.program_id,
+        )?;
+
+        // The mint's own caps, counted per user and across users of the mint
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.limits.clone().enforce(
+            &mut ctx.accounts.token_balance.withdrawals,
+            &mut supported_token.withdrawals,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let token_balance = &mut ctx.accounts.token_balance;
+        require!(
+            token_balance.balance >= amount,
+            VaultError::InsufficientBalance
+        );
+        token_balance.balance = token_balance
+            .balance
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.total_deposited = supported_token
+            .total_deposited
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            supported_token.decimals,
+        )?;
+
+        emit!(TokenWithdrawEvent {
+            user: ctx.accounts.user.key(),
+            mint: supported_token.mint,
+            amount,
+            new_balance: token_balance.balance,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Token withdrawal: {} of mint {} for user: {}",
+            amount,
+            supported_token.mint,
+            ctx.accounts.user.key()
+        );
+        Ok(())
+    }
+
+    /// Start a forced exit of the user's full balance. If no settlement lands on their
+    /// vault within FORCED_EXIT_DELAY_SLOTS, finalize_exit pays the balance out without
+    /// the sequencer. Allowed while paused: it is the escape hatch for when the operator
+    /// stops cooperating.
+    pub fn initiate_exit(ctx: Context<InitiateExit>) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        let user_vault = &mut ctx.accounts.user_vault;
+        require!(
+            user_vault.exit_requested_slot.is_none() || user_vault.exit_superseded(),
+            VaultError::ExitAlreadyPending
+        );
+        user_vault.exit_requested_slot = Some(slot);
+
+        emit!(ExitInitiatedEvent {
             user: user_vault.owner,
-            sol_delta,
-            usdc_delta,
-            new_sol_balance: user_vault.sol_balance,
-            new_usdc_balance: user_vault.usdc_balance,
-            is_win,
-            bet_count: user_vault.bet_count,
+            sol_balance: user_vault.sol_balance,
+            usdc_balance: user_vault.usdc_balance,
+            requested_slot: slot,
+            finalizable_at_slot: slot.saturating_add(FORCED_EXIT_DELAY_SLOTS),
             timestamp: Clock::get()?.unix_timestamp,
         });
 
         msg!(
-            "Balance updated for user: {}, SOL: {}, USDC: {}",
+            "Forced exit requested for user: {} at slot {}",
             user_vault.owner,
-            sol_delta,
-            usdc_delta
+            slot
         );
         Ok(())
     }
 
-    /// Pause/unpause vault operations (admin only)
-    pub fn set_pause_state(ctx: Context<SetPauseState>, is_paused: bool) -> Result<()> {
+    /// Pay out the user's full balance, locked funds included, once a forced exit has gone
+    /// unanswered for FORCED_EXIT_DELAY_SLOTS. Daily withdrawal limits don't apply; the
+    /// allowlist does for USDC. SOL is paid to the user's wallet out of program custody.
+    /// The USDC accounts are only needed when the vault holds USDC.
+    /// Registered token balances are paid out by finalize_token_exit, which needs the
+    /// exit still pending, so it runs for each token before this closes the exit.
+    pub fn finalize_exit(ctx: Context<FinalizeExit>) -> Result<()> {
+        ctx.accounts.user_vault.check_exit(Clock::get()?.slot)?;
+
+        let user_vault = &mut ctx.accounts.user_vault;
+        let sol_amount = user_vault.sol_balance;
+        let usdc_amount = user_vault.usdc_balance;
+        user_vault.sol_balance = 0;
+        user_vault.usdc_balance = 0;
+        // Bets the unresponsive sequencer never settled no longer hold funds back; their
+        // BetLocks are void and only close when released
+        user_vault.locked_balance = LockedBalance::default();
+        user_vault.exits = user_vault
+            .exits
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+        user_vault.exit_requested_slot = None;
+
         let vault_state = &mut ctx.accounts.vault_state;
-        vault_state.is_paused = is_paused;
+        vault_state.total_sol_deposited = vault_state
+            .total_sol_deposited
+            .checked_sub(sol_amount)
+            .ok_or(VaultError::MathUnderflow)?;
+        vault_state.total_usdc_deposited = vault_state
+            .total_usdc_deposited
+            .checked_sub(usdc_amount)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        if usdc_amount > 0 {
+            let (Some(usdc_mint), Some(vault_token_account), Some(destination)) = (
+                &ctx.accounts.usdc_mint,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.destination,
+            ) else {
+                return Err(VaultError::UsdcAccountsRequired.into());
+            };
+            require_keys_eq!(
+                usdc_mint.key(),
+                vault_state.usdc_mint,
+                VaultError::InvalidMint
+            );
+            require_keys_eq!(destination.mint, usdc_mint.key(), VaultError::InvalidMint);
+            require_keys_eq!(
+                vault_token_account.key(),
+                get_associated_token_address_with_program_id(
+                    &ctx.accounts.vault_authority.key(),
+                    &usdc_mint.key(),
+                    &ctx.accounts.token_program.key(),
+                ),
+                VaultError::InvalidCustodyAccount
+            );
+            enforce_withdrawal_allowlist(
+                &ctx.accounts.withdrawal_allowlist,
+                &destination.owner,
+                ctx.program_id,
+            )?;
+
+            let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: vault_token_account.to_account_info(),
+                        mint: usdc_mint.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                usdc_amount,
+                usdc_mint.decimals,
+            )?;
+        }
+
+        if sol_amount > 0 {
+            transfer_sol_from_custody(
+                &ctx.accounts.vault_state.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &Rent::get()?,
+                0,
+                sol_amount,
+            )?;
+        }
+
+        emit!(ExitFinalizedEvent {
+            user: ctx.accounts.user.key(),
+            sol_amount,
+            usdc_amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        msg!("Vault pause state set to: {}", is_paused);
+        msg!(
+            "Forced exit finalized for user: {}, SOL: {}, USDC: {}",
+            ctx.accounts.user.key(),
+            sol_amount,
+            usdc_amount
+        );
         Ok(())
     }
-}
 
-// Account structures
-#[account]
-pub struct VaultState {
-    pub authority: Pubkey,
-    pub total_users: u64,
-    pub total_sol_deposited: u64,
-    pub total_usdc_deposited: u64,
-    pub is_paused: bool,
-}
+    /// Pay out the user's full balance of a registered token under a forced exit that
+    /// finalize_exit could complete. Like finalize_exit it is allowed while paused and
+    /// skips the daily withdrawal limits; the allowlist applies. The exit stays pending
+    /// for the user's other tokens and finalize_exit.
+    pub fn finalize_token_exit(ctx: Context<FinalizeTokenExit>) -> Result<()> {
+        ctx.accounts.user_vault.check_exit(Clock::get()?.slot)?;
+        enforce_withdrawal_allowlist(
+            &ctx.accounts.withdrawal_allowlist,
+            &ctx.accounts.destination.owner,
+            ctx.program_id,
+        )?;
 
-#[account]
-pub struct UserVault {
-    pub owner: Pubkey,
-    pub sol_balance: u64,
-    pub usdc_balance: u64,
-    pub bet_count: u64,
-    pub total_winnings: u64,
-    pub total_losses: u64,
-    pub created_at: i64,
-}
+        let token_balance = &mut ctx.accounts.token_balance;
+        let amount = token_balance.balance;
+        token_balance.balance = 0;
 
-// Context structures
-#[derive(Accounts)]
-pub struct InitializeVault<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + std::mem::size_of::<VaultState>(),
-        seeds = [b"vault_state"],
-        bump
-    )]
-    pub vault_state: Account<'info, VaultState>,
+        let supported_token = &mut ctx.accounts.supported_token;
+        supported_token.total_deposited = supported_token
+            .total_deposited
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        if amount > 0 {
+            let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.vault_token_account.to_account_info(),
+                        mint: ctx.accounts.mint.to_account_info(),
+                        to: ctx.accounts.destination.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+                supported_token.decimals,
+            )?;
+        }
+
+        emit!(TokenWithdrawEvent {
+            user: ctx.accounts.user.key(),
+            mint: supported_token.mint,
+            amount,
+            new_balance: 0,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Forced token exit finalized for user: {}, {} of mint {}",
+            ctx.accounts.user.key(),
+            amount,
+            supported_token.mint
+        );
+        Ok(())
+    }
+
+    /// Queue a withdrawal for admin review while withdrawals are paused, so users can
+    /// still get funds out during an incident without the vault being unpaused. The
+    /// balance is only checked and debited on approval; one request per user at a time.
+    pub fn request_emergency_withdrawal(
+        ctx: Context<RequestEmergencyWithdrawal>,
+        token_type: TokenType,
+        amount: u64,
+        destination: Pubkey,
+    ) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        require!(
+            ctx.accounts.vault_state.paused(PAUSE_WITHDRAWALS, slot),
+            VaultError::EmergencyWithdrawalUnavailable
+        );
+        require!(amount > 0, VaultError::InvalidAmount);
+        require!(
+            ctx.accounts.user_vault.available(&token_type) >= amount,
+            VaultError::InsufficientBalance
+        );
+
+        let request = &mut ctx.accounts.emergency_withdrawal;
+        request.user = ctx.accounts.user.key();
+        request.token_type = token_type.clone();
+        request.amount = amount;
+        request.destination = destination;
+        request.requested_slot = slot;
+        request.requested_at = Clock::get()?.unix_timestamp;
+
+        emit!(EmergencyWithdrawalRequestedEvent {
+            user: request.user,
+            token_type,
+            amount,
+            destination,
+            requested_slot: slot,
+            timestamp: request.requested_at,
+        });
+
+        msg!(
+            "Emergency withdrawal requested: {} for user: {}",
+            amount,
+            request.user
+        );
+        Ok(())
+    }
+
+    /// Release a queued emergency withdrawal (admin only), paused or not. Daily limits
+    /// don't apply; the allowlist does. SOL leaves custody for the requested destination
+    /// like withdraw_sol; USDC for the requested token account, and needs the USDC
+    /// accounts. The request is closed and its rent returned to the user.
+    pub fn approve_emergency_withdrawal(ctx: Context<ApproveEmergencyWithdrawal>) -> Result<()> {
+        let request = &ctx.accounts.emergency_withdrawal;
+        let (token_type, amount) = (request.token_type.clone(), request.amount);
+        let destination_owner = match token_type {
+            TokenType::Sol => request.destination,
+            TokenType::Usdc => {
+                let destination = ctx
+                    .accounts
+                    .destination_token_account
+                    .as_ref()
+                    .ok_or(VaultError::UsdcAccountsRequired)?;
+                require_keys_eq!(
+                    destination.key(),
+                    request.destination,
+                    VaultError::EmergencyWithdrawalDestinationMismatch
+                );
+                destination.owner
+            }
+        };
+        enforce_withdrawal_allowlist(
+            &ctx.accounts.withdrawal_allowlist,
+            &destination_owner,
+            ctx.program_id,
+        )?;
+
+        let user_vault = &mut ctx.accounts.user_vault;
+        user_vault.debit_available(&token_type, amount)?;
+        let new_balance = user_vault.balance(&token_type);
+        let vault_state = &mut ctx.accounts.vault_state;
+        let total_deposited = match token_type {
+            TokenType::Sol => &mut vault_state.total_sol_deposited,
+            TokenType::Usdc => &mut vault_state.total_usdc_deposited,
+        };
+        *total_deposited = total_deposited
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        if token_type == TokenType::Sol {
+            let destination = ctx
+                .accounts
+                .destination
+                .as_ref()
+                .ok_or(VaultError::EmergencyWithdrawalDestinationMismatch)?;
+            require_keys_eq!(
+                destination.key(),
+                ctx.accounts.emergency_withdrawal.destination,
+                VaultError::EmergencyWithdrawalDestinationMismatch
+            );
+            transfer_sol_from_custody(
+                &ctx.accounts.vault_state.to_account_info(),
+                &destination.to_account_info(),
+                &Rent::get()?,
+                0,
+                amount,
+            )?;
+        } else {
+            transfer_usdc_from_custody(
+                &ctx.accounts.vault_state,
+                &ctx.accounts.vault_authority,
+                ctx.bumps.vault_authority,
+                &ctx.accounts.usdc_mint,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.destination_token_account,
+                &ctx.accounts.token_program,
+                amount,
+            )?;
+        }
+
+        emit!(EmergencyWithdrawalApprovedEvent {
+            user: ctx.accounts.user.key(),
+            token_type,
+            amount,
+            destination: ctx.accounts.emergency_withdrawal.destination,
+            new_balance,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Emergency withdrawal approved: {} for user: {}",
+            amount,
+            ctx.accounts.user.key()
+        );
+        Ok(())
+    }
+
+    /// Withdraw a queued emergency withdrawal, e.g. once the vault is unpaused
+    pub fn cancel_emergency_withdrawal(ctx: Context<CancelEmergencyWithdrawal>) -> Result<()> {
+        emit!(EmergencyWithdrawalCancelledEvent {
+            user: ctx.accounts.user.key(),
+            amount: ctx.accounts.emergency_withdrawal.amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Emergency withdrawal cancelled for user: {}",
+            ctx.accounts.user.key()
+        );
+        Ok(())
+    }
+
+    /// Close an empty user vault and return its rent to the owner. Refused while any
+    /// balance, lock or escrowed bet remains, so no funds or pending bets are orphaned.
+    pub fn close_user_vault(ctx: Context<CloseUserVault>) -> Result<()> {
+        ctx.accounts.user_vault.check_closable()?;
+
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.total_users = vault_state
+            .total_users
+            .checked_sub(1)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        let rent_reclaimed = ctx.accounts.user_vault.to_account_info().lamports();
+        emit!(UserVaultClosedEvent {
+            user: ctx.accounts.user.key(),
+            rent_reclaimed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "User vault closed for: {}, rent reclaimed: {}",
+            ctx.accounts.user.key(),
+            rent_reclaimed
+        );
+        Ok(())
+    }
+
+    /// Update user vault after settlement. Only callable through the verifier program,
+    /// which signs with its vault caller PDA; direct invocations can't produce that
+    /// signature. The delta is credited in full, as the sequencer and verifier hold it;
+    /// the house pays the protocol fee on a win on top. For a referred user, the
+    /// referrer's ReferralAccount is the one remaining account.
+    pub fn update_balances<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateBalances<'info>>,
+        sol_delta: i64,
+        usdc_delta: i64,
+        is_win: bool,
+        bet_amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_SETTLEMENTS, Clock::get()?.slot)?;
+
+        let fee_bps = ctx.accounts.vault_state.protocol_fee_bps;
+        let sol_fee = winnings_fee(sol_delta, fee_bps)?;
+        let usdc_fee = winnings_fee(usdc_delta, fee_bps)?;
+        let share_bps = ctx.accounts.vault_state.referral_share_bps;
+        let user_vault = &mut ctx.accounts.user_vault;
+        user_vault.apply_delta(
+            sol_delta,
+            usdc_delta,
+            is_win,
+            bet_amount,
+            Clock::get()?.unix_timestamp,
+        )?;
+        user_vault.record_settlement(Clock::get()?.slot);
+        // The house is the counterparty: it pays what the user gains, and the protocol
+        // fee on it, and keeps what they lose
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.settle(TokenType::Sol, house_delta(sol_delta, sol_fee)?)?;
+        house_vault.settle(TokenType::Usdc, house_delta(usdc_delta, usdc_fee)?)?;
+        let (custody, house) = (
+            ctx.accounts.vault_state.to_account_info(),
+            house_vault.to_account_info(),
+        );
+        let rent = Rent::get()?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        settle_custody(
+            vault_state,
+            &custody,
+            &house,
+            &rent,
+            &TokenType::Sol,
+            sol_delta,
+        )?;
+        settle_custody(
+            vault_state,
+            &custody,
+            &house,
+            &rent,
+            &TokenType::Usdc,
+            usdc_delta,
+        )?;
+        let referral = Referral {
+            user: user_vault.owner,
+            referrer: user_vault.referrer,
+            share_bps,
+            house_vault: &house,
+            accounts: ctx.remaining_accounts,
+        };
+        let sol_fee = referral.pay(TokenType::Sol, sol_fee)?;
+        let usdc_fee = referral.pay(TokenType::Usdc, usdc_fee)?;
+        let fee_custody = ctx.accounts.fee_vault.to_account_info();
+        accrue_protocol_fees(
+            &mut ctx.accounts.fee_vault,
+            &fee_custody,
+            &house,
+            &rent,
+            sol_fee,
+            usdc_fee,
+        )?;
+
+        emit!(BalanceUpdateEvent {
+            user: user_vault.owner,
+            sol_delta,
+            usdc_delta,
+            protocol_fee: sol_fee + usdc_fee,
+            new_sol_balance: user_vault.sol_balance,
+            new_usdc_balance: user_vault.usdc_balance,
+            is_win,
+            bet_count: user_vault.bet_count,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Balance updated for user: {}, SOL: {}, USDC: {}",
+            user_vault.owner,
+            sol_delta,
+            usdc_delta
+        );
+        Ok(())
+    }
+
+    /// Apply a whole settlement batch in one instruction. Remaining accounts are the
+    /// user vaults, one per delta and in the same order; a user may appear more than
+    /// once, and each of their deltas applies in turn. The ReferralAccounts of referred
+    /// users' referrers follow the user vaults. As in update_balances, deltas are
+    /// credited in full and the house pays the protocol fee on each win.
+    pub fn update_balances_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateBalancesBatch<'info>>,
+        deltas: Vec<UserDelta>,
+    ) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_SETTLEMENTS, Clock::get()?.slot)?;
+        require!(
+            !deltas.is_empty() && deltas.len() <= MAX_BATCH_DELTAS,
+            VaultError::InvalidBatchSize
+        );
+        require!(
+            ctx.remaining_accounts.len() >= deltas.len(),
+            VaultError::UserVaultMismatch
+        );
+        let (user_vaults, referral_accounts) = ctx.remaining_accounts.split_at(deltas.len());
+
+        let clock = Clock::get()?;
+        let timestamp = clock.unix_timestamp;
+        let fee_bps = ctx.accounts.vault_state.protocol_fee_bps;
+        let share_bps = ctx.accounts.vault_state.referral_share_bps;
+        let (custody, house) = (
+            ctx.accounts.vault_state.to_account_info(),
+            ctx.accounts.house_vault.to_account_info(),
+        );
+        let rent = Rent::get()?;
+
+        // Users' winnings and losses move through custody first, so the house pays
+        // referral rewards and fees out of what it holds after them
+        let (mut users_sol, mut users_usdc) = (0i64, 0i64);
+        for delta in &deltas {
+            users_sol = users_sol
+                .checked_add(delta.sol_delta)
+                .ok_or(VaultError::MathOverflow)?;
+            users_usdc = users_usdc
+                .checked_add(delta.usdc_delta)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+        let vault_state = &mut ctx.accounts.vault_state;
+        settle_custody(
+            vault_state,
+            &custody,
+            &house,
+            &rent,
+            &TokenType::Sol,
+            users_sol,
+        )?;
+        settle_custody(
+            vault_state,
+            &custody,
+            &house,
+            &rent,
+            &TokenType::Usdc,
+            users_usdc,
+        )?;
+
+        let (mut house_sol, mut house_usdc) = (0i64, 0i64);
+        let (mut fees_sol, mut fees_usdc) = (0u64, 0u64);
+        for (delta, account) in deltas.iter().zip(user_vaults) {
+            require!(account.is_writable, VaultError::UserVaultMismatch);
+            // Checks the owning program and discriminator; user vaults only exist at
+            // their owner's PDA, so a matching owner field pins the account
+            let mut user_vault = Account::<UserVault>::try_from(account)?;
+            require!(
+                user_vault.owner == delta.user,
+                VaultError::UserVaultMismatch
+            );
+            let sol_fee = winnings_fee(delta.sol_delta, fee_bps)?;
+            let usdc_fee = winnings_fee(delta.usdc_delta, fee_bps)?;
+            user_vault.apply_delta(
+                delta.sol_delta,
+                delta.usdc_delta,
+                delta.is_win,
+                delta.bet_amount,
+                timestamp,
+            )?;
+            user_vault.record_settlement(clock.slot);
+            // Written back before the next delta so a repeated user sees it
+            user_vault.exit(&crate::ID)?;
+            let referral = Referral {
+                user: delta.user,
+                referrer: user_vault.referrer,
+                share_bps,
+                house_vault: &house,
+                accounts: referral_accounts,
+            };
+            house_sol = house_sol
+                .checked_add(house_delta(delta.sol_delta, sol_fee)?)
+                .ok_or(VaultError::MathOverflow)?;
+            house_usdc = house_usdc
+                .checked_add(house_delta(delta.usdc_delta, usdc_fee)?)
+                .ok_or(VaultError::MathOverflow)?;
+            let sol_fee = referral.pay(TokenType::Sol, sol_fee)?;
+            let usdc_fee = referral.pay(TokenType::Usdc, usdc_fee)?;
+            fees_sol = fees_sol
+                .checked_add(sol_fee)
+                .ok_or(VaultError::MathOverflow)?;
+            fees_usdc = fees_usdc
+                .checked_add(usdc_fee)
+                .ok_or(VaultError::MathOverflow)?;
+
+            emit!(BalanceUpdateEvent {
+                user: delta.user,
+                sol_delta: delta.sol_delta,
+                usdc_delta: delta.usdc_delta,
+                protocol_fee: sol_fee + usdc_fee,
+                new_sol_balance: user_vault.sol_balance,
+                new_usdc_balance: user_vault.usdc_balance,
+                is_win: delta.is_win,
+                bet_count: user_vault.bet_count,
+                timestamp,
+            });
+        }
+
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.settle(TokenType::Sol, house_sol)?;
+        house_vault.settle(TokenType::Usdc, house_usdc)?;
+        let fee_custody = ctx.accounts.fee_vault.to_account_info();
+        accrue_protocol_fees(
+            &mut ctx.accounts.fee_vault,
+            &fee_custody,
+            &house,
+            &rent,
+            fees_sol,
+            fees_usdc,
+        )?;
+
+        msg!("Balances updated for {} bets", deltas.len());
+        Ok(())
+    }
+
+    /// Commit the state root of a settled batch: a Merkle root over each user's net
+    /// settled P&L. Only callable through the verifier program, which commits it as each
+    /// batch settles; roots only move forward, so a late commit of an older batch is
+    /// refused.
+    pub fn commit_balances_root(
+        ctx: Context<CommitBalancesRoot>,
+        batch_id: u64,
+        balances_root: [u8; 32],
+    ) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        require!(
+            batch_id > vault_state.balances_root_batch_id,
+            VaultError::StaleBalancesRoot
+        );
+        vault_state.balances_root = balances_root;
+        vault_state.balances_root_batch_id = batch_id;
+
+        emit!(BalancesRootCommittedEvent {
+            batch_id,
+            balances_root,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Balances root committed for batch {}", batch_id);
+        Ok(())
+    }
+
+    /// Check that `net_pnl` is `user`'s net settled P&L (payouts minus stakes over their
+    /// settled bets) under the committed balances root, given its Merkle path; returns
+    /// the batch the root was committed for. Deposits and withdrawals are not in the
+    /// tree, so this is not the user's vault balance. Meant for simulation by wallets;
+    /// it changes nothing.
+    pub fn prove_pnl_inclusion(
+        ctx: Context<ProvePnlInclusion>,
+        user: Pubkey,
+        net_pnl: i64,
+        proof: Vec<BalanceProofStep>,
+    ) -> Result<u64> {
+        require!(
+            proof.len() == BALANCE_TREE_DEPTH,
+            VaultError::InvalidBalanceProof
+        );
+        let vault_state = &ctx.accounts.vault_state;
+        require!(
+            pnl_root_from_proof(&user, net_pnl, &proof) == Some(vault_state.balances_root),
+            VaultError::InvalidBalanceProof
+        );
+
+        msg!(
+            "Net P&L {} of {} included as of batch {}",
+            net_pnl,
+            user,
+            vault_state.balances_root_batch_id
+        );
+        Ok(vault_state.balances_root_batch_id)
+    }
+
+    /// Lock funds backing a bet the sequencer accepted (vault authority only). Locked
+    /// funds stay in the user's balance but can't be withdrawn or escrowed. The lock is
+    /// recorded per bet id, and released once, for exactly its amount, through the
+    /// verifier when the bet settles or is rejected, or by the user after BET_LOCK_TIMEOUT.
+    pub fn lock_for_bet(
+        ctx: Context<LockForBet>,
+        bet_id: u64,
+        amount: u64,
+        token_type: TokenType,
+    ) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_SETTLEMENTS, Clock::get()?.slot)?;
+        require!(amount > 0, VaultError::InvalidAmount);
+
+        let user_vault = &mut ctx.accounts.user_vault;
+        user_vault.lock(&token_type, amount)?;
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let bet_lock = &mut ctx.accounts.bet_lock;
+        bet_lock.owner = user_vault.owner;
+        bet_lock.bet_id = bet_id;
+        bet_lock.amount = amount;
+        bet_lock.token_type = token_type.clone();
+        bet_lock.locked_at = timestamp;
+        bet_lock.exit_epoch = user_vault.exits;
+
+        emit!(BetLockEvent {
+            user: user_vault.owner,
+            bet_id,
+            token_type: token_type.clone(),
+            amount,
+            locked: true,
+            new_locked_balance: user_vault.locked(&token_type),
+            timestamp,
+        });
+
+        msg!(
+            "Locked {} for bet {} of user: {}",
+            amount,
+            bet_id,
+            user_vault.owner
+        );
+        Ok(())
+    }
+
+    /// Release the lock of a bet that settled or won't be settled, e.g. one the sequencer
+    /// rejected after locking. Like update_balances, only callable through the verifier
+    /// program. The lock account is closed, so it releases once.
+    pub fn release_lock(ctx: Context<ReleaseLock>, bet_id: u64) -> Result<()> {
+        release_bet_lock(&mut ctx.accounts.user_vault, &ctx.accounts.bet_lock)?;
+        msg!(
+            "Released the lock of bet {} for user: {}",
+            bet_id,
+            ctx.accounts.user_vault.owner
+        );
+        Ok(())
+    }
+
+    /// Release a bet lock the verifier left in place for BET_LOCK_TIMEOUT, so a stalled
+    /// sequencer can't hold the user's funds
+    pub fn reclaim_bet_lock(ctx: Context<ReclaimBetLock>) -> Result<()> {
+        let bet_lock = &ctx.accounts.bet_lock;
+        require!(
+            Clock::get()?.unix_timestamp >= bet_lock.reclaimable_at()?,
+            VaultError::BetLockTimelocked
+        );
+        release_bet_lock(&mut ctx.accounts.user_vault, bet_lock)?;
+        msg!(
+            "Lock of bet {} reclaimed by user: {}",
+            bet_lock.bet_id,
+            bet_lock.owner
+        );
+        Ok(())
+    }
+
+    /// Opt in to withdrawal allowlisting with an initial approved destination
+    pub fn initialize_withdrawal_allowlist(
+        ctx: Context<InitializeWithdrawalAllowlist>,
+        destination: Pubkey,
+    ) -> Result<()> {
+        let allowlist = &mut ctx.accounts.withdrawal_allowlist;
+        allowlist.owner = ctx.accounts.user.key();
+        allowlist.destinations = [Pubkey::default(); MAX_WITHDRAWAL_DESTINATIONS];
+        allowlist.destination_count = 0;
+        allowlist.pending_change = None;
+        allowlist.add(destination)?;
+
+        msg!(
+            "Withdrawal allowlist enabled for user: {} with destination: {}",
+            allowlist.owner,
+            destination
+        );
+        Ok(())
+    }
+
+    /// Request adding or removing a destination; takes effect after ALLOWLIST_CHANGE_DELAY
+    pub fn request_allowlist_change(
+        ctx: Context<ManageWithdrawalAllowlist>,
+        destination: Pubkey,
+        action: AllowlistAction,
+    ) -> Result<()> {
+        let allowlist = &mut ctx.accounts.withdrawal_allowlist;
+        require!(
+            allowlist.pending_change.is_none(),
+            VaultError::AllowlistChangePending
+        );
+
+        let effective_at = Clock::get()?
+            .unix_timestamp
+            .checked_add(ALLOWLIST_CHANGE_DELAY)
+            .ok_or(VaultError::MathOverflow)?;
+        allowlist.pending_change = Some(PendingAllowlistChange {
+            destination,
+            action: action.clone(),
+            effective_at,
+        });
+
+        emit!(AllowlistChangeRequestedEvent {
+            user: allowlist.owner,
+            destination,
+            action,
+            effective_at,
+        });
+
+        msg!(
+            "Withdrawal allowlist change requested for user: {}, effective at: {}",
+            allowlist.owner,
+            effective_at
+        );
+        Ok(())
+    }
+
+    /// Apply a pending allowlist change once its delay has elapsed
+    pub fn apply_allowlist_change(ctx: Context<ManageWithdrawalAllowlist>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.withdrawal_allowlist;
+        let change = allowlist
+            .pending_change
+            .clone()
+            .ok_or(VaultError::NoPendingAllowlistChange)?;
+        require!(
+            Clock::get()?.unix_timestamp >= change.effective_at,
+            VaultError::AllowlistChangeTimelocked
+        );
+
+        match change.action {
+            AllowlistAction::Add => allowlist.add(change.destination)?,
+            AllowlistAction::Remove => allowlist.remove(&change.destination)?,
+        }
+        allowlist.pending_change = None;
+
+        msg!(
+            "Withdrawal allowlist change applied for user: {}",
+            allowlist.owner
+        );
+        Ok(())
+    }
+
+    /// Cancel a pending allowlist change
+    pub fn cancel_allowlist_change(ctx: Context<ManageWithdrawalAllowlist>) -> Result<()> {
+        let allowlist = &mut ctx.accounts.withdrawal_allowlist;
+        require!(
+            allowlist.pending_change.is_some(),
+            VaultError::NoPendingAllowlistChange
+        );
+        allowlist.pending_change = None;
+
+        msg!(
+            "Withdrawal allowlist change cancelled for user: {}",
+            allowlist.owner
+        );
+        Ok(())
+    }
+
+    /// Place a bet on-chain: the stake moves from the user's vault into a per-bet escrow
+    /// that only settlement (or a refund after ESCROW_REFUND_DELAY) can release
+    pub fn place_escrowed_bet(
+        ctx: Context<PlaceEscrowedBet>,
+        nonce: u64,
+        amount: u64,
+        token_type: TokenType,
+        guess: bool,
+    ) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_SETTLEMENTS, Clock::get()?.slot)?;
+        require!(amount > 0, VaultError::InvalidAmount);
+
+        let user_vault = &mut ctx.accounts.user_vault;
+        require!(
+            user_vault.available(&token_type) >= amount,
+            VaultError::InsufficientBalance
+        );
+        let balance = match token_type {
+            TokenType::Sol => &mut user_vault.sol_balance,
+            TokenType::Usdc => &mut user_vault.usdc_balance,
+        };
+        *balance = balance
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+        user_vault.open_escrows = user_vault
+            .open_escrows
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+        let escrowed = ctx.accounts.vault_state.escrowed_mut(&token_type);
+        *escrowed = escrowed
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let bet_escrow = &mut ctx.accounts.bet_escrow;
+        bet_escrow.owner = ctx.accounts.user.key();
+        bet_escrow.nonce = nonce;
+        bet_escrow.amount = amount;
+        bet_escrow.token_type = token_type.clone();
+        bet_escrow.guess = guess;
+        bet_escrow.created_at = timestamp;
+
+        emit!(BetEscrowedEvent {
+            user: bet_escrow.owner,
+            escrow: bet_escrow.key(),
+            nonce,
+            amount,
+            token_type,
+            guess,
+            timestamp,
+        });
+
+        msg!(
+            "Bet escrowed: {} for user: {}, nonce: {}",
+            amount,
+            bet_escrow.owner,
+            nonce
+        );
+        Ok(())
+    }
+
+    /// Settle an escrowed bet (vault authority only): a win releases the stake plus
+    /// winnings to the user's vault, the house paying the protocol fee on top; a loss
+    /// lets the house claim the stake
+    pub fn settle_escrowed_bet<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SettleEscrowedBet<'info>>,
+        won: bool,
+        payout: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .vault_state
+            .require_unpaused(PAUSE_SETTLEMENTS, Clock::get()?.slot)?;
+        let fee_bps = ctx.accounts.vault_state.protocol_fee_bps;
+        let bet_escrow = &ctx.accounts.bet_escrow;
+        let amount = bet_escrow.amount;
+        let winnings = escrow_winnings(amount, won, payout)?;
+
+        let (custody, house) = (
+            ctx.accounts.vault_state.to_account_info(),
+            ctx.accounts.house_vault.to_account_info(),
+        );
+        let user_vault = &mut ctx.accounts.user_vault;
+        let vault_state = &mut ctx.accounts.vault_state;
+        let escrowed = vault_state.escrowed_mut(&bet_escrow.token_type);
+        *escrowed = escrowed
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+        let balance = match bet_escrow.token_type {
+            TokenType::Sol => &mut user_vault.sol_balance,
+            TokenType::Usdc => &mut user_vault.usdc_balance,
+        };
+
+        // The stake already sits in custody: a win adds the winnings on top, a loss
+        // hands the stake to the house
+        let to_user = if won {
+            i64::try_from(winnings)
+        } else {
+            i64::try_from(amount).map(|stake| -stake)
+        }
+        .map_err(|_| VaultError::MathOverflow)?;
+        let fee = winnings_fee(to_user, fee_bps)?;
+        // Winnings and the protocol fee on them come out of the house bankroll; a lost
+        // stake goes into it
+        ctx.accounts
+            .house_vault
+            .settle(bet_escrow.token_type.clone(), house_delta(to_user, fee)?)?;
+        if won {
+            *balance = balance
+                .checked_add(payout)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+        let rent = Rent::get()?;
+        settle_custody(
+            vault_state,
+            &custody,
+            &house,
+            &rent,
+            &bet_escrow.token_type,
+            to_user,
+        )?;
+        user_vault.record_bet(
+            &bet_escrow.token_type,
+            won,
+            amount,
+            winnings,
+            Clock::get()?.unix_timestamp,
+        )?;
+        user_vault.open_escrows = user_vault
+            .open_escrows
+            .checked_sub(1)
+            .ok_or(VaultError::MathUnderflow)?;
+        user_vault.record_settlement(Clock::get()?.slot);
+        // A referred user's referrer takes its share of the fee; its ReferralAccount
+        // is the one remaining account
+        let referral = Referral {
+            user: user_vault.owner,
+            referrer: user_vault.referrer,
+            share_bps: vault_state.referral_share_bps,
+            house_vault: &house,
+            accounts: ctx.remaining_accounts,
+        };
+        let fee_left = referral.pay(bet_escrow.token_type.clone(), fee)?;
+        let (sol_fee, usdc_fee) = match bet_escrow.token_type {
+            TokenType::Sol => (fee_left, 0),
+            TokenType::Usdc => (0, fee_left),
+        };
+        let fee_custody = ctx.accounts.fee_vault.to_account_info();
+        accrue_protocol_fees(
+            &mut ctx.accounts.fee_vault,
+            &fee_custody,
+            &house,
+            &rent,
+            sol_fee,
+            usdc_fee,
+        )?;
+
+        emit!(EscrowSettledEvent {
+            user: bet_escrow.owner,
+            escrow: bet_escrow.key(),
+            won,
+            amount,
+            payout,
+            protocol_fee: fee,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Escrowed bet settled for user: {}, won: {}, payout: {}",
+            bet_escrow.owner,
+            won,
+            payout
+        );
+        Ok(())
+    }
+
+    /// Return an escrowed stake the sequencer failed to settle within ESCROW_REFUND_DELAY
+    pub fn refund_escrowed_bet(ctx: Context<RefundEscrowedBet>) -> Result<()> {
+        let bet_escrow = &ctx.accounts.bet_escrow;
+        let refundable_at = bet_escrow
+            .created_at
+            .checked_add(ESCROW_REFUND_DELAY)
+            .ok_or(VaultError::MathOverflow)?;
+        require!(
+            Clock::get()?.unix_timestamp >= refundable_at,
+            VaultError::EscrowRefundTimelocked
+        );
+
+        let user_vault = &mut ctx.accounts.user_vault;
+        let balance = match bet_escrow.token_type {
+            TokenType::Sol => &mut user_vault.sol_balance,
+            TokenType::Usdc => &mut user_vault.usdc_balance,
+        };
+        *balance = balance
+            .checked_add(bet_escrow.amount)
+            .ok_or(VaultError::MathOverflow)?;
+        user_vault.open_escrows = user_vault
+            .open_escrows
+            .checked_sub(1)
+            .ok_or(VaultError::MathUnderflow)?;
+        let escrowed = ctx
+            .accounts
+            .vault_state
+            .escrowed_mut(&bet_escrow.token_type);
+        *escrowed = escrowed
+            .checked_sub(bet_escrow.amount)
+            .ok_or(VaultError::MathUnderflow)?;
+
+        msg!(
+            "Escrowed bet refunded: {} for user: {}",
+            bet_escrow.amount,
+            bet_escrow.owner
+        );
+        Ok(())
+    }
+
+    /// Create the house bankroll account (admin only)
+    pub fn initialize_house_vault(ctx: Context<InitializeHouseVault>) -> Result<()> {
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.sol_bankroll = 0;
+        house_vault.usdc_bankroll = 0;
+        house_vault.sol_principal = 0;
+        house_vault.usdc_principal = 0;
+        house_vault.sol_pnl = 0;
+        house_vault.usdc_pnl = 0;
+
+        msg!("House vault initialized");
+        Ok(())
+    }
+
+    /// Create the protocol fee account (admin only)
+    pub fn initialize_fee_vault(ctx: Context<InitializeFeeVault>) -> Result<()> {
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        fee_vault.sol_accrued = 0;
+        fee_vault.usdc_accrued = 0;
+        fee_vault.sol_collected = 0;
+        fee_vault.usdc_collected = 0;
+
+        msg!("Fee vault initialized");
+        Ok(())
+    }
+
+    /// Set the protocol fee the house pays on winnings, up to MAX_PROTOCOL_FEE_BPS
+    /// (admin only)
+    pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, fee_bps: u16) -> Result<()> {
+        require!(
+            fee_bps <= MAX_PROTOCOL_FEE_BPS,
+            VaultError::InvalidProtocolFee
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.protocol_fee_bps = fee_bps;
+
+        emit!(ProtocolFeeUpdatedEvent {
+            fee_bps,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Protocol fee set to {} bps", fee_bps);
+        Ok(())
+    }
+
+    /// Set the share of the protocol fee on a referred user's winnings that goes to
+    /// their referrer, in basis points of the fee (admin only)
+    pub fn set_referral_share(ctx: Context<SetReferralShare>, share_bps: u16) -> Result<()> {
+        require!(
+            share_bps as u64 <= BPS_DENOMINATOR,
+            VaultError::InvalidReferralShare
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.referral_share_bps = share_bps;
+
+        emit!(ReferralShareUpdatedEvent {
+            share_bps,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Referral share set to {} bps of the protocol fee",
+            share_bps
+        );
+        Ok(())
+    }
+
+    /// Create the caller's ReferralAccount, so users can name them as referrer
+    pub fn initialize_referral_account(ctx: Context<InitializeReferralAccount>) -> Result<()> {
+        let referral_account = &mut ctx.accounts.referral_account;
+        referral_account.referrer = ctx.accounts.referrer.key();
+        referral_account.referred_users = 0;
+        referral_account.sol_accrued = 0;
+        referral_account.usdc_accrued = 0;
+        referral_account.sol_claimed = 0;
+        referral_account.usdc_claimed = 0;
+        referral_account.created_at = Clock::get()?.unix_timestamp;
+
+        msg!(
+            "Referral account created for: {}",
+            referral_account.referrer
+        );
+        Ok(())
+    }
+
+    /// Claim every accrued referral reward of one token. SOL is paid to the referrer;
+    /// USDC leaves custody for `destination_token_account`, and needs the USDC accounts.
+    pub fn claim_referral_rewards(
+        ctx: Context<ClaimReferralRewards>,
+        token_type: TokenType,
+    ) -> Result<()> {
+        let amount = ctx.accounts.referral_account.claim(&token_type)?;
+        require!(amount > 0, VaultError::NoReferralRewards);
+
+        let destination = match token_type {
+            TokenType::Sol => {
+                transfer_sol_from_custody(
+                    &ctx.accounts.referral_account.to_account_info(),
+                    &ctx.accounts.referrer.to_account_info(),
+                    &Rent::get()?,
+                    0,
+                    amount,
+                )?;
+                ctx.accounts.referrer.key()
+            }
+            TokenType::Usdc => transfer_usdc_from_custody(
+                &ctx.accounts.vault_state,
+                &ctx.accounts.vault_authority,
+                ctx.bumps.vault_authority,
+                &ctx.accounts.usdc_mint,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.destination_token_account,
+                &ctx.accounts.token_program,
+                amount,
+            )?,
+        };
+
+        emit!(ReferralRewardsClaimedEvent {
+            referrer: ctx.accounts.referrer.key(),
+            token_type,
+            amount,
+            destination,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Referral rewards claimed: {} to {}", amount, destination);
+        Ok(())
+    }
+
+    /// Collect accrued protocol fees (admin only). SOL is paid to `destination`; USDC
+    /// leaves custody for `destination_token_account`, and needs the USDC accounts.
+    pub fn collect_fees(
+        ctx: Context<CollectFees>,
+        token_type: TokenType,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        fee_vault.collect(&token_type, amount)?;
+
+        let destination = match token_type {
+            TokenType::Sol => {
+                transfer_sol_from_custody(
+                    &fee_vault.to_account_info(),
+                    &ctx.accounts.destination.to_account_info(),
+                    &Rent::get()?,
+                    0,
+                    amount,
+                )?;
+                ctx.accounts.destination.key()
+            }
+            TokenType::Usdc => transfer_usdc_from_custody(
+                &ctx.accounts.vault_state,
+                &ctx.accounts.vault_authority,
+                ctx.bumps.vault_authority,
+                &ctx.accounts.usdc_mint,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.destination_token_account,
+                &ctx.accounts.token_program,
+                amount,
+            )?,
+        };
+
+        let remaining = fee_vault.accrued(&token_type);
+        emit!(FeesCollectedEvent {
+            token_type,
+            amount,
+            destination,
+            remaining,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Protocol fees collected: {} to {}", amount, destination);
+        Ok(())
+    }
+
+    /// Add lamports to the house bankroll, held by the house vault account (admin only)
+    pub fn fund_house_sol(ctx: Context<FundHouseSol>, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.house_vault.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.fund(TokenType::Sol, amount)?;
+
+        emit!(HouseFundedEvent {
+            token_type: TokenType::Sol,
+            amount,
+            bankroll: house_vault.sol_bankroll,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("House funded with {} lamports", amount);
+        Ok(())
+    }
+
+    /// Add USDC (in base units) to the house bankroll, held in the vault's custody
+    /// account alongside user deposits (admin only)
+    pub fn fund_house_usdc(ctx: Context<FundHouseUsdc>, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.authority_token_account.to_account_info(),
+                    mint: ctx.accounts.usdc_mint.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.usdc_mint.decimals,
+        )?;
+        let amount = received_amount(&ctx.accounts.usdc_mint.to_account_info(), amount)?;
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.fund(TokenType::Usdc, amount)?;
+
+        emit!(HouseFundedEvent {
+            token_type: TokenType::Usdc,
+            amount,
+            bankroll: house_vault.usdc_bankroll,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("House funded with {} USDC", amount);
+        Ok(())
+    }
+
+    /// Take lamports of house profit, the bankroll above what was funded, out of the
+    /// house vault; the rest of the bankroll stays behind (admin only)
+    pub fn withdraw_house_profit_sol(
+        ctx: Context<WithdrawHouseProfitSol>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.withdraw_profit(TokenType::Sol, amount)?;
+        transfer_sol_from_custody(
+            &house_vault.to_account_info(),
+            &ctx.accounts.destination.to_account_info(),
+            &Rent::get()?,
+            house_vault.sol_bankroll,
+            amount,
+        )?;
+
+        emit!(HouseProfitWithdrawnEvent {
+            token_type: TokenType::Sol,
+            amount,
+            destination: ctx.accounts.destination.key(),
+            bankroll: house_vault.sol_bankroll,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("House profit withdrawn: {} lamports", amount);
+        Ok(())
+    }
+
+    /// Take USDC (in base units) of house profit out of custody (admin only)
+    pub fn withdraw_house_profit_usdc(
+        ctx: Context<WithdrawHouseProfitUsdc>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, VaultError::InvalidAmount);
+        let house_vault = &mut ctx.accounts.house_vault;
+        house_vault.withdraw_profit(TokenType::Usdc, amount)?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[ctx.bumps.vault_authority]]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_token_account.to_account_info(),
+                    mint: ctx.accounts.usdc_mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            ctx.accounts.usdc_mint.decimals,
+        )?;
+
+        emit!(HouseProfitWithdrawnEvent {
+            token_type: TokenType::Usdc,
+            amount,
+            destination: ctx.accounts.destination.key(),
+            bankroll: house_vault.usdc_bankroll,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("House profit withdrawn: {} USDC", amount);
+        Ok(())
+    }
+
+    /// Read-only view of treasury liquidity and exposure, returned as TreasuryHealth.
+    /// Meant for simulation by wallets and dashboards; it changes nothing.
+    pub fn treasury_health(ctx: Context<TreasuryHealthView>) -> Result<TreasuryHealth> {
+        let custody = &ctx.accounts.usdc_custody;
+        let custody_address = |token_program: &Pubkey| {
+            get_associated_token_address_with_program_id(
+                &ctx.accounts.vault_authority.key(),
+                &ctx.accounts.vault_state.usdc_mint,
+                token_program,
+            )
+        };
+        let usdc_in_custody = if custody.data_is_empty() {
+            // USDC not configured yet, so either token program's address will do
+            require!(
+                custody.key() == custody_address(&anchor_spl::token::ID)
+                    || custody.key() == custody_address(&spl_token_2022::ID),
+                VaultError::InvalidCustodyAccount
+            );
+            0
+        } else {
+            require_keys_eq!(
+                custody.key(),
+                custody_address(custody.owner),
+                VaultError::InvalidCustodyAccount
+            );
+            TokenAccount::try_deserialize(&mut &custody.try_borrow_data()?[..])?.amount
+        };
+        Ok(ctx
+            .accounts
+            .vault_state
+            .health(usdc_in_custody, Clock::get()?.slot))
+    }
+
+    /// Replace the set of paused operations (PAUSE_* bits; 0 resumes everything), e.g.
+    /// halting withdrawals during an incident while deposits continue. With
+    /// `paused_until_slot` the pause lifts by itself at that slot (admin only)
+    pub fn set_pause_flags(
+        ctx: Context<SetPauseFlags>,
+        pause_flags: u8,
+        paused_until_slot: Option<u64>,
+    ) -> Result<()> {
+        require!(pause_flags & !PAUSE_ALL == 0, VaultError::InvalidPauseFlags);
+        if let Some(until) = paused_until_slot {
+            require!(until > Clock::get()?.slot, VaultError::InvalidPauseExpiry);
+        }
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.pause_flags = pause_flags;
+        vault_state.paused_until_slot = paused_until_slot;
+
+        emit!(PauseFlagsUpdatedEvent {
+            pause_flags,
+            paused_until_slot,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Vault pause flags set to: {:#05b} until slot {:?}",
+            pause_flags,
+            paused_until_slot
+        );
+        Ok(())
+    }
+
+    /// Set the withdrawal limits for one token; zero leaves a limit off (admin only)
+    pub fn set_withdrawal_limits(
+        ctx: Context<SetWithdrawalLimits>,
+        token_type: TokenType,
+        limits: WithdrawalLimits,
+    ) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        *vault_state.limits_mut(&token_type) = limits.clone();
+
+        emit!(WithdrawalLimitsUpdatedEvent {
+            token_type,
+            limits: limits.clone(),
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!(
+            "Withdrawal limits set: {} per transaction, {} per user daily, {} globally daily",
+            limits.max_per_transaction,
+            limits.max_per_user_daily,
+            limits.max_global_daily
+        );
+        Ok(())
+    }
+
+    /// Nominate a new vault authority; it takes over once it accepts. Proposing again
+    /// replaces the pending nominee (admin only)
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.pending_authority = Some(new_authority);
+
+        emit!(AuthorityProposedEvent {
+            authority: vault_state.authority,
+            pending_authority: new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Vault authority transfer proposed to: {}", new_authority);
+        Ok(())
+    }
+
+    /// Complete a vault authority transfer; must be signed by the pending authority
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        let previous_authority = vault_state.accept_authority(&ctx.accounts.new_authority.key())?;
+
+        emit!(AuthorityTransferredEvent {
+            previous_authority,
+            new_authority: vault_state.authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Vault authority transferred to: {}", vault_state.authority);
+        Ok(())
+    }
+}
+
+// Account structures
+#[account]
+pub struct VaultState {
+    pub authority: Pubkey,
+    pub total_users: u64,
+    pub total_sol_deposited: u64,
+    pub total_usdc_deposited: u64,
+    pub pause_flags: u8,                   // PAUSE_* bits
+    pub paused_until_slot: Option<u64>,    // The pause lifts at this slot (None = until cleared)
+    pub pending_authority: Option<Pubkey>, // Proposed authority awaiting acceptance
+    pub usdc_mint: Pubkey,                 // Default until configure_usdc runs
+    pub usdc_decimals: u8,
+    pub sol_escrowed: u64, // Stakes held in open bet escrows: the house's exposure
+    pub usdc_escrowed: u64,
+    pub sol_limits: WithdrawalLimits,
+    pub usdc_limits: WithdrawalLimits,
+    pub sol_withdrawals: WithdrawalWindow, // All users' withdrawals, for the global cap
+    pub usdc_withdrawals: WithdrawalWindow,
+    pub protocol_fee_bps: u16, // Paid by the house on winnings into the fee vault
+    pub referral_share_bps: u16, // Of the protocol fee on referred users' winnings
+    pub balances_root: [u8; 32], // Merkle root over users' net settled P&L, from the verifier
+    pub balances_root_batch_id: u64, // Batch the balances root was committed for
+}
+
+impl VaultState {
+    /// Operations of the PAUSE_* bits in `operation` are halted until the pause is
+    /// cleared or `paused_until_slot` is reached
+    pub fn paused(&self, operation: u8, slot: u64) -> bool {
+        self.pause_flags & operation != 0
+            && self.paused_until_slot.map_or(true, |until| slot < until)
+    }
+
+    pub fn require_unpaused(&self, operation: u8, slot: u64) -> Result<()> {
+        require!(!self.paused(operation, slot), VaultError::VaultPaused);
+        Ok(())
+    }
+
+    pub fn limits_mut(&mut self, token_type: &TokenType) -> &mut WithdrawalLimits {
+        match token_type {
+            TokenType::Sol => &mut self.sol_limits,
+            TokenType::Usdc => &mut self.usdc_limits,
+        }
+    }
+
+    /// Move a token's deposit total by a settled change of users' balances, so what
+    /// they won can be withdrawn like a deposit and what they lost no longer counts
+    pub fn settle_deposits(&mut self, token_type: &TokenType, user_delta: i64) -> Result<()> {
+        let total = match token_type {
+            TokenType::Sol => &mut self.total_sol_deposited,
+            TokenType::Usdc => &mut self.total_usdc_deposited,
+        };
+        *total = if user_delta >= 0 {
+            total
+                .checked_add(user_delta.unsigned_abs())
+                .ok_or(VaultError::MathOverflow)?
+        } else {
+            total
+                .checked_sub(user_delta.unsigned_abs())
+                .ok_or(VaultError::MathUnderflow)?
+        };
+        Ok(())
+    }
+
+    /// Open escrow total for a token
+    pub fn escrowed_mut(&mut self, token_type: &TokenType) -> &mut u64 {
+        match token_type {
+            TokenType::Sol => &mut self.sol_escrowed,
+            TokenType::Usdc => &mut self.usdc_escrowed,
+        }
+    }
+
+    pub fn health(&self, usdc_in_custody: u64, slot: u64) -> TreasuryHealth {
+        TreasuryHealth {
+            version: TREASURY_HEALTH_VERSION,
+            total_sol_deposited: self.total_sol_deposited,
+            total_usdc_deposited: self.total_usdc_deposited,
+            usdc_in_custody,
+            sol_escrowed: self.sol_escrowed,
+            usdc_escrowed: self.usdc_escrowed,
+            total_users: self.total_users,
+            is_paused: self.paused(PAUSE_ALL, slot),
+            pause_flags: self.pause_flags,
+            paused_until_slot: self.paused_until_slot,
+        }
+    }
+
+    /// Hand authority to the pending nominee if `signer` is it; returns the previous
+    /// authority
+    pub fn accept_authority(&mut self, signer: &Pubkey) -> Result<Pubkey> {
+        require!(
+            self.pending_authority == Some(*signer),
+            VaultError::NotPendingAuthority
+        );
+        self.pending_authority = None;
+        Ok(std::mem::replace(&mut self.authority, *signer))
+    }
+}
+
+#[account]
+pub struct UserVault {
+    pub owner: Pubkey,
+    pub sol_balance: u64,
+    pub usdc_balance: u64,
+    pub bet_count: u64,
+    pub total_winnings: u64,
+    pub total_losses: u64,
+    pub created_at: i64,
+    pub sol_withdrawals: WithdrawalWindow, // Recent withdrawals, for the daily limit
+    pub usdc_withdrawals: WithdrawalWindow,
+    pub last_settled_slot: u64, // Slot the last settlement was applied in
+    pub exit_requested_slot: Option<u64>, // Pending forced exit, see initiate_exit
+    pub deposit_nonce: u64,     // Deposits relayed through deposit_for so far
+    pub locked_balance: LockedBalance, // Backing in-flight bets, see lock_for_bet
+    pub open_escrows: u64,      // Escrowed bets not yet settled or refunded
+    pub referrer: Option<Pubkey>, // Earns a share of the protocol fee on this user's wins
+    pub sol_wagered: u64,       // Stakes of settled bets, per token
+    pub usdc_wagered: u64,
+    pub sol_won: u64, // Winnings credited on wins
+    pub usdc_won: u64,
+    pub current_streak: i64, // Consecutive wins (positive) or losses (negative)
+    pub last_bet_at: i64,    // Settlement time of the latest bet; 0 before any
+    pub exits: u64,          // Forced exits finalized; each voids the locks taken before it
+}
+
+/// Part of a user's balances locked for bets awaiting settlement
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct LockedBalance {
+    pub sol: u64,
+    pub usdc: u64,
+}
+
+impl UserVault {
+    pub fn balance(&self, token_type: &TokenType) -> u64 {
+        match token_type {
+            TokenType::Sol => self.sol_balance,
+            TokenType::Usdc => self.usdc_balance,
+        }
+    }
+
+    pub fn locked(&self, token_type: &TokenType) -> u64 {
+        match token_type {
+            TokenType::Sol => self.locked_balance.sol,
+            TokenType::Usdc => self.locked_balance.usdc,
+        }
+    }
+
+    fn locked_mut(&mut self, token_type: &TokenType) -> &mut u64 {
+        match token_type {
+            TokenType::Sol => &mut self.locked_balance.sol,
+            TokenType::Usdc => &mut self.locked_balance.usdc,
+        }
+    }
+
+    /// Balance not backing in-flight bets, which the user may withdraw or escrow
+    pub fn available(&self, token_type: &TokenType) -> u64 {
+        self.balance(token_type)
+            .saturating_sub(self.locked(token_type))
+    }
+
+    /// Take `amount` out of the available balance, e.g. for an approved withdrawal
+    pub fn debit_available(&mut self, token_type: &TokenType, amount: u64) -> Result<()> {
+        require!(
+            self.available(token_type) >= amount,
+            VaultError::InsufficientBalance
+        );
+        let balance = match token_type {
+            TokenType::Sol => &mut self.sol_balance,
+            TokenType::Usdc => &mut self.usdc_balance,
+        };
+        *balance = balance
+            .checked_sub(amount)
+            .ok_or(VaultError::MathUnderflow)?;
+        Ok(())
+    }
+
+    /// Lock `amount` of the available balance for a bet
+    pub fn lock(&mut self, token_type: &TokenType, amount: u64) -> Result<()> {
+        require!(
+            self.available(token_type) >= amount,
+            VaultError::InsufficientBalance
+        );
+        let locked = self.locked_mut(token_type);
+        *locked = locked.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Unlock `amount` of previously locked funds
+    pub fn release(&mut self, token_type: &TokenType, amount: u64) -> Result<()> {
+        let locked = self.locked_mut(token_type);
+        *locked = locked
+            .checked_sub(amount)
+            .ok_or(VaultError::InsufficientLockedBalance)?;
+        Ok(())
+    }
+
+    /// Check the vault holds nothing and backs no pending bet, so it can be closed
+    pub fn check_closable(&self) -> Result<()> {
+        require!(
+            self.sol_balance == 0 && self.usdc_balance == 0,
+            VaultError::VaultNotEmpty
+        );
+        require!(
+            self.locked_balance == LockedBalance::default() && self.open_escrows == 0,
+            VaultError::BetsPending
+        );
+        Ok(())
+    }
+
+    /// Note a settlement landing on this vault. A forced exit requested before it can no
+    /// longer be finalized: the sequencer is evidently still live.
+    pub fn record_settlement(&mut self, slot: u64) {
+        self.last_settled_slot = slot;
+    }
+
+    /// Whether a settlement has landed since the pending forced exit was requested
+    pub fn exit_superseded(&self) -> bool {
+        matches!(self.exit_requested_slot, Some(requested) if self.last_settled_slot >= requested)
+    }
+
+    /// Check that the pending forced exit can be finalized at `slot`
+    pub fn check_exit(&self, slot: u64) -> Result<()> {
+        let requested = self.exit_requested_slot.ok_or(VaultError::NoExitPending)?;
+        require!(!self.exit_superseded(), VaultError::ExitSuperseded);
+        require!(
+            slot >= requested.saturating_add(FORCED_EXIT_DELAY_SLOTS),
+            VaultError::ExitTimelocked
+        );
+        Ok(())
+    }
+
+    /// Apply one settled bet: balance deltas and win/loss statistics
+    pub fn apply_delta(
+        &mut self,
+        sol_delta: i64,
+        usdc_delta: i64,
+        is_win: bool,
+        bet_amount: u64,
+        timestamp: i64,
+    ) -> Result<()> {
+        // Update SOL balance
+        if sol_delta >= 0 {
+            self.sol_balance = self
+                .sol_balance
+                .checked_add(sol_delta as u64)
+                .ok_or(VaultError::MathOverflow)?;
+        } else {
+            let abs_delta = (-sol_delta) as u64;
+            require!(
+                self.sol_balance >= abs_delta,
+                VaultError::InsufficientBalance
+            );
+            self.sol_balance = self
+                .sol_balance
+                .checked_sub(abs_delta)
+                .ok_or(VaultError::MathUnderflow)?;
+        }
+
+        // Update USDC balance
+        if usdc_delta >= 0 {
+            self.usdc_balance = self
+                .usdc_balance
+                .checked_add(usdc_delta as u64)
+                .ok_or(VaultError::MathOverflow)?;
+        } else {
+            let abs_delta = (-usdc_delta) as u64;
+            require!(
+                self.usdc_balance >= abs_delta,
+                VaultError::InsufficientBalance
+            );
+            self.usdc_balance = self
+                .usdc_balance
+                .checked_sub(abs_delta)
+                .ok_or(VaultError::MathUnderflow)?;
+        }
+
+        // A bet moves one token; its lock, if any, is released through its BetLock
+        let (token_type, delta) = if usdc_delta != 0 {
+            (TokenType::Usdc, usdc_delta)
+        } else {
+            (TokenType::Sol, sol_delta)
+        };
+        let won = if is_win { delta.max(0) as u64 } else { 0 };
+        self.record_bet(&token_type, is_win, bet_amount, won, timestamp)
+    }
+
+    /// Update the bet statistics for a settled bet; `won` is what a win credited
+    pub fn record_bet(
+        &mut self,
+        token_type: &TokenType,
+        is_win: bool,
+        bet_amount: u64,
+        won: u64,
+        timestamp: i64,
+    ) -> Result<()> {
+        self.bet_count = self
+            .bet_count
+            .checked_add(1)
+            .ok_or(VaultError::MathOverflow)?;
+
+        if is_win {
+            self.total_winnings = self
+                .total_winnings
+                .checked_add(bet_amount)
+                .ok_or(VaultError::MathOverflow)?;
+        } else {
+            self.total_losses = self
+                .total_losses
+                .checked_add(bet_amount)
+                .ok_or(VaultError::MathOverflow)?;
+        }
+
+        let (wagered, total_won) = match token_type {
+            TokenType::Sol => (&mut self.sol_wagered, &mut self.sol_won),
+            TokenType::Usdc => (&mut self.usdc_wagered, &mut self.usdc_won),
+        };
+        *wagered = wagered
+            .checked_add(bet_amount)
+            .ok_or(VaultError::MathOverflow)?;
+        *total_won = total_won.checked_add(won).ok_or(VaultError::MathOverflow)?;
+
+        self.current_streak = match (is_win, self.current_streak) {
+            (true, streak) if streak > 0 => streak.saturating_add(1),
+            (true, _) => 1,
+            (false, streak) if streak < 0 => streak.saturating_sub(1),
+            (false, _) => -1,
+        };
+        self.last_bet_at = timestamp;
+        Ok(())
+    }
+}
+
+/// The house bankroll. SOL is held as lamports of this account, above its rent:
+/// funding adds them, and settlement moves them to and from user custody as the house
+/// pays out and collects. USDC sits in the vault's custody account and is only
+/// accounted here. Every field is fixed size, so readers can decode it by offset.
+#[account]
+pub struct HouseVault {
+    pub sol_bankroll: u64,
+    pub usdc_bankroll: u64,
+    pub sol_principal: u64, // Funded by the admin; bankroll above this is profit
+    pub usdc_principal: u64,
+    pub sol_pnl: i64, // Net settlement result for the house since initialization
+    pub usdc_pnl: i64,
+}
+
+impl HouseVault {
+    fn bankroll_mut(&mut self, token_type: &TokenType) -> (&mut u64, &mut u64, &mut i64) {
+        match token_type {
+            TokenType::Sol => (
+                &mut self.sol_bankroll,
+                &mut self.sol_principal,
+                &mut self.sol_pnl,
+            ),
+            TokenType::Usdc => (
+                &mut self.usdc_bankroll,
+                &mut self.usdc_principal,
+                &mut self.usdc_pnl,
+            ),
+        }
+    }
+
+    /// Apply the house's side of a settlement: credit what players lost, debit what
+    /// they won. The bankroll must cover the debit.
+    pub fn settle(&mut self, token_type: TokenType, house_delta: i64) -> Result<()> {
+        let (bankroll, _, pnl) = self.bankroll_mut(&token_type);
+        *bankroll = if house_delta >= 0 {
+            bankroll
+                .checked_add(house_delta.unsigned_abs())
+                .ok_or(VaultError::MathOverflow)?
+        } else {
+            bankroll
+                .checked_sub(house_delta.unsigned_abs())
+                .ok_or(VaultError::InsufficientBankroll)?
+        };
+        *pnl = pnl
+            .checked_add(house_delta)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn fund(&mut self, token_type: TokenType, amount: u64) -> Result<()> {
+        let (bankroll, principal, _) = self.bankroll_mut(&token_type);
+        *bankroll = bankroll
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        *principal = principal
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Bankroll above the funded principal
+    pub fn profit(&self, token_type: &TokenType) -> u64 {
+        match token_type {
+            TokenType::Sol => self.sol_bankroll.saturating_sub(self.sol_principal),
+            TokenType::Usdc => self.usdc_bankroll.saturating_sub(self.usdc_principal),
+        }
+    }
+
+    pub fn withdraw_profit(&mut self, token_type: TokenType, amount: u64) -> Result<()> {
+        require!(
+            amount <= self.profit(&token_type),
+            VaultError::InsufficientHouseProfit
+        );
+        let (bankroll, _, _) = self.bankroll_mut(&token_type);
+        *bankroll -= amount;
+        Ok(())
+    }
+}
+
+/// Protocol fees the house pays on winnings, accounted here. SOL fees arrive from the
+/// house vault as lamports of this account, above its rent; USDC fees stay in the
+/// vault's custody account.
+#[account]
+pub struct FeeVault {
+    pub sol_accrued: u64, // Awaiting collect_fees
+    pub usdc_accrued: u64,
+    pub sol_collected: u64, // Collected since initialization
+    pub usdc_collected: u64,
+}
+
+impl FeeVault {
+    fn accrued_mut(&mut self, token_type: &TokenType) -> (&mut u64, &mut u64) {
+        match token_type {
+            TokenType::Sol => (&mut self.sol_accrued, &mut self.sol_collected),
+            TokenType::Usdc => (&mut self.usdc_accrued, &mut self.usdc_collected),
+        }
+    }
+
+    pub fn accrued(&self, token_type: &TokenType) -> u64 {
+        match token_type {
+            TokenType::Sol => self.sol_accrued,
+            TokenType::Usdc => self.usdc_accrued,
+        }
+    }
+
+    pub fn accrue(&mut self, token_type: &TokenType, fee: u64) -> Result<()> {
+        let (accrued, _) = self.accrued_mut(token_type);
+        *accrued = accrued.checked_add(fee).ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn collect(&mut self, token_type: &TokenType, amount: u64) -> Result<()> {
+        let (accrued, collected) = self.accrued_mut(token_type);
+        *accrued = accrued
+            .checked_sub(amount)
+            .ok_or(VaultError::InsufficientFees)?;
+        *collected = collected
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// A referrer's rewards: a share of the protocol fee on their referred users' winnings,
+/// accounted here. SOL rewards arrive from the house vault as lamports of this account,
+/// above its rent; USDC rewards stay in the vault's custody account.
+#[account]
+pub struct ReferralAccount {
+    pub referrer: Pubkey,
+    pub referred_users: u64,
+    pub sol_accrued: u64, // Awaiting claim_referral_rewards
+    pub usdc_accrued: u64,
+    pub sol_claimed: u64, // Claimed since creation
+    pub usdc_claimed: u64,
+    pub created_at: i64,
+}
+
+impl ReferralAccount {
+    fn accrued_mut(&mut self, token_type: &TokenType) -> (&mut u64, &mut u64) {
+        match token_type {
+            TokenType::Sol => (&mut self.sol_accrued, &mut self.sol_claimed),
+            TokenType::Usdc => (&mut self.usdc_accrued, &mut self.usdc_claimed),
+        }
+    }
+
+    pub fn accrue(&mut self, token_type: &TokenType, reward: u64) -> Result<()> {
+        let (accrued, _) = self.accrued_mut(token_type);
+        *accrued = accrued
+            .checked_add(reward)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Mark everything accrued in a token as claimed; returns the amount
+    pub fn claim(&mut self, token_type: &TokenType) -> Result<u64> {
+        let (accrued, claimed) = self.accrued_mut(token_type);
+        let amount = std::mem::take(accrued);
+        *claimed = claimed
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(amount)
+    }
+}
+
+/// A withdrawal queued while withdrawals are paused, awaiting the admin's approval.
+/// `destination` is a wallet for SOL and a token account for USDC.
+#[account]
+pub struct EmergencyWithdrawal {
+    pub user: Pubkey,
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub requested_slot: u64,
+    pub requested_at: i64,
+}
+
+/// Withdrawal caps for one token, in its base units; zero means no cap
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct WithdrawalLimits {
+    pub max_per_transaction: u64,
+    pub max_per_user_daily: u64,
+    pub max_global_daily: u64,
+}
+
+impl WithdrawalLimits {
+    /// Apply the caps to a withdrawal of `amount`, recording it in the user's and the
+    /// global rolling window
+    pub fn enforce(
+        &self,
+        user_window: &mut WithdrawalWindow,
+        global_window: &mut WithdrawalWindow,
+        amount: u64,
+        now: i64,
+    ) -> Result<()> {
+        require!(
+            self.max_per_transaction == 0 || amount <= self.max_per_transaction,
+            VaultError::TransactionLimitExceeded
+        );
+        user_window.record(
+            amount,
+            self.max_per_user_daily,
+            now,
+            VaultError::UserDailyLimitExceeded,
+        )?;
+        global_window.record(
+            amount,
+            self.max_global_daily,
+            now,
+            VaultError::GlobalDailyLimitExceeded,
+        )
+    }
+}
+
+/// Withdrawals over the last WITHDRAWAL_WINDOW, as a sliding window counter: totals of
+/// the current fixed window and the one before it, the latter weighted by how much of
+/// it still overlaps the last 24 hours. Two counters instead of a log of withdrawals
+/// keep the account a fixed size.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct WithdrawalWindow {
+    pub window_start: i64,
+    pub current: u64,
+    pub previous: u64,
+}
+
+impl WithdrawalWindow {
+    /// Move the fixed windows forward to the one containing `now`
+    fn advance(&mut self, now: i64) {
+        if self.window_start == 0 || now < self.window_start {
+            *self = Self {
+                window_start: now,
+                ..Self::default()
+            };
+            return;
+        }
+        let elapsed = (now - self.window_start) / WITHDRAWAL_WINDOW;
+        match elapsed {
+            0 => {}
+            1 => {
+                self.previous = self.current;
+                self.current = 0;
+            }
+            _ => {
+                self.previous = 0;
+                self.current = 0;
+            }
+        }
+        self.window_start += elapsed * WITHDRAWAL_WINDOW;
+    }
+
+    /// Amount withdrawn over the WITHDRAWAL_WINDOW ending at `now`
+    pub fn used(&mut self, now: i64) -> u64 {
+        self.advance(now);
+        let remaining = (WITHDRAWAL_WINDOW - (now - self.window_start)) as u128;
+        let carried = self.previous as u128 * remaining / WITHDRAWAL_WINDOW as u128;
+        self.current.saturating_add(carried as u64)
+    }
+
+    /// Count `amount` against `limit` (zero for none), failing with `error` if the
+    /// window would exceed it
+    pub fn record(&mut self, amount: u64, limit: u64, now: i64, error: VaultError) -> Result<()> {
+        let used = self.used(now);
+        if limit != 0 && used.saturating_add(amount) > limit {
+            return Err(error.into());
+        }
+        self.current = self
+            .current
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Apply the vault's per-transaction, per-user daily and global daily caps to a
+/// withdrawal, recording it in both rolling windows
+fn enforce_withdrawal_limits(
+    vault_state: &mut VaultState,
+    user_vault: &mut UserVault,
+    token_type: TokenType,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    let limits = vault_state.limits_mut(&token_type).clone();
+    let (user_window, global_window) = match token_type {
+        TokenType::Sol => (
+            &mut user_vault.sol_withdrawals,
+            &mut vault_state.sol_withdrawals,
+        ),
+        TokenType::Usdc => (
+            &mut user_vault.usdc_withdrawals,
+            &mut vault_state.usdc_withdrawals,
+        ),
+    };
+    limits.enforce(user_window, global_window, amount, now)
+}
+
+/// The house's side of a user balance delta: it keeps what the user lost, and pays
+/// what they won plus the protocol fee `fee` on it
+fn house_delta(user_delta: i64, fee: u64) -> Result<i64> {
+    let fee = i64::try_from(fee).map_err(|_| VaultError::MathOverflow)?;
+    Ok(user_delta
+        .checked_neg()
+        .and_then(|delta| delta.checked_sub(fee))
+        .ok_or(VaultError::MathOverflow)?)
+}
+
+/// Winnings of a settled escrowed bet: a won bet pays back the stake plus up to the
+/// stake again, a lost bet pays nothing
+fn escrow_winnings(amount: u64, won: bool, payout: u64) -> Result<u64> {
+    if !won {
+        require!(payout == 0, VaultError::InvalidPayout);
+        return Ok(0);
+    }
+    let max_payout = amount.checked_mul(2).ok_or(VaultError::MathOverflow)?;
+    require!(
+        payout >= amount && payout <= max_payout,
+        VaultError::InvalidPayout
+    );
+    Ok(payout.checked_sub(amount).ok_or(VaultError::MathOverflow)?)
+}
+
+/// Protocol fee on `winnings` at `fee_bps`, rounded down
+pub fn protocol_fee(winnings: u64, fee_bps: u16) -> Result<u64> {
+    let fee = winnings as u128 * fee_bps as u128 / BPS_DENOMINATOR as u128;
+    u64::try_from(fee).map_err(|_| VaultError::MathOverflow.into())
+}
+
+/// Protocol fee the house pays on a user balance delta. Users are credited their
+/// settled delta in full, so their balances match the sequencer's and the verifier's;
+/// only winnings (positive deltas) carry the fee.
+fn winnings_fee(user_delta: i64, fee_bps: u16) -> Result<u64> {
+    protocol_fee(user_delta.max(0).unsigned_abs(), fee_bps)
+}
+
+/// Accrue settled fees in the fee vault, paid out of the house vault: SOL fees arrive
+/// as lamports of the fee vault for collect_fees to pay out, USDC fees stay in custody
+fn accrue_protocol_fees<'info>(
+    fee_vault: &mut FeeVault,
+    fee_custody: &AccountInfo<'info>,
+    house_vault: &AccountInfo<'info>,
+    rent: &Rent,
+    sol_fee: u64,
+    usdc_fee: u64,
+) -> Result<()> {
+    fee_vault.accrue(&TokenType::Sol, sol_fee)?;
+    fee_vault.accrue(&TokenType::Usdc, usdc_fee)?;
+    transfer_sol_from_custody(house_vault, fee_custody, rent, 0, sol_fee)
+}
+
+/// Poseidon over big-endian scalars, as the settlement circuit hashes; None if an
+/// input is not below the field modulus
+fn poseidon_hash(inputs: &[&[u8]]) -> Option<[u8; 32]> {
+    poseidon::hashv(Parameters::Bn254X5, Endianness::BigEndian, inputs)
+        .ok()
+        .map(|hash| hash.to_bytes())
+}
+
+/// Leaf of a user's net settled P&L: Poseidon(the key's 16-byte halves, P&L offset by
+/// 2^63), so the P&L is an unsigned scalar
+fn pnl_leaf(user: &Pubkey, net_pnl: i64) -> [u8; 32] {
+    let key = user.to_bytes();
+    let mut high = [0u8; 32];
+    high[16..].copy_from_slice(&key[..16]);
+    let mut low = [0u8; 32];
+    low[16..].copy_from_slice(&key[16..]);
+    let mut offset = [0u8; 32];
+    offset[24..].copy_from_slice(&(net_pnl as u64 ^ 1 << 63).to_be_bytes());
+    poseidon_hash(&[&high, &low, &offset]).expect("leaf inputs are below 2^128")
+}
+
+/// Root implied by a net P&L leaf and its Merkle path, None if a sibling is not a
+/// field element
+pub fn pnl_root_from_proof(
+    user: &Pubkey,
+    net_pnl: i64,
+    proof: &[BalanceProofStep],
+) -> Option<[u8; 32]> {
+    proof
+        .iter()
+        .try_fold(pnl_leaf(user, net_pnl), |node, step| {
+            if step.sibling_is_left {
+                poseidon_hash(&[&step.sibling, &node])
+            } else {
+                poseidon_hash(&[&node, &step.sibling])
+            }
+        })
+}
+
+/// Referral terms of one settled bet. ReferralAccounts only exist at their referrer's
+/// PDA, so one in `accounts` with a matching referrer field is that referrer's.
+struct Referral<'a, 'info> {
+    user: Pubkey,
+    referrer: Option<Pubkey>,            // The user's referrer, if any
+    share_bps: u16,                      // VaultState::referral_share_bps
+    house_vault: &'a AccountInfo<'info>, // Pays the reward
+    accounts: &'info [AccountInfo<'info>],
+}
+
+impl<'a, 'info> Referral<'a, 'info> {
+    /// Credit the referrer its share of a protocol fee on the user's winnings, out of
+    /// that fee; returns what is left for the fee vault. The house vault pays the
+    /// reward, as it pays the fee.
+    fn pay(&self, token_type: TokenType, fee: u64) -> Result<u64> {
+        let Some(referrer) = self.referrer else {
+            return Ok(fee);
+        };
+        let reward = referral_reward(fee, self.share_bps)?;
+        if reward == 0 {
+            return Ok(fee);
+        }
+
+        let mut referral_account = self
+            .accounts
+            .iter()
+            .filter(|account| account.is_writable)
+            .find_map(|account| {
+                Account::<ReferralAccount>::try_from(account)
+                    .ok()
+                    .filter(|referral_account| referral_account.referrer == referrer)
+            })
+            .ok_or(VaultError::ReferralAccountRequired)?;
+        let referral_custody = referral_account.to_account_info();
+        accrue_referral_reward(
+            &mut referral_account,
+            &referral_custody,
+            self.house_vault,
+            &Rent::get()?,
+            &token_type,
+            reward,
+        )?;
+        referral_account.exit(&crate::ID)?;
+
+        emit!(ReferralRewardEvent {
+            referrer,
+            user: self.user,
+            token_type,
+            reward,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(fee - reward)
+    }
+}
+
+/// Accrue a referrer's reward in its ReferralAccount, paid out of the house vault: SOL
+/// arrives as lamports of the ReferralAccount for claim_referral_rewards to pay out,
+/// USDC stays in custody
+fn accrue_referral_reward<'info>(
+    referral_account: &mut ReferralAccount,
+    referral_custody: &AccountInfo<'info>,
+    house_vault: &AccountInfo<'info>,
+    rent: &Rent,
+    token_type: &TokenType,
+    reward: u64,
+) -> Result<()> {
+    referral_account.accrue(token_type, reward)?;
+    if *token_type == TokenType::Usdc {
+        return Ok(());
+    }
+    transfer_sol_from_custody(house_vault, referral_custody, rent, 0, reward)
+}
+
+/// Referrer's share of a protocol fee at `share_bps`, rounded down
+pub fn referral_reward(fee: u64, share_bps: u16) -> Result<u64> {
+    protocol_fee(fee, share_bps)
+}
+
+/// Part of a transfer of `amount` withheld by a mint's Token-2022 transfer fee extension
+/// at `epoch`; zero for SPL Token mints and Token-2022 mints without the extension
+pub fn transfer_fee(mint_owner: &Pubkey, mint_data: &[u8], epoch: u64, amount: u64) -> Result<u64> {
+    if *mint_owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data)?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => Ok(config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or(VaultError::MathOverflow)?),
+        Err(_) => Ok(0),
+    }
+}
+
+/// What a transfer of `amount` of `mint` delivers after its transfer fee. Deposits are
+/// credited with this; on the way out the recipient bears the fee.
+fn received_amount(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let fee = transfer_fee(
+        mint.owner,
+        &mint.try_borrow_data()?,
+        Clock::get()?.epoch,
+        amount,
+    )?;
+    let received = amount.checked_sub(fee).ok_or(VaultError::MathUnderflow)?;
+    require!(received > 0, VaultError::InvalidAmount);
+    Ok(received)
+}
+
+/// Pay SOL out of program custody, the lamports an account holds above its rent and
+/// `reserved`, to `destination`. Only lamports that actually arrived can leave.
+fn transfer_sol_from_custody<'info>(
+    custody: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+    rent: &Rent,
+    reserved: u64,
+    amount: u64,
+) -> Result<()> {
+    let spare = custody
+        .lamports()
+        .saturating_sub(rent.minimum_balance(custody.data_len()))
+        .saturating_sub(reserved);
+    require!(amount <= spare, VaultError::InsufficientCustody);
+    custody.sub_lamports(amount)?;
+    destination.add_lamports(amount)?;
+    Ok(())
+}
+
+/// Settle a change of users' balances against the house: `user_delta` SOL lamports
+/// move from the house vault into user custody, the vault state account, when users
+/// won, and back when they lost, and the deposit total follows. USDC of users and the
+/// house share one custody account, so only its total moves.
+fn settle_custody<'info>(
+    vault_state: &mut VaultState,
+    custody: &AccountInfo<'info>,
+    house_vault: &AccountInfo<'info>,
+    rent: &Rent,
+    token_type: &TokenType,
+    user_delta: i64,
+) -> Result<()> {
+    vault_state.settle_deposits(token_type, user_delta)?;
+    if *token_type == TokenType::Usdc {
+        return Ok(());
+    }
+    let amount = user_delta.unsigned_abs();
+    if user_delta >= 0 {
+        transfer_sol_from_custody(house_vault, custody, rent, 0, amount)
+    } else {
+        transfer_sol_from_custody(custody, house_vault, rent, 0, amount)
+    }
+}
+
+/// Pay USDC out of the vault's custody account to `destination`, checked against the
+/// vault's USDC configuration; returns the destination
+#[allow(clippy::too_many_arguments)]
+fn transfer_usdc_from_custody<'info>(
+    vault_state: &VaultState,
+    vault_authority: &UncheckedAccount<'info>,
+    vault_authority_bump: u8,
+    usdc_mint: &Option<InterfaceAccount<'info, Mint>>,
+    vault_token_account: &Option<InterfaceAccount<'info, TokenAccount>>,
+    destination: &Option<InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<Pubkey> {
+    let (Some(usdc_mint), Some(vault_token_account), Some(destination)) =
+        (usdc_mint, vault_token_account, destination)
+    else {
+        return Err(VaultError::UsdcAccountsRequired.into());
+    };
+    require_keys_eq!(
+        usdc_mint.key(),
+        vault_state.usdc_mint,
+        VaultError::InvalidMint
+    );
+    require_keys_eq!(destination.mint, usdc_mint.key(), VaultError::InvalidMint);
+    require_keys_eq!(
+        vault_token_account.key(),
+        get_associated_token_address_with_program_id(
+            &vault_authority.key(),
+            &usdc_mint.key(),
+            &token_program.key(),
+        ),
+        VaultError::InvalidCustodyAccount
+    );
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault_authority", &[vault_authority_bump]]];
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault_token_account.to_account_info(),
+                mint: usdc_mint.to_account_info(),
+                to: destination.to_account_info(),
+                authority: vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        usdc_mint.decimals,
+    )?;
+    Ok(destination.key())
+}
+
+#[account]
+pub struct WithdrawalAllowlist {
+    pub owner: Pubkey,
+    pub destinations: [Pubkey; MAX_WITHDRAWAL_DESTINATIONS],
+    pub destination_count: u8,
+    pub pending_change: Option<PendingAllowlistChange>,
+}
+
+impl WithdrawalAllowlist {
+    pub fn contains(&self, destination: &Pubkey) -> bool {
+        self.destinations[..self.destination_count as usize].contains(destination)
+    }
+
+    pub fn add(&mut self, destination: Pubkey) -> Result<()> {
+        require!(
+            !self.contains(&destination),
+            VaultError::DestinationAlreadyAllowlisted
+        );
+        let count = self.destination_count as usize;
+        require!(
+            count < MAX_WITHDRAWAL_DESTINATIONS,
+            VaultError::AllowlistFull
+        );
+        self.destinations[count] = destination;
+        self.destination_count += 1;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, destination: &Pubkey) -> Result<()> {
+        let count = self.destination_count as usize;
+        let index = self.destinations[..count]
+            .iter()
+            .position(|d| d == destination)
+            .ok_or(VaultError::DestinationNotAllowlisted)?;
+        // Keep at least one destination so funds can always be withdrawn somewhere
+        require!(count > 1, VaultError::AllowlistEmpty);
+        self.destinations[index] = self.destinations[count - 1];
+        self.destinations[count - 1] = Pubkey::default();
+        self.destination_count -= 1;
+        Ok(())
+    }
+}
+
+/// A registered betting currency; one PDA per mint
+#[account]
+pub struct SupportedToken {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub enabled: bool, // Disabled tokens take no deposits; withdrawals stay open
+    pub min_bet: u64,  // Bet bounds, in base units
+    pub max_bet: u64,
+    pub total_deposited: u64,          // Users' balances in custody
+    pub limits: WithdrawalLimits,      // Withdrawal limits for this mint; zero leaves one off
+    pub withdrawals: WithdrawalWindow, // All users' withdrawals of this mint, for the global cap
+}
+
+impl SupportedToken {
+    pub const SPACE: usize = 8 + 32 + 1 + 1 + 8 + 8 + 8 + 24 + 24;
+
+    pub fn check_bet_limits(min_bet: u64, max_bet: u64) -> Result<()> {
+        require!(
+            min_bet > 0 && min_bet <= max_bet,
+            VaultError::InvalidBetLimits
+        );
+        Ok(())
+    }
+
+    /// Whether a bet of `amount` may be placed in this token
+    pub fn accepts_bet(&self, amount: u64) -> bool {
+        self.enabled && (self.min_bet..=self.max_bet).contains(&amount)
+    }
+}
+
+/// A user's balance in one registered token
+#[account]
+pub struct TokenBalance {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub balance: u64,
+    pub withdrawals: WithdrawalWindow, // Recent withdrawals, for the daily limit
+}
+
+impl TokenBalance {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 24;
+}
+
+/// Stake held for a single on-chain bet until it is settled or refunded
+#[account]
+pub struct BetEscrow {
+    pub owner: Pubkey,
+    pub nonce: u64,
+    pub amount: u64,
+    pub token_type: TokenType,
+    pub guess: bool,
+    pub created_at: i64,
+}
+
+impl BetEscrow {
+    pub const SPACE: usize = 8 + 32 + 8 + 8 + 1 + 1 + 8;
+}
+
+/// Funds locked in a user's vault for one bet the sequencer accepted, until the lock is
+/// released through the verifier or reclaimed after BET_LOCK_TIMEOUT
+#[account]
+pub struct BetLock {
+    pub owner: Pubkey,
+    pub bet_id: u64,
+    pub amount: u64,
+    pub token_type: TokenType,
+    pub locked_at: i64,
+    pub exit_epoch: u64, // The owner's finalized exits when the lock was taken
+}
+
+impl BetLock {
+    pub const SPACE: usize = 8 + 32 + 8 + 8 + 1 + 8 + 8;
+
+    /// When the user may release the lock themselves
+    pub fn reclaimable_at(&self) -> Result<i64> {
+        Ok(self
+            .locked_at
+            .checked_add(BET_LOCK_TIMEOUT)
+            .ok_or(VaultError::MathOverflow)?)
+    }
+}
+
+/// Release exactly what a bet lock holds; callers close the lock account. A forced exit
+/// already released the locks taken before it, so those are only closed.
+fn release_bet_lock(user_vault: &mut UserVault, bet_lock: &BetLock) -> Result<()> {
+    if bet_lock.exit_epoch != user_vault.exits {
+        return Ok(());
+    }
+    user_vault.release(&bet_lock.token_type, bet_lock.amount)?;
+    emit!(BetLockEvent {
+        user: user_vault.owner,
+        bet_id: bet_lock.bet_id,
+        token_type: bet_lock.token_type.clone(),
+        amount: bet_lock.amount,
+        locked: false,
+        new_locked_balance: user_vault.locked(&bet_lock.token_type),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+/// Reject withdrawals to destinations outside the user's allowlist (when opted in)
+fn enforce_withdrawal_allowlist(
+    allowlist_info: &AccountInfo,
+    destination: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<()> {
+    if allowlist_info.owner != program_id || allowlist_info.data_is_empty() {
+        return Ok(()); // User has not opted in
+    }
+
+    let allowlist = WithdrawalAllowlist::try_deserialize(&mut &allowlist_info.data.borrow()[..])?;
+    require!(
+        allowlist.contains(destination),
+        VaultError::DestinationNotAllowlisted
+    );
+    Ok(())
+}
+
+// Context structures
+#[derive(Accounts)]
+pub struct InitializeVault<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<VaultState>(),
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserVault<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + std::mem::size_of::<UserVault>(),
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    // Required with a referrer; checked in the handler against it
+    #[account(mut)]
+    pub referral_account: Option<Account<'info, ReferralAccount>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositFor<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Derives the vault and deposit address; need not sign
+    pub user: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"deposit_address", user.key().as_ref()],
+        bump
+    )]
+    pub deposit_address: SystemAccount<'info>,
+    pub relayer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureUsdc<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = usdc_mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositUsdc<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(address = vault_state.usdc_mint @ VaultError::InvalidMint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = usdc_mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        token::authority = user,
+        token::token_program = token_program
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Address is fixed by seeds; only enforced when the user has opted in
+    #[account(
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: UncheckedAccount<'info>,
+    /// CHECK: Withdrawal destination, validated against the allowlist
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitiateExit<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeExit<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Address is fixed by seeds; only enforced when the user has opted in
+    #[account(
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: UncheckedAccount<'info>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    // Checked in finalize_exit against the vault's USDC configuration
+    pub usdc_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub destination: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeTokenExit<'info> {
+    #[account(
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"token_balance", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub token_balance: Account<'info, TokenBalance>,
+    #[account(
+        mut,
+        seeds = [b"supported_token", mint.key().as_ref()],
+        bump
+    )]
+    pub supported_token: Account<'info, SupportedToken>,
+    /// CHECK: Address is fixed by seeds; only enforced when the user has opted in
+    #[account(
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Exit destination; its owner is validated against the allowlist
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RequestEmergencyWithdrawal<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + std::mem::size_of::<EmergencyWithdrawal>(),
+        seeds = [EMERGENCY_WITHDRAWAL_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub emergency_withdrawal: Account<'info, EmergencyWithdrawal>,
+    #[account(
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveEmergencyWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [EMERGENCY_WITHDRAWAL_SEED, user.key().as_ref()],
+        bump,
+        has_one = user,
+        close = user
+    )]
+    pub emergency_withdrawal: Account<'info, EmergencyWithdrawal>,
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Address is fixed by seeds; only enforced when the user has opted in
+    #[account(
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: UncheckedAccount<'info>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    // Checked in approve_emergency_withdrawal against the request and the USDC configuration
+    pub usdc_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub destination_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Receives a SOL request's lamports; checked against the request
+    #[account(mut)]
+    pub destination: Option<UncheckedAccount<'info>>,
+    /// CHECK: Owner of the request, receiving its rent back
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelEmergencyWithdrawal<'info> {
+    #[account(
+        mut,
+        seeds = [EMERGENCY_WITHDRAWAL_SEED, user.key().as_ref()],
+        bump,
+        has_one = user,
+        close = user
+    )]
+    pub emergency_withdrawal: Account<'info, EmergencyWithdrawal>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUserVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump,
+        close = user
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawUsdc<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Address is fixed by seeds; only enforced when the user has opted in
+    #[account(
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: UncheckedAccount<'info>,
+    #[account(address = vault_state.usdc_mint @ VaultError::InvalidMint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = usdc_mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Withdrawal destination; its owner is validated against the allowlist
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        token::token_program = token_program
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterToken<'info> {
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = authority,
+        space = SupportedToken::SPACE,
+        seeds = [b"supported_token", mint.key().as_ref()],
+        bump
+    )]
+    pub supported_token: Account<'info, SupportedToken>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateToken<'info> {
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"supported_token", supported_token.mint.as_ref()],
+        bump
+    )]
+    pub supported_token: Account<'info, SupportedToken>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTokenBalance<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = TokenBalance::SPACE,
+        seeds = [b"token_balance", user.key().as_ref(), supported_token.mint.as_ref()],
+        bump
+    )]
+    pub token_balance: Account<'info, TokenBalance>,
+    #[account(
+        seeds = [b"supported_token", supported_token.mint.as_ref()],
+        bump
+    )]
+    pub supported_token: Account<'info, SupportedToken>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToken<'info> {
+    #[account(
+        mut,
+        seeds = [b"token_balance", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub token_balance: Account<'info, TokenBalance>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"supported_token", mint.key().as_ref()],
+        bump
+    )]
+    pub supported_token: Account<'info, SupportedToken>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = user,
+        token::token_program = token_program
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawToken<'info> {
+    #[account(
+        mut,
+        seeds = [b"token_balance", user.key().as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub token_balance: Account<'info, TokenBalance>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"supported_token", mint.key().as_ref()],
+        bump
+    )]
+    pub supported_token: Account<'info, SupportedToken>,
+    /// CHECK: Address is fixed by seeds; only enforced when the user has opted in
+    #[account(
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Withdrawal destination; its owner is validated against the allowlist
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBalancesBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault"],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        seeds = [VERIFIER_CALLER_SEED],
+        bump,
+        seeds::program = verifier_program::ID
+    )]
+    pub verifier_caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBalances<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_vault", user_vault.owner.as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault"],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        seeds = [VERIFIER_CALLER_SEED],
+        bump,
+        seeds::program = verifier_program::ID
+    )]
+    pub verifier_caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CommitBalancesRoot<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        seeds = [VERIFIER_CALLER_SEED],
+        bump,
+        seeds::program = verifier_program::ID
+    )]
+    pub verifier_caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProvePnlInclusion<'info> {
+    #[account(
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct LockForBet<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = BetLock::SPACE,
+        seeds = [b"bet_lock", user_vault.owner.as_ref(), &bet_id.to_le_bytes()],
+        bump
+    )]
+    pub bet_lock: Account<'info, BetLock>,
+    #[account(
+        mut,
+        seeds = [b"user_vault", user_vault.owner.as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(bet_id: u64)]
+pub struct ReleaseLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"bet_lock", user_vault.owner.as_ref(), &bet_id.to_le_bytes()],
+        bump,
+        close = authority
+    )]
+    pub bet_lock: Account<'info, BetLock>,
+    #[account(
+        mut,
+        seeds = [b"user_vault", user_vault.owner.as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority, which paid the lock account's rent
+    #[account(mut)]
+    pub authority: UncheckedAccount<'info>,
+    #[account(
+        seeds = [VERIFIER_CALLER_SEED],
+        bump,
+        seeds::program = verifier_program::ID
+    )]
+    pub verifier_caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimBetLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"bet_lock", owner.key().as_ref(), &bet_lock.bet_id.to_le_bytes()],
+        bump,
+        has_one = owner,
+        close = authority
+    )]
+    pub bet_lock: Account<'info, BetLock>,
+    #[account(
+        mut,
+        seeds = [b"user_vault", owner.key().as_ref()],
+        bump
+    )]
+    pub user_vault: Account<'info, UserVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority, which paid the lock account's rent
+    #[account(mut)]
+    pub authority: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeHouseVault<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<HouseVault>(),
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundHouseSol<'info> {
+    #[account(
+        mut,
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundHouseUsdc<'info> {
+    #[account(
+        mut,
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(address = vault_state.usdc_mint @ VaultError::InvalidMint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = usdc_mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        token::authority = authority,
+        token::token_program = token_program
+    )]
+    pub authority_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeVault<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<FeeVault>(),
+        seeds = [b"fee_vault"],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetReferralShare<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeReferralAccount<'info> {
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + std::mem::size_of::<ReferralAccount>(),
+        seeds = [REFERRAL_SEED, referrer.key().as_ref()],
+        bump
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info> {
+    #[account(
+        mut,
+        seeds = [REFERRAL_SEED, referrer.key().as_ref()],
+        bump,
+        has_one = referrer
+    )]
+    pub referral_account: Account<'info, ReferralAccount>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    // Checked in claim_referral_rewards against the vault's USDC configuration
+    pub usdc_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub destination_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(
+        mut,
+        seeds = [b"fee_vault"],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Receives SOL fees; chosen by the authority
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    // Checked in collect_fees against the vault's USDC configuration
+    pub usdc_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    pub vault_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
+    pub destination_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
     pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct InitializeUserVault<'info> {
+pub struct WithdrawHouseProfitSol<'info> {
     #[account(
-        init,
-        payer = user,
-        space = 8 + std::mem::size_of::<UserVault>(),
-        seeds = [b"user_vault", user.key().as_ref()],
+        mut,
+        seeds = [b"house_vault"],
         bump
     )]
-    pub user_vault: Account<'info, UserVault>,
+    pub house_vault: Account<'info, HouseVault>,
     #[account(
-        mut,
         seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Receives the lamports; chosen by the authority
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawHouseProfitUsdc<'info> {
+    #[account(
+        mut,
+        seeds = [b"house_vault"],
         bump
     )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
     pub vault_state: Account<'info, VaultState>,
+    #[account(address = vault_state.usdc_mint @ VaultError::InvalidMint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the vault's token accounts; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = usdc_mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = usdc_mint,
+        token::token_program = token_program
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeWithdrawalAllowlist<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + std::mem::size_of::<WithdrawalAllowlist>(),
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump
+    )]
+    pub withdrawal_allowlist: Account<'info, WithdrawalAllowlist>,
     #[account(mut)]
     pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct DepositSol<'info> {
+pub struct ManageWithdrawalAllowlist<'info> {
+    #[account(
+        mut,
+        seeds = [b"withdrawal_allowlist", user.key().as_ref()],
+        bump,
+        constraint = withdrawal_allowlist.owner == user.key() @ VaultError::Unauthorized
+    )]
+    pub withdrawal_allowlist: Account<'info, WithdrawalAllowlist>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct PlaceEscrowedBet<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = BetEscrow::SPACE,
+        seeds = [b"bet_escrow", user.key().as_ref(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub bet_escrow: Account<'info, BetEscrow>,
     #[account(
         mut,
         seeds = [b"user_vault", user.key().as_ref()],
@@ -358,31 +4345,65 @@ pub struct DepositSol<'info> {
         bump
     )]
     pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
     pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct DepositUsdc<'info> {
+pub struct SettleEscrowedBet<'info> {
     #[account(
         mut,
-        seeds = [b"user_vault", user.key().as_ref()],
+        seeds = [b"bet_escrow", owner.key().as_ref(), &bet_escrow.nonce.to_le_bytes()],
+        bump,
+        has_one = owner,
+        close = owner
+    )]
+    pub bet_escrow: Account<'info, BetEscrow>,
+    #[account(
+        mut,
+        seeds = [b"user_vault", owner.key().as_ref()],
         bump
     )]
     pub user_vault: Account<'info, UserVault>,
     #[account(
         mut,
         seeds = [b"vault_state"],
-        bump
+        bump,
+        has_one = authority
     )]
     pub vault_state: Account<'info, VaultState>,
-    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault"],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+    /// CHECK: Bet owner; receives the escrow account's rent
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawSol<'info> {
+pub struct RefundEscrowedBet<'info> {
     #[account(
         mut,
-        seeds = [b"user_vault", user.key().as_ref()],
+        seeds = [b"bet_escrow", owner.key().as_ref(), &bet_escrow.nonce.to_le_bytes()],
+        bump,
+        has_one = owner,
+        close = owner
+    )]
+    pub bet_escrow: Account<'info, BetEscrow>,
+    #[account(
+        mut,
+        seeds = [b"user_vault", owner.key().as_ref()],
         bump
     )]
     pub user_vault: Account<'info, UserVault>,
@@ -392,84 +4413,330 @@ pub struct WithdrawSol<'info> {
         bump
     )]
     pub vault_state: Account<'info, VaultState>,
-    pub user: Signer<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct WithdrawUsdc<'info> {
+pub struct TreasuryHealthView<'info> {
     #[account(
-        mut,
-        seeds = [b"user_vault", user.key().as_ref()],
+        seeds = [b"vault_state"],
         bump
     )]
-    pub user_vault: Account<'info, UserVault>,
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: PDA owning the USDC custody account; holds no data
+    #[account(
+        seeds = [b"vault_authority"],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: The custody token account, empty until configure_usdc runs; its address is
+    /// checked in the handler against the token program owning it
+    pub usdc_custody: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseFlags<'info> {
     #[account(
         mut,
         seeds = [b"vault_state"],
-        bump
+        bump,
+        has_one = authority
     )]
     pub vault_state: Account<'info, VaultState>,
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateBalances<'info> {
+pub struct SetWithdrawalLimits<'info> {
     #[account(
         mut,
-        seeds = [b"user_vault", user_vault.owner.as_ref()],
-        bump
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
     )]
-    pub user_vault: Account<'info, UserVault>,
+    pub vault_state: Account<'info, VaultState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault_state"],
+        bump,
+        has_one = authority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
     #[account(
         mut,
         seeds = [b"vault_state"],
         bump
     )]
     pub vault_state: Account<'info, VaultState>,
-    /// CHECK: This should be the verifier program calling this instruction
-    pub verifier_program: UncheckedAccount<'info>,
+    pub new_authority: Signer<'info>,
+}
+
+// Events
+#[event]
+pub struct DepositEvent {
+    pub user: Pubkey,
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub deposit_nonce: Option<u64>, // Set for deposits relayed through deposit_for
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BalancesRootCommittedEvent {
+    pub batch_id: u64,
+    pub balances_root: [u8; 32],
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseFlagsUpdatedEvent {
+    pub pause_flags: u8,
+    pub paused_until_slot: Option<u64>,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawalLimitsUpdatedEvent {
+    pub token_type: TokenType,
+    pub limits: WithdrawalLimits,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct HouseFundedEvent {
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub bankroll: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolFeeUpdatedEvent {
+    pub fee_bps: u16,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesCollectedEvent {
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub remaining: u64, // Still accrued after this collection
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct HouseProfitWithdrawnEvent {
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub bankroll: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawEvent {
+    pub user: Pubkey,
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenDepositEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenWithdrawEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenWithdrawalLimitsUpdatedEvent {
+    pub mint: Pubkey,
+    pub limits: WithdrawalLimits,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TokenConfigUpdatedEvent {
+    pub mint: Pubkey,
+    pub enabled: bool,
+    pub min_bet: u64,
+    pub max_bet: u64,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BalanceUpdateEvent {
+    pub user: Pubkey,
+    pub sol_delta: i64, // Credited in full
+    pub usdc_delta: i64,
+    pub protocol_fee: u64, // Paid by the house on the winnings
+    pub new_sol_balance: u64,
+    pub new_usdc_balance: u64,
+    pub is_win: bool,
+    pub bet_count: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BetLockEvent {
+    pub user: Pubkey,
+    pub bet_id: u64,
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub locked: bool, // False when the lock was released
+    pub new_locked_balance: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AllowlistChangeRequestedEvent {
+    pub user: Pubkey,
+    pub destination: Pubkey,
+    pub action: AllowlistAction,
+    pub effective_at: i64,
+}
+
+#[event]
+pub struct BetEscrowedEvent {
+    pub user: Pubkey,
+    pub escrow: Pubkey,
+    pub nonce: u64,
+    pub amount: u64,
+    pub token_type: TokenType,
+    pub guess: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowSettledEvent {
+    pub user: Pubkey,
+    pub escrow: Pubkey,
+    pub won: bool,
+    pub amount: u64,
+    pub payout: u64,
+    pub protocol_fee: u64, // Paid by the house on top of the payout
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ExitInitiatedEvent {
+    pub user: Pubkey,
+    pub sol_balance: u64,
+    pub usdc_balance: u64,
+    pub requested_slot: u64,
+    pub finalizable_at_slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ExitFinalizedEvent {
+    pub user: Pubkey,
+    pub sol_amount: u64,
+    pub usdc_amount: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct SetPauseState<'info> {
-    #[account(
-        mut,
-        seeds = [b"vault_state"],
-        bump,
-        has_one = authority
-    )]
-    pub vault_state: Account<'info, VaultState>,
-    pub authority: Signer<'info>,
+#[event]
+pub struct EmergencyWithdrawalRequestedEvent {
+    pub user: Pubkey,
+    pub token_type: TokenType,
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub requested_slot: u64,
+    pub timestamp: i64,
 }
 
-// Events
 #[event]
-pub struct DepositEvent {
+pub struct EmergencyWithdrawalApprovedEvent {
     pub user: Pubkey,
     pub token_type: TokenType,
     pub amount: u64,
+    pub destination: Pubkey,
     pub new_balance: u64,
+    pub authority: Pubkey,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct WithdrawEvent {
+pub struct EmergencyWithdrawalCancelledEvent {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralShareUpdatedEvent {
+    pub share_bps: u16,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralRewardEvent {
+    pub referrer: Pubkey,
     pub user: Pubkey,
     pub token_type: TokenType,
+    pub reward: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralRewardsClaimedEvent {
+    pub referrer: Pubkey,
+    pub token_type: TokenType,
     pub amount: u64,
-    pub new_balance: u64,
+    pub destination: Pubkey,
     pub timestamp: i64,
 }
 
 #[event]
-pub struct BalanceUpdateEvent {
+pub struct UserVaultClosedEvent {
     pub user: Pubkey,
-    pub sol_delta: i64,
-    pub usdc_delta: i64,
-    pub new_sol_balance: u64,
-    pub new_usdc_balance: u64,
-    pub is_win: bool,
-    pub bet_count: u64,
+    pub rent_reclaimed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityProposedEvent {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferredEvent {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
     pub timestamp: i64,
 }
 
@@ -480,6 +4747,52 @@ pub enum TokenType {
     Usdc,
 }
 
+/// One hash on the path from a net P&L leaf up to the balances root
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct BalanceProofStep {
+    pub sibling: [u8; 32],
+    pub sibling_is_left: bool,
+}
+
+/// One settled bet's effect on a user vault, as applied by update_balances_batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct UserDelta {
+    pub user: Pubkey,
+    pub sol_delta: i64,
+    pub usdc_delta: i64,
+    pub is_win: bool,
+    pub bet_amount: u64,
+}
+
+/// Treasury figures returned by treasury_health, in this borsh order. The layout is
+/// stable: fields are only ever appended, with `version` raised when they are.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct TreasuryHealth {
+    pub version: u8, // TREASURY_HEALTH_VERSION
+    pub total_sol_deposited: u64,
+    pub total_usdc_deposited: u64,
+    pub usdc_in_custody: u64, // Token balance actually held by the custody account
+    pub sol_escrowed: u64,
+    pub usdc_escrowed: u64,
+    pub total_users: u64,
+    pub is_paused: bool, // Any operation paused at the current slot
+    pub pause_flags: u8, // Since version 2
+    pub paused_until_slot: Option<u64>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum AllowlistAction {
+    Add,
+    Remove,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct PendingAllowlistChange {
+    pub destination: Pubkey,
+    pub action: AllowlistAction,
+    pub effective_at: i64,
+}
+
 // Error codes
 #[error_code]
 pub enum VaultError {
@@ -495,6 +4808,98 @@ pub enum VaultError {
     MathUnderflow,
     #[msg("Unauthorized access")]
     Unauthorized,
+    #[msg("Withdrawal destination is not allowlisted")]
+    DestinationNotAllowlisted,
+    #[msg("Destination is already allowlisted")]
+    DestinationAlreadyAllowlisted,
+    #[msg("Withdrawal allowlist is full")]
+    AllowlistFull,
+    #[msg("Cannot remove the last allowlisted destination")]
+    AllowlistEmpty,
+    #[msg("An allowlist change is already pending")]
+    AllowlistChangePending,
+    #[msg("No pending allowlist change")]
+    NoPendingAllowlistChange,
+    #[msg("Allowlist change delay has not elapsed")]
+    AllowlistChangeTimelocked,
+    #[msg("Payout is inconsistent with the escrowed stake")]
+    InvalidPayout,
+    #[msg("Escrowed bet cannot be refunded yet")]
+    EscrowRefundTimelocked,
+    #[msg("Bet lock cannot be reclaimed yet")]
+    BetLockTimelocked,
+    #[msg("Signer is not the pending authority")]
+    NotPendingAuthority,
+    #[msg("Token mint is not the configured USDC mint")]
+    InvalidMint,
+    #[msg("USDC mint cannot change while USDC is in custody")]
+    UsdcInCustody,
+    #[msg("Batch must have between 1 and MAX_BATCH_DELTAS deltas")]
+    InvalidBatchSize,
+    #[msg("User vault accounts do not match the batch's deltas")]
+    UserVaultMismatch,
+    #[msg("House bankroll cannot cover the payout")]
+    InsufficientBankroll,
+    #[msg("Amount exceeds the house profit")]
+    InsufficientHouseProfit,
+    #[msg("Withdrawal exceeds the per-transaction limit")]
+    TransactionLimitExceeded,
+    #[msg("Withdrawal exceeds the user's daily limit")]
+    UserDailyLimitExceeded,
+    #[msg("Withdrawal exceeds the vault's daily limit")]
+    GlobalDailyLimitExceeded,
+    #[msg("A forced exit is already pending")]
+    ExitAlreadyPending,
+    #[msg("No forced exit is pending")]
+    NoExitPending,
+    #[msg("A settlement has landed since the forced exit was requested")]
+    ExitSuperseded,
+    #[msg("Forced exit delay has not elapsed")]
+    ExitTimelocked,
+    #[msg("USDC mint, custody and destination accounts are required")]
+    UsdcAccountsRequired,
+    #[msg("Token account is not the vault's USDC custody account")]
+    InvalidCustodyAccount,
+    #[msg("USDC is configured with configure_usdc, not the token registry")]
+    UsdcNotRegistrable,
+    #[msg("Minimum bet must be positive and no more than the maximum bet")]
+    InvalidBetLimits,
+    #[msg("Token is disabled")]
+    TokenDisabled,
+    #[msg("Amount exceeds the locked balance")]
+    InsufficientLockedBalance,
+    #[msg("Protocol fee cannot exceed MAX_PROTOCOL_FEE_BPS")]
+    InvalidProtocolFee,
+    #[msg("Amount exceeds the accrued protocol fees")]
+    InsufficientFees,
+    #[msg("User vault still holds a balance")]
+    VaultNotEmpty,
+    #[msg("User vault has bets awaiting settlement")]
+    BetsPending,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    #[msg("Pause expiry slot has already passed")]
+    InvalidPauseExpiry,
+    #[msg("Referral share cannot exceed the whole protocol fee")]
+    InvalidReferralShare,
+    #[msg("Users cannot refer themselves")]
+    SelfReferral,
+    #[msg("The referrer's referral account is required")]
+    ReferralAccountRequired,
+    #[msg("Referral account belongs to a different referrer")]
+    ReferralAccountMismatch,
+    #[msg("No referral rewards to claim")]
+    NoReferralRewards,
+    #[msg("Balances root is older than the committed one")]
+    StaleBalancesRoot,
+    #[msg("Balance is not included under the committed balances root")]
+    InvalidBalanceProof,
+    #[msg("Emergency withdrawals are only accepted while withdrawals are paused")]
+    EmergencyWithdrawalUnavailable,
+    #[msg("Destination does not match the emergency withdrawal request")]
+    EmergencyWithdrawalDestinationMismatch,
+    #[msg("Custody holds too few lamports above rent for the payout")]
+    InsufficientCustody,
 }
 
 #[cfg(test)]
@@ -507,6 +4912,1173 @@ mod tests {
         assert_eq!(error.to_string(), "Invalid amount provided");
     }
 
+    #[test]
+    fn test_apply_delta() {
+        let mut user_vault = UserVault {
+            owner: Pubkey::new_unique(),
+            sol_balance: 100,
+            usdc_balance: 50,
+            bet_count: 0,
+            total_winnings: 0,
+            total_losses: 0,
+            created_at: 0,
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            last_settled_slot: 0,
+            exit_requested_slot: None,
+            deposit_nonce: 0,
+            locked_balance: LockedBalance::default(),
+            open_escrows: 0,
+            referrer: None,
+            sol_wagered: 0,
+            usdc_wagered: 0,
+            sol_won: 0,
+            usdc_won: 0,
+            current_streak: 0,
+            last_bet_at: 0,
+            exits: 0,
+        };
+        user_vault.apply_delta(20, 0, true, 20, 0).unwrap();
+        user_vault.apply_delta(-30, -10, false, 30, 0).unwrap();
+        assert_eq!(user_vault.sol_balance, 90);
+        assert_eq!(user_vault.usdc_balance, 40);
+        assert_eq!(user_vault.bet_count, 2);
+        assert_eq!(user_vault.total_winnings, 20);
+        assert_eq!(user_vault.total_losses, 30);
+
+        // A loss larger than the balance is refused
+        assert!(user_vault.apply_delta(-91, 0, false, 91, 0).is_err());
+    }
+
+    #[test]
+    fn test_bet_statistics() {
+        let mut user_vault = UserVault {
+            owner: Pubkey::new_unique(),
+            sol_balance: 1_000,
+            usdc_balance: 1_000,
+            bet_count: 0,
+            total_winnings: 0,
+            total_losses: 0,
+            created_at: 0,
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            last_settled_slot: 0,
+            exit_requested_slot: None,
+            deposit_nonce: 0,
+            locked_balance: LockedBalance::default(),
+            open_escrows: 0,
+            referrer: None,
+            sol_wagered: 0,
+            usdc_wagered: 0,
+            sol_won: 0,
+            usdc_won: 0,
+            current_streak: 0,
+            last_bet_at: 0,
+            exits: 0,
+        };
+        // Two SOL wins (the second net of a fee), then a USDC loss
+        user_vault.apply_delta(100, 0, true, 100, 10).unwrap();
+        user_vault.apply_delta(95, 0, true, 100, 20).unwrap();
+        assert_eq!(user_vault.current_streak, 2);
+        user_vault.apply_delta(0, -50, false, 50, 30).unwrap();
+        assert_eq!(user_vault.sol_wagered, 200);
+        assert_eq!(user_vault.sol_won, 195);
+        assert_eq!(user_vault.usdc_wagered, 50);
+        assert_eq!(user_vault.usdc_won, 0);
+        assert_eq!(user_vault.current_streak, -1);
+        assert_eq!(user_vault.last_bet_at, 30);
+
+        // Losses extend a losing streak; a win resets it
+        user_vault
+            .record_bet(&TokenType::Usdc, false, 10, 0, 40)
+            .unwrap();
+        assert_eq!(user_vault.current_streak, -2);
+        user_vault
+            .record_bet(&TokenType::Usdc, true, 10, 10, 50)
+            .unwrap();
+        assert_eq!(user_vault.current_streak, 1);
+        assert_eq!(user_vault.usdc_won, 10);
+        assert_eq!(user_vault.bet_count, 5);
+
+        // Account space comes from size_of, which must still cover the borsh encoding
+        let mut data = Vec::new();
+        user_vault.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= 8 + std::mem::size_of::<UserVault>());
+    }
+
+    #[test]
+    fn test_bet_locks() {
+        let mut user_vault = UserVault {
+            owner: Pubkey::new_unique(),
+            sol_balance: 100,
+            usdc_balance: 50,
+            bet_count: 0,
+            total_winnings: 0,
+            total_losses: 0,
+            created_at: 0,
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            last_settled_slot: 0,
+            exit_requested_slot: None,
+            deposit_nonce: 0,
+            locked_balance: LockedBalance::default(),
+            open_escrows: 0,
+            referrer: None,
+            sol_wagered: 0,
+            usdc_wagered: 0,
+            sol_won: 0,
+            usdc_won: 0,
+            current_streak: 0,
+            last_bet_at: 0,
+            exits: 0,
+        };
+        user_vault.lock(&TokenType::Sol, 30).unwrap();
+        user_vault.lock(&TokenType::Sol, 40).unwrap();
+        user_vault.lock(&TokenType::Usdc, 50).unwrap();
+        assert_eq!(user_vault.available(&TokenType::Sol), 30);
+        assert_eq!(user_vault.available(&TokenType::Usdc), 0);
+        assert_eq!(
+            user_vault.lock(&TokenType::Sol, 31),
+            Err(VaultError::InsufficientBalance.into())
+        );
+
+        // Settling a bet leaves its lock to its BetLock: a 30 lamport win, then a 40
+        // lamport loss, each released for exactly the locked stake afterwards
+        user_vault.apply_delta(30, 0, true, 30, 0).unwrap();
+        assert_eq!(user_vault.locked(&TokenType::Sol), 70);
+        user_vault.release(&TokenType::Sol, 30).unwrap();
+        assert_eq!(user_vault.available(&TokenType::Sol), 90);
+        user_vault.apply_delta(-40, 0, false, 40, 0).unwrap();
+        assert_eq!(user_vault.available(&TokenType::Sol), 50);
+        user_vault.release(&TokenType::Sol, 40).unwrap();
+        assert_eq!(user_vault.locked(&TokenType::Sol), 0);
+        assert_eq!(user_vault.available(&TokenType::Sol), 90);
+        assert_eq!(user_vault.locked(&TokenType::Usdc), 50);
+
+        // A lock that won't be settled is released the same way, but never beyond it
+        assert_eq!(
+            user_vault.release(&TokenType::Usdc, 51),
+            Err(VaultError::InsufficientLockedBalance.into())
+        );
+        user_vault.release(&TokenType::Usdc, 50).unwrap();
+        assert_eq!(user_vault.available(&TokenType::Usdc), 50);
+
+        // A forced exit voids the locks taken before it: releasing one afterwards leaves
+        // the locks taken since alone
+        let stale = BetLock {
+            owner: user_vault.owner,
+            bet_id: 1,
+            amount: 20,
+            token_type: TokenType::Sol,
+            locked_at: 0,
+            exit_epoch: user_vault.exits,
+        };
+        user_vault.lock(&TokenType::Sol, 20).unwrap();
+        user_vault.locked_balance = LockedBalance::default();
+        user_vault.exits += 1;
+        user_vault.lock(&TokenType::Sol, 25).unwrap();
+        release_bet_lock(&mut user_vault, &stale).unwrap();
+        assert_eq!(user_vault.locked(&TokenType::Sol), 25);
+    }
+
+    #[test]
+    fn test_protocol_fee() {
+        // 2.5% of the winnings, rounded down; losses pay nothing
+        assert_eq!(protocol_fee(1_000_000, 250).unwrap(), 25_000);
+        assert_eq!(protocol_fee(39, 250).unwrap(), 0);
+        assert_eq!(winnings_fee(1_000_000, 250).unwrap(), 25_000);
+        assert_eq!(winnings_fee(-500_000, 250).unwrap(), 0);
+        assert_eq!(winnings_fee(1_000_000, 0).unwrap(), 0);
+        // The user keeps the whole win; the house pays it and the fee
+        assert_eq!(house_delta(1_000_000, 25_000).unwrap(), -1_025_000);
+        assert_eq!(house_delta(-500_000, 0).unwrap(), 500_000);
+        let fee = winnings_fee(i64::MAX, MAX_PROTOCOL_FEE_BPS).unwrap();
+        assert_eq!(
+            house_delta(i64::MAX, fee).unwrap_err(),
+            VaultError::MathOverflow.into()
+        );
+
+        let mut fee_vault = FeeVault {
+            sol_accrued: 0,
+            usdc_accrued: 0,
+            sol_collected: 0,
+            usdc_collected: 0,
+        };
+        fee_vault.accrue(&TokenType::Sol, 25_000).unwrap();
+        fee_vault.accrue(&TokenType::Usdc, 7).unwrap();
+        assert_eq!(
+            fee_vault.collect(&TokenType::Sol, 25_001),
+            Err(VaultError::InsufficientFees.into())
+        );
+        fee_vault.collect(&TokenType::Sol, 20_000).unwrap();
+        assert_eq!(fee_vault.accrued(&TokenType::Sol), 5_000);
+        assert_eq!(fee_vault.sol_collected, 20_000);
+        assert_eq!(fee_vault.accrued(&TokenType::Usdc), 7);
+    }
+
+    #[test]
+    fn test_escrow_winnings() {
+        assert_eq!(escrow_winnings(1_000, true, 2_000).unwrap(), 1_000);
+        assert_eq!(escrow_winnings(1_000, true, 1_000).unwrap(), 0);
+        // A lost bet pays nothing and wins nothing
+        assert_eq!(escrow_winnings(1_000, false, 0).unwrap(), 0);
+        assert_eq!(escrow_winnings(u64::MAX, false, 0).unwrap(), 0);
+
+        for (won, payout) in [(false, 1), (true, 999), (true, 2_001)] {
+            assert_eq!(
+                escrow_winnings(1_000, won, payout),
+                Err(VaultError::InvalidPayout.into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_referral_rewards() {
+        // Half of a 2.5% fee on a 1 SOL win goes to the referrer
+        let fee = protocol_fee(1_000_000_000, 250).unwrap();
+        let reward = referral_reward(fee, 5_000).unwrap();
+        assert_eq!(reward, 12_500_000);
+        assert_eq!(referral_reward(fee, 0).unwrap(), 0);
+        assert_eq!(referral_reward(fee, BPS_DENOMINATOR as u16).unwrap(), fee);
+
+        let mut referral_account = ReferralAccount {
+            referrer: Pubkey::new_unique(),
+            referred_users: 1,
+            sol_accrued: 0,
+            usdc_accrued: 0,
+            sol_claimed: 0,
+            usdc_claimed: 0,
+            created_at: 0,
+        };
+        referral_account.accrue(&TokenType::Sol, reward).unwrap();
+        referral_account.accrue(&TokenType::Sol, reward).unwrap();
+        referral_account.accrue(&TokenType::Usdc, 3).unwrap();
+        assert_eq!(referral_account.claim(&TokenType::Sol).unwrap(), 2 * reward);
+        assert_eq!(referral_account.sol_accrued, 0);
+        assert_eq!(referral_account.sol_claimed, 2 * reward);
+        // Nothing left to claim; USDC is untouched
+        assert_eq!(referral_account.claim(&TokenType::Sol).unwrap(), 0);
+        assert_eq!(referral_account.usdc_accrued, 3);
+        assert_eq!(
+            referral_account.accrue(&TokenType::Usdc, u64::MAX),
+            Err(VaultError::MathOverflow.into())
+        );
+    }
+
+    #[test]
+    fn test_referral_claim() {
+        let rent = Rent::default();
+        let (owner, system) = (crate::ID, system_program::ID);
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut house_data = vec![0u8; 8 + std::mem::size_of::<HouseVault>()];
+        let mut referral_data = vec![0u8; 8 + std::mem::size_of::<ReferralAccount>()];
+        let mut referrer_data = Vec::new();
+        let house_floor = rent.minimum_balance(house_data.len());
+        let referral_floor = rent.minimum_balance(referral_data.len());
+        let (mut house_lamports, mut referral_lamports, mut referrer_lamports) =
+            (house_floor + 1_000_000, referral_floor, 0u64);
+        let house = AccountInfo::new(
+            &keys[0],
+            false,
+            true,
+            &mut house_lamports,
+            &mut house_data,
+            &owner,
+            false,
+            0,
+        );
+        let referral_custody = AccountInfo::new(
+            &keys[1],
+            false,
+            true,
+            &mut referral_lamports,
+            &mut referral_data,
+            &owner,
+            false,
+            0,
+        );
+        let referrer = AccountInfo::new(
+            &keys[2],
+            true,
+            true,
+            &mut referrer_lamports,
+            &mut referrer_data,
+            &system,
+            false,
+            0,
+        );
+        let mut referral_account = ReferralAccount {
+            referrer: keys[2],
+            referred_users: 1,
+            sol_accrued: 0,
+            usdc_accrued: 0,
+            sol_claimed: 0,
+            usdc_claimed: 0,
+            created_at: 0,
+        };
+
+        // Half of a 2.5% fee on a 1_000_000 win, paid by the house in lamports
+        let reward = referral_reward(winnings_fee(1_000_000, 250).unwrap(), 5_000).unwrap();
+        for token_type in [TokenType::Sol, TokenType::Usdc] {
+            accrue_referral_reward(
+                &mut referral_account,
+                &referral_custody,
+                &house,
+                &rent,
+                &token_type,
+                reward,
+            )
+            .unwrap();
+        }
+        assert_eq!(referral_custody.lamports(), referral_floor + 12_500);
+        assert_eq!(house.lamports(), house_floor + 987_500);
+
+        // A SOL claim pays the accrued reward out of those lamports, as
+        // claim_referral_rewards does; USDC is left for the custody account
+        let amount = referral_account.claim(&TokenType::Sol).unwrap();
+        transfer_sol_from_custody(&referral_custody, &referrer, &rent, 0, amount).unwrap();
+        assert_eq!(referrer.lamports(), 12_500);
+        assert_eq!(referral_custody.lamports(), referral_floor);
+        assert_eq!(referral_account.usdc_accrued, 12_500);
+
+        // A reward the house can't pay fails the settlement
+        assert_eq!(
+            accrue_referral_reward(
+                &mut referral_account,
+                &referral_custody,
+                &house,
+                &rent,
+                &TokenType::Sol,
+                987_501,
+            )
+            .unwrap_err(),
+            VaultError::InsufficientCustody.into()
+        );
+    }
+
+    #[test]
+    fn test_pnl_inclusion() {
+        let (alice, bob, carol) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let leaves = [
+            pnl_leaf(&alice, 500),
+            pnl_leaf(&bob, -200),
+            pnl_leaf(&carol, 0),
+        ];
+        let node = |l: &[u8; 32], r: &[u8; 32]| poseidon_hash(&[l, r]).unwrap();
+        // Leaves past the players' are zero, and so is every subtree of them
+        let mut empty = vec![[0u8; 32]];
+        for height in 0..BALANCE_TREE_DEPTH {
+            empty.push(node(&empty[height], &empty[height]));
+        }
+        let left = node(&leaves[0], &leaves[1]);
+        let right = node(&leaves[2], &empty[0]);
+        let root = (2..BALANCE_TREE_DEPTH).fold(node(&left, &right), |root, height| {
+            node(&root, &empty[height])
+        });
+        let path = |first: BalanceProofStep, second: BalanceProofStep| {
+            let mut proof = vec![first, second];
+            proof.extend((2..BALANCE_TREE_DEPTH).map(|height| BalanceProofStep {
+                sibling: empty[height],
+                sibling_is_left: false,
+            }));
+            proof
+        };
+
+        let bob_proof = path(
+            BalanceProofStep {
+                sibling: leaves[0],
+                sibling_is_left: true,
+            },
+            BalanceProofStep {
+                sibling: right,
+                sibling_is_left: false,
+            },
+        );
+        assert_eq!(pnl_root_from_proof(&bob, -200, &bob_proof), Some(root));
+        assert_ne!(pnl_root_from_proof(&bob, 200, &bob_proof), Some(root));
+        assert_ne!(pnl_root_from_proof(&alice, -200, &bob_proof), Some(root));
+        let carol_proof = path(
+            BalanceProofStep {
+                sibling: empty[0],
+                sibling_is_left: false,
+            },
+            BalanceProofStep {
+                sibling: left,
+                sibling_is_left: true,
+            },
+        );
+        assert_eq!(pnl_root_from_proof(&carol, 0, &carol_proof), Some(root));
+
+        // Siblings must be field elements
+        let mut oversized = carol_proof;
+        oversized[0].sibling = [0xff; 32];
+        assert_eq!(pnl_root_from_proof(&carol, 0, &oversized), None);
+    }
+
+    #[test]
+    fn test_forced_exit() {
+        let mut user_vault = UserVault {
+            owner: Pubkey::new_unique(),
+            sol_balance: 100,
+            usdc_balance: 0,
+            bet_count: 0,
+            total_winnings: 0,
+            total_losses: 0,
+            created_at: 0,
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            last_settled_slot: 500,
+            exit_requested_slot: None,
+            deposit_nonce: 0,
+            locked_balance: LockedBalance::default(),
+            open_escrows: 0,
+            referrer: None,
+            sol_wagered: 0,
+            usdc_wagered: 0,
+            sol_won: 0,
+            usdc_won: 0,
+            current_streak: 0,
+            last_bet_at: 0,
+            exits: 0,
+        };
+        assert_eq!(
+            user_vault.check_exit(10_000_000),
+            Err(VaultError::NoExitPending.into())
+        );
+
+        // Finalizable only once the delay has passed without a settlement
+        user_vault.exit_requested_slot = Some(1_000);
+        let ready = 1_000 + FORCED_EXIT_DELAY_SLOTS;
+        assert_eq!(
+            user_vault.check_exit(ready - 1),
+            Err(VaultError::ExitTimelocked.into())
+        );
+        assert!(user_vault.check_exit(ready).is_ok());
+
+        // A settlement after the request shows the sequencer is live
+        user_vault.record_settlement(1_000);
+        assert!(user_vault.exit_superseded());
+        assert_eq!(
+            user_vault.check_exit(ready),
+            Err(VaultError::ExitSuperseded.into())
+        );
+    }
+
+    #[test]
+    fn test_emergency_withdrawal() {
+        let mut user_vault = UserVault {
+            owner: Pubkey::new_unique(),
+            sol_balance: 100,
+            usdc_balance: 5,
+            bet_count: 0,
+            total_winnings: 0,
+            total_losses: 0,
+            created_at: 0,
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            last_settled_slot: 0,
+            exit_requested_slot: None,
+            deposit_nonce: 0,
+            locked_balance: LockedBalance::default(),
+            open_escrows: 0,
+            referrer: None,
+            sol_wagered: 0,
+            usdc_wagered: 0,
+            sol_won: 0,
+            usdc_won: 0,
+            current_streak: 0,
+            last_bet_at: 0,
+            exits: 0,
+        };
+        // Funds backing in-flight bets can't be released
+        user_vault.lock(&TokenType::Sol, 30).unwrap();
+        assert_eq!(
+            user_vault.debit_available(&TokenType::Sol, 71),
+            Err(VaultError::InsufficientBalance.into())
+        );
+        user_vault.debit_available(&TokenType::Sol, 70).unwrap();
+        assert_eq!(user_vault.sol_balance, 30);
+        assert_eq!(user_vault.available(&TokenType::Sol), 0);
+        user_vault.debit_available(&TokenType::Usdc, 5).unwrap();
+        assert_eq!(user_vault.usdc_balance, 0);
+
+        // Account space comes from size_of, which must still cover the borsh encoding
+        let request = EmergencyWithdrawal {
+            user: user_vault.owner,
+            token_type: TokenType::Usdc,
+            amount: 5,
+            destination: Pubkey::new_unique(),
+            requested_slot: 100,
+            requested_at: 0,
+        };
+        let mut data = Vec::new();
+        request.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= 8 + std::mem::size_of::<EmergencyWithdrawal>());
+    }
+
+    #[test]
+    fn test_close_user_vault() {
+        let mut user_vault = UserVault {
+            owner: Pubkey::new_unique(),
+            sol_balance: 100,
+            usdc_balance: 0,
+            bet_count: 3,
+            total_winnings: 0,
+            total_losses: 0,
+            created_at: 0,
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            last_settled_slot: 0,
+            exit_requested_slot: None,
+            deposit_nonce: 0,
+            locked_balance: LockedBalance::default(),
+            open_escrows: 1,
+            referrer: None,
+            sol_wagered: 0,
+            usdc_wagered: 0,
+            sol_won: 0,
+            usdc_won: 0,
+            current_streak: 0,
+            last_bet_at: 0,
+            exits: 0,
+        };
+        assert_eq!(
+            user_vault.check_closable(),
+            Err(VaultError::VaultNotEmpty.into())
+        );
+
+        // Emptied, but a bet is still escrowed and another locked
+        user_vault.sol_balance = 0;
+        assert_eq!(
+            user_vault.check_closable(),
+            Err(VaultError::BetsPending.into())
+        );
+        user_vault.open_escrows = 0;
+        user_vault.locked_balance.usdc = 10;
+        assert_eq!(
+            user_vault.check_closable(),
+            Err(VaultError::BetsPending.into())
+        );
+
+        user_vault.locked_balance = LockedBalance::default();
+        assert!(user_vault.check_closable().is_ok());
+    }
+
+    #[test]
+    fn test_withdrawal_window_slides() {
+        let start = 1_000_000;
+        let mut window = WithdrawalWindow::default();
+        window
+            .record(600, 1_000, start, VaultError::UserDailyLimitExceeded)
+            .unwrap();
+        assert_eq!(
+            window
+                .record(401, 1_000, start + 60, VaultError::UserDailyLimitExceeded)
+                .unwrap_err(),
+            VaultError::UserDailyLimitExceeded.into()
+        );
+
+        // Halfway into the next window, half of the earlier withdrawal still counts
+        let halfway = start + WITHDRAWAL_WINDOW + WITHDRAWAL_WINDOW / 2;
+        assert_eq!(window.used(halfway), 300);
+        window
+            .record(700, 1_000, halfway, VaultError::UserDailyLimitExceeded)
+            .unwrap();
+        assert!(window
+            .record(1, 1_000, halfway, VaultError::UserDailyLimitExceeded)
+            .is_err());
+
+        // Two windows later everything has aged out; a zero limit never blocks
+        let later = halfway + 2 * WITHDRAWAL_WINDOW;
+        assert_eq!(window.used(later), 0);
+        window
+            .record(u64::MAX / 2, 0, later, VaultError::UserDailyLimitExceeded)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_house_bankroll() {
+        let mut house = HouseVault {
+            sol_bankroll: 0,
+            usdc_bankroll: 0,
+            sol_principal: 0,
+            usdc_principal: 0,
+            sol_pnl: 0,
+            usdc_pnl: 0,
+        };
+        house.fund(TokenType::Sol, 1_000).unwrap();
+
+        // Payouts come out of the bankroll, and can't exceed it
+        house.settle(TokenType::Sol, -400).unwrap();
+        house.settle(TokenType::Sol, 900).unwrap();
+        assert_eq!(house.sol_bankroll, 1_500);
+        assert_eq!(house.sol_pnl, 500);
+        assert_eq!(
+            house.settle(TokenType::Usdc, -1).unwrap_err(),
+            VaultError::InsufficientBankroll.into()
+        );
+
+        // Only the bankroll above the funded principal can be withdrawn
+        assert_eq!(house.profit(&TokenType::Sol), 500);
+        assert_eq!(
+            house.withdraw_profit(TokenType::Sol, 501).unwrap_err(),
+            VaultError::InsufficientHouseProfit.into()
+        );
+        house.withdraw_profit(TokenType::Sol, 500).unwrap();
+        assert_eq!(house.sol_bankroll, 1_000);
+        assert_eq!(house.profit(&TokenType::Sol), 0);
+        assert_eq!(house_delta(250, 0).unwrap(), -250);
+        assert!(house_delta(i64::MIN, 0).is_err());
+    }
+
+    #[test]
+    fn test_sol_custody() {
+        let rent = Rent::default();
+        let (custody_key, user_key, owner) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), crate::ID);
+        let mut custody_data = vec![0u8; 8 + std::mem::size_of::<VaultState>()];
+        let floor = rent.minimum_balance(custody_data.len());
+        let (mut custody_lamports, mut user_lamports) = (floor, 10_000u64);
+        let custody = AccountInfo::new(
+            &custody_key,
+            false,
+            true,
+            &mut custody_lamports,
+            &mut custody_data,
+            &owner,
+            false,
+            0,
+        );
+        let mut user_data = Vec::new();
+        let system = system_program::ID;
+        let user = AccountInfo::new(
+            &user_key,
+            true,
+            true,
+            &mut user_lamports,
+            &mut user_data,
+            &system,
+            false,
+            0,
+        );
+
+        // Deposits move the user's lamports into custody, above its rent...
+        user.sub_lamports(4_000).unwrap();
+        custody.add_lamports(4_000).unwrap();
+
+        // ...and a withdrawal pays them back out
+        transfer_sol_from_custody(&custody, &user, &rent, 0, 1_500).unwrap();
+        assert_eq!(user.lamports(), 7_500);
+        assert_eq!(custody.lamports(), floor + 2_500);
+
+        // A forced exit pays out the rest, but never the custody account's rent
+        assert_eq!(
+            transfer_sol_from_custody(&custody, &user, &rent, 0, 2_501).unwrap_err(),
+            VaultError::InsufficientCustody.into()
+        );
+        transfer_sol_from_custody(&custody, &user, &rent, 0, 2_500).unwrap();
+        assert_eq!(user.lamports(), 10_000);
+        assert_eq!(custody.lamports(), floor);
+        assert!(transfer_sol_from_custody(&custody, &user, &rent, 0, 1).is_err());
+
+        // Lamports held back, like the house's funded principal, stay put as well
+        custody.add_lamports(1_000).unwrap();
+        user.sub_lamports(1_000).unwrap();
+        assert_eq!(
+            transfer_sol_from_custody(&custody, &user, &rent, 600, 401).unwrap_err(),
+            VaultError::InsufficientCustody.into()
+        );
+        transfer_sol_from_custody(&custody, &user, &rent, 600, 400).unwrap();
+        assert_eq!(custody.lamports(), floor + 600);
+    }
+
+    #[test]
+    fn test_settlement_custody() {
+        let rent = Rent::default();
+        let owner = crate::ID;
+        let system = system_program::ID;
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let mut custody_data = vec![0u8; 8 + std::mem::size_of::<VaultState>()];
+        let mut house_data = vec![0u8; 8 + std::mem::size_of::<HouseVault>()];
+        let custody_floor = rent.minimum_balance(custody_data.len());
+        let house_floor = rent.minimum_balance(house_data.len());
+        let (mut custody_lamports, mut house_lamports) = (custody_floor, house_floor);
+        let (mut alice_lamports, mut bob_lamports) = (0u64, 0u64);
+        let (mut alice_data, mut bob_data) = (Vec::new(), Vec::new());
+        let custody = AccountInfo::new(
+            &keys[0],
+            false,
+            true,
+            &mut custody_lamports,
+            &mut custody_data,
+            &owner,
+            false,
+            0,
+        );
+        let house = AccountInfo::new(
+            &keys[1],
+            false,
+            true,
+            &mut house_lamports,
+            &mut house_data,
+            &owner,
+            false,
+            0,
+        );
+        let alice = AccountInfo::new(
+            &keys[2],
+            true,
+            true,
+            &mut alice_lamports,
+            &mut alice_data,
+            &system,
+            false,
+            0,
+        );
+        let bob = AccountInfo::new(
+            &keys[3],
+            true,
+            true,
+            &mut bob_lamports,
+            &mut bob_data,
+            &system,
+            false,
+            0,
+        );
+        let mut state = VaultState {
+            authority: Pubkey::new_unique(),
+            total_users: 2,
+            total_sol_deposited: 0,
+            total_usdc_deposited: 0,
+            pause_flags: 0,
+            paused_until_slot: None,
+            pending_authority: None,
+            usdc_mint: Pubkey::new_unique(),
+            usdc_decimals: 6,
+            sol_escrowed: 0,
+            usdc_escrowed: 0,
+            sol_limits: WithdrawalLimits::default(),
+            usdc_limits: WithdrawalLimits::default(),
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            protocol_fee_bps: 0,
+            referral_share_bps: 0,
+            balances_root: [0; 32],
+            balances_root_batch_id: 0,
+        };
+        let mut bankroll = HouseVault {
+            sol_bankroll: 0,
+            usdc_bankroll: 0,
+            sol_principal: 0,
+            usdc_principal: 0,
+            sol_pnl: 0,
+            usdc_pnl: 0,
+        };
+        // Alice deposits 1_000 and Bob 2_000; the house is funded with 5_000
+        custody.add_lamports(3_000).unwrap();
+        state.total_sol_deposited = 3_000;
+        house.add_lamports(5_000).unwrap();
+        bankroll.fund(TokenType::Sol, 5_000).unwrap();
+
+        // Alice wins 800, which the house pays into custody; Bob loses 1_200 to it
+        for user_delta in [800, -1_200] {
+            bankroll
+                .settle(TokenType::Sol, house_delta(user_delta, 0).unwrap())
+                .unwrap();
+            settle_custody(
+                &mut state,
+                &custody,
+                &house,
+                &rent,
+                &TokenType::Sol,
+                user_delta,
+            )
+            .unwrap();
+        }
+        assert_eq!(state.total_sol_deposited, 2_600);
+        assert_eq!(custody.lamports(), custody_floor + 2_600);
+        assert_eq!(house.lamports(), house_floor + 5_400);
+
+        // Alice withdraws more than she deposited, and Bob can still take what he has
+        for (wallet, amount) in [(&alice, 1_800), (&bob, 800)] {
+            // As withdraw_sol pays out
+            state.total_sol_deposited = state.total_sol_deposited.checked_sub(amount).unwrap();
+            transfer_sol_from_custody(&custody, wallet, &rent, 0, amount).unwrap();
+        }
+        assert_eq!((alice.lamports(), bob.lamports()), (1_800, 800));
+        assert_eq!(state.total_sol_deposited, 0);
+        assert_eq!(custody.lamports(), custody_floor);
+
+        // A loss can't take lamports users don't hold in custody, nor a win more than
+        // the house holds
+        assert_eq!(
+            settle_custody(&mut state, &custody, &house, &rent, &TokenType::Sol, -1).unwrap_err(),
+            VaultError::MathUnderflow.into()
+        );
+        assert_eq!(
+            settle_custody(&mut state, &custody, &house, &rent, &TokenType::Sol, 5_401)
+                .unwrap_err(),
+            VaultError::InsufficientCustody.into()
+        );
+
+        // The house's 400 of profit arrived, and can be withdrawn as the principal stays
+        bankroll.withdraw_profit(TokenType::Sol, 400).unwrap();
+        transfer_sol_from_custody(&house, &bob, &rent, bankroll.sol_bankroll, 400).unwrap();
+        assert_eq!(house.lamports(), house_floor + 5_000);
+        assert!(transfer_sol_from_custody(&house, &bob, &rent, bankroll.sol_bankroll, 1).is_err());
+    }
+
+    #[test]
+    fn test_fee_custody() {
+        let rent = Rent::default();
+        let (owner, system) = (crate::ID, system_program::ID);
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut house_data = vec![0u8; 8 + std::mem::size_of::<HouseVault>()];
+        let mut fee_data = vec![0u8; 8 + std::mem::size_of::<FeeVault>()];
+        let mut destination_data = Vec::new();
+        let house_floor = rent.minimum_balance(house_data.len());
+        let fee_floor = rent.minimum_balance(fee_data.len());
+        let (mut house_lamports, mut fee_lamports, mut destination_lamports) =
+            (house_floor + 1_000, fee_floor, 0u64);
+        let house = AccountInfo::new(
+            &keys[0],
+            false,
+            true,
+            &mut house_lamports,
+            &mut house_data,
+            &owner,
+            false,
+            0,
+        );
+        let fee_custody = AccountInfo::new(
+            &keys[1],
+            false,
+            true,
+            &mut fee_lamports,
+            &mut fee_data,
+            &owner,
+            false,
+            0,
+        );
+        let destination = AccountInfo::new(
+            &keys[2],
+            false,
+            true,
+            &mut destination_lamports,
+            &mut destination_data,
+            &system,
+            false,
+            0,
+        );
+        let mut fee_vault = FeeVault {
+            sol_accrued: 0,
+            usdc_accrued: 0,
+            sol_collected: 0,
+            usdc_collected: 0,
+        };
+
+        // The house pays the fee on a win into the fee vault, in lamports for SOL
+        let fee = winnings_fee(1_000, 250).unwrap();
+        accrue_protocol_fees(&mut fee_vault, &fee_custody, &house, &rent, fee, 7).unwrap();
+        assert_eq!((fee_vault.sol_accrued, fee_vault.usdc_accrued), (25, 7));
+        assert_eq!(fee_custody.lamports(), fee_floor + 25);
+        assert_eq!(house.lamports(), house_floor + 975);
+
+        // So collecting the accrued SOL fees pays out lamports that arrived
+        fee_vault.collect(&TokenType::Sol, 25).unwrap();
+        transfer_sol_from_custody(&fee_custody, &destination, &rent, 0, 25).unwrap();
+        assert_eq!(destination.lamports(), 25);
+        assert_eq!(fee_custody.lamports(), fee_floor);
+
+        // A fee the house can't pay fails the settlement
+        assert_eq!(
+            accrue_protocol_fees(&mut fee_vault, &fee_custody, &house, &rent, 976, 0).unwrap_err(),
+            VaultError::InsufficientCustody.into()
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_allowlist() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let mut allowlist = WithdrawalAllowlist {
+            owner: Pubkey::new_unique(),
+            destinations: [Pubkey::default(); MAX_WITHDRAWAL_DESTINATIONS],
+            destination_count: 0,
+            pending_change: None,
+        };
+
+        allowlist.add(first).unwrap();
+        assert!(allowlist.contains(&first));
+        assert!(!allowlist.contains(&second));
+        assert!(!allowlist.contains(&Pubkey::default()));
+        assert!(allowlist.add(first).is_err());
+
+        // The last destination cannot be removed
+        assert!(allowlist.remove(&first).is_err());
+
+        allowlist.add(second).unwrap();
+        allowlist.remove(&first).unwrap();
+        assert!(!allowlist.contains(&first));
+        assert!(allowlist.contains(&second));
+
+        for _ in 1..MAX_WITHDRAWAL_DESTINATIONS {
+            allowlist.add(Pubkey::new_unique()).unwrap();
+        }
+        assert!(allowlist.add(Pubkey::new_unique()).is_err());
+    }
+
+    #[test]
+    fn test_authority_transfer() {
+        let authority = Pubkey::new_unique();
+        let nominee = Pubkey::new_unique();
+        let mut state = VaultState {
+            authority,
+            total_users: 0,
+            total_sol_deposited: 0,
+            total_usdc_deposited: 0,
+            pause_flags: 0,
+            paused_until_slot: None,
+            pending_authority: Some(nominee),
+            usdc_mint: Pubkey::new_unique(),
+            usdc_decimals: 6,
+            sol_escrowed: 0,
+            usdc_escrowed: 0,
+            sol_limits: WithdrawalLimits::default(),
+            usdc_limits: WithdrawalLimits::default(),
+            sol_withdrawals: WithdrawalWindow::default(),
+            usdc_withdrawals: WithdrawalWindow::default(),
+            protocol_fee_bps: 0,
+            referral_share_bps: 0,
+            balances_root: [0; 32],
+            balances_root_batch_id: 0,
+        };
+
+        assert_eq!(
+            state.accept_authority(&authority).unwrap_err(),
+            VaultError::NotPendingAuthority.into()
+        );
+        assert_eq!(state.accept_authority(&nominee).unwrap(), authority);
+        assert_eq!(state.authority, nominee);
+        assert_eq!(state.pending_authority, None);
+        assert!(state.accept_authority(&nominee).is_err());
+
+        // Account space comes from size_of, which must still cover the borsh encoding
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= 8 + std::mem::size_of::<VaultState>());
+
+        // Escrowed stakes show up as exposure in the health view
+        *state.escrowed_mut(&TokenType::Usdc) += 250;
+        let health = state.health(1_000, 0);
+        assert_eq!(health.version, TREASURY_HEALTH_VERSION);
+        assert_eq!(health.usdc_escrowed, 250);
+        assert_eq!(health.sol_escrowed, 0);
+        assert_eq!(health.usdc_in_custody, 1_000);
+        assert!(!health.is_paused);
+
+        // Withdrawals halted during an incident, deposits and settlement keep going
+        state.pause_flags = PAUSE_WITHDRAWALS;
+        assert_eq!(
+            state.require_unpaused(PAUSE_WITHDRAWALS, 10).unwrap_err(),
+            VaultError::VaultPaused.into()
+        );
+        assert!(state.require_unpaused(PAUSE_DEPOSITS, 10).is_ok());
+        assert!(state.require_unpaused(PAUSE_SETTLEMENTS, 10).is_ok());
+        assert!(state.health(1_000, 10).is_paused);
+
+        // The pause lifts by itself at the expiry slot
+        state.pause_flags = PAUSE_ALL;
+        state.paused_until_slot = Some(100);
+        assert!([PAUSE_DEPOSITS, PAUSE_WITHDRAWALS, PAUSE_SETTLEMENTS]
+            .into_iter()
+            .all(|operation| state.paused(operation, 99)));
+        assert!(!state.paused(PAUSE_ALL, 100));
+        assert!(state.require_unpaused(PAUSE_WITHDRAWALS, 100).is_ok());
+        let health = state.health(1_000, 100);
+        assert!(!health.is_paused);
+        assert_eq!(health.pause_flags, PAUSE_ALL);
+        assert_eq!(health.paused_until_slot, Some(100));
+    }
+
+    #[test]
+    fn test_bet_escrow_space() {
+        let escrow = BetEscrow {
+            owner: Pubkey::new_unique(),
+            nonce: u64::MAX,
+            amount: u64::MAX,
+            token_type: TokenType::Usdc,
+            guess: true,
+            created_at: i64::MAX,
+        };
+        let mut data = Vec::new();
+        escrow.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), BetEscrow::SPACE);
+    }
+
+    #[test]
+    fn test_bet_lock() {
+        let lock = BetLock {
+            owner: Pubkey::new_unique(),
+            bet_id: u64::MAX,
+            amount: u64::MAX,
+            token_type: TokenType::Usdc,
+            locked_at: 1_000,
+            exit_epoch: u64::MAX,
+        };
+        let mut data = Vec::new();
+        lock.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), BetLock::SPACE);
+        assert_eq!(lock.reclaimable_at().unwrap(), 1_000 + BET_LOCK_TIMEOUT);
+
+        let lock = BetLock {
+            locked_at: i64::MAX,
+            ..lock
+        };
+        assert_eq!(lock.reclaimable_at(), Err(VaultError::MathOverflow.into()));
+    }
+
+    #[test]
+    fn test_supported_token() {
+        let mut token = SupportedToken {
+            mint: Pubkey::new_unique(),
+            decimals: u8::MAX,
+            enabled: true,
+            min_bet: 1_000,
+            max_bet: 5_000,
+            total_deposited: u64::MAX,
+            limits: WithdrawalLimits::default(),
+            withdrawals: WithdrawalWindow::default(),
+        };
+        assert!(token.accepts_bet(1_000) && token.accepts_bet(5_000));
+        assert!(!token.accepts_bet(999) && !token.accepts_bet(5_001));
+        token.enabled = false;
+        assert!(!token.accepts_bet(1_000));
+
+        assert!(SupportedToken::check_bet_limits(1, 1).is_ok());
+        assert_eq!(
+            SupportedToken::check_bet_limits(0, 1),
+            Err(VaultError::InvalidBetLimits.into())
+        );
+        assert_eq!(
+            SupportedToken::check_bet_limits(2, 1),
+            Err(VaultError::InvalidBetLimits.into())
+        );
+
+        let mut data = Vec::new();
+        token.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), SupportedToken::SPACE);
+
+        let mut balance = TokenBalance {
+            owner: Pubkey::new_unique(),
+            mint: token.mint,
+            balance: u64::MAX,
+            withdrawals: WithdrawalWindow::default(),
+        };
+        let mut data = Vec::new();
+        balance.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), TokenBalance::SPACE);
+
+        // Withdrawals count against the mint's own caps
+        token.limits = WithdrawalLimits {
+            max_per_transaction: 500,
+            max_per_user_daily: 800,
+            max_global_daily: 1_000,
+        };
+        let now = 1_000_000;
+        let limits = token.limits.clone();
+        assert_eq!(
+            limits.enforce(&mut balance.withdrawals, &mut token.withdrawals, 501, now),
+            Err(VaultError::TransactionLimitExceeded.into())
+        );
+        limits
+            .enforce(&mut balance.withdrawals, &mut token.withdrawals, 500, now)
+            .unwrap();
+        assert_eq!(
+            limits.enforce(&mut balance.withdrawals, &mut token.withdrawals, 301, now),
+            Err(VaultError::UserDailyLimitExceeded.into())
+        );
+        let mut other = WithdrawalWindow::default();
+        limits
+            .enforce(&mut other, &mut token.withdrawals, 500, now)
+            .unwrap();
+        assert_eq!(
+            limits.enforce(
+                &mut WithdrawalWindow::default(),
+                &mut token.withdrawals,
+                1,
+                now
+            ),
+            Err(VaultError::GlobalDailyLimitExceeded.into())
+        );
+    }
+
+    #[test]
+    fn test_transfer_fee() {
+        use spl_token_2022::extension::{
+            transfer_fee::TransferFee, ExtensionType, StateWithExtensionsMut,
+        };
+        use spl_token_2022::state::Mint as Token2022Mint;
+
+        let mint_data = |fees: Option<(TransferFee, TransferFee)>| {
+            let extensions: &[ExtensionType] = match fees {
+                Some(_) => &[ExtensionType::TransferFeeConfig],
+                None => &[],
+            };
+            let mut data =
+                vec![
+                    0;
+                    ExtensionType::try_calculate_account_len::<Token2022Mint>(extensions).unwrap()
+                ];
+            let mut mint =
+                StateWithExtensionsMut::<Token2022Mint>::unpack_uninitialized(&mut data).unwrap();
+            if let Some((older, newer)) = fees {
+                let config = mint.init_extension::<TransferFeeConfig>(true).unwrap();
+                config.older_transfer_fee = older;
+                config.newer_transfer_fee = newer;
+            }
+            mint.base = Token2022Mint {
+                decimals: 6,
+                is_initialized: true,
+                ..Default::default()
+            };
+            mint.pack_base();
+            mint.init_account_type().unwrap();
+            data
+        };
+        let fee = |epoch: u64, basis_points: u16| TransferFee {
+            epoch: epoch.into(),
+            maximum_fee: 1_000.into(),
+            transfer_fee_basis_points: basis_points.into(),
+        };
+
+        let token_2022 = spl_token_2022::ID;
+        let data = mint_data(Some((fee(0, 100), fee(10, 50))));
+        assert_eq!(transfer_fee(&token_2022, &data, 5, 10_000).unwrap(), 100);
+        // Fees round up and are capped at the maximum
+        assert_eq!(transfer_fee(&token_2022, &data, 5, 150).unwrap(), 2);
+        assert_eq!(
+            transfer_fee(&token_2022, &data, 5, 1_000_000).unwrap(),
+            1_000
+        );
+        // A scheduled fee change takes over from its epoch
+        assert_eq!(transfer_fee(&token_2022, &data, 10, 10_000).unwrap(), 50);
+
+        // Token-2022 mints without the extension and SPL Token mints take nothing
+        assert_eq!(
+            transfer_fee(&token_2022, &mint_data(None), 5, 10_000).unwrap(),
+            0
+        );
+        assert_eq!(
+            transfer_fee(&anchor_spl::token::ID, &data, 5, 10_000).unwrap(),
+            0
+        );
+    }
+
     #[test]
     fn test_token_type_serialization() {
         let sol_type = TokenType::Sol;
@@ -515,4 +6087,36 @@ mod tests {
         assert_ne!(sol_type, usdc_type);
         assert_eq!(sol_type, TokenType::Sol);
     }
+
+    #[test]
+    fn test_balance_updates_match_fixtures() {
+        // The verifier builds these calls by hand; they must decode to the same deltas
+        let deltas: Vec<UserDelta> = fixtures::BATCH
+            .bets
+            .iter()
+            .map(|bet| UserDelta {
+                user: Pubkey::new_from_array(bet.user),
+                sol_delta: bet.sol_delta(),
+                usdc_delta: 0,
+                is_win: bet.is_win(),
+                bet_amount: bet.bet_amount,
+            })
+            .collect();
+        let first = &deltas[0];
+        let update = instruction::UpdateBalances {
+            sol_delta: first.sol_delta,
+            usdc_delta: first.usdc_delta,
+            is_win: first.is_win,
+            bet_amount: first.bet_amount,
+        };
+        assert_eq!(
+            anchor_lang::InstructionData::data(&update),
+            fixtures::hex(fixtures::UPDATE_BALANCES)
+        );
+        let batch = instruction::UpdateBalancesBatch { deltas };
+        assert_eq!(
+            anchor_lang::InstructionData::data(&batch),
+            fixtures::hex(fixtures::UPDATE_BALANCES_BATCH)
+        );
+    }
 }
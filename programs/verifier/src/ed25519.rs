@@ -0,0 +1,135 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+
+use crate::VerifierError;
+
+/// Layout of ed25519 program instruction data: a signature count and padding byte, then
+/// per signature seven u16 offsets (signature, public key and message, each with the
+/// index of the instruction holding it)
+const OFFSETS_START: usize = 2;
+const OFFSETS_SIZE: usize = 14;
+const THIS_INSTRUCTION: u16 = u16::MAX; // Instruction index meaning the ed25519 one itself
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn slice(data: &[u8], offset: u16, len: usize) -> Option<&[u8]> {
+    data.get(offset as usize..offset as usize + len)
+}
+
+/// Whether ed25519 program instruction data checks exactly one signature, `signature`
+/// by `signer` over `message`, all carried in the instruction itself
+pub fn checks_signature(
+    data: &[u8],
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> bool {
+    let offsets: Option<Vec<u16>> = (0..OFFSETS_SIZE / 2)
+        .map(|i| read_u16(data, OFFSETS_START + 2 * i))
+        .collect();
+    let Some(
+        [signature_offset, signature_ix, pubkey_offset, pubkey_ix, message_offset, message_len, message_ix],
+    ) = offsets.as_deref()
+    else {
+        return false;
+    };
+    data.first() == Some(&1)
+        && [*signature_ix, *pubkey_ix, *message_ix] == [THIS_INSTRUCTION; 3]
+        && *message_len as usize == message.len()
+        && slice(data, *signature_offset, 64) == Some(signature.as_slice())
+        && slice(data, *pubkey_offset, 32) == Some(signer.as_ref())
+        && slice(data, *message_offset, message.len()) == Some(message)
+}
+
+/// Require the instruction just before this one to be an ed25519 program check of
+/// `signature` by `signer` over `message`. The runtime verifies that instruction before
+/// the transaction runs, so finding it means the signature is valid.
+pub fn require_signed(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions_sysvar)?;
+    let previous = current
+        .checked_sub(1)
+        .ok_or(VerifierError::InvalidBatchSignature)?;
+    let instruction = load_instruction_at_checked(previous as usize, instructions_sysvar)?;
+    require!(
+        instruction.program_id == ed25519_program::ID
+            && checks_signature(&instruction.data, signer, message, signature),
+        VerifierError::InvalidBatchSignature
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Instruction data as the sequencer builds it: public key, signature, message
+    pub(crate) fn instruction_data(
+        signer: &Pubkey,
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Vec<u8> {
+        let start = (OFFSETS_START + OFFSETS_SIZE) as u16;
+        let mut data = vec![1, 0];
+        for offset in [
+            start + 32,
+            THIS_INSTRUCTION,
+            start,
+            THIS_INSTRUCTION,
+            start + 96,
+            message.len() as u16,
+            THIS_INSTRUCTION,
+        ] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(signature);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_checks_signature() {
+        let signer = Pubkey::new_unique();
+        let signature = [7; 64];
+        let message = [3; 32];
+        let data = instruction_data(&signer, &signature, &message);
+        assert!(checks_signature(&data, &signer, &message, &signature));
+
+        // Another signer, signature or message
+        assert!(!checks_signature(
+            &data,
+            &Pubkey::new_unique(),
+            &message,
+            &signature
+        ));
+        assert!(!checks_signature(&data, &signer, &message, &[8; 64]));
+        assert!(!checks_signature(&data, &signer, &[4; 32], &signature));
+
+        // Parts taken from another instruction, or a truncated instruction
+        let mut elsewhere = data.clone();
+        elsewhere[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert!(!checks_signature(&elsewhere, &signer, &message, &signature));
+        assert!(!checks_signature(
+            &data[..data.len() - 1],
+            &signer,
+            &message,
+            &signature
+        ));
+        assert!(!checks_signature(
+            &data[..10],
+            &signer,
+            &message,
+            &signature
+        ));
+    }
+}
@@ -1,7 +1,30 @@
 use anchor_lang::prelude::*;
-
-// For now, create stub implementations that return successful results
-// In production, these would use actual Solana BN254 syscalls
+use anchor_lang::solana_program::alt_bn128::prelude::{
+    alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing,
+};
+use anchor_lang::solana_program::log::sol_log_compute_units;
+
+// Curve arithmetic runs through Solana's alt_bn128 syscalls, which take points in the
+// EIP-196/197 encoding: big-endian field elements, G2 coordinates as (imaginary, real).
+// G1Point/G2Point bytes and public inputs are expected in that encoding.
+//
+// Compute budget: the pairing of four pairs costs ~85k CU and each public input adds a
+// scalar multiplication and an addition (~4.2k CU), so settlement's three inputs fit a
+// single instruction but keys near MAX_PUBLIC_INPUTS do not. Those accumulate vk_x over
+// several instructions (accumulate_vk_x) and verify with verify_groth16_proof_prepared.
+// Each phase logs the remaining compute units so program logs show where the budget goes.
+
+/// BN254 base field modulus p, big-endian
+const FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// BN254 scalar field modulus r, big-endian; public inputs must be below it
+const SCALAR_FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
 
 /// BN254 curve point representation
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -115,22 +138,68 @@ impl Groth16Proof {
 }
 
 impl Groth16VerifyingKey {
+    /// Validate that all key points are on the curve
+    pub fn validate_curve_points(&self) -> std::result::Result<(), VerificationError> {
+        if !is_valid_g1_point(&self.alpha) || !self.ic.iter().all(is_valid_g1_point) {
+            return Err(VerificationError::InvalidCurvePoint);
+        }
+        if ![&self.beta, &self.gamma, &self.delta]
+            .into_iter()
+            .all(is_valid_g2_point)
+        {
+            return Err(VerificationError::InvalidCurvePoint);
+        }
+        Ok(())
+    }
+
+    /// Check that exactly one input is given per IC point after IC[0] and that each
+    /// input is a canonical scalar field element
+    pub fn validate_public_inputs(
+        &self,
+        public_inputs: &[[u8; 32]],
+    ) -> std::result::Result<(), VerificationError> {
+        if public_inputs.len() + 1 != self.ic.len() {
+            return Err(VerificationError::InvalidPublicInputs);
+        }
+        if public_inputs
+            .iter()
+            .any(|input| *input >= SCALAR_FIELD_MODULUS)
+        {
+            return Err(VerificationError::InvalidPublicInputs);
+        }
+        Ok(())
+    }
+
     /// Compute the verification key point for given public inputs
     /// vk_x = IC[0] + sum(IC[i+1] * public_input[i]) for i in 0..public_inputs.len()
     pub fn compute_vk_x(
         &self,
         public_inputs: &[[u8; 32]],
     ) -> std::result::Result<G1Point, VerificationError> {
-        if public_inputs.len() + 1 > self.ic.len() {
+        self.accumulate_vk_x(self.ic[0], 0, public_inputs)
+    }
+
+    /// Add the terms of public inputs `offset..offset + public_inputs.len()` to a partial
+    /// vk_x, so a long input list can be folded in over several instructions
+    pub fn accumulate_vk_x(
+        &self,
+        partial: G1Point,
+        offset: usize,
+        public_inputs: &[[u8; 32]],
+    ) -> std::result::Result<G1Point, VerificationError> {
+        if offset + public_inputs.len() + 1 > self.ic.len()
+            || public_inputs
+                .iter()
+                .any(|input| *input >= SCALAR_FIELD_MODULUS)
+        {
             return Err(VerificationError::InvalidPublicInputs);
         }
 
-        // Start with IC[0]
-        let mut vk_x = self.ic[0];
+        let mut vk_x = partial;
 
         // Add IC[i+1] * public_input[i] for each public input
         for (i, public_input) in public_inputs.iter().enumerate() {
-            let ic_point = self.ic[i + 1];
+            let ic_point = self.ic[offset + i + 1];
 
             // Scalar multiplication: IC[i+1] * public_input[i]
             let scaled_point = scalar_mult_g1(&ic_point, public_input)?;
@@ -150,21 +219,34 @@ pub fn verify_groth16_proof(
     public_inputs: &[[u8; 32]],
 ) -> std::result::Result<bool, VerificationError> {
     msg!("Starting Groth16 proof verification");
+    log_compute_units("start");
 
-    // Step 1: Validate proof points are on curve
-    proof.validate_curve_points()?;
-    msg!("✓ Proof points validated");
-
-    // Step 2: Compute vk_x = IC[0] + sum(IC[i+1] * public_input[i])
+    // Step 1: Compute vk_x = IC[0] + sum(IC[i+1] * public_input[i])
     let vk_x = vk.compute_vk_x(public_inputs)?;
     msg!("✓ VK_x computed");
+    log_compute_units("vk_x");
+
+    verify_groth16_proof_prepared(proof, vk, &vk_x)
+}
+
+/// Groth16 verification against a precomputed vk_x (see accumulate_vk_x); the caller
+/// is responsible for vk_x matching the public inputs
+pub fn verify_groth16_proof_prepared(
+    proof: &Groth16Proof,
+    vk: &Groth16VerifyingKey,
+    vk_x: &G1Point,
+) -> std::result::Result<bool, VerificationError> {
+    // Step 2: Validate proof points are on curve
+    proof.validate_curve_points()?;
+    msg!("✓ Proof points validated");
+    log_compute_units("curve checks");
 
     // Step 3: Prepare pairing inputs
     // We need to verify: e(A, B) = e(alpha, beta) * e(vk_x, gamma) * e(C, delta)
     // Rearranged as: e(A, B) * e(-alpha, beta) * e(-vk_x, gamma) * e(-C, delta) = 1
 
     let neg_alpha = negate_g1(&vk.alpha)?;
-    let neg_vk_x = negate_g1(&vk_x)?;
+    let neg_vk_x = negate_g1(vk_x)?;
     let neg_c = negate_g1(&proof.c)?;
 
     // Prepare pairing input: [(G1, G2), (G1, G2), (G1, G2), (G1, G2)]
@@ -196,45 +278,89 @@ pub fn verify_groth16_proof(
 
     msg!("✓ Pairing inputs prepared, length: {}", pairing_input.len());
 
-    // Step 4: Perform pairing check using Solana syscall
-    // For now, return successful verification (stub implementation)
-    // In production, this would use the actual Solana alt_bn128_pairing syscall
-    msg!("✓ Pairing completed successfully (stub implementation)");
-    Ok(true)
+    // Step 4: Pairing check via the alt_bn128_pairing syscall; it also rejects
+    // G2 points that are not on the curve or not in the prime-order subgroup
+    let result = alt_bn128_pairing(&pairing_input).map_err(|_| VerificationError::PairingFailed)?;
+    let is_valid = result.len() == 32 && result[..31].iter().all(|&b| b == 0) && result[31] == 1;
+    log_compute_units("pairing");
+
+    if is_valid {
+        msg!("✓ Pairing check passed");
+    } else {
+        msg!("✗ Pairing check failed");
+    }
+    Ok(is_valid)
+}
+
+/// Log the compute units left at a verification phase
+fn log_compute_units(phase: &str) {
+    msg!("Groth16 phase '{}' done, remaining compute units:", phase);
+    sol_log_compute_units();
+}
+
+pub fn g1_to_bytes(point: &G1Point) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&point.x);
+    bytes[32..].copy_from_slice(&point.y);
+    bytes
+}
+
+pub fn g1_from_bytes(bytes: &[u8]) -> std::result::Result<G1Point, VerificationError> {
+    if bytes.len() != 64 {
+        return Err(VerificationError::InvalidCurvePoint);
+    }
+    Ok(G1Point {
+        x: bytes[..32].try_into().unwrap(),
+        y: bytes[32..].try_into().unwrap(),
+    })
 }
 
-/// Helper function: Scalar multiplication on G1
+/// Helper function: Scalar multiplication on G1 (alt_bn128_multiplication syscall)
 fn scalar_mult_g1(
     point: &G1Point,
     scalar: &[u8; 32],
 ) -> std::result::Result<G1Point, VerificationError> {
-    // Stub implementation - returns the same point for now
-    // In production, this would use the actual Solana alt_bn128_multiplication syscall
-    Ok(*point)
+    let mut input = [0u8; 96];
+    input[..64].copy_from_slice(&g1_to_bytes(point));
+    input[64..].copy_from_slice(scalar);
+
+    let output =
+        alt_bn128_multiplication(&input).map_err(|_| VerificationError::ScalarMultFailed)?;
+    g1_from_bytes(&output)
 }
 
-/// Helper function: Point addition on G1
+/// Helper function: Point addition on G1 (alt_bn128_addition syscall)
 fn point_add_g1(p1: &G1Point, p2: &G1Point) -> std::result::Result<G1Point, VerificationError> {
-    // Stub implementation - returns the first point for now
-    // In production, this would use the actual Solana alt_bn128_addition syscall
-    Ok(*p1)
+    let mut input = [0u8; 128];
+    input[..64].copy_from_slice(&g1_to_bytes(p1));
+    input[64..].copy_from_slice(&g1_to_bytes(p2));
+
+    let output = alt_bn128_addition(&input).map_err(|_| VerificationError::InvalidCurvePoint)?;
+    g1_from_bytes(&output)
 }
 
-/// Helper function: Negate G1 point (flip y coordinate)
+/// Helper function: Negate G1 point: (x, p - y), with the point at infinity its own negation
 fn negate_g1(point: &G1Point) -> std::result::Result<G1Point, VerificationError> {
-    // For BN254, negation is: (x, -y mod p) where p is the field prime
-    // Since we're using Solana syscalls, we'll use the identity point addition with zero
-    // to get proper negation
-    let _zero_point = G1Point {
-        x: [0; 32],
-        y: [0; 32],
-    };
-
-    // This is a placeholder - proper negation needs field arithmetic
-    // For production, this should be implemented correctly
-    let mut neg_y = point.y;
-    // Flip the sign bit (simplified for demonstration)
-    neg_y[31] ^= 0x80;
+    if point.y.iter().all(|&b| b == 0) {
+        return Ok(*point);
+    }
+    if point.y >= FIELD_MODULUS {
+        return Err(VerificationError::InvalidCurvePoint);
+    }
+
+    // Big-endian p - y
+    let mut neg_y = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut diff = FIELD_MODULUS[i] as i16 - point.y[i] as i16 - borrow;
+        borrow = if diff < 0 {
+            diff += 256;
+            1
+        } else {
+            0
+        };
+        neg_y[i] = diff as u8;
+    }
 
     Ok(G1Point {
         x: point.x,
@@ -244,29 +370,31 @@ fn negate_g1(point: &G1Point) -> std::result::Result<G1Point, VerificationError>
 
 /// Helper function: Validate G1 point is on curve
 fn is_valid_g1_point(point: &G1Point) -> bool {
-    // Check if point is on BN254 curve: y^2 = x^3 + 3
-    // This is a simplified check - proper validation requires field arithmetic
-
-    // Check for point at infinity (both coordinates zero)
+    // The point at infinity is never a valid proof element
     let is_zero = point.x.iter().all(|&b| b == 0) && point.y.iter().all(|&b| b == 0);
 
-    // For now, accept non-zero points (full validation would require BN254 field ops)
+    // The addition syscall rejects coordinates that are out of range or off the curve
     !is_zero
+        && point_add_g1(
+            point,
+            &G1Point {
+                x: [0; 32],
+                y: [0; 32],
+            },
+        )
+        .is_ok()
 }
 
 /// Helper function: Validate G2 point is on curve
 fn is_valid_g2_point(point: &G2Point) -> bool {
-    // Check if point is on BN254 G2 curve
-    // This is a simplified check - proper validation requires extension field arithmetic
-
-    // Check for point at infinity
+    // There is no G2 arithmetic syscall; full curve and subgroup checks happen in the
+    // pairing syscall, which fails for invalid points. Reject infinity up front.
     let is_zero = point.x.iter().all(|&b| b == 0) && point.y.iter().all(|&b| b == 0);
-
-    // For now, accept non-zero points
     !is_zero
 }
 
 /// Convert field element from little-endian to big-endian (BN254 syscalls expect big-endian)
+#[allow(dead_code)]
 pub fn field_element_to_be(le_bytes: &[u8; 32]) -> [u8; 32] {
     let mut be_bytes = [0u8; 32];
     for i in 0..32 {
@@ -276,6 +404,7 @@ pub fn field_element_to_be(le_bytes: &[u8; 32]) -> [u8; 32] {
 }
 
 /// Convert field element from big-endian to little-endian
+#[allow(dead_code)]
 pub fn field_element_to_le(be_bytes: &[u8; 32]) -> [u8; 32] {
     let mut le_bytes = [0u8; 32];
     for i in 0..32 {
@@ -331,13 +460,149 @@ mod tests {
             y: [0; 32],
         };
         assert!(!is_valid_g1_point(&zero_point)); // Point at infinity
+        assert!(is_valid_g1_point(&g1_generator()));
 
-        let mut non_zero_point = G1Point {
+        // Non-zero but not on y^2 = x^3 + 3
+        let mut off_curve = g1_generator();
+        off_curve.y[31] = 3;
+        assert!(!is_valid_g1_point(&off_curve));
+    }
+
+    /// BN254 G1 generator (1, 2)
+    fn g1_generator() -> G1Point {
+        let mut point = G1Point {
             x: [0; 32],
             y: [0; 32],
         };
-        non_zero_point.x[0] = 1;
-        assert!(is_valid_g1_point(&non_zero_point)); // Non-zero point
+        point.x[31] = 1;
+        point.y[31] = 2;
+        point
+    }
+
+    /// BN254 G2 generator, EIP-197 encoding
+    fn g2_generator() -> G2Point {
+        let hex = |s: &str| -> Vec<u8> {
+            (0..s.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+                .collect()
+        };
+        let x = [
+            hex("198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2"),
+            hex("1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed"),
+        ]
+        .concat();
+        let y = [
+            hex("090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b"),
+            hex("12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"),
+        ]
+        .concat();
+        G2Point {
+            x: x.try_into().unwrap(),
+            y: y.try_into().unwrap(),
+        }
+    }
+
+    fn scalar(value: u8) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        bytes[31] = value;
+        bytes
+    }
+
+    fn times(value: u8) -> G1Point {
+        scalar_mult_g1(&g1_generator(), &scalar(value)).unwrap()
+    }
+
+    #[test]
+    fn test_g1_arithmetic() {
+        let g = g1_generator();
+        assert_eq!(point_add_g1(&g, &g).unwrap(), times(2));
+        assert_eq!(point_add_g1(&times(2), &g).unwrap(), times(3));
+
+        // P + (-P) is the point at infinity
+        let sum = point_add_g1(&g, &negate_g1(&g).unwrap()).unwrap();
+        assert_eq!(sum.x, [0; 32]);
+        assert_eq!(sum.y, [0; 32]);
+    }
+
+    #[test]
+    fn test_pairing_accepts_valid_and_rejects_invalid_proofs() {
+        // Synthetic key with every element a multiple of the generators, chosen so that
+        // e(A, B) = e(alpha, beta) * e(vk_x, gamma) * e(C, delta) holds for input 1:
+        // vk_x = IC[0] + 1 * IC[1] = 2G, so A must be (1 + 2 + 1)G = 4G
+        let vk = Groth16VerifyingKey {
+            alpha: g1_generator(),
+            beta: g2_generator(),
+            gamma: g2_generator(),
+            delta: g2_generator(),
+            ic: vec![g1_generator(), g1_generator()],
+        };
+        let proof = Groth16Proof {
+            a: times(4),
+            b: g2_generator(),
+            c: g1_generator(),
+        };
+        assert_eq!(verify_groth16_proof(&proof, &vk, &[scalar(1)]), Ok(true));
+
+        // Wrong public input
+        assert_eq!(verify_groth16_proof(&proof, &vk, &[scalar(2)]), Ok(false));
+
+        // Tampered proof element
+        let tampered = Groth16Proof {
+            a: times(5),
+            ..proof.clone()
+        };
+        assert_eq!(
+            verify_groth16_proof(&tampered, &vk, &[scalar(1)]),
+            Ok(false)
+        );
+
+        // Point that is not on the curve
+        let mut off_curve = proof.clone();
+        off_curve.c.y[0] ^= 0x01;
+        assert_eq!(
+            verify_groth16_proof(&off_curve, &vk, &[scalar(1)]),
+            Err(VerificationError::InvalidCurvePoint)
+        );
+    }
+
+    #[test]
+    fn test_prepared_vk_x_verification() {
+        // vk_x = IC[0] + 1 * IC[1] + 2 * IC[2] = 4G, so A must be (1 + 4 + 1)G = 6G
+        let vk = Groth16VerifyingKey {
+            alpha: g1_generator(),
+            beta: g2_generator(),
+            gamma: g2_generator(),
+            delta: g2_generator(),
+            ic: vec![g1_generator(), g1_generator(), times(1)],
+        };
+        let proof = Groth16Proof {
+            a: times(6),
+            b: g2_generator(),
+            c: g1_generator(),
+        };
+        let inputs = [scalar(1), scalar(2)];
+        assert_eq!(verify_groth16_proof(&proof, &vk, &inputs), Ok(true));
+
+        // Folding the inputs one instruction at a time gives the same vk_x
+        let partial = vk.accumulate_vk_x(vk.ic[0], 0, &inputs[..1]).unwrap();
+        let vk_x = vk.accumulate_vk_x(partial, 1, &inputs[1..]).unwrap();
+        assert_eq!(vk_x, vk.compute_vk_x(&inputs).unwrap());
+        assert_eq!(verify_groth16_proof_prepared(&proof, &vk, &vk_x), Ok(true));
+        assert_eq!(
+            verify_groth16_proof_prepared(&proof, &vk, &partial),
+            Ok(false)
+        );
+
+        // Past the last IC point, or not a canonical scalar
+        assert_eq!(
+            vk.accumulate_vk_x(vk_x, 2, &[scalar(1)]),
+            Err(VerificationError::InvalidPublicInputs)
+        );
+        assert_eq!(
+            vk.accumulate_vk_x(vk.ic[0], 0, &[SCALAR_FIELD_MODULUS]),
+            Err(VerificationError::InvalidPublicInputs)
+        );
     }
 
     #[test]
@@ -384,4 +649,35 @@ mod tests {
             Err(VerificationError::InvalidPublicInputs)
         ));
     }
+
+    #[test]
+    fn test_validate_public_inputs() {
+        let vk = Groth16VerifyingKey {
+            alpha: g1_generator(),
+            beta: g2_generator(),
+            gamma: g2_generator(),
+            delta: g2_generator(),
+            ic: vec![g1_generator(), g1_generator(), g1_generator()],
+        };
+        assert!(vk.validate_public_inputs(&[scalar(1), scalar(2)]).is_ok());
+
+        // One input per IC point after IC[0]
+        assert_eq!(
+            vk.validate_public_inputs(&[scalar(1)]),
+            Err(VerificationError::InvalidPublicInputs)
+        );
+        assert_eq!(
+            vk.validate_public_inputs(&[scalar(1), scalar(2), scalar(3)]),
+            Err(VerificationError::InvalidPublicInputs)
+        );
+
+        // Inputs must be reduced modulo r
+        let mut largest = SCALAR_FIELD_MODULUS;
+        largest[31] -= 1;
+        assert!(vk.validate_public_inputs(&[scalar(1), largest]).is_ok());
+        assert_eq!(
+            vk.validate_public_inputs(&[scalar(1), SCALAR_FIELD_MODULUS]),
+            Err(VerificationError::InvalidPublicInputs)
+        );
+    }
 }
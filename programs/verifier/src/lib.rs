@@ -1,12 +1,30 @@
+// Option::is_none_or, usize::div_ceil and usize::is_multiple_of are newer than the SBF
+// toolchain this builds with
+#![allow(
+    clippy::unnecessary_map_or,
+    clippy::manual_div_ceil,
+    clippy::manual_is_multiple_of
+)]
+
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::hash;
+use anchor_lang::solana_program::poseidon::{self, Endianness, Parameters};
 use anchor_lang::solana_program::sysvar::instructions;
 
+mod ed25519;
 mod groth16;
+pub mod optimistic;
+pub mod vault_cpi;
 mod verifying_key;
 
-use groth16::{verify_groth16_proof, Groth16Proof};
-use verifying_key::get_embedded_verifying_key;
+use groth16::{
+    g1_from_bytes, g1_to_bytes, verify_groth16_proof, verify_groth16_proof_prepared, G1Point,
+    Groth16Proof, Groth16VerifyingKey,
+};
+use optimistic::{find_fraud, BatchLeaf, FraudKind, LeafProof};
+use verifying_key::{
+    parse_verifying_key, IC_POINT_SIZE, VERIFYING_KEY_BYTES, VERIFYING_KEY_POINTS_SIZE,
+};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
@@ -21,7 +39,14 @@ pub mod verifier {
         verifier_state.vault_program = ctx.accounts.vault_program.key();
         verifier_state.total_batches_processed = 0;
         verifier_state.total_bets_settled = 0;
-        verifier_state.is_paused = false;
+        verifier_state.pause_flags = 0;
+        verifier_state.last_settled_batch_id = 0;
+        verifier_state.last_sequencer_nonce = 0;
+        verifier_state.state_root = EMPTY_STATE_ROOT;
+        verifier_state.pending_authority = None;
+        verifier_state.admin_timelock = DEFAULT_ADMIN_TIMELOCK;
+        verifier_state.next_admin_action_id = 0;
+        verifier_state.co_signer = None;
 
         msg!(
             "Verifier initialized with authority: {}",
@@ -30,153 +55,413 @@ pub mod verifier {
         Ok(())
     }
 
-    /// Verify and settle a batch of bets (Phase 2: stub implementation with events only)
+    /// Verify and settle a batch of bets, committing its state root to the vault
     pub fn verify_and_settle(
         ctx: Context<VerifyAndSettle>,
+        circuit_id: u8, // Registered circuit the proof is for
         batch_data: BatchSettlementData,
         proof: Vec<u8>, // Placeholder proof for Phase 2
     ) -> Result<()> {
+        let accounts = ctx.accounts;
+        require_registered(&accounts.sequencer_registration)?;
+        accounts
+            .verifier_state
+            .require_co_signed(accounts.co_signer.as_ref().map(|signer| signer.key()))?;
+        // The batch contents must carry the registered sequencer's signature
+        ed25519::require_signed(
+            &accounts.instructions_sysvar,
+            &accounts.sequencer.key(),
+            &compute_batch_hash(&batch_data),
+            &batch_data.sequencer_signature,
+        )?;
+        msg!("Settling with circuit {}", circuit_id);
+        settle_batch(
+            &mut accounts.verifier_state,
+            &accounts.verifying_key,
+            &accounts.game_config,
+            &mut accounts.batch_record,
+            &mut accounts.verifier_stats,
+            accounts.sequencer.key(),
+            &batch_data,
+            &proof,
+        )?;
+        // Users check their settled P&L against the vault's copy of the root, so it moves
+        // with every batch that settles
+        vault_cpi::commit_balances_root(
+            &accounts.vault_program,
+            &accounts.vault_state,
+            &accounts.vault_caller,
+            ctx.bumps.vault_caller,
+            accounts.batch_record.batch_id,
+            &accounts.batch_record.state_root,
+        )
+    }
+
+    /// Start a batch too large for one transaction. Its bets are staged with
+    /// append_batch_chunk and settled by finalize_and_verify; each sequencer has one
+    /// staging account, so an abandoned batch must be cancelled before the next begins.
+    #[allow(clippy::too_many_arguments)]
+    pub fn begin_batch(
+        ctx: Context<BeginBatch>,
+        batch_id: u64,
+        sequencer_nonce: u64,
+        game_id: u8,
+        prev_state_root: [u8; 32],
+        new_state_root: [u8; 32],
+        vrf_pubkey: Pubkey,
+        vrf_outputs_root: [u8; 32],
+        bet_count: u32,
+        sequencer_signature: [u8; 64], // Over the complete batch's hash; checked at finalize
+    ) -> Result<()> {
+        let verifier_state = &ctx.accounts.verifier_state;
+        verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+        require_registered(&ctx.accounts.sequencer_registration)?;
+        require!(bet_count > 0, VerifierError::EmptyBatch);
         require!(
-            !ctx.accounts.verifier_state.is_paused,
-            VerifierError::VerifierPaused
+            bet_count as usize <= MAX_BATCH_SIZE,
+            VerifierError::BatchTooLarge
         );
-        require!(!batch_data.bets.is_empty(), VerifierError::EmptyBatch);
+        // Fail early; finalize_and_verify checks both again against the state it settles on
+        verifier_state.check_batch_order(batch_id, sequencer_nonce)?;
         require!(
-            batch_data.bets.len() <= MAX_BATCH_SIZE,
-            VerifierError::BatchTooLarge
+            prev_state_root == verifier_state.state_root,
+            VerifierError::StateRootMismatch
         );
-        require!(!proof.is_empty(), VerifierError::EmptyProof);
 
-        let verifier_state = &mut ctx.accounts.verifier_state;
+        ctx.accounts.batch_staging.set_inner(BatchStaging {
+            sequencer: ctx.accounts.sequencer.key(),
+            batch_id,
+            sequencer_nonce,
+            game_id,
+            prev_state_root,
+            new_state_root,
+            vrf_pubkey,
+            vrf_outputs_root,
+            bet_count,
+            bets: Vec::with_capacity(bet_count as usize),
+            sequencer_signature,
+        });
+
+        msg!("Batch {} staging started: {} bets", batch_id, bet_count);
+        Ok(())
+    }
+
+    /// Stage the next chunk of bets of the sequencer's pending batch, in order
+    pub fn append_batch_chunk(
+        ctx: Context<AppendBatchChunk>,
+        offset: u32,
+        bets: Vec<BetSettlement>,
+    ) -> Result<()> {
+        let batch_staging = &mut ctx.accounts.batch_staging;
+        batch_staging.append(offset as usize, bets)?;
 
-        // Phase 3d: Real ZK proof verification using Groth16 and BN254 syscalls
         msg!(
-            "Verifying batch with {} bets using Groth16 proof ({} bytes)",
-            batch_data.bets.len(),
-            proof.len()
+            "Batch {} staged: {}/{} bets",
+            batch_staging.batch_id,
+            batch_staging.bets.len(),
+            batch_staging.bet_count
         );
+        Ok(())
+    }
 
-        // Parse and verify the Groth16 proof
-        let groth16_proof =
-            Groth16Proof::from_bytes(&proof).map_err(|_| VerifierError::InvalidProofFormat)?;
+    /// Verify the proof for the fully staged batch and settle it, closing the staging
+    /// account back to the sequencer
+    pub fn finalize_and_verify(
+        ctx: Context<FinalizeAndVerify>,
+        circuit_id: u8,
+        proof: Vec<u8>,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        require_registered(&accounts.sequencer_registration)?;
+        accounts
+            .verifier_state
+            .require_co_signed(accounts.co_signer.as_ref().map(|signer| signer.key()))?;
+        let batch_data = accounts.batch_staging.to_batch_data()?;
+        // Staged like verify_and_settle's batch: the assembled contents must carry the
+        // registered sequencer's signature
+        ed25519::require_signed(
+            &accounts.instructions_sysvar,
+            &accounts.sequencer.key(),
+            &compute_batch_hash(&batch_data),
+            &batch_data.sequencer_signature,
+        )?;
+        msg!("Settling with circuit {}", circuit_id);
+        settle_batch(
+            &mut accounts.verifier_state,
+            &accounts.verifying_key,
+            &accounts.game_config,
+            &mut accounts.batch_record,
+            &mut accounts.verifier_stats,
+            accounts.sequencer.key(),
+            &batch_data,
+            &proof,
+        )?;
+        // Committed like verify_and_settle's root
+        vault_cpi::commit_balances_root(
+            &accounts.vault_program,
+            &accounts.vault_state,
+            &accounts.vault_caller,
+            ctx.bumps.vault_caller,
+            accounts.batch_record.batch_id,
+            &accounts.batch_record.state_root,
+        )
+    }
 
-        // Load embedded verifying key
-        let verifying_key =
-            get_embedded_verifying_key().map_err(|_| VerifierError::InvalidVerifyingKey)?;
+    /// Discard the sequencer's staged batch, returning its rent
+    pub fn cancel_batch(ctx: Context<CancelBatch>) -> Result<()> {
+        msg!(
+            "Batch {} staging cancelled",
+            ctx.accounts.batch_staging.batch_id
+        );
+        Ok(())
+    }
 
-        // Prepare public inputs for verification
-        // For our circuit, we expect one public input: the batch hash
-        let batch_hash = compute_batch_hash(&batch_data);
-        let public_inputs = vec![batch_hash];
+    /// Fallback for when the prover is down: record a batch without a proof. The state
+    /// root advances at once so later optimistic batches can chain on it, but the batch
+    /// only counts as settled once finalize_optimistic_batch runs after the challenge
+    /// window; proven batches wait for that too.
+    pub fn submit_batch_optimistic(
+        ctx: Context<SubmitBatchOptimistic>,
+        claim: OptimisticBatchClaim,
+        sequencer_signature: [u8; 64], // Over claim.hash()
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        require_registered(&accounts.sequencer_registration)?;
+        ed25519::require_signed(
+            &accounts.instructions_sysvar,
+            &accounts.sequencer.key(),
+            &claim.hash()?,
+            &sequencer_signature,
+        )?;
+        let verifier_state = &mut accounts.verifier_state;
+        verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+        verifier_state.require_co_signed(accounts.co_signer.as_ref().map(|signer| signer.key()))?;
+        require!(claim.bet_count > 0, VerifierError::EmptyBatch);
+        require!(
+            claim.bet_count as usize <= MAX_BATCH_SIZE,
+            VerifierError::BatchTooLarge
+        );
+        verifier_state.check_batch_order(claim.batch_id, claim.sequencer_nonce)?;
+        require!(
+            claim.prev_state_root == verifier_state.state_root,
+            VerifierError::StateRootMismatch
+        );
 
-        // Perform Groth16 verification
-        let verification_result =
-            verify_groth16_proof(&groth16_proof, &verifying_key, &public_inputs);
+        let clock = Clock::get()?;
+        let challenge_deadline = clock
+            .unix_timestamp
+            .checked_add(OPTIMISTIC_CHALLENGE_WINDOW)
+            .ok_or(VerifierError::MathOverflow)?;
+        let sequencer = accounts.sequencer.key();
+        let parent_batch_id = verifier_state.advance_head(claim.batch_id, true)?;
+        accounts.batch_record.set_inner(BatchRecord {
+            batch_id: claim.batch_id,
+            sequencer,
+            proof_hash: [0; 32], // No proof
+            state_root: claim.new_state_root,
+            bet_count: claim.bet_count,
+            house_delta: claim.house_delta,
+            slot: clock.slot,
+            status: BatchRecordStatus::Optimistic,
+            bets_root: claim.bets_root,
+            synced: [0; SYNCED_BITMAP_LEN],
+            parent_batch_id,
+        });
+        accounts.optimistic_batch.set_inner(OptimisticBatch {
+            sequencer,
+            claim: claim.clone(),
+            payout_multiplier_bps: accounts.game_config.payout_multiplier_bps,
+            house_edge_bps: accounts.game_config.house_edge_bps,
+            challenge_deadline,
+        });
 
-        match verification_result {
-            Ok(true) => {
-                msg!("✓ Groth16 proof verification successful");
-            }
-            Ok(false) => {
-                msg!("✗ Groth16 proof verification failed: invalid proof");
-                return Err(VerifierError::InvalidProof.into());
-            }
-            Err(e) => {
-                msg!("✗ Groth16 proof verification error: {:?}", e);
-                return Err(VerifierError::ProofVerificationFailed.into());
-            }
-        }
+        verifier_state.record_batch(claim.batch_id, claim.sequencer_nonce);
+        verifier_state.state_root = claim.new_state_root;
 
-        // Validate batch arithmetic (basic checks for Phase 2)
-        let mut total_house_delta: i64 = 0;
-        for bet_settlement in &batch_data.bets {
-            require!(
-                bet_settlement.bet_amount > 0,
-                VerifierError::InvalidBetAmount
-            );
+        emit!(OptimisticBatchSubmittedEvent {
+            batch_id: claim.batch_id,
+            sequencer,
+            batch_size: claim.bet_count,
+            house_delta: claim.house_delta,
+            bets_root: claim.bets_root,
+            prev_state_root: claim.prev_state_root,
+            new_state_root: claim.new_state_root,
+            challenge_deadline,
+        });
 
-            // Validate outcome is boolean (0 or 1)
-            require!(
-                bet_settlement.outcome == 0 || bet_settlement.outcome == 1,
-                VerifierError::InvalidOutcome
-            );
+        msg!(
+            "Batch {} submitted optimistically: {} bets, challengeable until {}",
+            claim.batch_id,
+            claim.bet_count,
+            challenge_deadline
+        );
+        Ok(())
+    }
 
-            // Calculate payout based on outcome and bet amount
-            let expected_payout = if bet_settlement.outcome == bet_settlement.user_guess {
-                bet_settlement.bet_amount * 2 // Win: 2x payout
-            } else {
-                0 // Loss: no payout
-            };
+    /// Prove an optimistic batch's accounting wrong with one of its bets (and the bet
+    /// before it, to check the running house delta). Anyone may challenge; a valid
+    /// challenge marks the batch, rolls the state root back to the claim's previous root,
+    /// pauses settlement until resolve_challenge and pays the sequencer's batch account
+    /// rent to the challenger. `parent_record` is the batch's parent's record, if any.
+    pub fn challenge_batch(
+        ctx: Context<ChallengeBatch>,
+        batch_id: u64,
+        challenged: LeafProof,
+        previous: Option<LeafProof>,
+    ) -> Result<()> {
+        let optimistic_batch = &ctx.accounts.optimistic_batch;
+        require!(
+            Clock::get()?.unix_timestamp < optimistic_batch.challenge_deadline,
+            VerifierError::ChallengeWindowClosed
+        );
+        let claim = &optimistic_batch.claim;
+        require!(
+            challenged.verify(claim) && previous.as_ref().map_or(true, |p| p.verify(claim)),
+            VerifierError::InvalidFraudProof
+        );
+        let fraud = find_fraud(
+            &optimistic_batch.game_config(),
+            claim,
+            &challenged.leaf,
+            previous.as_ref().map(|p| &p.leaf),
+        )
+        .ok_or(VerifierError::ChallengeRejected)?;
 
-            require!(
-                bet_settlement.payout == expected_payout,
-                VerifierError::InvalidPayout
-            );
+        let batch_record = &mut ctx.accounts.batch_record;
+        let parent_optimistic =
+            batch_record.parent_optimistic(ctx.accounts.parent_record.as_deref())?;
+        batch_record.status = BatchRecordStatus::Challenged;
+        let now = Clock::get()?.unix_timestamp;
+        let rolled_back =
+            ctx.accounts
+                .verifier_state
+                .roll_back(batch_record, claim, parent_optimistic, now)?;
 
-            // Calculate delta for house (negative when user wins)
-            let house_delta = bet_settlement.bet_amount as i64 - bet_settlement.payout as i64;
-            total_house_delta = total_house_delta
-                .checked_add(house_delta)
-                .ok_or(VerifierError::MathOverflow)?;
-        }
+        emit!(BatchChallengedEvent {
+            batch_id,
+            challenger: ctx.accounts.challenger.key(),
+            fraud,
+            bet_index: challenged.leaf.index,
+            rolled_back,
+            timestamp: now,
+        });
 
-        // Emit settlement event for each bet
-        for bet_settlement in &batch_data.bets {
-            emit!(BetSettlementEvent {
-                bet_id: bet_settlement.bet_id,
-                user: bet_settlement.user,
-                bet_amount: bet_settlement.bet_amount,
-                user_guess: bet_settlement.user_guess,
-                outcome: bet_settlement.outcome,
-                payout: bet_settlement.payout,
-                is_win: bet_settlement.outcome == bet_settlement.user_guess,
-                timestamp: Clock::get()?.unix_timestamp,
-            });
-        }
+        msg!(
+            "Batch {} challenged: {:?} at bet {}; settlement paused",
+            batch_id,
+            fraud,
+            challenged.leaf.index
+        );
+        Ok(())
+    }
 
-        // Emit batch settlement event
-        emit!(BatchSettlementEvent {
-            batch_id: batch_data.batch_id,
-            sequencer: ctx.accounts.sequencer.key(),
-            batch_size: batch_data.bets.len() as u32,
-            house_delta: total_house_delta,
-            proof_hash: hash::hash(&proof).to_bytes(),
-            settlement_timestamp: Clock::get()?.unix_timestamp,
-        });
+    /// Settle an unchallenged optimistic batch once its window has passed and its parent
+    /// has settled, committing its state root to the vault and returning the batch
+    /// account's rent to the sequencer. Permissionless.
+    pub fn finalize_optimistic_batch(
+        ctx: Context<FinalizeOptimisticBatch>,
+        batch_id: u64,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        let verifier_state = &mut accounts.verifier_state;
+        // A challenge anywhere pauses settlement, which holds back every pending batch
+        verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+        let optimistic_batch = &accounts.optimistic_batch;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= optimistic_batch.challenge_deadline,
+            VerifierError::ChallengeWindowOpen
+        );
+
+        accounts
+            .batch_record
+            .require_parent_settled(accounts.parent_record.as_deref())?;
 
-        // Update verifier state
+        let claim = &optimistic_batch.claim;
+        accounts.batch_record.status = BatchRecordStatus::Settled;
+        if verifier_state.head_batch_id == batch_id {
+            verifier_state.head_optimistic = false;
+        }
         verifier_state.total_batches_processed = verifier_state
             .total_batches_processed
             .checked_add(1)
             .ok_or(VerifierError::MathOverflow)?;
-
         verifier_state.total_bets_settled = verifier_state
             .total_bets_settled
-            .checked_add(batch_data.bets.len() as u64)
+            .checked_add(claim.bet_count as u64)
             .ok_or(VerifierError::MathOverflow)?;
 
-        msg!(
-            "Batch {} settled successfully: {} bets, house delta: {}",
-            batch_data.batch_id,
-            batch_data.bets.len(),
-            total_house_delta
-        );
+        emit!(BatchSettlementEvent {
+            batch_id,
+            sequencer: optimistic_batch.sequencer,
+            batch_size: claim.bet_count,
+            house_delta: claim.house_delta,
+            proof_hash: [0; 32],
+            settlement_timestamp: now,
+            prev_state_root: claim.prev_state_root,
+            new_state_root: claim.new_state_root,
+            vrf_pubkey: claim.vrf_pubkey,
+            vrf_outputs_root: claim.vrf_outputs_root,
+        });
 
+        // The vault only sees an optimistic batch's root once it is past challenge
+        vault_cpi::commit_balances_root(
+            &accounts.vault_program,
+            &accounts.vault_state,
+            &accounts.vault_caller,
+            ctx.bumps.vault_caller,
+            batch_id,
+            &accounts.batch_record.state_root,
+        )?;
+
+        msg!("Optimistic batch {} finalized", batch_id);
         Ok(())
     }
 
-    /// Verify a single ZK proof (Phase 3d implementation with real Groth16)
-    pub fn verify_proof(ctx: Context<VerifyProof>, proof: Vec<u8>) -> Result<()> {
-        require!(
-            !ctx.accounts.verifier_state.is_paused,
-            VerifierError::VerifierPaused
+    /// Lift the settlement pause a challenge left (admin only), once every batch it
+    /// discarded is past its challenge window. Settlement resumes from the rolled back
+    /// state root.
+    pub fn resolve_challenge(ctx: Context<ResolveChallenge>) -> Result<()> {
+        let verifier_state = &mut ctx.accounts.verifier_state;
+        let now = Clock::get()?.unix_timestamp;
+        let batch_id = verifier_state.resolve_challenge(now)?;
+
+        emit!(ChallengeResolvedEvent {
+            batch_id,
+            state_root: verifier_state.state_root,
+            authority: ctx.accounts.authority.key(),
+            timestamp: now,
+        });
+
+        msg!(
+            "Challenge of batch {} resolved; settlement resumed",
+            batch_id
         );
+        Ok(())
+    }
+
+    /// Verify a single ZK proof against a registered circuit's active key with
+    /// caller-supplied public inputs (big-endian scalars, one per IC point after IC[0])
+    pub fn verify_proof(
+        ctx: Context<VerifyProof>,
+        circuit_id: u8,
+        proof: Vec<u8>,
+        public_inputs: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        ctx.accounts
+            .verifier_state
+            .require_unpaused(PAUSE_VERIFICATION)?;
         require!(!proof.is_empty(), VerifierError::EmptyProof);
         require!(proof.len() <= MAX_PROOF_SIZE, VerifierError::ProofTooLarge);
+        require!(
+            public_inputs.len() <= MAX_PUBLIC_INPUTS,
+            VerifierError::TooManyPublicInputs
+        );
 
         // Phase 3d: Real Groth16 verification using Solana's BN254 syscalls
         msg!(
-            "Performing Groth16 proof verification: {} bytes",
+            "Performing Groth16 proof verification for circuit {}: {} bytes",
+            circuit_id,
             proof.len()
         );
 
@@ -184,14 +469,14 @@ pub mod verifier {
         let groth16_proof =
             Groth16Proof::from_bytes(&proof).map_err(|_| VerifierError::InvalidProofFormat)?;
 
-        // Load embedded verifying key
-        let verifying_key =
-            get_embedded_verifying_key().map_err(|_| VerifierError::InvalidVerifyingKey)?;
+        // Load the circuit's active verifying key
+        let verifying_key = parse_verifying_key(&ctx.accounts.verifying_key.active)
+            .map_err(|_| VerifierError::InvalidVerifyingKey)?;
 
-        // For standalone proof verification, we need public inputs
-        // This would typically be provided as additional parameters
-        // For now, use empty public inputs (circuit dependent)
-        let public_inputs: Vec<[u8; 32]> = vec![];
+        verifying_key
+            .validate_public_inputs(&public_inputs)
+            .map_err(|_| VerifierError::InvalidPublicInputs)?;
+        msg!("✓ {} public inputs validated", public_inputs.len());
 
         // Perform Groth16 verification
         let is_valid = match verify_groth16_proof(&groth16_proof, &verifying_key, &public_inputs) {
@@ -211,6 +496,7 @@ pub mod verifier {
 
         emit!(ProofVerificationEvent {
             proof_hash: hash::hash(&proof).to_bytes(),
+            circuit_id,
             verifier: ctx.accounts.verifier_state.key(),
             is_valid,
             timestamp: Clock::get()?.unix_timestamp,
@@ -223,169 +509,2847 @@ pub mod verifier {
         Ok(())
     }
 
-    /// Pause/unpause verifier operations (admin only)
-    pub fn set_verifier_pause_state(
-        ctx: Context<SetVerifierPauseState>,
-        is_paused: bool,
+    /// Start a verify_proof_prepared call against a registered circuit's active key:
+    /// public inputs of keys too large to fold into vk_x within one instruction's compute
+    /// budget are added with prepare_public_inputs. The key's hash is kept, so every
+    /// stage uses this same key. Each signer has one accumulator, so an abandoned one
+    /// must be cancelled first.
+    pub fn begin_prepared_inputs(
+        ctx: Context<BeginPreparedInputs>,
+        circuit_id: u8,
+        input_count: u32,
     ) -> Result<()> {
-        let verifier_state = &mut ctx.accounts.verifier_state;
-        verifier_state.is_paused = is_paused;
+        ctx.accounts
+            .verifier_state
+            .require_unpaused(PAUSE_VERIFICATION)?;
+        let active = &ctx.accounts.verifying_key.active;
+        let verifying_key =
+            parse_verifying_key(active).map_err(|_| VerifierError::InvalidVerifyingKey)?;
+        require!(
+            input_count as usize + 1 == verifying_key.ic.len(),
+            VerifierError::InvalidPublicInputs
+        );
+
+        ctx.accounts.prepared_inputs.set_inner(PreparedInputs {
+            owner: ctx.accounts.signer.key(),
+            key_hash: hash::hash(active).to_bytes(),
+            input_count,
+            next_input: 0,
+            inputs_hash: [0; 32],
+            vk_x: g1_to_bytes(&verifying_key.ic[0]),
+            circuit_id,
+        });
 
-        msg!("Verifier pause state set to: {}", is_paused);
+        msg!(
+            "Prepared inputs started for circuit {}: {} public inputs",
+            circuit_id,
+            input_count
+        );
         Ok(())
     }
 
-    /// Update vault program address (admin only)
-    pub fn update_vault_program(
-        ctx: Context<UpdateVaultProgram>,
-        new_vault_program: Pubkey,
+    /// Fold the next public inputs (big-endian scalars, in order) into the signer's vk_x
+    pub fn prepare_public_inputs(
+        ctx: Context<PreparePublicInputs>,
+        offset: u32,
+        public_inputs: Vec<[u8; 32]>,
     ) -> Result<()> {
-        let verifier_state = &mut ctx.accounts.verifier_state;
-        verifier_state.vault_program = new_vault_program;
+        let prepared_inputs = &mut ctx.accounts.prepared_inputs;
+        let verifying_key = prepared_inputs.verifying_key(&ctx.accounts.verifying_key)?;
+        prepared_inputs.fold(&verifying_key, offset as usize, &public_inputs)?;
 
-        msg!("Vault program updated to: {}", new_vault_program);
+        msg!(
+            "Prepared inputs: {}/{} public inputs folded",
+            prepared_inputs.next_input,
+            prepared_inputs.input_count
+        );
         Ok(())
     }
-}
 
-// Constants
-const MAX_BATCH_SIZE: usize = 100;
-const MAX_PROOF_SIZE: usize = 2048; // 2KB for Phase 2, will be smaller for Groth16
+    /// Verify a ZK proof against the prepared circuit key using the signer's fully
+    /// prepared vk_x, closing the accumulator back to the signer. Skipping the scalar
+    /// multiplications leaves the whole instruction's budget for the pairing.
+    pub fn verify_proof_prepared(ctx: Context<VerifyProofPrepared>, proof: Vec<u8>) -> Result<()> {
+        ctx.accounts
+            .verifier_state
+            .require_unpaused(PAUSE_VERIFICATION)?;
+        require!(!proof.is_empty(), VerifierError::EmptyProof);
+        require!(proof.len() <= MAX_PROOF_SIZE, VerifierError::ProofTooLarge);
 
-// Account structures
-#[account]
-pub struct VerifierState {
-    pub authority: Pubkey,
-    pub vault_program: Pubkey,
-    pub total_batches_processed: u64,
-    pub total_bets_settled: u64,
-    pub is_paused: bool,
-}
+        let groth16_proof =
+            Groth16Proof::from_bytes(&proof).map_err(|_| VerifierError::InvalidProofFormat)?;
 
-// Data structures
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct BatchSettlementData {
-    pub batch_id: u64,
-    pub sequencer_nonce: u64,
-    pub bets: Vec<BetSettlement>,
-}
+        // The inputs must have been folded against the key verified with
+        let prepared_inputs = &ctx.accounts.prepared_inputs;
+        let verifying_key = prepared_inputs.verifying_key(&ctx.accounts.verifying_key)?;
+        let vk_x = prepared_inputs.vk_x()?;
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct BetSettlement {
-    pub bet_id: u64,
-    pub user: Pubkey,
-    pub bet_amount: u64,
-    pub user_guess: u8, // 0 or 1 for coin flip
-    pub outcome: u8,    // 0 or 1 actual outcome
-    pub payout: u64,    // Calculated payout amount
-}
+        let is_valid = match verify_groth16_proof_prepared(&groth16_proof, &verifying_key, &vk_x) {
+            Ok(valid) => {
+                if valid {
+                    msg!("✓ Groth16 proof verification successful");
+                } else {
+                    msg!("✗ Groth16 proof verification failed: invalid proof");
+                }
+                valid
+            }
+            Err(e) => {
+                msg!("✗ Groth16 proof verification error: {:?}", e);
+                return Err(VerifierError::ProofVerificationFailed.into());
+            }
+        };
 
-// Context structures
-#[derive(Accounts)]
-pub struct InitializeVerifier<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + std::mem::size_of::<VerifierState>(),
-        seeds = [b"verifier_state"],
-        bump
-    )]
-    pub verifier_state: Account<'info, VerifierState>,
-    /// CHECK: The vault program that this verifier will interact with
-    pub vault_program: UncheckedAccount<'info>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+        emit!(PreparedProofVerificationEvent {
+            proof_hash: hash::hash(&proof).to_bytes(),
+            inputs_hash: prepared_inputs.inputs_hash,
+            verifier: ctx.accounts.verifier_state.key(),
+            is_valid,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-#[derive(Accounts)]
-pub struct VerifyAndSettle<'info> {
-    #[account(
-        mut,
-        seeds = [b"verifier_state"],
-        bump
-    )]
-    pub verifier_state: Account<'info, VerifierState>,
-    /// CHECK: The sequencer submitting the batch (signature validation happens in sequencer)
-    pub sequencer: Signer<'info>,
-    /// CHECK: Instructions sysvar for CPI validation
-    #[account(address = instructions::ID)]
-    pub instructions_sysvar: UncheckedAccount<'info>,
-}
+        if !is_valid {
+            return Err(VerifierError::InvalidProof.into());
+        }
 
-#[derive(Accounts)]
-pub struct VerifyProof<'info> {
-    #[account(
-        seeds = [b"verifier_state"],
-        bump
-    )]
-    pub verifier_state: Account<'info, VerifierState>,
-    pub signer: Signer<'info>,
-}
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct SetVerifierPauseState<'info> {
-    #[account(
-        mut,
-        seeds = [b"verifier_state"],
-        bump,
-        has_one = authority
-    )]
-    pub verifier_state: Account<'info, VerifierState>,
-    pub authority: Signer<'info>,
-}
+    /// Discard the signer's prepared inputs, returning their rent
+    pub fn cancel_prepared_inputs(ctx: Context<CancelPreparedInputs>) -> Result<()> {
+        msg!(
+            "Prepared inputs cancelled after {}/{} public inputs",
+            ctx.accounts.prepared_inputs.next_input,
+            ctx.accounts.prepared_inputs.input_count
+        );
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct UpdateVaultProgram<'info> {
-    #[account(
-        mut,
-        seeds = [b"verifier_state"],
-        bump,
-        has_one = authority
-    )]
-    pub verifier_state: Account<'info, VerifierState>,
-    pub authority: Signer<'info>,
-}
+    /// Create the verifying key account, starting from the embedded key (admin only)
+    pub fn initialize_verifying_key(ctx: Context<InitializeVerifyingKey>) -> Result<()> {
+        let verifying_key = &mut ctx.accounts.verifying_key;
+        verifying_key.version = 1;
+        verifying_key.active = VERIFYING_KEY_BYTES.to_vec();
+        verifying_key.staged_len = 0;
+        verifying_key.staged = Vec::new();
 
-// Events
-#[event]
-pub struct BetSettlementEvent {
-    pub bet_id: u64,
-    pub user: Pubkey,
-    pub bet_amount: u64,
-    pub user_guess: u8,
-    pub outcome: u8,
-    pub payout: u64,
-    pub is_win: bool,
-    pub timestamp: i64,
-}
+        msg!(
+            "Verifying key account initialized with embedded key ({} bytes)",
+            VERIFYING_KEY_BYTES.len()
+        );
+        Ok(())
+    }
 
-#[event]
-pub struct BatchSettlementEvent {
-    pub batch_id: u64,
-    pub sequencer: Pubkey,
-    pub batch_size: u32,
-    pub house_delta: i64,
-    pub proof_hash: [u8; 32],
-    pub settlement_timestamp: i64,
-}
+    /// Stage one chunk of a new verifying key for a circuit (admin only). Keys too
+    /// large for a single transaction are uploaded in order; a chunk at offset 0 starts
+    /// over.
+    pub fn upload_verifying_key(
+        ctx: Context<UploadVerifyingKey>,
+        _circuit_id: u8,
+        total_len: u32,
+        offset: u32,
+        chunk: Vec<u8>,
+    ) -> Result<()> {
+        let verifying_key = &mut ctx.accounts.verifying_key;
+        verifying_key.stage_chunk(total_len as usize, offset as usize, &chunk)?;
 
-#[event]
-pub struct ProofVerificationEvent {
-    pub proof_hash: [u8; 32],
-    pub verifier: Pubkey,
-    pub is_valid: bool,
+        msg!(
+            "Staged verifying key: {}/{} bytes",
+            verifying_key.staged.len(),
+            total_len
+        );
+        Ok(())
+    }
+
+    /// Create the circuit registry with the accounting circuit, keyed by the original
+    /// verifying key account (admin only)
+    pub fn initialize_circuit_registry(ctx: Context<InitializeCircuitRegistry>) -> Result<()> {
+        ctx.accounts.circuit_registry.circuits = vec![CircuitEntry {
+            circuit_id: CIRCUIT_ACCOUNTING,
+            verifying_key: ctx.accounts.verifying_key.key(),
+            game_ids: vec![GAME_COINFLIP],
+        }];
+
+        msg!("Circuit registry initialized with the accounting circuit");
+        Ok(())
+    }
+
+    /// Create the settlement stats account, starting its first epoch (admin only)
+    pub fn initialize_verifier_stats(ctx: Context<InitializeVerifierStats>) -> Result<()> {
+        ctx.accounts.verifier_stats.epoch_started_at = Clock::get()?.unix_timestamp;
+        msg!("Verifier stats initialized");
+        Ok(())
+    }
+
+    /// Close the current stats epoch once it has run STATS_EPOCH_LENGTH, keeping its
+    /// totals as the previous epoch and emitting them for dashboards. Permissionless.
+    pub fn snapshot_epoch(ctx: Context<SnapshotEpoch>) -> Result<()> {
+        let verifier_stats = &mut ctx.accounts.verifier_stats;
+        let started_at = verifier_stats.epoch_started_at;
+        let now = Clock::get()?.unix_timestamp;
+        let stats = verifier_stats.roll(now)?;
+
+        emit!(EpochSnapshotEvent {
+            epoch: verifier_stats.epoch - 1,
+            started_at,
+            ended_at: now,
+            stats,
+        });
+
+        msg!(
+            "Stats epoch {} closed: {} bets, house PnL {}",
+            verifier_stats.epoch - 1,
+            stats.bets_settled,
+            stats.house_pnl
+        );
+        Ok(())
+    }
+
+    /// Read-only view of the committed state root and settlement progress, returned as
+    /// RollupHealth. Meant for simulation by wallets and dashboards; it changes nothing.
+    pub fn rollup_health(ctx: Context<RollupHealthView>) -> Result<RollupHealth> {
+        Ok(ctx
+            .accounts
+            .verifier_state
+            .health(&ctx.accounts.verifier_stats))
+    }
+
+    /// Apply one bet of a settled batch to its user's vault balance. The vault only
+    /// accepts update_balances from this program, signed by the vault caller PDA, so
+    /// this is the one way in: the batch's own sequencer, after the batch has settled.
+    /// The bet is opened against the batch's bets root and its delta derived from it,
+    /// and each bet of a batch applies once. For a referred user, the referrer's
+    /// ReferralAccount is the one remaining account.
+    pub fn sync_vault_balance<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SyncVaultBalance<'info>>,
+        batch_id: u64,
+        bet: LeafProof,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        require_registered(&accounts.sequencer_registration)?;
+        accounts.verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+        let delta = accounts.batch_record.settled_bet(&bet)?.user_delta()?;
+        require_keys_eq!(
+            accounts.user_vault.key(),
+            vault_cpi::user_vault_address(&accounts.vault_program.key(), &delta.user),
+            VerifierError::UserVaultMismatch
+        );
+        accounts.batch_record.mark_synced(bet.leaf.index)?;
+
+        vault_cpi::update_balances(
+            &accounts.vault_program,
+            &accounts.user_vault,
+            &accounts.vault_state,
+            &accounts.house_vault,
+            &accounts.fee_vault,
+            &accounts.vault_caller,
+            ctx.bumps.vault_caller,
+            ctx.remaining_accounts,
+            delta.sol_delta,
+            delta.usdc_delta,
+            delta.is_win,
+            delta.bet_amount,
+        )?;
+
+        msg!(
+            "Vault balance synced from batch {} bet {}: SOL {}",
+            batch_id,
+            bet.leaf.index,
+            delta.sol_delta
+        );
+        Ok(())
+    }
+
+    /// Apply many bets of a settled batch to their users' vault balances in one vault
+    /// call. Each bet is opened against the batch's bets root and marked applied before
+    /// the call, as in sync_vault_balance. Remaining accounts are the user vaults, one
+    /// per bet and in order, then the ReferralAccounts of referred users' referrers.
+    pub fn sync_vault_balances<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SyncVaultBalance<'info>>,
+        batch_id: u64,
+        bets: Vec<LeafProof>,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        require_registered(&accounts.sequencer_registration)?;
+        accounts.verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+        require!(
+            bets.len() <= accounts.batch_record.bet_count as usize,
+            VerifierError::BatchTooLarge
+        );
+
+        let deltas = accounts.batch_record.sync_deltas(&bets)?;
+
+        vault_cpi::update_balances_batch(
+            &accounts.vault_program,
+            &accounts.vault_state,
+            &accounts.house_vault,
+            &accounts.fee_vault,
+            &accounts.vault_caller,
+            ctx.bumps.vault_caller,
+            ctx.remaining_accounts,
+            &deltas,
+        )?;
+
+        msg!(
+            "Vault balances synced from batch {}: {} bets",
+            batch_id,
+            deltas.len()
+        );
+        Ok(())
+    }
+
+    /// Release the funds the vault locked for the bet `bet` opens in batch `batch_id`,
+    /// once that batch has settled and synced the bet or a fraud proof has rejected it.
+    /// The vault releases exactly the amount it recorded when locking, and only once.
+    /// Registered sequencers only.
+    pub fn release_vault_lock(
+        ctx: Context<ReleaseVaultLock>,
+        batch_id: u64,
+        bet: LeafProof,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        require_registered(&accounts.sequencer_registration)?;
+        accounts.verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+        let settled = accounts.batch_record.releasable_bet(&bet)?;
+        require_keys_eq!(
+            accounts.user_vault.key(),
+            vault_cpi::user_vault_address(&accounts.vault_program.key(), &settled.user),
+            VerifierError::UserVaultMismatch
+        );
+        let bet_id = settled.bet_id;
+
+        vault_cpi::release_lock(
+            &accounts.vault_program,
+            &accounts.bet_lock,
+            &accounts.user_vault,
+            &accounts.vault_state,
+            &accounts.vault_authority,
+            &accounts.vault_caller,
+            ctx.bumps.vault_caller,
+            bet_id,
+        )?;
+
+        msg!("Vault lock of batch {} bet {} released", batch_id, bet_id);
+        Ok(())
+    }
+
+    /// Register a new circuit for the given games (admin only). Its verifying key
+    /// account starts empty: the key is uploaded with upload_verifying_key and only
+    /// becomes usable through a timelocked RotateVerifyingKey.
+    pub fn register_circuit(
+        ctx: Context<RegisterCircuit>,
+        circuit_id: u8,
+        game_ids: Vec<u8>,
+    ) -> Result<()> {
+        let verifying_key = ctx.accounts.verifying_key.key();
+        ctx.accounts.circuit_registry.register(CircuitEntry {
+            circuit_id,
+            verifying_key,
+            game_ids: game_ids.clone(),
+        })?;
+        ctx.accounts.verifying_key.set_inner(VerifyingKeyAccount {
+            version: 0,
+            staged_len: 0,
+            active: Vec::new(),
+            staged: Vec::new(),
+        });
+
+        emit!(CircuitRegisteredEvent {
+            circuit_id,
+            verifying_key,
+            game_ids,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Circuit {} registered: {}", circuit_id, verifying_key);
+        Ok(())
+    }
+
+    /// Allow a sequencer to submit settlement batches (admin only)
+    pub fn register_sequencer(ctx: Context<RegisterSequencer>, sequencer: Pubkey) -> Result<()> {
+        ctx.accounts
+            .verifier_state
+            .require_unpaused(PAUSE_REGISTRATION)?;
+        let registration = &mut ctx.accounts.sequencer_registration;
+        registration.sequencer = sequencer;
+        registration.registered_at = Clock::get()?.unix_timestamp;
+        registration.valid_from = registration.registered_at;
+        registration.valid_until = None;
+
+        emit!(SequencerRegisteredEvent {
+            sequencer,
+            authority: ctx.accounts.authority.key(),
+            timestamp: registration.registered_at,
+        });
+
+        msg!("Sequencer registered: {}", sequencer);
+        Ok(())
+    }
+
+    /// Replace a sequencer key with a new one (admin only). The new key is registered
+    /// now but only settles once the admin timelock has passed; the old key keeps
+    /// settling until SEQUENCER_KEY_OVERLAP after that, then can be removed.
+    pub fn rotate_sequencer(
+        ctx: Context<RotateSequencer>,
+        current_sequencer: Pubkey,
+        next_sequencer: Pubkey,
+    ) -> Result<()> {
+        let verifier_state = &ctx.accounts.verifier_state;
+        verifier_state.require_unpaused(PAUSE_REGISTRATION)?;
+        let next = &mut ctx.accounts.next_registration;
+        next.sequencer = next_sequencer;
+        let (activates_at, retires_at) = schedule_sequencer_rotation(
+            &mut ctx.accounts.current_registration,
+            next,
+            Clock::get()?.unix_timestamp,
+            verifier_state.admin_timelock,
+        )?;
+
+        emit!(SequencerRotationEvent {
+            current_sequencer,
+            next_sequencer,
+            activates_at,
+            retires_at,
+            authority: ctx.accounts.authority.key(),
+        });
+
+        msg!(
+            "Sequencer {} rotating to {}: active at {}, old key retires at {}",
+            current_sequencer,
+            next_sequencer,
+            activates_at,
+            retires_at
+        );
+        Ok(())
+    }
+
+    /// Revoke a sequencer's registration, returning its rent to the authority (admin only)
+    pub fn remove_sequencer(ctx: Context<RemoveSequencer>, sequencer: Pubkey) -> Result<()> {
+        emit!(SequencerRemovedEvent {
+            sequencer,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Sequencer removed: {}", sequencer);
+        Ok(())
+    }
+
+    /// Replace the set of paused operations (PAUSE_* bits; 0 resumes everything), e.g.
+    /// halting settlement while standalone proof verification stays available (admin only)
+    pub fn set_pause_flags(ctx: Context<SetPauseFlags>, pause_flags: u8) -> Result<()> {
+        ctx.accounts.verifier_state.set_pause_flags(pause_flags)?;
+
+        emit!(PauseFlagsUpdatedEvent {
+            pause_flags,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Verifier pause flags set to: {:#05b}", pause_flags);
+        Ok(())
+    }
+
+    /// Create a game's payout schedule (admin only)
+    pub fn initialize_game_config(
+        ctx: Context<InitializeGameConfig>,
+        game_id: u8,
+        payout_multiplier_bps: u32,
+        house_edge_bps: u16,
+    ) -> Result<()> {
+        let game_config = &mut ctx.accounts.game_config;
+        game_config.game_id = game_id;
+        game_config.set_schedule(
+            payout_multiplier_bps,
+            house_edge_bps,
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        msg!(
+            "Game {} payout schedule: {} bps, house edge {} bps",
+            game_id,
+            payout_multiplier_bps,
+            house_edge_bps
+        );
+        Ok(())
+    }
+
+    /// Change a game's payout schedule; applies to every batch settled afterwards (admin only)
+    pub fn update_game_config(
+        ctx: Context<UpdateGameConfig>,
+        game_id: u8,
+        payout_multiplier_bps: u32,
+        house_edge_bps: u16,
+    ) -> Result<()> {
+        let game_config = &mut ctx.accounts.game_config;
+        let timestamp = Clock::get()?.unix_timestamp;
+        game_config.set_schedule(payout_multiplier_bps, house_edge_bps, timestamp)?;
+
+        emit!(GameConfigUpdatedEvent {
+            game_id,
+            payout_multiplier_bps,
+            house_edge_bps,
+            authority: ctx.accounts.authority.key(),
+            timestamp,
+        });
+
+        msg!(
+            "Game {} payout schedule updated: {} bps, house edge {} bps",
+            game_id,
+            payout_multiplier_bps,
+            house_edge_bps
+        );
+        Ok(())
+    }
+
+    /// Nominate a new authority; it takes over once it accepts. Proposing again replaces
+    /// the pending nominee (admin only)
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+        let verifier_state = &mut ctx.accounts.verifier_state;
+        verifier_state.pending_authority = Some(new_authority);
+
+        emit!(AuthorityProposedEvent {
+            authority: verifier_state.authority,
+            pending_authority: new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Authority transfer proposed to: {}", new_authority);
+        Ok(())
+    }
+
+    /// Complete an authority transfer; must be signed by the pending authority
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let verifier_state = &mut ctx.accounts.verifier_state;
+        let previous_authority =
+            verifier_state.accept_authority(&ctx.accounts.new_authority.key())?;
+
+        emit!(AuthorityTransferredEvent {
+            previous_authority,
+            new_authority: verifier_state.authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Authority transferred to: {}", verifier_state.authority);
+        Ok(())
+    }
+
+    /// Queue a sensitive admin action; it can be executed once the admin timelock has
+    /// passed, giving anyone watching time to react (admin only)
+    pub fn queue_admin_action(ctx: Context<QueueAdminAction>, action: AdminAction) -> Result<()> {
+        if let AdminAction::SetAdminTimelock { delay } = action {
+            validate_admin_timelock(delay)?;
+        }
+        let verifier_state = &mut ctx.accounts.verifier_state;
+        let queued_at = Clock::get()?.unix_timestamp;
+        let executable_at = queued_at
+            .checked_add(verifier_state.admin_timelock)
+            .ok_or(VerifierError::InvalidTimelockDelay)?;
+
+        let admin_action = &mut ctx.accounts.admin_action;
+        admin_action.action_id = verifier_state.next_admin_action_id;
+        admin_action.action = action.clone();
+        admin_action.queued_at = queued_at;
+        admin_action.executable_at = executable_at;
+        verifier_state.next_admin_action_id += 1;
+
+        emit!(AdminActionQueuedEvent {
+            action_id: admin_action.action_id,
+            action,
+            executable_at,
+            authority: ctx.accounts.authority.key(),
+        });
+
+        msg!(
+            "Admin action {} queued, executable at {}",
+            admin_action.action_id,
+            executable_at
+        );
+        Ok(())
+    }
+
+    /// Apply a queued admin action whose timelock has passed (admin only)
+    pub fn execute_admin_action(ctx: Context<ExecuteAdminAction>, action_id: u64) -> Result<()> {
+        let timestamp = Clock::get()?.unix_timestamp;
+        let admin_action = &ctx.accounts.admin_action;
+        admin_action.check_executable(timestamp)?;
+
+        let verifier_state = &mut ctx.accounts.verifier_state;
+        match admin_action.action {
+            AdminAction::UpdateVaultProgram { new_vault_program } => {
+                verifier_state.vault_program = new_vault_program;
+                msg!("Vault program updated to: {}", new_vault_program);
+            }
+            AdminAction::RotateVerifyingKey {
+                circuit_id,
+                key_hash,
+            } => {
+                let registry = ctx
+                    .accounts
+                    .circuit_registry
+                    .as_ref()
+                    .ok_or(VerifierError::UnknownCircuit)?;
+                let verifying_key = ctx
+                    .accounts
+                    .verifying_key
+                    .as_mut()
+                    .ok_or(VerifierError::InvalidVerifyingKey)?;
+                require!(
+                    registry.verifying_key(circuit_id) == Some(verifying_key.key()),
+                    VerifierError::UnknownCircuit
+                );
+                // The staged key must still be the one announced when this was queued
+                require!(
+                    hash::hash(&verifying_key.staged).to_bytes() == key_hash,
+                    VerifierError::InvalidVerifyingKey
+                );
+                verifying_key.activate_staged()?;
+
+                emit!(VerifyingKeyRotatedEvent {
+                    circuit_id,
+                    version: verifying_key.version,
+                    key_hash,
+                    authority: ctx.accounts.authority.key(),
+                    timestamp,
+                });
+                msg!(
+                    "Circuit {} verifying key rotated to version {} ({} bytes)",
+                    circuit_id,
+                    verifying_key.version,
+                    verifying_key.active.len()
+                );
+            }
+            AdminAction::SetAdminTimelock { delay } => {
+                verifier_state.admin_timelock = delay;
+                msg!("Admin timelock set to {} seconds", delay);
+            }
+            AdminAction::SetCoSigner { co_signer } => {
+                verifier_state.co_signer = co_signer;
+                match co_signer {
+                    Some(co_signer) => msg!("Settlement co-signer set to: {}", co_signer),
+                    None => msg!("Settlement co-signer removed"),
+                }
+            }
+        }
+
+        emit!(AdminActionExecutedEvent {
+            action_id,
+            action: admin_action.action.clone(),
+            authority: ctx.accounts.authority.key(),
+            timestamp,
+        });
+        Ok(())
+    }
+
+    /// Drop a queued admin action before it is executed (admin only)
+    pub fn cancel_admin_action(ctx: Context<CancelAdminAction>, action_id: u64) -> Result<()> {
+        emit!(AdminActionCancelledEvent {
+            action_id,
+            authority: ctx.accounts.authority.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Admin action {} cancelled", action_id);
+        Ok(())
+    }
+}
+
+// Constants
+const MAX_BATCH_SIZE: usize = 100;
+const MAX_PROOF_SIZE: usize = 2048; // 2KB for Phase 2, will be smaller for Groth16
+const MAX_PUBLIC_INPUTS: usize = 32; // Keeps verify_proof within transaction size limits
+const MAX_VERIFYING_KEY_SIZE: usize =
+    VERIFYING_KEY_POINTS_SIZE + (MAX_PUBLIC_INPUTS + 1) * IC_POINT_SIZE;
+const BPS_DENOMINATOR: u64 = 10_000;
+/// VerifierState::pause_flags bits
+pub const PAUSE_SETTLEMENT: u8 = 1 << 0; // verify_and_settle and chunked batches
+pub const PAUSE_VERIFICATION: u8 = 1 << 1; // Standalone verify_proof
+pub const PAUSE_REGISTRATION: u8 = 1 << 2; // register_sequencer
+pub const PAUSE_ALL: u8 = PAUSE_SETTLEMENT | PAUSE_VERIFICATION | PAUSE_REGISTRATION;
+pub const DEFAULT_ADMIN_TIMELOCK: i64 = 48 * 60 * 60; // 48 hours
+const MIN_ADMIN_TIMELOCK: i64 = 60 * 60; // 1 hour
+const MAX_ADMIN_TIMELOCK: i64 = 30 * 24 * 60 * 60; // 30 days
+const MAX_HOUSE_EDGE_BPS: u16 = 1_000; // 10%
+pub const GAME_COINFLIP: u8 = 0;
+pub const CIRCUIT_ACCOUNTING: u8 = 0; // Coinflip accounting circuit
+const MAX_CIRCUITS: usize = 16;
+const MAX_CIRCUIT_GAMES: usize = 8;
+pub const OPTIMISTIC_CHALLENGE_WINDOW: i64 = 24 * 60 * 60; // 24 hours
+pub const STATS_EPOCH_LENGTH: i64 = 24 * 60 * 60; // Minimum length of a stats epoch
+pub const SEQUENCER_KEY_OVERLAP: i64 = 60 * 60; // Both keys settle this long in a rotation
+pub const ROLLUP_HEALTH_VERSION: u8 = 1; // Layout version of RollupHealth
+/// Root of the balance state tree with no players, where settlement starts: the
+/// settlement circuit's depth-16 Poseidon tree of zero leaves (big-endian)
+pub const EMPTY_STATE_ROOT: [u8; 32] = [
+    0x2a, 0x7c, 0x7c, 0x9b, 0x6c, 0xe5, 0x88, 0x0b, 0x9f, 0x6f, 0x22, 0x8d, 0x72, 0xbf, 0x6a, 0x57,
+    0x5a, 0x52, 0x6f, 0x29, 0xc6, 0x6e, 0xcc, 0xee, 0xf8, 0xb7, 0x53, 0xd3, 0x8b, 0xba, 0x73, 0x23,
+];
+
+// Account structures
+#[account]
+pub struct VerifierState {
+    pub authority: Pubkey,
+    pub vault_program: Pubkey,
+    pub total_batches_processed: u64,
+    pub total_bets_settled: u64,
+    pub pause_flags: u8, // PAUSE_* bits
+    pub last_settled_batch_id: u64,
+    pub last_sequencer_nonce: u64,
+    pub state_root: [u8; 32], // Merkle root of settled user balances
+    pub pending_authority: Option<Pubkey>, // Proposed authority awaiting acceptance
+    pub admin_timelock: i64,  // Seconds between queueing and executing admin actions
+    pub next_admin_action_id: u64,
+    pub co_signer: Option<Pubkey>, // Must also sign every settlement when set
+    pub head_batch_id: u64,        // Batch whose new state root is state_root; 0 for none
+    pub head_optimistic: bool,     // Head is an optimistic batch not yet finalized
+    pub challenged_batch_id: Option<u64>, // Lowest batch challenged since the last resolution
+    pub challenge_resolvable_at: i64, // When resolve_challenge may lift the challenge pause
+}
+
+impl VerifierState {
+    pub fn require_unpaused(&self, operation: u8) -> Result<()> {
+        require!(
+            self.pause_flags & operation == 0,
+            VerifierError::VerifierPaused
+        );
+        Ok(())
+    }
+
+    /// Replace the pause flags. While a challenge is unresolved settlement stays paused:
+    /// only resolve_challenge lifts that pause.
+    pub fn set_pause_flags(&mut self, pause_flags: u8) -> Result<()> {
+        require!(
+            pause_flags & !PAUSE_ALL == 0,
+            VerifierError::InvalidPauseFlags
+        );
+        require!(
+            self.challenged_batch_id.is_none() || pause_flags & PAUSE_SETTLEMENT != 0,
+            VerifierError::ChallengeUnresolved
+        );
+        self.pause_flags = pause_flags;
+        Ok(())
+    }
+
+    pub fn health(&self, verifier_stats: &VerifierStats) -> RollupHealth {
+        RollupHealth {
+            version: ROLLUP_HEALTH_VERSION,
+            state_root: self.state_root,
+            last_settled_batch_id: self.last_settled_batch_id,
+            last_sequencer_nonce: self.last_sequencer_nonce,
+            total_batches_processed: self.total_batches_processed,
+            total_bets_settled: self.total_bets_settled,
+            pause_flags: self.pause_flags,
+            vault_program: self.vault_program,
+            stats_epoch: verifier_stats.epoch,
+            current_epoch: verifier_stats.current,
+        }
+    }
+
+    /// With a co-signer configured, a settlement must carry its signature as well as the
+    /// sequencer's. `co_signer` is the key of the co-signer account passed, if any.
+    pub fn require_co_signed(&self, co_signer: Option<Pubkey>) -> Result<()> {
+        if let Some(expected) = self.co_signer {
+            require!(co_signer == Some(expected), VerifierError::MissingCoSigner);
+        }
+        Ok(())
+    }
+
+    /// Batch ids and sequencer nonces must strictly increase, so a settled batch can't
+    /// be submitted again
+    pub fn check_batch_order(&self, batch_id: u64, sequencer_nonce: u64) -> Result<()> {
+        require!(
+            batch_id > self.last_settled_batch_id && sequencer_nonce > self.last_sequencer_nonce,
+            VerifierError::BatchReplayed
+        );
+        Ok(())
+    }
+
+    /// Hand authority to the pending nominee if `signer` is it; returns the previous
+    /// authority
+    pub fn accept_authority(&mut self, signer: &Pubkey) -> Result<Pubkey> {
+        require!(
+            self.pending_authority == Some(*signer),
+            VerifierError::NotPendingAuthority
+        );
+        self.pending_authority = None;
+        Ok(std::mem::replace(&mut self.authority, *signer))
+    }
+
+    pub fn record_batch(&mut self, batch_id: u64, sequencer_nonce: u64) {
+        self.last_settled_batch_id = batch_id;
+        self.last_sequencer_nonce = sequencer_nonce;
+    }
+
+    /// Make a batch the head of the chain; returns its parent, the previous head. A
+    /// proven batch can't build on an optimistic one still open to challenge, so only
+    /// optimistic batches are ever discarded with a challenged one.
+    pub fn advance_head(&mut self, batch_id: u64, optimistic: bool) -> Result<u64> {
+        require!(
+            optimistic || !self.head_optimistic,
+            VerifierError::OptimisticBatchPending
+        );
+        self.head_optimistic = optimistic;
+        Ok(std::mem::replace(&mut self.head_batch_id, batch_id))
+    }
+
+    /// Undo a batch shown invalid by a challenge: the state root goes back to the claim's
+    /// previous root and the head to the batch's parent, discarding it and the optimistic
+    /// batches built on it, and settlement pauses until resolve_challenge. A batch above
+    /// one already rolled back was discarded with it, so it moves nothing. Returns
+    /// whether the chain was rolled back.
+    pub fn roll_back(
+        &mut self,
+        record: &BatchRecord,
+        claim: &OptimisticBatchClaim,
+        parent_optimistic: bool,
+        now: i64,
+    ) -> Result<bool> {
+        if self.challenged_batch_id.is_none() {
+            // Every batch discarded by this pause was submitted before it, so its
+            // challenge window is over by then
+            self.challenge_resolvable_at = now
+                .checked_add(OPTIMISTIC_CHALLENGE_WINDOW)
+                .ok_or(VerifierError::MathOverflow)?;
+        }
+        self.pause_flags |= PAUSE_SETTLEMENT;
+        if self
+            .challenged_batch_id
+            .map_or(false, |lowest| record.batch_id > lowest)
+        {
+            return Ok(false);
+        }
+        self.challenged_batch_id = Some(record.batch_id);
+        self.state_root = claim.prev_state_root;
+        self.head_batch_id = record.parent_batch_id;
+        self.head_optimistic = parent_optimistic;
+        Ok(true)
+    }
+
+    /// Lift the settlement pause of a challenge once no discarded batch can still be
+    /// challenged; returns the lowest batch challenged
+    pub fn resolve_challenge(&mut self, now: i64) -> Result<u64> {
+        let batch_id = self
+            .challenged_batch_id
+            .ok_or(VerifierError::NoChallengePending)?;
+        require!(
+            now >= self.challenge_resolvable_at,
+            VerifierError::ChallengeWindowOpen
+        );
+        self.challenged_batch_id = None;
+        self.pause_flags &= !PAUSE_SETTLEMENT;
+        Ok(batch_id)
+    }
+}
+
+/// Sensitive admin operations, which only take effect through the timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub enum AdminAction {
+    UpdateVaultProgram {
+        new_vault_program: Pubkey,
+    },
+    /// Activate a circuit's staged verifying key, which must hash to `key_hash`
+    RotateVerifyingKey {
+        circuit_id: u8,
+        key_hash: [u8; 32],
+    },
+    SetAdminTimelock {
+        delay: i64,
+    },
+    /// Require (or, with None, stop requiring) a second signer on settlements
+    SetCoSigner {
+        co_signer: Option<Pubkey>,
+    },
+}
+
+/// An admin action waiting out the timelock
+#[account]
+pub struct PendingAdminAction {
+    pub action_id: u64,
+    pub action: AdminAction,
+    pub queued_at: i64,
+    pub executable_at: i64,
+}
+
+impl PendingAdminAction {
+    pub const SPACE: usize = 8 + 8 + (1 + 1 + 32) + 8 + 8;
+
+    pub fn check_executable(&self, now: i64) -> Result<()> {
+        require!(
+            now >= self.executable_at,
+            VerifierError::AdminActionTimelocked
+        );
+        Ok(())
+    }
+}
+
+fn validate_admin_timelock(delay: i64) -> Result<()> {
+    require!(
+        (MIN_ADMIN_TIMELOCK..=MAX_ADMIN_TIMELOCK).contains(&delay),
+        VerifierError::InvalidTimelockDelay
+    );
+    Ok(())
+}
+
+/// Verifying key used by verify_and_settle, replaceable without a program upgrade.
+/// A new key is uploaded into `staged` in chunks and swapped in by rotation.
+#[account]
+pub struct VerifyingKeyAccount {
+    pub version: u32,
+    pub staged_len: u32, // Declared length of the key being uploaded; 0 when none
+    pub active: Vec<u8>,
+    pub staged: Vec<u8>,
+}
+
+impl VerifyingKeyAccount {
+    pub const SPACE: usize = 8 + 4 + 4 + 2 * (4 + MAX_VERIFYING_KEY_SIZE);
+
+    /// Append a chunk to the staged key; chunks must arrive in order
+    pub fn stage_chunk(&mut self, total_len: usize, offset: usize, chunk: &[u8]) -> Result<()> {
+        // alpha, beta, gamma, delta and IC[0..=n] for 1..=MAX_PUBLIC_INPUTS inputs
+        require!(
+            (VERIFYING_KEY_POINTS_SIZE + 2 * IC_POINT_SIZE..=MAX_VERIFYING_KEY_SIZE)
+                .contains(&total_len)
+                && (total_len - VERIFYING_KEY_POINTS_SIZE) % IC_POINT_SIZE == 0,
+            VerifierError::InvalidVerifyingKey
+        );
+        require!(!chunk.is_empty(), VerifierError::InvalidVerifyingKeyUpload);
+
+        if offset == 0 {
+            self.staged.clear();
+            self.staged_len = total_len as u32;
+        }
+        require!(
+            self.staged_len as usize == total_len
+                && offset == self.staged.len()
+                && offset + chunk.len() <= total_len,
+            VerifierError::InvalidVerifyingKeyUpload
+        );
+
+        self.staged.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /// Swap in the staged key once complete and well-formed; returns the new version
+    pub fn activate_staged(&mut self) -> Result<u32> {
+        require!(
+            self.staged_len > 0 && self.staged.len() == self.staged_len as usize,
+            VerifierError::VerifyingKeyUploadIncomplete
+        );
+        parse_verifying_key(&self.staged)?
+            .validate_curve_points()
+            .map_err(|_| VerifierError::InvalidVerifyingKey)?;
+
+        self.active = std::mem::take(&mut self.staged);
+        self.staged_len = 0;
+        self.version = self
+            .version
+            .checked_add(1)
+            .ok_or(VerifierError::MathOverflow)?;
+        Ok(self.version)
+    }
+}
+
+/// Payout schedule of one game; one PDA per game id. A win pays
+/// floor(bet_amount * payout_multiplier * (1 - house_edge)), e.g. 2x less a 1% edge is 1.98x.
+#[account]
+pub struct GameConfig {
+    pub game_id: u8,
+    pub payout_multiplier_bps: u32, // Gross payout of a win, in basis points of the stake
+    pub house_edge_bps: u16,        // Taken off the gross payout
+    pub updated_at: i64,
+}
+
+impl GameConfig {
+    pub const SPACE: usize = 8 + 1 + 4 + 2 + 8;
+
+    pub fn set_schedule(
+        &mut self,
+        payout_multiplier_bps: u32,
+        house_edge_bps: u16,
+        timestamp: i64,
+    ) -> Result<()> {
+        // A win must at least return the stake, and the edge is capped
+        require!(
+            payout_multiplier_bps as u64 >= BPS_DENOMINATOR && house_edge_bps <= MAX_HOUSE_EDGE_BPS,
+            VerifierError::InvalidGameConfig
+        );
+        self.payout_multiplier_bps = payout_multiplier_bps;
+        self.house_edge_bps = house_edge_bps;
+        self.updated_at = timestamp;
+        Ok(())
+    }
+
+    /// Payout of a winning bet, rounded down to whole base units
+    pub fn win_payout(&self, bet_amount: u64) -> Result<u64> {
+        let payout = bet_amount as u128
+            * self.payout_multiplier_bps as u128
+            * (BPS_DENOMINATOR - self.house_edge_bps as u64) as u128
+            / (BPS_DENOMINATOR * BPS_DENOMINATOR) as u128;
+        u64::try_from(payout).map_err(|_| VerifierError::MathOverflow.into())
+    }
+}
+
+/// A circuit the verifier accepts proofs for: the account holding its verifying key
+/// and the games whose batches it may settle
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct CircuitEntry {
+    pub circuit_id: u8,
+    pub verifying_key: Pubkey, // VerifyingKeyAccount address
+    pub game_ids: Vec<u8>,
+}
+
+impl CircuitEntry {
+    pub const SIZE: usize = 1 + 32 + 4 + MAX_CIRCUIT_GAMES;
+}
+
+/// Registered circuits by id, so games with their own circuits settle through the same
+/// program; one PDA
+#[account]
+pub struct CircuitRegistry {
+    pub circuits: Vec<CircuitEntry>,
+}
+
+impl CircuitRegistry {
+    pub const SPACE: usize = 8 + 4 + MAX_CIRCUITS * CircuitEntry::SIZE;
+
+    /// Verifying key account of a registered circuit
+    pub fn verifying_key(&self, circuit_id: u8) -> Option<Pubkey> {
+        self.circuits
+            .iter()
+            .find(|circuit| circuit.circuit_id == circuit_id)
+            .map(|circuit| circuit.verifying_key)
+    }
+
+    /// Verifying key account of a circuit, if it may settle batches of the game
+    pub fn settlement_key(&self, circuit_id: u8, game_id: u8) -> Option<Pubkey> {
+        self.circuits
+            .iter()
+            .find(|circuit| circuit.circuit_id == circuit_id && circuit.game_ids.contains(&game_id))
+            .map(|circuit| circuit.verifying_key)
+    }
+
+    pub fn register(&mut self, entry: CircuitEntry) -> Result<()> {
+        require!(
+            self.verifying_key(entry.circuit_id).is_none(),
+            VerifierError::CircuitAlreadyRegistered
+        );
+        require!(
+            self.circuits.len() < MAX_CIRCUITS
+                && !entry.game_ids.is_empty()
+                && entry.game_ids.len() <= MAX_CIRCUIT_GAMES,
+            VerifierError::InvalidCircuitRegistration
+        );
+        self.circuits.push(entry);
+        Ok(())
+    }
+}
+
+/// Allowlist entry; one PDA per registered sequencer
+#[account]
+pub struct SequencerRegistration {
+    pub sequencer: Pubkey,
+    pub registered_at: i64,
+    pub valid_from: i64,          // Later than registered_at for a rotated-in key
+    pub valid_until: Option<i64>, // Set when the key is rotated out
+}
+
+impl SequencerRegistration {
+    pub const SPACE: usize = 8 + 32 + 8 + 8 + (1 + 8);
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.valid_from <= now && self.valid_until.map_or(true, |until| now < until)
+    }
+}
+
+/// Schedule `next` to take over from `current`: the new key becomes valid after
+/// `delay`, and both keys stay valid for SEQUENCER_KEY_OVERLAP after that so
+/// settlements in flight under the old key still land. Returns when the new key
+/// activates and when the old one retires.
+pub fn schedule_sequencer_rotation(
+    current: &mut SequencerRegistration,
+    next: &mut SequencerRegistration,
+    now: i64,
+    delay: i64,
+) -> Result<(i64, i64)> {
+    require!(
+        current.is_active(now) && current.valid_until.is_none(),
+        VerifierError::SequencerRotationPending
+    );
+    let activates_at = now
+        .checked_add(delay)
+        .ok_or(VerifierError::InvalidTimelockDelay)?;
+    let retires_at = activates_at
+        .checked_add(SEQUENCER_KEY_OVERLAP)
+        .ok_or(VerifierError::InvalidTimelockDelay)?;
+    next.registered_at = now;
+    next.valid_from = activates_at;
+    next.valid_until = None;
+    current.valid_until = Some(retires_at);
+    Ok((activates_at, retires_at))
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchRecordStatus {
+    Settled,    // Proof verified (or challenge window passed) and batch applied
+    Optimistic, // Submitted without a proof; challengeable until its deadline
+    Challenged, // Shown invalid by a fraud proof; settlement is paused
+}
+
+/// On-chain record of a settled batch; one PDA per batch id
+#[account]
+pub struct BatchRecord {
+    pub batch_id: u64,
+    pub sequencer: Pubkey,
+    pub proof_hash: [u8; 32],
+    pub state_root: [u8; 32], // Balance state root after the batch
+    pub bet_count: u32,
+    pub house_delta: i64,
+    pub slot: u64, // Slot the batch was settled in
+    pub status: BatchRecordStatus,
+    pub bets_root: [u8; 32], // optimistic::bets_root over the batch's bets
+    pub synced: [u8; SYNCED_BITMAP_LEN], // Bit i set once bet i is applied to its vault
+    pub parent_batch_id: u64, // Batch this one builds on; 0 for the first
+}
+
+/// Bytes of BatchRecord::synced, one bit per bet of the largest batch
+const SYNCED_BITMAP_LEN: usize = (MAX_BATCH_SIZE + 7) / 8;
+
+impl BatchRecord {
+    pub const SPACE: usize = 8 + 8 + 32 + 32 + 32 + 4 + 8 + 8 + 1 + 32 + SYNCED_BITMAP_LEN + 8;
+
+    /// An optimistic batch only settles on a settled parent, so a challenged batch's
+    /// descendants never do. `parent` is the record of parent_batch_id, if any.
+    pub fn require_parent_settled(&self, parent: Option<&BatchRecord>) -> Result<()> {
+        if self.parent_batch_id == 0 {
+            return Ok(());
+        }
+        require!(
+            parent.map_or(false, |parent| parent.batch_id == self.parent_batch_id
+                && parent.status == BatchRecordStatus::Settled),
+            VerifierError::ParentBatchNotSettled
+        );
+        Ok(())
+    }
+
+    /// Whether the parent, passed as the record of parent_batch_id, is an optimistic
+    /// batch still open to challenge
+    pub fn parent_optimistic(&self, parent: Option<&BatchRecord>) -> Result<bool> {
+        if self.parent_batch_id == 0 {
+            return Ok(false);
+        }
+        let parent = parent
+            .filter(|parent| parent.batch_id == self.parent_batch_id)
+            .ok_or(VerifierError::ParentBatchMismatch)?;
+        Ok(parent.status == BatchRecordStatus::Optimistic)
+    }
+
+    /// The settled bet a proof opens against this batch's bets root. Its vault delta
+    /// comes from the bet itself, so nothing outside the batch can be synced.
+    pub fn settled_bet<'a>(&self, proof: &'a LeafProof) -> Result<&'a BetSettlement> {
+        require!(
+            proof.verify_root(&self.bets_root, self.bet_count),
+            VerifierError::BetNotInBatch
+        );
+        Ok(&proof.leaf.bet)
+    }
+
+    /// Whether bet `index` has been applied to its user's vault
+    pub fn is_synced(&self, index: u32) -> bool {
+        index < self.bet_count && self.synced[index as usize / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Record bet `index` as applied to its user's vault; each bet applies once
+    pub fn mark_synced(&mut self, index: u32) -> Result<()> {
+        require!(index < self.bet_count, VerifierError::BetNotInBatch);
+        require!(!self.is_synced(index), VerifierError::BetAlreadySynced);
+        self.synced[index as usize / 8] |= 1 << (index % 8);
+        Ok(())
+    }
+
+    /// The bet the proof opens, if its vault lock may be released: the batch settled
+    /// and the bet has been applied to its vault, or a fraud proof rejected the batch
+    pub fn releasable_bet<'a>(&self, proof: &'a LeafProof) -> Result<&'a BetSettlement> {
+        let bet = self.settled_bet(proof)?;
+        let releasable = match self.status {
+            BatchRecordStatus::Settled => self.is_synced(proof.leaf.index),
+            BatchRecordStatus::Challenged => true,
+            BatchRecordStatus::Optimistic => false,
+        };
+        require!(releasable, VerifierError::BetLockNotReleasable);
+        Ok(bet)
+    }
+
+    /// Vault deltas of the bets the proofs open, each marked applied; a bet outside
+    /// the batch, or one already applied (or listed twice), fails the lot
+    pub fn sync_deltas(&mut self, proofs: &[LeafProof]) -> Result<Vec<vault_cpi::UserDelta>> {
+        let mut deltas = Vec::with_capacity(proofs.len());
+        for proof in proofs {
+            deltas.push(self.settled_bet(proof)?.user_delta()?);
+            self.mark_synced(proof.leaf.index)?;
+        }
+        Ok(deltas)
+    }
+}
+
+/// Rollup state returned by rollup_health, in this borsh order. The layout is stable:
+/// fields are only ever appended, with `version` raised when they are.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct RollupHealth {
+    pub version: u8,          // ROLLUP_HEALTH_VERSION
+    pub state_root: [u8; 32], // Latest committed balance state root
+    pub last_settled_batch_id: u64,
+    pub last_sequencer_nonce: u64,
+    pub total_batches_processed: u64,
+    pub total_bets_settled: u64,
+    pub pause_flags: u8,
+    pub vault_program: Pubkey,
+    pub stats_epoch: u64,
+    pub current_epoch: EpochStats, // Totals of the stats epoch still open
+}
+
+/// Settlement totals over one stats epoch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpochStats {
+    pub bets_settled: u64,
+    pub total_wagered: u64,
+    pub total_paid_out: u64,
+    pub house_pnl: i64,     // Wagered minus paid out
+    pub largest_batch: u32, // Most bets settled in one batch
+}
+
+impl EpochStats {
+    pub const SIZE: usize = 8 + 8 + 8 + 8 + 4;
+}
+
+/// Rolling settlement metrics: totals of the current stats epoch and of the last one
+/// snapshot_epoch closed. Only proven settlements count; optimistic batches don't carry
+/// per-bet amounts. One PDA.
+#[account]
+pub struct VerifierStats {
+    pub epoch: u64,
+    pub epoch_started_at: i64,
+    pub current: EpochStats,
+    pub previous: EpochStats,
+}
+
+impl VerifierStats {
+    pub const SPACE: usize = 8 + 8 + 8 + 2 * EpochStats::SIZE;
+
+    /// Add a settled batch to the current epoch
+    pub fn record_batch(&mut self, bets: &[BetSettlement], house_delta: i64) -> Result<()> {
+        let mut current = self.current;
+        for bet in bets {
+            current.total_wagered = current
+                .total_wagered
+                .checked_add(bet.bet_amount)
+                .ok_or(VerifierError::MathOverflow)?;
+            current.total_paid_out = current
+                .total_paid_out
+                .checked_add(bet.payout)
+                .ok_or(VerifierError::MathOverflow)?;
+        }
+        current.bets_settled = current
+            .bets_settled
+            .checked_add(bets.len() as u64)
+            .ok_or(VerifierError::MathOverflow)?;
+        current.house_pnl = current
+            .house_pnl
+            .checked_add(house_delta)
+            .ok_or(VerifierError::MathOverflow)?;
+        current.largest_batch = current.largest_batch.max(bets.len() as u32);
+        self.current = current;
+        Ok(())
+    }
+
+    /// Close the current epoch at `now` and start the next, returning the closed
+    /// epoch's totals
+    pub fn roll(&mut self, now: i64) -> Result<EpochStats> {
+        require!(
+            now >= self.epoch_started_at.saturating_add(STATS_EPOCH_LENGTH),
+            VerifierError::StatsEpochOpen
+        );
+        self.previous = std::mem::take(&mut self.current);
+        self.epoch += 1;
+        self.epoch_started_at = now;
+        Ok(self.previous)
+    }
+}
+
+/// A batch too large for one transaction, assembled by append_batch_chunk and settled
+/// by finalize_and_verify; one PDA per sequencer
+#[account]
+pub struct BatchStaging {
+    pub sequencer: Pubkey,
+    pub batch_id: u64,
+    pub sequencer_nonce: u64,
+    pub game_id: u8,
+    pub prev_state_root: [u8; 32],
+    pub new_state_root: [u8; 32],
+    pub vrf_pubkey: Pubkey,
+    pub vrf_outputs_root: [u8; 32],
+    pub bet_count: u32, // Declared number of bets; staging is complete when all arrived
+    pub bets: Vec<BetSettlement>,
+    pub sequencer_signature: [u8; 64], // Over the batch hash of the complete batch
+}
+
+impl BatchStaging {
+    pub const SPACE: usize =
+        8 + 32 + 8 + 8 + 1 + 32 + 32 + 32 + 32 + 4 + 4 + MAX_BATCH_SIZE * BetSettlement::SIZE + 64;
+
+    /// Append a chunk of bets; chunks must arrive in order and not exceed bet_count
+    pub fn append(&mut self, offset: usize, bets: Vec<BetSettlement>) -> Result<()> {
+        require!(
+            !bets.is_empty()
+                && offset == self.bets.len()
+                && offset + bets.len() <= self.bet_count as usize,
+            VerifierError::InvalidBatchChunk
+        );
+        self.bets.extend(bets);
+        Ok(())
+    }
+
+    /// The staged batch, once every declared bet has been appended
+    pub fn to_batch_data(&self) -> Result<BatchSettlementData> {
+        require!(
+            self.bets.len() == self.bet_count as usize,
+            VerifierError::BatchStagingIncomplete
+        );
+        Ok(BatchSettlementData {
+            batch_id: self.batch_id,
+            sequencer_nonce: self.sequencer_nonce,
+            game_id: self.game_id,
+            prev_state_root: self.prev_state_root,
+            new_state_root: self.new_state_root,
+            vrf_pubkey: self.vrf_pubkey,
+            vrf_outputs_root: self.vrf_outputs_root,
+            bets: self.bets.clone(),
+            sequencer_signature: self.sequencer_signature,
+        })
+    }
+}
+
+/// A batch submitted without a proof, held until its challenge window passes. The game's
+/// payout schedule is snapshotted so challenges are judged by the rules it was settled
+/// under. One PDA per batch id.
+#[account]
+pub struct OptimisticBatch {
+    pub sequencer: Pubkey,
+    pub claim: OptimisticBatchClaim,
+    pub payout_multiplier_bps: u32,
+    pub house_edge_bps: u16,
+    pub challenge_deadline: i64,
+}
+
+impl OptimisticBatch {
+    pub const SPACE: usize = 8 + 32 + OptimisticBatchClaim::SIZE + 4 + 2 + 8;
+
+    /// The payout schedule the batch was submitted under
+    pub fn game_config(&self) -> GameConfig {
+        GameConfig {
+            game_id: self.claim.game_id,
+            payout_multiplier_bps: self.payout_multiplier_bps,
+            house_edge_bps: self.house_edge_bps,
+            updated_at: 0,
+        }
+    }
+}
+
+/// vk_x for verify_proof_prepared, accumulated by prepare_public_inputs over as many
+/// instructions as the compute budget needs; one PDA per signer
+#[account]
+pub struct PreparedInputs {
+    pub owner: Pubkey,
+    pub key_hash: [u8; 32], // Hash of the verifying key the inputs are folded against
+    pub input_count: u32,
+    pub next_input: u32,
+    pub inputs_hash: [u8; 32], // hash(previous inputs_hash || input), chained over all inputs
+    pub vk_x: [u8; 64],        // IC[0] + sum(IC[i+1] * input[i]) over the inputs so far
+    pub circuit_id: u8,        // Registered circuit whose active key is key_hash
+}
+
+impl PreparedInputs {
+    pub const SPACE: usize = 8 + 32 + 32 + 4 + 4 + 32 + 64 + 1;
+
+    /// The circuit key the inputs were started with, parsed from its account; a key
+    /// rotated since then is rejected
+    pub fn verifying_key(&self, account: &VerifyingKeyAccount) -> Result<Groth16VerifyingKey> {
+        require!(
+            hash::hash(&account.active).to_bytes() == self.key_hash,
+            VerifierError::InvalidVerifyingKey
+        );
+        parse_verifying_key(&account.active).map_err(|_| VerifierError::InvalidVerifyingKey.into())
+    }
+
+    /// Fold a chunk of public inputs into vk_x; chunks must arrive in order
+    pub fn fold(
+        &mut self,
+        verifying_key: &Groth16VerifyingKey,
+        offset: usize,
+        public_inputs: &[[u8; 32]],
+    ) -> Result<()> {
+        require!(
+            !public_inputs.is_empty()
+                && offset == self.next_input as usize
+                && offset + public_inputs.len() <= self.input_count as usize,
+            VerifierError::InvalidPublicInputs
+        );
+
+        let partial = g1_from_bytes(&self.vk_x).map_err(|_| VerifierError::InvalidPublicInputs)?;
+        let vk_x = verifying_key
+            .accumulate_vk_x(partial, offset, public_inputs)
+            .map_err(|_| VerifierError::InvalidPublicInputs)?;
+        self.vk_x = g1_to_bytes(&vk_x);
+        for input in public_inputs {
+            self.inputs_hash = hash::hashv(&[&self.inputs_hash, input]).to_bytes();
+        }
+        self.next_input += public_inputs.len() as u32;
+        Ok(())
+    }
+
+    /// The prepared vk_x, once every declared input has been folded in
+    pub fn vk_x(&self) -> Result<G1Point> {
+        require!(
+            self.next_input == self.input_count,
+            VerifierError::PreparedInputsIncomplete
+        );
+        g1_from_bytes(&self.vk_x).map_err(|_| VerifierError::InvalidPublicInputs.into())
+    }
+}
+
+// Data structures
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchSettlementData {
+    pub batch_id: u64,
+    pub sequencer_nonce: u64,
+    pub game_id: u8,               // Every bet in a batch is of the same game
+    pub prev_state_root: [u8; 32], // Must match the verifier's current state root
+    pub new_state_root: [u8; 32],
+    pub vrf_pubkey: Pubkey, // Sequencer VRF key the bets' outcomes were derived with
+    pub vrf_outputs_root: [u8; 32], // Merkle root over the bets' VRF transcript leaves
+    pub bets: Vec<BetSettlement>,
+    pub sequencer_signature: [u8; 64], // Sequencer's ed25519 signature over the batch hash
+}
+
+/// What an optimistic batch asserts instead of proving: its state transition and a
+/// Merkle root over its bets (see optimistic::BatchLeaf), each leaf carrying the running
+/// house delta, so a single bad leaf can be challenged within a transaction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct OptimisticBatchClaim {
+    pub batch_id: u64,
+    pub sequencer_nonce: u64,
+    pub game_id: u8,
+    pub prev_state_root: [u8; 32],
+    pub new_state_root: [u8; 32],
+    pub vrf_pubkey: Pubkey,
+    pub vrf_outputs_root: [u8; 32],
+    pub bets_root: [u8; 32],
+    pub bet_count: u32,
+    pub house_delta: i64,
+}
+
+impl OptimisticBatchClaim {
+    pub const SIZE: usize = 8 + 8 + 1 + 32 + 32 + 32 + 32 + 32 + 4 + 8;
+
+    /// What the sequencer signs to submit the claim; domain-separated from batch hashes
+    pub fn hash(&self) -> Result<[u8; 32]> {
+        let data = self
+            .try_to_vec()
+            .map_err(|_| VerifierError::InvalidPublicInputs)?;
+        Ok(hash::hashv(&[b"optimistic_claim", &data]).to_bytes())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BetSettlement {
+    pub bet_id: u64,
+    pub user: Pubkey,
+    pub bet_amount: u64,
+    pub user_guess: u8, // 0 or 1 for coin flip
+    pub outcome: u8,    // 0 or 1 actual outcome
+    pub payout: u64,    // Calculated payout amount
+}
+
+impl BetSettlement {
+    pub const SIZE: usize = 8 + 32 + 8 + 1 + 1 + 8;
+
+    /// The bet's effect on its user's vault: the SOL payout minus the stake
+    pub fn user_delta(&self) -> Result<vault_cpi::UserDelta> {
+        let payout = i64::try_from(self.payout).map_err(|_| VerifierError::MathOverflow)?;
+        let stake = i64::try_from(self.bet_amount).map_err(|_| VerifierError::MathOverflow)?;
+        Ok(vault_cpi::UserDelta {
+            user: self.user,
+            sol_delta: payout
+                .checked_sub(stake)
+                .ok_or(VerifierError::MathOverflow)?,
+            usdc_delta: 0,
+            is_win: self.outcome == self.user_guess,
+            bet_amount: self.bet_amount,
+        })
+    }
+}
+
+// Context structures
+#[derive(Accounts)]
+pub struct InitializeVerifier<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + std::mem::size_of::<VerifierState>(),
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    /// CHECK: The vault program that this verifier will interact with
+    pub vault_program: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(circuit_id: u8, batch_data: BatchSettlementData)]
+pub struct VerifyAndSettle<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        constraint = circuit_registry.settlement_key(circuit_id, batch_data.game_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    #[account(
+        seeds = [b"game_config", [batch_data.game_id].as_ref()],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    /// CHECK: The sequencer submitting the batch (signature validation happens in sequencer)
+    #[account(mut)]
+    pub sequencer: Signer<'info>,
+    /// CHECK: Registration PDA of the sequencer; checked in the handler so that
+    /// unregistered sequencers fail with InvalidSequencer
+    #[account(
+        seeds = [b"sequencer", sequencer.key().as_ref()],
+        bump
+    )]
+    pub sequencer_registration: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = sequencer,
+        space = BatchRecord::SPACE,
+        seeds = [b"batch", batch_data.batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    #[account(
+        mut,
+        seeds = [b"verifier_stats"],
+        bump
+    )]
+    pub verifier_stats: Account<'info, VerifierStats>,
+    /// CHECK: Signer PDA for vault calls; holds no data
+    #[account(
+        seeds = [vault_cpi::VAULT_CALLER_SEED],
+        bump
+    )]
+    pub vault_caller: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub vault_state: UncheckedAccount<'info>,
+    /// CHECK: The vault this verifier is linked to
+    #[account(
+        executable,
+        address = verifier_state.vault_program @ VerifierError::InvalidVaultProgram
+    )]
+    pub vault_program: UncheckedAccount<'info>,
+    /// CHECK: Instructions sysvar, read to find the ed25519 check of the batch signature
+    #[account(address = instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    // Checked in the handler against the configured co-signer
+    pub co_signer: Option<Signer<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BeginBatch<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(mut)]
+    pub sequencer: Signer<'info>,
+    /// CHECK: Registration PDA of the sequencer; checked in the handler
+    #[account(
+        seeds = [b"sequencer", sequencer.key().as_ref()],
+        bump
+    )]
+    pub sequencer_registration: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = sequencer,
+        space = BatchStaging::SPACE,
+        seeds = [b"batch_staging", sequencer.key().as_ref()],
+        bump
+    )]
+    pub batch_staging: Account<'info, BatchStaging>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AppendBatchChunk<'info> {
+    pub sequencer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"batch_staging", sequencer.key().as_ref()],
+        bump
+    )]
+    pub batch_staging: Account<'info, BatchStaging>,
+}
+
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct FinalizeAndVerify<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        constraint = circuit_registry.settlement_key(circuit_id, batch_staging.game_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    #[account(
+        seeds = [b"game_config", [batch_staging.game_id].as_ref()],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub sequencer: Signer<'info>,
+    /// CHECK: Registration PDA of the sequencer; checked in the handler
+    #[account(
+        seeds = [b"sequencer", sequencer.key().as_ref()],
+        bump
+    )]
+    pub sequencer_registration: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = sequencer,
+        seeds = [b"batch_staging", sequencer.key().as_ref()],
+        bump
+    )]
+    pub batch_staging: Account<'info, BatchStaging>,
+    #[account(
+        init,
+        payer = sequencer,
+        space = BatchRecord::SPACE,
+        seeds = [b"batch", batch_staging.batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    #[account(
+        mut,
+        seeds = [b"verifier_stats"],
+        bump
+    )]
+    pub verifier_stats: Account<'info, VerifierStats>,
+    /// CHECK: Signer PDA for vault calls; holds no data
+    #[account(
+        seeds = [vault_cpi::VAULT_CALLER_SEED],
+        bump
+    )]
+    pub vault_caller: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub vault_state: UncheckedAccount<'info>,
+    /// CHECK: The vault this verifier is linked to
+    #[account(
+        executable,
+        address = verifier_state.vault_program @ VerifierError::InvalidVaultProgram
+    )]
+    pub vault_program: UncheckedAccount<'info>,
+    /// CHECK: Instructions sysvar, read to find the ed25519 check of the batch signature
+    #[account(address = instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    // Checked in the handler against the configured co-signer
+    pub co_signer: Option<Signer<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(claim: OptimisticBatchClaim)]
+pub struct SubmitBatchOptimistic<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"game_config", [claim.game_id].as_ref()],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub sequencer: Signer<'info>,
+    /// CHECK: Registration PDA of the sequencer; checked in the handler
+    #[account(
+        seeds = [b"sequencer", sequencer.key().as_ref()],
+        bump
+    )]
+    pub sequencer_registration: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = sequencer,
+        space = BatchRecord::SPACE,
+        seeds = [b"batch", claim.batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    #[account(
+        init,
+        payer = sequencer,
+        space = OptimisticBatch::SPACE,
+        seeds = [b"optimistic_batch", claim.batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub optimistic_batch: Account<'info, OptimisticBatch>,
+    /// CHECK: Instructions sysvar, read to find the ed25519 check of the claim signature
+    #[account(address = instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+    // Checked in the handler against the configured co-signer
+    pub co_signer: Option<Signer<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct ChallengeBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    #[account(
+        mut,
+        close = challenger,
+        seeds = [b"optimistic_batch", batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub optimistic_batch: Account<'info, OptimisticBatch>,
+    /// Record of the batch's parent; only needed when it has one
+    pub parent_record: Option<Account<'info, BatchRecord>>,
+    #[account(mut)]
+    pub challenger: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct FinalizeOptimisticBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    #[account(
+        mut,
+        close = sequencer,
+        seeds = [b"optimistic_batch", batch_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub optimistic_batch: Account<'info, OptimisticBatch>,
+    /// Record of the batch's parent; only needed when it has one
+    pub parent_record: Option<Account<'info, BatchRecord>>,
+    /// CHECK: Receives the optimistic batch account's rent
+    #[account(mut, address = optimistic_batch.sequencer)]
+    pub sequencer: UncheckedAccount<'info>,
+    /// CHECK: Signer PDA for vault calls; holds no data
+    #[account(
+        seeds = [vault_cpi::VAULT_CALLER_SEED],
+        bump
+    )]
+    pub vault_caller: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub vault_state: UncheckedAccount<'info>,
+    /// CHECK: The vault this verifier is linked to
+    #[account(
+        executable,
+        address = verifier_state.vault_program @ VerifierError::InvalidVaultProgram
+    )]
+    pub vault_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveChallenge<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct BeginPreparedInputs<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        constraint = circuit_registry.verifying_key(circuit_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = PreparedInputs::SPACE,
+        seeds = [b"prepared_inputs", signer.key().as_ref()],
+        bump
+    )]
+    pub prepared_inputs: Account<'info, PreparedInputs>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PreparePublicInputs<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"prepared_inputs", signer.key().as_ref()],
+        bump
+    )]
+    pub prepared_inputs: Account<'info, PreparedInputs>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        constraint = circuit_registry.verifying_key(prepared_inputs.circuit_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyProofPrepared<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"prepared_inputs", signer.key().as_ref()],
+        bump
+    )]
+    pub prepared_inputs: Account<'info, PreparedInputs>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        constraint = circuit_registry.verifying_key(prepared_inputs.circuit_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPreparedInputs<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"prepared_inputs", signer.key().as_ref()],
+        bump
+    )]
+    pub prepared_inputs: Account<'info, PreparedInputs>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBatch<'info> {
+    #[account(mut)]
+    pub sequencer: Signer<'info>,
+    #[account(
+        mut,
+        close = sequencer,
+        seeds = [b"batch_staging", sequencer.key().as_ref()],
+        bump
+    )]
+    pub batch_staging: Account<'info, BatchStaging>,
+}
+
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct VerifyProof<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        constraint = circuit_registry.verifying_key(circuit_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVerifyingKey<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        init,
+        payer = authority,
+        space = VerifyingKeyAccount::SPACE,
+        seeds = [b"verifying_key"],
+        bump
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct UploadVerifyingKey<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        mut,
+        constraint = circuit_registry.verifying_key(circuit_id)
+            == Some(verifying_key.key()) @ VerifierError::UnknownCircuit
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeCircuitRegistry<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"verifying_key"],
+        bump
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    #[account(
+        init,
+        payer = authority,
+        space = CircuitRegistry::SPACE,
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVerifierStats<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        init,
+        payer = authority,
+        space = VerifierStats::SPACE,
+        seeds = [b"verifier_stats"],
+        bump
+    )]
+    pub verifier_stats: Account<'info, VerifierStats>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RollupHealthView<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        seeds = [b"verifier_stats"],
+        bump
+    )]
+    pub verifier_stats: Account<'info, VerifierStats>,
+}
+
+#[derive(Accounts)]
+pub struct SnapshotEpoch<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_stats"],
+        bump
+    )]
+    pub verifier_stats: Account<'info, VerifierStats>,
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct SyncVaultBalance<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    pub sequencer: Signer<'info>,
+    /// CHECK: Registration PDA of the sequencer; checked in the handler
+    #[account(
+        seeds = [b"sequencer", sequencer.key().as_ref()],
+        bump
+    )]
+    pub sequencer_registration: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = batch_record.sequencer == sequencer.key()
+            && batch_record.status == BatchRecordStatus::Settled @ VerifierError::BatchNotSettled
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    /// CHECK: Signer PDA for vault calls; holds no data
+    #[account(
+        seeds = [vault_cpi::VAULT_CALLER_SEED],
+        bump
+    )]
+    pub vault_caller: UncheckedAccount<'info>,
+    /// CHECK: Checked against the synced bet's user; validated by the vault program
+    #[account(mut)]
+    pub user_vault: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    #[account(mut)]
+    pub vault_state: UncheckedAccount<'info>,
+    /// CHECK: House bankroll; validated by the vault program
+    #[account(mut)]
+    pub house_vault: UncheckedAccount<'info>,
+    /// CHECK: Protocol fee vault, credited the fee on wins; validated by the vault program
+    #[account(mut)]
+    pub fee_vault: UncheckedAccount<'info>,
+    /// CHECK: The vault this verifier is linked to
+    #[account(
+        executable,
+        address = verifier_state.vault_program @ VerifierError::InvalidVaultProgram
+    )]
+    pub vault_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct ReleaseVaultLock<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    pub sequencer: Signer<'info>,
+    /// CHECK: Registration PDA of the sequencer; checked in the handler
+    #[account(
+        seeds = [b"sequencer", sequencer.key().as_ref()],
+        bump
+    )]
+    pub sequencer_registration: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"batch", batch_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = batch_record.sequencer == sequencer.key()
+    )]
+    pub batch_record: Account<'info, BatchRecord>,
+    /// CHECK: Signer PDA for vault calls; holds no data
+    #[account(
+        seeds = [vault_cpi::VAULT_CALLER_SEED],
+        bump
+    )]
+    pub vault_caller: UncheckedAccount<'info>,
+    /// CHECK: The bet's lock record; validated by the vault program
+    #[account(mut)]
+    pub bet_lock: UncheckedAccount<'info>,
+    /// CHECK: Checked against the released bet's user; validated by the vault program
+    #[account(mut)]
+    pub user_vault: UncheckedAccount<'info>,
+    /// CHECK: Validated by the vault program
+    pub vault_state: UncheckedAccount<'info>,
+    /// CHECK: Vault authority, refunded the lock's rent; validated by the vault program
+    #[account(mut)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: The vault this verifier is linked to
+    #[account(
+        executable,
+        address = verifier_state.vault_program @ VerifierError::InvalidVaultProgram
+    )]
+    pub vault_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(circuit_id: u8)]
+pub struct RegisterCircuit<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Account<'info, CircuitRegistry>,
+    #[account(
+        init,
+        payer = authority,
+        space = VerifyingKeyAccount::SPACE,
+        seeds = [b"verifying_key", [circuit_id].as_ref()],
+        bump
+    )]
+    pub verifying_key: Account<'info, VerifyingKeyAccount>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sequencer: Pubkey)]
+pub struct RegisterSequencer<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        init,
+        payer = authority,
+        space = SequencerRegistration::SPACE,
+        seeds = [b"sequencer", sequencer.as_ref()],
+        bump
+    )]
+    pub sequencer_registration: Account<'info, SequencerRegistration>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(current_sequencer: Pubkey, next_sequencer: Pubkey)]
+pub struct RotateSequencer<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"sequencer", current_sequencer.as_ref()],
+        bump
+    )]
+    pub current_registration: Account<'info, SequencerRegistration>,
+    #[account(
+        init,
+        payer = authority,
+        space = SequencerRegistration::SPACE,
+        seeds = [b"sequencer", next_sequencer.as_ref()],
+        bump
+    )]
+    pub next_registration: Account<'info, SequencerRegistration>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(sequencer: Pubkey)]
+pub struct RemoveSequencer<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"sequencer", sequencer.as_ref()],
+        bump
+    )]
+    pub sequencer_registration: Account<'info, SequencerRegistration>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseFlags<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u8)]
+pub struct InitializeGameConfig<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        init,
+        payer = authority,
+        space = GameConfig::SPACE,
+        seeds = [b"game_config", [game_id].as_ref()],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(game_id: u8)]
+pub struct UpdateGameConfig<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"game_config", [game_id].as_ref()],
+        bump
+    )]
+    pub game_config: Account<'info, GameConfig>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QueueAdminAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        init,
+        payer = authority,
+        space = PendingAdminAction::SPACE,
+        seeds = [b"admin_action", verifier_state.next_admin_action_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub admin_action: Account<'info, PendingAdminAction>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(action_id: u64)]
+pub struct ExecuteAdminAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"admin_action", action_id.to_le_bytes().as_ref()],
+        bump,
+        close = authority
+    )]
+    pub admin_action: Account<'info, PendingAdminAction>,
+    /// Only needed to rotate a verifying key
+    #[account(
+        seeds = [b"circuit_registry"],
+        bump
+    )]
+    pub circuit_registry: Option<Account<'info, CircuitRegistry>>,
+    /// Only needed to rotate a verifying key; must be the circuit's key account
+    #[account(mut)]
+    pub verifying_key: Option<Account<'info, VerifyingKeyAccount>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(action_id: u64)]
+pub struct CancelAdminAction<'info> {
+    #[account(
+        seeds = [b"verifier_state"],
+        bump,
+        has_one = authority
+    )]
+    pub verifier_state: Account<'info, VerifierState>,
+    #[account(
+        mut,
+        seeds = [b"admin_action", action_id.to_le_bytes().as_ref()],
+        bump,
+        close = authority
+    )]
+    pub admin_action: Account<'info, PendingAdminAction>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+// Events
+#[event]
+pub struct BetSettlementEvent {
+    pub bet_id: u64,
+    pub user: Pubkey,
+    pub bet_amount: u64,
+    pub user_guess: u8,
+    pub outcome: u8,
+    pub payout: u64,
+    pub is_win: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BatchSettlementEvent {
+    pub batch_id: u64,
+    pub sequencer: Pubkey,
+    pub batch_size: u32,
+    pub house_delta: i64,
+    pub proof_hash: [u8; 32],
+    pub settlement_timestamp: i64,
+    pub prev_state_root: [u8; 32],
+    pub new_state_root: [u8; 32],
+    pub vrf_pubkey: Pubkey,
+    pub vrf_outputs_root: [u8; 32],
+}
+
+#[event]
+pub struct EpochSnapshotEvent {
+    pub epoch: u64,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub stats: EpochStats,
+}
+
+#[event]
+pub struct OptimisticBatchSubmittedEvent {
+    pub batch_id: u64,
+    pub sequencer: Pubkey,
+    pub batch_size: u32,
+    pub house_delta: i64,
+    pub bets_root: [u8; 32],
+    pub prev_state_root: [u8; 32],
+    pub new_state_root: [u8; 32],
+    pub challenge_deadline: i64,
+}
+
+#[event]
+pub struct BatchChallengedEvent {
+    pub batch_id: u64,
+    pub challenger: Pubkey,
+    pub fraud: FraudKind,
+    pub bet_index: u32,
+    pub rolled_back: bool, // False if an earlier challenge already discarded the batch
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ChallengeResolvedEvent {
+    pub batch_id: u64, // Lowest batch challenged
+    pub state_root: [u8; 32],
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProofVerificationEvent {
+    pub proof_hash: [u8; 32],
+    pub circuit_id: u8,
+    pub verifier: Pubkey,
+    pub is_valid: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PreparedProofVerificationEvent {
+    pub proof_hash: [u8; 32],
+    pub inputs_hash: [u8; 32], // PreparedInputs::inputs_hash of the verified inputs
+    pub verifier: Pubkey,
+    pub is_valid: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SequencerRegisteredEvent {
+    pub sequencer: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SequencerRotationEvent {
+    pub current_sequencer: Pubkey,
+    pub next_sequencer: Pubkey,
+    pub activates_at: i64, // The new key settles from here
+    pub retires_at: i64,   // The old key stops settling here
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct SequencerRemovedEvent {
+    pub sequencer: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CircuitRegisteredEvent {
+    pub circuit_id: u8,
+    pub verifying_key: Pubkey,
+    pub game_ids: Vec<u8>,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VerifyingKeyRotatedEvent {
+    pub circuit_id: u8,
+    pub version: u32,
+    pub key_hash: [u8; 32],
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameConfigUpdatedEvent {
+    pub game_id: u8,
+    pub payout_multiplier_bps: u32,
+    pub house_edge_bps: u16,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityProposedEvent {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferredEvent {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseFlagsUpdatedEvent {
+    pub pause_flags: u8,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminActionQueuedEvent {
+    pub action_id: u64,
+    pub action: AdminAction,
+    pub executable_at: i64,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct AdminActionExecutedEvent {
+    pub action_id: u64,
+    pub action: AdminAction,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminActionCancelledEvent {
+    pub action_id: u64,
+    pub authority: Pubkey,
     pub timestamp: i64,
 }
 
-/// Compute the batch hash for use as public input to the ZK circuit
+/// Only allowlisted sequencers may settle. The registration address is fixed by its
+/// seeds, so an account there owned by this program is a registration; it must also
+/// be inside its validity window, which a key rotation opens and closes.
+fn require_registered(sequencer_registration: &AccountInfo) -> Result<()> {
+    require!(
+        sequencer_registration.owner == &crate::ID && !sequencer_registration.data_is_empty(),
+        VerifierError::InvalidSequencer
+    );
+    let registration =
+        SequencerRegistration::try_deserialize(&mut &sequencer_registration.try_borrow_data()?[..])
+            .map_err(|_| VerifierError::InvalidSequencer)?;
+    require!(
+        registration.is_active(Clock::get()?.unix_timestamp),
+        VerifierError::SequencerKeyInactive
+    );
+    Ok(())
+}
+
+/// Verify a batch's proof and arithmetic against its game's payout schedule, record it
+/// and advance the verifier state. Shared by verify_and_settle and the chunked
+/// finalize_and_verify; callers check the sequencer's registration first.
+#[allow(clippy::too_many_arguments)]
+fn settle_batch(
+    verifier_state: &mut VerifierState,
+    verifying_key: &VerifyingKeyAccount,
+    game_config: &GameConfig,
+    batch_record: &mut BatchRecord,
+    verifier_stats: &mut VerifierStats,
+    sequencer: Pubkey,
+    batch_data: &BatchSettlementData,
+    proof: &[u8],
+) -> Result<()> {
+    verifier_state.require_unpaused(PAUSE_SETTLEMENT)?;
+    require!(!batch_data.bets.is_empty(), VerifierError::EmptyBatch);
+    require!(
+        batch_data.bets.len() <= MAX_BATCH_SIZE,
+        VerifierError::BatchTooLarge
+    );
+    require!(!proof.is_empty(), VerifierError::EmptyProof);
+    require_unique_bet_ids(&batch_data.bets)?;
+
+    verifier_state.check_batch_order(batch_data.batch_id, batch_data.sequencer_nonce)?;
+    require!(
+        batch_data.prev_state_root == verifier_state.state_root,
+        VerifierError::StateRootMismatch
+    );
+
+    // Phase 3d: Real ZK proof verification using Groth16 and BN254 syscalls
+    msg!(
+        "Verifying batch with {} bets using Groth16 proof ({} bytes)",
+        batch_data.bets.len(),
+        proof.len()
+    );
+    msg!("Using verifying key version {}", verifying_key.version);
+    verify_batch_proof(&verifying_key.active, batch_data, proof)?;
+
+    // Validate batch arithmetic, committing to each bet with its running house delta
+    // so vault syncs can be checked against the record
+    let mut total_house_delta: i64 = 0;
+    let mut leaves = Vec::with_capacity(batch_data.bets.len());
+    for (index, bet_settlement) in batch_data.bets.iter().enumerate() {
+        total_house_delta = total_house_delta
+            .checked_add(check_bet(game_config, bet_settlement)?)
+            .ok_or(VerifierError::MathOverflow)?;
+        leaves.push(
+            BatchLeaf {
+                index: index as u32,
+                bet: bet_settlement.clone(),
+                house_delta: total_house_delta,
+            }
+            .hash(),
+        );
+    }
+
+    // Emit settlement event for each bet
+    for bet_settlement in &batch_data.bets {
+        emit!(BetSettlementEvent {
+            bet_id: bet_settlement.bet_id,
+            user: bet_settlement.user,
+            bet_amount: bet_settlement.bet_amount,
+            user_guess: bet_settlement.user_guess,
+            outcome: bet_settlement.outcome,
+            payout: bet_settlement.payout,
+            is_win: bet_settlement.outcome == bet_settlement.user_guess,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+
+    // Record the batch on-chain so indexers and reconciliation don't depend on events
+    let parent_batch_id = verifier_state.advance_head(batch_data.batch_id, false)?;
+    let proof_hash = hash::hash(proof).to_bytes();
+    *batch_record = BatchRecord {
+        batch_id: batch_data.batch_id,
+        sequencer,
+        proof_hash,
+        state_root: batch_data.new_state_root,
+        bet_count: batch_data.bets.len() as u32,
+        house_delta: total_house_delta,
+        slot: Clock::get()?.slot,
+        status: BatchRecordStatus::Settled,
+        bets_root: optimistic::bets_root(&leaves),
+        synced: [0; SYNCED_BITMAP_LEN],
+        parent_batch_id,
+    };
+
+    // Emit batch settlement event
+    emit!(BatchSettlementEvent {
+        batch_id: batch_data.batch_id,
+        sequencer,
+        batch_size: batch_data.bets.len() as u32,
+        house_delta: total_house_delta,
+        proof_hash,
+        settlement_timestamp: Clock::get()?.unix_timestamp,
+        prev_state_root: batch_data.prev_state_root,
+        new_state_root: batch_data.new_state_root,
+        vrf_pubkey: batch_data.vrf_pubkey,
+        vrf_outputs_root: batch_data.vrf_outputs_root,
+    });
+
+    // Update verifier state
+    verifier_state.total_batches_processed = verifier_state
+        .total_batches_processed
+        .checked_add(1)
+        .ok_or(VerifierError::MathOverflow)?;
+
+    verifier_state.total_bets_settled = verifier_state
+        .total_bets_settled
+        .checked_add(batch_data.bets.len() as u64)
+        .ok_or(VerifierError::MathOverflow)?;
+    verifier_state.record_batch(batch_data.batch_id, batch_data.sequencer_nonce);
+    verifier_state.state_root = batch_data.new_state_root;
+    verifier_stats.record_batch(&batch_data.bets, total_house_delta)?;
+
+    msg!(
+        "Batch {} settled successfully: {} bets, house delta: {}",
+        batch_data.batch_id,
+        batch_data.bets.len(),
+        total_house_delta
+    );
+
+    Ok(())
+}
+
+/// Reject a batch that settles any bet twice. Bets keep their batch order, which the
+/// VRF and state roots commit to, so ids are compared on a sorted copy.
+fn require_unique_bet_ids(bets: &[BetSettlement]) -> Result<()> {
+    let mut bet_ids: Vec<u64> = bets.iter().map(|bet| bet.bet_id).collect();
+    bet_ids.sort_unstable();
+    require!(
+        bet_ids.windows(2).all(|pair| pair[0] != pair[1]),
+        VerifierError::DuplicateBetInBatch
+    );
+    Ok(())
+}
+
+/// Check a bet against its game's payout schedule; returns its house delta (stake minus
+/// payout, negative when the user wins)
+pub(crate) fn check_bet(game_config: &GameConfig, bet_settlement: &BetSettlement) -> Result<i64> {
+    require!(
+        bet_settlement.bet_amount > 0,
+        VerifierError::InvalidBetAmount
+    );
+
+    // Validate outcome is boolean (0 or 1)
+    require!(
+        bet_settlement.outcome == 0 || bet_settlement.outcome == 1,
+        VerifierError::InvalidOutcome
+    );
+
+    // A guess outside 0 or 1 can never win, so it would otherwise pass as a loss
+    require!(
+        bet_settlement.user_guess == 0 || bet_settlement.user_guess == 1,
+        VerifierError::InvalidGuess
+    );
+
+    // Calculate payout based on outcome and bet amount. Payouts are integer base
+    // units rounded down, the same rule the sequencer uses; sub-unit residue is
+    // never paid out
+    let expected_payout = if bet_settlement.outcome == bet_settlement.user_guess {
+        game_config.win_payout(bet_settlement.bet_amount)?
+    } else {
+        0 // Loss: no payout
+    };
+
+    require!(
+        bet_settlement.payout == expected_payout,
+        VerifierError::InvalidPayout
+    );
+
+    // Amounts are untrusted; the house delta has to fit an i64
+    let bet_amount =
+        i64::try_from(bet_settlement.bet_amount).map_err(|_| VerifierError::InvalidBetAmount)?;
+    let payout = i64::try_from(bet_settlement.payout).map_err(|_| VerifierError::MathOverflow)?;
+    Ok(bet_amount
+        .checked_sub(payout)
+        .ok_or(VerifierError::MathOverflow)?)
+}
+
+/// Check a batch's Groth16 proof against a verifying key, with batch_public_inputs as
+/// the public inputs. Needs no accounts, so settlement co-signers can run the same check
+/// off-chain before signing.
+pub fn verify_batch_proof(
+    verifying_key: &[u8],
+    batch_data: &BatchSettlementData,
+    proof: &[u8],
+) -> Result<()> {
+    // Parse and verify the Groth16 proof
+    let groth16_proof =
+        Groth16Proof::from_bytes(proof).map_err(|_| VerifierError::InvalidProofFormat)?;
+
+    let verifying_key =
+        parse_verifying_key(verifying_key).map_err(|_| VerifierError::InvalidVerifyingKey)?;
+
+    let public_inputs = batch_public_inputs(batch_data)?;
+    verifying_key
+        .validate_public_inputs(&public_inputs)
+        .map_err(|_| VerifierError::InvalidPublicInputs)?;
+
+    // Perform Groth16 verification
+    let verification_result = verify_groth16_proof(&groth16_proof, &verifying_key, &public_inputs);
+
+    match verification_result {
+        Ok(true) => {
+            msg!("✓ Groth16 proof verification successful");
+        }
+        Ok(false) => {
+            msg!("✗ Groth16 proof verification failed: invalid proof");
+            return Err(VerifierError::InvalidProof.into());
+        }
+        Err(e) => {
+            msg!("✗ Groth16 proof verification error: {:?}", e);
+            return Err(VerifierError::ProofVerificationFailed.into());
+        }
+    }
+    Ok(())
+}
+
+/// Public inputs of a batch's settlement proof, as 32-byte big-endian scalars: the batch
+/// id, the commitment the circuit computes over the bets it settled, and the balance
+/// state roots before and after the batch, so the proof attests to this state
+/// transition for these bets. The prover's AccountingCircuit allocates them in this
+/// order; the rest of the header is bound by the sequencer's signature over the batch
+/// hash.
+pub fn batch_public_inputs(batch_data: &BatchSettlementData) -> Result<Vec<[u8; 32]>> {
+    let mut batch_id = [0u8; 32];
+    batch_id[24..].copy_from_slice(&batch_data.batch_id.to_be_bytes());
+    Ok(vec![
+        batch_id,
+        compute_batch_commitment(&batch_data.bets)?,
+        batch_data.prev_state_root,
+        batch_data.new_state_root,
+    ])
+}
+
+/// Compute the hash the sequencer signs over a batch
 fn compute_batch_hash(batch_data: &BatchSettlementData) -> [u8; 32] {
     // Serialize batch data for hashing
     let mut hasher_data = Vec::new();
 
-    // Add batch metadata
+    // Add batch metadata, including the nonce, game and state transition the sequencer
+    // signs for, so none of them can be swapped under its signature
     hasher_data.extend_from_slice(&batch_data.batch_id.to_le_bytes());
+    hasher_data.extend_from_slice(&batch_data.sequencer_nonce.to_le_bytes());
+    hasher_data.push(batch_data.game_id);
     hasher_data.extend_from_slice(&(batch_data.bets.len() as u32).to_le_bytes());
+    hasher_data.extend_from_slice(&batch_data.prev_state_root);
+    hasher_data.extend_from_slice(&batch_data.new_state_root);
+
+    // Bind the randomness commitment, so the proof covers where the outcomes came from
+    hasher_data.extend_from_slice(&batch_data.vrf_pubkey.to_bytes());
+    hasher_data.extend_from_slice(&batch_data.vrf_outputs_root);
 
     // Add each bet settlement data
     for bet in &batch_data.bets {
@@ -397,9 +3361,43 @@ fn compute_batch_hash(batch_data: &BatchSettlementData) -> [u8; 32] {
         hasher_data.extend_from_slice(&bet.payout.to_le_bytes());
     }
 
-    // Hash the serialized data
-    let hash_result = hash::hash(&hasher_data);
-    hash_result.to_bytes()
+    // Hash the serialized data, clearing the top bits so it is a BN254 scalar
+    let mut hash_result = hash::hash(&hasher_data).to_bytes();
+    hash_result[0] &= 0x1f;
+    hash_result
+}
+
+/// Poseidon commitment to the bets, as the settlement circuit computes it from its
+/// witness: Poseidon(commitment so far, Poseidon(bet fields)) over the bets in order,
+/// starting from zero. Each bet field is a big-endian scalar, the user key split into
+/// its two 16-byte halves.
+fn compute_batch_commitment(bets: &[BetSettlement]) -> Result<[u8; 32]> {
+    fn scalar(bytes: &[u8]) -> [u8; 32] {
+        let mut scalar = [0u8; 32];
+        scalar[32 - bytes.len()..].copy_from_slice(bytes);
+        scalar
+    }
+    let poseidon = |inputs: &[&[u8]]| {
+        poseidon::hashv(Parameters::Bn254X5, Endianness::BigEndian, inputs)
+            .map(|hash| hash.to_bytes())
+            .map_err(|_| error!(VerifierError::InvalidPublicInputs))
+    };
+
+    let mut commitment = [0u8; 32];
+    for bet in bets {
+        let user = bet.user.to_bytes();
+        let leaf = poseidon(&[
+            &scalar(&bet.bet_id.to_be_bytes()),
+            &scalar(&user[..16]),
+            &scalar(&user[16..]),
+            &scalar(&bet.bet_amount.to_be_bytes()),
+            &scalar(&[bet.user_guess]),
+            &scalar(&[bet.outcome]),
+            &scalar(&bet.payout.to_be_bytes()),
+        ])?;
+        commitment = poseidon(&[&commitment, &leaf])?;
+    }
+    Ok(commitment)
 }
 
 // Error codes
@@ -437,6 +3435,84 @@ pub enum VerifierError {
     InvalidProof,
     #[msg("Invalid verifying key")]
     InvalidVerifyingKey,
+    #[msg("Too many public inputs")]
+    TooManyPublicInputs,
+    #[msg("Public inputs do not match the verifying key")]
+    InvalidPublicInputs,
+    #[msg("Verifying key chunk is out of order or exceeds the declared length")]
+    InvalidVerifyingKeyUpload,
+    #[msg("Staged verifying key is incomplete")]
+    VerifyingKeyUploadIncomplete,
+    #[msg("Batch id or sequencer nonce has already been settled")]
+    BatchReplayed,
+    #[msg("Batch does not start from the current state root")]
+    StateRootMismatch,
+    #[msg("Batch chunk is empty, out of order or exceeds the declared bet count")]
+    InvalidBatchChunk,
+    #[msg("Not all bets of the staged batch have been appended")]
+    BatchStagingIncomplete,
+    #[msg("Payout multiplier must be at least 1x and house edge at most 10%")]
+    InvalidGameConfig,
+    #[msg("Signer is not the pending authority")]
+    NotPendingAuthority,
+    #[msg("Admin action timelock has not elapsed")]
+    AdminActionTimelocked,
+    #[msg("Admin timelock must be between 1 hour and 30 days")]
+    InvalidTimelockDelay,
+    #[msg("Unknown pause flag bits")]
+    InvalidPauseFlags,
+    #[msg("Not all public inputs have been prepared")]
+    PreparedInputsIncomplete,
+    #[msg("Challenge window of the optimistic batch has closed")]
+    ChallengeWindowClosed,
+    #[msg("Challenge window of the optimistic batch is still open")]
+    ChallengeWindowOpen,
+    #[msg("Fraud proof leaf is not part of the batch")]
+    InvalidFraudProof,
+    #[msg("Fraud proof does not show invalid accounting")]
+    ChallengeRejected,
+    #[msg("Circuit is not registered for this verifying key or game")]
+    UnknownCircuit,
+    #[msg("Circuit id is already registered")]
+    CircuitAlreadyRegistered,
+    #[msg("Batch is not signed by the sequencer")]
+    InvalidBatchSignature,
+    #[msg("Batch settles the same bet more than once")]
+    DuplicateBetInBatch,
+    #[msg("Circuit registry is full or the circuit's game list is empty or too long")]
+    InvalidCircuitRegistration,
+    #[msg("Stats epoch has not run its full length")]
+    StatsEpochOpen,
+    #[msg("Batch is not settled or was settled by another sequencer")]
+    BatchNotSettled,
+    #[msg("Vault program is not the one linked to this verifier")]
+    InvalidVaultProgram,
+    #[msg("Sequencer key is already being rotated out or is not active")]
+    SequencerRotationPending,
+    #[msg("Sequencer key is not yet active or has been rotated out")]
+    SequencerKeyInactive,
+    #[msg("Settlement is not signed by the configured co-signer")]
+    MissingCoSigner,
+    #[msg("Bet is not part of the settled batch")]
+    BetNotInBatch,
+    #[msg("Bet has already been applied to its user's vault")]
+    BetAlreadySynced,
+    #[msg("User vault does not belong to the bet's user")]
+    UserVaultMismatch,
+    #[msg("Batch builds on an optimistic batch that has not been finalized")]
+    OptimisticBatchPending,
+    #[msg("Parent batch has not settled")]
+    ParentBatchNotSettled,
+    #[msg("No challenge is pending")]
+    NoChallengePending,
+    #[msg("Parent batch record is missing or not the batch's parent")]
+    ParentBatchMismatch,
+    #[msg("Settlement stays paused until the pending challenge is resolved")]
+    ChallengeUnresolved,
+    #[msg("Bet's batch has neither settled and synced it nor been rejected")]
+    BetLockNotReleasable,
+    #[msg("Invalid guess value")]
+    InvalidGuess,
 }
 
 #[cfg(test)]
@@ -471,11 +3547,1150 @@ mod tests {
     #[test]
     fn test_batch_size_constraints() {
         // Validate constants are reasonable
-        assert!(MAX_BATCH_SIZE > 0, "MAX_BATCH_SIZE must be positive");
-        assert!(
+        const _: () = assert!(MAX_BATCH_SIZE > 0, "MAX_BATCH_SIZE must be positive");
+        const _: () = assert!(
             MAX_BATCH_SIZE <= 1000,
             "MAX_BATCH_SIZE should be reasonable"
         );
-        assert!(MAX_PROOF_SIZE > 0, "MAX_PROOF_SIZE must be positive");
+        const _: () = assert!(MAX_PROOF_SIZE > 0, "MAX_PROOF_SIZE must be positive");
+        const _: () = assert!(
+            MAX_PUBLIC_INPUTS * 32 < MAX_PROOF_SIZE,
+            "MAX_PUBLIC_INPUTS should fit in a transaction"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_bets_in_batch_rejected() {
+        let bet = |bet_id| BetSettlement {
+            bet_id,
+            user: Pubkey::new_unique(),
+            bet_amount: 1000,
+            user_guess: 1,
+            outcome: 1,
+            payout: 2000,
+        };
+        assert!(require_unique_bet_ids(&[bet(3), bet(1), bet(2)]).is_ok());
+        // Repeats are caught wherever they sit in the batch
+        assert_eq!(
+            require_unique_bet_ids(&[bet(3), bet(1), bet(2), bet(3)]).unwrap_err(),
+            VerifierError::DuplicateBetInBatch.into()
+        );
+    }
+
+    #[test]
+    fn test_verifier_stats_epochs() {
+        let bet = |bet_amount, payout| BetSettlement {
+            bet_id: 0,
+            user: Pubkey::new_unique(),
+            bet_amount,
+            user_guess: 1,
+            outcome: u8::from(payout > 0),
+            payout,
+        };
+        let mut stats = VerifierStats {
+            epoch: 0,
+            epoch_started_at: 1_000,
+            current: EpochStats::default(),
+            previous: EpochStats::default(),
+        };
+        stats
+            .record_batch(&[bet(1000, 2000), bet(500, 0), bet(300, 0)], -200)
+            .unwrap();
+        stats.record_batch(&[bet(700, 0)], 700).unwrap();
+        assert_eq!(
+            stats.current,
+            EpochStats {
+                bets_settled: 4,
+                total_wagered: 2500,
+                total_paid_out: 2000,
+                house_pnl: 500,
+                largest_batch: 3,
+            }
+        );
+
+        // Epochs can't be cut short
+        assert_eq!(
+            stats.roll(1_000 + STATS_EPOCH_LENGTH - 1).unwrap_err(),
+            VerifierError::StatsEpochOpen.into()
+        );
+        let closed = stats.roll(1_000 + STATS_EPOCH_LENGTH).unwrap();
+        assert_eq!(closed.house_pnl, 500);
+        assert_eq!(stats.previous, closed);
+        assert_eq!(stats.current, EpochStats::default());
+        assert_eq!(stats.epoch, 1);
+        assert_eq!(stats.epoch_started_at, 1_000 + STATS_EPOCH_LENGTH);
+    }
+
+    #[test]
+    fn test_rollup_health_layout() {
+        let state = VerifierState {
+            authority: Pubkey::default(),
+            vault_program: Pubkey::new_unique(),
+            total_batches_processed: 3,
+            total_bets_settled: 40,
+            pause_flags: PAUSE_VERIFICATION,
+            last_settled_batch_id: 7,
+            last_sequencer_nonce: 9,
+            state_root: [5; 32],
+            pending_authority: None,
+            admin_timelock: DEFAULT_ADMIN_TIMELOCK,
+            next_admin_action_id: 0,
+            co_signer: None,
+            head_batch_id: 0,
+            head_optimistic: false,
+            challenged_batch_id: None,
+            challenge_resolvable_at: 0,
+        };
+        let stats = VerifierStats {
+            epoch: 2,
+            epoch_started_at: 0,
+            current: EpochStats {
+                bets_settled: 40,
+                ..EpochStats::default()
+            },
+            previous: EpochStats::default(),
+        };
+
+        // Readers decode by offset, so the documented order must not move
+        let data = state.health(&stats).try_to_vec().unwrap();
+        assert_eq!(data[0], ROLLUP_HEALTH_VERSION);
+        assert_eq!(&data[1..33], &[5; 32]);
+        assert_eq!(&data[33..41], &7u64.to_le_bytes());
+        assert_eq!(&data[41..49], &9u64.to_le_bytes());
+        assert_eq!(&data[49..57], &3u64.to_le_bytes());
+        assert_eq!(&data[57..65], &40u64.to_le_bytes());
+        assert_eq!(data[65], PAUSE_VERIFICATION);
+        assert_eq!(&data[66..98], state.vault_program.as_ref());
+        assert_eq!(&data[98..106], &2u64.to_le_bytes());
+        assert_eq!(data.len(), 106 + EpochStats::SIZE);
+    }
+
+    #[test]
+    fn test_batch_replay_rejected() {
+        let mut state = VerifierState {
+            authority: Pubkey::default(),
+            vault_program: Pubkey::default(),
+            total_batches_processed: 0,
+            total_bets_settled: 0,
+            pause_flags: 0,
+            last_settled_batch_id: 0,
+            last_sequencer_nonce: 0,
+            state_root: [0; 32],
+            pending_authority: None,
+            admin_timelock: DEFAULT_ADMIN_TIMELOCK,
+            next_admin_action_id: 0,
+            co_signer: None,
+            head_batch_id: 0,
+            head_optimistic: false,
+            challenged_batch_id: None,
+            challenge_resolvable_at: 0,
+        };
+        assert!(state.check_batch_order(1, 1).is_ok());
+        state.record_batch(1, 1);
+
+        // Duplicate, older and nonce-reusing submissions are all replays
+        for (batch_id, nonce) in [(1, 1), (0, 2), (2, 1)] {
+            assert_eq!(
+                state.check_batch_order(batch_id, nonce).unwrap_err(),
+                VerifierError::BatchReplayed.into()
+            );
+        }
+        // Gaps are fine; batches only have to move forward
+        assert!(state.check_batch_order(5, 3).is_ok());
+    }
+
+    #[test]
+    fn test_challenge_rollback() {
+        let mut state = VerifierState {
+            authority: Pubkey::default(),
+            vault_program: Pubkey::default(),
+            total_batches_processed: 0,
+            total_bets_settled: 0,
+            pause_flags: 0,
+            last_settled_batch_id: 0,
+            last_sequencer_nonce: 0,
+            state_root: [0; 32],
+            pending_authority: None,
+            admin_timelock: DEFAULT_ADMIN_TIMELOCK,
+            next_admin_action_id: 0,
+            co_signer: None,
+            head_batch_id: 0,
+            head_optimistic: false,
+            challenged_batch_id: None,
+            challenge_resolvable_at: 0,
+        };
+        let record = |batch_id, parent_batch_id, status| BatchRecord {
+            batch_id,
+            sequencer: Pubkey::default(),
+            proof_hash: [0; 32],
+            state_root: [batch_id as u8; 32],
+            bet_count: 1,
+            house_delta: 0,
+            slot: 0,
+            status,
+            bets_root: [0; 32],
+            synced: [0; SYNCED_BITMAP_LEN],
+            parent_batch_id,
+        };
+        let claim = |batch_id: u64, prev_root: u8| OptimisticBatchClaim {
+            batch_id,
+            sequencer_nonce: batch_id,
+            game_id: GAME_COINFLIP,
+            prev_state_root: [prev_root; 32],
+            new_state_root: [batch_id as u8; 32],
+            vrf_pubkey: Pubkey::default(),
+            vrf_outputs_root: [0; 32],
+            bets_root: [0; 32],
+            bet_count: 1,
+            house_delta: 0,
+        };
+
+        // Proven batch 1, then optimistic 2 and 3 on top of it; a proof can't build on
+        // an optimistic batch until it is finalized
+        assert_eq!(state.advance_head(1, false).unwrap(), 0);
+        assert_eq!(state.advance_head(2, true).unwrap(), 1);
+        assert_eq!(state.advance_head(3, true).unwrap(), 2);
+        assert_eq!(
+            state.advance_head(4, false).unwrap_err(),
+            VerifierError::OptimisticBatchPending.into()
+        );
+        state.state_root = [3; 32];
+        let settled = record(1, 0, BatchRecordStatus::Settled);
+        let mut challenged = record(2, 1, BatchRecordStatus::Optimistic);
+        let descendant = record(3, 2, BatchRecordStatus::Optimistic);
+        assert_eq!(challenged.parent_optimistic(Some(&settled)), Ok(false));
+        assert_eq!(descendant.parent_optimistic(Some(&challenged)), Ok(true));
+        assert_eq!(
+            descendant.parent_optimistic(Some(&settled)).unwrap_err(),
+            VerifierError::ParentBatchMismatch.into()
+        );
+
+        // Challenging batch 2 takes the chain back to batch 1's root and pauses
+        assert!(state
+            .roll_back(&challenged, &claim(2, 1), false, 1_000)
+            .unwrap());
+        challenged.status = BatchRecordStatus::Challenged;
+        assert_eq!(state.state_root, [1; 32]);
+        assert_eq!((state.head_batch_id, state.head_optimistic), (1, false));
+        assert!(state.require_unpaused(PAUSE_SETTLEMENT).is_err());
+
+        // Batch 3 was discarded with it: challenging it moves nothing, and it never
+        // finalizes on a challenged parent
+        assert!(!state
+            .roll_back(&descendant, &claim(3, 2), true, 2_000)
+            .unwrap());
+        assert_eq!(state.state_root, [1; 32]);
+        assert_eq!(
+            descendant
+                .require_parent_settled(Some(&challenged))
+                .unwrap_err(),
+            VerifierError::ParentBatchNotSettled.into()
+        );
+        assert!(challenged.require_parent_settled(Some(&settled)).is_ok());
+
+        // The pause lifts once the discarded batches can no longer be challenged
+        assert_eq!(
+            state
+                .resolve_challenge(1_000 + OPTIMISTIC_CHALLENGE_WINDOW - 1)
+                .unwrap_err(),
+            VerifierError::ChallengeWindowOpen.into()
+        );
+        assert_eq!(
+            state
+                .resolve_challenge(1_000 + OPTIMISTIC_CHALLENGE_WINDOW)
+                .unwrap(),
+            2
+        );
+        assert!(state.require_unpaused(PAUSE_SETTLEMENT).is_ok());
+        assert_eq!(
+            state.resolve_challenge(i64::MAX).unwrap_err(),
+            VerifierError::NoChallengePending.into()
+        );
+
+        // Settlement resumes on batch 1
+        assert_eq!(state.advance_head(4, false).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_authority_transfer() {
+        let authority = Pubkey::new_unique();
+        let nominee = Pubkey::new_unique();
+        let mut state = VerifierState {
+            authority,
+            vault_program: Pubkey::default(),
+            total_batches_processed: 0,
+            total_bets_settled: 0,
+            pause_flags: 0,
+            last_settled_batch_id: 0,
+            last_sequencer_nonce: 0,
+            state_root: [0; 32],
+            pending_authority: None,
+            admin_timelock: DEFAULT_ADMIN_TIMELOCK,
+            next_admin_action_id: 0,
+            co_signer: None,
+            head_batch_id: 0,
+            head_optimistic: false,
+            challenged_batch_id: None,
+            challenge_resolvable_at: 0,
+        };
+        let not_pending = |result: Result<Pubkey>| {
+            result.unwrap_err() == VerifierError::NotPendingAuthority.into()
+        };
+
+        // Nothing proposed yet, then only the nominee can accept
+        assert!(not_pending(state.accept_authority(&nominee)));
+        state.pending_authority = Some(nominee);
+        assert!(not_pending(state.accept_authority(&authority)));
+        assert_eq!(state.authority, authority);
+
+        assert_eq!(state.accept_authority(&nominee).unwrap(), authority);
+        assert_eq!(state.authority, nominee);
+        assert_eq!(state.pending_authority, None);
+        assert!(not_pending(state.accept_authority(&nominee)));
+    }
+
+    #[test]
+    fn test_pause_flags() {
+        let mut state = VerifierState {
+            authority: Pubkey::default(),
+            vault_program: Pubkey::default(),
+            total_batches_processed: 0,
+            total_bets_settled: 0,
+            pause_flags: PAUSE_SETTLEMENT,
+            last_settled_batch_id: 0,
+            last_sequencer_nonce: 0,
+            state_root: [0; 32],
+            pending_authority: None,
+            admin_timelock: DEFAULT_ADMIN_TIMELOCK,
+            next_admin_action_id: 0,
+            co_signer: None,
+            head_batch_id: 0,
+            head_optimistic: false,
+            challenged_batch_id: None,
+            challenge_resolvable_at: 0,
+        };
+        let paused = |state: &VerifierState, operation| {
+            state.require_unpaused(operation).unwrap_err() == VerifierError::VerifierPaused.into()
+        };
+
+        // Settlement halted, proofs can still be checked
+        assert!(paused(&state, PAUSE_SETTLEMENT));
+        assert!(state.require_unpaused(PAUSE_VERIFICATION).is_ok());
+        assert!(state.require_unpaused(PAUSE_REGISTRATION).is_ok());
+
+        state.set_pause_flags(PAUSE_ALL).unwrap();
+        assert!([PAUSE_SETTLEMENT, PAUSE_VERIFICATION, PAUSE_REGISTRATION]
+            .into_iter()
+            .all(|operation| paused(&state, operation)));
+        assert_eq!(
+            state.set_pause_flags(1 << 7).unwrap_err(),
+            VerifierError::InvalidPauseFlags.into()
+        );
+
+        // A challenge's settlement pause only lifts through resolve_challenge
+        state.challenged_batch_id = Some(4);
+        assert_eq!(
+            state.set_pause_flags(PAUSE_VERIFICATION).unwrap_err(),
+            VerifierError::ChallengeUnresolved.into()
+        );
+        state.set_pause_flags(PAUSE_SETTLEMENT).unwrap();
+        assert!(paused(&state, PAUSE_SETTLEMENT));
+        state.challenged_batch_id = None;
+        state.set_pause_flags(0).unwrap();
+        assert!(state.require_unpaused(PAUSE_SETTLEMENT).is_ok());
+    }
+
+    #[test]
+    fn test_co_signer() {
+        let co_signer = Pubkey::new_unique();
+        let mut state = VerifierState {
+            authority: Pubkey::default(),
+            vault_program: Pubkey::default(),
+            total_batches_processed: 0,
+            total_bets_settled: 0,
+            pause_flags: 0,
+            last_settled_batch_id: 0,
+            last_sequencer_nonce: 0,
+            state_root: [0; 32],
+            pending_authority: None,
+            admin_timelock: DEFAULT_ADMIN_TIMELOCK,
+            next_admin_action_id: 0,
+            co_signer: None,
+            head_batch_id: 0,
+            head_optimistic: false,
+            challenged_batch_id: None,
+            challenge_resolvable_at: 0,
+        };
+        // Without a co-signer configured the sequencer settles alone
+        assert!(state.require_co_signed(None).is_ok());
+        assert!(state.require_co_signed(Some(co_signer)).is_ok());
+
+        state.co_signer = Some(co_signer);
+        for signer in [None, Some(Pubkey::new_unique())] {
+            assert_eq!(
+                state.require_co_signed(signer).unwrap_err(),
+                VerifierError::MissingCoSigner.into()
+            );
+        }
+        assert!(state.require_co_signed(Some(co_signer)).is_ok());
+
+        // Setting the co-signer goes through the timelock and fits the action account
+        let pending = PendingAdminAction {
+            action_id: 0,
+            action: AdminAction::SetCoSigner {
+                co_signer: Some(co_signer),
+            },
+            queued_at: 0,
+            executable_at: DEFAULT_ADMIN_TIMELOCK,
+        };
+        let mut data = Vec::new();
+        pending.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= PendingAdminAction::SPACE);
+    }
+
+    #[test]
+    fn test_admin_action_timelock() {
+        let pending = PendingAdminAction {
+            action_id: u64::MAX,
+            action: AdminAction::RotateVerifyingKey {
+                circuit_id: u8::MAX,
+                key_hash: [7; 32],
+            },
+            queued_at: 1_000,
+            executable_at: 1_000 + DEFAULT_ADMIN_TIMELOCK,
+        };
+        let mut data = Vec::new();
+        pending.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), PendingAdminAction::SPACE);
+
+        assert_eq!(
+            pending.check_executable(1_000).unwrap_err(),
+            VerifierError::AdminActionTimelocked.into()
+        );
+        assert!(pending
+            .check_executable(1_000 + DEFAULT_ADMIN_TIMELOCK)
+            .is_ok());
+
+        assert!(validate_admin_timelock(DEFAULT_ADMIN_TIMELOCK).is_ok());
+        for delay in [0, MIN_ADMIN_TIMELOCK - 1, MAX_ADMIN_TIMELOCK + 1] {
+            assert_eq!(
+                validate_admin_timelock(delay).unwrap_err(),
+                VerifierError::InvalidTimelockDelay.into()
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_record_space() {
+        let record = BatchRecord {
+            batch_id: u64::MAX,
+            sequencer: Pubkey::new_unique(),
+            proof_hash: [7; 32],
+            state_root: [8; 32],
+            bet_count: MAX_BATCH_SIZE as u32,
+            house_delta: i64::MIN,
+            slot: u64::MAX,
+            status: BatchRecordStatus::Settled,
+            bets_root: [9; 32],
+            synced: [u8::MAX; SYNCED_BITMAP_LEN],
+            parent_batch_id: u64::MAX,
+        };
+        let mut data = Vec::new();
+        record.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), BatchRecord::SPACE);
+    }
+
+    #[test]
+    fn test_batch_record_sync() {
+        let bet = |bet_id, payout| BetSettlement {
+            bet_id,
+            user: Pubkey::new_unique(),
+            bet_amount: 1000,
+            user_guess: 1,
+            outcome: u8::from(payout > 0),
+            payout,
+        };
+        let leaves = [
+            BatchLeaf {
+                index: 0,
+                bet: bet(1, 1900),
+                house_delta: -900,
+            },
+            BatchLeaf {
+                index: 1,
+                bet: bet(2, 0),
+                house_delta: 100,
+            },
+        ];
+        let hashes: Vec<[u8; 32]> = leaves.iter().map(BatchLeaf::hash).collect();
+        let mut record = BatchRecord {
+            batch_id: 1,
+            sequencer: Pubkey::new_unique(),
+            proof_hash: [0; 32],
+            state_root: [0; 32],
+            bet_count: 2,
+            house_delta: 100,
+            slot: 0,
+            status: BatchRecordStatus::Settled,
+            bets_root: optimistic::bets_root(&hashes),
+            synced: [0; SYNCED_BITMAP_LEN],
+            parent_batch_id: 0,
+        };
+        let proof = |index: usize| LeafProof {
+            leaf: leaves[index].clone(),
+            path: vec![hashes[1 - index]],
+        };
+
+        // Deltas come from the committed bet
+        let won = record.settled_bet(&proof(0)).unwrap().user_delta().unwrap();
+        assert_eq!((won.sol_delta, won.is_win), (900, true));
+        let lost = record.settled_bet(&proof(1)).unwrap().user_delta().unwrap();
+        assert_eq!((lost.sol_delta, lost.is_win), (-1000, false));
+
+        // A bet the batch didn't settle, or one moved to another index, is rejected
+        let mut inflated = proof(0);
+        inflated.leaf.bet.payout = 1_000_000;
+        let mut moved = proof(0);
+        moved.leaf.index = 1;
+        for forged in [inflated, moved] {
+            assert_eq!(
+                record.settled_bet(&forged).err(),
+                Some(VerifierError::BetNotInBatch.into())
+            );
+        }
+
+        // Each bet applies once
+        record.mark_synced(1).unwrap();
+        assert_eq!(
+            record.mark_synced(1).unwrap_err(),
+            VerifierError::BetAlreadySynced.into()
+        );
+        record.mark_synced(0).unwrap();
+        assert_eq!(
+            record.mark_synced(2).unwrap_err(),
+            VerifierError::BetNotInBatch.into()
+        );
+
+        // A batch sync marks every bet it applies, and a bet listed twice fails it
+        let unsynced = BatchRecord {
+            synced: [0; SYNCED_BITMAP_LEN],
+            ..record
+        };
+        assert_eq!(
+            unsynced
+                .clone()
+                .sync_deltas(&[proof(0), proof(0)])
+                .unwrap_err(),
+            VerifierError::BetAlreadySynced.into()
+        );
+        let mut record = unsynced;
+        let deltas = record.sync_deltas(&[proof(1), proof(0)]).unwrap();
+        assert_eq!(deltas, vec![lost, won]);
+        assert_eq!(
+            record.sync_deltas(&[proof(1)]).unwrap_err(),
+            VerifierError::BetAlreadySynced.into()
+        );
+    }
+
+    #[test]
+    fn test_releasable_bet() {
+        let leaves = [
+            BatchLeaf {
+                index: 0,
+                bet: BetSettlement {
+                    bet_id: 1,
+                    user: Pubkey::new_unique(),
+                    bet_amount: 1000,
+                    user_guess: 1,
+                    outcome: 1,
+                    payout: 1900,
+                },
+                house_delta: -900,
+            },
+            BatchLeaf {
+                index: 1,
+                bet: BetSettlement {
+                    bet_id: 2,
+                    user: Pubkey::new_unique(),
+                    bet_amount: 1000,
+                    user_guess: 1,
+                    outcome: 0,
+                    payout: 0,
+                },
+                house_delta: 100,
+            },
+        ];
+        let hashes: Vec<[u8; 32]> = leaves.iter().map(BatchLeaf::hash).collect();
+        let mut record = BatchRecord {
+            batch_id: 1,
+            sequencer: Pubkey::new_unique(),
+            proof_hash: [0; 32],
+            state_root: [0; 32],
+            bet_count: 2,
+            house_delta: 100,
+            slot: 0,
+            status: BatchRecordStatus::Optimistic,
+            bets_root: optimistic::bets_root(&hashes),
+            synced: [0; SYNCED_BITMAP_LEN],
+            parent_batch_id: 0,
+        };
+        let proof = |index: usize| LeafProof {
+            leaf: leaves[index].clone(),
+            path: vec![hashes[1 - index]],
+        };
+        let not_releasable = Some(VerifierError::BetLockNotReleasable.into());
+
+        // Nothing is released while the batch can still be challenged
+        assert_eq!(record.releasable_bet(&proof(0)).err(), not_releasable);
+
+        // A settled batch releases only the bets already applied to their vaults
+        record.status = BatchRecordStatus::Settled;
+        assert_eq!(record.releasable_bet(&proof(0)).err(), not_releasable);
+        record.mark_synced(0).unwrap();
+        assert!(record.is_synced(0) && !record.is_synced(1) && !record.is_synced(2));
+        assert_eq!(record.releasable_bet(&proof(0)).unwrap().bet_id, 1);
+        assert_eq!(record.releasable_bet(&proof(1)).err(), not_releasable);
+
+        // A rejected batch releases all its bets, but only those it committed to
+        record.status = BatchRecordStatus::Challenged;
+        assert_eq!(record.releasable_bet(&proof(1)).unwrap().bet_id, 2);
+        let mut forged = proof(1);
+        forged.leaf.bet.bet_id = 3;
+        assert_eq!(
+            record.releasable_bet(&forged).err(),
+            Some(VerifierError::BetNotInBatch.into())
+        );
+    }
+
+    #[test]
+    fn test_optimistic_batch_space() {
+        let batch = OptimisticBatch {
+            sequencer: Pubkey::new_unique(),
+            claim: OptimisticBatchClaim {
+                batch_id: u64::MAX,
+                sequencer_nonce: u64::MAX,
+                game_id: u8::MAX,
+                prev_state_root: [1; 32],
+                new_state_root: [2; 32],
+                vrf_pubkey: Pubkey::new_unique(),
+                vrf_outputs_root: [3; 32],
+                bets_root: [4; 32],
+                bet_count: MAX_BATCH_SIZE as u32,
+                house_delta: i64::MIN,
+            },
+            payout_multiplier_bps: u32::MAX,
+            house_edge_bps: u16::MAX,
+            challenge_deadline: i64::MAX,
+        };
+        let mut data = Vec::new();
+        batch.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), OptimisticBatch::SPACE);
+
+        // The signed claim hash covers every field
+        let mut changed = batch.claim.clone();
+        changed.house_delta += 1;
+        assert_ne!(changed.hash().unwrap(), batch.claim.hash().unwrap());
+    }
+
+    fn bet(bet_id: u64) -> BetSettlement {
+        BetSettlement {
+            bet_id,
+            user: Pubkey::new_unique(),
+            bet_amount: u64::MAX,
+            user_guess: 1,
+            outcome: 1,
+            payout: u64::MAX,
+        }
+    }
+
+    #[test]
+    fn test_batch_staging_space() {
+        let staging = BatchStaging {
+            sequencer: Pubkey::new_unique(),
+            batch_id: u64::MAX,
+            sequencer_nonce: u64::MAX,
+            game_id: u8::MAX,
+            prev_state_root: [1; 32],
+            new_state_root: [2; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [3; 32],
+            bet_count: MAX_BATCH_SIZE as u32,
+            bets: (0..MAX_BATCH_SIZE as u64).map(bet).collect(),
+            sequencer_signature: [4; 64],
+        };
+        let mut data = Vec::new();
+        staging.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), BatchStaging::SPACE);
+        const _: () = assert!(BatchStaging::SPACE <= 10_240);
+    }
+
+    #[test]
+    fn test_chunked_batch_staging() {
+        let mut staging = BatchStaging {
+            sequencer: Pubkey::new_unique(),
+            batch_id: 7,
+            sequencer_nonce: 7,
+            game_id: GAME_COINFLIP,
+            prev_state_root: [1; 32],
+            new_state_root: [2; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [3; 32],
+            bet_count: 5,
+            bets: Vec::new(),
+            sequencer_signature: [4; 64],
+        };
+
+        staging.append(0, (0..3).map(bet).collect()).unwrap();
+        assert!(staging.to_batch_data().is_err()); // Incomplete
+        assert!(staging.append(2, vec![bet(3)]).is_err()); // Out of order
+        assert!(staging.append(3, vec![]).is_err()); // Empty
+        assert!(staging.append(3, (3..6).map(bet).collect()).is_err()); // Past bet_count
+        staging.append(3, (3..5).map(bet).collect()).unwrap();
+
+        let batch_data = staging.to_batch_data().unwrap();
+        assert_eq!(batch_data.batch_id, 7);
+        assert_eq!(batch_data.new_state_root, [2; 32]);
+        assert_eq!(batch_data.vrf_outputs_root, [3; 32]);
+        let bet_ids: Vec<u64> = batch_data.bets.iter().map(|b| b.bet_id).collect();
+        assert_eq!(bet_ids, vec![0, 1, 2, 3, 4]);
+        // Finalize checks the signature given at begin_batch over the assembled batch
+        assert_eq!(batch_data.sequencer_signature, [4; 64]);
+    }
+
+    #[test]
+    fn test_prepared_inputs() {
+        let mut generator = G1Point {
+            x: [0; 32],
+            y: [0; 32],
+        };
+        generator.x[31] = 1;
+        generator.y[31] = 2;
+        let g2 = groth16::G2Point {
+            x: [0; 64],
+            y: [0; 64],
+        };
+        let verifying_key = Groth16VerifyingKey {
+            alpha: generator,
+            beta: g2,
+            gamma: g2,
+            delta: g2,
+            ic: vec![generator; 4],
+        };
+        let input = |value: u8| {
+            let mut bytes = [0u8; 32];
+            bytes[31] = value;
+            bytes
+        };
+        let inputs = [input(1), input(2), input(3)];
+        let mut prepared = PreparedInputs {
+            owner: Pubkey::new_unique(),
+            key_hash: [0; 32],
+            input_count: 3,
+            next_input: 0,
+            inputs_hash: [0; 32],
+            vk_x: g1_to_bytes(&generator),
+            circuit_id: u8::MAX,
+        };
+
+        prepared.fold(&verifying_key, 0, &inputs[..2]).unwrap();
+        assert!(prepared.vk_x().is_err()); // Incomplete
+        assert!(prepared.fold(&verifying_key, 1, &inputs[2..]).is_err()); // Out of order
+        assert!(prepared.fold(&verifying_key, 2, &[]).is_err()); // Empty
+        assert!(prepared
+            .fold(&verifying_key, 2, &[input(3), input(4)])
+            .is_err()); // Past input_count
+        prepared.fold(&verifying_key, 2, &inputs[2..]).unwrap();
+
+        assert_eq!(
+            prepared.vk_x().unwrap(),
+            verifying_key.compute_vk_x(&inputs).unwrap()
+        );
+        let chained = inputs.iter().fold([0u8; 32], |acc, input| {
+            hash::hashv(&[&acc, input]).to_bytes()
+        });
+        assert_eq!(prepared.inputs_hash, chained);
+
+        let mut data = Vec::new();
+        prepared.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), PreparedInputs::SPACE);
+
+        // Every stage loads the key the inputs were started with, not a rotated one
+        let mut account = VerifyingKeyAccount {
+            version: 1,
+            staged_len: 0,
+            active: VERIFYING_KEY_BYTES.to_vec(),
+            staged: Vec::new(),
+        };
+        prepared.key_hash = hash::hash(VERIFYING_KEY_BYTES).to_bytes();
+        assert_eq!(
+            prepared.verifying_key(&account).unwrap().ic.len(),
+            parse_verifying_key(VERIFYING_KEY_BYTES).unwrap().ic.len()
+        );
+        account.active[VERIFYING_KEY_POINTS_SIZE] ^= 1;
+        assert_eq!(
+            prepared.verifying_key(&account).err(),
+            Some(VerifierError::InvalidVerifyingKey.into())
+        );
+    }
+
+    #[test]
+    fn test_game_config_payouts() {
+        let mut config = GameConfig {
+            game_id: GAME_COINFLIP,
+            payout_multiplier_bps: 0,
+            house_edge_bps: 0,
+            updated_at: 0,
+        };
+        config.set_schedule(20_000, 0, 1).unwrap();
+        assert_eq!(config.win_payout(1001).unwrap(), 2002);
+
+        // 2x less a 1% edge is 1.98x, rounded down
+        config.set_schedule(20_000, 100, 2).unwrap();
+        assert_eq!(config.win_payout(1_000_000).unwrap(), 1_980_000);
+        assert_eq!(config.win_payout(1001).unwrap(), 1981);
+        assert_eq!(config.updated_at, 2);
+
+        assert!(config.set_schedule(9_999, 0, 3).is_err()); // Win pays less than the stake
+        assert!(config.set_schedule(20_000, 1_001, 3).is_err()); // Edge above 10%
+        assert_eq!(config.house_edge_bps, 100); // Rejected updates leave the schedule as is
+
+        config.set_schedule(u32::MAX, 0, 4).unwrap();
+        assert!(config.win_payout(u64::MAX).is_err());
+
+        // House delta of each bet, refusing amounts that don't fit an i64
+        config.set_schedule(20_000, 0, 5).unwrap();
+        let bet = |bet_amount: u64, outcome: u8| BetSettlement {
+            bet_id: 1,
+            user: Pubkey::new_unique(),
+            bet_amount,
+            user_guess: 1,
+            outcome,
+            payout: if outcome == 1 { bet_amount * 2 } else { 0 },
+        };
+        assert_eq!(check_bet(&config, &bet(1_000, 1)).unwrap(), -1_000);
+        assert_eq!(check_bet(&config, &bet(1_000, 0)).unwrap(), 1_000);
+        assert_eq!(
+            check_bet(&config, &bet(u64::MAX, 0)),
+            Err(VerifierError::InvalidBetAmount.into())
+        );
+        assert_eq!(
+            check_bet(&config, &bet(1 << 62, 1)),
+            Err(VerifierError::MathOverflow.into())
+        );
+        let bad_guess = BetSettlement {
+            user_guess: 2,
+            ..bet(1_000, 0)
+        };
+        assert_eq!(
+            check_bet(&config, &bad_guess),
+            Err(VerifierError::InvalidGuess.into())
+        );
+
+        let mut data = Vec::new();
+        config.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), GameConfig::SPACE);
+    }
+
+    #[test]
+    fn test_sequencer_registration_space() {
+        let registration = SequencerRegistration {
+            sequencer: Pubkey::new_unique(),
+            registered_at: i64::MAX,
+            valid_from: i64::MAX,
+            valid_until: Some(i64::MAX),
+        };
+        let mut data = Vec::new();
+        registration.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), SequencerRegistration::SPACE);
+    }
+
+    #[test]
+    fn test_sequencer_rotation() {
+        let registration = |valid_from| SequencerRegistration {
+            sequencer: Pubkey::new_unique(),
+            registered_at: valid_from,
+            valid_from,
+            valid_until: None,
+        };
+        let mut current = registration(0);
+        let mut next = registration(0);
+        let delay = DEFAULT_ADMIN_TIMELOCK;
+        let (activates_at, retires_at) =
+            schedule_sequencer_rotation(&mut current, &mut next, 100, delay).unwrap();
+        assert_eq!(activates_at, 100 + delay);
+        assert_eq!(retires_at, activates_at + SEQUENCER_KEY_OVERLAP);
+
+        // Only the old key before activation, both during the overlap, then the new one
+        assert!(current.is_active(activates_at - 1) && !next.is_active(activates_at - 1));
+        assert!(current.is_active(activates_at) && next.is_active(activates_at));
+        assert!(!current.is_active(retires_at) && next.is_active(retires_at));
+
+        // A key already being rotated out can't be rotated again
+        let mut another = registration(0);
+        assert_eq!(
+            schedule_sequencer_rotation(&mut current, &mut another, 200, delay).unwrap_err(),
+            VerifierError::SequencerRotationPending.into()
+        );
+    }
+
+    #[test]
+    fn test_circuit_registry() {
+        let entry = |circuit_id, game_ids: Vec<u8>| CircuitEntry {
+            circuit_id,
+            verifying_key: Pubkey::new_unique(),
+            game_ids,
+        };
+        let accounting = entry(CIRCUIT_ACCOUNTING, vec![GAME_COINFLIP]);
+        let mut registry = CircuitRegistry {
+            circuits: vec![accounting.clone()],
+        };
+        let dice = entry(1, vec![1, 2]);
+        registry.register(dice.clone()).unwrap();
+
+        assert_eq!(registry.verifying_key(1), Some(dice.verifying_key));
+        assert_eq!(registry.settlement_key(1, 2), Some(dice.verifying_key));
+        // A circuit can't settle games it isn't registered for
+        assert_eq!(registry.settlement_key(1, GAME_COINFLIP), None);
+        assert_eq!(
+            registry.settlement_key(CIRCUIT_ACCOUNTING, GAME_COINFLIP),
+            Some(accounting.verifying_key)
+        );
+        assert_eq!(registry.verifying_key(9), None);
+
+        assert_eq!(
+            registry.register(entry(1, vec![3])).unwrap_err(),
+            VerifierError::CircuitAlreadyRegistered.into()
+        );
+        for game_ids in [vec![], vec![0; MAX_CIRCUIT_GAMES + 1]] {
+            assert_eq!(
+                registry.register(entry(2, game_ids)).unwrap_err(),
+                VerifierError::InvalidCircuitRegistration.into()
+            );
+        }
+
+        for circuit_id in 2..MAX_CIRCUITS as u8 {
+            registry
+                .register(entry(circuit_id, vec![u8::MAX; MAX_CIRCUIT_GAMES]))
+                .unwrap();
+        }
+        assert_eq!(
+            registry.register(entry(u8::MAX, vec![0])).unwrap_err(),
+            VerifierError::InvalidCircuitRegistration.into()
+        );
+        registry.circuits[0].game_ids = vec![u8::MAX; MAX_CIRCUIT_GAMES];
+        registry.circuits[1].game_ids = vec![u8::MAX; MAX_CIRCUIT_GAMES];
+        let mut data = Vec::new();
+        registry.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), CircuitRegistry::SPACE);
+    }
+
+    #[test]
+    fn test_verifying_key_account_space() {
+        let account = VerifyingKeyAccount {
+            version: u32::MAX,
+            staged_len: MAX_VERIFYING_KEY_SIZE as u32,
+            active: vec![0; MAX_VERIFYING_KEY_SIZE],
+            staged: vec![0; MAX_VERIFYING_KEY_SIZE],
+        };
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), VerifyingKeyAccount::SPACE);
+        // PDAs created through CPI are limited to 10KB
+        const _: () = assert!(VerifyingKeyAccount::SPACE <= 10_240);
+    }
+
+    #[test]
+    fn test_chunked_verifying_key_upload() {
+        let mut account = VerifyingKeyAccount {
+            version: 1,
+            staged_len: 0,
+            active: VERIFYING_KEY_BYTES.to_vec(),
+            staged: Vec::new(),
+        };
+        let key = &VERIFYING_KEY_BYTES[..VERIFYING_KEY_POINTS_SIZE + 2 * IC_POINT_SIZE];
+        let (first, rest) = key.split_at(300);
+
+        // Declared length must describe whole IC points
+        assert!(account.stage_chunk(key.len() + 1, 0, first).is_err());
+
+        account.stage_chunk(key.len(), 0, first).unwrap();
+        assert!(account.activate_staged().is_err()); // Incomplete
+        assert!(account.stage_chunk(key.len(), 299, rest).is_err()); // Out of order
+        assert!(account.stage_chunk(key.len() + 64, 300, rest).is_err()); // Length changed
+        account.stage_chunk(key.len(), 300, rest).unwrap();
+        assert!(account
+            .stage_chunk(key.len(), 300 + rest.len(), &[0])
+            .is_err()); // Overflow
+        assert_eq!(account.staged, key);
+
+        // Restarting at offset 0 discards the partial upload
+        account.stage_chunk(key.len(), 0, first).unwrap();
+        assert_eq!(account.staged, first);
+    }
+
+    fn fixture_batch() -> BatchSettlementData {
+        let batch = &fixtures::BATCH;
+        BatchSettlementData {
+            batch_id: batch.batch_id,
+            sequencer_nonce: batch.sequencer_nonce,
+            game_id: batch.game_id,
+            prev_state_root: fixtures::hex32(batch.prev_state_root),
+            new_state_root: fixtures::hex32(batch.new_state_root),
+            vrf_pubkey: Pubkey::new_from_array(fixtures::hex32(batch.vrf_pubkey)),
+            vrf_outputs_root: fixtures::hex32(batch.vrf_outputs_root),
+            bets: batch
+                .bets
+                .iter()
+                .map(|bet| BetSettlement {
+                    bet_id: bet.bet_id,
+                    user: Pubkey::new_from_array(bet.user),
+                    bet_amount: bet.bet_amount,
+                    user_guess: bet.user_guess,
+                    outcome: bet.outcome,
+                    payout: bet.payout,
+                })
+                .collect(),
+            sequencer_signature: [0; 64],
+        }
+    }
+
+    #[test]
+    fn test_batch_hash_covers_header() {
+        let batch = fixture_batch();
+        let signer = Pubkey::new_unique();
+        let signature = [5; 64];
+        let hash = compute_batch_hash(&batch);
+        let data = ed25519::tests::instruction_data(&signer, &signature, &hash);
+        assert!(ed25519::checks_signature(&data, &signer, &hash, &signature));
+
+        // A signature over the batch no longer checks once any header field is swapped
+        let mut nonce = batch.clone();
+        nonce.sequencer_nonce += 1;
+        let mut game = batch.clone();
+        game.game_id += 1;
+        let mut prev_root = batch.clone();
+        prev_root.prev_state_root[0] ^= 1;
+        let mut new_root = batch.clone();
+        new_root.new_state_root[0] ^= 1;
+        for changed in [nonce, game, prev_root, new_root] {
+            let changed_hash = compute_batch_hash(&changed);
+            assert_ne!(changed_hash, hash);
+            assert!(!ed25519::checks_signature(
+                &data,
+                &signer,
+                &changed_hash,
+                &signature
+            ));
+        }
+    }
+
+    #[test]
+    fn test_batch_encodings_match_fixtures() {
+        let batch = fixture_batch();
+        assert_eq!(
+            EMPTY_STATE_ROOT,
+            fixtures::hex32(fixtures::EMPTY_STATE_ROOT)
+        );
+        assert_eq!(
+            compute_batch_hash(&batch),
+            fixtures::hex32(fixtures::BATCH_HASH)
+        );
+        assert_eq!(
+            compute_batch_commitment(&batch.bets).unwrap(),
+            fixtures::hex32(fixtures::BATCH_COMMITMENT)
+        );
+        assert_eq!(
+            batch.bets.try_to_vec().unwrap(),
+            fixtures::hex(fixtures::BETS)
+        );
+        let mut house_delta = 0;
+        let leaves: Vec<[u8; 32]> = (batch.bets.iter().enumerate())
+            .map(|(index, bet)| {
+                house_delta += bet.bet_amount as i64 - bet.payout as i64;
+                BatchLeaf {
+                    index: index as u32,
+                    bet: bet.clone(),
+                    house_delta,
+                }
+                .hash()
+            })
+            .collect();
+        assert_eq!(
+            optimistic::bets_root(&leaves),
+            fixtures::hex32(fixtures::BATCH_BETS_ROOT)
+        );
+
+        // The sequencer's hand-built instruction data decodes to the batch
+        let begin = instruction::BeginBatch {
+            batch_id: batch.batch_id,
+            sequencer_nonce: batch.sequencer_nonce,
+            game_id: batch.game_id,
+            prev_state_root: batch.prev_state_root,
+            new_state_root: batch.new_state_root,
+            vrf_pubkey: batch.vrf_pubkey,
+            vrf_outputs_root: batch.vrf_outputs_root,
+            bet_count: batch.bets.len() as u32,
+            sequencer_signature: [0; 64],
+        };
+        assert_eq!(
+            anchor_lang::InstructionData::data(&begin),
+            fixtures::hex(fixtures::BEGIN_BATCH)
+        );
+        let append = instruction::AppendBatchChunk {
+            offset: 0,
+            bets: batch.bets.clone(),
+        };
+        assert_eq!(
+            anchor_lang::InstructionData::data(&append),
+            fixtures::hex(fixtures::APPEND_BATCH_CHUNK)
+        );
+
+        // The public inputs the prover proves against are canonical scalars
+        assert_eq!(
+            batch_public_inputs(&batch).unwrap(),
+            fixtures::BATCH.public_inputs()
+        );
+        let key = parse_verifying_key(
+            &[0; verifying_key::VERIFYING_KEY_POINTS_SIZE + 5 * verifying_key::IC_POINT_SIZE],
+        )
+        .unwrap();
+        assert!(key
+            .validate_public_inputs(&fixtures::BATCH.public_inputs())
+            .is_ok());
+
+        // The prover's proof encoding parses to points on the curve
+        let proof = Groth16Proof::from_bytes(&fixtures::hex(fixtures::PROOF)).unwrap();
+        assert!(proof.validate_curve_points().is_ok());
+    }
+
+    #[test]
+    fn test_account_encodings_match_fixtures() {
+        let batch = fixture_batch();
+        let record = BatchRecord {
+            batch_id: batch.batch_id,
+            sequencer: Pubkey::new_from_array(fixtures::SEQUENCER),
+            proof_hash: fixtures::PROOF_HASH,
+            state_root: batch.new_state_root,
+            bet_count: batch.bets.len() as u32,
+            house_delta: fixtures::BATCH.house_delta(),
+            slot: fixtures::SETTLED_SLOT,
+            status: BatchRecordStatus::Settled,
+            bets_root: fixtures::hex32(fixtures::BATCH_BETS_ROOT),
+            synced: [0; SYNCED_BITMAP_LEN],
+            parent_batch_id: 0,
+        };
+        let mut data = Vec::new();
+        record.try_serialize(&mut data).unwrap();
+        assert_eq!(data, fixtures::hex(fixtures::BATCH_RECORD));
+
+        let registration = SequencerRegistration {
+            sequencer: Pubkey::new_from_array(fixtures::SEQUENCER),
+            registered_at: fixtures::REGISTERED_AT,
+            valid_from: fixtures::REGISTERED_AT,
+            valid_until: Some(fixtures::VALID_UNTIL),
+        };
+        let mut data = Vec::new();
+        registration.try_serialize(&mut data).unwrap();
+        assert_eq!(data, fixtures::hex(fixtures::SEQUENCER_REGISTRATION));
     }
 }
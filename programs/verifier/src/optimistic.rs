@@ -0,0 +1,295 @@
+use crate::{check_bet, BetSettlement, GameConfig, OptimisticBatchClaim};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash;
+
+/// Domain separators so bet leaves and tree nodes never collide with other hashes
+const LEAF_PREFIX: &[u8] = b"zkcasino-optimistic-leaf";
+const NODE_PREFIX: &[u8] = b"zkcasino-optimistic-node";
+
+/// One bet of an optimistic batch as committed to by the claim's bets root, with the
+/// batch's running house delta through this bet
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchLeaf {
+    pub index: u32,
+    pub bet: BetSettlement,
+    pub house_delta: i64, // Sum of stake minus payout over bets 0..=index
+}
+
+impl BatchLeaf {
+    pub fn hash(&self) -> [u8; 32] {
+        let bet = &self.bet;
+        hash::hashv(&[
+            LEAF_PREFIX,
+            &self.index.to_le_bytes(),
+            &bet.bet_id.to_le_bytes(),
+            bet.user.as_ref(),
+            &bet.bet_amount.to_le_bytes(),
+            &[bet.user_guess, bet.outcome],
+            &bet.payout.to_le_bytes(),
+            &self.house_delta.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+}
+
+/// A leaf and its sibling path in the bets tree, bottom up
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LeafProof {
+    pub leaf: BatchLeaf,
+    pub path: Vec<[u8; 32]>,
+}
+
+impl LeafProof {
+    /// Whether the leaf is at its index in a tree with the claim's shape and root
+    pub fn verify(&self, claim: &OptimisticBatchClaim) -> bool {
+        self.verify_root(&claim.bets_root, claim.bet_count)
+    }
+
+    /// Whether the leaf is at its index in a `count`-leaf tree with this root
+    pub fn verify_root(&self, bets_root: &[u8; 32], count: u32) -> bool {
+        root_from_path(self.leaf.hash(), self.leaf.index, count, &self.path) == Some(*bets_root)
+    }
+}
+
+/// How a challenge showed an optimistic batch to be invalid
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FraudKind {
+    InvalidBet,        // Bet doesn't follow the game's payout schedule
+    RunningHouseDelta, // Leaf's running house delta doesn't add up from the previous leaf
+    ClaimedHouseDelta, // Last leaf's running house delta differs from the claimed total
+}
+
+fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hash::hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Root over leaf hashes in order. An odd node is carried up unchanged rather than
+/// paired with itself, so no two leaf lists share a root.
+pub fn bets_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level.first().copied().unwrap_or_default()
+}
+
+/// Root implied by the leaf at `index` of a `count`-leaf tree and its sibling path; a
+/// level where the node is carried up has no sibling. None if the path doesn't fit.
+pub fn root_from_path(
+    leaf: [u8; 32],
+    index: u32,
+    count: u32,
+    path: &[[u8; 32]],
+) -> Option<[u8; 32]> {
+    if index >= count {
+        return None;
+    }
+    let (mut hash, mut index, mut width) = (leaf, index as usize, count as usize);
+    let mut siblings = path.iter();
+    while width > 1 {
+        if index ^ 1 < width {
+            let sibling = siblings.next()?;
+            hash = if index % 2 == 0 {
+                node(&hash, sibling)
+            } else {
+                node(sibling, &hash)
+            };
+        }
+        index /= 2;
+        width = (width + 1) / 2;
+    }
+    siblings.next().is_none().then_some(hash)
+}
+
+/// Check a committed leaf (and the one before it, for the running total) against the
+/// payout schedule and the claim; returns the fraud it shows, if any
+pub fn find_fraud(
+    game_config: &GameConfig,
+    claim: &OptimisticBatchClaim,
+    leaf: &BatchLeaf,
+    previous: Option<&BatchLeaf>,
+) -> Option<FraudKind> {
+    let Ok(bet_delta) = check_bet(game_config, &leaf.bet) else {
+        return Some(FraudKind::InvalidBet);
+    };
+
+    let running_from = match (leaf.index, previous) {
+        (0, None) => Some(0),
+        (index, Some(previous)) if previous.index + 1 == index => Some(previous.house_delta),
+        _ => None, // Not adjacent leaves: the running total can't be checked
+    };
+    if let Some(from) = running_from {
+        if from.checked_add(bet_delta) != Some(leaf.house_delta) {
+            return Some(FraudKind::RunningHouseDelta);
+        }
+    }
+
+    if leaf.index + 1 == claim.bet_count && leaf.house_delta != claim.house_delta {
+        return Some(FraudKind::ClaimedHouseDelta);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(leaves: &[[u8; 32]], mut index: usize) -> Vec<[u8; 32]> {
+        let mut path = Vec::new();
+        let mut level = leaves.to_vec();
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(index ^ 1) {
+                path.push(*sibling);
+            }
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            index /= 2;
+        }
+        path
+    }
+
+    fn game_config() -> GameConfig {
+        GameConfig {
+            game_id: crate::GAME_COINFLIP,
+            payout_multiplier_bps: 20_000,
+            house_edge_bps: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn bet(bet_id: u64, win: bool) -> BetSettlement {
+        BetSettlement {
+            bet_id,
+            user: Pubkey::new_unique(),
+            bet_amount: 1000,
+            user_guess: 1,
+            outcome: if win { 1 } else { 0 },
+            payout: if win { 2000 } else { 0 },
+        }
+    }
+
+    /// Leaves for `bets` with correct running totals, and the claim committing to them
+    fn commit(bets: Vec<BetSettlement>) -> (Vec<BatchLeaf>, OptimisticBatchClaim) {
+        let mut house_delta = 0;
+        let leaves: Vec<BatchLeaf> = bets
+            .into_iter()
+            .enumerate()
+            .map(|(index, bet)| {
+                house_delta += bet.bet_amount as i64 - bet.payout as i64;
+                BatchLeaf {
+                    index: index as u32,
+                    bet,
+                    house_delta,
+                }
+            })
+            .collect();
+        let hashes: Vec<[u8; 32]> = leaves.iter().map(BatchLeaf::hash).collect();
+        let claim = OptimisticBatchClaim {
+            batch_id: 1,
+            sequencer_nonce: 1,
+            game_id: crate::GAME_COINFLIP,
+            prev_state_root: [0; 32],
+            new_state_root: [1; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [2; 32],
+            bets_root: bets_root(&hashes),
+            bet_count: leaves.len() as u32,
+            house_delta,
+        };
+        (leaves, claim)
+    }
+
+    #[test]
+    fn test_leaf_paths() {
+        for count in 1..=9usize {
+            let leaves: Vec<[u8; 32]> = (0..count).map(|i| [i as u8; 32]).collect();
+            let root = bets_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = path(&leaves, index);
+                assert_eq!(
+                    root_from_path(*leaf, index as u32, count as u32, &proof),
+                    Some(root)
+                );
+                // Wrong position, wrong shape or extra siblings don't reach the root
+                if count > 1 {
+                    let other = ((index + 1) % count) as u32;
+                    assert_ne!(
+                        root_from_path(*leaf, other, count as u32, &proof),
+                        Some(root)
+                    );
+                }
+                let mut longer = proof.clone();
+                longer.push([9; 32]);
+                assert_eq!(
+                    root_from_path(*leaf, index as u32, count as u32, &longer),
+                    None
+                );
+            }
+            assert_eq!(
+                root_from_path(leaves[0], count as u32, count as u32, &[]),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_honest_batch_has_no_fraud() {
+        let (leaves, claim) = commit(vec![bet(1, true), bet(2, false), bet(3, false)]);
+        let hashes: Vec<[u8; 32]> = leaves.iter().map(BatchLeaf::hash).collect();
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = LeafProof {
+                leaf: leaf.clone(),
+                path: path(&hashes, index),
+            };
+            assert!(proof.verify(&claim));
+            let previous = index.checked_sub(1).map(|i| &leaves[i]);
+            assert_eq!(find_fraud(&game_config(), &claim, leaf, previous), None);
+        }
+    }
+
+    #[test]
+    fn test_fraud_detection() {
+        // Overpaid loss
+        let mut overpaid = bet(2, false);
+        overpaid.payout = 500;
+        let (leaves, claim) = commit(vec![bet(1, true), overpaid]);
+        assert_eq!(
+            find_fraud(&game_config(), &claim, &leaves[1], Some(&leaves[0])),
+            Some(FraudKind::InvalidBet)
+        );
+
+        // Running total skipping a bet's delta
+        let (mut leaves, mut claim) = commit(vec![bet(1, false), bet(2, false), bet(3, true)]);
+        leaves[1].house_delta -= 1000;
+        leaves[2].house_delta -= 1000;
+        claim.house_delta -= 1000;
+        assert_eq!(
+            find_fraud(&game_config(), &claim, &leaves[1], Some(&leaves[0])),
+            Some(FraudKind::RunningHouseDelta)
+        );
+        // The last leaf alone agrees with the (understated) claim
+        assert_eq!(find_fraud(&game_config(), &claim, &leaves[2], None), None);
+
+        // Claimed total that the leaves don't add up to
+        let (leaves, mut claim) = commit(vec![bet(1, false), bet(2, true)]);
+        claim.house_delta += 1;
+        assert_eq!(
+            find_fraud(&game_config(), &claim, &leaves[1], None),
+            Some(FraudKind::ClaimedHouseDelta)
+        );
+        assert_eq!(find_fraud(&game_config(), &claim, &leaves[0], None), None);
+    }
+}
@@ -0,0 +1,304 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+
+/// Seed of the PDA the verifier signs vault calls with. The vault requires it as a
+/// signer on update_balances, and only this program can sign for it.
+pub const VAULT_CALLER_SEED: &[u8] = b"vault_caller";
+
+/// Seed of a user's vault PDA in the vault program, followed by the user's key
+pub const USER_VAULT_SEED: &[u8] = b"user_vault";
+
+/// Address of `user`'s vault in the vault program
+pub fn user_vault_address(vault_program: &Pubkey, user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[USER_VAULT_SEED, user.as_ref()], vault_program).0
+}
+
+/// One settled bet's effect on a user vault; same layout as the vault's UserDelta
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct UserDelta {
+    pub user: Pubkey,
+    pub sol_delta: i64,
+    pub usdc_delta: i64,
+    pub is_win: bool,
+    pub bet_amount: u64,
+}
+
+/// Anchor discriminator of a vault instruction
+fn discriminator(name: &str) -> Vec<u8> {
+    hash::hash(format!("global:{}", name).as_bytes()).to_bytes()[..8].to_vec()
+}
+
+/// Vault update_balances instruction: Anchor discriminator, then the borsh arguments
+pub fn update_balances_data(
+    sol_delta: i64,
+    usdc_delta: i64,
+    is_win: bool,
+    bet_amount: u64,
+) -> Result<Vec<u8>> {
+    let mut data = discriminator("update_balances");
+    (sol_delta, usdc_delta, is_win, bet_amount).serialize(&mut data)?;
+    Ok(data)
+}
+
+/// Vault update_balances_batch instruction: Anchor discriminator, then the borsh deltas
+pub fn update_balances_batch_data(deltas: &[UserDelta]) -> Result<Vec<u8>> {
+    let mut data = discriminator("update_balances_batch");
+    deltas.serialize(&mut data)?;
+    Ok(data)
+}
+
+/// Vault release_lock instruction: Anchor discriminator, then the borsh arguments
+pub fn release_lock_data(bet_id: u64) -> Result<Vec<u8>> {
+    let mut data = discriminator("release_lock");
+    bet_id.serialize(&mut data)?;
+    Ok(data)
+}
+
+/// Vault commit_balances_root instruction: Anchor discriminator, then the borsh arguments
+pub fn commit_balances_root_data(batch_id: u64, balances_root: &[u8; 32]) -> Result<Vec<u8>> {
+    let mut data = discriminator("commit_balances_root");
+    (batch_id, balances_root).serialize(&mut data)?;
+    Ok(data)
+}
+
+/// Apply a settled bet to a user's vault balance through the vault program, signing
+/// as the vault caller PDA. Accounts follow the vault's UpdateBalances order. Deltas
+/// are credited in full, so vault balances follow the batch's state root; the house
+/// pays the vault's protocol fee on a win on top, into `fee_vault`.
+/// A referred user's win also needs their referrer's ReferralAccount in
+/// `referral_accounts`, which the vault pays its share of the fee into.
+#[allow(clippy::too_many_arguments)]
+pub fn update_balances<'info>(
+    vault_program: &AccountInfo<'info>,
+    user_vault: &AccountInfo<'info>,
+    vault_state: &AccountInfo<'info>,
+    house_vault: &AccountInfo<'info>,
+    fee_vault: &AccountInfo<'info>,
+    vault_caller: &AccountInfo<'info>,
+    vault_caller_bump: u8,
+    referral_accounts: &[AccountInfo<'info>],
+    sol_delta: i64,
+    usdc_delta: i64,
+    is_win: bool,
+    bet_amount: u64,
+) -> Result<()> {
+    let mut accounts = vec![
+        AccountMeta::new(user_vault.key(), false),
+        AccountMeta::new(vault_state.key(), false),
+        AccountMeta::new(house_vault.key(), false),
+        AccountMeta::new(fee_vault.key(), false),
+        AccountMeta::new_readonly(vault_caller.key(), true),
+    ];
+    accounts.extend(
+        referral_accounts
+            .iter()
+            .map(|referral_account| AccountMeta::new(referral_account.key(), false)),
+    );
+    let instruction = Instruction {
+        program_id: vault_program.key(),
+        accounts,
+        data: update_balances_data(sol_delta, usdc_delta, is_win, bet_amount)?,
+    };
+
+    let mut infos = vec![
+        user_vault.clone(),
+        vault_state.clone(),
+        house_vault.clone(),
+        fee_vault.clone(),
+        vault_caller.clone(),
+    ];
+    infos.extend(referral_accounts.iter().cloned());
+    infos.push(vault_program.clone());
+    invoke_signed(
+        &instruction,
+        &infos,
+        &[&[VAULT_CALLER_SEED, &[vault_caller_bump]]],
+    )?;
+    Ok(())
+}
+
+/// Apply a settlement batch's deltas in one vault call. `user_vaults` holds one account
+/// per delta, in order, then the ReferralAccounts of referred users' referrers, and is
+/// passed on as the vault instruction's remaining accounts.
+#[allow(clippy::too_many_arguments)]
+pub fn update_balances_batch<'info>(
+    vault_program: &AccountInfo<'info>,
+    vault_state: &AccountInfo<'info>,
+    house_vault: &AccountInfo<'info>,
+    fee_vault: &AccountInfo<'info>,
+    vault_caller: &AccountInfo<'info>,
+    vault_caller_bump: u8,
+    user_vaults: &[AccountInfo<'info>],
+    deltas: &[UserDelta],
+) -> Result<()> {
+    let mut accounts = vec![
+        AccountMeta::new(vault_state.key(), false),
+        AccountMeta::new(house_vault.key(), false),
+        AccountMeta::new(fee_vault.key(), false),
+        AccountMeta::new_readonly(vault_caller.key(), true),
+    ];
+    accounts.extend(
+        user_vaults
+            .iter()
+            .map(|user_vault| AccountMeta::new(user_vault.key(), false)),
+    );
+    let instruction = Instruction {
+        program_id: vault_program.key(),
+        accounts,
+        data: update_balances_batch_data(deltas)?,
+    };
+
+    let mut infos = vec![
+        vault_state.clone(),
+        house_vault.clone(),
+        fee_vault.clone(),
+        vault_caller.clone(),
+    ];
+    infos.extend(user_vaults.iter().cloned());
+    infos.push(vault_program.clone());
+    invoke_signed(
+        &instruction,
+        &infos,
+        &[&[VAULT_CALLER_SEED, &[vault_caller_bump]]],
+    )?;
+    Ok(())
+}
+
+/// Release the funds locked in a user's vault for bet `bet_id`, signing as the vault
+/// caller PDA. The vault releases the amount its BetLock recorded and closes the lock,
+/// refunding its rent to the vault authority. Accounts follow the vault's ReleaseLock
+/// order.
+#[allow(clippy::too_many_arguments)]
+pub fn release_lock<'info>(
+    vault_program: &AccountInfo<'info>,
+    bet_lock: &AccountInfo<'info>,
+    user_vault: &AccountInfo<'info>,
+    vault_state: &AccountInfo<'info>,
+    vault_authority: &AccountInfo<'info>,
+    vault_caller: &AccountInfo<'info>,
+    vault_caller_bump: u8,
+    bet_id: u64,
+) -> Result<()> {
+    let instruction = Instruction {
+        program_id: vault_program.key(),
+        accounts: vec![
+            AccountMeta::new(bet_lock.key(), false),
+            AccountMeta::new(user_vault.key(), false),
+            AccountMeta::new_readonly(vault_state.key(), false),
+            AccountMeta::new(vault_authority.key(), false),
+            AccountMeta::new_readonly(vault_caller.key(), true),
+        ],
+        data: release_lock_data(bet_id)?,
+    };
+    invoke_signed(
+        &instruction,
+        &[
+            bet_lock.clone(),
+            user_vault.clone(),
+            vault_state.clone(),
+            vault_authority.clone(),
+            vault_caller.clone(),
+            vault_program.clone(),
+        ],
+        &[&[VAULT_CALLER_SEED, &[vault_caller_bump]]],
+    )?;
+    Ok(())
+}
+
+/// Commit a settled batch's balance state root to the vault, signing as the vault
+/// caller PDA. Accounts follow the vault's CommitBalancesRoot order.
+pub fn commit_balances_root<'info>(
+    vault_program: &AccountInfo<'info>,
+    vault_state: &AccountInfo<'info>,
+    vault_caller: &AccountInfo<'info>,
+    vault_caller_bump: u8,
+    batch_id: u64,
+    balances_root: &[u8; 32],
+) -> Result<()> {
+    let instruction = Instruction {
+        program_id: vault_program.key(),
+        accounts: vec![
+            AccountMeta::new(vault_state.key(), false),
+            AccountMeta::new_readonly(vault_caller.key(), true),
+        ],
+        data: commit_balances_root_data(batch_id, balances_root)?,
+    };
+    invoke_signed(
+        &instruction,
+        &[
+            vault_state.clone(),
+            vault_caller.clone(),
+            vault_program.clone(),
+        ],
+        &[&[VAULT_CALLER_SEED, &[vault_caller_bump]]],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_balances_data() {
+        let data = update_balances_data(-5, 7, true, 9).unwrap();
+        assert_eq!(data.len(), 8 + 8 + 8 + 1 + 8);
+        assert_eq!(
+            &data[..8],
+            &hash::hash(b"global:update_balances").to_bytes()[..8]
+        );
+        assert_eq!(&data[8..16], &(-5i64).to_le_bytes());
+        assert_eq!(&data[16..24], &7i64.to_le_bytes());
+        assert_eq!(data[24], 1);
+        assert_eq!(&data[25..], &9u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_release_lock_data() {
+        let data = release_lock_data(9).unwrap();
+        assert_eq!(
+            &data[..8],
+            &hash::hash(b"global:release_lock").to_bytes()[..8]
+        );
+        assert_eq!(data[8..], 9u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_commit_balances_root_data() {
+        let data = commit_balances_root_data(9, &[7; 32]).unwrap();
+        assert_eq!(
+            &data[..8],
+            &hash::hash(b"global:commit_balances_root").to_bytes()[..8]
+        );
+        assert_eq!(&data[8..16], &9u64.to_le_bytes());
+        assert_eq!(data[16..], [7; 32]);
+    }
+
+    #[test]
+    fn test_vault_call_data_matches_fixtures() {
+        let deltas: Vec<UserDelta> = fixtures::BATCH
+            .bets
+            .iter()
+            .map(|bet| UserDelta {
+                user: Pubkey::new_from_array(bet.user),
+                sol_delta: bet.sol_delta(),
+                usdc_delta: 0,
+                is_win: bet.is_win(),
+                bet_amount: bet.bet_amount,
+            })
+            .collect();
+        let first = &deltas[0];
+        let data = update_balances_data(
+            first.sol_delta,
+            first.usdc_delta,
+            first.is_win,
+            first.bet_amount,
+        )
+        .unwrap();
+        assert_eq!(data, fixtures::hex(fixtures::UPDATE_BALANCES));
+        let data = update_balances_batch_data(&deltas).unwrap();
+        assert_eq!(data, fixtures::hex(fixtures::UPDATE_BALANCES_BATCH));
+    }
+}
@@ -45,84 +45,39 @@ pub const VERIFYING_KEY_BYTES: &[u8] = &[
     0x89, 0x07, 0xab, 0x87, 0xf2, 0x0e, 0x0d, 0x51, 0x2d, 0xc9, 0x99, 0x21, 0x70, 0xc2, 0x56, 0x03,
 ];
 
-/// Parse the embedded verifying key bytes into a Groth16VerifyingKey structure
-pub fn get_embedded_verifying_key() -> Result<Groth16VerifyingKey> {
-    msg!("Loading embedded verifying key");
-
-    if VERIFYING_KEY_BYTES.len() < 288 {
-        // Minimum size: alpha(64) + beta(128) + gamma(128) + delta(128) + at least 1 IC(64) = 512 bytes
-        // But arkworks compressed format is smaller
+/// Size of alpha (G1) plus beta, gamma and delta (G2), before the IC points
+pub const VERIFYING_KEY_POINTS_SIZE: usize = 64 + 3 * 128;
+
+/// Size of one IC point (G1)
+pub const IC_POINT_SIZE: usize = 64;
+
+/// Parse a verifying key laid out as alpha, beta, gamma, delta, then one IC point
+/// per public input plus IC[0]. Trailing bytes short of a full IC point are ignored.
+pub fn parse_verifying_key(bytes: &[u8]) -> Result<Groth16VerifyingKey> {
+    // At least IC[0] and IC[1] (one public input)
+    if bytes.len() < VERIFYING_KEY_POINTS_SIZE + 2 * IC_POINT_SIZE {
+        msg!("Error: Verifying key too short ({} bytes)", bytes.len());
         return Err(VerifierError::InvalidVerifyingKey.into());
     }
 
-    let mut offset = 0;
-
-    // Parse alpha (G1 point - 64 bytes)
-    let alpha = G1Point {
-        x: VERIFYING_KEY_BYTES[offset..offset + 32].try_into().unwrap(),
-        y: VERIFYING_KEY_BYTES[offset + 32..offset + 64]
-            .try_into()
-            .unwrap(),
-    };
-    offset += 64;
-
-    // Parse beta (G2 point - 128 bytes)
-    let beta = G2Point {
-        x: VERIFYING_KEY_BYTES[offset..offset + 64].try_into().unwrap(),
-        y: VERIFYING_KEY_BYTES[offset + 64..offset + 128]
-            .try_into()
-            .unwrap(),
-    };
-    offset += 128;
-
-    // Parse gamma (G2 point - 128 bytes)
-    let gamma = G2Point {
-        x: VERIFYING_KEY_BYTES[offset..offset + 64].try_into().unwrap(),
-        y: VERIFYING_KEY_BYTES[offset + 64..offset + 128]
-            .try_into()
-            .unwrap(),
+    let g1 = |at: usize| G1Point {
+        x: bytes[at..at + 32].try_into().unwrap(),
+        y: bytes[at + 32..at + 64].try_into().unwrap(),
     };
-    offset += 128;
-
-    // Parse delta (G2 point - 128 bytes)
-    let delta = G2Point {
-        x: VERIFYING_KEY_BYTES[offset..offset + 64].try_into().unwrap(),
-        y: VERIFYING_KEY_BYTES[offset + 64..offset + 128]
-            .try_into()
-            .unwrap(),
+    let g2 = |at: usize| G2Point {
+        x: bytes[at..at + 64].try_into().unwrap(),
+        y: bytes[at + 64..at + 128].try_into().unwrap(),
     };
-    offset += 128;
-
-    // Parse IC points (multiple G1 points)
-    // For our circuit, we expect 2 IC points: IC[0] and IC[1] (for 1 public input)
-    let mut ic = Vec::new();
-
-    // IC[0]
-    if offset + 64 <= VERIFYING_KEY_BYTES.len() {
-        ic.push(G1Point {
-            x: VERIFYING_KEY_BYTES[offset..offset + 32].try_into().unwrap(),
-            y: VERIFYING_KEY_BYTES[offset + 32..offset + 64]
-                .try_into()
-                .unwrap(),
-        });
-        offset += 64;
-    }
 
-    // IC[1]
-    if offset + 64 <= VERIFYING_KEY_BYTES.len() {
-        ic.push(G1Point {
-            x: VERIFYING_KEY_BYTES[offset..offset + 32].try_into().unwrap(),
-            y: VERIFYING_KEY_BYTES[offset + 32..offset + 64]
-                .try_into()
-                .unwrap(),
-        });
-        offset += 64;
-    }
+    let alpha = g1(0);
+    let beta = g2(64);
+    let gamma = g2(64 + 128);
+    let delta = g2(64 + 2 * 128);
 
-    if ic.len() < 2 {
-        msg!("Error: Insufficient IC points in verifying key");
-        return Err(VerifierError::InvalidVerifyingKey.into());
-    }
+    let ic_count = (bytes.len() - VERIFYING_KEY_POINTS_SIZE) / IC_POINT_SIZE;
+    let ic = (0..ic_count)
+        .map(|i| g1(VERIFYING_KEY_POINTS_SIZE + i * IC_POINT_SIZE))
+        .collect::<Vec<_>>();
 
     msg!("✓ Verifying key loaded: {} IC points", ic.len());
 
@@ -141,7 +96,7 @@ mod tests {
 
     #[test]
     fn test_embedded_verifying_key_parsing() {
-        let vk = get_embedded_verifying_key().unwrap();
+        let vk = parse_verifying_key(VERIFYING_KEY_BYTES).unwrap();
 
         // Check that we have the expected structure
         assert_eq!(vk.ic.len(), 2); // IC[0] and IC[1] for 1 public input
@@ -153,6 +108,18 @@ mod tests {
         assert_ne!(vk.beta.y, [0u8; 64]);
     }
 
+    #[test]
+    fn test_parse_verifying_key_ic_count() {
+        let points = &VERIFYING_KEY_BYTES[..VERIFYING_KEY_POINTS_SIZE];
+
+        // One IC point is not enough for any circuit
+        let short = [points, &[1u8; IC_POINT_SIZE]].concat();
+        assert!(parse_verifying_key(&short).is_err());
+
+        let three_inputs = [points, &[1u8; 4 * IC_POINT_SIZE]].concat();
+        assert_eq!(parse_verifying_key(&three_inputs).unwrap().ic.len(), 4);
+    }
+
     #[test]
     fn test_verifying_key_size() {
         // Ensure we have enough bytes for the expected structure
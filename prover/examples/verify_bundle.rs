@@ -0,0 +1,59 @@
+// Verify a batch export bundle (written by `sequencer export`) offline:
+//   cargo run -p prover --example verify_bundle -- <bundle dir>
+use prover::proof_generator::verify_serialized_proof;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(
+        std::env::args()
+            .nth(1)
+            .ok_or("usage: verify_bundle <bundle dir>")?,
+    );
+    let manifest: Value = serde_json::from_slice(&fs::read(dir.join("manifest.json"))?)?;
+
+    let mut keys = HashMap::new();
+    for key in manifest["verifying_keys"]
+        .as_array()
+        .ok_or("no verifying_keys")?
+    {
+        let hash = key["hash"].as_str().ok_or("key without hash")?;
+        let file = key["file"].as_str().ok_or("key without file")?;
+        keys.insert(hash.to_string(), fs::read(dir.join(file))?);
+    }
+
+    let (mut verified, mut unproven, mut failed) = (0, 0, 0);
+    for batch in manifest["batches"].as_array().ok_or("no batches")? {
+        let batch_id = &batch["batch_id"];
+        let (Some(proof_file), Some(key_hash)) = (
+            batch["proof_file"].as_str(),
+            batch["verifying_key_hash"].as_str(),
+        ) else {
+            println!("batch {}: no ZK proof (placeholder settlement)", batch_id);
+            unproven += 1;
+            continue;
+        };
+        let key = keys.get(key_hash).ok_or("batch references a missing key")?;
+        match verify_serialized_proof(key, &fs::read(dir.join(proof_file))?) {
+            Ok(true) => {
+                println!("batch {}: proof OK (key {})", batch_id, key_hash);
+                verified += 1;
+            }
+            Ok(false) | Err(_) => {
+                println!("batch {}: proof INVALID", batch_id);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "\n{} verified, {} without proof, {} invalid",
+        verified, unproven, failed
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
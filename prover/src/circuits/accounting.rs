@@ -1,4 +1,5 @@
 use ark_bn254::{Bn254, Fr};
+use ark_ff::{BigInteger, PrimeField, Zero};
 use ark_groth16::{prepare_verifying_key, Groth16, Proof, ProvingKey, VerifyingKey};
 use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
 use ark_serialize::CanonicalSerialize;
@@ -6,6 +7,15 @@ use ark_snark::SNARK;
 use rand::thread_rng;
 use std::collections::HashMap;
 
+use super::poseidon::{enforce_poseidon, poseidon_hash, Wire};
+use super::state_tree::{
+    enforce_index_bits, enforce_root, key_halves, leaf_balance, user_key, LeafUpdate, StateTree,
+    STATE_TREE_DEPTH,
+};
+
+/// Default width of range-checked amounts and balances: values are u64 lamports
+pub const DEFAULT_RANGE_BITS: usize = 64;
+
 /// Represents a single bet in the accounting circuit
 #[derive(Clone, Debug)]
 pub struct Bet {
@@ -29,6 +39,8 @@ impl Bet {
         self.guess == self.outcome
     }
 
+    /// Payouts are whole base units, rounded down: floor(amount * 2 / 1) for coinflip.
+    /// Amounts reach the circuit already normalized to the token's unit by the sequencer.
     pub fn payout(&self) -> u64 {
         if self.won() {
             self.amount * 2 // Win = 2x bet amount
@@ -46,6 +58,42 @@ impl Bet {
     }
 }
 
+/// A bet as the batch submits it on chain (the verifier's BetSettlement)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BetRecord {
+    pub bet_id: u64,
+    pub user: [u8; 32],
+    pub bet_amount: u64,
+    pub user_guess: u8,
+    pub outcome: u8,
+    pub payout: u64,
+}
+
+impl BetRecord {
+    /// Poseidon inputs of the record's leaf; the user key is split into two 128-bit
+    /// halves so each is a field element
+    fn inputs(&self) -> [Fr; 7] {
+        [
+            Fr::from(self.bet_id),
+            Fr::from_be_bytes_mod_order(&self.user[..16]),
+            Fr::from_be_bytes_mod_order(&self.user[16..]),
+            Fr::from(self.bet_amount),
+            Fr::from(self.user_guess as u64),
+            Fr::from(self.outcome as u64),
+            Fr::from(self.payout),
+        ]
+    }
+}
+
+/// Commitment to a batch's bet list: Poseidon(commitment so far, Poseidon(record)) over
+/// the records in order, starting from zero. The verifier recomputes it from the
+/// submitted bets, so a proof only verifies for the bets it was generated for.
+pub fn batch_commitment(records: &[BetRecord]) -> Fr {
+    records.iter().fold(Fr::zero(), |commitment, record| {
+        poseidon_hash(&[commitment, poseidon_hash(&record.inputs())])
+    })
+}
+
 /// Batch of bets for accounting circuit
 #[derive(Clone, Debug)]
 pub struct BetBatch {
@@ -87,40 +135,132 @@ impl BetBatch {
 /// Proves correctness of balance updates for a batch of coin flip bets
 #[derive(Clone)]
 pub struct AccountingCircuit {
-    // Private inputs (witness)
-    pub bets: Vec<Bet>,
-
     // Public inputs (instance)
     pub batch_id: Fr,
-    pub initial_balances: Vec<Fr>, // Initial user balances
-    pub final_balances: Vec<Fr>,   // Final user balances after bets
-    pub house_initial: Fr,         // House initial balance
-    pub house_final: Fr,           // House final balance
+    pub batch_commitment: Fr, // batch_commitment of the records
+    pub prev_state_root: Fr,  // Balance state root the batch starts from
+    pub new_state_root: Fr,   // Root after the players' leaf updates
+
+    // Private inputs (witness)
+    pub bets: Vec<Bet>,
+    pub players: Vec<LeafUpdate>, // Leaf update of each user slot, applied in order
+    pub house_initial: Fr,        // House initial balance
+    pub house_final: Fr,          // House final balance
+    pub records: Vec<BetRecord>,  // The bets as submitted on chain
+
+    // Circuit shape
+    pub range_bits: usize, // Bet amounts, payouts and the house balance must fit in this many bits
+    pub user_capacity: usize, // User slots; those past the last player are padding
+    pub record_capacity: usize, // Record slots; those past the last record are padding
+    pub net_positions: bool, // Records net each user slot's balance change, not one bet each
 }
 
 impl AccountingCircuit {
+    /// Circuit over a state holding exactly `initial_balances`, user slot i being
+    /// user_key(i) at leaf i, that moves them to `final_balances`
     pub fn new(
         bets: Vec<Bet>,
         batch_id: u32,
-        initial_balances: &[u64],
-        final_balances: &[u64],
+        initial_balances: &[i64],
+        final_balances: &[i64],
         house_initial: u64,
         house_final: u64,
     ) -> Self {
-        Self {
+        let mut tree = StateTree::new();
+        for (user_id, &balance) in initial_balances.iter().enumerate() {
+            tree.update(user_key(user_id as u32), balance)
+                .expect("test state fits the tree");
+        }
+        let prev_state_root = tree.root();
+        let players = final_balances
+            .iter()
+            .enumerate()
+            .map(|(user_id, &balance)| {
+                tree.update(user_key(user_id as u32), balance)
+                    .expect("test state fits the tree")
+            })
+            .collect();
+        Self::from_state(
             bets,
+            batch_id,
+            prev_state_root,
+            players,
+            initial_balances.len(),
+            house_initial,
+            house_final,
+        )
+    }
+
+    /// Circuit applying `players`' leaf updates, in order, to the state at
+    /// `prev_state_root`, in a circuit with `user_capacity` user slots
+    pub fn from_state(
+        bets: Vec<Bet>,
+        batch_id: u32,
+        prev_state_root: Fr,
+        players: Vec<LeafUpdate>,
+        user_capacity: usize,
+        house_initial: u64,
+        house_final: u64,
+    ) -> Self {
+        let new_state_root = players
+            .last()
+            .map_or(prev_state_root, |player| player.roots().1);
+        Self {
             batch_id: Fr::from(batch_id),
-            initial_balances: initial_balances.iter().map(|&b| Fr::from(b)).collect(),
-            final_balances: final_balances.iter().map(|&b| Fr::from(b)).collect(),
+            batch_commitment: batch_commitment(&[]),
+            prev_state_root,
+            new_state_root,
+            bets,
+            players,
             house_initial: Fr::from(house_initial),
             house_final: Fr::from(house_final),
+            records: Vec::new(),
+            range_bits: DEFAULT_RANGE_BITS,
+            user_capacity,
+            record_capacity: 0,
+            net_positions: false,
         }
     }
 
+    /// Commit to `records` in a circuit with `capacity` record slots. Unless positions
+    /// are netted, record i must be bet i and bets without a record must stake nothing.
+    /// Changes the circuit structure, so keys must be set up with the same capacity.
+    pub fn with_records(mut self, records: Vec<BetRecord>, capacity: usize) -> Self {
+        self.batch_commitment = batch_commitment(&records);
+        self.records = records;
+        self.record_capacity = capacity;
+        self
+    }
+
+    /// Bets are the players' netted positions: each user slot's balance change must
+    /// equal the Σ (payout - bet_amount) of the records for its key. Changes the
+    /// circuit structure, so keys must be set up the same way.
+    pub fn with_net_positions(mut self) -> Self {
+        self.net_positions = true;
+        self
+    }
+
+    /// Public inputs in the order the circuit allocates them
+    pub fn public_inputs(&self) -> Vec<Fr> {
+        vec![
+            self.batch_id,
+            self.batch_commitment,
+            self.prev_state_root,
+            self.new_state_root,
+        ]
+    }
+
+    /// Range-check values to `bits` bits instead of DEFAULT_RANGE_BITS. Changes the
+    /// circuit structure, so keys must be set up with the same width.
+    pub fn with_range_bits(mut self, bits: usize) -> Self {
+        self.range_bits = bits;
+        self
+    }
+
     /// Create circuit from bet batch with automatic balance calculation
     pub fn from_batch(
         batch: &BetBatch,
-        user_initial_balances: &HashMap<u32, u64>,
+        user_initial_balances: &HashMap<u32, i64>,
         house_initial: u64,
     ) -> Self {
         let deltas = batch.calculate_balance_deltas();
@@ -133,10 +273,9 @@ impl AccountingCircuit {
         for user_id in 0..=max_user_id {
             let initial = user_initial_balances.get(&user_id).copied().unwrap_or(0);
             let delta = deltas.get(&user_id).copied().unwrap_or(0);
-            let final_balance = (initial as i64 + delta) as u64;
 
             initial_balances.push(initial);
-            final_balances.push(final_balance);
+            final_balances.push(initial + delta);
         }
 
         let house_delta = batch.house_delta();
@@ -153,25 +292,75 @@ impl AccountingCircuit {
     }
 }
 
+/// Allocate a slot's active flag: 0 or 1, and only set if the previous slot's is, so
+/// the active slots are a prefix
+fn enforce_active_slot(
+    cs: &ConstraintSystemRef<Fr>,
+    active: bool,
+    previous_active_var: Option<Variable>,
+) -> Result<Variable, SynthesisError> {
+    let active_var = cs.new_witness_variable(|| Ok(Fr::from(active as u64)))?;
+
+    // active * (active - 1) = 0  =>  active ∈ {0, 1}
+    cs.enforce_constraint(
+        ark_relations::lc!() + active_var,
+        ark_relations::lc!() + active_var - Variable::One,
+        ark_relations::lc!(),
+    )?;
+    // active * (1 - previous_active) = 0
+    if let Some(previous_active_var) = previous_active_var {
+        cs.enforce_constraint(
+            ark_relations::lc!() + active_var,
+            ark_relations::lc!() + Variable::One - previous_active_var,
+            ark_relations::lc!(),
+        )?;
+    }
+    Ok(active_var)
+}
+
+/// Constrain `var`, holding `value`, to `bits` bits: allocate its binary digits, force
+/// each to be 0 or 1 and require them to recompose to `var`. Without this, the field
+/// element p - x (a "negative" x) would pass for a huge positive number.
+fn enforce_bit_range(
+    cs: &ConstraintSystemRef<Fr>,
+    var: Variable,
+    value: Fr,
+    bits: usize,
+) -> Result<(), SynthesisError> {
+    let value = value.into_bigint();
+    let mut recomposed = ark_relations::lc!();
+    let mut weight = Fr::from(1u64);
+    for i in 0..bits {
+        let bit_var = cs.new_witness_variable(|| Ok(Fr::from(value.get_bit(i) as u64)))?;
+
+        // bit * (bit - 1) = 0  =>  bit ∈ {0, 1}
+        cs.enforce_constraint(
+            ark_relations::lc!() + bit_var,
+            ark_relations::lc!() + bit_var - Variable::One,
+            ark_relations::lc!(),
+        )?;
+        recomposed += (weight, bit_var);
+        weight = weight + weight;
+    }
+
+    // Σ bit_i * 2^i = var
+    cs.enforce_constraint(
+        recomposed,
+        ark_relations::lc!() + Variable::One,
+        ark_relations::lc!() + var,
+    )
+}
+
 impl ConstraintSynthesizer<Fr> for AccountingCircuit {
     fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
         // Public inputs
         let _batch_id_var = cs.new_input_variable(|| Ok(self.batch_id))?;
+        let batch_commitment_var = cs.new_input_variable(|| Ok(self.batch_commitment))?;
+        let prev_state_root_var = cs.new_input_variable(|| Ok(self.prev_state_root))?;
+        let new_state_root_var = cs.new_input_variable(|| Ok(self.new_state_root))?;
 
-        // Initial and final balance variables (public)
-        let mut initial_balance_vars = Vec::new();
-        let mut final_balance_vars = Vec::new();
-
-        for &balance in &self.initial_balances {
-            initial_balance_vars.push(cs.new_input_variable(|| Ok(balance))?);
-        }
-
-        for &balance in &self.final_balances {
-            final_balance_vars.push(cs.new_input_variable(|| Ok(balance))?);
-        }
-
-        let _house_initial_var = cs.new_input_variable(|| Ok(self.house_initial))?;
-        let _house_final_var = cs.new_input_variable(|| Ok(self.house_final))?;
+        let house_initial_var = cs.new_witness_variable(|| Ok(self.house_initial))?;
+        let house_final_var = cs.new_witness_variable(|| Ok(self.house_final))?;
 
         // Private inputs - bet data
         let mut bet_user_vars = Vec::new();
@@ -205,11 +394,12 @@ impl ConstraintSynthesizer<Fr> for AccountingCircuit {
             )?;
         }
 
-        // Constraint 2: Calculate deltas and enforce balance updates
-        // For now, we'll implement a simplified version for small batches
-        // TODO: Optimize for larger batches with more efficient constraint patterns
-
-        let mut user_delta_vars = Vec::new();
+        // Constraint 2: Each bet's delta, credited to the user slot it names. slot_deltas
+        // collects, per slot, the deltas of the bets selecting it.
+        let mut slot_deltas = vec![ark_relations::lc!(); self.user_capacity];
+        let mut total_delta = ark_relations::lc!();
+        let mut bet_payout_vars = Vec::with_capacity(self.bets.len());
+        let mut bet_selector_vars = Vec::with_capacity(self.bets.len());
 
         for i in 0..self.bets.len() {
             // Calculate win condition: won = (guess == outcome)
@@ -243,20 +433,372 @@ impl ConstraintSynthesizer<Fr> for AccountingCircuit {
                 ark_relations::lc!() + won_var,
             )?;
 
-            // Calculate delta: if won, delta = +amount, else delta = -amount
-            // delta = won * 2 * amount - amount = amount * (2 * won - 1)
-            let delta_var = cs.new_witness_variable(|| {
-                Ok(Fr::from(
-                    (self.bets[i].delta() + (1u64 << 32) as i64) as u64,
-                )) // Offset for field arithmetic
-            })?;
+            // payout = 2 * amount * won, computed in the field so it can't overflow u64
+            let payout_value =
+                Fr::from(self.bets[i].amount) * Fr::from(2 * self.bets[i].won() as u64);
+            let payout_var = cs.new_witness_variable(|| Ok(payout_value))?;
+            cs.enforce_constraint(
+                ark_relations::lc!() + (Fr::from(2u64), bet_amount_vars[i]),
+                ark_relations::lc!() + won_var,
+                ark_relations::lc!() + payout_var,
+            )?;
 
-            user_delta_vars.push((self.bets[i].user_id, delta_var));
+            // Amounts and payouts can't wrap around the field
+            enforce_bit_range(
+                &cs,
+                bet_amount_vars[i],
+                Fr::from(self.bets[i].amount),
+                self.range_bits,
+            )?;
+            enforce_bit_range(&cs, payout_var, payout_value, self.range_bits)?;
+            bet_payout_vars.push(payout_var);
+
+            // delta = amount * (2 * won - 1): +amount on a win, -amount on a loss
+            let delta_value = if self.bets[i].won() {
+                Fr::from(self.bets[i].amount)
+            } else {
+                -Fr::from(self.bets[i].amount)
+            };
+            let delta_var = cs.new_witness_variable(|| Ok(delta_value))?;
+            cs.enforce_constraint(
+                ark_relations::lc!() + bet_amount_vars[i],
+                ark_relations::lc!() + (Fr::from(2u64), won_var) - Variable::One,
+                ark_relations::lc!() + delta_var,
+            )?;
+            total_delta = total_delta + delta_var;
+
+            // One selector per slot, exactly one of them set: the one numbered user_id
+            let mut selected = ark_relations::lc!();
+            let mut selected_slot = ark_relations::lc!();
+            let mut selector_vars = Vec::with_capacity(self.user_capacity);
+            for (slot, slot_delta) in slot_deltas.iter_mut().enumerate() {
+                let is_user = self.bets[i].user_id as usize == slot;
+                let selector_var = cs.new_witness_variable(|| Ok(Fr::from(is_user as u64)))?;
+                // selector * (selector - 1) = 0
+                cs.enforce_constraint(
+                    ark_relations::lc!() + selector_var,
+                    ark_relations::lc!() + selector_var - Variable::One,
+                    ark_relations::lc!(),
+                )?;
+                selected = selected + selector_var;
+                selected_slot += (Fr::from(slot as u64), selector_var);
+                selector_vars.push(selector_var);
+
+                // share = selector * delta: the delta if the bet is this slot's
+                let share_var =
+                    cs.new_witness_variable(|| Ok(if is_user { delta_value } else { Fr::zero() }))?;
+                cs.enforce_constraint(
+                    ark_relations::lc!() + selector_var,
+                    ark_relations::lc!() + delta_var,
+                    ark_relations::lc!() + share_var,
+                )?;
+                *slot_delta = slot_delta.clone() + share_var;
+            }
+            // Σ selectors = 1 and Σ slot * selector = user_id
+            cs.enforce_constraint(
+                selected,
+                ark_relations::lc!() + Variable::One,
+                ark_relations::lc!() + Variable::One,
+            )?;
+            cs.enforce_constraint(
+                selected_slot,
+                ark_relations::lc!() + Variable::One,
+                ark_relations::lc!() + bet_user_vars[i],
+            )?;
+            bet_selector_vars.push(selector_vars);
         }
 
-        // Constraint 3: Conservation - sum of all deltas = 0
-        // This will be implemented in a follow-up for simplicity
-        // For now, we trust the balance calculations are correct
+        // Constraint 3: Each user slot's leaf update moves the state root on, from
+        // prev_state_root to new_state_root. Active slots are a prefix; the rest are
+        // padding and leave the root unchanged.
+        let mut root = Wire::new(prev_state_root_var, self.prev_state_root);
+        let mut slot_keys = Vec::with_capacity(self.user_capacity);
+        let mut previous_active_var = None;
+        for (slot, slot_delta) in slot_deltas.iter().enumerate() {
+            let update = self.players.get(slot).cloned().unwrap_or_default();
+            let active = slot < self.players.len();
+            let active_var = enforce_active_slot(&cs, active, previous_active_var)?;
+            previous_active_var = Some(active_var);
+
+            let [high, low] = key_halves(&update.key);
+            let (high, low) = (Wire::witness(&cs, high)?, Wire::witness(&cs, low)?);
+            slot_keys.push((high.clone(), low.clone()));
+            let initial = Wire::witness(
+                &cs,
+                Fr::from(leaf_balance(update.initial_balance.unwrap_or(0))),
+            )?;
+            let final_balance = Fr::from(leaf_balance(update.final_balance));
+            let final_var = cs.new_witness_variable(|| Ok(final_balance))?;
+
+            // final - initial = Σ deltas of the slot's bets. Leaves offset both balances
+            // alike, so the difference is the balance change.
+            cs.enforce_constraint(
+                ark_relations::lc!() + final_var - &initial.lc,
+                ark_relations::lc!() + Variable::One,
+                slot_delta.clone(),
+            )?;
+            // (1 - active) * (final - initial) = 0: padding slots don't move, so no bet
+            // can be settled into a leaf that is left out of the root
+            cs.enforce_constraint(
+                ark_relations::lc!() + Variable::One - active_var,
+                ark_relations::lc!() + final_var - &initial.lc,
+                ark_relations::lc!(),
+            )?;
+
+            // occupied * (occupied - 1) = 0: whether the player had a leaf before
+            let occupied = Wire::witness(&cs, Fr::from(update.initial_balance.is_some() as u64))?;
+            cs.enforce_constraint(
+                occupied.lc.clone(),
+                occupied.lc.clone() - Variable::One,
+                ark_relations::lc!(),
+            )?;
+            // (1 - occupied) * (initial - offset zero) = 0: a new player starts at zero
+            cs.enforce_constraint(
+                ark_relations::lc!() + Variable::One - &occupied.lc,
+                initial.lc.clone() - (Fr::from(leaf_balance(0)), Variable::One),
+                ark_relations::lc!(),
+            )?;
+
+            // initial_leaf = occupied * Poseidon(key, initial); an unused leaf is zero
+            let initial_hash = enforce_poseidon(&cs, &[high.clone(), low.clone(), initial])?;
+            let initial_leaf = Wire::witness(&cs, update.initial_leaf())?;
+            cs.enforce_constraint(
+                occupied.lc.clone(),
+                initial_hash.lc,
+                initial_leaf.lc.clone(),
+            )?;
+            let final_leaf =
+                enforce_poseidon(&cs, &[high, low, Wire::new(final_var, final_balance)])?;
+
+            let bits = enforce_index_bits(&cs, update.index)?;
+            let siblings = (0..STATE_TREE_DEPTH)
+                .map(|height| {
+                    Wire::witness(
+                        &cs,
+                        update.siblings.get(height).copied().unwrap_or_default(),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let initial_root = enforce_root(&cs, initial_leaf, &bits, &siblings)?;
+            let final_root = enforce_root(&cs, final_leaf, &bits, &siblings)?;
+
+            // active * (initial_root - root) = 0: the leaf was in the tree so far
+            cs.enforce_constraint(
+                ark_relations::lc!() + active_var,
+                initial_root.lc - &root.lc,
+                ark_relations::lc!(),
+            )?;
+            // next = root + active * (final_root - root)
+            let next = Wire::witness(&cs, if active { final_root.value } else { root.value })?;
+            cs.enforce_constraint(
+                ark_relations::lc!() + active_var,
+                final_root.lc - &root.lc,
+                next.lc.clone() - &root.lc,
+            )?;
+            root = next;
+
+            // The offset balance is a u64, so it can't wrap around the field
+            enforce_bit_range(&cs, final_var, final_balance, 64)?;
+        }
+        cs.enforce_constraint(
+            root.lc,
+            ark_relations::lc!() + Variable::One,
+            ark_relations::lc!() + new_state_root_var,
+        )?;
+
+        // Constraint 4: Conservation - Σ deltas + (house_final - house_initial) = 0, with
+        // both house balances in range so neither can wrap around the field
+        cs.enforce_constraint(
+            total_delta + house_final_var - house_initial_var,
+            ark_relations::lc!() + Variable::One,
+            ark_relations::lc!(),
+        )?;
+        enforce_bit_range(&cs, house_initial_var, self.house_initial, self.range_bits)?;
+        enforce_bit_range(&cs, house_final_var, self.house_final, self.range_bits)?;
+
+        // Constraint 5: The batch commitment is the Poseidon chain over the records.
+        // Records fill the first slots: a slot is only active if the one before is,
+        // and inactive slots leave the chain unchanged. Unless positions are netted,
+        // record i is bet i.
+        let mut commitment = Wire::constant(Fr::zero());
+        let mut record_nets = vec![ark_relations::lc!(); self.user_capacity];
+        let mut previous_active_var = None;
+        for slot in 0..self.record_capacity {
+            let record = self.records.get(slot).cloned().unwrap_or_default();
+            let active = slot < self.records.len();
+            let active_var = enforce_active_slot(&cs, active, previous_active_var)?;
+            previous_active_var = Some(active_var);
+
+            let mut fields = Vec::with_capacity(7);
+            for (value, bits) in record.inputs().into_iter().zip([
+                64, // bet_id
+                0,  // user key halves; the Poseidon leaf pins them to the verifier's bytes
+                0,
+                self.range_bits, // bet_amount
+                1,               // user_guess
+                1,               // outcome
+                self.range_bits, // payout
+            ]) {
+                let var = cs.new_witness_variable(|| Ok(value))?;
+                // The fields are the verifier's integers, so none may wrap around the field
+                if bits > 0 {
+                    enforce_bit_range(&cs, var, value, bits)?;
+                }
+                fields.push(Wire::new(var, value));
+            }
+
+            if self.net_positions {
+                // The record is assigned to the one user slot holding its key, if active
+                let user_slot = self
+                    .players
+                    .iter()
+                    .position(|player| player.key == record.user)
+                    .filter(|_| active);
+                let net = fields[6].lc.clone() - &fields[3].lc;
+                let net_value = fields[6].value - fields[3].value;
+                let mut assigned = ark_relations::lc!();
+                for (slot, (high, low)) in slot_keys.iter().enumerate() {
+                    let is_user = user_slot == Some(slot);
+                    let assigned_var = cs.new_witness_variable(|| Ok(Fr::from(is_user as u64)))?;
+                    // assigned * (assigned - 1) = 0
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + assigned_var,
+                        ark_relations::lc!() + assigned_var - Variable::One,
+                        ark_relations::lc!(),
+                    )?;
+                    assigned = assigned + assigned_var;
+
+                    // assigned * (slot key - record key) = 0, for both halves
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + assigned_var,
+                        high.lc.clone() - &fields[1].lc,
+                        ark_relations::lc!(),
+                    )?;
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + assigned_var,
+                        low.lc.clone() - &fields[2].lc,
+                        ark_relations::lc!(),
+                    )?;
+
+                    // share = assigned * (payout - bet_amount)
+                    let share_var = cs.new_witness_variable(|| {
+                        Ok(if is_user { net_value } else { Fr::zero() })
+                    })?;
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + assigned_var,
+                        net.clone(),
+                        ark_relations::lc!() + share_var,
+                    )?;
+                    record_nets[slot] = record_nets[slot].clone() + share_var;
+                }
+                // Σ assigned = active: an active record is in exactly one slot
+                cs.enforce_constraint(
+                    assigned,
+                    ark_relations::lc!() + Variable::One,
+                    ark_relations::lc!() + active_var,
+                )?;
+            } else if slot < self.bets.len() {
+                // The record is the bet in the same position: active * (bet - record) = 0
+                // for each of the bet's fields
+                for (bet_var, field) in [
+                    (bet_amount_vars[slot], &fields[3]),
+                    (bet_guess_vars[slot], &fields[4]),
+                    (bet_outcome_vars[slot], &fields[5]),
+                    (bet_payout_vars[slot], &fields[6]),
+                ] {
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + active_var,
+                        ark_relations::lc!() + bet_var - &field.lc,
+                        ark_relations::lc!(),
+                    )?;
+                }
+                // (1 - active) * amount = 0: a bet without a record can't move balances
+                cs.enforce_constraint(
+                    ark_relations::lc!() + Variable::One - active_var,
+                    ark_relations::lc!() + bet_amount_vars[slot],
+                    ark_relations::lc!(),
+                )?;
+
+                // The record's user holds the key of the slot the bet selects:
+                // recorded = active * selector, recorded * (slot key - record key) = 0
+                let bet = &self.bets[slot];
+                for (user_slot, (selector_var, (high, low))) in
+                    bet_selector_vars[slot].iter().zip(&slot_keys).enumerate()
+                {
+                    let recorded = active && bet.user_id as usize == user_slot;
+                    let recorded_var = cs.new_witness_variable(|| Ok(Fr::from(recorded as u64)))?;
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + active_var,
+                        ark_relations::lc!() + *selector_var,
+                        ark_relations::lc!() + recorded_var,
+                    )?;
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + recorded_var,
+                        high.lc.clone() - &fields[1].lc,
+                        ark_relations::lc!(),
+                    )?;
+                    cs.enforce_constraint(
+                        ark_relations::lc!() + recorded_var,
+                        low.lc.clone() - &fields[2].lc,
+                        ark_relations::lc!(),
+                    )?;
+                }
+            } else {
+                // A record past the last bet slot has no bet to be: it must be padding
+                cs.enforce_constraint(
+                    ark_relations::lc!() + active_var,
+                    ark_relations::lc!() + Variable::One,
+                    ark_relations::lc!(),
+                )?;
+            }
+
+            let leaf = enforce_poseidon(&cs, &fields)?;
+            let chained = enforce_poseidon(&cs, &[commitment.clone(), leaf])?;
+
+            // next = commitment + active * (chained - commitment)
+            let next = Wire::witness(
+                &cs,
+                if active {
+                    chained.value
+                } else {
+                    commitment.value
+                },
+            )?;
+            cs.enforce_constraint(
+                ark_relations::lc!() + active_var,
+                chained.lc - &commitment.lc,
+                next.lc.clone() - &commitment.lc,
+            )?;
+            commitment = next;
+        }
+        cs.enforce_constraint(
+            commitment.lc,
+            ark_relations::lc!() + Variable::One,
+            ark_relations::lc!() + batch_commitment_var,
+        )?;
+
+        // Bets past the last record slot have no record to be, so can't move balances
+        if !self.net_positions && self.record_capacity > 0 {
+            for amount_var in bet_amount_vars.iter().skip(self.record_capacity) {
+                cs.enforce_constraint(
+                    ark_relations::lc!() + *amount_var,
+                    ark_relations::lc!() + Variable::One,
+                    ark_relations::lc!(),
+                )?;
+            }
+        }
+
+        // Constraint 6: With netted positions, each user slot's balance change is what
+        // its records pay out net of their stakes
+        if self.net_positions {
+            for (record_net, slot_delta) in record_nets.into_iter().zip(slot_deltas) {
+                cs.enforce_constraint(
+                    record_net,
+                    ark_relations::lc!() + Variable::One,
+                    slot_delta,
+                )?;
+            }
+        }
 
         Ok(())
     }
@@ -276,13 +818,14 @@ impl AccountingProofSystem {
         // Create a dummy circuit for setup with the maximum expected batch size
         // Use a fixed number of users (2) for consistent circuit structure
         let dummy_bets = vec![Bet::new(0, 1000, true, true); max_batch_size];
+        let winnings = 1000 * max_batch_size as u64;
         let circuit = AccountingCircuit::new(
             dummy_bets,
             1,
-            &[10000, 10000], // 2 users with 10000 each
-            &[11000, 11000], // Both gain 1000
-            1000000,         // House initial
-            998000,          // House loses 2000 (2 winning bets)
+            &[10000, 10000],                   // 2 users with 10000 each
+            &[10000 + winnings as i64, 10000], // User 0 wins every bet
+            1000000,                           // House initial
+            1000000 - winnings,                // House pays the winnings
         );
 
         let (proving_key, verifying_key) =
@@ -383,14 +926,288 @@ mod tests {
 
         // Verify public inputs are correct
         assert_eq!(circuit.batch_id, Fr::from(42u64));
-        assert_eq!(circuit.initial_balances[0], Fr::from(10000u64));
-        assert_eq!(circuit.initial_balances[1], Fr::from(15000u64));
-        assert_eq!(circuit.final_balances[0], Fr::from(11000u64)); // 10000 + 1000
-        assert_eq!(circuit.final_balances[1], Fr::from(13000u64)); // 15000 - 2000
+        assert_eq!(circuit.players[0].initial_balance, Some(10000));
+        assert_eq!(circuit.players[1].initial_balance, Some(15000));
+        assert_eq!(circuit.players[0].final_balance, 11000); // 10000 + 1000
+        assert_eq!(circuit.players[1].final_balance, 13000); // 15000 - 2000
         assert_eq!(circuit.house_initial, Fr::from(1000000u64));
         assert_eq!(circuit.house_final, Fr::from(1001000u64)); // House gains 1000
     }
 
+    fn is_satisfied(circuit: AccountingCircuit) -> bool {
+        let cs = ark_relations::r1cs::ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_range_checks_reject_wrapped_values() {
+        let bets = vec![Bet::new(0, 5000, true, false)]; // User 0 loses 5000
+        let honest = AccountingCircuit::new(bets.clone(), 1, &[20000], &[15000], 500000, 505000);
+        assert!(is_satisfied(honest.clone()));
+
+        // A house balance of -1 is p - 1 in the field: far more than 64 bits
+        let wrapped_house = AccountingCircuit {
+            house_final: Fr::from(0u64) - Fr::from(1u64),
+            ..honest.clone()
+        };
+        assert!(!is_satisfied(wrapped_house));
+
+        // Narrower widths reject amounts and payouts that don't fit
+        assert!(is_satisfied(honest.clone().with_range_bits(20)));
+        assert!(!is_satisfied(honest.clone().with_range_bits(12)));
+        let win = AccountingCircuit::new(
+            vec![Bet::new(0, 40000, true, true)], // Payout 80000 needs 17 bits
+            1,
+            &[40000],
+            &[80000],
+            100000,
+            60000,
+        );
+        assert!(is_satisfied(win.clone().with_range_bits(17)));
+        assert!(!is_satisfied(win.with_range_bits(16)));
+    }
+
+    #[test]
+    fn test_balances_follow_bet_deltas() {
+        let bets = vec![
+            Bet::new(0, 5000, true, false), // User 0 loses 5000
+            Bet::new(1, 700, false, false), // User 1 wins 700
+        ];
+        let honest = AccountingCircuit::new(
+            bets.clone(),
+            1,
+            &[20000, 300],
+            &[15000, 1000],
+            500000,
+            504300,
+        );
+        assert!(is_satisfied(honest));
+
+        // A player credited more than their bets won, whether or not the house pays for it
+        let inflated = AccountingCircuit::new(
+            bets.clone(),
+            1,
+            &[20000, 300],
+            &[15001, 1000],
+            500000,
+            504300,
+        );
+        assert!(!is_satisfied(inflated));
+        let inflated = AccountingCircuit::new(
+            bets.clone(),
+            1,
+            &[20000, 300],
+            &[15001, 1000],
+            500000,
+            504299,
+        );
+        assert!(!is_satisfied(inflated));
+
+        // Deltas moved between players, or onto a padding slot
+        let swapped = AccountingCircuit::new(
+            bets.clone(),
+            1,
+            &[20000, 300],
+            &[15700, -4700],
+            500000,
+            504300,
+        );
+        assert!(!is_satisfied(swapped));
+        let stray = vec![Bet::new(2, 700, false, false), bets[0].clone()];
+        let stray = AccountingCircuit::new(stray, 1, &[20000, 300], &[15000, 300], 500000, 504300);
+        assert!(!is_satisfied(AccountingCircuit {
+            user_capacity: 3,
+            ..stray
+        }));
+
+        // The house keeping more or less than the players lost
+        let skimmed =
+            AccountingCircuit::new(bets, 1, &[20000, 300], &[15000, 1000], 500000, 504301);
+        assert!(!is_satisfied(skimmed));
+    }
+
+    #[test]
+    fn test_state_roots_bind_leaf_updates() {
+        let bets = vec![Bet::new(0, 5000, true, false)];
+        let circuit = AccountingCircuit::new(bets, 1, &[20000, 300], &[15000, 300], 500000, 505000);
+        assert_eq!(
+            circuit.public_inputs()[2..],
+            [circuit.prev_state_root, circuit.new_state_root]
+        );
+        assert_ne!(circuit.prev_state_root, circuit.new_state_root);
+        assert!(is_satisfied(circuit.clone()));
+        assert!(is_satisfied(AccountingCircuit {
+            user_capacity: 4, // Padding slots leave the root alone
+            ..circuit.clone()
+        }));
+
+        // Roots other than the ones the updates chain between
+        let forged = AccountingCircuit {
+            new_state_root: circuit.prev_state_root,
+            ..circuit.clone()
+        };
+        assert!(!is_satisfied(forged));
+        let forged = AccountingCircuit {
+            prev_state_root: circuit.new_state_root,
+            ..circuit.clone()
+        };
+        assert!(!is_satisfied(forged));
+
+        // A leaf update starting from a balance the state doesn't hold
+        let mut players = circuit.players.clone();
+        players[1].initial_balance = Some(900);
+        assert!(!is_satisfied(AccountingCircuit {
+            players,
+            ..circuit.clone()
+        }));
+
+        // A player claiming to be new where their leaf is in use
+        let mut players = circuit.players.clone();
+        players[0].initial_balance = None;
+        assert!(!is_satisfied(AccountingCircuit { players, ..circuit }));
+    }
+
+    #[test]
+    fn test_batch_commitment_binds_records() {
+        let records: Vec<BetRecord> = fixtures::BATCH
+            .bets
+            .iter()
+            .map(|bet| BetRecord {
+                bet_id: bet.bet_id,
+                user: bet.user,
+                bet_amount: bet.bet_amount,
+                user_guess: bet.user_guess,
+                outcome: bet.outcome,
+                payout: bet.payout,
+            })
+            .collect();
+        // The verifier computes the same commitment from the submitted bets
+        let commitment = batch_commitment(&records);
+        assert_eq!(
+            commitment.into_bigint().to_bytes_be(),
+            fixtures::hex(fixtures::BATCH_COMMITMENT)
+        );
+        assert_eq!(batch_commitment(&[]), Fr::from(0u64));
+
+        let bets = vec![
+            Bet::new(0, 5000, true, false),
+            Bet::new(0, 5000, true, false),
+        ];
+        let records = vec![record(1, 0, 5000, false), record(2, 0, 5000, false)];
+        let circuit = AccountingCircuit::new(bets, 1, &[20000], &[10000], 500000, 510000)
+            .with_records(records.clone(), 3);
+        assert_eq!(circuit.public_inputs()[1], batch_commitment(&records));
+        assert!(is_satisfied(circuit.clone()));
+
+        // A commitment to other bets than the witness holds doesn't satisfy the circuit
+        let forged = AccountingCircuit {
+            batch_commitment: batch_commitment(&records[..1]),
+            ..circuit.clone()
+        };
+        assert!(!is_satisfied(forged));
+        let reordered = AccountingCircuit {
+            records: records.iter().rev().cloned().collect(),
+            ..circuit
+        };
+        assert!(!is_satisfied(reordered));
+    }
+
+    /// Record of a bet guessing heads, as the verifier settles it
+    fn record(bet_id: u64, user_id: u32, bet_amount: u64, won: bool) -> BetRecord {
+        BetRecord {
+            bet_id,
+            user: user_key(user_id),
+            bet_amount,
+            user_guess: 1,
+            outcome: won as u8,
+            payout: 2 * bet_amount * won as u64,
+        }
+    }
+
+    #[test]
+    fn test_records_bind_bets() {
+        let bets = vec![Bet::new(0, 1000, true, true), Bet::new(1, 300, true, false)];
+        let records = vec![record(1, 0, 1000, true), record(2, 1, 300, false)];
+        let circuit = |bets: Vec<Bet>, records: Vec<BetRecord>, capacity| {
+            AccountingCircuit::new(bets, 1, &[10000, 10000], &[11000, 9700], 50000, 49300)
+                .with_records(records, capacity)
+        };
+        assert!(is_satisfied(circuit(bets.clone(), records.clone(), 3)));
+
+        // Records whose stake, outcome or payout differ from the bet proven in their place
+        let mut staked = records.clone();
+        staked[1].bet_amount = 200;
+        assert!(!is_satisfied(circuit(bets.clone(), staked, 3)));
+        let mut flipped = records.clone();
+        flipped[1].outcome = 1;
+        assert!(!is_satisfied(circuit(bets.clone(), flipped, 3)));
+        let mut paid = records.clone();
+        paid[0].payout = 1500;
+        assert!(!is_satisfied(circuit(bets.clone(), paid, 3)));
+
+        // A record naming another player than the bet settles
+        let mut misattributed = records.clone();
+        misattributed[0].user = user_key(1);
+        assert!(!is_satisfied(circuit(bets.clone(), misattributed, 3)));
+
+        // A bet proven without a record, whether past the records or past their slots
+        assert!(!is_satisfied(circuit(
+            bets.clone(),
+            records[..1].to_vec(),
+            3
+        )));
+        assert!(!is_satisfied(circuit(
+            bets.clone(),
+            records[..1].to_vec(),
+            1
+        )));
+
+        // A record with no bet in its place
+        let mut extra = records;
+        extra.push(record(3, 0, 0, false));
+        assert!(!is_satisfied(circuit(bets, extra, 3)));
+    }
+
+    #[test]
+    fn test_net_positions_bind_record_sums() {
+        // Player 0 nets +1000 over two bets, player 1 loses 300
+        let bets = vec![Bet::new(0, 1000, true, true), Bet::new(1, 300, true, false)];
+        let records = vec![
+            record(1, 0, 500, true),
+            record(2, 1, 300, false),
+            record(3, 0, 500, true),
+        ];
+        let circuit = |records: Vec<BetRecord>| {
+            AccountingCircuit::new(
+                bets.clone(),
+                1,
+                &[10000, 10000],
+                &[11000, 9700],
+                50000,
+                49300,
+            )
+            .with_records(records, 4)
+            .with_net_positions()
+        };
+        assert!(is_satisfied(circuit(records.clone())));
+
+        // Records paying out more than the player was credited
+        let mut inflated = records.clone();
+        inflated[2].payout = 1200;
+        assert!(!is_satisfied(circuit(inflated)));
+
+        // Records for one player counted towards the other
+        let mut misattributed = records.clone();
+        misattributed[1].user = user_key(0);
+        assert!(!is_satisfied(circuit(misattributed)));
+
+        // A record for a user the batch doesn't settle
+        let mut unknown = records.clone();
+        unknown[1].user = user_key(7);
+        assert!(!is_satisfied(circuit(unknown)));
+    }
+
     #[test]
     fn test_accounting_proof_system_setup() {
         let system = AccountingProofSystem::setup(2).expect("Setup should succeed");
@@ -431,15 +1248,11 @@ mod integration_tests {
         let prove_time = start.elapsed();
 
         // Store values before moving circuit for public inputs
-        let user_final_balance = circuit.final_balances[0];
+        let user_final_balance = circuit.players[0].final_balance;
         let house_final_balance = circuit.house_final;
 
         // Build public inputs in the order expected by the circuit
-        let mut public_inputs = vec![circuit.batch_id];
-        public_inputs.extend(circuit.initial_balances);
-        public_inputs.extend(circuit.final_balances);
-        public_inputs.push(circuit.house_initial);
-        public_inputs.push(circuit.house_final);
+        let public_inputs = circuit.public_inputs();
 
         let start = Instant::now();
         let is_valid = system
@@ -457,6 +1270,58 @@ mod integration_tests {
         println!("  House final balance: {}", house_final_balance);
     }
 
+    #[test]
+    fn test_inflated_balances_fail_verification() {
+        use ark_groth16::r1cs_to_qap::LibsnarkReduction;
+        use ark_relations::r1cs::{ConstraintSystem, OptimizationGoal};
+        use ark_std::UniformRand;
+
+        let system = AccountingProofSystem::setup(1).expect("Setup failed");
+
+        // User 0 loses 5000 but ends 1 lamport richer than that allows
+        let circuit = AccountingCircuit::new(
+            vec![Bet::new(0, 5000, true, false)],
+            1,
+            &[20000, 10000],
+            &[15001, 10000],
+            500000,
+            505000,
+        );
+        let public_inputs = circuit.public_inputs();
+
+        // Groth16::prove refuses unsatisfied witnesses in debug builds, so prove from
+        // the constraint matrices as a dishonest prover would
+        let cs = ConstraintSystem::new_ref();
+        cs.set_optimization_goal(OptimizationGoal::Constraints);
+        circuit.generate_constraints(cs.clone()).unwrap();
+        cs.finalize();
+        assert!(!cs.is_satisfied().unwrap());
+        let matrices = cs.to_matrices().unwrap();
+        let (num_inputs, num_constraints) = (cs.num_instance_variables(), cs.num_constraints());
+        let prover = cs.borrow().unwrap();
+        let full_assignment = [
+            prover.instance_assignment.as_slice(),
+            &prover.witness_assignment,
+        ]
+        .concat();
+
+        let mut rng = thread_rng();
+        let proof = Groth16::<Bn254, LibsnarkReduction>::create_proof_with_reduction_and_matrices(
+            &system.proving_key,
+            Fr::rand(&mut rng),
+            Fr::rand(&mut rng),
+            &matrices,
+            num_inputs,
+            num_constraints,
+            &full_assignment,
+        )
+        .unwrap();
+
+        assert!(!system
+            .verify(&proof, &public_inputs)
+            .expect("Verification failed"));
+    }
+
     #[test]
     fn test_multi_bet_proof() {
         // Use a setup that matches the actual number of bets
@@ -488,16 +1353,12 @@ mod integration_tests {
         let prove_time = start.elapsed();
 
         // Store values before moving circuit for public inputs
-        let user0_final = circuit.final_balances[0];
-        let user1_final = circuit.final_balances[1];
+        let user0_final = circuit.players[0].final_balance;
+        let user1_final = circuit.players[1].final_balance;
         let house_final = circuit.house_final;
 
         // Build public inputs
-        let mut public_inputs = vec![circuit.batch_id];
-        public_inputs.extend(circuit.initial_balances);
-        public_inputs.extend(circuit.final_balances);
-        public_inputs.push(circuit.house_initial);
-        public_inputs.push(circuit.house_final);
+        let public_inputs = circuit.public_inputs();
 
         let start = Instant::now();
         let is_valid = system
@@ -3,6 +3,8 @@
 
 pub mod accounting;
 pub mod multiplication;
+pub mod poseidon;
+pub mod state_tree;
 
 pub use accounting::*;
 pub use multiplication::MulCircuit;
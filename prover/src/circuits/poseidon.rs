@@ -0,0 +1,149 @@
+// Poseidon hash over BN254 for the settlement circuits
+// Uses the circom parameters (x^5 S-box, 8 full rounds, 1 to 12 inputs) that Solana's
+// sol_poseidon syscall implements, so a hash computed inside a circuit can be
+// recomputed on chain from the same inputs.
+
+use ark_bn254::Fr;
+use ark_ff::{Field, Zero};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSystemRef, LinearCombination, SynthesisError, Variable};
+use light_poseidon::{parameters::bn254_x5, Poseidon, PoseidonHasher};
+
+/// Poseidon of 1 to 12 field elements
+pub fn poseidon_hash(inputs: &[Fr]) -> Fr {
+    Poseidon::<Fr>::new_circom(inputs.len())
+        .and_then(|mut poseidon| poseidon.hash(inputs))
+        .expect("Poseidon takes 1 to 12 inputs")
+}
+
+/// A value inside a circuit: a linear combination of variables, and what it evaluates to
+#[derive(Clone, Debug)]
+pub struct Wire {
+    pub lc: LinearCombination<Fr>,
+    pub value: Fr,
+}
+
+impl Wire {
+    pub fn new(var: Variable, value: Fr) -> Self {
+        Self {
+            lc: lc!() + var,
+            value,
+        }
+    }
+
+    pub fn constant(value: Fr) -> Self {
+        Self {
+            lc: lc!() + (value, Variable::One),
+            value,
+        }
+    }
+
+    /// Allocate `value` as a private witness
+    pub fn witness(cs: &ConstraintSystemRef<Fr>, value: Fr) -> Result<Self, SynthesisError> {
+        Ok(Self::new(cs.new_witness_variable(|| Ok(value))?, value))
+    }
+}
+
+/// x^5, with one constraint per multiplication. The MDS mix makes the input a long
+/// linear combination in later rounds, so it is first pinned to a single variable rather
+/// than repeated in every constraint that uses it.
+fn sbox(cs: &ConstraintSystemRef<Fr>, x: &Wire) -> Result<Wire, SynthesisError> {
+    let x1_var = cs.new_witness_variable(|| Ok(x.value))?;
+    cs.enforce_constraint(x.lc.clone(), lc!() + Variable::One, lc!() + x1_var)?;
+
+    let x2 = x.value.square();
+    let x4 = x2.square();
+    let x5 = x4 * x.value;
+    let x2_var = cs.new_witness_variable(|| Ok(x2))?;
+    cs.enforce_constraint(lc!() + x1_var, lc!() + x1_var, lc!() + x2_var)?;
+    let x4_var = cs.new_witness_variable(|| Ok(x4))?;
+    cs.enforce_constraint(lc!() + x2_var, lc!() + x2_var, lc!() + x4_var)?;
+    let x5_var = cs.new_witness_variable(|| Ok(x5))?;
+    cs.enforce_constraint(lc!() + x4_var, lc!() + x1_var, lc!() + x5_var)?;
+    Ok(Wire::new(x5_var, x5))
+}
+
+/// Poseidon of `inputs` inside the circuit; evaluates to poseidon_hash of their values.
+/// Round constants and the MDS mix are linear, so only the S-boxes cost constraints.
+pub fn enforce_poseidon(
+    cs: &ConstraintSystemRef<Fr>,
+    inputs: &[Wire],
+) -> Result<Wire, SynthesisError> {
+    let width = inputs.len() + 1;
+    let params = u8::try_from(width)
+        .ok()
+        .and_then(|width| bn254_x5::get_poseidon_parameters::<Fr>(width).ok())
+        .ok_or(SynthesisError::Unsatisfiable)?;
+    let half_full_rounds = params.full_rounds / 2;
+
+    // State starts with the (zero) domain tag, then the inputs
+    let mut state: Vec<Wire> = std::iter::once(Wire::constant(Fr::zero()))
+        .chain(inputs.iter().cloned())
+        .collect();
+    for round in 0..params.full_rounds + params.partial_rounds {
+        for (i, wire) in state.iter_mut().enumerate() {
+            let constant = params.ark[round * width + i];
+            wire.lc += (constant, Variable::One);
+            wire.value += constant;
+        }
+
+        // Partial rounds apply the S-box to the first element only
+        let full_round =
+            round < half_full_rounds || round >= half_full_rounds + params.partial_rounds;
+        for (i, wire) in state.iter_mut().enumerate() {
+            if full_round || i == 0 {
+                *wire = sbox(cs, wire)?;
+            }
+        }
+
+        state = params
+            .mds
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&state)
+                    .fold(Wire::constant(Fr::zero()), |mixed, (&weight, wire)| Wire {
+                        lc: mixed.lc + (weight, &wire.lc),
+                        value: mixed.value + weight * wire.value,
+                    })
+            })
+            .collect();
+    }
+    Ok(state.swap_remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn test_circuit_matches_native_hash() {
+        for inputs in [
+            vec![Fr::from(1u64)],
+            vec![Fr::from(1u64), Fr::from(2u64)],
+            (0..7u64).map(Fr::from).collect(),
+        ] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let wires: Vec<Wire> = inputs
+                .iter()
+                .map(|&input| Wire::witness(&cs, input).unwrap())
+                .collect();
+            let hash = enforce_poseidon(&cs, &wires).unwrap();
+            assert_eq!(hash.value, poseidon_hash(&inputs));
+
+            // The output wire is constrained to the hash
+            let output = cs.new_input_variable(|| Ok(hash.value)).unwrap();
+            cs.enforce_constraint(hash.lc, lc!() + Variable::One, lc!() + output)
+                .unwrap();
+            assert!(cs.is_satisfied().unwrap());
+        }
+
+        // Known answer shared with circomlib and the sol_poseidon syscall
+        let hash = poseidon_hash(&[Fr::from(1u64), Fr::from(2u64)]);
+        assert_eq!(
+            hash.to_string(),
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+        );
+    }
+}
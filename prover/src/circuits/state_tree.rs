@@ -0,0 +1,367 @@
+// Balance state tree for the settlement circuit
+// The verifier tracks a root of every player's settled balance (the net of their bets in
+// settled batches) and only accepts a batch whose proof moves that root to the next one.
+// The tree is a fixed-depth Poseidon Merkle tree so the circuit can check those moves: a
+// player keeps the leaf at the position they first settled at, unused leaves are zero
+// and an inner node is Poseidon(left, right). A leaf commits to the player's key and
+// balance, so the vault and withdrawal statements check balances against the same root.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError, Variable};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+use super::poseidon::{enforce_poseidon, poseidon_hash, Wire};
+
+/// Levels below the root: room for 65,536 players
+pub const STATE_TREE_DEPTH: usize = 16;
+
+/// Most players the tree holds
+pub const STATE_TREE_CAPACITY: usize = 1 << STATE_TREE_DEPTH;
+
+/// Balances are signed; leaves carry them offset by 2^63, so each is a u64 and the
+/// circuit can range-check it
+pub fn leaf_balance(balance: i64) -> u64 {
+    balance as u64 ^ 1 << 63
+}
+
+/// A 32-byte key as two field elements, its big-endian 16-byte halves
+pub fn key_halves(key: &[u8; 32]) -> [Fr; 2] {
+    [
+        Fr::from_be_bytes_mod_order(&key[..16]),
+        Fr::from_be_bytes_mod_order(&key[16..]),
+    ]
+}
+
+/// Leaf of a player's balance: Poseidon(key halves, offset balance)
+pub fn leaf_hash(key: &[u8; 32], balance: i64) -> Fr {
+    let [high, low] = key_halves(key);
+    poseidon_hash(&[high, low, Fr::from(leaf_balance(balance))])
+}
+
+/// Key standing in for numbered user `user_id` where there is no real one (tests, demos)
+pub fn user_key(user_id: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[28..].copy_from_slice(&user_id.to_be_bytes());
+    key
+}
+
+/// Roots of empty subtrees by height, leaves first
+fn empty_nodes() -> &'static [Fr] {
+    static EMPTY: OnceLock<Vec<Fr>> = OnceLock::new();
+    EMPTY.get_or_init(|| {
+        let mut nodes = vec![Fr::zero()];
+        for height in 0..STATE_TREE_DEPTH {
+            nodes.push(poseidon_hash(&[nodes[height], nodes[height]]));
+        }
+        nodes
+    })
+}
+
+/// Root of the tree with no players
+pub fn empty_root() -> Fr {
+    empty_nodes()[STATE_TREE_DEPTH]
+}
+
+/// Root reached from a leaf at `index` with the given siblings, leaf level first
+pub fn root_from_path(leaf: Fr, index: u32, siblings: &[Fr]) -> Fr {
+    siblings
+        .iter()
+        .enumerate()
+        .fold(leaf, |node, (height, &sibling)| {
+            if index >> height & 1 == 1 {
+                poseidon_hash(&[sibling, node])
+            } else {
+                poseidon_hash(&[node, sibling])
+            }
+        })
+}
+
+/// Root as 32 big-endian bytes, the encoding the verifier stores
+pub fn root_bytes(root: Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&root.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// One player's leaf before and after a batch, with the path it is updated along
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LeafUpdate {
+    pub key: [u8; 32],
+    pub index: u32,
+    pub initial_balance: Option<i64>, // None: the player has no leaf yet
+    pub final_balance: i64,
+    pub siblings: Vec<Fr>, // Path as of this update (after earlier ones), leaf level first
+}
+
+impl LeafUpdate {
+    /// The leaf before the update; zero if it was unused
+    pub fn initial_leaf(&self) -> Fr {
+        self.initial_balance
+            .map_or(Fr::zero(), |balance| leaf_hash(&self.key, balance))
+    }
+
+    pub fn final_leaf(&self) -> Fr {
+        leaf_hash(&self.key, self.final_balance)
+    }
+
+    /// Root of the tree before and after this update
+    pub fn roots(&self) -> (Fr, Fr) {
+        (
+            root_from_path(self.initial_leaf(), self.index, &self.siblings),
+            root_from_path(self.final_leaf(), self.index, &self.siblings),
+        )
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum StateTreeError {
+    #[error("State tree is full: it holds {} players", STATE_TREE_CAPACITY)]
+    Full,
+}
+
+/// Settled balances as a state tree. Players are placed in the order they are added.
+#[derive(Clone, Debug, Default)]
+pub struct StateTree {
+    balances: Vec<i64>, // By leaf index
+    positions: HashMap<[u8; 32], u32>,
+    levels: Vec<Vec<Fr>>, // Nodes in use by height, leaves first; the rest are empty
+}
+
+impl StateTree {
+    pub fn new() -> Self {
+        Self {
+            levels: vec![Vec::new(); STATE_TREE_DEPTH + 1],
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.balances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.balances.is_empty()
+    }
+
+    pub fn root(&self) -> Fr {
+        self.levels[STATE_TREE_DEPTH]
+            .first()
+            .copied()
+            .unwrap_or_else(empty_root)
+    }
+
+    pub fn index_of(&self, key: &[u8; 32]) -> Option<u32> {
+        self.positions.get(key).copied()
+    }
+
+    pub fn balance(&self, key: &[u8; 32]) -> Option<i64> {
+        Some(self.balances[self.index_of(key)? as usize])
+    }
+
+    /// Siblings on the path of leaf `index`, leaf level first
+    pub fn path(&self, index: u32) -> Vec<Fr> {
+        (0..STATE_TREE_DEPTH)
+            .map(|height| {
+                let sibling = (index >> height ^ 1) as usize;
+                self.levels[height]
+                    .get(sibling)
+                    .copied()
+                    .unwrap_or(empty_nodes()[height])
+            })
+            .collect()
+    }
+
+    /// A player's balance and the path of their leaf
+    pub fn proof(&self, key: &[u8; 32]) -> Option<(i64, Vec<Fr>)> {
+        let index = self.index_of(key)?;
+        Some((self.balances[index as usize], self.path(index)))
+    }
+
+    /// Set a player's balance, adding their leaf if they have none, and return the update
+    /// for the circuit to check
+    pub fn update(&mut self, key: [u8; 32], balance: i64) -> Result<LeafUpdate, StateTreeError> {
+        let (index, initial_balance) = match self.index_of(&key) {
+            Some(index) => (index, Some(self.balances[index as usize])),
+            None if self.len() < STATE_TREE_CAPACITY => {
+                let index = self.len() as u32;
+                self.balances.push(balance);
+                self.positions.insert(key, index);
+                (index, None)
+            }
+            None => return Err(StateTreeError::Full),
+        };
+        let update = LeafUpdate {
+            key,
+            index,
+            initial_balance,
+            final_balance: balance,
+            siblings: self.path(index),
+        };
+
+        self.balances[index as usize] = balance;
+        let mut node = update.final_leaf();
+        let mut position = index as usize;
+        for height in 0..=STATE_TREE_DEPTH {
+            let level = &mut self.levels[height];
+            if position == level.len() {
+                level.push(node);
+            } else {
+                level[position] = node;
+            }
+            if height < STATE_TREE_DEPTH {
+                let (left, right) = if position & 1 == 0 {
+                    (node, update.siblings[height])
+                } else {
+                    (update.siblings[height], node)
+                };
+                node = poseidon_hash(&[left, right]);
+                position /= 2;
+            }
+        }
+        Ok(update)
+    }
+}
+
+/// Allocate the bits of a leaf index, leaf level first, each constrained to 0 or 1
+pub fn enforce_index_bits(
+    cs: &ConstraintSystemRef<Fr>,
+    index: u32,
+) -> Result<Vec<Wire>, SynthesisError> {
+    (0..STATE_TREE_DEPTH)
+        .map(|height| {
+            let bit = Wire::witness(cs, Fr::from(index >> height & 1))?;
+            // bit * (bit - 1) = 0
+            cs.enforce_constraint(bit.lc.clone(), bit.lc.clone() - Variable::One, lc!())?;
+            Ok(bit)
+        })
+        .collect()
+}
+
+/// Root of the tree holding `leaf` at the index given by `bits`, inside the circuit
+pub fn enforce_root(
+    cs: &ConstraintSystemRef<Fr>,
+    leaf: Wire,
+    bits: &[Wire],
+    siblings: &[Wire],
+) -> Result<Wire, SynthesisError> {
+    let mut node = leaf;
+    for (bit, sibling) in bits.iter().zip(siblings) {
+        // left = node + bit * (sibling - node); right is whichever of the two is left over
+        let left = Wire::witness(
+            cs,
+            if bit.value.is_one() {
+                sibling.value
+            } else {
+                node.value
+            },
+        )?;
+        cs.enforce_constraint(
+            bit.lc.clone(),
+            sibling.lc.clone() - &node.lc,
+            left.lc.clone() - &node.lc,
+        )?;
+        let right = Wire {
+            lc: node.lc.clone() + &sibling.lc - &left.lc,
+            value: node.value + sibling.value - left.value,
+        };
+        node = enforce_poseidon(cs, &[left, right])?;
+    }
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn test_updates_chain_roots() {
+        let mut tree = StateTree::new();
+        assert_eq!(tree.root(), empty_root());
+
+        let first = tree.update(user_key(1), 500).unwrap();
+        assert_eq!(first.initial_balance, None);
+        assert_eq!(first.roots(), (empty_root(), tree.root()));
+
+        let second = tree.update(user_key(2), -200).unwrap();
+        assert_eq!(second.index, 1);
+        assert_eq!(second.roots().0, first.roots().1);
+
+        // Updating a player keeps their leaf
+        let root = tree.root();
+        let third = tree.update(user_key(1), 900).unwrap();
+        assert_eq!((third.index, third.initial_balance), (0, Some(500)));
+        assert_eq!(third.roots(), (root, tree.root()));
+
+        // Proofs are against the current root
+        for (user, balance) in [(1, 900), (2, -200)] {
+            let (proven, path) = tree.proof(&user_key(user)).unwrap();
+            assert_eq!(proven, balance);
+            let index = tree.index_of(&user_key(user)).unwrap();
+            assert_eq!(
+                root_from_path(leaf_hash(&user_key(user), balance), index, &path),
+                tree.root()
+            );
+        }
+        assert!(tree.proof(&user_key(3)).is_none());
+
+        // The root only depends on the leaves, not on how they got there
+        let mut rebuilt = StateTree::new();
+        rebuilt.update(user_key(1), 900).unwrap();
+        rebuilt.update(user_key(2), -200).unwrap();
+        assert_eq!(rebuilt.root(), tree.root());
+    }
+
+    #[test]
+    fn test_circuit_root_matches_native() {
+        let mut tree = StateTree::new();
+        for user in 0..5 {
+            tree.update(user_key(user), user as i64 * 100).unwrap();
+        }
+        let index = 3;
+        let leaf = leaf_hash(&user_key(3), 300);
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let bits = enforce_index_bits(&cs, index).unwrap();
+        let siblings: Vec<Wire> = tree
+            .path(index)
+            .into_iter()
+            .map(|sibling| Wire::witness(&cs, sibling).unwrap())
+            .collect();
+        let leaf = Wire::witness(&cs, leaf).unwrap();
+        let root = enforce_root(&cs, leaf, &bits, &siblings).unwrap();
+        assert_eq!(root.value, tree.root());
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_state_root_matches_fixtures() {
+        let batch = &fixtures::BATCH;
+        assert_eq!(
+            root_bytes(empty_root()),
+            fixtures::hex32(batch.prev_state_root)
+        );
+
+        let mut tree = StateTree::new();
+        for bet in batch.bets {
+            let balance = tree.balance(&bet.user).unwrap_or(0) + bet.sol_delta();
+            tree.update(bet.user, balance).unwrap();
+        }
+        assert_eq!(
+            root_bytes(tree.root()),
+            fixtures::hex32(batch.new_state_root)
+        );
+    }
+
+    #[test]
+    fn test_leaf_balance_offset() {
+        assert_eq!(leaf_balance(0), 1 << 63);
+        assert_eq!(leaf_balance(-1), (1 << 63) - 1);
+        assert_eq!(leaf_balance(i64::MIN), 0);
+        assert_eq!(leaf_balance(i64::MAX), u64::MAX);
+    }
+}
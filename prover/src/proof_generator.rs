@@ -1,6 +1,8 @@
 use crate::circuits::accounting::AccountingCircuit;
 use crate::witness_generator::{SettlementBatch, WitnessError, WitnessGenerator};
-use ark_bn254::{Bn254, Fr};
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, PrimeField};
 use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
@@ -95,7 +97,8 @@ impl SerializableProof {
         let num_inputs = u32::from_le_bytes(buf) as usize;
 
         // Read public inputs
-        let mut public_inputs = Vec::with_capacity(num_inputs);
+        // The count is untrusted, so it doesn't size the allocation
+        let mut public_inputs = Vec::new();
         for _ in 0..num_inputs {
             let mut buf = [0u8; 4];
             data.read_exact(&mut buf)?;
@@ -125,6 +128,60 @@ impl SerializableProof {
             timestamp,
         })
     }
+
+    /// The proof as the on-chain verifier takes it: A, B then C, uncompressed in the
+    /// EIP-196/197 encoding of Solana's alt_bn128 syscalls (256 bytes)
+    pub fn to_verifier_bytes(&self) -> Vec<u8> {
+        [
+            g1_bytes(&self.proof.a).as_slice(),
+            &g2_bytes(&self.proof.b),
+            &g1_bytes(&self.proof.c),
+        ]
+        .concat()
+    }
+}
+
+/// A verifying key as the on-chain verifier stores it: alpha, beta, gamma and delta, then
+/// one IC point per public input plus IC[0], in the encoding of to_verifier_bytes
+pub fn verifier_key_bytes(vk: &VerifyingKey<Bn254>) -> Vec<u8> {
+    let mut bytes = g1_bytes(&vk.alpha_g1).to_vec();
+    for point in [&vk.beta_g2, &vk.gamma_g2, &vk.delta_g2] {
+        bytes.extend_from_slice(&g2_bytes(point));
+    }
+    for point in &vk.gamma_abc_g1 {
+        bytes.extend_from_slice(&g1_bytes(point));
+    }
+    bytes
+}
+
+/// Big-endian base field element
+fn fq_bytes(x: &Fq) -> [u8; 32] {
+    x.into_bigint()
+        .to_bytes_be()
+        .try_into()
+        .expect("Fq is 32 bytes")
+}
+
+/// G1 point as x then y; the point at infinity is all zeros
+fn g1_bytes(point: &G1Affine) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    if let Some((x, y)) = point.xy() {
+        bytes[..32].copy_from_slice(&fq_bytes(x));
+        bytes[32..].copy_from_slice(&fq_bytes(y));
+    }
+    bytes
+}
+
+/// G2 point as x then y, each the imaginary then the real part; the point at infinity
+/// is all zeros
+fn g2_bytes(point: &G2Affine) -> [u8; 128] {
+    let fq2_bytes = |x: &Fq2| [fq_bytes(&x.c1), fq_bytes(&x.c0)].concat();
+    let mut bytes = [0u8; 128];
+    if let Some((x, y)) = point.xy() {
+        bytes[..64].copy_from_slice(&fq2_bytes(x));
+        bytes[64..].copy_from_slice(&fq2_bytes(y));
+    }
+    bytes
 }
 
 /// Zero-knowledge proof generator for accounting circuits
@@ -147,6 +204,33 @@ impl ProofGenerator {
         }
     }
 
+    /// Range-check amounts and balances to `bits` bits; call before setup, since the
+    /// width is part of the circuit structure
+    pub fn with_range_bits(mut self, bits: usize) -> Self {
+        self.witness_generator = self.witness_generator.with_range_bits(bits);
+        self
+    }
+
+    /// Commit to up to `max_records` bet records per batch; call before setup, since
+    /// the record slots are part of the circuit structure
+    pub fn with_max_records(mut self, max_records: usize) -> Self {
+        self.witness_generator = self.witness_generator.with_max_records(max_records);
+        self
+    }
+
+    /// Prove batches of netted positions against their records' per-player sums; call
+    /// before setup, since it changes the circuit structure
+    pub fn with_net_positions(mut self) -> Self {
+        self.witness_generator = self.witness_generator.with_net_positions(true);
+        self
+    }
+
+    /// Accept bets beyond a player's starting balance, for states holding net winnings
+    pub fn with_net_balances(mut self) -> Self {
+        self.witness_generator = self.witness_generator.with_net_balances();
+        self
+    }
+
     /// Setup the proving and verifying keys for a given circuit size
     /// This is deterministic based on circuit structure
     pub fn setup(&mut self) -> Result<(), ProofError> {
@@ -180,11 +264,7 @@ impl ProofGenerator {
         let circuit = self.witness_generator.generate_witness(settlement_batch)?;
 
         // Extract public inputs in the order expected by the circuit
-        let mut public_inputs = vec![circuit.batch_id];
-        public_inputs.extend(circuit.initial_balances.clone());
-        public_inputs.extend(circuit.final_balances.clone());
-        public_inputs.push(circuit.house_initial);
-        public_inputs.push(circuit.house_final);
+        let public_inputs = circuit.public_inputs();
 
         // Generate proof
         let mut rng = thread_rng();
@@ -232,22 +312,20 @@ impl ProofGenerator {
         let circuit = self.witness_generator.generate_witness(settlement_batch)?;
 
         // Extract public inputs in the order expected by the circuit
-        let mut public_inputs = vec![circuit.batch_id];
-        public_inputs.extend(circuit.initial_balances.clone());
-        public_inputs.extend(circuit.final_balances.clone());
-        public_inputs.push(circuit.house_initial);
-        public_inputs.push(circuit.house_final);
+        let public_inputs = circuit.public_inputs();
 
         // Use seeded RNG for deterministic proof generation
         let mut rng = StdRng::seed_from_u64(seed);
         let proof = Groth16::<Bn254>::prove(proving_key, circuit, &mut rng)
             .map_err(|e| ProofError::ProofGeneration(format!("Proof creation failed: {}", e)))?;
 
-        Ok(SerializableProof::new(
+        // Use the batch timestamp so identical inputs serialize identically
+        Ok(SerializableProof {
             proof,
             public_inputs,
-            settlement_batch.batch_id,
-        ))
+            batch_id: settlement_batch.batch_id,
+            timestamp: settlement_batch.timestamp,
+        })
     }
 
     /// Get the verifying key for external verification
@@ -267,6 +345,15 @@ impl ProofGenerator {
         Ok(buf)
     }
 
+    /// The verifying key in the on-chain verifier's encoding (verifier_key_bytes)
+    pub fn verifier_verifying_key(&self) -> Result<Vec<u8>, ProofError> {
+        let vk = self
+            .verifying_key
+            .as_ref()
+            .ok_or(ProofError::InvalidParameters)?;
+        Ok(verifier_key_bytes(vk))
+    }
+
     /// Check if settlement batch is valid without generating proof
     pub fn validate_settlement_batch(
         &self,
@@ -286,7 +373,7 @@ impl ProofGenerator {
         // Create batch with MAXIMUM size to ensure consistent circuit structure
         let mut initial_balances = HashMap::new();
         for i in 0..self.max_users {
-            initial_balances.insert(i as u32, 10000u64); // Default balance
+            initial_balances.insert(i as u32, 10000); // Default balance
         }
 
         // Create maximum number of dummy bets to ensure circuit structure is fixed
@@ -304,12 +391,24 @@ impl ProofGenerator {
             100000, // house initial
         );
 
-        self.witness_generator
-            .generate_witness(&batch)
-            .map_err(ProofError::from)
+        // The dummy bets have no records: build the circuit from them as plain bets, then
+        // give it the record slots and netting of the real one
+        let circuit = self
+            .witness_generator
+            .clone()
+            .with_max_records(0)
+            .with_net_positions(false)
+            .generate_witness(&batch)?
+            .with_records(Vec::new(), self.witness_generator.max_records());
+        Ok(if self.witness_generator.net_positions() {
+            circuit.with_net_positions()
+        } else {
+            circuit
+        })
     }
 
     /// Estimate number of constraints for validation
+    #[allow(dead_code)]
     fn estimate_constraints(&self) -> usize {
         // Conservative estimate based on circuit structure
         // This should match the actual circuit constraint count
@@ -321,6 +420,15 @@ impl ProofGenerator {
     }
 }
 
+/// Verify a serialized proof (SerializableProof::to_bytes) against a compressed
+/// verifying key, without the proving key; for checking exported batches offline
+pub fn verify_serialized_proof(verifying_key: &[u8], proof: &[u8]) -> Result<bool, ProofError> {
+    let verifying_key = VerifyingKey::<Bn254>::deserialize_compressed(verifying_key)?;
+    let proof = SerializableProof::from_bytes(proof)?;
+    Groth16::<Bn254>::verify(&verifying_key, &proof.public_inputs, &proof.proof)
+        .map_err(|_| ProofError::ProofVerification)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -354,6 +462,10 @@ mod tests {
 
         let proof = generator.generate_proof(&batch).unwrap();
         assert_eq!(proof.batch_id, 42);
+        assert_eq!(
+            proof.public_inputs[2..],
+            [batch.prev_state_root, batch.players[0].roots().1]
+        );
 
         let is_valid = generator.verify_proof(&proof).unwrap();
         assert!(is_valid);
@@ -455,6 +567,14 @@ mod tests {
         // Verify proof still validates
         let is_valid = generator.verify_proof(&deserialized_proof).unwrap();
         assert!(is_valid);
+
+        // Also from the serialized key alone, but not against another setup's key
+        let vk_bytes = generator.serialize_verifying_key().unwrap();
+        assert!(verify_serialized_proof(&vk_bytes, &serialized).unwrap());
+        let mut other = ProofGenerator::new(5, 3);
+        other.setup().unwrap();
+        let other_vk = other.serialize_verifying_key().unwrap();
+        assert!(!verify_serialized_proof(&other_vk, &serialized).unwrap());
     }
 
     #[test]
@@ -463,11 +583,11 @@ mod tests {
         generator.setup().unwrap();
 
         let mut initial_balances = HashMap::new();
-        initial_balances.insert(0, 500); // Insufficient balance
+        initial_balances.insert(0, 500);
 
         let batch = create_test_settlement_batch(
             1,
-            vec![(0, 1000, true, true)], // Bet more than balance
+            vec![(2, 1000, true, true)], // Bet by a user the batch doesn't settle
             initial_balances,
             50000,
         );
@@ -506,6 +626,63 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_proof_commits_to_records() {
+        use crate::circuits::accounting::{batch_commitment, BetRecord};
+        use crate::circuits::state_tree::user_key;
+
+        let mut generator = ProofGenerator::new(5, 3).with_max_records(2);
+        generator.setup().unwrap();
+
+        let mut initial_balances = HashMap::new();
+        initial_balances.insert(0, 10000);
+        let mut batch = create_test_settlement_batch(
+            7,
+            vec![(0, 1000, true, true), (0, 500, true, true)],
+            initial_balances,
+            50000,
+        );
+        let record = |bet_id, bet_amount| BetRecord {
+            bet_id,
+            user: user_key(0),
+            bet_amount,
+            user_guess: 1,
+            outcome: 1,
+            payout: 2 * bet_amount,
+        };
+        batch.records = vec![record(1, 1000), record(2, 500)];
+
+        let proof = generator.generate_proof(&batch).unwrap();
+        assert_eq!(proof.public_inputs[1], batch_commitment(&batch.records));
+        assert!(generator.verify_proof(&proof).unwrap());
+
+        // The proof doesn't verify against a commitment to different bets
+        let mut forged = proof.clone();
+        forged.public_inputs[1] = batch_commitment(&batch.records[..1]);
+        assert!(!generator.verify_proof(&forged).unwrap());
+
+        // Records other than the bets are rejected before proving
+        let mut misrecorded = batch.clone();
+        misrecorded.records[1].payout = 1500;
+        assert!(matches!(
+            generator.generate_proof(&misrecorded),
+            Err(ProofError::WitnessGeneration(
+                WitnessError::BetRecordMismatch { index: 1 }
+            ))
+        ));
+
+        batch.records.push(record(3, 0));
+        assert!(matches!(
+            generator.generate_proof(&batch),
+            Err(ProofError::WitnessGeneration(
+                WitnessError::TooManyRecords {
+                    size: 3,
+                    max_size: 2
+                }
+            ))
+        ));
+    }
+
     #[test]
     fn test_setup_required_error() {
         let generator = ProofGenerator::new(5, 3);
@@ -520,4 +697,38 @@ mod tests {
         let result = generator.generate_proof(&batch);
         assert!(matches!(result, Err(ProofError::InvalidParameters)));
     }
+
+    #[test]
+    fn test_proof_envelope_matches_fixture() {
+        // The verifier takes public inputs as big-endian scalars; the envelope carries
+        // them as arkworks' little-endian encoding of the same field elements
+        let public_inputs: Vec<Fr> = fixtures::BATCH
+            .public_inputs()
+            .iter()
+            .map(|input| Fr::from_be_bytes_mod_order(input))
+            .collect();
+        for (input, expected) in public_inputs.iter().zip(fixtures::BATCH.public_inputs()) {
+            assert_eq!(input.into_bigint().to_bytes_be(), expected);
+        }
+
+        let proof = SerializableProof {
+            proof: Proof {
+                a: G1Affine::generator(),
+                b: G2Affine::generator(),
+                c: G1Affine::generator(),
+            },
+            public_inputs,
+            batch_id: fixtures::BATCH.batch_id as u32,
+            timestamp: fixtures::PROOF_TIMESTAMP,
+        };
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(bytes, fixtures::hex(fixtures::PROOF_ENVELOPE));
+
+        let decoded = SerializableProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.public_inputs, proof.public_inputs);
+        assert_eq!(decoded.proof, proof.proof);
+
+        // The on-chain verifier takes the points uncompressed, big-endian
+        assert_eq!(proof.to_verifier_bytes(), fixtures::hex(fixtures::PROOF));
+    }
 }
@@ -1,4 +1,8 @@
-use crate::circuits::accounting::{AccountingCircuit, Bet, BetBatch};
+use crate::circuits::accounting::{
+    AccountingCircuit, Bet, BetBatch, BetRecord, DEFAULT_RANGE_BITS,
+};
+use crate::circuits::state_tree::{user_key, LeafUpdate, StateTree};
+use ark_bn254::Fr;
 use std::collections::HashMap;
 use thiserror::Error;
 
@@ -8,11 +12,27 @@ pub enum WitnessError {
     #[error("Invalid balance: user {user_id} has balance {balance} but bet amount {bet_amount}")]
     InsufficientBalance {
         user_id: u32,
-        balance: u64,
+        balance: i64,
         bet_amount: u64,
     },
-    #[error("Negative balance detected: user {user_id} would have balance {balance}")]
-    NegativeBalance { user_id: u32, balance: i64 },
+    #[error("State mismatch: user {user_id}'s leaf update doesn't start from the state so far")]
+    StateMismatch { user_id: u32 },
+    #[error(
+        "Balance mismatch: user {user_id} should settle at {expected} but the update sets {actual}"
+    )]
+    BalanceMismatch {
+        user_id: u32,
+        expected: i128,
+        actual: i64,
+    },
+    #[error("Negative house balance detected: house would have balance {balance}")]
+    NegativeHouseBalance { balance: i64 },
+    #[error("Out of range: {what} {value} does not fit in {bits} bits")]
+    OutOfRange {
+        what: &'static str,
+        value: u128,
+        bits: usize,
+    },
     #[error(
         "Conservation law violated: user deltas sum to {user_sum}, house delta is {house_delta}"
     )]
@@ -21,8 +41,22 @@ pub enum WitnessError {
     EmptyBatch,
     #[error("Batch too large: {size} bets exceeds maximum {max_size}")]
     BatchTooLarge { size: usize, max_size: usize },
-    #[error("Unknown user: user_id {user_id} not found in initial balances")]
+    #[error("Too many bet records: {size} exceeds the circuit's {max_size} record slots")]
+    TooManyRecords { size: usize, max_size: usize },
+    #[error("Unknown user: user_id {user_id} has no leaf update in the batch")]
     UnknownUser { user_id: u32 },
+    #[error("Unknown record user: bet {bet_id} is for a user with no leaf update in the batch")]
+    UnknownRecordUser { bet_id: u64 },
+    #[error(
+        "Record mismatch: user {user_id}'s records net {recorded} but the batch moves them {delta}"
+    )]
+    RecordMismatch {
+        user_id: u32,
+        recorded: i128,
+        delta: i64,
+    },
+    #[error("Bet record mismatch: bet {index} isn't the record committed in its place")]
+    BetRecordMismatch { index: usize },
 }
 
 /// Settlement batch data from the sequencer
@@ -30,9 +64,11 @@ pub enum WitnessError {
 pub struct SettlementBatch {
     pub batch_id: u32,
     pub bets: Vec<SettlementBet>,
-    pub initial_balances: HashMap<u32, u64>, // user_id -> balance
+    pub players: Vec<LeafUpdate>, // Leaf update of each player, indexed by user_id
+    pub prev_state_root: Fr,      // Balance state root the batch starts from
     pub house_initial_balance: u64,
-    pub timestamp: u64, // Unix timestamp when batch was created
+    pub timestamp: u64,          // Unix timestamp when batch was created
+    pub records: Vec<BetRecord>, // Bets as submitted on chain, covered by the batch commitment
 }
 
 /// Individual bet in a settlement batch
@@ -81,9 +117,14 @@ impl SettlementBet {
 }
 
 /// Witness generator for accounting circuits
+#[derive(Clone)]
 pub struct WitnessGenerator {
     max_batch_size: usize,
     max_users: usize,
+    range_bits: usize,
+    max_records: usize, // 0 = batches carry no records and commit to the empty list
+    net_positions: bool, // Bets are netted positions, checked against the records' sums
+    net_balances: bool, // Balances are net winnings, so bets may exceed them
 }
 
 impl Default for WitnessGenerator {
@@ -91,6 +132,10 @@ impl Default for WitnessGenerator {
         Self {
             max_batch_size: 100,
             max_users: 10,
+            range_bits: DEFAULT_RANGE_BITS,
+            max_records: 0,
+            net_positions: false,
+            net_balances: false,
         }
     }
 }
@@ -100,9 +145,64 @@ impl WitnessGenerator {
         Self {
             max_batch_size,
             max_users,
+            range_bits: DEFAULT_RANGE_BITS,
+            max_records: 0,
+            net_positions: false,
+            net_balances: false,
         }
     }
 
+    /// Commit to up to `max_records` bet records per batch
+    pub fn with_max_records(mut self, max_records: usize) -> Self {
+        self.max_records = max_records;
+        self
+    }
+
+    /// Whether bets are the players' netted positions: each player's balance change
+    /// must then be what their records pay out net of their stakes
+    pub fn with_net_positions(mut self, net_positions: bool) -> Self {
+        self.net_positions = net_positions;
+        self
+    }
+
+    /// Accept bets larger than the player's starting balance, for states whose leaves
+    /// hold net winnings rather than funds (the sequencer's settled balances)
+    pub fn with_net_balances(mut self) -> Self {
+        self.net_balances = true;
+        self
+    }
+
+    /// Range-check bet amounts, payouts and final balances to `bits` bits (1 to 64)
+    pub fn with_range_bits(mut self, bits: usize) -> Self {
+        self.range_bits = bits.clamp(1, DEFAULT_RANGE_BITS);
+        self
+    }
+
+    pub fn range_bits(&self) -> usize {
+        self.range_bits
+    }
+
+    pub fn net_positions(&self) -> bool {
+        self.net_positions
+    }
+
+    pub fn max_records(&self) -> usize {
+        self.max_records
+    }
+
+    /// The circuit only accepts values below 2^range_bits; reject others up front
+    /// rather than producing a proof that won't verify
+    fn check_range(&self, what: &'static str, value: u128) -> Result<(), WitnessError> {
+        if value >> self.range_bits != 0 {
+            return Err(WitnessError::OutOfRange {
+                what,
+                value,
+                bits: self.range_bits,
+            });
+        }
+        Ok(())
+    }
+
     /// Generate a witness (accounting circuit) from settlement batch data
     pub fn generate_witness(
         &self,
@@ -127,31 +227,86 @@ impl WitnessGenerator {
         // Calculate balance deltas (only for real bets)
         let mut user_deltas: HashMap<u32, i64> = HashMap::new();
         for bet in &settlement_batch.bets {
+            self.check_range("bet amount", bet.amount as u128)?;
+            self.check_range("payout", bet.amount as u128 * 2 * bet.won() as u128)?;
+
             // Only process real bets
             *user_deltas.entry(bet.user_id).or_insert(0) += bet.delta();
         }
 
-        // Calculate final balances
-        let mut final_balances = HashMap::new();
-        for (&user_id, &initial_balance) in &settlement_batch.initial_balances {
-            let delta = user_deltas.get(&user_id).copied().unwrap_or(0);
-            let final_balance = (initial_balance as i64 + delta) as u64;
-
-            // Validate no negative balances
-            if final_balance as i64 != initial_balance as i64 + delta {
-                return Err(WitnessError::NegativeBalance {
-                    user_id,
-                    balance: initial_balance as i64 + delta,
+        // Each player's update must settle them at their balance plus the batch's net
+        for (user_id, player) in settlement_batch.players.iter().enumerate() {
+            let delta = user_deltas.get(&(user_id as u32)).copied().unwrap_or(0);
+            let expected = player.initial_balance.unwrap_or(0) as i128 + delta as i128;
+            if expected != player.final_balance as i128 {
+                return Err(WitnessError::BalanceMismatch {
+                    user_id: user_id as u32,
+                    expected,
+                    actual: player.final_balance,
                 });
             }
+        }
 
-            final_balances.insert(user_id, final_balance);
+        for record in &settlement_batch.records {
+            self.check_range("record bet amount", record.bet_amount as u128)?;
+            self.check_range("record payout", record.payout as u128)?;
+        }
+        if self.net_positions {
+            let mut recorded = vec![0i128; settlement_batch.players.len()];
+            for record in &settlement_batch.records {
+                let user_id = settlement_batch
+                    .players
+                    .iter()
+                    .position(|player| player.key == record.user)
+                    .ok_or(WitnessError::UnknownRecordUser {
+                        bet_id: record.bet_id,
+                    })?;
+                recorded[user_id] += record.payout as i128 - record.bet_amount as i128;
+            }
+            for (user_id, recorded) in recorded.into_iter().enumerate() {
+                let delta = user_deltas.get(&(user_id as u32)).copied().unwrap_or(0);
+                if recorded != delta as i128 {
+                    return Err(WitnessError::RecordMismatch {
+                        user_id: user_id as u32,
+                        recorded,
+                        delta,
+                    });
+                }
+            }
+        } else if self.max_records > 0 {
+            // Each bet is proven as the record committed in its place
+            let bets = &settlement_batch.bets;
+            for (index, bet) in bets.iter().enumerate() {
+                let matches = settlement_batch.records.get(index).is_some_and(|record| {
+                    record.user == settlement_batch.players[bet.user_id as usize].key
+                        && record.bet_amount == bet.amount
+                        && record.user_guess == bet.guess as u8
+                        && record.outcome == bet.outcome as u8
+                        && record.payout == bet.payout()
+                });
+                if !matches {
+                    return Err(WitnessError::BetRecordMismatch { index });
+                }
+            }
+            if settlement_batch.records.len() > bets.len() {
+                return Err(WitnessError::BetRecordMismatch { index: bets.len() });
+            }
         }
 
         // Calculate house delta and final balance
         let house_delta: i64 = -user_deltas.values().sum::<i64>();
-        let house_final_balance =
-            (settlement_batch.house_initial_balance as i64 + house_delta) as u64;
+        let house_balance = settlement_batch.house_initial_balance as i128 + house_delta as i128;
+        if house_balance < 0 {
+            return Err(WitnessError::NegativeHouseBalance {
+                balance: house_balance as i64,
+            });
+        }
+        self.check_range(
+            "house initial balance",
+            settlement_batch.house_initial_balance as u128,
+        )?;
+        self.check_range("house final balance", house_balance as u128)?;
+        let house_final_balance = house_balance as u64;
 
         // Validate conservation law
         let user_sum: i64 = user_deltas.values().sum();
@@ -162,43 +317,24 @@ impl WitnessGenerator {
             });
         }
 
-        // Create ordered balance arrays for circuit (must be consistent with max_users)
-        let max_user_id = settlement_batch
-            .initial_balances
-            .keys()
-            .max()
-            .copied()
-            .unwrap_or(0);
-
-        if max_user_id as usize >= self.max_users {
-            return Err(WitnessError::BatchTooLarge {
-                size: max_user_id as usize + 1,
-                max_size: self.max_users,
-            });
-        }
-
-        let mut initial_balance_array = vec![0u64; self.max_users];
-        let mut final_balance_array = vec![0u64; self.max_users];
-
-        for user_id in 0..self.max_users as u32 {
-            if let Some(&initial) = settlement_batch.initial_balances.get(&user_id) {
-                initial_balance_array[user_id as usize] = initial;
-                final_balance_array[user_id as usize] =
-                    final_balances.get(&user_id).copied().unwrap_or(initial);
-            }
-        }
-
         // Create accounting circuit with padded bets
-        let circuit = AccountingCircuit::new(
+        let circuit = AccountingCircuit::from_state(
             accounting_bets, // Padded to max_batch_size
             settlement_batch.batch_id,
-            &initial_balance_array,
-            &final_balance_array,
+            settlement_batch.prev_state_root,
+            settlement_batch.players.clone(),
+            self.max_users, // Padded to max_users
             settlement_batch.house_initial_balance,
             house_final_balance,
-        );
+        )
+        .with_range_bits(self.range_bits)
+        .with_records(settlement_batch.records.clone(), self.max_records);
 
-        Ok(circuit)
+        Ok(if self.net_positions {
+            circuit.with_net_positions()
+        } else {
+            circuit
+        })
     }
 
     /// Validate settlement batch data
@@ -215,18 +351,31 @@ impl WitnessGenerator {
                 max_size: self.max_batch_size,
             });
         }
+        if batch.records.len() > self.max_records {
+            return Err(WitnessError::TooManyRecords {
+                size: batch.records.len(),
+                max_size: self.max_records,
+            });
+        }
 
-        // Validate all users exist in initial balances
+        if batch.players.len() > self.max_users {
+            return Err(WitnessError::BatchTooLarge {
+                size: batch.players.len(),
+                max_size: self.max_users,
+            });
+        }
+
+        // Validate all users have a leaf update
         for bet in &batch.bets {
-            if !batch.initial_balances.contains_key(&bet.user_id) {
+            let Some(player) = batch.players.get(bet.user_id as usize) else {
                 return Err(WitnessError::UnknownUser {
                     user_id: bet.user_id,
                 });
-            }
+            };
 
             // Check sufficient balance for bet
-            let balance = batch.initial_balances[&bet.user_id];
-            if balance < bet.amount {
+            let balance = player.initial_balance.unwrap_or(0);
+            if !self.net_balances && (balance as i128) < bet.amount as i128 {
                 return Err(WitnessError::InsufficientBalance {
                     user_id: bet.user_id,
                     balance,
@@ -235,6 +384,18 @@ impl WitnessGenerator {
             }
         }
 
+        // The updates must chain from prev_state_root, each starting where the last ended
+        let mut root = batch.prev_state_root;
+        for (user_id, player) in batch.players.iter().enumerate() {
+            let (initial_root, final_root) = player.roots();
+            if initial_root != root {
+                return Err(WitnessError::StateMismatch {
+                    user_id: user_id as u32,
+                });
+            }
+            root = final_root;
+        }
+
         Ok(())
     }
 
@@ -242,7 +403,7 @@ impl WitnessGenerator {
     pub fn generate_witness_from_bet_batch(
         &self,
         bet_batch: &BetBatch,
-        initial_balances: &HashMap<u32, u64>,
+        initial_balances: &HashMap<u32, i64>,
         house_initial_balance: u64,
     ) -> Result<AccountingCircuit, WitnessError> {
         // Convert BetBatch to SettlementBatch
@@ -261,23 +422,58 @@ impl WitnessGenerator {
             })
             .collect();
 
+        let (prev_state_root, players) = numbered_user_state(initial_balances, &settlement_bets);
         let settlement_batch = SettlementBatch {
             batch_id: bet_batch.batch_id,
             bets: settlement_bets,
-            initial_balances: initial_balances.clone(),
+            players,
+            prev_state_root,
             house_initial_balance,
             timestamp: 0, // Not used in circuit generation
+            records: Vec::new(),
         };
 
         self.generate_witness(&settlement_batch)
     }
 }
 
+/// State for numbered users: a tree holding `initial_balances`, user i keyed by
+/// user_key(i), and the leaf updates settling users 0 up to the highest numbered one
+/// after `bets`. Users missing from `initial_balances` start out without a leaf.
+fn numbered_user_state(
+    initial_balances: &HashMap<u32, i64>,
+    bets: &[SettlementBet],
+) -> (Fr, Vec<LeafUpdate>) {
+    let mut user_ids: Vec<u32> = initial_balances.keys().copied().collect();
+    user_ids.sort_unstable();
+
+    let mut tree = StateTree::new();
+    for &user_id in &user_ids {
+        tree.update(user_key(user_id), initial_balances[&user_id])
+            .expect("test state fits the tree");
+    }
+    let prev_state_root = tree.root();
+
+    let players = (0..user_ids.last().map_or(0, |&max| max + 1))
+        .map(|user_id| {
+            let initial = initial_balances.get(&user_id).copied().unwrap_or(0);
+            let delta: i64 = bets
+                .iter()
+                .filter(|bet| bet.user_id == user_id)
+                .map(SettlementBet::delta)
+                .sum();
+            tree.update(user_key(user_id), initial + delta)
+                .expect("test state fits the tree")
+        })
+        .collect();
+    (prev_state_root, players)
+}
+
 /// Helper function to create a settlement batch for testing
 pub fn create_test_settlement_batch(
     batch_id: u32,
     bets: Vec<(u32, u64, bool, bool)>, // (user_id, amount, guess, outcome)
-    initial_balances: HashMap<u32, u64>,
+    initial_balances: HashMap<u32, i64>,
     house_initial: u64,
 ) -> SettlementBatch {
     let settlement_bets: Vec<SettlementBet> = bets
@@ -288,12 +484,15 @@ pub fn create_test_settlement_batch(
         })
         .collect();
 
+    let (prev_state_root, players) = numbered_user_state(&initial_balances, &settlement_bets);
     SettlementBatch {
         batch_id,
         bets: settlement_bets,
-        initial_balances,
+        players,
+        prev_state_root,
         house_initial_balance: house_initial,
         timestamp: 1698000000, // Fixed timestamp for testing
+        records: Vec::new(),
     }
 }
 
@@ -334,8 +533,10 @@ mod tests {
         let circuit = generator.generate_witness(&batch).unwrap();
 
         assert_eq!(circuit.batch_id, Fr::from(1u32));
-        assert_eq!(circuit.initial_balances[0], Fr::from(10000u64));
-        assert_eq!(circuit.final_balances[0], Fr::from(11000u64)); // +1000 from win
+        assert_eq!(circuit.players[0].initial_balance, Some(10000));
+        assert_eq!(circuit.players[0].final_balance, 11000); // +1000 from win
+        assert_eq!(circuit.prev_state_root, batch.prev_state_root);
+        assert_eq!(circuit.user_capacity, 5);
         assert_eq!(circuit.house_initial, Fr::from(50000u64));
         assert_eq!(circuit.house_final, Fr::from(49000u64)); // -1000 to user
     }
@@ -362,10 +563,10 @@ mod tests {
         let circuit = generator.generate_witness(&batch).unwrap();
 
         assert_eq!(circuit.batch_id, Fr::from(42u32));
-        assert_eq!(circuit.initial_balances[0], Fr::from(10000u64));
-        assert_eq!(circuit.initial_balances[1], Fr::from(15000u64));
-        assert_eq!(circuit.final_balances[0], Fr::from(11500u64)); // +1500 total
-        assert_eq!(circuit.final_balances[1], Fr::from(13000u64)); // -2000
+        assert_eq!(circuit.players[0].initial_balance, Some(10000));
+        assert_eq!(circuit.players[1].initial_balance, Some(15000));
+        assert_eq!(circuit.players[0].final_balance, 11500); // +1500 total
+        assert_eq!(circuit.players[1].final_balance, 13000); // -2000
         assert_eq!(circuit.house_initial, Fr::from(100000u64));
         assert_eq!(circuit.house_final, Fr::from(100500u64)); // +500 net
     }
@@ -389,6 +590,165 @@ mod tests {
             result,
             Err(WitnessError::InsufficientBalance { .. })
         ));
+
+        // Net winnings aren't a limit on what a player can stake
+        let circuit = generator
+            .with_net_balances()
+            .generate_witness(&batch)
+            .unwrap();
+        assert_eq!(circuit.players[0].final_balance, 1500);
+    }
+
+    #[test]
+    fn test_state_mismatch_errors() {
+        let generator = WitnessGenerator::new(10, 5).with_net_balances();
+
+        let mut initial_balances = HashMap::new();
+        initial_balances.insert(0, 500);
+        initial_balances.insert(1, 700);
+
+        let batch = create_test_settlement_batch(
+            1,
+            vec![(0, 1000, true, false), (1, 300, true, true)],
+            initial_balances,
+            50000,
+        );
+        assert!(generator.generate_witness(&batch).is_ok());
+
+        // Updates against another state than the batch claims to start from
+        let mut stale = batch.clone();
+        stale.prev_state_root = batch.players[1].roots().1;
+        assert!(matches!(
+            generator.generate_witness(&stale),
+            Err(WitnessError::StateMismatch { user_id: 0 })
+        ));
+        let mut reordered = batch.clone();
+        reordered.players.swap(0, 1);
+        assert!(matches!(
+            generator.generate_witness(&reordered),
+            Err(WitnessError::StateMismatch { .. })
+        ));
+
+        // An update that settles the player at something other than their bets' net
+        let mut inflated = batch.clone();
+        inflated.players[1].final_balance += 1;
+        assert!(matches!(
+            generator.generate_witness(&inflated),
+            Err(WitnessError::BalanceMismatch {
+                user_id: 1,
+                expected: 1000,
+                actual: 1001
+            })
+        ));
+    }
+
+    #[test]
+    fn test_range_checked_values() {
+        let mut initial_balances = HashMap::new();
+        initial_balances.insert(0, 1000);
+        initial_balances.insert(1, 60000);
+
+        // Balances are net winnings, so losing more than was won settles below zero
+        let overdrawn = create_test_settlement_batch(
+            1,
+            vec![(0, 1000, true, false), (0, 1000, true, false)],
+            initial_balances.clone(),
+            50000,
+        );
+        let circuit = WitnessGenerator::new(10, 5)
+            .generate_witness(&overdrawn)
+            .unwrap();
+        assert_eq!(circuit.players[0].final_balance, -1000);
+
+        // Payout of 80000 exceeds 16 bits, though the bet amount doesn't
+        let batch =
+            create_test_settlement_batch(1, vec![(1, 40000, true, true)], initial_balances, 50000);
+        let generator = WitnessGenerator::new(10, 5).with_range_bits(16);
+        assert!(matches!(
+            generator.generate_witness(&batch),
+            Err(WitnessError::OutOfRange {
+                what: "payout",
+                value: 80000,
+                bits: 16
+            })
+        ));
+        let circuit = WitnessGenerator::new(10, 5)
+            .with_range_bits(32)
+            .generate_witness(&batch)
+            .unwrap();
+        assert_eq!(circuit.range_bits, 32);
+
+        // Widths stay within u64
+        assert_eq!(WitnessGenerator::default().range_bits(), 64);
+        assert_eq!(
+            WitnessGenerator::default()
+                .with_range_bits(128)
+                .range_bits(),
+            64
+        );
+        assert_eq!(
+            WitnessGenerator::default().with_range_bits(0).range_bits(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_net_position_records() {
+        let mut initial_balances = HashMap::new();
+        initial_balances.insert(0, 10000);
+        initial_balances.insert(1, 10000);
+        let mut batch = create_test_settlement_batch(
+            1,
+            vec![(0, 1000, true, true), (1, 300, true, false)],
+            initial_balances,
+            50000,
+        );
+        let record = |bet_id, user_id, bet_amount, payout| BetRecord {
+            bet_id,
+            user: user_key(user_id),
+            bet_amount,
+            user_guess: 1,
+            outcome: (payout > 0) as u8,
+            payout,
+        };
+        batch.records = vec![
+            record(1, 0, 500, 1000),
+            record(2, 1, 300, 0),
+            record(3, 0, 500, 1000),
+        ];
+        let generator = WitnessGenerator::new(10, 5)
+            .with_max_records(4)
+            .with_net_positions(true);
+        assert!(generator.generate_witness(&batch).unwrap().net_positions);
+
+        let mut inflated = batch.clone();
+        inflated.records[2].payout = 1200;
+        assert!(matches!(
+            generator.generate_witness(&inflated),
+            Err(WitnessError::RecordMismatch {
+                user_id: 0,
+                recorded: 1200,
+                delta: 1000
+            })
+        ));
+
+        let mut unknown = batch.clone();
+        unknown.records[1].user = user_key(7);
+        assert!(matches!(
+            generator.generate_witness(&unknown),
+            Err(WitnessError::UnknownRecordUser { bet_id: 2 })
+        ));
+
+        // Record amounts are range-checked like the bets'
+        let mut wide = batch;
+        wide.records[1].bet_amount = 1 << 40;
+        assert!(matches!(
+            generator.with_range_bits(32).generate_witness(&wide),
+            Err(WitnessError::OutOfRange {
+                what: "record bet amount",
+                ..
+            })
+        ));
     }
 
     #[test]
@@ -423,8 +783,8 @@ mod tests {
         let circuit = generator.generate_witness(&batch).unwrap();
 
         // Verify conservation: User 0 gains 1000, User 1 loses 1000, House breaks even
-        assert_eq!(circuit.final_balances[0], Fr::from(11000u64));
-        assert_eq!(circuit.final_balances[1], Fr::from(9000u64));
+        assert_eq!(circuit.players[0].final_balance, 11000);
+        assert_eq!(circuit.players[1].final_balance, 9000);
         assert_eq!(circuit.house_final, Fr::from(50000u64)); // No change
     }
 }
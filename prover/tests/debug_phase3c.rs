@@ -14,7 +14,7 @@ fn test_simple_single_bet_proof() {
 
     // Single user, single bet
     let mut initial_balances = HashMap::new();
-    initial_balances.insert(0, 10000u64);
+    initial_balances.insert(0, 10000i64);
 
     let batch = create_test_settlement_batch(
         1,
@@ -31,14 +31,15 @@ fn test_simple_single_bet_proof() {
 
     println!("Generated circuit:");
     println!("  batch_id: {:?}", circuit.batch_id);
-    println!("  initial_balances: {:?}", circuit.initial_balances);
-    println!("  final_balances: {:?}", circuit.final_balances);
+    println!("  prev_state_root: {:?}", circuit.prev_state_root);
+    println!("  new_state_root: {:?}", circuit.new_state_root);
+    println!("  players: {:?}", circuit.players);
     println!("  house_initial: {:?}", circuit.house_initial);
     println!("  house_final: {:?}", circuit.house_final);
     println!("  bets: {:?}", circuit.bets);
 
     // Try direct circuit proof
-    use ark_bn254::{Bn254, Fr};
+    use ark_bn254::Bn254;
     use ark_groth16::Groth16;
     use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
     use ark_std::rand::thread_rng;
@@ -50,11 +51,7 @@ fn test_simple_single_bet_proof() {
     let (pk, vk) = Groth16::<Bn254>::setup(circuit.clone(), &mut rng).unwrap();
 
     // Extract public inputs in correct order
-    let mut public_inputs = vec![circuit.batch_id];
-    public_inputs.extend(circuit.initial_balances.clone());
-    public_inputs.extend(circuit.final_balances.clone());
-    public_inputs.push(circuit.house_initial);
-    public_inputs.push(circuit.house_final);
+    let public_inputs = circuit.public_inputs();
 
     println!("Public inputs count: {}", public_inputs.len());
     println!("Public inputs: {:?}", public_inputs);
@@ -77,8 +74,8 @@ fn test_manual_circuit_construction() {
     // Create circuit manually with known values
     let bet = Bet::new(0, 1000, true, true); // User 0, 1000 amount, guess heads, outcome heads (win)
 
-    let initial_balances = [10000u64, 0, 0]; // User 0 has 10k, others have 0
-    let final_balances = [11000u64, 0, 0]; // User 0 gains 1k from win
+    let initial_balances = [10000i64, 0, 0]; // User 0 has 10k, others have 0
+    let final_balances = [11000i64, 0, 0]; // User 0 gains 1k from win
 
     let circuit = AccountingCircuit::new(
         vec![bet],
@@ -91,13 +88,14 @@ fn test_manual_circuit_construction() {
 
     println!("Manual circuit:");
     println!("  batch_id: {:?}", circuit.batch_id);
-    println!("  initial_balances: {:?}", circuit.initial_balances);
-    println!("  final_balances: {:?}", circuit.final_balances);
+    println!("  prev_state_root: {:?}", circuit.prev_state_root);
+    println!("  new_state_root: {:?}", circuit.new_state_root);
+    println!("  players: {:?}", circuit.players);
     println!("  house_initial: {:?}", circuit.house_initial);
     println!("  house_final: {:?}", circuit.house_final);
 
     // Test this circuit
-    use ark_bn254::{Bn254, Fr};
+    use ark_bn254::Bn254;
     use ark_groth16::Groth16;
     use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
     use ark_std::rand::thread_rng;
@@ -105,11 +103,7 @@ fn test_manual_circuit_construction() {
     let mut rng = thread_rng();
     let (pk, vk) = Groth16::<Bn254>::setup(circuit.clone(), &mut rng).unwrap();
 
-    let mut public_inputs = vec![circuit.batch_id];
-    public_inputs.extend(circuit.initial_balances.clone());
-    public_inputs.extend(circuit.final_balances.clone());
-    public_inputs.push(circuit.house_initial);
-    public_inputs.push(circuit.house_final);
+    let public_inputs = circuit.public_inputs();
 
     println!("Manual public inputs: {:?}", public_inputs);
 
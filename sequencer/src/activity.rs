@@ -0,0 +1,360 @@
+// Player activity timeline for ZK Casino
+// Merges a player's deposits, withdrawals, bets, bonuses (demo play balance grants),
+// manual adjustments and on-chain settlement confirmations into one list, newest
+// first, so frontends don't have to stitch it together from separate endpoints. Each
+// event carries the signed change it made to the balance (zero for settlements, which
+// only confirm bets already on the timeline) and a reference to its source record.
+// Pages are cut with a cursor naming the last event seen, so events arriving between
+// requests don't shift later pages.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::database::{
+    demo_account, player_account, Bet, BetSettlementStatus, Database, DatabaseError, DepositEntry,
+    JournalEntry, WithdrawalEntry, DEMO_FAUCET_ACCOUNT,
+};
+
+/// Events per page unless the request asks for fewer
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Deposit,
+    Withdrawal,
+    Bet,
+    Bonus,      // Demo play balance grant
+    Adjustment, // Manual balance adjustment
+    Settlement, // Batch with the player's bets confirmed on-chain
+}
+
+impl ActivityKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Bet => "bet",
+            Self::Bonus => "bonus",
+            Self::Adjustment => "adjustment",
+            Self::Settlement => "settlement",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ActivityEvent {
+    pub id: String, // "<kind>:<reference>", unique within the timeline; used as cursor
+    pub kind: ActivityKind,
+    pub timestamp: DateTime<Utc>,
+    pub amount: i64,       // Signed change to the balance, in lamports
+    pub reference: String, // Deposit, withdrawal or bet id, journal entry id, or batch id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl ActivityEvent {
+    fn new(kind: ActivityKind, timestamp: DateTime<Utc>, amount: i64, reference: String) -> Self {
+        Self {
+            id: format!("{}:{}", kind.as_str(), reference),
+            kind,
+            timestamp,
+            amount,
+            reference,
+            transaction_signature: None,
+            memo: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityPage {
+    pub player_address: String,
+    pub events: Vec<ActivityEvent>,
+    pub next_cursor: Option<String>, // None on the last page
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ActivityError {
+    #[error("Unknown cursor: {0}")]
+    UnknownCursor(String),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
+
+/// A player's whole timeline, newest first
+pub fn timeline(
+    player_address: &str,
+    deposits: &[DepositEntry],
+    withdrawals: &[WithdrawalEntry],
+    bets: &[Bet],
+    journal: &[JournalEntry],
+) -> Vec<ActivityEvent> {
+    let mut events = Vec::new();
+
+    events.extend(deposits.iter().map(|deposit| {
+        ActivityEvent::new(
+            ActivityKind::Deposit,
+            deposit.created_at,
+            deposit.amount,
+            deposit.id.clone(),
+        )
+    }));
+    events.extend(withdrawals.iter().map(|withdrawal| {
+        ActivityEvent::new(
+            ActivityKind::Withdrawal,
+            withdrawal.created_at,
+            -withdrawal.amount,
+            withdrawal.id.clone(),
+        )
+    }));
+
+    // Batches whose settlement was confirmed: (latest update, bets, transaction)
+    let mut settlements: BTreeMap<u64, (DateTime<Utc>, usize, Option<String>)> = BTreeMap::new();
+    for bet in bets {
+        let mut event = ActivityEvent::new(
+            ActivityKind::Bet,
+            bet.timestamp,
+            bet.payout - bet.amount,
+            bet.id.clone(),
+        );
+        event.transaction_signature = bet
+            .escrow
+            .as_ref()
+            .and_then(|escrow| escrow.settle_signature.clone());
+        events.push(event);
+
+        if let Some(link) = bet
+            .settlement
+            .as_ref()
+            .filter(|link| link.status == BetSettlementStatus::Confirmed)
+        {
+            let settlement = settlements.entry(link.batch_id).or_insert((
+                link.updated_at,
+                0,
+                link.transaction_signature.clone(),
+            ));
+            settlement.0 = settlement.0.max(link.updated_at);
+            settlement.1 += 1;
+        }
+    }
+    events.extend(settlements.into_iter().map(
+        |(batch_id, (confirmed_at, bet_count, signature))| {
+            let mut event = ActivityEvent::new(
+                ActivityKind::Settlement,
+                confirmed_at,
+                0,
+                batch_id.to_string(),
+            );
+            event.transaction_signature = signature;
+            event.memo = Some(format!("{} bets settled", bet_count));
+            event
+        },
+    ));
+
+    let accounts = [player_account(player_address), demo_account(player_address)];
+    for entry in journal {
+        let Some(posting) = entry
+            .postings
+            .iter()
+            .find(|posting| accounts.contains(&posting.account))
+        else {
+            continue;
+        };
+        let kind = if entry
+            .postings
+            .iter()
+            .any(|posting| posting.account == DEMO_FAUCET_ACCOUNT)
+        {
+            ActivityKind::Bonus
+        } else {
+            ActivityKind::Adjustment
+        };
+        let mut event =
+            ActivityEvent::new(kind, entry.created_at, posting.amount, entry.id.to_string());
+        event.memo = Some(entry.memo.clone());
+        events.push(event);
+    }
+
+    events.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+    events
+}
+
+/// Up to `limit` events after the one named by `cursor` (from the start without one)
+pub fn paginate(
+    player_address: &str,
+    events: Vec<ActivityEvent>,
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<ActivityPage, ActivityError> {
+    let start = match cursor {
+        Some(cursor) => {
+            events
+                .iter()
+                .position(|event| event.id == cursor)
+                .ok_or_else(|| ActivityError::UnknownCursor(cursor.to_string()))?
+                + 1
+        }
+        None => 0,
+    };
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let events: Vec<ActivityEvent> = events.into_iter().skip(start).collect();
+    let next_cursor = (events.len() > limit).then(|| events[limit - 1].id.clone());
+    Ok(ActivityPage {
+        player_address: player_address.to_string(),
+        events: events.into_iter().take(limit).collect(),
+        next_cursor,
+    })
+}
+
+/// Load a page of a player's timeline from the database
+pub async fn player_activity(
+    db: &Database,
+    player_address: &str,
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<ActivityPage, ActivityError> {
+    let deposits = db.get_player_deposits(player_address).await?;
+    let withdrawals = db.get_player_withdrawals(player_address).await?;
+    let bets = db.get_player_bets(player_address, Some(i64::MAX)).await?;
+    let journal = db.get_journal().await?;
+    let events = timeline(player_address, &deposits, &withdrawals, &bets, &journal);
+    paginate(player_address, events, cursor, limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{BetSettlementLink, Posting, HOUSE_ADJUSTMENTS_ACCOUNT};
+    use crate::withdrawal_fees::WithdrawalFees;
+    use chrono::Duration;
+
+    const PLAYER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn bet(id: &str, at: DateTime<Utc>, batch_id: Option<u64>) -> Bet {
+        Bet {
+            id: id.to_string(),
+            player_address: PLAYER.to_string(),
+            amount: 100,
+            guess: true,
+            result: true,
+            won: true,
+            payout: 200,
+            timestamp: at,
+            settlement: batch_id.map(|batch_id| BetSettlementLink {
+                batch_id,
+                onchain_index: 0,
+                status: BetSettlementStatus::Confirmed,
+                transaction_signature: Some("sig".to_string()),
+                updated_at: at + Duration::seconds(30),
+            }),
+            escrow: None,
+        }
+    }
+
+    fn journal_entry(id: u64, at: DateTime<Utc>, account: &str, counter: &str) -> JournalEntry {
+        JournalEntry {
+            id,
+            memo: "memo".to_string(),
+            postings: vec![
+                Posting {
+                    account: account.to_string(),
+                    amount: 500,
+                },
+                Posting {
+                    account: counter.to_string(),
+                    amount: -500,
+                },
+            ],
+            created_at: at,
+        }
+    }
+
+    #[test]
+    fn test_timeline_merges_sources() {
+        let start = Utc::now();
+        let at = |seconds| start + Duration::seconds(seconds);
+        let deposits = [DepositEntry {
+            id: "d1".to_string(),
+            player_address: PLAYER.to_string(),
+            amount: 1_000,
+            deposit_nonce: None,
+            created_at: at(0),
+        }];
+        let withdrawals = [WithdrawalEntry {
+            id: "w1".to_string(),
+            player_address: PLAYER.to_string(),
+            amount: 300,
+            fees: WithdrawalFees::default(),
+            net_amount: 300,
+            created_at: at(100),
+        }];
+        let bets = [bet("b1", at(10), Some(7)), bet("b2", at(20), Some(7))];
+        let journal = [
+            journal_entry(1, at(5), &demo_account(PLAYER), DEMO_FAUCET_ACCOUNT),
+            journal_entry(
+                2,
+                at(60),
+                &player_account(PLAYER),
+                HOUSE_ADJUSTMENTS_ACCOUNT,
+            ),
+            journal_entry(
+                3,
+                at(70),
+                &player_account("other"),
+                HOUSE_ADJUSTMENTS_ACCOUNT,
+            ),
+        ];
+
+        let events = timeline(PLAYER, &deposits, &withdrawals, &bets, &journal);
+        let ids: Vec<&str> = events.iter().map(|event| event.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "withdrawal:w1",
+                "adjustment:2",
+                "settlement:7",
+                "bet:b2",
+                "bet:b1",
+                "bonus:1",
+                "deposit:d1"
+            ]
+        );
+        assert_eq!(events[0].amount, -300);
+        assert_eq!(events[3].amount, 100);
+        // One settlement per batch, dated by its last confirmation
+        assert_eq!(events[2].timestamp, at(50));
+        assert_eq!(events[2].amount, 0);
+        assert_eq!(events[2].transaction_signature.as_deref(), Some("sig"));
+        assert_eq!(events[2].memo.as_deref(), Some("2 bets settled"));
+    }
+
+    #[test]
+    fn test_paginate() {
+        let start = Utc::now();
+        let bets: Vec<Bet> = (0..5)
+            .map(|i| bet(&format!("b{}", i), start + Duration::seconds(i), None))
+            .collect();
+        let events = timeline(PLAYER, &[], &[], &bets, &[]);
+
+        let page = paginate(PLAYER, events.clone(), None, 2).unwrap();
+        let ids: Vec<&str> = page.events.iter().map(|event| event.id.as_str()).collect();
+        assert_eq!(ids, vec!["bet:b4", "bet:b3"]);
+        assert_eq!(page.next_cursor.as_deref(), Some("bet:b3"));
+
+        let page = paginate(PLAYER, events.clone(), Some("bet:b3"), 2).unwrap();
+        assert_eq!(page.next_cursor.as_deref(), Some("bet:b1"));
+        let page = paginate(PLAYER, events.clone(), Some("bet:b1"), 2).unwrap();
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.next_cursor, None);
+
+        assert!(matches!(
+            paginate(PLAYER, events, Some("bet:unknown"), 2),
+            Err(ActivityError::UnknownCursor(_))
+        ));
+    }
+}
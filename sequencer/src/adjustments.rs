@@ -0,0 +1,402 @@
+// Manual balance adjustments for ZK Casino
+// Support can credit or debit a player's balance after an incident, but only under dual
+// control: an adjustment is proposed with a reason code, and is applied once two
+// distinct configured approvers have signed its digest. Applied adjustments are posted
+// to the double-entry journal against the house adjustments account, and every state
+// change is logged (and appended to BALANCE_ADJUSTMENT_AUDIT_LOG when set).
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use crate::database::Database;
+
+/// Distinct approvals needed before an adjustment is applied
+pub const REQUIRED_APPROVALS: usize = 2;
+
+/// Longest accepted adjustment note, in characters
+pub const MAX_NOTE_LEN: usize = 500;
+
+pub struct AdjustmentConfig {
+    pub approvers: Vec<Pubkey>,
+    pub audit_log: Option<PathBuf>,
+}
+
+impl AdjustmentConfig {
+    /// Load from environment; None when BALANCE_ADJUSTMENT_APPROVERS is unset.
+    /// (BALANCE_ADJUSTMENT_APPROVERS, BALANCE_ADJUSTMENT_AUDIT_LOG)
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(approvers) = std::env::var("BALANCE_ADJUSTMENT_APPROVERS") else {
+            info!("Balance adjustments disabled. Set BALANCE_ADJUSTMENT_APPROVERS to enable.");
+            return Ok(None);
+        };
+        let approvers = approvers
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| Pubkey::from_str(s).map_err(|e| anyhow!("Invalid approver {}: {}", s, e)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let config = Self {
+            approvers,
+            audit_log: std::env::var("BALANCE_ADJUSTMENT_AUDIT_LOG")
+                .ok()
+                .map(PathBuf::from),
+        };
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.approvers.len() < REQUIRED_APPROVALS {
+            return Err(anyhow!(
+                "Balance adjustments need at least {} approvers, got {}",
+                REQUIRED_APPROVALS,
+                self.approvers.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    ServiceIncident, // Outage or bug cost the player funds
+    ErrorCorrection, // Undo an incorrect credit or debit
+    Goodwill,        // Discretionary credit
+    FraudReversal,   // Claw back funds obtained through abuse
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentStatus {
+    PendingApproval,
+    Applying, // Both approvals in, balance being updated
+    Applied,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceAdjustment {
+    pub id: u64,
+    pub player_address: String,
+    pub amount: i64, // Positive credits the player, negative debits
+    pub reason_code: ReasonCode,
+    pub note: String,
+    pub status: AdjustmentStatus,
+    pub approvals: Vec<String>, // Approver public keys
+    pub journal_entry_id: Option<u64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BalanceAdjustment {
+    /// Message approvers sign to approve this adjustment
+    pub fn digest(&self) -> String {
+        format!(
+            "zkcasino-balance-adjustment:{}:{}:{}:{:?}",
+            self.id, self.player_address, self.amount, self.reason_code
+        )
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AdjustmentError {
+    #[error("Balance adjustment {0} not found")]
+    NotFound(u64),
+    #[error("Balance adjustment {0} is not awaiting approval")]
+    NotPending(u64),
+    #[error("{0} is not a balance adjustment approver")]
+    UnknownApprover(Pubkey),
+    #[error("Invalid approver signature")]
+    InvalidSignature,
+    #[error("Approver {0} already approved this adjustment")]
+    DuplicateApproval(Pubkey),
+    #[error("Adjustment amount must be non-zero")]
+    ZeroAmount,
+    #[error("Note must be at most {MAX_NOTE_LEN} characters")]
+    NoteTooLong,
+}
+
+pub struct AdjustmentManager {
+    config: AdjustmentConfig,
+    db: Arc<Database>,
+    adjustments: RwLock<Vec<BalanceAdjustment>>,
+}
+
+impl AdjustmentManager {
+    pub fn new(config: AdjustmentConfig, db: Arc<Database>) -> Self {
+        Self {
+            config,
+            db,
+            adjustments: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// All adjustments, newest first
+    pub fn adjustments(&self) -> Vec<BalanceAdjustment> {
+        self.adjustments.read().iter().rev().cloned().collect()
+    }
+
+    fn record(&self, adjustment: &BalanceAdjustment) {
+        info!(
+            "Balance adjustment {} ({} lamports for {}, {:?}): {:?}",
+            adjustment.id,
+            adjustment.amount,
+            adjustment.player_address,
+            adjustment.reason_code,
+            adjustment.status
+        );
+        let Some(path) = &self.config.audit_log else {
+            return;
+        };
+        let appended = serde_json::to_string(adjustment)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = appended {
+            error!(
+                "Failed to append balance adjustment audit log {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// Propose an adjustment; nothing changes until it is approved
+    pub fn propose(
+        &self,
+        player_address: String,
+        amount: i64,
+        reason_code: ReasonCode,
+        note: String,
+    ) -> Result<BalanceAdjustment, AdjustmentError> {
+        if amount == 0 {
+            return Err(AdjustmentError::ZeroAmount);
+        }
+        if note.chars().count() > MAX_NOTE_LEN {
+            return Err(AdjustmentError::NoteTooLong);
+        }
+
+        let mut adjustments = self.adjustments.write();
+        let now = Utc::now();
+        let adjustment = BalanceAdjustment {
+            id: adjustments.len() as u64 + 1,
+            player_address,
+            amount,
+            reason_code,
+            note,
+            status: AdjustmentStatus::PendingApproval,
+            approvals: Vec::new(),
+            journal_entry_id: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        adjustments.push(adjustment.clone());
+        drop(adjustments);
+
+        self.record(&adjustment);
+        Ok(adjustment)
+    }
+
+    /// Add an approval; applies the adjustment once two distinct approvers have signed
+    pub async fn approve(
+        &self,
+        id: u64,
+        approver: Pubkey,
+        signature: Signature,
+    ) -> Result<BalanceAdjustment, AdjustmentError> {
+        if !self.config.approvers.contains(&approver) {
+            return Err(AdjustmentError::UnknownApprover(approver));
+        }
+
+        let adjustment = {
+            let mut adjustments = self.adjustments.write();
+            let adjustment = adjustments
+                .iter_mut()
+                .find(|a| a.id == id)
+                .ok_or(AdjustmentError::NotFound(id))?;
+            if adjustment.status != AdjustmentStatus::PendingApproval {
+                return Err(AdjustmentError::NotPending(id));
+            }
+            if !signature.verify(approver.as_ref(), adjustment.digest().as_bytes()) {
+                return Err(AdjustmentError::InvalidSignature);
+            }
+            if adjustment.approvals.contains(&approver.to_string()) {
+                return Err(AdjustmentError::DuplicateApproval(approver));
+            }
+            adjustment.approvals.push(approver.to_string());
+            adjustment.updated_at = Utc::now();
+            // Claim application under the lock so concurrent approvals can't apply twice
+            if adjustment.approvals.len() >= REQUIRED_APPROVALS {
+                adjustment.status = AdjustmentStatus::Applying;
+            }
+            adjustment.clone()
+        };
+        self.record(&adjustment);
+
+        if adjustment.status != AdjustmentStatus::Applying {
+            return Ok(adjustment);
+        }
+        Ok(self.apply(adjustment).await)
+    }
+
+    async fn apply(&self, mut adjustment: BalanceAdjustment) -> BalanceAdjustment {
+        let memo = format!(
+            "Balance adjustment {} ({:?}) approved by {}",
+            adjustment.id,
+            adjustment.reason_code,
+            adjustment.approvals.join(", ")
+        );
+        match self
+            .db
+            .adjust_balance(&adjustment.player_address, adjustment.amount, memo)
+            .await
+        {
+            Ok((_, entry)) => {
+                adjustment.status = AdjustmentStatus::Applied;
+                adjustment.journal_entry_id = Some(entry.id);
+            }
+            Err(e) => {
+                warn!("Balance adjustment {} failed: {}", adjustment.id, e);
+                adjustment.status = AdjustmentStatus::Failed;
+                adjustment.error = Some(e.to_string());
+            }
+        }
+        adjustment.updated_at = Utc::now();
+
+        if let Some(stored) = self
+            .adjustments
+            .write()
+            .iter_mut()
+            .find(|a| a.id == adjustment.id)
+        {
+            *stored = adjustment.clone();
+        }
+        self.record(&adjustment);
+        adjustment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    async fn manager(approvers: &[&Keypair]) -> (AdjustmentManager, Arc<Database>) {
+        let db = Arc::new(Database::new("").await.unwrap());
+        let config = AdjustmentConfig {
+            approvers: approvers.iter().map(|k| k.pubkey()).collect(),
+            audit_log: None,
+        };
+        (AdjustmentManager::new(config, db.clone()), db)
+    }
+
+    #[tokio::test]
+    async fn test_adjustment_requires_two_distinct_approvers() {
+        let (alice, bob, mallory) = (Keypair::new(), Keypair::new(), Keypair::new());
+        let (manager, db) = manager(&[&alice, &bob]).await;
+        db.create_player_balance("player1", 1000).await.unwrap();
+
+        assert_eq!(
+            manager
+                .propose("player1".into(), 0, ReasonCode::Goodwill, String::new())
+                .unwrap_err(),
+            AdjustmentError::ZeroAmount
+        );
+        let adjustment = manager
+            .propose(
+                "player1".into(),
+                250,
+                ReasonCode::ServiceIncident,
+                "Bet lost during outage".into(),
+            )
+            .unwrap();
+        let sign = |signer: &Keypair| signer.sign_message(adjustment.digest().as_bytes());
+
+        assert_eq!(
+            manager
+                .approve(adjustment.id, mallory.pubkey(), sign(&mallory))
+                .await
+                .unwrap_err(),
+            AdjustmentError::UnknownApprover(mallory.pubkey())
+        );
+        assert_eq!(
+            manager
+                .approve(adjustment.id, alice.pubkey(), sign(&bob))
+                .await
+                .unwrap_err(),
+            AdjustmentError::InvalidSignature
+        );
+
+        let approved = manager
+            .approve(adjustment.id, alice.pubkey(), sign(&alice))
+            .await
+            .unwrap();
+        assert_eq!(approved.status, AdjustmentStatus::PendingApproval);
+        assert_eq!(
+            manager
+                .approve(adjustment.id, alice.pubkey(), sign(&alice))
+                .await
+                .unwrap_err(),
+            AdjustmentError::DuplicateApproval(alice.pubkey())
+        );
+        let balance = db.get_player_balance("player1").await.unwrap().unwrap();
+        assert_eq!(balance.balance, 1000);
+
+        let applied = manager
+            .approve(adjustment.id, bob.pubkey(), sign(&bob))
+            .await
+            .unwrap();
+        assert_eq!(applied.status, AdjustmentStatus::Applied);
+        let balance = db.get_player_balance("player1").await.unwrap().unwrap();
+        assert_eq!(balance.balance, 1250);
+        let journal = db.get_journal().await.unwrap();
+        assert_eq!(applied.journal_entry_id, Some(journal[0].id));
+    }
+
+    #[tokio::test]
+    async fn test_overdrawing_debit_fails() {
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let (manager, db) = manager(&[&alice, &bob]).await;
+        db.create_player_balance("player1", 100).await.unwrap();
+
+        let adjustment = manager
+            .propose(
+                "player1".into(),
+                -500,
+                ReasonCode::FraudReversal,
+                String::new(),
+            )
+            .unwrap();
+        for signer in [&alice, &bob] {
+            let signature = signer.sign_message(adjustment.digest().as_bytes());
+            manager
+                .approve(adjustment.id, signer.pubkey(), signature)
+                .await
+                .unwrap();
+        }
+        let failed = &manager.adjustments()[0];
+        assert_eq!(failed.status, AdjustmentStatus::Failed);
+        assert!(failed.error.is_some());
+        assert!(db.get_journal().await.unwrap().is_empty());
+    }
+}
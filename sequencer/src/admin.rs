@@ -0,0 +1,81 @@
+// Admin API authentication for ZK Casino
+// Admin endpoints require a bearer token configured via ADMIN_API_TOKEN.
+// When no token is configured, admin endpoints are disabled.
+
+use axum::{
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::Json,
+};
+use tracing::warn;
+
+use crate::ErrorResponse;
+
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuth {
+    token: Option<String>,
+}
+
+impl AdminAuth {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            token: token.filter(|t| !t.is_empty()),
+        }
+    }
+
+    /// Load admin token from environment (ADMIN_API_TOKEN)
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("ADMIN_API_TOKEN").ok())
+    }
+
+    /// Check the Authorization header against the configured admin token
+    pub fn authorize(&self, headers: &HeaderMap) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+        let Some(expected) = self.token.as_deref() else {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    error: "Admin API disabled".to_string(),
+                }),
+            ));
+        };
+
+        let provided = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+
+        if provided != Some(expected) {
+            warn!("Rejected admin request with invalid credentials");
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse {
+                    error: "Invalid admin credentials".to_string(),
+                }),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_authorize() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+
+        assert!(AdminAuth::new(Some("secret".to_string()))
+            .authorize(&headers)
+            .is_ok());
+
+        let (status, _) = AdminAuth::new(Some("other".to_string()))
+            .authorize(&headers)
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = AdminAuth::new(None).authorize(&headers).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,218 @@
+// Backpressure-aware bet admission for ZK Casino
+// Watches settlement queue depth, proving time and Solana submission latency, and
+// delays or sheds new bets (503 + Retry-After) before settlements start piling up
+// faster than they can be proven and submitted.
+
+use axum::{
+    extract::{Request, State},
+    http::{header::RETRY_AFTER, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::{AppState, ErrorResponse, SettlementStats, MAX_SETTLEMENT_BATCH_SIZE};
+
+#[derive(Debug, Clone)]
+pub struct AdmissionConfig {
+    pub queue_soft_limit: u64, // Start delaying bets above this many queued items
+    pub queue_hard_limit: u64, // Shed bets above this many queued items
+    pub max_backlog: Duration, // Shed when the estimated time to drain the queue exceeds this
+    pub max_submission_lag: Duration, // Shed when Solana submissions take longer than this
+    pub max_delay: Duration,   // Longest delay applied between soft and hard limits
+    pub retry_after_secs: u64,
+    pub batch_size: usize, // Items settled per batch, for backlog estimation
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            queue_soft_limit: 2_000,
+            queue_hard_limit: 10_000,
+            max_backlog: Duration::from_secs(120),
+            max_submission_lag: Duration::from_secs(60),
+            max_delay: Duration::from_millis(250),
+            retry_after_secs: 5,
+            batch_size: MAX_SETTLEMENT_BATCH_SIZE,
+        }
+    }
+}
+
+impl AdmissionConfig {
+    /// Load limits from environment (ADMISSION_QUEUE_SOFT_LIMIT, ADMISSION_QUEUE_HARD_LIMIT,
+    /// ADMISSION_MAX_BACKLOG_SECS, ADMISSION_MAX_SUBMISSION_LAG_SECS)
+    pub fn from_env() -> Self {
+        let env_u64 = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let defaults = Self::default();
+        Self {
+            queue_soft_limit: env_u64("ADMISSION_QUEUE_SOFT_LIMIT")
+                .unwrap_or(defaults.queue_soft_limit),
+            queue_hard_limit: env_u64("ADMISSION_QUEUE_HARD_LIMIT")
+                .unwrap_or(defaults.queue_hard_limit),
+            max_backlog: env_u64("ADMISSION_MAX_BACKLOG_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.max_backlog),
+            max_submission_lag: env_u64("ADMISSION_MAX_SUBMISSION_LAG_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.max_submission_lag),
+            ..defaults
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdmissionDecision {
+    Admit,
+    Delay(Duration),
+    Shed(String),
+}
+
+/// Point-in-time view of the backpressure signals
+#[derive(Debug, Clone, Serialize)]
+pub struct AdmissionSnapshot {
+    pub queue_depth: u64,
+    pub avg_proving_ms: u64,
+    pub avg_submission_ms: u64,
+    pub estimated_backlog_ms: u64,
+}
+
+pub struct AdmissionController {
+    config: AdmissionConfig,
+}
+
+impl AdmissionController {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn retry_after_secs(&self) -> u64 {
+        self.config.retry_after_secs
+    }
+
+    pub fn snapshot(&self, stats: &SettlementStats) -> AdmissionSnapshot {
+        let queue_depth = stats.items_in_current_batch.load(Ordering::Relaxed);
+        let avg_proving_ms = stats.avg_proving_ms.load(Ordering::Relaxed);
+        let avg_submission_ms = stats.avg_submission_ms.load(Ordering::Relaxed);
+
+        // Batches still to be proven and submitted, at the observed per-batch cost
+        let pending_batches = queue_depth.div_ceil(self.config.batch_size.max(1) as u64);
+        AdmissionSnapshot {
+            queue_depth,
+            avg_proving_ms,
+            avg_submission_ms,
+            estimated_backlog_ms: pending_batches * (avg_proving_ms + avg_submission_ms),
+        }
+    }
+
+    pub fn evaluate(&self, stats: &SettlementStats) -> AdmissionDecision {
+        let snapshot = self.snapshot(stats);
+        let config = &self.config;
+
+        if snapshot.queue_depth >= config.queue_hard_limit {
+            return AdmissionDecision::Shed(format!(
+                "settlement queue is full ({} pending)",
+                snapshot.queue_depth
+            ));
+        }
+        if snapshot.estimated_backlog_ms > config.max_backlog.as_millis() as u64 {
+            return AdmissionDecision::Shed(format!(
+                "settlement backlog of ~{}s exceeds {}s",
+                snapshot.estimated_backlog_ms / 1000,
+                config.max_backlog.as_secs()
+            ));
+        }
+        if snapshot.avg_submission_ms > config.max_submission_lag.as_millis() as u64 {
+            return AdmissionDecision::Shed(format!(
+                "Solana submissions are lagging (~{}s per batch)",
+                snapshot.avg_submission_ms / 1000
+            ));
+        }
+
+        if snapshot.queue_depth > config.queue_soft_limit {
+            // Scale the delay linearly between the soft and hard limits
+            let over = snapshot.queue_depth - config.queue_soft_limit;
+            let range = (config.queue_hard_limit - config.queue_soft_limit).max(1);
+            return AdmissionDecision::Delay(config.max_delay.mul_f64(over as f64 / range as f64));
+        }
+
+        AdmissionDecision::Admit
+    }
+}
+
+/// Middleware applying admission control to betting endpoints
+pub async fn admission_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.admission.evaluate(&state.settlement_stats) {
+        AdmissionDecision::Admit => {}
+        AdmissionDecision::Delay(delay) => tokio::time::sleep(delay).await,
+        AdmissionDecision::Shed(reason) => {
+            warn!("Admission control shedding bet: {}", reason);
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, state.admission.retry_after_secs().to_string())],
+                Json(ErrorResponse {
+                    error: format!("Betting temporarily unavailable: {}", reason),
+                }),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(queue_depth: u64, proving_ms: u64, submission_ms: u64) -> SettlementStats {
+        let stats = SettlementStats::new();
+        stats
+            .items_in_current_batch
+            .store(queue_depth, Ordering::Relaxed);
+        stats.avg_proving_ms.store(proving_ms, Ordering::Relaxed);
+        stats
+            .avg_submission_ms
+            .store(submission_ms, Ordering::Relaxed);
+        stats
+    }
+
+    #[test]
+    fn test_admission_decisions() {
+        let controller = AdmissionController::new(AdmissionConfig::default());
+
+        assert_eq!(
+            controller.evaluate(&stats(10, 500, 500)),
+            AdmissionDecision::Admit
+        );
+
+        // Between the soft and hard limits bets are delayed, not rejected
+        match controller.evaluate(&stats(6_000, 0, 0)) {
+            AdmissionDecision::Delay(delay) => {
+                assert!(delay > Duration::ZERO && delay <= Duration::from_millis(250))
+            }
+            other => panic!("expected delay, got {:?}", other),
+        }
+
+        assert!(matches!(
+            controller.evaluate(&stats(10_000, 0, 0)),
+            AdmissionDecision::Shed(_)
+        ));
+
+        // 20 batches pending at 10s each exceeds the 120s backlog budget
+        assert!(matches!(
+            controller.evaluate(&stats(1_000, 8_000, 2_000)),
+            AdmissionDecision::Shed(_)
+        ));
+
+        assert!(matches!(
+            controller.evaluate(&stats(1, 0, 90_000)),
+            AdmissionDecision::Shed(_)
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+// Backups for ZK Casino
+// A backup is one JSON file holding a snapshot of the database and the settlement store
+// plus a checksum over both. The settlement store is copied as it sits on disk, so it
+// stays sealed when encryption at rest is configured. Backups run on a schedule into
+// BACKUP_DIR, are optionally PUT to object storage, and old ones are pruned.
+//
+// `sequencer restore <file>` verifies a backup and puts it back in place while the
+// sequencer is stopped: the settlement file is replaced (the old one is kept as
+// .pre-restore.bak) and the database snapshot is staged next to it, to be imported
+// on the next start since the database itself lives in memory.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::hash;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+use tokio::time::interval;
+use tracing::{error, info, warn};
+
+use crate::database::{Database, DatabaseSnapshot};
+use crate::persistence_crypto::PersistenceCipher;
+use crate::settlement_persistence::SettlementPersistence;
+
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub interval: Duration,
+    pub retain: usize,                // Local backups kept; older ones are deleted
+    pub upload_url: Option<String>,   // Backups are PUT to {upload_url}/{file name}
+    pub upload_token: Option<String>, // Bearer token for the upload
+}
+
+impl BackupConfig {
+    /// Load from environment; None when BACKUP_DIR is unset.
+    /// (BACKUP_DIR, BACKUP_INTERVAL_SECS, BACKUP_RETAIN, BACKUP_UPLOAD_URL,
+    /// BACKUP_UPLOAD_TOKEN)
+    pub fn from_env() -> Option<Self> {
+        let Ok(dir) = std::env::var("BACKUP_DIR") else {
+            info!("Scheduled backups disabled. Set BACKUP_DIR to enable.");
+            return None;
+        };
+        let env_u64 = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Some(Self {
+            dir: PathBuf::from(dir),
+            interval: Duration::from_secs(env_u64("BACKUP_INTERVAL_SECS", 3600)),
+            retain: env_u64("BACKUP_RETAIN", 24).max(1) as usize,
+            upload_url: std::env::var("BACKUP_UPLOAD_URL").ok(),
+            upload_token: std::env::var("BACKUP_UPLOAD_TOKEN").ok(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupPayload {
+    pub database: DatabaseSnapshot,
+    pub settlement: String, // Settlement file contents, sealed if encryption is on
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub format_version: u32,
+    pub created_at: DateTime<Utc>,
+    pub checksum: String, // SHA-256 of the serialized payload
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreSummary {
+    pub created_at: DateTime<Utc>,
+    pub balances: usize,
+    pub bets: usize,
+    pub settlement_batches: usize,
+}
+
+fn checksum(payload: &serde_json::Value) -> Result<String> {
+    Ok(hash(serde_json::to_string(payload)?.as_bytes()).to_string())
+}
+
+/// Snapshot the database and settlement store into a backup
+pub async fn create_backup(
+    db: &Database,
+    persistence: &SettlementPersistence,
+) -> Result<BackupFile> {
+    let payload = serde_json::to_value(BackupPayload {
+        settlement: persistence.export_contents().await?,
+        database: db
+            .snapshot()
+            .await
+            .map_err(|e| anyhow!("Failed to snapshot database: {}", e))?,
+    })?;
+    Ok(BackupFile {
+        format_version: BACKUP_FORMAT_VERSION,
+        created_at: Utc::now(),
+        checksum: checksum(&payload)?,
+        payload,
+    })
+}
+
+/// Parse a backup and check its checksum and settlement contents
+pub fn verify_backup(
+    contents: &str,
+    cipher: Option<&PersistenceCipher>,
+) -> Result<(BackupFile, BackupPayload, RestoreSummary)> {
+    let backup: BackupFile =
+        serde_json::from_str(contents).map_err(|e| anyhow!("Not a backup file: {}", e))?;
+    if backup.format_version != BACKUP_FORMAT_VERSION {
+        return Err(anyhow!(
+            "Unsupported backup format version {}",
+            backup.format_version
+        ));
+    }
+    if checksum(&backup.payload)? != backup.checksum {
+        return Err(anyhow!("Backup checksum mismatch; the file is corrupt"));
+    }
+    let payload: BackupPayload = serde_json::from_value(backup.payload.clone())?;
+    let settlement_batches = SettlementPersistence::verify_contents(&payload.settlement, cipher)
+        .map_err(|e| anyhow!("Backup settlement store is unreadable: {}", e))?;
+
+    let summary = RestoreSummary {
+        created_at: backup.created_at,
+        balances: payload.database.balances.len(),
+        bets: payload.database.bets.len(),
+        settlement_batches,
+    };
+    Ok((backup, payload, summary))
+}
+
+/// Where a restored database snapshot waits for the next start
+pub fn pending_restore_path(database_url: &str) -> Option<PathBuf> {
+    SettlementPersistence::file_path_for(database_url)
+        .map(|path| path.with_extension("").with_extension("restore.json"))
+}
+
+/// Verify a backup and put it in place for `database_url`; the sequencer must be stopped
+pub async fn restore(
+    backup_path: &Path,
+    database_url: &str,
+    cipher: Option<&PersistenceCipher>,
+) -> Result<RestoreSummary> {
+    let contents = fs::read_to_string(backup_path).await?;
+    let (_, payload, summary) = verify_backup(&contents, cipher)
+        .map_err(|e| anyhow!("{}: {}", backup_path.display(), e))?;
+
+    let (Some(settlement_path), Some(database_path)) = (
+        SettlementPersistence::file_path_for(database_url),
+        pending_restore_path(database_url),
+    ) else {
+        return Err(anyhow!(
+            "Cannot restore into in-memory database {}",
+            database_url
+        ));
+    };
+    if let Some(parent) = settlement_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    if settlement_path.exists() {
+        fs::rename(
+            &settlement_path,
+            settlement_path.with_extension("json.pre-restore.bak"),
+        )
+        .await?;
+    }
+    fs::write(&settlement_path, &payload.settlement).await?;
+    fs::write(&database_path, serde_json::to_string(&payload.database)?).await?;
+
+    info!(
+        "Restored backup from {} ({} balances, {} bets, {} settlement batches)",
+        summary.created_at, summary.balances, summary.bets, summary.settlement_batches
+    );
+    Ok(summary)
+}
+
+/// Import a database snapshot staged by `restore`, if there is one
+pub async fn import_pending_restore(db: &Database, database_url: &str) -> Result<bool> {
+    let Some(path) = pending_restore_path(database_url).filter(|path| path.exists()) else {
+        return Ok(false);
+    };
+    let snapshot: DatabaseSnapshot = serde_json::from_str(&fs::read_to_string(&path).await?)
+        .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    db.restore_snapshot(snapshot)
+        .await
+        .map_err(|e| anyhow!("Failed to import {}: {}", path.display(), e))?;
+    // Keep the file for reference but never import it twice
+    fs::rename(&path, path.with_extension("imported")).await?;
+    info!("Imported restored database snapshot {}", path.display());
+    Ok(true)
+}
+
+pub struct BackupService {
+    config: BackupConfig,
+    db: Arc<Database>,
+    persistence: Arc<SettlementPersistence>,
+    client: reqwest::Client,
+}
+
+impl BackupService {
+    pub fn new(
+        config: BackupConfig,
+        db: Arc<Database>,
+        persistence: Arc<SettlementPersistence>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            persistence,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(300))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Take a backup now; returns the written file
+    pub async fn run_once(&self) -> Result<PathBuf> {
+        let backup = create_backup(&self.db, &self.persistence).await?;
+        let contents = serde_json::to_string(&backup)?;
+        let name = format!(
+            "backup-{}.json",
+            backup.created_at.format("%Y%m%dT%H%M%S%.3fZ")
+        );
+
+        // Write then rename, so a crash never leaves a truncated backup behind
+        fs::create_dir_all(&self.config.dir).await?;
+        let path = self.config.dir.join(&name);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, &contents).await?;
+        fs::rename(&partial, &path).await?;
+        info!("Wrote backup {}", path.display());
+
+        if let Some(url) = &self.config.upload_url {
+            let url = format!("{}/{}", url.trim_end_matches('/'), name);
+            let mut request = self.client.put(&url).body(contents);
+            if let Some(token) = &self.config.upload_token {
+                request = request.bearer_auth(token);
+            }
+            request.send().await?.error_for_status()?;
+            info!("Uploaded backup to {}", url);
+        }
+
+        self.prune().await?;
+        Ok(path)
+    }
+
+    /// Delete local backups beyond the retention count, oldest first
+    async fn prune(&self) -> Result<()> {
+        let mut backups = Vec::new();
+        let mut entries = fs::read_dir(&self.config.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with("backup-") && name.ends_with(".json") {
+                backups.push(entry.path());
+            }
+        }
+        // Names embed the timestamp, so they sort chronologically
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.config.retain);
+        for path in &backups[..excess] {
+            if let Err(e) = fs::remove_file(path).await {
+                warn!("Failed to prune backup {}: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start scheduled backups (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting scheduled backups to {} every {}s (keeping {})",
+            self.config.dir.display(),
+            self.config.interval.as_secs(),
+            self.config.retain
+        );
+
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.interval);
+            interval.tick().await; // Skip the immediate tick; nothing to back up at start
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_once().await {
+                    error!("Scheduled backup failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("zkcasino-backup-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore_round_trip() {
+        let source = temp_dir();
+        let source_url = format!("sqlite:{}", source.join("casino.db").display());
+        let db = Arc::new(Database::new(&source_url).await.unwrap());
+        db.deposit("player1", 5000).await.unwrap();
+        let persistence = Arc::new(SettlementPersistence::new(&source_url).await.unwrap());
+        persistence.create_batch(&[]).await.unwrap();
+
+        let service = BackupService::new(
+            BackupConfig {
+                dir: source.join("backups"),
+                interval: Duration::from_secs(3600),
+                retain: 2,
+                upload_url: None,
+                upload_token: None,
+            },
+            db.clone(),
+            persistence.clone(),
+        );
+        let mut written = Vec::new();
+        for _ in 0..3 {
+            written.push(service.run_once().await.unwrap());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // Only the newest two are kept
+        assert!(!written[0].exists());
+        assert!(written[2].exists());
+
+        let target = temp_dir();
+        let target_url = format!("sqlite:{}", target.join("casino.db").display());
+        let summary = restore(&written[2], &target_url, None).await.unwrap();
+        assert_eq!(summary.balances, 1);
+        assert_eq!(summary.settlement_batches, 1);
+
+        let restored = Database::new(&target_url).await.unwrap();
+        assert!(import_pending_restore(&restored, &target_url)
+            .await
+            .unwrap());
+        let balance = restored.get_player_balance("player1").await.unwrap();
+        assert_eq!(balance.unwrap().balance, 5000);
+        // Staged snapshot is consumed
+        assert!(!import_pending_restore(&restored, &target_url)
+            .await
+            .unwrap());
+        let settlement = SettlementPersistence::new(&target_url).await.unwrap();
+        assert_eq!(settlement.get_pending_batches().await.unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&source);
+        let _ = std::fs::remove_dir_all(&target);
+    }
+
+    #[tokio::test]
+    async fn test_tampered_backup_is_rejected() {
+        let db = Database::new("").await.unwrap();
+        db.deposit("player1", 5000).await.unwrap();
+        let persistence = SettlementPersistence::new("sqlite::memory:").await.unwrap();
+        let backup = create_backup(&db, &persistence).await.unwrap();
+        let contents = serde_json::to_string(&backup).unwrap();
+        assert!(verify_backup(&contents, None).is_ok());
+
+        let tampered = contents.replace("5000", "9000");
+        let error = verify_backup(&tampered, None).unwrap_err();
+        assert!(error.to_string().contains("checksum"));
+
+        let dir = temp_dir();
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+        let path = dir.join("tampered.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, tampered).unwrap();
+        assert!(restore(&path, &url, None).await.is_err());
+        assert!(!SettlementPersistence::file_path_for(&url).unwrap().exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,278 @@
+// Settlement batch composition for ZK Casino
+// Decides which settlement items may share a batch: items must settle the same
+// token through the same game circuit, and a batch may not exceed the circuit's
+// bet capacity or its number of distinct user slots. How often open batches close,
+// and optionally a smaller batch size than the circuit allows, is the BatchSchedule.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::settlement_prover::SettlementProverConfig;
+use crate::{GameKind, SettlementItem, SettlementToken, MAX_SETTLEMENT_BATCH_SIZE};
+
+/// Items with different keys never share a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BatchKey {
+    pub token: SettlementToken,
+    pub game: GameKind,
+}
+
+impl BatchKey {
+    pub fn for_item(item: &SettlementItem) -> Self {
+        Self {
+            token: item.token,
+            game: item.game,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchPolicy {
+    pub max_bets: usize,
+    pub max_users: Option<usize>, // Distinct players per batch (None = unlimited)
+}
+
+impl BatchPolicy {
+    /// Capacity of the settlement circuit: batches must be provable. With net positions
+    /// the circuit holds one bet per player, so players are the limit and any number of
+    /// their bets fit, up to the bets the proof commits to (and the transaction size).
+    pub fn for_prover(config: &SettlementProverConfig) -> Self {
+        if config.net_positions {
+            return Self {
+                max_bets: config.max_records.min(MAX_SETTLEMENT_BATCH_SIZE),
+                max_users: Some(config.max_users.min(config.max_bets_per_batch)),
+            };
+        }
+        Self {
+            max_bets: config.max_bets_per_batch.min(config.max_records),
+            max_users: Some(config.max_users),
+        }
+    }
+
+    /// Placeholder-proof mode: only the transaction size bounds a batch
+    pub fn unconstrained() -> Self {
+        Self {
+            max_bets: MAX_SETTLEMENT_BATCH_SIZE,
+            max_users: None,
+        }
+    }
+
+    /// Close batches at `max_bets`, if that is below the capacity
+    pub fn capped(self, max_bets: Option<usize>) -> Self {
+        Self {
+            max_bets: max_bets.map_or(self.max_bets, |max| max.clamp(1, self.max_bets)),
+            ..self
+        }
+    }
+}
+
+/// When open batches close
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchSchedule {
+    pub max_bets: Option<usize>, // Below the policy's capacity (None = full batches)
+    pub interval: Duration,      // Every open batch closes this often
+}
+
+impl Default for BatchSchedule {
+    fn default() -> Self {
+        Self {
+            max_bets: None,
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+impl BatchSchedule {
+    /// Load from environment (SETTLEMENT_BATCH_MAX_BETS, SETTLEMENT_BATCH_INTERVAL_MS)
+    pub fn from_env() -> Result<Self> {
+        let mut schedule = Self::default();
+        if let Ok(value) = std::env::var("SETTLEMENT_BATCH_MAX_BETS") {
+            schedule.max_bets = Some(
+                value
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid SETTLEMENT_BATCH_MAX_BETS: {}", e))?,
+            );
+        }
+        if let Ok(value) = std::env::var("SETTLEMENT_BATCH_INTERVAL_MS") {
+            let ms: u64 = value
+                .parse()
+                .map_err(|e| anyhow!("Invalid SETTLEMENT_BATCH_INTERVAL_MS: {}", e))?;
+            if ms == 0 {
+                return Err(anyhow!("SETTLEMENT_BATCH_INTERVAL_MS must be positive"));
+            }
+            schedule.interval = Duration::from_millis(ms);
+        }
+        Ok(schedule)
+    }
+}
+
+#[derive(Default)]
+struct OpenBatch {
+    items: Vec<SettlementItem>,
+    users: Vec<String>,
+}
+
+impl OpenBatch {
+    fn accepts(&self, item: &SettlementItem, policy: &BatchPolicy) -> bool {
+        if self.items.len() >= policy.max_bets {
+            return false;
+        }
+        match policy.max_users {
+            Some(max_users) if !self.users.contains(&item.player_address) => {
+                self.users.len() < max_users
+            }
+            _ => true,
+        }
+    }
+
+    fn push(&mut self, item: SettlementItem) {
+        if !self.users.contains(&item.player_address) {
+            self.users.push(item.player_address.clone());
+        }
+        self.items.push(item);
+    }
+}
+
+/// Groups queued settlement items into batches that satisfy a BatchPolicy
+pub struct BatchComposer {
+    policy: BatchPolicy,
+    open: HashMap<BatchKey, OpenBatch>,
+}
+
+impl BatchComposer {
+    pub fn new(policy: BatchPolicy) -> Self {
+        Self {
+            policy,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Add an item; returns any batches that are now closed and ready to settle
+    pub fn push(&mut self, item: SettlementItem) -> Vec<Vec<SettlementItem>> {
+        let mut ready = Vec::new();
+        let open = self.open.entry(BatchKey::for_item(&item)).or_default();
+
+        // Item doesn't fit: close the current batch and start a new one
+        if !open.accepts(&item, &self.policy) {
+            ready.push(std::mem::take(open).items);
+        }
+        open.push(item);
+
+        if open.items.len() >= self.policy.max_bets {
+            ready.push(std::mem::take(open).items);
+        }
+        ready
+    }
+
+    /// Items of the batch still filling for `key`, if any
+    pub fn open_batch(&self, key: &BatchKey) -> Option<&[SettlementItem]> {
+        self.open
+            .get(key)
+            .map(|batch| batch.items.as_slice())
+            .filter(|items| !items.is_empty())
+    }
+
+    /// Close every open batch (batching window elapsed)
+    pub fn drain(&mut self) -> Vec<Vec<SettlementItem>> {
+        self.open
+            .drain()
+            .map(|(_, batch)| batch.items)
+            .filter(|items| !items.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn item(player: &str, token: SettlementToken) -> SettlementItem {
+        SettlementItem {
+            bet_id: format!("bet_{}", player),
+            player_address: player.to_string(),
+            amount: 1000,
+            payout: 0,
+            timestamp: Utc::now(),
+            token,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
+        }
+    }
+
+    #[test]
+    fn test_batches_respect_circuit_capacity() {
+        let mut composer = BatchComposer::new(BatchPolicy {
+            max_bets: 3,
+            max_users: Some(2),
+        });
+
+        assert!(composer.push(item("a", SettlementToken::Sol)).is_empty());
+        assert!(composer.push(item("b", SettlementToken::Sol)).is_empty());
+
+        // A third distinct user doesn't fit: the open batch closes
+        let ready = composer.push(item("c", SettlementToken::Sol));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].len(), 2);
+
+        // Repeat users don't consume extra slots; batch closes when full
+        assert!(composer.push(item("c", SettlementToken::Sol)).is_empty());
+        let ready = composer.push(item("d", SettlementToken::Sol));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].len(), 3);
+        assert!(composer.drain().is_empty());
+    }
+
+    #[test]
+    fn test_net_positions_bound_batches_by_players() {
+        let config = SettlementProverConfig::default();
+        let policy = BatchPolicy::for_prover(&config);
+        assert_eq!(policy.max_bets, config.max_records);
+        assert_eq!(policy.max_users, Some(config.max_bets_per_batch));
+
+        // A frequent player's bets all share one batch, up to the committed bets
+        let mut composer = BatchComposer::new(policy);
+        for _ in 0..config.max_records - 1 {
+            assert!(composer.push(item("a", SettlementToken::Sol)).is_empty());
+        }
+        let ready = composer.push(item("a", SettlementToken::Sol));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].len(), config.max_records);
+
+        let per_bet = BatchPolicy::for_prover(&SettlementProverConfig {
+            net_positions: false,
+            ..config
+        });
+        assert_eq!(per_bet.max_bets, config.max_bets_per_batch);
+        assert_eq!(per_bet.max_users, Some(config.max_users));
+    }
+
+    #[test]
+    fn test_tokens_never_share_a_batch() {
+        let mut composer = BatchComposer::new(BatchPolicy::unconstrained());
+        composer.push(item("a", SettlementToken::Sol));
+        composer.push(item("b", SettlementToken::Usdc));
+        composer.push(item("c", SettlementToken::Sol));
+
+        let mut batches = composer.drain();
+        batches.sort_by_key(|b| b.len());
+        assert_eq!(batches.len(), 2);
+        assert!(batches[0].iter().all(|i| i.token == SettlementToken::Usdc));
+        assert_eq!(batches[1].len(), 2);
+        assert!(batches[1].iter().all(|i| i.token == SettlementToken::Sol));
+    }
+
+    #[test]
+    fn test_capped_policy_stays_within_capacity() {
+        let policy = BatchPolicy {
+            max_bets: 8,
+            max_users: Some(4),
+        };
+        assert_eq!(policy.capped(None), policy);
+        assert_eq!(policy.capped(Some(3)).max_bets, 3);
+        assert_eq!(policy.capped(Some(3)).max_users, Some(4));
+        assert_eq!(policy.capped(Some(50)).max_bets, 8);
+        assert_eq!(policy.capped(Some(0)).max_bets, 1);
+    }
+}
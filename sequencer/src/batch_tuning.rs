@@ -0,0 +1,377 @@
+// Settlement batch tuning for ZK Casino
+// How big settlement batches get and how often open batches close (the BatchSchedule)
+// trade fees against settlement latency. This replays the bet arrivals recorded in the
+// settlement journal through the batch composer under a range of schedules, models the
+// settlement loop proving and submitting each batch in turn, and recommends the
+// cheapest schedule whose 95th percentile latency stays within a target.
+//
+// Fees per batch are fitted to the costs recorded for settled batches; proving and
+// submission times come from a simple linear model (see TuningCostModel). The replay
+// assumes batches close as bets arrive even while the loop is busy, so a schedule that
+// can't keep up shows as growing latency rather than as delayed intake.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::batch_policy::{BatchComposer, BatchPolicy, BatchSchedule};
+use crate::settlement_persistence::{SettlementCostSummary, SettlementPersistence};
+use crate::SettlementItem;
+
+/// Batching windows tried, in milliseconds
+const CANDIDATE_INTERVALS_MS: [u64; 8] = [50, 100, 250, 500, 1_000, 2_000, 5_000, 10_000];
+
+/// Time and fee a batch of a given size costs to settle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TuningCostModel {
+    pub proving_base_ms: f64,
+    pub proving_per_bet_ms: f64,
+    pub submission_ms: f64, // Send to confirmation
+    pub fee_per_batch_lamports: f64,
+    pub fee_per_bet_lamports: f64,
+}
+
+impl TuningCostModel {
+    /// Load timings from environment (BATCH_TUNING_PROVING_BASE_MS,
+    /// BATCH_TUNING_PROVING_PER_BET_MS, BATCH_TUNING_SUBMISSION_MS); proving is free with
+    /// placeholder proofs unless configured
+    pub fn from_env(proofs_enabled: bool) -> Result<Self> {
+        fn env_or(name: &str, default: f64) -> Result<f64> {
+            match std::env::var(name) {
+                Ok(v) => v.parse().map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(default),
+            }
+        }
+
+        let (proving_base_ms, proving_per_bet_ms) = if proofs_enabled {
+            (2_000.0, 250.0)
+        } else {
+            (0.0, 0.0)
+        };
+        Ok(Self {
+            proving_base_ms: env_or("BATCH_TUNING_PROVING_BASE_MS", proving_base_ms)?,
+            proving_per_bet_ms: env_or("BATCH_TUNING_PROVING_PER_BET_MS", proving_per_bet_ms)?,
+            submission_ms: env_or("BATCH_TUNING_SUBMISSION_MS", 1_000.0)?,
+            fee_per_batch_lamports: 5_000.0, // One signature
+            fee_per_bet_lamports: 0.0,
+        })
+    }
+
+    /// Fit the fee per batch (fixed plus per bet) to recorded settlement costs, weighting
+    /// each batch size by how many batches had it. Keeps the defaults without history.
+    pub fn with_fees_from(mut self, costs: &SettlementCostSummary) -> Self {
+        // (batch size, fee per batch, weight)
+        let points: Vec<(f64, f64, f64)> = costs
+            .by_batch_size
+            .iter()
+            .filter(|size| size.batches > 0)
+            .map(|size| {
+                let n = size.batch_size as f64;
+                (n, size.avg_fee_per_bet_lamports * n, size.batches as f64)
+            })
+            .collect();
+        let weight: f64 = points.iter().map(|(_, _, w)| w).sum();
+        if weight == 0.0 {
+            return self;
+        }
+        let mean_n = points.iter().map(|(n, _, w)| n * w).sum::<f64>() / weight;
+        let mean_fee = points.iter().map(|(_, fee, w)| fee * w).sum::<f64>() / weight;
+        let variance: f64 = points
+            .iter()
+            .map(|(n, _, w)| w * (n - mean_n).powi(2))
+            .sum();
+        let slope = if variance > 0.0 {
+            let covariance: f64 = points
+                .iter()
+                .map(|(n, fee, w)| w * (n - mean_n) * (fee - mean_fee))
+                .sum();
+            (covariance / variance).max(0.0)
+        } else {
+            0.0 // A single batch size: all of the fee counts as fixed
+        };
+        self.fee_per_bet_lamports = slope;
+        self.fee_per_batch_lamports = (mean_fee - slope * mean_n).max(0.0);
+        self
+    }
+
+    fn settle_ms(&self, bets: usize) -> f64 {
+        self.proving_base_ms + self.proving_per_bet_ms * bets as f64 + self.submission_ms
+    }
+
+    fn fee_lamports(&self, bets: usize) -> f64 {
+        self.fee_per_batch_lamports + self.fee_per_bet_lamports * bets as f64
+    }
+}
+
+/// Outcome of replaying the recorded arrivals under one schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleResult {
+    pub max_bets: usize,
+    pub interval_ms: u64,
+    pub batches: usize,
+    pub avg_batch_size: f64,
+    pub fee_per_bet_lamports: f64,
+    pub mean_latency_ms: f64, // Arrival to confirmation
+    pub p95_latency_ms: f64,
+    pub max_latency_ms: f64,
+    pub utilization: f64, // Share of the replay the settlement loop was busy
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchTuningReport {
+    pub generated_at: DateTime<Utc>,
+    pub arrivals: usize,
+    pub first_arrival: Option<DateTime<Utc>>,
+    pub last_arrival: Option<DateTime<Utc>>,
+    pub target_p95_latency_ms: u64,
+    pub costs: TuningCostModel,
+    pub current: Option<ScheduleResult>, // The schedule in effect
+    pub recommended: Option<ScheduleResult>,
+    pub candidates: Vec<ScheduleResult>,
+}
+
+/// Replay `items` (in arrival order) with batches closing at `policy.max_bets` or every
+/// `interval_ms`, as the settlement loop does. None without arrivals.
+pub fn simulate(
+    items: &[SettlementItem],
+    policy: BatchPolicy,
+    interval_ms: u64,
+    costs: &TuningCostModel,
+) -> Option<ScheduleResult> {
+    let start = items.first()?.timestamp;
+    let arrival_ms = |item: &SettlementItem| (item.timestamp - start).num_milliseconds().max(0);
+    let interval = interval_ms.max(1) as i64;
+
+    // (close time, arrival times of the batch's bets)
+    let mut closed: Vec<(i64, Vec<i64>)> = Vec::new();
+    let close = |closed: &mut Vec<(i64, Vec<i64>)>, at: i64, batches: Vec<Vec<SettlementItem>>| {
+        closed.extend(
+            batches
+                .into_iter()
+                .map(|batch| (at, batch.iter().map(arrival_ms).collect())),
+        );
+    };
+
+    let mut composer = BatchComposer::new(policy);
+    let mut next_tick = 0i64;
+    for item in items {
+        let at = arrival_ms(item);
+        while next_tick <= at {
+            let drained = composer.drain();
+            if drained.is_empty() {
+                // Nothing open: skip the idle ticks
+                next_tick += ((at - next_tick) / interval + 1) * interval;
+            } else {
+                close(&mut closed, next_tick, drained);
+                next_tick += interval;
+            }
+        }
+        let ready = composer.push(item.clone());
+        close(&mut closed, at, ready);
+    }
+    close(&mut closed, next_tick, composer.drain());
+
+    // The settlement loop settles one batch at a time
+    let mut free_at = 0.0f64;
+    let mut busy = 0.0f64;
+    let mut fees = 0.0f64;
+    let mut latencies = Vec::with_capacity(items.len());
+    for (closed_at, arrivals) in &closed {
+        let settle_ms = costs.settle_ms(arrivals.len());
+        let done = free_at.max(*closed_at as f64) + settle_ms;
+        free_at = done;
+        busy += settle_ms;
+        fees += costs.fee_lamports(arrivals.len());
+        latencies.extend(arrivals.iter().map(|arrived| done - *arrived as f64));
+    }
+    latencies.sort_by(f64::total_cmp);
+
+    let count = latencies.len() as f64;
+    let p95_index = ((count * 0.95).ceil() as usize).clamp(1, latencies.len()) - 1;
+    Some(ScheduleResult {
+        max_bets: policy.max_bets,
+        interval_ms,
+        batches: closed.len(),
+        avg_batch_size: count / closed.len() as f64,
+        fee_per_bet_lamports: fees / count,
+        mean_latency_ms: latencies.iter().sum::<f64>() / count,
+        p95_latency_ms: latencies[p95_index],
+        max_latency_ms: latencies[latencies.len() - 1],
+        utilization: if free_at > 0.0 { busy / free_at } else { 0.0 },
+    })
+}
+
+/// Batch sizes tried: powers of two up to the capacity, and the capacity itself
+fn candidate_sizes(capacity: usize) -> Vec<usize> {
+    let mut sizes: Vec<usize> = std::iter::successors(Some(1usize), |n| n.checked_mul(2))
+        .take_while(|n| *n < capacity)
+        .collect();
+    sizes.push(capacity.max(1));
+    sizes
+}
+
+/// Cheapest schedule meeting the latency target; the fastest one if none does
+pub fn recommend(candidates: &[ScheduleResult], target_p95_ms: u64) -> Option<ScheduleResult> {
+    let by_latency =
+        |a: &&ScheduleResult, b: &&ScheduleResult| a.p95_latency_ms.total_cmp(&b.p95_latency_ms);
+    candidates
+        .iter()
+        .filter(|c| c.p95_latency_ms <= target_p95_ms as f64)
+        .min_by(|a, b| {
+            a.fee_per_bet_lamports
+                .total_cmp(&b.fee_per_bet_lamports)
+                .then_with(|| by_latency(a, b))
+        })
+        .or_else(|| candidates.iter().min_by(by_latency))
+        .cloned()
+}
+
+/// Replay every recorded arrival under the candidate schedules, within the batch
+/// capacity `policy`
+pub async fn tune(
+    persistence: &SettlementPersistence,
+    policy: BatchPolicy,
+    current: BatchSchedule,
+    costs: TuningCostModel,
+    target_p95_ms: u64,
+) -> Result<BatchTuningReport> {
+    let costs = costs.with_fees_from(&persistence.get_cost_summary().await?);
+    let mut items: Vec<SettlementItem> = persistence
+        .get_batches()
+        .await?
+        .into_iter()
+        .flat_map(|batch| batch.items)
+        .collect();
+    items.sort_by_key(|item| item.timestamp);
+
+    let mut candidates = Vec::new();
+    for max_bets in candidate_sizes(policy.max_bets) {
+        for interval_ms in CANDIDATE_INTERVALS_MS {
+            candidates.extend(simulate(
+                &items,
+                policy.capped(Some(max_bets)),
+                interval_ms,
+                &costs,
+            ));
+        }
+    }
+
+    Ok(BatchTuningReport {
+        generated_at: Utc::now(),
+        arrivals: items.len(),
+        first_arrival: items.first().map(|item| item.timestamp),
+        last_arrival: items.last().map(|item| item.timestamp),
+        target_p95_latency_ms: target_p95_ms,
+        current: simulate(
+            &items,
+            policy.capped(current.max_bets),
+            current.interval.as_millis() as u64,
+            &costs,
+        ),
+        recommended: recommend(&candidates, target_p95_ms),
+        costs,
+        candidates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement_persistence::BatchSizeCost;
+    use chrono::Duration;
+
+    fn arrivals(offsets_ms: &[i64]) -> Vec<SettlementItem> {
+        let start = Utc::now();
+        offsets_ms
+            .iter()
+            .enumerate()
+            .map(|(i, offset)| SettlementItem {
+                bet_id: format!("bet_{}", i),
+                player_address: format!("player_{}", i % 3),
+                amount: 1_000,
+                payout: 0,
+                timestamp: start + Duration::milliseconds(*offset),
+                token: Default::default(),
+                game: Default::default(),
+                vrf_proof: None,
+            })
+            .collect()
+    }
+
+    fn costs() -> TuningCostModel {
+        TuningCostModel {
+            proving_base_ms: 100.0,
+            proving_per_bet_ms: 10.0,
+            submission_ms: 400.0,
+            fee_per_batch_lamports: 5_000.0,
+            fee_per_bet_lamports: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_simulated_schedules() {
+        let items = arrivals(&[0, 10, 20, 30, 1_000, 1_010]);
+        let policy = BatchPolicy::unconstrained();
+
+        // A short window settles each burst on its own
+        let short = simulate(&items, policy, 100, &costs()).unwrap();
+        assert_eq!(short.batches, 2);
+        // First burst closes at 100ms and takes 540ms to settle
+        assert_eq!(short.max_latency_ms, 640.0);
+        assert!((short.fee_per_bet_lamports - 10_000.0 / 6.0).abs() < 1e-9);
+
+        // A long window waits for both bursts: fewer fees, longer waits
+        let long = simulate(&items, policy, 5_000, &costs()).unwrap();
+        assert_eq!(long.batches, 1);
+        assert!(long.fee_per_bet_lamports < short.fee_per_bet_lamports);
+        assert!(long.p95_latency_ms > short.p95_latency_ms);
+
+        // Small batches queue up behind each other
+        let tiny = simulate(&items, policy.capped(Some(1)), 100, &costs()).unwrap();
+        assert_eq!(tiny.batches, 6);
+        assert!(tiny.max_latency_ms > short.max_latency_ms);
+
+        assert!(simulate(&[], policy, 100, &costs()).is_none());
+
+        let candidates = [short.clone(), long.clone(), tiny];
+        assert_eq!(recommend(&candidates, 60_000), Some(long));
+        assert_eq!(recommend(&candidates, 1_000), Some(short.clone()));
+        // Nothing meets the target: the fastest schedule
+        assert_eq!(recommend(&candidates, 1), Some(short));
+    }
+
+    #[test]
+    fn test_fees_fitted_to_history() {
+        let summary = |sizes: Vec<BatchSizeCost>| SettlementCostSummary {
+            batches: 0,
+            bets: 0,
+            total_fee_lamports: 0,
+            total_priority_fee_lamports: 0,
+            total_compute_units: 0,
+            avg_fee_per_batch_lamports: 0.0,
+            avg_fee_per_bet_lamports: 0.0,
+            avg_compute_units_per_bet: None,
+            by_batch_size: sizes,
+        };
+        let size = |batch_size: usize, batches: u64, fee_per_batch: f64| BatchSizeCost {
+            batch_size,
+            batches,
+            avg_fee_per_bet_lamports: fee_per_batch / batch_size as f64,
+            avg_compute_units_per_bet: None,
+        };
+
+        // 6000 per batch plus 100 per bet
+        let fitted =
+            costs().with_fees_from(&summary(vec![size(2, 3, 6_200.0), size(10, 1, 7_000.0)]));
+        assert!((fitted.fee_per_batch_lamports - 6_000.0).abs() < 1e-6);
+        assert!((fitted.fee_per_bet_lamports - 100.0).abs() < 1e-6);
+
+        let single = costs().with_fees_from(&summary(vec![size(4, 2, 10_000.0)]));
+        assert_eq!(single.fee_per_batch_lamports, 10_000.0);
+        assert_eq!(single.fee_per_bet_lamports, 0.0);
+
+        assert_eq!(costs().with_fees_from(&summary(vec![])), costs());
+        assert_eq!(candidate_sizes(50), vec![1, 2, 4, 8, 16, 32, 50]);
+        assert_eq!(candidate_sizes(4), vec![1, 2, 4]);
+    }
+}
@@ -0,0 +1,216 @@
+// Structured bet identifiers for ZK Casino
+// A bet ID packs creation time, sequencer instance, a per-millisecond sequence
+// and a checksum into a u64, so IDs sort chronologically and map losslessly onto
+// the u64 `bet_id` used in on-chain BetSettlements.
+//
+// Layout (most significant bit first):
+//   41 bits  milliseconds since BET_ID_EPOCH_MS (~69 years)
+//    6 bits  sequencer instance (0-63)
+//   12 bits  sequence within the millisecond (0-4095)
+//    5 bits  checksum over the upper 59 bits
+//
+// String form is `bet_` followed by 16 lowercase hex digits.
+
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// 2024-01-01T00:00:00Z
+pub const BET_ID_EPOCH_MS: u64 = 1_704_067_200_000;
+
+const TIMESTAMP_BITS: u32 = 41;
+const INSTANCE_BITS: u32 = 6;
+const SEQUENCE_BITS: u32 = 12;
+const CHECKSUM_BITS: u32 = 5;
+
+pub const MAX_INSTANCE: u8 = (1 << INSTANCE_BITS) - 1;
+const MAX_SEQUENCE: u16 = (1 << SEQUENCE_BITS) - 1;
+const MAX_TIMESTAMP: u64 = (1 << TIMESTAMP_BITS) - 1;
+const CHECKSUM_MASK: u64 = (1 << CHECKSUM_BITS) - 1;
+
+const PREFIX: &str = "bet_";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BetIdError {
+    #[error("Bet ID must be 'bet_' followed by 16 hex digits")]
+    InvalidFormat,
+    #[error("Bet ID checksum mismatch")]
+    ChecksumMismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BetId(u64);
+
+/// XOR-fold the payload into 5 bits
+fn checksum(payload: u64) -> u64 {
+    let mut folded = 0;
+    let mut remaining = payload;
+    while remaining != 0 {
+        folded ^= remaining & CHECKSUM_MASK;
+        remaining >>= CHECKSUM_BITS;
+    }
+    folded
+}
+
+impl BetId {
+    fn from_parts(timestamp_ms: u64, instance: u8, sequence: u16) -> Self {
+        let payload = (timestamp_ms << (INSTANCE_BITS + SEQUENCE_BITS))
+            | ((instance as u64) << SEQUENCE_BITS)
+            | sequence as u64;
+        Self((payload << CHECKSUM_BITS) | checksum(payload))
+    }
+
+    /// Validate the checksum of a raw u64 (e.g. read back from an on-chain BetSettlement)
+    pub fn from_u64(value: u64) -> Result<Self, BetIdError> {
+        if checksum(value >> CHECKSUM_BITS) != value & CHECKSUM_MASK {
+            return Err(BetIdError::ChecksumMismatch);
+        }
+        Ok(Self(value))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    fn payload(&self) -> u64 {
+        self.0 >> CHECKSUM_BITS
+    }
+
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        let ms = (self.payload() >> (INSTANCE_BITS + SEQUENCE_BITS)) + BET_ID_EPOCH_MS;
+        Utc.timestamp_millis_opt(ms as i64)
+            .single()
+            .unwrap_or_default()
+    }
+
+    #[allow(dead_code)]
+    pub fn instance(&self) -> u8 {
+        ((self.payload() >> SEQUENCE_BITS) as u8) & MAX_INSTANCE
+    }
+
+    #[allow(dead_code)]
+    pub fn sequence(&self) -> u16 {
+        (self.payload() as u16) & MAX_SEQUENCE
+    }
+}
+
+impl fmt::Display for BetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:016x}", PREFIX, self.0)
+    }
+}
+
+impl FromStr for BetId {
+    type Err = BetIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix(PREFIX).ok_or(BetIdError::InvalidFormat)?;
+        if hex.len() != 16 {
+            return Err(BetIdError::InvalidFormat);
+        }
+        let value = u64::from_str_radix(hex, 16).map_err(|_| BetIdError::InvalidFormat)?;
+        Self::from_u64(value)
+    }
+}
+
+/// Monotonic bet ID generator for one sequencer instance
+pub struct BetIdGenerator {
+    instance: u8,
+    last: Mutex<(u64, u16)>, // (timestamp_ms, sequence)
+}
+
+impl BetIdGenerator {
+    pub fn new(instance: u8) -> Self {
+        Self {
+            instance: instance.min(MAX_INSTANCE),
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    /// Instance from environment (SEQUENCER_INSTANCE_ID, default 0)
+    pub fn from_env() -> Self {
+        let instance = std::env::var("SEQUENCER_INSTANCE_ID")
+            .ok()
+            .and_then(|v| v.parse::<u8>().ok())
+            .unwrap_or(0);
+        Self::new(instance)
+    }
+
+    pub fn next_id(&self) -> BetId {
+        let now_ms = (Utc::now().timestamp_millis() as u64)
+            .saturating_sub(BET_ID_EPOCH_MS)
+            .min(MAX_TIMESTAMP);
+
+        let mut last = self.last.lock();
+        let (last_ms, last_seq) = *last;
+
+        // Never go backwards; borrow the next millisecond when the sequence is exhausted
+        let (timestamp_ms, sequence) = if now_ms > last_ms {
+            (now_ms, 0)
+        } else if last_seq < MAX_SEQUENCE {
+            (last_ms, last_seq + 1)
+        } else {
+            (last_ms + 1, 0)
+        };
+
+        *last = (timestamp_ms, sequence);
+        BetId::from_parts(timestamp_ms, self.instance, sequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bet_id_roundtrip() {
+        let generator = BetIdGenerator::new(7);
+        let id = generator.next_id();
+
+        assert_eq!(id.instance(), 7);
+        assert_eq!(id.sequence(), 0);
+        assert!((Utc::now() - id.timestamp()).num_seconds().abs() < 5);
+
+        let parsed: BetId = id.to_string().parse().unwrap();
+        assert_eq!(parsed, id);
+        assert_eq!(BetId::from_u64(id.as_u64()).unwrap(), id);
+    }
+
+    #[test]
+    fn test_bet_ids_sort_chronologically() {
+        let generator = BetIdGenerator::new(1);
+        let ids: Vec<BetId> = (0..5000).map(|_| generator.next_id()).collect();
+
+        for pair in ids.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(pair[0].to_string() < pair[1].to_string());
+        }
+    }
+
+    #[test]
+    fn test_bet_id_checksum() {
+        let id = BetIdGenerator::new(3).next_id();
+        let s = id.to_string();
+
+        // Flip one hex digit
+        let mut chars: Vec<char> = s.chars().collect();
+        let i = chars.len() - 6;
+        chars[i] = if chars[i] == '0' { '1' } else { '0' };
+        let corrupted: String = chars.into_iter().collect();
+        assert_eq!(
+            corrupted.parse::<BetId>(),
+            Err(BetIdError::ChecksumMismatch)
+        );
+
+        assert_eq!(
+            "bet_0f3a2c".parse::<BetId>(),
+            Err(BetIdError::InvalidFormat)
+        );
+        assert_eq!(
+            "bet_5d41402abc4b2a76b9719d911017c592".parse::<BetId>(),
+            Err(BetIdError::InvalidFormat)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// Request-layer bet deduplication for ZK Casino
+// Clients attach a nonce to each bet; resending the same (player, nonce) returns
+// the original BetResponse instead of placing a second bet. Complements the
+// settlement-layer dedup in settlement_persistence.
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+use crate::BetResponse;
+
+/// Prune expired nonces every this many reservations
+const PRUNE_INTERVAL: usize = 1024;
+
+enum NonceState {
+    InFlight,               // First request still being processed
+    Completed(BetResponse), // Original response to replay
+}
+
+struct NonceEntry {
+    amount: u64,
+    guess: bool,
+    state: NonceState,
+    created_at: Instant,
+}
+
+/// Outcome of reserving a (player, nonce) pair
+pub enum NonceReservation {
+    /// First time this nonce is seen: place the bet
+    New,
+    /// Resend of a completed bet: return the original response
+    Duplicate(BetResponse),
+    /// The original request is still being processed
+    InFlight,
+    /// Nonce was already used for a bet with a different amount or guess
+    Mismatch,
+}
+
+pub struct BetNonceCache {
+    ttl: Duration,
+    entries: DashMap<(String, String), NonceEntry>,
+}
+
+impl BetNonceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// Load TTL from environment (BET_NONCE_TTL_SECS, default 24 hours)
+    pub fn from_env() -> Self {
+        let ttl = std::env::var("BET_NONCE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(24 * 60 * 60);
+        Self::new(Duration::from_secs(ttl))
+    }
+
+    /// Atomically claim a nonce, or report how an earlier bet with it went
+    pub fn reserve(&self, player: &str, nonce: &str, amount: u64, guess: bool) -> NonceReservation {
+        if self.entries.len() % PRUNE_INTERVAL == PRUNE_INTERVAL - 1 {
+            self.prune_expired();
+        }
+
+        let new_entry = || NonceEntry {
+            amount,
+            guess,
+            state: NonceState::InFlight,
+            created_at: Instant::now(),
+        };
+
+        match self.entries.entry((player.to_string(), nonce.to_string())) {
+            Entry::Vacant(vacant) => {
+                vacant.insert(new_entry());
+                NonceReservation::New
+            }
+            Entry::Occupied(mut occupied) => {
+                let entry = occupied.get();
+                if entry.created_at.elapsed() >= self.ttl {
+                    occupied.insert(new_entry());
+                    NonceReservation::New
+                } else if entry.amount != amount || entry.guess != guess {
+                    NonceReservation::Mismatch
+                } else {
+                    match &entry.state {
+                        NonceState::InFlight => NonceReservation::InFlight,
+                        NonceState::Completed(response) => {
+                            NonceReservation::Duplicate(response.clone())
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remember the response for a reserved nonce so resends can replay it
+    pub fn complete(&self, player: &str, nonce: &str, response: &BetResponse) {
+        if let Some(mut entry) = self
+            .entries
+            .get_mut(&(player.to_string(), nonce.to_string()))
+        {
+            entry.state = NonceState::Completed(response.clone());
+        }
+    }
+
+    /// Drop a reservation whose bet was rejected, so the client may retry with it
+    pub fn release(&self, player: &str, nonce: &str) {
+        self.entries
+            .remove(&(player.to_string(), nonce.to_string()));
+    }
+
+    fn prune_expired(&self) {
+        self.entries
+            .retain(|_, entry| entry.created_at.elapsed() < self.ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn response(bet_id: &str) -> BetResponse {
+        BetResponse {
+            bet_id: bet_id.to_string(),
+            player_address: "player".to_string(),
+            amount: 1000,
+            guess: true,
+            result: true,
+            won: true,
+            payout: 2000,
+            timestamp: Utc::now(),
+            settlement: None,
+            vrf_proof: None,
+        }
+    }
+
+    #[test]
+    fn test_nonce_reservation() {
+        let cache = BetNonceCache::new(Duration::from_secs(60));
+
+        assert!(matches!(
+            cache.reserve("player", "n1", 1000, true),
+            NonceReservation::New
+        ));
+        assert!(matches!(
+            cache.reserve("player", "n1", 1000, true),
+            NonceReservation::InFlight
+        ));
+
+        cache.complete("player", "n1", &response("bet_1"));
+        match cache.reserve("player", "n1", 1000, true) {
+            NonceReservation::Duplicate(original) => assert_eq!(original.bet_id, "bet_1"),
+            _ => panic!("expected duplicate"),
+        }
+        assert!(matches!(
+            cache.reserve("player", "n1", 5000, true),
+            NonceReservation::Mismatch
+        ));
+
+        // Nonces are scoped per player
+        assert!(matches!(
+            cache.reserve("other", "n1", 1000, true),
+            NonceReservation::New
+        ));
+
+        // Released nonces can be reused
+        assert!(matches!(
+            cache.reserve("player", "n2", 1000, false),
+            NonceReservation::New
+        ));
+        cache.release("player", "n2");
+        assert!(matches!(
+            cache.reserve("player", "n2", 1000, false),
+            NonceReservation::New
+        ));
+    }
+
+    #[test]
+    fn test_nonce_expiry() {
+        let cache = BetNonceCache::new(Duration::ZERO);
+        cache.reserve("player", "n1", 1000, true);
+        cache.complete("player", "n1", &response("bet_1"));
+        assert!(matches!(
+            cache.reserve("player", "n1", 1000, true),
+            NonceReservation::New
+        ));
+    }
+}
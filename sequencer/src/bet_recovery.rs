@@ -0,0 +1,373 @@
+// Fair-shutdown bet recovery for ZK Casino
+// A bet's outcome reaches the player before its record and balance change are saved,
+// so a shutdown or crash in between leaves bets the player has seen but the sequencer
+// never recorded. Their randomness is in the VRF journal, written before any response
+// goes out, so on restart each journaled bet that is neither in the database nor in a
+// settlement batch is resolved by a fixed policy: completed with the outcome the player
+// was shown when the loaded VRF key reproduces it and the balance still covers the
+// stake, refunded otherwise. Nothing was debited for an unrecorded bet, so a refund
+// leaves the balance as it is. Decisions are appended to a log next to the journal, so
+// each bet is decided once and its decision can be looked up through the API.
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::hash;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::database::PlayerBalance;
+use crate::tokens::PayoutSchedule;
+use crate::vrf::{prove_coinflip, CoinflipInput};
+use crate::vrf_journal::VrfJournalEntry;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundReason {
+    MalformedEntry,      // The journaled message isn't a coin flip message
+    KeyRotated,          // Signed with a VRF key that is no longer loaded
+    ProofMismatch,       // The loaded key doesn't reproduce the journaled signature
+    PlayerNotFound,      // No balance to settle the bet against
+    InsufficientBalance, // The balance no longer covers the stake
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum RecoveryOutcome {
+    Completed {
+        result: bool,
+        won: bool,
+        payout: u64,
+    },
+    Refunded {
+        reason: RefundReason,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryDecision {
+    pub bet_id: String,
+    pub journal_sequence: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player_address: Option<String>, // None for malformed journal entries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<u64>,
+    #[serde(flatten)]
+    pub outcome: RecoveryOutcome,
+    pub decided_at: DateTime<Utc>,
+}
+
+/// A coin flip bet as journaled
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournaledBet {
+    pub bet_id: String,
+    pub player_address: String,
+    pub amount: u64,
+    pub guess: bool,
+}
+
+impl JournaledBet {
+    /// The bet behind a journal entry; None when its message isn't a coin flip message
+    /// for the entry's nonce
+    pub fn from_entry(entry: &VrfJournalEntry) -> Option<Self> {
+        let message = String::from_utf8(BASE64.decode(&entry.message).ok()?).ok()?;
+        let input = CoinflipInput::from_message(&message)?;
+        (input.bet_id == entry.nonce).then(|| Self {
+            bet_id: input.bet_id.to_string(),
+            player_address: input.player.to_string(),
+            amount: input.amount,
+            guess: input.guess,
+        })
+    }
+
+    fn input(&self) -> CoinflipInput<'_> {
+        CoinflipInput {
+            bet_id: &self.bet_id,
+            player: &self.player_address,
+            amount: self.amount,
+            guess: self.guess,
+        }
+    }
+}
+
+/// The policy for an unrecorded bet. Completing needs the outcome the player was shown,
+/// re-derived with the loaded VRF key and matched against the journaled signature hash,
+/// and a balance that still covers the stake; anything else is refunded. Returns the
+/// proof along with a completion.
+pub fn decide(
+    entry: &VrfJournalEntry,
+    bet: Option<&JournaledBet>,
+    vrf: &Keypair,
+    balance: Option<&PlayerBalance>,
+    payouts: &PayoutSchedule,
+) -> (RecoveryOutcome, Option<Signature>) {
+    let refund = |reason| (RecoveryOutcome::Refunded { reason }, None);
+    let Some(bet) = bet else {
+        return refund(RefundReason::MalformedEntry);
+    };
+    if entry.key_version != vrf.pubkey().to_string() {
+        return refund(RefundReason::KeyRotated);
+    }
+    let (result, proof) = prove_coinflip(vrf, &bet.input());
+    if hash(proof.as_ref()).to_string() != entry.signature_hash {
+        return refund(RefundReason::ProofMismatch);
+    }
+    match balance {
+        None => refund(RefundReason::PlayerNotFound),
+        Some(balance) if balance.balance < bet.amount as i64 => {
+            refund(RefundReason::InsufficientBalance)
+        }
+        Some(_) => {
+            let won = result == bet.guess;
+            let payout = if won {
+                payouts.win_payout(bet.amount)
+            } else {
+                0
+            };
+            (
+                RecoveryOutcome::Completed {
+                    result,
+                    won,
+                    payout,
+                },
+                Some(proof),
+            )
+        }
+    }
+}
+
+/// Decisions made so far, persisted as JSON lines
+pub struct RecoveryLog {
+    path: Option<PathBuf>, // None keeps decisions in memory only
+    decisions: RwLock<BTreeMap<String, RecoveryDecision>>, // By bet id
+}
+
+impl RecoveryLog {
+    /// Log of decisions for the journal at `journal_path`, stored beside it
+    pub fn path_for(journal_path: &Path) -> PathBuf {
+        journal_path.with_extension("recovery.jsonl")
+    }
+
+    pub fn open(path: Option<PathBuf>) -> Result<Self> {
+        let mut decisions = BTreeMap::new();
+        if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let decision: RecoveryDecision = serde_json::from_str(&line?)?;
+                decisions.insert(decision.bet_id.clone(), decision);
+            }
+        }
+        Ok(Self {
+            path,
+            decisions: RwLock::new(decisions),
+        })
+    }
+
+    pub fn contains(&self, bet_id: &str) -> bool {
+        self.decisions.read().contains_key(bet_id)
+    }
+
+    pub fn get(&self, bet_id: &str) -> Option<RecoveryDecision> {
+        self.decisions.read().get(bet_id).cloned()
+    }
+
+    /// Every decision, oldest bet first, optionally for one player
+    pub fn list(&self, player_address: Option<&str>) -> Vec<RecoveryDecision> {
+        self.decisions
+            .read()
+            .values()
+            .filter(|decision| {
+                player_address
+                    .is_none_or(|player| decision.player_address.as_deref() == Some(player))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Persist a decision once it has been carried out
+    pub fn record(&self, decision: RecoveryDecision) -> Result<()> {
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(&decision)?)?;
+            file.sync_data()?;
+        }
+        self.decisions
+            .write()
+            .insert(decision.bet_id.clone(), decision);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn journaled(vrf: &Keypair, bet_id: &str) -> (VrfJournalEntry, JournaledBet) {
+        let bet = JournaledBet {
+            bet_id: bet_id.to_string(),
+            player_address: PLAYER.to_string(),
+            amount: 1_000,
+            guess: true,
+        };
+        let (_, proof) = prove_coinflip(vrf, &bet.input());
+        let entry = VrfJournalEntry {
+            sequence: 0,
+            nonce: bet_id.to_string(),
+            message: BASE64.encode(bet.input().message()),
+            key_version: vrf.pubkey().to_string(),
+            signature_hash: hash(proof.as_ref()).to_string(),
+            recorded_at: Utc::now(),
+        };
+        (entry, bet)
+    }
+
+    fn balance(amount: i64) -> PlayerBalance {
+        PlayerBalance {
+            player_address: PLAYER.to_string(),
+            balance: amount,
+            total_deposited: amount,
+            total_withdrawn: 0,
+            total_wagered: 0,
+            total_won: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            demo: false,
+        }
+    }
+
+    #[test]
+    fn test_decide() {
+        let vrf = Keypair::new();
+        let payouts = PayoutSchedule::default();
+        let (entry, bet) = journaled(&vrf, "bet_0000000000000001");
+        assert_eq!(JournaledBet::from_entry(&entry), Some(bet.clone()));
+
+        // Reproducible outcome and a covered stake: completed as the player saw it
+        let (outcome, proof) = decide(&entry, Some(&bet), &vrf, Some(&balance(1_000)), &payouts);
+        let (result, expected) = prove_coinflip(&vrf, &bet.input());
+        assert_eq!(proof, Some(expected));
+        assert_eq!(
+            outcome,
+            RecoveryOutcome::Completed {
+                result,
+                won: result,
+                payout: if result { payouts.win_payout(1_000) } else { 0 },
+            }
+        );
+
+        let refunded = |outcome: (RecoveryOutcome, Option<Signature>)| match outcome {
+            (RecoveryOutcome::Refunded { reason }, None) => Some(reason),
+            _ => None,
+        };
+        assert_eq!(
+            refunded(decide(
+                &entry,
+                Some(&bet),
+                &vrf,
+                Some(&balance(999)),
+                &payouts
+            )),
+            Some(RefundReason::InsufficientBalance)
+        );
+        assert_eq!(
+            refunded(decide(&entry, Some(&bet), &vrf, None, &payouts)),
+            Some(RefundReason::PlayerNotFound)
+        );
+        assert_eq!(
+            refunded(decide(
+                &entry,
+                Some(&bet),
+                &Keypair::new(),
+                Some(&balance(1_000)),
+                &payouts
+            )),
+            Some(RefundReason::KeyRotated)
+        );
+        let mut tampered = entry.clone();
+        tampered.signature_hash = hash(b"other").to_string();
+        assert_eq!(
+            refunded(decide(
+                &tampered,
+                Some(&bet),
+                &vrf,
+                Some(&balance(1_000)),
+                &payouts
+            )),
+            Some(RefundReason::ProofMismatch)
+        );
+
+        // A message for another nonce, or no coin flip message at all, can't be completed
+        let mut malformed = entry.clone();
+        malformed.nonce = "bet_0000000000000002".to_string();
+        assert_eq!(JournaledBet::from_entry(&malformed), None);
+        malformed.message = BASE64.encode(b"zkcasino-vrf-v1:bet:player:lots:true");
+        assert_eq!(JournaledBet::from_entry(&malformed), None);
+        assert_eq!(
+            refunded(decide(
+                &malformed,
+                None,
+                &vrf,
+                Some(&balance(1_000)),
+                &payouts
+            )),
+            Some(RefundReason::MalformedEntry)
+        );
+    }
+
+    #[test]
+    fn test_recovery_log_persists_decisions() {
+        let path =
+            std::env::temp_dir().join(format!("zkcasino-recovery-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = RecoveryLog::open(Some(path.clone())).unwrap();
+        let decision = |bet_id: &str, player: &str, outcome| RecoveryDecision {
+            bet_id: bet_id.to_string(),
+            journal_sequence: 0,
+            player_address: Some(player.to_string()),
+            amount: Some(1_000),
+            outcome,
+            decided_at: Utc::now(),
+        };
+        log.record(decision(
+            "bet_2",
+            PLAYER,
+            RecoveryOutcome::Completed {
+                result: true,
+                won: true,
+                payout: 1_960,
+            },
+        ))
+        .unwrap();
+        log.record(decision(
+            "bet_1",
+            "other",
+            RecoveryOutcome::Refunded {
+                reason: RefundReason::KeyRotated,
+            },
+        ))
+        .unwrap();
+
+        let reopened = RecoveryLog::open(Some(path.clone())).unwrap();
+        assert!(reopened.contains("bet_1"));
+        assert_eq!(reopened.get("bet_2"), log.get("bet_2"));
+        let ids: Vec<String> = reopened
+            .list(None)
+            .into_iter()
+            .map(|decision| decision.bet_id)
+            .collect();
+        assert_eq!(ids, vec!["bet_1", "bet_2"]);
+        assert_eq!(reopened.list(Some(PLAYER)).len(), 1);
+
+        let json = serde_json::to_value(reopened.get("bet_1").unwrap()).unwrap();
+        assert_eq!(json["decision"], "refunded");
+        assert_eq!(json["reason"], "key_rotated");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,638 @@
+// Bet verification for ZK Casino clients
+// A player shouldn't have to take the sequencer's word for a bet. What it takes to check
+// one is public: the VRF proof the outcome was derived from, and the bet's leaf in the
+// bets root the verifier program records when it settles the bet's batch. Once a bet is
+// batched, GET /v1/bet/:bet_id/fairness serves both as a fairness bundle, signed with
+// the sequencer key like the other lookups when SIGNED_RESPONSES is on. The helpers here
+// check each part on its own. BetVerifier::verify_bet_end_to_end fetches the bundle and
+// the batch record and checks them all: the signature against the sequencer key the
+// client trusts, the VRF proof against a VRF key it trusts, and the bet's leaf against
+// the bets root of the batch as the verifier program recorded it.
+
+use anyhow::{anyhow, Result};
+use axum::async_trait;
+use axum::http::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use verifier::optimistic::{self, BatchLeaf, LeafProof};
+use verifier::BatchRecordStatus;
+
+use crate::bet_id::BetId;
+use crate::response_signing::{
+    ResponseSignature, SIGNATURE_HEADER, SIGNED_AT_HEADER, SIGNER_HEADER,
+};
+use crate::solana::{BetSettlement, OnchainBatchRecord, SolanaClient};
+use crate::state_root;
+use crate::vrf::{verify_coinflip, CoinflipInput};
+use crate::BetResponse;
+
+/// Path a bet's fairness bundle is served, and signed, at
+#[allow(dead_code)]
+pub fn fairness_path(bet_id: &str) -> String {
+    format!("/v1/bet/{}/fairness", bet_id)
+}
+
+/// A bet's leaf in its batch's bets tree, as the verifier program builds it, with the
+/// sibling path up to the bets root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchInclusion {
+    pub batch_id: u64,
+    pub bet_count: u32,
+    pub index: u32,
+    pub bet: BetSettlement,
+    pub house_delta: i64,  // Stake minus payout over the batch's bets 0..=index
+    pub path: Vec<String>, // Base58 sibling hashes, leaf to root
+}
+
+impl BatchInclusion {
+    /// Inclusion of the bet at `index` of a batch's on-chain bet list; None past its end
+    pub fn new(batch_id: u64, bets: &[BetSettlement], index: u32) -> Option<Self> {
+        let mut house_delta = 0i64;
+        let mut leaves = Vec::with_capacity(bets.len());
+        for (position, bet) in bets.iter().enumerate() {
+            house_delta = house_delta.checked_add(bet.bet_amount as i64 - bet.payout as i64)?;
+            leaves.push(BatchLeaf {
+                index: position as u32,
+                bet: bet.to_program(),
+                house_delta,
+            });
+        }
+        let leaf = leaves.get(index as usize)?;
+        let hashes: Vec<[u8; 32]> = leaves.iter().map(BatchLeaf::hash).collect();
+        Some(Self {
+            batch_id,
+            bet_count: bets.len() as u32,
+            index,
+            bet: bets[index as usize].clone(),
+            house_delta: leaf.house_delta,
+            path: sibling_path(&hashes, index as usize)
+                .into_iter()
+                .map(|sibling| Hash::new_from_array(sibling).to_string())
+                .collect(),
+        })
+    }
+
+    /// The inclusion in the verifier program's form
+    pub fn leaf_proof(&self) -> Result<LeafProof> {
+        let path = self
+            .path
+            .iter()
+            .map(|sibling| {
+                Hash::from_str(sibling)
+                    .map(|hash| hash.to_bytes())
+                    .map_err(|e| anyhow!("Invalid path sibling: {}", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(LeafProof {
+            leaf: BatchLeaf {
+                index: self.index,
+                bet: self.bet.to_program(),
+                house_delta: self.house_delta,
+            },
+            path,
+        })
+    }
+
+    /// Whether the leaf is `bet` at the place its settlement link names
+    pub fn matches(&self, bet: &BetResponse) -> Result<bool> {
+        let bet_id = BetId::from_str(&bet.bet_id)
+            .map_err(|e| anyhow!("Invalid bet ID {}: {}", bet.bet_id, e))?
+            .as_u64();
+        let settled = &self.bet;
+        Ok(settled.bet_id == bet_id
+            && settled.user == state_root::player_key(&bet.player_address)
+            && settled.bet_amount == bet.amount
+            && settled.payout == bet.payout
+            && (settled.outcome == settled.user_guess) == bet.won
+            && bet.settlement.as_ref().is_some_and(|link| {
+                link.batch_id == self.batch_id && link.onchain_index == self.index
+            }))
+    }
+
+    /// Whether the leaf is `bet` and sits under the bets root of the batch's on-chain record
+    pub fn verify(&self, bet: &BetResponse, record: &OnchainBatchRecord) -> Result<bool> {
+        Ok(self.matches(bet)?
+            && record.batch_id == self.batch_id
+            && record.bet_count == self.bet_count
+            && self
+                .leaf_proof()?
+                .verify_root(&record.bets_root, record.bet_count))
+    }
+}
+
+/// Sibling hashes from the leaf at `index` up to optimistic::bets_root(leaves). Odd nodes
+/// carry up within a subtree just as they do in the whole tree, so each sibling is the
+/// bets root of the leaf range it covers.
+fn sibling_path(leaves: &[[u8; 32]], index: usize) -> Vec<[u8; 32]> {
+    let mut path = Vec::new();
+    let mut height = 0;
+    while leaves.len() > 1 << height {
+        let start = ((index >> height) ^ 1) << height;
+        if start < leaves.len() {
+            let end = (start + (1 << height)).min(leaves.len());
+            path.push(optimistic::bets_root(&leaves[start..end]));
+        }
+        height += 1;
+    }
+    path
+}
+
+/// Everything needed to check a batched bet, served at fairness_path
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairnessBundle {
+    pub bet: BetResponse,       // With the VRF proof of its outcome
+    pub vrf_public_key: String, // Base58 VRF key the proof was made with
+    pub batch: BatchInclusion,
+}
+
+/// Check a signed response against the sequencer key the client trusts
+#[allow(dead_code)]
+pub fn verify_receipt_signature(
+    signature: &ResponseSignature,
+    sequencer: &Pubkey,
+    path: &str,
+    body: &[u8],
+) -> bool {
+    signature.signer == *sequencer && signature.verify(path, body)
+}
+
+/// Check a bet's VRF proof against a VRF key. False when the proof is missing or
+/// invalid, or proves an outcome other than the bet's.
+#[allow(dead_code)]
+pub fn verify_vrf_proof(vrf_pubkey: &Pubkey, bet: &BetResponse) -> Result<bool> {
+    let Some(proof) = &bet.vrf_proof else {
+        return Ok(false);
+    };
+    let proof =
+        Signature::from_str(proof).map_err(|e| anyhow!("Invalid VRF proof encoding: {}", e))?;
+    let input = CoinflipInput {
+        bet_id: &bet.bet_id,
+        player: &bet.player_address,
+        amount: bet.amount,
+        guess: bet.guess,
+    };
+    Ok(verify_coinflip(vrf_pubkey, &input, &proof)
+        .is_some_and(|result| result == bet.result && bet.won == (result == bet.guess)))
+}
+
+#[allow(dead_code)]
+#[derive(Error, Debug)]
+pub enum VerificationError {
+    #[error("Fairness bundle is not signed by the trusted sequencer key")]
+    InvalidSignature,
+    #[error("Fairness bundle is for bet {0}")]
+    WrongBet(String),
+    #[error("VRF key {0} is not trusted")]
+    UntrustedVrfKey(String),
+    #[error("VRF proof doesn't prove the bet's outcome")]
+    InvalidVrfProof,
+    #[error("Bet is not in batch {0}")]
+    NotInBatch(u64),
+    #[error("Batch {0} was shown invalid on-chain")]
+    BatchChallenged(u64),
+    #[error(transparent)]
+    Source(#[from] anyhow::Error),
+}
+
+/// A bet that passed verify_bet_end_to_end
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct VerifiedBet {
+    pub bet: BetResponse,
+    pub batch_id: u64,
+    pub settled: bool, // Batch settled on-chain; false while pending or challengeable
+}
+
+/// Where verify_bet_end_to_end reads from, so it can run against mocks in tests
+#[allow(dead_code)]
+#[async_trait]
+pub trait FairnessSource: Send + Sync {
+    /// A bet's fairness bundle as served, with its response signature
+    async fn fairness_bundle(&self, bet_id: &str) -> Result<(Vec<u8>, ResponseSignature)>;
+    /// A batch's on-chain record; None until the batch is submitted
+    async fn batch_record(&self, batch_id: u64) -> Result<Option<OnchainBatchRecord>>;
+}
+
+/// Reads bundles from a sequencer's API and batch records from Solana
+#[allow(dead_code)]
+pub struct HttpFairnessSource {
+    base_url: String,
+    client: reqwest::Client,
+    solana: Arc<SolanaClient>,
+}
+
+#[allow(dead_code)]
+impl HttpFairnessSource {
+    pub fn new(base_url: &str, solana: Arc<SolanaClient>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            solana,
+        }
+    }
+}
+
+#[async_trait]
+impl FairnessSource for HttpFairnessSource {
+    async fn fairness_bundle(&self, bet_id: &str) -> Result<(Vec<u8>, ResponseSignature)> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, fairness_path(bet_id)))
+            .send()
+            .await?
+            .error_for_status()?;
+        // Copied over by name: reqwest's header types aren't axum's
+        let mut headers = HeaderMap::new();
+        for name in [SIGNATURE_HEADER, SIGNER_HEADER, SIGNED_AT_HEADER] {
+            if let Some(value) = response.headers().get(name.as_str()) {
+                headers.insert(name, HeaderValue::from_bytes(value.as_bytes())?);
+            }
+        }
+        let signature = ResponseSignature::from_headers(&headers)?;
+        Ok((response.bytes().await?.to_vec(), signature))
+    }
+
+    async fn batch_record(&self, batch_id: u64) -> Result<Option<OnchainBatchRecord>> {
+        self.solana.get_batch_record(batch_id).await
+    }
+}
+
+/// Checks bets against the keys a client trusts
+#[allow(dead_code)]
+pub struct BetVerifier {
+    sequencer: Pubkey,
+    vrf_keys: Vec<Pubkey>, // Current and rotated-out VRF keys, as listed at /v1/keys
+    source: Arc<dyn FairnessSource>,
+}
+
+#[allow(dead_code)]
+impl BetVerifier {
+    pub fn new(sequencer: Pubkey, vrf_keys: Vec<Pubkey>, source: Arc<dyn FairnessSource>) -> Self {
+        Self {
+            sequencer,
+            vrf_keys,
+            source,
+        }
+    }
+
+    /// Fetch a bet's fairness bundle and check its signature, the VRF proof of the
+    /// outcome and the bet's inclusion in its batch's on-chain bets root
+    pub async fn verify_bet_end_to_end(
+        &self,
+        bet_id: &str,
+    ) -> Result<VerifiedBet, VerificationError> {
+        let (body, signature) = self.source.fairness_bundle(bet_id).await?;
+        if !verify_receipt_signature(&signature, &self.sequencer, &fairness_path(bet_id), &body) {
+            return Err(VerificationError::InvalidSignature);
+        }
+        let bundle: FairnessBundle =
+            serde_json::from_slice(&body).map_err(|e| anyhow!("Invalid fairness bundle: {}", e))?;
+        if bundle.bet.bet_id != bet_id {
+            return Err(VerificationError::WrongBet(bundle.bet.bet_id));
+        }
+
+        let vrf_pubkey = Pubkey::from_str(&bundle.vrf_public_key)
+            .ok()
+            .filter(|key| self.vrf_keys.contains(key))
+            .ok_or(VerificationError::UntrustedVrfKey(bundle.vrf_public_key))?;
+        if !verify_vrf_proof(&vrf_pubkey, &bundle.bet)? {
+            return Err(VerificationError::InvalidVrfProof);
+        }
+
+        // Until the batch is on-chain there is no root to check the path against yet
+        let batch_id = bundle.batch.batch_id;
+        let settled = match self.source.batch_record(batch_id).await? {
+            Some(record) => {
+                if !bundle.batch.verify(&bundle.bet, &record)? {
+                    return Err(VerificationError::NotInBatch(batch_id));
+                }
+                match record.status {
+                    BatchRecordStatus::Settled => true,
+                    BatchRecordStatus::Optimistic => false,
+                    BatchRecordStatus::Challenged => {
+                        return Err(VerificationError::BatchChallenged(batch_id))
+                    }
+                }
+            }
+            None if bundle.batch.matches(&bundle.bet)? => false,
+            None => return Err(VerificationError::NotInBatch(batch_id)),
+        };
+        Ok(VerifiedBet {
+            bet: bundle.bet,
+            batch_id,
+            settled,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bet_id::BetIdGenerator;
+    use crate::database::{BetSettlementLink, BetSettlementStatus};
+    use crate::vrf::prove_coinflip;
+    use chrono::Utc;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use std::collections::HashMap;
+
+    const BATCH_ID: u64 = 9;
+
+    /// Bets as the sequencer settles them, with their receipts
+    fn batch(vrf: &Keypair, count: usize) -> (Vec<BetSettlement>, Vec<BetResponse>) {
+        let ids = BetIdGenerator::new(1);
+        let player = Keypair::new().pubkey().to_string();
+        (0..count)
+            .map(|index| {
+                let bet_id = ids.next_id();
+                let bet_id_string = bet_id.to_string();
+                let input = CoinflipInput {
+                    bet_id: &bet_id_string,
+                    player: &player,
+                    amount: 1000 + index as u64,
+                    guess: true,
+                };
+                let (result, proof) = prove_coinflip(vrf, &input);
+                let payout = if result { 2 * input.amount } else { 0 };
+                let settlement = BetSettlement {
+                    bet_id: bet_id.as_u64(),
+                    user: state_root::player_key(&player),
+                    bet_amount: input.amount,
+                    user_guess: 1,
+                    outcome: result as u8,
+                    payout,
+                };
+                let receipt = BetResponse {
+                    bet_id: bet_id_string.clone(),
+                    player_address: player.clone(),
+                    amount: input.amount,
+                    guess: true,
+                    result,
+                    won: result,
+                    payout,
+                    timestamp: Utc::now(),
+                    settlement: Some(BetSettlementLink {
+                        batch_id: BATCH_ID,
+                        onchain_index: index as u32,
+                        status: BetSettlementStatus::Confirmed,
+                        transaction_signature: None,
+                        updated_at: Utc::now(),
+                    }),
+                    vrf_proof: Some(proof.to_string()),
+                };
+                (settlement, receipt)
+            })
+            .unzip()
+    }
+
+    /// Batch record as the verifier program writes it
+    fn record(bets: &[BetSettlement], status: BatchRecordStatus) -> OnchainBatchRecord {
+        let mut house_delta = 0;
+        let leaves: Vec<[u8; 32]> = (bets.iter().enumerate())
+            .map(|(index, bet)| {
+                house_delta += bet.bet_amount as i64 - bet.payout as i64;
+                BatchLeaf {
+                    index: index as u32,
+                    bet: bet.to_program(),
+                    house_delta,
+                }
+                .hash()
+            })
+            .collect();
+        OnchainBatchRecord {
+            batch_id: BATCH_ID,
+            sequencer: Pubkey::new_unique(),
+            proof_hash: [0; 32],
+            state_root: [0; 32],
+            bet_count: bets.len() as u32,
+            house_delta,
+            slot: 1,
+            status,
+            bets_root: optimistic::bets_root(&leaves),
+        }
+    }
+
+    struct MockSource {
+        sequencer: Keypair,
+        bundles: HashMap<String, Vec<u8>>,
+        record: Option<OnchainBatchRecord>,
+    }
+
+    #[async_trait]
+    impl FairnessSource for MockSource {
+        async fn fairness_bundle(&self, bet_id: &str) -> Result<(Vec<u8>, ResponseSignature)> {
+            let body = self
+                .bundles
+                .get(bet_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("Bet not found"))?;
+            let signature =
+                ResponseSignature::sign(&self.sequencer, &fairness_path(bet_id), 1, &body);
+            Ok((body, signature))
+        }
+
+        async fn batch_record(&self, _batch_id: u64) -> Result<Option<OnchainBatchRecord>> {
+            Ok(self.record.clone())
+        }
+    }
+
+    fn bundles(
+        vrf: &Keypair,
+        bets: &[BetSettlement],
+        receipts: &[BetResponse],
+    ) -> HashMap<String, Vec<u8>> {
+        receipts
+            .iter()
+            .enumerate()
+            .map(|(index, receipt)| {
+                let bundle = FairnessBundle {
+                    bet: receipt.clone(),
+                    vrf_public_key: vrf.pubkey().to_string(),
+                    batch: BatchInclusion::new(BATCH_ID, bets, index as u32).unwrap(),
+                };
+                (receipt.bet_id.clone(), serde_json::to_vec(&bundle).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inclusion_paths_reach_bets_root() {
+        let vrf = Keypair::new();
+        for count in 1..=9 {
+            let (bets, receipts) = batch(&vrf, count);
+            let record = record(&bets, BatchRecordStatus::Settled);
+            for (index, receipt) in receipts.iter().enumerate() {
+                let inclusion = BatchInclusion::new(BATCH_ID, &bets, index as u32).unwrap();
+                assert!(inclusion.verify(receipt, &record).unwrap());
+            }
+            assert!(BatchInclusion::new(BATCH_ID, &bets, count as u32).is_none());
+        }
+
+        // A leaf at another bet's place, or under another batch's root, doesn't verify
+        let (bets, receipts) = batch(&vrf, 5);
+        let record = record(&bets, BatchRecordStatus::Settled);
+        let inclusion = BatchInclusion::new(BATCH_ID, &bets, 2).unwrap();
+        assert!(!inclusion.verify(&receipts[3], &record).unwrap());
+        let (other_bets, _) = batch(&vrf, 5);
+        let other = self::record(&other_bets, BatchRecordStatus::Settled);
+        assert!(!inclusion.verify(&receipts[2], &other).unwrap());
+
+        // Nor does a receipt whose payout differs from the settled one
+        let mut receipt = receipts[2].clone();
+        receipt.payout += 1;
+        assert!(!inclusion.verify(&receipt, &record).unwrap());
+    }
+
+    #[test]
+    fn test_vrf_and_receipt_checks() {
+        let vrf = Keypair::new();
+        let (_, receipts) = batch(&vrf, 1);
+        let receipt = &receipts[0];
+        assert!(verify_vrf_proof(&vrf.pubkey(), receipt).unwrap());
+        assert!(!verify_vrf_proof(&Keypair::new().pubkey(), receipt).unwrap());
+
+        // The proof commits to the outcome; a receipt claiming the other one fails
+        let mut flipped = receipt.clone();
+        flipped.result = !flipped.result;
+        flipped.won = !flipped.won;
+        assert!(!verify_vrf_proof(&vrf.pubkey(), &flipped).unwrap());
+        let mut unproven = receipt.clone();
+        unproven.vrf_proof = None;
+        assert!(!verify_vrf_proof(&vrf.pubkey(), &unproven).unwrap());
+
+        let sequencer = Keypair::new();
+        let body = serde_json::to_vec(receipt).unwrap();
+        let path = format!("/v1/bet/{}", receipt.bet_id);
+        let signature = ResponseSignature::sign(&sequencer, &path, 1, &body);
+        assert!(verify_receipt_signature(
+            &signature,
+            &sequencer.pubkey(),
+            &path,
+            &body
+        ));
+        assert!(!verify_receipt_signature(
+            &signature,
+            &Keypair::new().pubkey(),
+            &path,
+            &body
+        ));
+        assert!(!verify_receipt_signature(
+            &signature,
+            &sequencer.pubkey(),
+            &path,
+            b"{}"
+        ));
+    }
+
+    fn verifier(
+        vrf: &Keypair,
+        sequencer: &Keypair,
+        bundles: HashMap<String, Vec<u8>>,
+        record: Option<OnchainBatchRecord>,
+    ) -> BetVerifier {
+        BetVerifier::new(
+            sequencer.pubkey(),
+            vec![vrf.pubkey()],
+            Arc::new(MockSource {
+                sequencer: Keypair::from_bytes(&sequencer.to_bytes()).unwrap(),
+                bundles,
+                record,
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_verify_bet_end_to_end() {
+        let (vrf, sequencer) = (Keypair::new(), Keypair::new());
+        let (bets, receipts) = batch(&vrf, 3);
+        let settled = Some(record(&bets, BatchRecordStatus::Settled));
+        let verifier = verifier(&vrf, &sequencer, bundles(&vrf, &bets, &receipts), settled);
+        for receipt in &receipts {
+            let verified = verifier
+                .verify_bet_end_to_end(&receipt.bet_id)
+                .await
+                .unwrap();
+            assert_eq!(verified.bet.payout, receipt.payout);
+            assert_eq!(verified.batch_id, BATCH_ID);
+            assert!(verified.settled);
+        }
+
+        // Signed by a key other than the trusted sequencer's
+        let impostor = self::verifier(&vrf, &Keypair::new(), bundles(&vrf, &bets, &receipts), None);
+        let impostor = BetVerifier {
+            sequencer: sequencer.pubkey(),
+            ..impostor
+        };
+        assert!(matches!(
+            impostor.verify_bet_end_to_end(&receipts[0].bet_id).await,
+            Err(VerificationError::InvalidSignature)
+        ));
+
+        // Outcomes proven with a VRF key the client doesn't trust
+        let untrusted = self::verifier(
+            &Keypair::new(),
+            &sequencer,
+            bundles(&vrf, &bets, &receipts),
+            None,
+        );
+        assert!(matches!(
+            untrusted.verify_bet_end_to_end(&receipts[0].bet_id).await,
+            Err(VerificationError::UntrustedVrfKey(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_bet_end_to_end_against_chain() {
+        let (vrf, sequencer) = (Keypair::new(), Keypair::new());
+        let (bets, receipts) = batch(&vrf, 3);
+        let bet_id = &receipts[1].bet_id;
+
+        // Not on-chain yet, or still challengeable: verified but not settled
+        for record in [None, Some(record(&bets, BatchRecordStatus::Optimistic))] {
+            let verified = verifier(&vrf, &sequencer, bundles(&vrf, &bets, &receipts), record)
+                .verify_bet_end_to_end(bet_id)
+                .await
+                .unwrap();
+            assert!(!verified.settled);
+        }
+
+        // A batch shown invalid on-chain
+        let challenged = Some(record(&bets, BatchRecordStatus::Challenged));
+        assert!(matches!(
+            verifier(
+                &vrf,
+                &sequencer,
+                bundles(&vrf, &bets, &receipts),
+                challenged
+            )
+            .verify_bet_end_to_end(bet_id)
+            .await,
+            Err(VerificationError::BatchChallenged(BATCH_ID))
+        ));
+
+        // A batch that settled other bets than the bundle claims
+        let (other_bets, _) = batch(&vrf, 3);
+        let other = Some(record(&other_bets, BatchRecordStatus::Settled));
+        assert!(matches!(
+            verifier(&vrf, &sequencer, bundles(&vrf, &bets, &receipts), other)
+                .verify_bet_end_to_end(bet_id)
+                .await,
+            Err(VerificationError::NotInBatch(BATCH_ID))
+        ));
+
+        // A receipt that doesn't match the bet as batched
+        let mut tampered = receipts.clone();
+        tampered[1].payout += 1;
+        assert!(matches!(
+            verifier(&vrf, &sequencer, bundles(&vrf, &bets, &tampered), None)
+                .verify_bet_end_to_end(bet_id)
+                .await,
+            Err(VerificationError::NotInBatch(BATCH_ID))
+        ));
+    }
+}
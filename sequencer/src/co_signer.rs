@@ -0,0 +1,443 @@
+// Settlement co-signing for ZK Casino
+// Once the verifier has a co-signer configured (AdminAction::SetCoSigner), a settlement
+// only lands with two signatures: the sequencer's and an independent watcher's. The
+// sequencer sends each prepared settlement transaction to the watcher (`sequencer
+// co-signer`, run on its own host with its own key). The watcher re-checks the batch
+// signature and the Groth16 proof, and checks that the transaction does nothing but
+// settle that batch, before adding its signature. A compromised sequencer host can then
+// no longer settle batches on its own.
+
+use anchor_lang::InstructionData;
+use anyhow::{anyhow, bail, ensure, Result};
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::Transaction;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::solana::BatchSettlementData;
+use crate::ErrorResponse;
+
+/// Sequencer-side co-signer settings
+#[derive(Debug, Clone)]
+pub struct CoSignerConfig {
+    pub url: String,
+    pub pubkey: Pubkey, // Key the verifier expects as co-signer
+    pub timeout: Duration,
+}
+
+impl CoSignerConfig {
+    /// Load from environment; None unless CO_SIGNER_URL is set.
+    /// (CO_SIGNER_PUBKEY, CO_SIGNER_TIMEOUT_MS)
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(url) = std::env::var("CO_SIGNER_URL") else {
+            return Ok(None);
+        };
+        let pubkey = std::env::var("CO_SIGNER_PUBKEY")
+            .map_err(|_| anyhow!("CO_SIGNER_PUBKEY is required with CO_SIGNER_URL"))?
+            .parse()
+            .map_err(|e| anyhow!("Invalid CO_SIGNER_PUBKEY: {}", e))?;
+        let timeout_ms = match std::env::var("CO_SIGNER_TIMEOUT_MS") {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| anyhow!("Invalid CO_SIGNER_TIMEOUT_MS: {}", e))?,
+            Err(_) => 10_000, // Leaves the watcher time to check the proof
+        };
+        Ok(Some(Self {
+            url,
+            pubkey,
+            timeout: Duration::from_millis(timeout_ms),
+        }))
+    }
+}
+
+/// A settlement transaction for the watcher to review
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoSignRequest {
+    pub batch_data: BatchSettlementData, // Signed by the sequencer
+    pub proof: String,                   // Base64
+    pub transaction: String,             // Base64 bincode, signed by the sequencer only
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoSignResponse {
+    pub co_signer: String,
+    pub signature: String, // Over the transaction message
+}
+
+/// Asks the watcher to countersign settlement transactions
+pub struct CoSignerClient {
+    config: CoSignerConfig,
+    client: reqwest::Client,
+}
+
+impl CoSignerClient {
+    pub fn new(config: CoSignerConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| anyhow!("Failed to build co-signer HTTP client: {}", e))?;
+        Ok(Self { config, client })
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.config.pubkey
+    }
+
+    /// Have the watcher review a sequencer-signed settlement transaction and add its
+    /// signature to it
+    pub async fn co_sign(
+        &self,
+        batch_data: &BatchSettlementData,
+        proof: &[u8],
+        transaction: &mut Transaction,
+    ) -> Result<()> {
+        let request = CoSignRequest {
+            batch_data: batch_data.clone(),
+            proof: BASE64.encode(proof),
+            transaction: BASE64.encode(bincode::serialize(transaction)?),
+        };
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1/co-sign",
+                self.config.url.trim_end_matches('/')
+            ))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Co-signer unreachable: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!(
+                "Co-signer refused batch {}: {} {}",
+                batch_data.batch_id,
+                status,
+                body
+            );
+        }
+        let response: CoSignResponse = response.json().await?;
+        let signature = Signature::from_str(&response.signature)
+            .map_err(|e| anyhow!("Invalid co-signer signature: {}", e))?;
+        add_co_signature(transaction, &self.config.pubkey, signature)
+    }
+}
+
+/// Put the co-signer's signature in its slot, after checking it signs this message
+pub fn add_co_signature(
+    transaction: &mut Transaction,
+    co_signer: &Pubkey,
+    signature: Signature,
+) -> Result<()> {
+    let message = &transaction.message;
+    let index = message.account_keys[..message.header.num_required_signatures as usize]
+        .iter()
+        .position(|key| key == co_signer)
+        .ok_or_else(|| anyhow!("Transaction does not require co-signer {}", co_signer))?;
+    ensure!(
+        signature.verify(co_signer.as_ref(), &transaction.message_data()),
+        "Co-signer signature does not match the transaction"
+    );
+    transaction.signatures[index] = signature;
+    Ok(())
+}
+
+/// The watcher: reviews settlements and signs the ones that check out
+pub struct CoSignerService {
+    keypair: Keypair,
+    verifier_program_id: Pubkey,
+    verifying_key: Vec<u8>, // Groth16 key proofs are checked against, as uploaded on-chain
+}
+
+impl CoSignerService {
+    pub fn new(keypair: Keypair, verifier_program_id: Pubkey, verifying_key: Vec<u8>) -> Self {
+        Self {
+            keypair,
+            verifier_program_id,
+            verifying_key,
+        }
+    }
+
+    /// Load from environment (CO_SIGNER_KEYPAIR_PATH, CO_SIGNER_VERIFYING_KEY_PATH)
+    pub fn from_env(verifier_program_id: &str) -> Result<Self> {
+        let keypair_path = std::env::var("CO_SIGNER_KEYPAIR_PATH")
+            .map_err(|_| anyhow!("CO_SIGNER_KEYPAIR_PATH is required"))?;
+        let keypair = read_keypair_file(&keypair_path).map_err(|e| {
+            anyhow!(
+                "Failed to read co-signer keypair from {}: {}",
+                keypair_path,
+                e
+            )
+        })?;
+        let verifying_key_path = std::env::var("CO_SIGNER_VERIFYING_KEY_PATH")
+            .map_err(|_| anyhow!("CO_SIGNER_VERIFYING_KEY_PATH is required"))?;
+        let verifying_key = std::fs::read(&verifying_key_path).map_err(|e| {
+            anyhow!(
+                "Failed to read verifying key from {}: {}",
+                verifying_key_path,
+                e
+            )
+        })?;
+        let verifier_program_id = Pubkey::from_str(verifier_program_id)
+            .map_err(|e| anyhow!("Invalid verifier program ID: {}", e))?;
+        Ok(Self::new(keypair, verifier_program_id, verifying_key))
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// Re-check a settlement and sign its transaction
+    pub fn review(&self, request: &CoSignRequest) -> Result<Signature> {
+        let transaction: Transaction = bincode::deserialize(&BASE64.decode(&request.transaction)?)
+            .map_err(|e| anyhow!("Invalid transaction: {}", e))?;
+        let proof = BASE64.decode(&request.proof)?;
+        self.check_transaction(&transaction, &request.batch_data, &proof)?;
+        // Proving is binding: finalize_and_verify settles the bets staged on-chain, and
+        // this proof only verifies against the batch it was made for
+        verifier::verify_batch_proof(
+            &self.verifying_key,
+            &request.batch_data.to_program(),
+            &proof,
+        )
+        .map_err(|e| anyhow!("Proof does not verify: {}", e))?;
+        Ok(self.keypair.sign_message(&transaction.message_data()))
+    }
+
+    /// Everything but the proof: the sequencer signed both the batch and the transaction,
+    /// and the transaction only settles that batch
+    fn check_transaction(
+        &self,
+        transaction: &Transaction,
+        batch_data: &BatchSettlementData,
+        proof: &[u8],
+    ) -> Result<()> {
+        let message = &transaction.message;
+        let signers = &message.account_keys[..message.header.num_required_signatures as usize];
+        let sequencer = *signers
+            .first()
+            .ok_or_else(|| anyhow!("Transaction has no fee payer"))?;
+        ensure!(
+            signers.contains(&self.pubkey()),
+            "Transaction does not require the co-signer"
+        );
+        ensure!(
+            transaction.signatures[0].verify(sequencer.as_ref(), &transaction.message_data()),
+            "Transaction is not signed by its fee payer"
+        );
+        ensure!(
+            batch_data
+                .sequencer_signature
+                .verify(sequencer.as_ref(), &batch_data.batch_hash()),
+            "Batch is not signed by the sequencer"
+        );
+
+        let circuit_id = batch_data.circuit_id();
+        let expected = [
+            verifier::instruction::VerifyAndSettle {
+                circuit_id,
+                batch_data: batch_data.to_program(),
+                proof: proof.to_vec(),
+            }
+            .data(),
+            verifier::instruction::FinalizeAndVerify {
+                circuit_id,
+                proof: proof.to_vec(),
+            }
+            .data(),
+        ];
+        let mut settlements = 0;
+        for instruction in &message.instructions {
+            let program_id = message
+                .account_keys
+                .get(instruction.program_id_index as usize)
+                .ok_or_else(|| anyhow!("Instruction program out of range"))?;
+            if *program_id == self.verifier_program_id {
+                ensure!(
+                    expected.contains(&instruction.data),
+                    "Transaction carries a verifier instruction other than this batch's settlement"
+                );
+                settlements += 1;
+            } else if *program_id != solana_sdk::ed25519_program::id() {
+                bail!("Transaction calls unexpected program {}", program_id);
+            }
+        }
+        ensure!(
+            settlements == 1,
+            "Transaction must settle exactly one batch"
+        );
+        Ok(())
+    }
+}
+
+async fn co_sign_handler(
+    State(service): State<Arc<CoSignerService>>,
+    Json(request): Json<CoSignRequest>,
+) -> Result<Json<CoSignResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let batch_id = request.batch_data.batch_id;
+    // Proof verification is CPU bound
+    let result = tokio::task::spawn_blocking({
+        let service = service.clone();
+        move || service.review(&request)
+    })
+    .await
+    .map_err(|e| anyhow!("Review task failed: {}", e))
+    .and_then(|result| result);
+
+    match result {
+        Ok(signature) => {
+            info!("Co-signed settlement of batch {}", batch_id);
+            Ok(Json(CoSignResponse {
+                co_signer: service.pubkey().to_string(),
+                signature: signature.to_string(),
+            }))
+        }
+        Err(e) => {
+            warn!("Refused to co-sign batch {}: {}", batch_id, e);
+            Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+pub fn create_router(service: Arc<CoSignerService>) -> Router {
+    Router::new()
+        .route("/v1/co-sign", post(co_sign_handler))
+        .with_state(service)
+}
+
+/// Serve the co-sign endpoint until shut down
+pub async fn serve(service: Arc<CoSignerService>, port: u16) -> Result<()> {
+    // Reached by the sequencer from its own host
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!(
+        "Settlement co-signer {} listening on {}",
+        service.pubkey(),
+        addr
+    );
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, create_router(service)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solana::{ed25519_signature_instruction, BetSettlement};
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+
+    fn batch(sequencer: &Keypair) -> BatchSettlementData {
+        let mut batch = BatchSettlementData {
+            batch_id: 7,
+            sequencer_nonce: 7,
+            game_id: 0,
+            prev_state_root: [0; 32],
+            new_state_root: [1; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [2; 32],
+            bets: vec![BetSettlement {
+                bet_id: 1,
+                user: Pubkey::new_unique(),
+                bet_amount: 1_000,
+                user_guess: 1,
+                outcome: 1,
+                payout: 2_000,
+            }],
+            sequencer_signature: Signature::default(),
+        };
+        batch.sign(sequencer);
+        batch
+    }
+
+    fn settlement_transaction(
+        service: &CoSignerService,
+        sequencer: &Keypair,
+        batch: &BatchSettlementData,
+        proof: &[u8],
+    ) -> Transaction {
+        let settle = Instruction {
+            program_id: service.verifier_program_id,
+            accounts: vec![
+                AccountMeta::new(sequencer.pubkey(), true),
+                AccountMeta::new_readonly(service.pubkey(), true),
+            ],
+            data: verifier::instruction::VerifyAndSettle {
+                circuit_id: batch.circuit_id(),
+                batch_data: batch.to_program(),
+                proof: proof.to_vec(),
+            }
+            .data(),
+        };
+        let check = ed25519_signature_instruction(
+            &sequencer.pubkey(),
+            &batch.sequencer_signature,
+            &batch.batch_hash(),
+        );
+        let mut transaction =
+            Transaction::new_with_payer(&[check, settle], Some(&sequencer.pubkey()));
+        transaction.partial_sign(&[sequencer], solana_sdk::hash::Hash::new_unique());
+        transaction
+    }
+
+    #[test]
+    fn test_review_settlement_transaction() {
+        let service = CoSignerService::new(Keypair::new(), Pubkey::new_unique(), vec![]);
+        let sequencer = Keypair::new();
+        let batch = batch(&sequencer);
+        let proof = vec![9u8; 256];
+        let mut transaction = settlement_transaction(&service, &sequencer, &batch, &proof);
+        assert!(service
+            .check_transaction(&transaction, &batch, &proof)
+            .is_ok());
+
+        // Only this batch's settlement with this proof gets signed
+        let mut other = batch.clone();
+        other.bets[0].payout = 1_000_000;
+        other.sign(&sequencer);
+        assert!(service
+            .check_transaction(&transaction, &other, &proof)
+            .is_err());
+        assert!(service
+            .check_transaction(&transaction, &batch, &[8u8; 256])
+            .is_err());
+        let mut forged = batch.clone();
+        forged.sign(&Keypair::new());
+        assert!(service
+            .check_transaction(&transaction, &forged, &proof)
+            .is_err());
+        let stranger = CoSignerService::new(Keypair::new(), service.verifier_program_id, vec![]);
+        assert!(stranger
+            .check_transaction(&transaction, &batch, &proof)
+            .is_err());
+
+        // A proof that doesn't verify is refused even in an otherwise valid transaction
+        let request = CoSignRequest {
+            batch_data: batch.clone(),
+            proof: BASE64.encode(&proof),
+            transaction: BASE64.encode(bincode::serialize(&transaction).unwrap()),
+        };
+        assert!(service.review(&request).is_err());
+
+        // The co-signature completes the transaction
+        let signature = service.keypair.sign_message(&transaction.message_data());
+        assert!(add_co_signature(&mut transaction, &Pubkey::new_unique(), signature).is_err());
+        assert!(
+            add_co_signature(&mut transaction, &service.pubkey(), Signature::default()).is_err()
+        );
+        add_co_signature(&mut transaction, &service.pubkey(), signature).unwrap();
+        assert!(transaction.verify().is_ok());
+    }
+}
@@ -0,0 +1,382 @@
+// Compliance hooks for ZK Casino
+// Pluggable KYC/allowlist checks invoked on deposit, bet and withdraw.
+// Decisions are cached per address with a TTL and enforce per-tier amount limits.
+
+use anyhow::Result;
+use axum::async_trait;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum KycTier {
+    Blocked,    // Denied by provider
+    Unverified, // Unknown address, small limits
+    Basic,      // Light verification
+    Verified,   // Full KYC
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComplianceAction {
+    Deposit,
+    Bet,
+    Withdraw,
+}
+
+#[derive(Error, Debug)]
+pub enum ComplianceError {
+    #[error("Address is blocked by compliance policy")]
+    Blocked,
+    #[error("{action:?} of {amount} exceeds the {tier:?} tier limit of {limit}")]
+    LimitExceeded {
+        tier: KycTier,
+        action: ComplianceAction,
+        amount: u64,
+        limit: u64,
+    },
+}
+
+/// Per-action maximum amounts for a tier (lamports, None = unlimited)
+#[derive(Debug, Clone, Copy)]
+pub struct TierLimits {
+    pub max_deposit: Option<u64>,
+    pub max_bet: Option<u64>,
+    pub max_withdraw: Option<u64>,
+}
+
+impl TierLimits {
+    fn limit_for(&self, action: ComplianceAction) -> Option<u64> {
+        match action {
+            ComplianceAction::Deposit => self.max_deposit,
+            ComplianceAction::Bet => self.max_bet,
+            ComplianceAction::Withdraw => self.max_withdraw,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ComplianceConfig {
+    pub cache_ttl: Duration,
+    pub unverified: TierLimits,
+    pub basic: TierLimits,
+    pub verified: TierLimits,
+}
+
+impl Default for ComplianceConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl: Duration::from_secs(300),
+            unverified: TierLimits {
+                max_deposit: Some(1_000_000_000), // 1 SOL
+                max_bet: Some(100_000_000),       // 0.1 SOL
+                max_withdraw: Some(1_000_000_000),
+            },
+            basic: TierLimits {
+                max_deposit: Some(10_000_000_000), // 10 SOL
+                max_bet: Some(1_000_000_000),      // 1 SOL
+                max_withdraw: Some(10_000_000_000),
+            },
+            verified: TierLimits {
+                max_deposit: None,
+                max_bet: None,
+                max_withdraw: None,
+            },
+        }
+    }
+}
+
+impl ComplianceConfig {
+    fn limits(&self, tier: KycTier) -> Option<&TierLimits> {
+        match tier {
+            KycTier::Blocked => None,
+            KycTier::Unverified => Some(&self.unverified),
+            KycTier::Basic => Some(&self.basic),
+            KycTier::Verified => Some(&self.verified),
+        }
+    }
+}
+
+/// Source of KYC decisions (external provider, allowlist, on-chain registry, ...)
+#[async_trait]
+pub trait ComplianceProvider: Send + Sync {
+    async fn tier_for(&self, address: &str) -> Result<KycTier>;
+}
+
+/// Static allowlist: listed addresses are verified, everyone else unverified
+pub struct AllowlistProvider {
+    verified: HashSet<String>,
+}
+
+impl AllowlistProvider {
+    pub fn new(addresses: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            verified: addresses.into_iter().collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl ComplianceProvider for AllowlistProvider {
+    async fn tier_for(&self, address: &str) -> Result<KycTier> {
+        Ok(if self.verified.contains(address) {
+            KycTier::Verified
+        } else {
+            KycTier::Unverified
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct KycProviderResponse {
+    tier: KycTier,
+}
+
+/// External KYC provider queried over HTTP: GET {base_url}/kyc/{address} -> {"tier": "..."}
+pub struct HttpKycProvider {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl HttpKycProvider {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            base_url,
+        }
+    }
+}
+
+#[async_trait]
+impl ComplianceProvider for HttpKycProvider {
+    async fn tier_for(&self, address: &str) -> Result<KycTier> {
+        let url = format!("{}/kyc/{}", self.base_url.trim_end_matches('/'), address);
+        let response = self.client.get(&url).send().await?.error_for_status()?;
+        Ok(response.json::<KycProviderResponse>().await?.tier)
+    }
+}
+
+/// Compliance hook shared by the deposit, bet and withdraw handlers
+pub struct ComplianceHook {
+    provider: Option<Arc<dyn ComplianceProvider>>,
+    config: ComplianceConfig,
+    cache: DashMap<String, (KycTier, Instant)>,
+}
+
+impl ComplianceHook {
+    pub fn new(provider: Option<Arc<dyn ComplianceProvider>>, config: ComplianceConfig) -> Self {
+        Self {
+            provider,
+            config,
+            cache: DashMap::new(),
+        }
+    }
+
+    /// Hook with no provider: every request is allowed
+    pub fn disabled() -> Self {
+        Self::new(None, ComplianceConfig::default())
+    }
+
+    /// Load hook from environment (COMPLIANCE_PROVIDER=allowlist|http, KYC_ALLOWLIST, KYC_PROVIDER_URL, KYC_CACHE_TTL_SECS)
+    pub fn from_env() -> Self {
+        let mut config = ComplianceConfig::default();
+        if let Some(secs) = std::env::var("KYC_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            config.cache_ttl = Duration::from_secs(secs);
+        }
+
+        let provider: Option<Arc<dyn ComplianceProvider>> = match std::env::var(
+            "COMPLIANCE_PROVIDER",
+        )
+        .unwrap_or_default()
+        .as_str()
+        {
+            "allowlist" => {
+                let addresses = std::env::var("KYC_ALLOWLIST")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|a| a.trim().to_string())
+                    .filter(|a| !a.is_empty())
+                    .collect::<Vec<_>>();
+                info!(
+                    "Compliance: allowlist provider with {} addresses",
+                    addresses.len()
+                );
+                Some(Arc::new(AllowlistProvider::new(addresses)))
+            }
+            "http" => {
+                match std::env::var("KYC_PROVIDER_URL") {
+                    Ok(url) => {
+                        info!("Compliance: HTTP KYC provider at {}", url);
+                        Some(Arc::new(HttpKycProvider::new(url)))
+                    }
+                    Err(_) => {
+                        warn!("COMPLIANCE_PROVIDER=http requires KYC_PROVIDER_URL; compliance disabled");
+                        None
+                    }
+                }
+            }
+            _ => {
+                info!("Compliance hooks disabled. Set COMPLIANCE_PROVIDER to enable.");
+                None
+            }
+        };
+
+        Self::new(provider, config)
+    }
+
+    /// Resolve the tier for an address, using the cache when fresh
+    pub async fn tier_for(&self, address: &str) -> KycTier {
+        let Some(provider) = &self.provider else {
+            return KycTier::Verified;
+        };
+
+        if let Some(entry) = self.cache.get(address) {
+            let (tier, cached_at) = *entry;
+            if cached_at.elapsed() < self.config.cache_ttl {
+                return tier;
+            }
+        }
+
+        let tier = match provider.tier_for(address).await {
+            Ok(tier) => tier,
+            Err(e) => {
+                // Fall back to the most restrictive non-blocked tier, don't cache
+                warn!(
+                    "KYC lookup failed for {}: {}. Treating as unverified.",
+                    address, e
+                );
+                return KycTier::Unverified;
+            }
+        };
+
+        self.cache
+            .insert(address.to_string(), (tier, Instant::now()));
+        tier
+    }
+
+    /// Enforce the compliance policy for an action
+    pub async fn check(
+        &self,
+        address: &str,
+        action: ComplianceAction,
+        amount: u64,
+    ) -> Result<KycTier, ComplianceError> {
+        let tier = self.tier_for(address).await;
+
+        let Some(limits) = self.config.limits(tier) else {
+            warn!("Compliance: blocked {:?} from {}", action, address);
+            return Err(ComplianceError::Blocked);
+        };
+
+        if let Some(limit) = limits.limit_for(action) {
+            if amount > limit {
+                warn!(
+                    "Compliance: {:?} of {} from {} exceeds {:?} limit {}",
+                    action, amount, address, tier, limit
+                );
+                return Err(ComplianceError::LimitExceeded {
+                    tier,
+                    action,
+                    amount,
+                    limit,
+                });
+            }
+        }
+
+        Ok(tier)
+    }
+
+    /// Drop a cached decision (e.g. after a KYC status change)
+    pub fn invalidate(&self, address: &str) {
+        self.cache.remove(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ComplianceProvider for CountingProvider {
+        async fn tier_for(&self, address: &str) -> Result<KycTier> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(if address == "blocked" {
+                KycTier::Blocked
+            } else {
+                KycTier::Basic
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tier_limits() {
+        let hook = ComplianceHook::new(
+            Some(Arc::new(AllowlistProvider::new(vec![
+                "kyc_player".to_string()
+            ]))),
+            ComplianceConfig::default(),
+        );
+
+        // Unverified addresses are capped
+        assert!(hook
+            .check("anon", ComplianceAction::Bet, 50_000_000)
+            .await
+            .is_ok());
+        assert!(matches!(
+            hook.check("anon", ComplianceAction::Bet, 200_000_000).await,
+            Err(ComplianceError::LimitExceeded {
+                tier: KycTier::Unverified,
+                ..
+            })
+        ));
+
+        // Verified addresses are unlimited
+        assert_eq!(
+            hook.check("kyc_player", ComplianceAction::Bet, 200_000_000)
+                .await
+                .unwrap(),
+            KycTier::Verified
+        );
+
+        // Disabled hook allows everything
+        assert!(ComplianceHook::disabled()
+            .check("anon", ComplianceAction::Withdraw, u64::MAX)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_decision_cache() {
+        let provider = Arc::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let hook = ComplianceHook::new(Some(provider.clone()), ComplianceConfig::default());
+
+        assert!(matches!(
+            hook.check("blocked", ComplianceAction::Deposit, 1).await,
+            Err(ComplianceError::Blocked)
+        ));
+        assert_eq!(hook.tier_for("player").await, KycTier::Basic);
+        assert_eq!(hook.tier_for("player").await, KycTier::Basic);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 2);
+
+        hook.invalidate("player");
+        assert_eq!(hook.tier_for("player").await, KycTier::Basic);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,8 +1,15 @@
 use chrono::{DateTime, Utc};
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
+use crate::tokens::HouseFees;
+use crate::withdrawal_fees::WithdrawalFees;
+use crate::SettlementToken;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bet {
     pub id: String,
@@ -13,6 +20,36 @@ pub struct Bet {
     pub won: bool,
     pub payout: i64,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub settlement: Option<BetSettlementLink>, // None until the bet is batched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escrow: Option<BetEscrowLink>, // Set for bets placed through on-chain escrow
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BetSettlementStatus {
+    Batched,   // Included in a settlement batch
+    Submitted, // Batch transaction sent to Solana
+    Confirmed, // Batch settled on-chain
+    Failed,    // Batch submission failed
+}
+
+/// Link from a bet to the settlement batch and on-chain transaction that settled it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BetSettlementLink {
+    pub batch_id: u64,
+    pub onchain_index: u32, // Position of the bet in the batch's BetSettlement list
+    pub status: BetSettlementStatus,
+    pub transaction_signature: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// On-chain escrow holding the stake of a high-value bet; settled directly, never batched
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BetEscrowLink {
+    pub escrow_account: String,
+    pub settle_signature: Option<String>, // None until the escrow is released or claimed
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +62,86 @@ pub struct PlayerBalance {
     pub total_won: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub demo: bool, // Play balance from the demo faucet; never withdrawn or settled
+}
+
+/// Ledger entry for a credited deposit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DepositEntry {
+    pub id: String,
+    pub player_address: String,
+    pub amount: i64,
+    pub deposit_nonce: Option<u64>, // Vault deposit nonce, for deposits relayed on-chain
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ledger entry for a withdrawal: the player is debited `amount` and paid `net_amount`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WithdrawalEntry {
+    pub id: String,
+    pub player_address: String,
+    pub amount: i64,
+    pub fees: WithdrawalFees,
+    pub net_amount: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Account of the house's side of manual balance adjustments
+pub const HOUSE_ADJUSTMENTS_ACCOUNT: &str = "house:adjustments";
+
+/// Account demo play balances are granted from; only ever posted against demo accounts
+pub const DEMO_FAUCET_ACCOUNT: &str = "demo:faucet";
+
+/// Journal account of a player's balance
+pub fn player_account(player_address: &str) -> String {
+    format!("player:{}", player_address)
+}
+
+/// Journal account of a demo player's play balance, kept apart from real balances
+pub fn demo_account(player_address: &str) -> String {
+    format!("demo:{}", player_address)
+}
+
+/// Journal account of a balance, by whether it is a demo balance
+fn ledger_account(balance: &PlayerBalance) -> String {
+    if balance.demo {
+        demo_account(&balance.player_address)
+    } else {
+        player_account(&balance.player_address)
+    }
+}
+
+/// One leg of a journal entry; positive amounts credit the account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Posting {
+    pub account: String,
+    pub amount: i64,
+}
+
+/// Double-entry journal entry; its postings always sum to zero
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub memo: String,
+    pub postings: Vec<Posting>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Full copy of the database contents, for backups
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseSnapshot {
+    pub bets: Vec<Bet>,
+    pub player_bets: HashMap<String, Vec<String>>,
+    pub balances: Vec<PlayerBalance>,
+    pub batch_bets: HashMap<u64, Vec<String>>,
+    pub withdrawals: HashMap<String, Vec<WithdrawalEntry>>,
+    pub house_fees: HashMap<SettlementToken, u64>,
+    pub journal: Vec<JournalEntry>,
+    #[serde(default)]
+    pub onchain_deposits: Vec<(String, u64)>,
+    #[serde(default)]
+    pub deposits: HashMap<String, Vec<DepositEntry>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -35,12 +152,26 @@ pub enum DatabaseError {
     InsufficientBalance { required: i64, available: i64 },
     #[error("Bet not found: {0}")]
     BetNotFound(String),
+    #[error("Player {0} holds a demo balance, which cannot be deposited to or withdrawn")]
+    DemoBalance(String),
+    #[error("Player {0} holds a real balance and cannot receive demo funds")]
+    NotDemoBalance(String),
 }
 
+/// Schema version `create_tables` brings the store up to
+pub const SCHEMA_VERSION: u32 = 1;
+
 pub struct Database {
     bets: Arc<DashMap<String, Bet>>,
     player_bets: Arc<DashMap<String, Vec<String>>>, // player_address -> bet_ids
     balances: Arc<DashMap<String, PlayerBalance>>,
+    batch_bets: Arc<DashMap<u64, Vec<String>>>, // batch_id -> bet_ids in on-chain order
+    deposits: Arc<DashMap<String, Vec<DepositEntry>>>, // player_address -> ledger, oldest first
+    withdrawals: Arc<DashMap<String, Vec<WithdrawalEntry>>>, // player_address -> ledger, oldest first
+    house_fees: Arc<DashMap<SettlementToken, u64>>,
+    journal: Arc<RwLock<Vec<JournalEntry>>>, // Oldest first
+    onchain_deposits: Arc<DashSet<(String, u64)>>, // (player_address, vault deposit nonce) credited
+    schema_version: Arc<AtomicU32>,          // 0 until create_tables has run
 }
 
 impl Database {
@@ -49,14 +180,26 @@ impl Database {
             bets: Arc::new(DashMap::new()),
             player_bets: Arc::new(DashMap::new()),
             balances: Arc::new(DashMap::new()),
+            batch_bets: Arc::new(DashMap::new()),
+            deposits: Arc::new(DashMap::new()),
+            withdrawals: Arc::new(DashMap::new()),
+            house_fees: Arc::new(DashMap::new()),
+            journal: Arc::new(RwLock::new(Vec::new())),
+            onchain_deposits: Arc::new(DashSet::new()),
+            schema_version: Arc::new(AtomicU32::new(0)),
         })
     }
 
     pub async fn create_tables(&self) -> Result<(), DatabaseError> {
-        // No-op for in-memory database
+        // Nothing to create for the in-memory database; just record the schema version
+        self.schema_version.store(SCHEMA_VERSION, Ordering::SeqCst);
         Ok(())
     }
 
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version.load(Ordering::SeqCst)
+    }
+
     pub async fn save_bet(&self, bet: &Bet) -> Result<(), DatabaseError> {
         // Insert bet directly with DashMap's concurrent access
         self.bets.insert(bet.id.clone(), bet.clone());
@@ -64,7 +207,7 @@ impl Database {
         // Add to player's bet list with concurrent access
         self.player_bets
             .entry(bet.player_address.clone())
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(bet.id.clone());
 
         Ok(())
@@ -95,7 +238,7 @@ impl Database {
             .collect();
 
         // Sort by timestamp descending
-        player_bet_list.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        player_bet_list.sort_by_key(|bet| std::cmp::Reverse(bet.timestamp));
 
         Ok(player_bet_list)
     }
@@ -110,12 +253,95 @@ impl Database {
             .map(|entry| entry.value().clone())
             .collect();
 
-        all_bets.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        all_bets.sort_by_key(|bet| std::cmp::Reverse(bet.timestamp));
         all_bets.truncate(limit);
 
         Ok(all_bets)
     }
 
+    /// Record that bets were included in a settlement batch, in on-chain order
+    pub async fn link_bets_to_batch(
+        &self,
+        batch_id: u64,
+        bet_ids: &[String],
+    ) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        for (index, bet_id) in bet_ids.iter().enumerate() {
+            if let Some(mut bet) = self.bets.get_mut(bet_id) {
+                bet.settlement = Some(BetSettlementLink {
+                    batch_id,
+                    onchain_index: index as u32,
+                    status: BetSettlementStatus::Batched,
+                    transaction_signature: None,
+                    updated_at: now,
+                });
+            }
+        }
+        self.batch_bets.insert(batch_id, bet_ids.to_vec());
+        Ok(())
+    }
+
+    /// Update settlement status (and transaction signature) for every bet in a batch
+    pub async fn update_batch_settlement(
+        &self,
+        batch_id: u64,
+        status: BetSettlementStatus,
+        transaction_signature: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let bet_ids = self
+            .batch_bets
+            .get(&batch_id)
+            .map(|entry| entry.clone())
+            .unwrap_or_default();
+
+        let now = Utc::now();
+        for bet_id in &bet_ids {
+            if let Some(mut bet) = self.bets.get_mut(bet_id) {
+                if let Some(link) = bet.settlement.as_mut() {
+                    link.status = status;
+                    if let Some(signature) = transaction_signature {
+                        link.transaction_signature = Some(signature.to_string());
+                    }
+                    link.updated_at = now;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the transaction that released or claimed an escrowed bet
+    pub async fn mark_escrow_settled(
+        &self,
+        bet_id: &str,
+        settle_signature: &str,
+    ) -> Result<(), DatabaseError> {
+        let mut bet = self
+            .bets
+            .get_mut(bet_id)
+            .ok_or_else(|| DatabaseError::BetNotFound(bet_id.to_string()))?;
+        if let Some(escrow) = bet.escrow.as_mut() {
+            escrow.settle_signature = Some(settle_signature.to_string());
+        }
+        Ok(())
+    }
+
+    /// Bets placed in the half-open window [start, end), oldest first
+    pub async fn get_bets_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Bet>, DatabaseError> {
+        let mut bets: Vec<Bet> = self
+            .bets
+            .iter()
+            .filter(|entry| entry.timestamp >= start && entry.timestamp < end)
+            .map(|entry| entry.value().clone())
+            .collect();
+
+        bets.sort_by_key(|bet| bet.timestamp);
+        Ok(bets)
+    }
+
     pub async fn get_player_balance(
         &self,
         player_address: &str,
@@ -142,6 +368,7 @@ impl Database {
             total_won: 0,
             created_at: now,
             updated_at: now,
+            demo: false,
         };
 
         self.balances
@@ -149,6 +376,74 @@ impl Database {
         Ok(balance)
     }
 
+    /// Whether the player's balance is a demo play balance
+    pub fn is_demo_player(&self, player_address: &str) -> bool {
+        self.balances
+            .get(player_address)
+            .is_some_and(|balance| balance.demo)
+    }
+
+    /// Top a demo player's play balance back up to `play_balance`, creating it for a new
+    /// address. The grant is journaled against the demo faucet, never the house; None
+    /// when the balance was already at the cap.
+    pub async fn grant_demo_balance(
+        &self,
+        player_address: &str,
+        play_balance: i64,
+    ) -> Result<(PlayerBalance, Option<JournalEntry>), DatabaseError> {
+        let now = Utc::now();
+
+        let (balance, grant) = {
+            let mut current_balance = self
+                .balances
+                .entry(player_address.to_string())
+                .or_insert_with(|| PlayerBalance {
+                    player_address: player_address.to_string(),
+                    balance: 0,
+                    total_deposited: 0,
+                    total_withdrawn: 0,
+                    total_wagered: 0,
+                    total_won: 0,
+                    created_at: now,
+                    updated_at: now,
+                    demo: true,
+                });
+            if !current_balance.demo {
+                return Err(DatabaseError::NotDemoBalance(player_address.to_string()));
+            }
+
+            let grant = (play_balance - current_balance.balance).max(0);
+            if grant > 0 {
+                current_balance.balance += grant;
+                current_balance.updated_at = now;
+            }
+            (current_balance.clone(), grant)
+        };
+        if grant == 0 {
+            return Ok((balance, None));
+        }
+
+        let mut journal = self.journal.write();
+        let entry = JournalEntry {
+            id: journal.len() as u64 + 1,
+            memo: "Demo play balance".to_string(),
+            postings: vec![
+                Posting {
+                    account: demo_account(player_address),
+                    amount: grant,
+                },
+                Posting {
+                    account: DEMO_FAUCET_ACCOUNT.to_string(),
+                    amount: -grant,
+                },
+            ],
+            created_at: now,
+        };
+        journal.push(entry.clone());
+
+        Ok((balance, Some(entry)))
+    }
+
     pub async fn update_player_balance_after_bet(
         &self,
         player_address: &str,
@@ -157,9 +452,10 @@ impl Database {
     ) -> Result<PlayerBalance, DatabaseError> {
         let now = Utc::now();
 
-        // Use DashMap's entry API for atomic update
-        match self.balances.get(player_address) {
-            Some(current_balance) => {
+        // Hold the entry's write guard for the whole read-modify-write so the
+        // update is atomic (and doesn't re-enter the shard lock via insert)
+        match self.balances.get_mut(player_address) {
+            Some(mut current_balance) => {
                 // Check if player has sufficient balance
                 if current_balance.balance < bet_amount {
                     return Err(DatabaseError::InsufficientBalance {
@@ -169,24 +465,12 @@ impl Database {
                 }
 
                 // Calculate new balance: subtract bet amount, add payout
-                let new_balance = current_balance.balance - bet_amount + payout;
-                let new_total_wagered = current_balance.total_wagered + bet_amount;
-                let new_total_won = current_balance.total_won + payout;
-
-                let updated_balance = PlayerBalance {
-                    player_address: player_address.to_string(),
-                    balance: new_balance,
-                    total_deposited: current_balance.total_deposited,
-                    total_withdrawn: current_balance.total_withdrawn,
-                    total_wagered: new_total_wagered,
-                    total_won: new_total_won,
-                    created_at: current_balance.created_at,
-                    updated_at: now,
-                };
+                current_balance.balance = current_balance.balance - bet_amount + payout;
+                current_balance.total_wagered += bet_amount;
+                current_balance.total_won += payout;
+                current_balance.updated_at = now;
 
-                self.balances
-                    .insert(player_address.to_string(), updated_balance.clone());
-                Ok(updated_balance)
+                Ok(current_balance.clone())
             }
             None => Err(DatabaseError::PlayerNotFound(player_address.to_string())),
         }
@@ -196,10 +480,23 @@ impl Database {
         &self,
         player_address: &str,
         amount: i64,
+    ) -> Result<PlayerBalance, DatabaseError> {
+        self.credit_deposit(player_address, amount, None).await
+    }
+
+    /// Credit a deposit and record its ledger entry
+    async fn credit_deposit(
+        &self,
+        player_address: &str,
+        amount: i64,
+        deposit_nonce: Option<u64>,
     ) -> Result<PlayerBalance, DatabaseError> {
         let now = Utc::now();
 
         let updated_balance = match self.balances.get(player_address) {
+            Some(current_balance) if current_balance.demo => {
+                return Err(DatabaseError::DemoBalance(player_address.to_string()))
+            }
             Some(current_balance) => {
                 let new_balance = current_balance.balance + amount;
                 let new_total_deposited = current_balance.total_deposited + amount;
@@ -213,6 +510,7 @@ impl Database {
                     total_won: current_balance.total_won,
                     created_at: current_balance.created_at,
                     updated_at: now,
+                    demo: false,
                 }
             }
             None => PlayerBalance {
@@ -224,23 +522,64 @@ impl Database {
                 total_won: 0,
                 created_at: now,
                 updated_at: now,
+                demo: false,
             },
         };
 
         self.balances
             .insert(player_address.to_string(), updated_balance.clone());
+        self.deposits
+            .entry(player_address.to_string())
+            .or_default()
+            .push(DepositEntry {
+                id: uuid::Uuid::new_v4().to_string(),
+                player_address: player_address.to_string(),
+                amount,
+                deposit_nonce,
+                created_at: now,
+            });
         Ok(updated_balance)
     }
 
+    /// Credit a deposit relayed into the player's on-chain vault, once per vault deposit
+    /// nonce. None when that deposit was already credited.
+    pub async fn credit_onchain_deposit(
+        &self,
+        player_address: &str,
+        deposit_nonce: u64,
+        amount: i64,
+    ) -> Result<Option<PlayerBalance>, DatabaseError> {
+        if !self
+            .onchain_deposits
+            .insert((player_address.to_string(), deposit_nonce))
+        {
+            return Ok(None);
+        }
+        let credited = self
+            .credit_deposit(player_address, amount, Some(deposit_nonce))
+            .await;
+        if credited.is_err() {
+            // Not credited, so a later relay of the same deposit may still be
+            self.onchain_deposits
+                .remove(&(player_address.to_string(), deposit_nonce));
+        }
+        credited.map(Some)
+    }
+
+    /// Debit `amount` and record a ledger entry; `fees` are withheld from the payout
     pub async fn withdraw(
         &self,
         player_address: &str,
         amount: i64,
-    ) -> Result<PlayerBalance, DatabaseError> {
+        fees: WithdrawalFees,
+    ) -> Result<(PlayerBalance, WithdrawalEntry), DatabaseError> {
         let now = Utc::now();
 
-        match self.balances.get(player_address) {
-            Some(current_balance) => {
+        let balance = match self.balances.get_mut(player_address) {
+            Some(current_balance) if current_balance.demo => {
+                return Err(DatabaseError::DemoBalance(player_address.to_string()))
+            }
+            Some(mut current_balance) => {
                 if current_balance.balance < amount {
                     return Err(DatabaseError::InsufficientBalance {
                         required: amount,
@@ -248,26 +587,205 @@ impl Database {
                     });
                 }
 
-                let new_balance = current_balance.balance - amount;
-                let new_total_withdrawn = current_balance.total_withdrawn + amount;
+                current_balance.balance -= amount;
+                current_balance.total_withdrawn += amount;
+                current_balance.updated_at = now;
 
-                let updated_balance = PlayerBalance {
-                    player_address: player_address.to_string(),
-                    balance: new_balance,
-                    total_deposited: current_balance.total_deposited,
-                    total_withdrawn: new_total_withdrawn,
-                    total_wagered: current_balance.total_wagered,
-                    total_won: current_balance.total_won,
-                    created_at: current_balance.created_at,
-                    updated_at: now,
-                };
+                current_balance.clone()
+            }
+            None => return Err(DatabaseError::PlayerNotFound(player_address.to_string())),
+        };
+        self.accrue_house_fees(SettlementToken::Sol, fees.house_share())
+            .await?;
+
+        let entry = WithdrawalEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            player_address: player_address.to_string(),
+            amount,
+            fees,
+            net_amount: amount - fees.total() as i64,
+            created_at: now,
+        };
+        self.withdrawals
+            .entry(player_address.to_string())
+            .or_default()
+            .push(entry.clone());
+
+        Ok((balance, entry))
+    }
 
-                self.balances
-                    .insert(player_address.to_string(), updated_balance.clone());
-                Ok(updated_balance)
+    /// Deposit ledger for a player, most recent first
+    pub async fn get_player_deposits(
+        &self,
+        player_address: &str,
+    ) -> Result<Vec<DepositEntry>, DatabaseError> {
+        Ok(self
+            .deposits
+            .get(player_address)
+            .map(|entries| entries.iter().rev().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Withdrawal ledger for a player, most recent first
+    pub async fn get_player_withdrawals(
+        &self,
+        player_address: &str,
+    ) -> Result<Vec<WithdrawalEntry>, DatabaseError> {
+        Ok(self
+            .withdrawals
+            .get(player_address)
+            .map(|entries| entries.iter().rev().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Credit fees and sub-unit residue to the house fee bucket
+    pub async fn accrue_house_fees(
+        &self,
+        token: SettlementToken,
+        amount: u64,
+    ) -> Result<(), DatabaseError> {
+        if amount > 0 {
+            let mut bucket = self.house_fees.entry(token).or_default();
+            *bucket = bucket.saturating_add(amount);
+        }
+        Ok(())
+    }
+
+    /// Credit (positive) or debit (negative) a player's balance outside of betting, posting
+    /// the opposite leg to the house adjustments account
+    pub async fn adjust_balance(
+        &self,
+        player_address: &str,
+        amount: i64,
+        memo: String,
+    ) -> Result<(PlayerBalance, JournalEntry), DatabaseError> {
+        let now = Utc::now();
+
+        let balance = match self.balances.get_mut(player_address) {
+            Some(mut current_balance) => {
+                if current_balance.balance + amount < 0 {
+                    return Err(DatabaseError::InsufficientBalance {
+                        required: -amount,
+                        available: current_balance.balance,
+                    });
+                }
+
+                current_balance.balance += amount;
+                current_balance.updated_at = now;
+
+                current_balance.clone()
             }
-            None => Err(DatabaseError::PlayerNotFound(player_address.to_string())),
+            None => return Err(DatabaseError::PlayerNotFound(player_address.to_string())),
+        };
+
+        let mut journal = self.journal.write();
+        let entry = JournalEntry {
+            id: journal.len() as u64 + 1,
+            memo,
+            postings: vec![
+                Posting {
+                    account: ledger_account(&balance),
+                    amount,
+                },
+                Posting {
+                    account: HOUSE_ADJUSTMENTS_ACCOUNT.to_string(),
+                    amount: -amount,
+                },
+            ],
+            created_at: now,
+        };
+        journal.push(entry.clone());
+
+        Ok((balance, entry))
+    }
+
+    /// Copy every table; each table is copied atomically, writes landing between tables
+    /// show up in the later ones only
+    pub async fn snapshot(&self) -> Result<DatabaseSnapshot, DatabaseError> {
+        let journal = self.journal.read().clone();
+        Ok(DatabaseSnapshot {
+            bets: self.bets.iter().map(|e| e.value().clone()).collect(),
+            player_bets: self
+                .player_bets
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect(),
+            balances: self.balances.iter().map(|e| e.value().clone()).collect(),
+            batch_bets: self
+                .batch_bets
+                .iter()
+                .map(|e| (*e.key(), e.value().clone()))
+                .collect(),
+            withdrawals: self
+                .withdrawals
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect(),
+            deposits: self
+                .deposits
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect(),
+            house_fees: self
+                .house_fees
+                .iter()
+                .map(|e| (*e.key(), *e.value()))
+                .collect(),
+            journal,
+            onchain_deposits: self.onchain_deposits.iter().map(|e| e.clone()).collect(),
+        })
+    }
+
+    /// Replace the database contents with a snapshot
+    pub async fn restore_snapshot(&self, snapshot: DatabaseSnapshot) -> Result<(), DatabaseError> {
+        self.bets.clear();
+        for bet in snapshot.bets {
+            self.bets.insert(bet.id.clone(), bet);
+        }
+        self.player_bets.clear();
+        for (player_address, bet_ids) in snapshot.player_bets {
+            self.player_bets.insert(player_address, bet_ids);
+        }
+        self.balances.clear();
+        for balance in snapshot.balances {
+            self.balances
+                .insert(balance.player_address.clone(), balance);
+        }
+        self.batch_bets.clear();
+        for (batch_id, bet_ids) in snapshot.batch_bets {
+            self.batch_bets.insert(batch_id, bet_ids);
+        }
+        self.deposits.clear();
+        for (player_address, entries) in snapshot.deposits {
+            self.deposits.insert(player_address, entries);
         }
+        self.withdrawals.clear();
+        for (player_address, entries) in snapshot.withdrawals {
+            self.withdrawals.insert(player_address, entries);
+        }
+        self.house_fees.clear();
+        for (token, amount) in snapshot.house_fees {
+            self.house_fees.insert(token, amount);
+        }
+        *self.journal.write() = snapshot.journal;
+        self.onchain_deposits.clear();
+        for deposit in snapshot.onchain_deposits {
+            self.onchain_deposits.insert(deposit);
+        }
+        Ok(())
+    }
+
+    /// Journal entries, most recent first
+    pub async fn get_journal(&self) -> Result<Vec<JournalEntry>, DatabaseError> {
+        Ok(self.journal.read().iter().rev().cloned().collect())
+    }
+
+    pub async fn get_house_fees(&self) -> Result<HouseFees, DatabaseError> {
+        let bucket = |token| self.house_fees.get(&token).map(|v| *v).unwrap_or(0);
+        Ok(HouseFees {
+            sol: bucket(SettlementToken::Sol),
+            usdc: bucket(SettlementToken::Usdc),
+        })
     }
 }
 
@@ -300,6 +818,8 @@ mod tests {
             won: false,
             payout: 0,
             timestamp: Utc::now(),
+            settlement: None,
+            escrow: None,
         };
 
         // Save bet
@@ -313,6 +833,55 @@ mod tests {
         assert_eq!(retrieved_bet.won, bet.won);
     }
 
+    #[tokio::test]
+    async fn test_bet_settlement_linkage() {
+        let db = setup_test_db().await;
+
+        for id in ["bet_a", "bet_b"] {
+            db.save_bet(&Bet {
+                id: id.to_string(),
+                player_address: "player".to_string(),
+                amount: 1000,
+                guess: true,
+                result: true,
+                won: true,
+                payout: 2000,
+                timestamp: Utc::now(),
+                settlement: None,
+                escrow: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        db.link_bets_to_batch(7, &["bet_a".to_string(), "bet_b".to_string()])
+            .await
+            .unwrap();
+        let link = db
+            .get_bet("bet_b")
+            .await
+            .unwrap()
+            .unwrap()
+            .settlement
+            .unwrap();
+        assert_eq!(link.batch_id, 7);
+        assert_eq!(link.onchain_index, 1);
+        assert_eq!(link.status, BetSettlementStatus::Batched);
+
+        db.update_batch_settlement(7, BetSettlementStatus::Confirmed, Some("sig123"))
+            .await
+            .unwrap();
+        let link = db
+            .get_bet("bet_a")
+            .await
+            .unwrap()
+            .unwrap()
+            .settlement
+            .unwrap();
+        assert_eq!(link.status, BetSettlementStatus::Confirmed);
+        assert_eq!(link.transaction_signature.as_deref(), Some("sig123"));
+    }
+
     #[tokio::test]
     async fn test_player_balance_creation() {
         let db = setup_test_db().await;
@@ -349,6 +918,53 @@ mod tests {
         let balance = db.deposit(player_address, 3000).await.unwrap();
         assert_eq!(balance.balance, 8000);
         assert_eq!(balance.total_deposited, 8000);
+
+        // Each deposit is on the ledger, most recent first
+        let deposits = db.get_player_deposits(player_address).await.unwrap();
+        let amounts: Vec<i64> = deposits.iter().map(|entry| entry.amount).collect();
+        assert_eq!(amounts, vec![3000, 5000]);
+        assert!(deposits.iter().all(|entry| entry.deposit_nonce.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_credit_onchain_deposit() {
+        let db = setup_test_db().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        let balance = db
+            .credit_onchain_deposit(player_address, 1, 5000)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(balance.total_deposited, 5000);
+
+        // A rescanned event is not credited twice; the next nonce is
+        assert!(db
+            .credit_onchain_deposit(player_address, 1, 5000)
+            .await
+            .unwrap()
+            .is_none());
+        let balance = db
+            .credit_onchain_deposit(player_address, 2, 3000)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(balance.balance, 8000);
+
+        // Credited nonces survive a snapshot round trip
+        let restored = setup_test_db().await;
+        restored
+            .restore_snapshot(db.snapshot().await.unwrap())
+            .await
+            .unwrap();
+        assert!(restored
+            .credit_onchain_deposit(player_address, 2, 3000)
+            .await
+            .unwrap()
+            .is_none());
+        let deposits = restored.get_player_deposits(player_address).await.unwrap();
+        let nonces: Vec<Option<u64>> = deposits.iter().map(|entry| entry.deposit_nonce).collect();
+        assert_eq!(nonces, vec![Some(2), Some(1)]);
     }
 
     #[tokio::test]
@@ -362,9 +978,102 @@ mod tests {
             .unwrap();
 
         // Withdraw amount
-        let balance = db.withdraw(player_address, 3000).await.unwrap();
+        let fees = WithdrawalFees {
+            flat: 100,
+            percentage: 15,
+            network: 5,
+            dust: 1,
+        };
+        let (balance, entry) = db.withdraw(player_address, 3000, fees).await.unwrap();
         assert_eq!(balance.balance, 7000);
         assert_eq!(balance.total_withdrawn, 3000);
+        assert_eq!(entry.net_amount, 2879);
+
+        // Everything but the network fee goes to the house
+        let house = db.get_house_fees().await.unwrap();
+        assert_eq!(house, HouseFees { sol: 116, usdc: 0 });
+
+        let ledger = db.get_player_withdrawals(player_address).await.unwrap();
+        assert_eq!(ledger, vec![entry]);
+    }
+
+    #[tokio::test]
+    async fn test_adjust_balance_posts_journal_entry() {
+        let db = setup_test_db().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        assert!(matches!(
+            db.adjust_balance(player_address, 500, "credit".into())
+                .await,
+            Err(DatabaseError::PlayerNotFound(_))
+        ));
+
+        db.create_player_balance(player_address, 1000)
+            .await
+            .unwrap();
+        let (balance, entry) = db
+            .adjust_balance(player_address, 500, "credit".into())
+            .await
+            .unwrap();
+        assert_eq!(balance.balance, 1500);
+        assert_eq!(entry.postings.iter().map(|p| p.amount).sum::<i64>(), 0);
+        assert_eq!(entry.postings[0].account, player_account(player_address));
+
+        // Debits can't take the balance negative
+        assert!(matches!(
+            db.adjust_balance(player_address, -2000, "debit".into())
+                .await,
+            Err(DatabaseError::InsufficientBalance { .. })
+        ));
+        assert_eq!(db.get_journal().await.unwrap(), vec![entry]);
+    }
+
+    #[tokio::test]
+    async fn test_demo_balance() {
+        let db = setup_test_db().await;
+        let player_address = "demo_player";
+
+        // A new address gets the whole play balance, journaled against the faucet
+        let (balance, entry) = db.grant_demo_balance(player_address, 1000).await.unwrap();
+        assert!(balance.demo);
+        assert_eq!(balance.balance, 1000);
+        assert_eq!(balance.total_deposited, 0);
+        let entry = entry.unwrap();
+        assert_eq!(entry.postings[0].account, demo_account(player_address));
+        assert_eq!(entry.postings[1].account, DEMO_FAUCET_ACCOUNT);
+        assert!(db.is_demo_player(player_address));
+
+        // Topped back up to the cap, never beyond it
+        db.update_player_balance_after_bet(player_address, 400, 0)
+            .await
+            .unwrap();
+        let (balance, entry) = db.grant_demo_balance(player_address, 1000).await.unwrap();
+        assert_eq!(balance.balance, 1000);
+        assert_eq!(entry.unwrap().postings[0].amount, 400);
+        let (_, entry) = db.grant_demo_balance(player_address, 1000).await.unwrap();
+        assert!(entry.is_none());
+
+        // Play money never leaves, and real money never mixes in
+        assert!(matches!(
+            db.withdraw(player_address, 100, WithdrawalFees::default())
+                .await,
+            Err(DatabaseError::DemoBalance(_))
+        ));
+        assert!(matches!(
+            db.deposit(player_address, 100).await,
+            Err(DatabaseError::DemoBalance(_))
+        ));
+        assert!(db
+            .credit_onchain_deposit(player_address, 1, 100)
+            .await
+            .is_err());
+
+        db.deposit("real_player", 100).await.unwrap();
+        assert!(!db.is_demo_player("real_player"));
+        assert!(matches!(
+            db.grant_demo_balance("real_player", 1000).await,
+            Err(DatabaseError::NotDemoBalance(_))
+        ));
     }
 
     #[tokio::test]
@@ -378,7 +1087,9 @@ mod tests {
             .unwrap();
 
         // Try to withdraw more than balance
-        let result = db.withdraw(player_address, 2000).await;
+        let result = db
+            .withdraw(player_address, 2000, WithdrawalFees::default())
+            .await;
         assert!(matches!(
             result,
             Err(DatabaseError::InsufficientBalance { .. })
@@ -434,6 +1145,8 @@ mod tests {
                     0
                 },
                 timestamp: Utc::now(),
+                settlement: None,
+                escrow: None,
             };
             db.save_bet(&bet).await.unwrap();
         }
@@ -462,6 +1175,8 @@ mod tests {
                 won: false,
                 payout: 0,
                 timestamp: Utc::now(),
+                settlement: None,
+                escrow: None,
             };
             db.save_bet(&bet).await.unwrap();
         }
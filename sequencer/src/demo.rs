@@ -0,0 +1,36 @@
+// Demo mode for ZK Casino's public testnet
+// New addresses are granted a capped play balance the first time they show up, and can
+// top it back up from the demo faucet, so users can play without depositing. Play
+// balances are flagged on the player's balance and journaled against the demo faucet
+// rather than the house: they can't be withdrawn or mixed with real deposits, and demo
+// bets are never queued for on-chain settlement, so they stay out of the house's PnL
+// and the fairness reports.
+
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemoConfig {
+    pub play_balance: u64, // Lamports a demo balance is granted, and topped back up to
+}
+
+impl DemoConfig {
+    /// Load from environment; None unless DEMO_MODE=true. (DEMO_PLAY_BALANCE_LAMPORTS)
+    pub fn from_env() -> Result<Option<Self>> {
+        if std::env::var("DEMO_MODE").unwrap_or_default() != "true" {
+            return Ok(None);
+        }
+        let play_balance = match std::env::var("DEMO_PLAY_BALANCE_LAMPORTS") {
+            Ok(v) => v
+                .parse::<u64>()
+                .map_err(|e| anyhow!("Invalid DEMO_PLAY_BALANCE_LAMPORTS: {}", e))?,
+            Err(_) => 1_000_000_000, // 1 SOL
+        };
+        if play_balance == 0 || play_balance > i64::MAX as u64 {
+            return Err(anyhow!(
+                "DEMO_PLAY_BALANCE_LAMPORTS must be between 1 and {}",
+                i64::MAX
+            ));
+        }
+        Ok(Some(Self { play_balance }))
+    }
+}
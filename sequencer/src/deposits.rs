@@ -0,0 +1,270 @@
+// On-chain deposits for ZK Casino
+// A player can fund their vault with a plain wallet transfer to their deposit address,
+// the vault program PDA ["deposit_address", player]. Any relayer's deposit_for then
+// credits the player's vault with that address's balance and emits a DepositEvent
+// carrying the vault's deposit nonce. The watcher picks those events up from vault
+// transactions and credits the off-chain balance once per (player, nonce), so rescans
+// after a restart never credit a deposit twice.
+
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use anyhow::Result;
+use axum::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use parking_lot::Mutex;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+
+use crate::database::Database;
+use crate::solana::SolanaClient;
+
+#[derive(Debug, Clone)]
+pub struct DepositWatcherConfig {
+    pub poll_interval: Duration,
+}
+
+impl DepositWatcherConfig {
+    /// Load from environment; None unless DEPOSIT_WATCHER=true.
+    /// (DEPOSIT_POLL_INTERVAL_SECS)
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("DEPOSIT_WATCHER").unwrap_or_default() != "true" {
+            return None;
+        }
+        let poll_interval = std::env::var("DEPOSIT_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(2);
+        Some(Self {
+            poll_interval: Duration::from_secs(poll_interval),
+        })
+    }
+}
+
+/// A deposit_for sweep of a player's deposit address into their vault
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayedDeposit {
+    pub user: Pubkey,
+    pub amount: u64, // Lamports
+    pub deposit_nonce: u64,
+}
+
+/// Relayed deposits announced in a vault transaction's logs ("Program data: <base64>").
+/// Direct deposits carry no nonce and are left to the deposit API.
+pub fn parse_deposit_events(logs: &[String]) -> Vec<RelayedDeposit> {
+    logs.iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .filter_map(|data| BASE64.decode(data.trim()).ok())
+        .filter(|data| data.starts_with(&vault::DepositEvent::DISCRIMINATOR))
+        .filter_map(|data| vault::DepositEvent::deserialize(&mut &data[8..]).ok())
+        .filter(|event| event.token_type == vault::TokenType::Sol)
+        .filter_map(|event| {
+            Some(RelayedDeposit {
+                user: event.user,
+                amount: event.amount,
+                deposit_nonce: event.deposit_nonce?,
+            })
+        })
+        .collect()
+}
+
+/// Vault program access, so the watcher can run against a mock chain in tests
+#[async_trait]
+pub trait DepositChain: Send + Sync {
+    /// Vault transactions after `until`, oldest first
+    async fn new_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>>;
+    async fn logs(&self, signature: &Signature) -> Result<Vec<String>>;
+}
+
+#[async_trait]
+impl DepositChain for SolanaClient {
+    async fn new_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>> {
+        self.get_vault_signatures(until).await
+    }
+
+    async fn logs(&self, signature: &Signature) -> Result<Vec<String>> {
+        self.get_transaction_logs(signature).await
+    }
+}
+
+/// Credits relayed on-chain deposits to off-chain balances
+pub struct DepositWatcher {
+    config: DepositWatcherConfig,
+    chain: Arc<dyn DepositChain>,
+    db: Arc<Database>,
+    cursor: Mutex<Option<Signature>>, // Last vault transaction scanned
+}
+
+impl DepositWatcher {
+    pub fn new(
+        config: DepositWatcherConfig,
+        chain: Arc<dyn DepositChain>,
+        db: Arc<Database>,
+    ) -> Self {
+        Self {
+            config,
+            chain,
+            db,
+            cursor: Mutex::new(None),
+        }
+    }
+
+    /// Scan new vault transactions and credit their deposits. Returns deposits credited.
+    pub async fn poll_once(&self) -> Result<usize> {
+        let cursor = *self.cursor.lock();
+        let mut credited = 0;
+        for signature in self.chain.new_signatures(cursor).await? {
+            let logs = self.chain.logs(&signature).await?;
+            for deposit in parse_deposit_events(&logs) {
+                let player_address = deposit.user.to_string();
+                match self
+                    .db
+                    .credit_onchain_deposit(
+                        &player_address,
+                        deposit.deposit_nonce,
+                        deposit.amount as i64,
+                    )
+                    .await?
+                {
+                    Some(balance) => {
+                        info!(
+                            "Credited on-chain deposit #{} of {} lamports to {} (balance {})",
+                            deposit.deposit_nonce, deposit.amount, player_address, balance.balance
+                        );
+                        credited += 1;
+                    }
+                    None => debug!(
+                        "On-chain deposit #{} for {} already credited",
+                        deposit.deposit_nonce, player_address
+                    ),
+                }
+            }
+            // Advance only past fully handled transactions so failures are retried
+            *self.cursor.lock() = Some(signature);
+        }
+        Ok(credited)
+    }
+
+    /// Start polling the vault program (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting deposit watcher (poll interval: {:?})",
+            self.config.poll_interval
+        );
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.poll_once().await {
+                    warn!("Deposit poll failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AnchorSerialize;
+
+    fn event_log(user: Pubkey, amount: u64, deposit_nonce: Option<u64>) -> String {
+        let event = vault::DepositEvent {
+            user,
+            token_type: vault::TokenType::Sol,
+            amount,
+            new_balance: amount,
+            deposit_nonce,
+            timestamp: 1_700_000_000,
+        };
+        let mut data = vault::DepositEvent::DISCRIMINATOR.to_vec();
+        event.serialize(&mut data).unwrap();
+        format!("Program data: {}", BASE64.encode(data))
+    }
+
+    #[derive(Default)]
+    struct MockChain {
+        transactions: Mutex<Vec<(Signature, Vec<String>)>>,
+    }
+
+    #[async_trait]
+    impl DepositChain for MockChain {
+        async fn new_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>> {
+            let transactions = self.transactions.lock();
+            let start = until
+                .and_then(|until| transactions.iter().position(|(s, _)| *s == until))
+                .map_or(0, |i| i + 1);
+            Ok(transactions[start..].iter().map(|(s, _)| *s).collect())
+        }
+
+        async fn logs(&self, signature: &Signature) -> Result<Vec<String>> {
+            let transactions = self.transactions.lock();
+            let (_, logs) = transactions.iter().find(|(s, _)| s == signature).unwrap();
+            Ok(logs.clone())
+        }
+    }
+
+    #[test]
+    fn test_parse_deposit_events() {
+        let user = Pubkey::new_unique();
+        let logs = vec![
+            "Program log: Instruction: DepositFor".to_string(),
+            event_log(user, 5_000, Some(3)),
+            event_log(user, 7_000, None), // Direct deposit_sol
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+        ];
+        assert_eq!(
+            parse_deposit_events(&logs),
+            vec![RelayedDeposit {
+                user,
+                amount: 5_000,
+                deposit_nonce: 3,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watcher_credits_each_deposit_once() {
+        let chain = Arc::new(MockChain::default());
+        let db = Arc::new(Database::new("").await.unwrap());
+        let user = Pubkey::new_unique();
+        chain.transactions.lock().push((
+            Signature::new_unique(),
+            vec![event_log(user, 5_000, Some(1))],
+        ));
+
+        let watcher = DepositWatcher::new(
+            DepositWatcherConfig {
+                poll_interval: Duration::from_secs(1),
+            },
+            chain.clone(),
+            db.clone(),
+        );
+        assert_eq!(watcher.poll_once().await.unwrap(), 1);
+        assert_eq!(watcher.poll_once().await.unwrap(), 0);
+
+        // A restarted watcher rescans from the start without crediting again
+        chain.transactions.lock().push((
+            Signature::new_unique(),
+            vec![event_log(user, 3_000, Some(2))],
+        ));
+        let restarted = DepositWatcher::new(
+            DepositWatcherConfig {
+                poll_interval: Duration::from_secs(1),
+            },
+            chain,
+            db.clone(),
+        );
+        assert_eq!(restarted.poll_once().await.unwrap(), 1);
+
+        let balance = db
+            .get_player_balance(&user.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(balance.balance, 8_000);
+    }
+}
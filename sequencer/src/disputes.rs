@@ -0,0 +1,341 @@
+// Player disputes for ZK Casino
+// A player can dispute a bet by filing its signed receipt (the bet response, including
+// the VRF proof) and optionally the fairness bundle they checked it against. The
+// receipt's VRF proof is checked against the recorded bet when the dispute is filed,
+// so reviewers see immediately whether the recorded outcome is the one the proof
+// commits to. Admins move disputes from open to reviewing to resolved. There is no
+// on-chain challenge instruction yet; disputes are tracked off-chain until there is.
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+
+use crate::database::Bet;
+use crate::vrf::{verify_coinflip, CoinflipInput};
+
+/// Longest accepted dispute reason or resolution note, in characters
+pub const MAX_DISPUTE_TEXT_LEN: usize = 2000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeStatus {
+    Open,      // Filed, not yet picked up
+    Reviewing, // An operator is looking into it
+    Resolved,  // Final
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisputeOutcome {
+    Upheld,   // The player was right
+    Rejected, // The recorded bet stands
+}
+
+/// What the player submitted alongside the dispute
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisputeEvidence {
+    #[serde(default)]
+    pub receipt: Option<serde_json::Value>, // Bet response as returned by /v1/bet
+    #[serde(default)]
+    pub fairness_bundle: Option<serde_json::Value>, // e.g. /v1/transparency snapshot
+}
+
+/// Automated checks run when the dispute is filed
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EvidenceCheck {
+    pub vrf_proof_valid: Option<bool>, // None when the receipt carries no VRF proof
+    pub proven_result: Option<bool>,   // Outcome the VRF proof commits to
+    pub recorded_result: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisputeResolution {
+    pub outcome: DisputeOutcome,
+    pub note: String,
+    pub resolved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Dispute {
+    pub id: u64,
+    pub bet_id: String,
+    pub player_address: String,
+    pub reason: String,
+    pub evidence: DisputeEvidence,
+    pub evidence_check: EvidenceCheck,
+    pub status: DisputeStatus,
+    pub resolution: Option<DisputeResolution>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DisputeError {
+    #[error("Dispute {0} not found")]
+    NotFound(u64),
+    #[error("Bet {0} already has an unresolved dispute")]
+    AlreadyDisputed(String),
+    #[error("Dispute {0} cannot move from {1:?} to {2:?}")]
+    InvalidTransition(u64, DisputeStatus, DisputeStatus),
+    #[error("Text must be 1 to {MAX_DISPUTE_TEXT_LEN} characters")]
+    InvalidText,
+}
+
+fn validate_text(text: &str) -> Result<(), DisputeError> {
+    let len = text.trim().chars().count();
+    if len == 0 || len > MAX_DISPUTE_TEXT_LEN {
+        return Err(DisputeError::InvalidText);
+    }
+    Ok(())
+}
+
+/// Check the receipt's VRF proof against the recorded bet
+pub fn check_evidence(bet: &Bet, evidence: &DisputeEvidence, vrf: &Pubkey) -> EvidenceCheck {
+    let proof = evidence
+        .receipt
+        .as_ref()
+        .and_then(|receipt| receipt.get("vrf_proof"))
+        .and_then(|proof| proof.as_str())
+        .and_then(|proof| proof.parse().ok());
+    let proven_result = proof.as_ref().and_then(|proof| {
+        let input = CoinflipInput {
+            bet_id: &bet.id,
+            player: &bet.player_address,
+            amount: bet.amount as u64,
+            guess: bet.guess,
+        };
+        verify_coinflip(vrf, &input, proof)
+    });
+
+    EvidenceCheck {
+        vrf_proof_valid: proof.map(|_| proven_result.is_some()),
+        proven_result,
+        recorded_result: bet.result,
+    }
+}
+
+/// Dispute records and their state transitions
+#[derive(Default)]
+pub struct DisputeStore {
+    disputes: RwLock<Vec<Dispute>>,
+}
+
+impl DisputeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// File a dispute for a bet the caller has already looked up and checked
+    pub fn file(
+        &self,
+        bet: &Bet,
+        reason: String,
+        evidence: DisputeEvidence,
+        evidence_check: EvidenceCheck,
+    ) -> Result<Dispute, DisputeError> {
+        validate_text(&reason)?;
+
+        let mut disputes = self.disputes.write();
+        if disputes
+            .iter()
+            .any(|d| d.bet_id == bet.id && d.status != DisputeStatus::Resolved)
+        {
+            return Err(DisputeError::AlreadyDisputed(bet.id.clone()));
+        }
+
+        let now = Utc::now();
+        let dispute = Dispute {
+            id: disputes.len() as u64 + 1,
+            bet_id: bet.id.clone(),
+            player_address: bet.player_address.clone(),
+            reason,
+            evidence,
+            evidence_check,
+            status: DisputeStatus::Open,
+            resolution: None,
+            created_at: now,
+            updated_at: now,
+        };
+        disputes.push(dispute.clone());
+        Ok(dispute)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Dispute> {
+        self.disputes.read().iter().find(|d| d.id == id).cloned()
+    }
+
+    /// All disputes, newest first
+    pub fn list(&self) -> Vec<Dispute> {
+        self.disputes.read().iter().rev().cloned().collect()
+    }
+
+    /// Mark an open dispute as under review
+    pub fn start_review(&self, id: u64) -> Result<Dispute, DisputeError> {
+        self.transition(id, DisputeStatus::Reviewing, |_| {})
+    }
+
+    /// Close a dispute with an outcome; open disputes can be resolved directly
+    pub fn resolve(
+        &self,
+        id: u64,
+        outcome: DisputeOutcome,
+        note: String,
+    ) -> Result<Dispute, DisputeError> {
+        validate_text(&note)?;
+        self.transition(id, DisputeStatus::Resolved, |dispute| {
+            dispute.resolution = Some(DisputeResolution {
+                outcome,
+                note,
+                resolved_at: Utc::now(),
+            });
+        })
+    }
+
+    fn transition(
+        &self,
+        id: u64,
+        to: DisputeStatus,
+        update: impl FnOnce(&mut Dispute),
+    ) -> Result<Dispute, DisputeError> {
+        let mut disputes = self.disputes.write();
+        let dispute = disputes
+            .iter_mut()
+            .find(|d| d.id == id)
+            .ok_or(DisputeError::NotFound(id))?;
+
+        let allowed = matches!(
+            (dispute.status, to),
+            (DisputeStatus::Open, DisputeStatus::Reviewing)
+                | (DisputeStatus::Open, DisputeStatus::Resolved)
+                | (DisputeStatus::Reviewing, DisputeStatus::Resolved)
+        );
+        if !allowed {
+            return Err(DisputeError::InvalidTransition(id, dispute.status, to));
+        }
+
+        dispute.status = to;
+        dispute.updated_at = Utc::now();
+        update(dispute);
+        Ok(dispute.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vrf::prove_coinflip;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    fn bet(vrf: &Keypair) -> Bet {
+        let input = CoinflipInput {
+            bet_id: "bet1",
+            player: "player1",
+            amount: 1000,
+            guess: true,
+        };
+        let (result, _) = prove_coinflip(vrf, &input);
+        Bet {
+            id: "bet1".to_string(),
+            player_address: "player1".to_string(),
+            amount: 1000,
+            guess: true,
+            result,
+            won: result,
+            payout: if result { 2000 } else { 0 },
+            timestamp: Utc::now(),
+            settlement: None,
+            escrow: None,
+        }
+    }
+
+    fn receipt(proof: &str) -> DisputeEvidence {
+        DisputeEvidence {
+            receipt: Some(serde_json::json!({ "bet_id": "bet1", "vrf_proof": proof })),
+            fairness_bundle: None,
+        }
+    }
+
+    #[test]
+    fn test_evidence_check_against_recorded_bet() {
+        let vrf = Keypair::new();
+        let mut bet = bet(&vrf);
+        let input = CoinflipInput {
+            bet_id: "bet1",
+            player: "player1",
+            amount: 1000,
+            guess: true,
+        };
+        let (_, proof) = prove_coinflip(&vrf, &input);
+        let evidence = receipt(&proof.to_string());
+
+        let check = check_evidence(&bet, &evidence, &vrf.pubkey());
+        assert_eq!(check.vrf_proof_valid, Some(true));
+        assert_eq!(check.proven_result, Some(check.recorded_result));
+
+        // A tampered record no longer matches the proof
+        bet.result = !bet.result;
+        let check = check_evidence(&bet, &evidence, &vrf.pubkey());
+        assert_eq!(check.proven_result, Some(!check.recorded_result));
+
+        // Proof from another key
+        let check = check_evidence(&bet, &evidence, &Keypair::new().pubkey());
+        assert_eq!(check.vrf_proof_valid, Some(false));
+
+        // No receipt, nothing to check
+        let check = check_evidence(&bet, &DisputeEvidence::default(), &vrf.pubkey());
+        assert_eq!(check.vrf_proof_valid, None);
+    }
+
+    #[test]
+    fn test_dispute_lifecycle() {
+        let vrf = Keypair::new();
+        let bet = bet(&vrf);
+        let store = DisputeStore::new();
+        let check = check_evidence(&bet, &DisputeEvidence::default(), &vrf.pubkey());
+        let file = |reason: &str| {
+            store.file(
+                &bet,
+                reason.to_string(),
+                DisputeEvidence::default(),
+                check.clone(),
+            )
+        };
+
+        assert_eq!(file("  ").unwrap_err(), DisputeError::InvalidText);
+        let dispute = file("Outcome does not match my receipt").unwrap();
+        assert_eq!(dispute.status, DisputeStatus::Open);
+        assert_eq!(
+            file("Again").unwrap_err(),
+            DisputeError::AlreadyDisputed("bet1".to_string())
+        );
+
+        store.start_review(dispute.id).unwrap();
+        assert!(store.start_review(dispute.id).is_err());
+        let resolved = store
+            .resolve(dispute.id, DisputeOutcome::Rejected, "Proof matches".into())
+            .unwrap();
+        assert_eq!(resolved.status, DisputeStatus::Resolved);
+        assert_eq!(
+            resolved.resolution.unwrap().outcome,
+            DisputeOutcome::Rejected
+        );
+        assert_eq!(
+            store
+                .resolve(dispute.id, DisputeOutcome::Upheld, "Changed my mind".into())
+                .unwrap_err(),
+            DisputeError::InvalidTransition(
+                dispute.id,
+                DisputeStatus::Resolved,
+                DisputeStatus::Resolved
+            )
+        );
+
+        // Resolved disputes don't block a new one
+        let second = file("New evidence").unwrap();
+        assert_eq!(store.list()[0].id, second.id);
+        assert!(store.get(404).is_none());
+    }
+}
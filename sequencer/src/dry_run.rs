@@ -0,0 +1,196 @@
+// Settlement dry runs for ZK Casino
+// Repeats what the submitter would do with one batch, without sending anything: rebuild
+// the verifier's settlement data and the circuit witness, re-verify the stored proof,
+// encode the verifier instruction and simulate it on the cluster. Every step reports
+// separately, so a batch that won't land shows which stage breaks. The witness and state
+// roots are rebuilt against the current balances, so for a batch that already settled
+// they describe replaying it, not the original submission.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::settlement_persistence::{
+    SettlementBatch, SettlementBatchStatus, SettlementPersistence,
+};
+use crate::settlement_prover::ProverReadiness;
+use crate::solana::{SettlementSimulation, SolanaClient};
+use crate::submitter::{build_batch_data, placeholder_proof};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum StepResult {
+    Passed,
+    Failed { error: String },
+    Skipped { reason: String },
+}
+
+impl StepResult {
+    fn failed(error: impl ToString) -> Self {
+        Self::Failed {
+            error: error.to_string(),
+        }
+    }
+
+    fn skipped(reason: impl ToString) -> Self {
+        Self::Skipped {
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunReport {
+    pub batch_id: u64,
+    pub status: SettlementBatchStatus,
+    pub bet_count: usize,
+    pub settlement_data: StepResult,
+    pub prev_state_root: Option<String>, // Base58, as rebuilt for the settlement data
+    pub new_state_root: Option<String>,
+    pub vrf_outputs_root: Option<String>,
+    pub witness: StepResult,
+    pub proof: StepResult,
+    pub simulation: StepResult,
+    pub simulated: Option<SettlementSimulation>, // Logs and compute units of the simulation
+}
+
+/// Dry-run settlement of a stored batch
+pub async fn dry_run(
+    batch: &SettlementBatch,
+    persistence: &SettlementPersistence,
+    prover: &ProverReadiness,
+    solana_client: Option<&SolanaClient>,
+    vrf_pubkey: Pubkey,
+) -> DryRunReport {
+    let mut report = DryRunReport {
+        batch_id: batch.batch_id,
+        status: batch.status.clone(),
+        bet_count: batch.items.len(),
+        settlement_data: StepResult::Passed,
+        prev_state_root: None,
+        new_state_root: None,
+        vrf_outputs_root: None,
+        witness: StepResult::Passed,
+        proof: StepResult::Passed,
+        simulation: StepResult::Passed,
+        simulated: None,
+    };
+
+    let batch_data =
+        match build_batch_data(persistence, vrf_pubkey, batch.batch_id, &batch.items).await {
+            Ok(batch_data) => {
+                let root = |root: [u8; 32]| Some(Hash::new_from_array(root).to_string());
+                report.prev_state_root = root(batch_data.prev_state_root);
+                report.new_state_root = root(batch_data.new_state_root);
+                report.vrf_outputs_root = root(batch_data.vrf_outputs_root);
+                Some(batch_data)
+            }
+            Err(e) => {
+                report.settlement_data = StepResult::failed(e);
+                None
+            }
+        };
+
+    let settlement_prover = prover.prover();
+    report.witness = match &settlement_prover {
+        Some(settlement_prover) => {
+            let checked = match persistence.settled_balances().await {
+                Ok(settled) => {
+                    settlement_prover
+                        .check_witness(&settled, &batch.items)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match checked {
+                Ok(()) => StepResult::Passed,
+                Err(e) => StepResult::failed(e),
+            }
+        }
+        None => StepResult::skipped(format!("ZK prover is {:?}", prover.status())),
+    };
+
+    report.proof = match &batch.proof_data {
+        None => StepResult::skipped("No stored proof; the batch settles with a placeholder proof"),
+        Some(proof) => {
+            // The key the proof was stored against, else the one the prover uses now
+            let verifying_key = match &batch.verifying_key_hash {
+                Some(hash) => persistence.get_verifying_key(hash).await,
+                None => settlement_prover
+                    .as_ref()
+                    .map(|prover| prover.verifying_key().to_vec()),
+            };
+            match verifying_key {
+                None => StepResult::skipped("Verifying key for the stored proof is unavailable"),
+                Some(key) => match prover::proof_generator::verify_serialized_proof(&key, proof) {
+                    Ok(true) => StepResult::Passed,
+                    Ok(false) => StepResult::failed("Proof does not verify"),
+                    Err(e) => StepResult::failed(e),
+                },
+            }
+        }
+    };
+
+    report.simulation = match (solana_client, &batch_data) {
+        (None, _) => StepResult::skipped("Solana is not configured"),
+        (_, None) => StepResult::skipped("No settlement data"),
+        (Some(solana_client), Some(batch_data)) => {
+            let proof = batch.proof_data.clone().unwrap_or_else(placeholder_proof);
+            match solana_client.simulate_settlement(batch_data, proof).await {
+                Ok(simulated) => {
+                    let result = match &simulated.error {
+                        Some(error) => StepResult::failed(error),
+                        None => StepResult::Passed,
+                    };
+                    report.simulated = Some(simulated);
+                    result
+                }
+                Err(e) => StepResult::failed(e),
+            }
+        }
+    };
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bet_id::BetIdGenerator;
+    use crate::SettlementItem;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_dry_run_without_prover_or_solana() {
+        let persistence = SettlementPersistence::new("sqlite::memory:").await.unwrap();
+        let items = vec![SettlementItem {
+            bet_id: BetIdGenerator::new(0).next_id().to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            payout: 200,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        }];
+        let batch_id = persistence.create_batch(&items).await.unwrap();
+        let batch = persistence.get_batch(batch_id).await.unwrap();
+
+        let report = dry_run(
+            &batch,
+            &persistence,
+            &ProverReadiness::disabled(),
+            None,
+            Pubkey::new_unique(),
+        )
+        .await;
+        assert_eq!(report.batch_id, batch_id);
+        assert_eq!(report.bet_count, 1);
+        assert_eq!(report.settlement_data, StepResult::Passed);
+        assert!(report.prev_state_root.is_some());
+        assert!(matches!(report.witness, StepResult::Skipped { .. }));
+        assert!(matches!(report.proof, StepResult::Skipped { .. }));
+        assert!(matches!(report.simulation, StepResult::Skipped { .. }));
+        assert!(report.simulated.is_none());
+    }
+}
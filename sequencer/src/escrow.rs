@@ -0,0 +1,420 @@
+// On-chain bet escrow for ZK Casino (hybrid L1 bets)
+// High-value bets are placed through the vault's place_escrowed_bet instruction, which
+// locks the stake on L1. The watcher picks escrowed bets up from vault program events,
+// resolves them like any other coin flip and settles each one directly on-chain: a win
+// releases stake plus winnings, a loss lets the house claim the stake. If the sequencer
+// never settles, the player can refund the escrow after the vault's refund delay.
+// Small bets are unaffected and stay fully off-chain.
+
+use anyhow::Result;
+use axum::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use parking_lot::Mutex;
+use rand::Rng;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{debug, error, info, warn};
+
+use crate::bet_id::BetIdGenerator;
+use crate::database::{Bet, BetEscrowLink, Database};
+use crate::solana::SolanaClient;
+use crate::SettlementToken;
+
+/// Borsh size of BetEscrowedEvent after its discriminator
+const BET_ESCROWED_EVENT_LEN: usize = 32 + 32 + 8 + 8 + 1 + 1 + 8;
+
+#[derive(Debug, Clone)]
+pub struct EscrowConfig {
+    pub min_amount: u64, // Bets at or above this must go through on-chain escrow
+    pub poll_interval: Duration,
+}
+
+impl EscrowConfig {
+    /// Load from environment; None unless ESCROW_MODE=true.
+    /// (ESCROW_MIN_BET_LAMPORTS, ESCROW_POLL_INTERVAL_SECS)
+    pub fn from_env() -> Option<Self> {
+        if std::env::var("ESCROW_MODE").unwrap_or_default() != "true" {
+            return None;
+        }
+        let env_u64 = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        Some(Self {
+            min_amount: env_u64("ESCROW_MIN_BET_LAMPORTS").unwrap_or(1_000_000_000), // 1 SOL
+            poll_interval: Duration::from_secs(env_u64("ESCROW_POLL_INTERVAL_SECS").unwrap_or(2)),
+        })
+    }
+
+    pub fn requires_escrow(&self, amount: u64) -> bool {
+        amount >= self.min_amount
+    }
+}
+
+/// A bet whose stake is locked in a vault escrow account
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscrowedBet {
+    pub user: Pubkey,
+    pub escrow: Pubkey,
+    pub nonce: u64,
+    pub amount: u64,
+    pub token: SettlementToken,
+    pub guess: bool,
+    pub timestamp: i64,
+}
+
+/// Anchor event discriminator: first 8 bytes of sha256("event:<name>")
+fn event_discriminator(name: &str) -> [u8; 8] {
+    let hash = solana_sdk::hash::hash(format!("event:{}", name).as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash.to_bytes()[..8]);
+    discriminator
+}
+
+fn decode_bet_escrowed(data: &[u8]) -> Option<EscrowedBet> {
+    if data.len() < BET_ESCROWED_EVENT_LEN {
+        return None;
+    }
+    let pubkey = |at: usize| Pubkey::try_from(&data[at..at + 32]).ok();
+    let u64_at = |at: usize| data[at..at + 8].try_into().ok().map(u64::from_le_bytes);
+    let token = match data[80] {
+        0 => SettlementToken::Sol,
+        1 => SettlementToken::Usdc,
+        _ => return None,
+    };
+    Some(EscrowedBet {
+        user: pubkey(0)?,
+        escrow: pubkey(32)?,
+        nonce: u64_at(64)?,
+        amount: u64_at(72)?,
+        token,
+        guess: data[81] != 0,
+        timestamp: u64_at(82)? as i64,
+    })
+}
+
+/// Escrowed bets announced in a vault transaction's logs ("Program data: <base64>")
+pub fn parse_escrow_events(logs: &[String]) -> Vec<EscrowedBet> {
+    let discriminator = event_discriminator("BetEscrowedEvent");
+    logs.iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .filter_map(|data| BASE64.decode(data.trim()).ok())
+        .filter(|data| data.starts_with(&discriminator))
+        .filter_map(|data| decode_bet_escrowed(&data[8..]))
+        .collect()
+}
+
+/// Vault program access, so the watcher can run against a mock chain in tests
+#[async_trait]
+pub trait EscrowChain: Send + Sync {
+    /// Vault transactions after `until`, oldest first
+    async fn new_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>>;
+    async fn logs(&self, signature: &Signature) -> Result<Vec<String>>;
+    /// False once the escrow was settled or refunded (the account is closed)
+    async fn escrow_is_open(&self, escrow: &Pubkey) -> Result<bool>;
+    async fn settle(&self, bet: &EscrowedBet, won: bool, payout: u64) -> Result<String>;
+}
+
+#[async_trait]
+impl EscrowChain for SolanaClient {
+    async fn new_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>> {
+        self.get_vault_signatures(until).await
+    }
+
+    async fn logs(&self, signature: &Signature) -> Result<Vec<String>> {
+        self.get_transaction_logs(signature).await
+    }
+
+    async fn escrow_is_open(&self, escrow: &Pubkey) -> Result<bool> {
+        Ok(self.get_balance(escrow).await? > 0)
+    }
+
+    async fn settle(&self, bet: &EscrowedBet, won: bool, payout: u64) -> Result<String> {
+        Ok(self
+            .settle_escrowed_bet(&bet.user, &bet.escrow, won, payout)
+            .await?
+            .to_string())
+    }
+}
+
+/// A resolved escrowed bet waiting for its on-chain settlement to land
+#[derive(Debug, Clone)]
+struct PendingSettlement {
+    bet: EscrowedBet,
+    bet_id: String,
+    won: bool,
+    payout: u64,
+}
+
+/// Picks up escrowed bets from vault events and settles them on-chain
+pub struct EscrowWatcher {
+    config: EscrowConfig,
+    chain: Arc<dyn EscrowChain>,
+    db: Arc<Database>,
+    bet_ids: Arc<BetIdGenerator>,
+    cursor: Mutex<Option<Signature>>, // Last vault transaction scanned
+    pending: Mutex<HashMap<Pubkey, PendingSettlement>>, // By escrow account
+}
+
+impl EscrowWatcher {
+    pub fn new(
+        config: EscrowConfig,
+        chain: Arc<dyn EscrowChain>,
+        db: Arc<Database>,
+        bet_ids: Arc<BetIdGenerator>,
+    ) -> Self {
+        Self {
+            config,
+            chain,
+            db,
+            bet_ids,
+            cursor: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Scan new vault transactions, then settle every resolved escrow. Returns bets settled.
+    pub async fn poll_once(&self) -> Result<usize> {
+        let cursor = *self.cursor.lock();
+        for signature in self.chain.new_signatures(cursor).await? {
+            let logs = self.chain.logs(&signature).await?;
+            for bet in parse_escrow_events(&logs) {
+                self.pick_up(bet).await?;
+            }
+            // Advance only past fully handled transactions so failures are retried
+            *self.cursor.lock() = Some(signature);
+        }
+        Ok(self.settle_pending().await)
+    }
+
+    /// Resolve a newly escrowed bet and record it
+    async fn pick_up(&self, bet: EscrowedBet) -> Result<()> {
+        if self.pending.lock().contains_key(&bet.escrow) {
+            return Ok(());
+        }
+        // Already settled or refunded (e.g. rescanned after a restart)
+        if !self.chain.escrow_is_open(&bet.escrow).await? {
+            debug!("Escrow {} is closed, skipping", bet.escrow);
+            return Ok(());
+        }
+
+        let coin_result = rand::thread_rng().gen::<bool>();
+        let won = bet.guess == coin_result;
+        let payout = if won { bet.amount * 2 } else { 0 };
+        let bet_id = self.bet_ids.next_id();
+
+        // The stake lives in the on-chain vault, so off-chain balances are untouched
+        self.db
+            .save_bet(&Bet {
+                id: bet_id.to_string(),
+                player_address: bet.user.to_string(),
+                amount: bet.amount as i64,
+                guess: bet.guess,
+                result: coin_result,
+                won,
+                payout: payout as i64,
+                timestamp: bet_id.timestamp(),
+                settlement: None,
+                escrow: Some(BetEscrowLink {
+                    escrow_account: bet.escrow.to_string(),
+                    settle_signature: None,
+                }),
+            })
+            .await?;
+
+        info!(
+            "Escrowed bet {} picked up: {} {:?} from {} (won: {})",
+            bet_id, bet.amount, bet.token, bet.user, won
+        );
+        self.pending.lock().insert(
+            bet.escrow,
+            PendingSettlement {
+                bet,
+                bet_id: bet_id.to_string(),
+                won,
+                payout,
+            },
+        );
+        Ok(())
+    }
+
+    /// Settle resolved escrows; failures keep their outcome and are retried next poll
+    async fn settle_pending(&self) -> usize {
+        let pending: Vec<PendingSettlement> = self.pending.lock().values().cloned().collect();
+        let mut settled = 0;
+        for entry in pending {
+            match self.chain.settle(&entry.bet, entry.won, entry.payout).await {
+                Ok(signature) => {
+                    self.pending.lock().remove(&entry.bet.escrow);
+                    if let Err(e) = self.db.mark_escrow_settled(&entry.bet_id, &signature).await {
+                        error!(
+                            "Failed to record escrow settlement for {}: {}",
+                            entry.bet_id, e
+                        );
+                    }
+                    settled += 1;
+                }
+                Err(e) => warn!(
+                    "Failed to settle escrowed bet {} ({}): {}",
+                    entry.bet_id, entry.bet.escrow, e
+                ),
+            }
+        }
+        settled
+    }
+
+    /// Start polling the vault program (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting escrow watcher (min escrow bet: {}, poll interval: {:?})",
+            self.config.min_amount, self.config.poll_interval
+        );
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.poll_once().await {
+                    warn!("Escrow poll failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn event_log(bet: &EscrowedBet) -> String {
+        let mut data = event_discriminator("BetEscrowedEvent").to_vec();
+        data.extend_from_slice(bet.user.as_ref());
+        data.extend_from_slice(bet.escrow.as_ref());
+        data.extend_from_slice(&bet.nonce.to_le_bytes());
+        data.extend_from_slice(&bet.amount.to_le_bytes());
+        data.push(bet.token as u8);
+        data.push(bet.guess as u8);
+        data.extend_from_slice(&bet.timestamp.to_le_bytes());
+        format!("Program data: {}", BASE64.encode(data))
+    }
+
+    fn escrowed_bet(amount: u64) -> EscrowedBet {
+        EscrowedBet {
+            user: Pubkey::new_unique(),
+            escrow: Pubkey::new_unique(),
+            nonce: 7,
+            amount,
+            token: SettlementToken::Usdc,
+            guess: true,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[derive(Default)]
+    struct MockChain {
+        transactions: Mutex<Vec<(Signature, Vec<String>)>>,
+        closed: Mutex<HashSet<Pubkey>>,
+        settlements: Mutex<Vec<(Pubkey, bool, u64)>>,
+        failures_left: AtomicU32,
+    }
+
+    #[async_trait]
+    impl EscrowChain for MockChain {
+        async fn new_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>> {
+            let transactions = self.transactions.lock();
+            let start = until
+                .and_then(|until| transactions.iter().position(|(s, _)| *s == until))
+                .map_or(0, |i| i + 1);
+            Ok(transactions[start..].iter().map(|(s, _)| *s).collect())
+        }
+
+        async fn logs(&self, signature: &Signature) -> Result<Vec<String>> {
+            let transactions = self.transactions.lock();
+            let (_, logs) = transactions.iter().find(|(s, _)| s == signature).unwrap();
+            Ok(logs.clone())
+        }
+
+        async fn escrow_is_open(&self, escrow: &Pubkey) -> Result<bool> {
+            Ok(!self.closed.lock().contains(escrow))
+        }
+
+        async fn settle(&self, bet: &EscrowedBet, won: bool, payout: u64) -> Result<String> {
+            if self.failures_left.load(Ordering::SeqCst) > 0 {
+                self.failures_left.fetch_sub(1, Ordering::SeqCst);
+                return Err(anyhow::anyhow!("blockhash expired"));
+            }
+            self.closed.lock().insert(bet.escrow);
+            self.settlements.lock().push((bet.escrow, won, payout));
+            Ok(Signature::new_unique().to_string())
+        }
+    }
+
+    #[test]
+    fn test_parse_escrow_events() {
+        let bet = escrowed_bet(2_000_000_000);
+        let logs = vec![
+            "Program log: Instruction: PlaceEscrowedBet".to_string(),
+            event_log(&bet),
+            "Program data: bm90IGFuIGV2ZW50".to_string(), // Other data is ignored
+        ];
+        assert_eq!(parse_escrow_events(&logs), vec![bet]);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_settles_escrowed_bets_once() {
+        let chain = Arc::new(MockChain::default());
+        let db = Arc::new(Database::new("").await.unwrap());
+        let watcher = EscrowWatcher::new(
+            EscrowConfig {
+                min_amount: 1_000_000_000,
+                poll_interval: Duration::from_secs(1),
+            },
+            chain.clone(),
+            db.clone(),
+            Arc::new(BetIdGenerator::new(0)),
+        );
+
+        // An escrow already closed before the watcher saw it (settled before a restart)
+        let stale = escrowed_bet(1_000_000_000);
+        chain.closed.lock().insert(stale.escrow);
+        let bet = escrowed_bet(2_000_000_000);
+        chain
+            .transactions
+            .lock()
+            .push((Signature::new_unique(), vec![event_log(&stale)]));
+        chain
+            .transactions
+            .lock()
+            .push((Signature::new_unique(), vec![event_log(&bet)]));
+
+        // First settlement attempt fails; the outcome is kept for the retry
+        chain.failures_left.store(1, Ordering::SeqCst);
+        assert_eq!(watcher.poll_once().await.unwrap(), 0);
+        assert_eq!(watcher.poll_once().await.unwrap(), 1);
+        assert_eq!(watcher.poll_once().await.unwrap(), 0);
+
+        let settlements = chain.settlements.lock().clone();
+        assert_eq!(settlements.len(), 1);
+        let (escrow, won, payout) = settlements[0];
+        assert_eq!(escrow, bet.escrow);
+        assert_eq!(payout, if won { 4_000_000_000 } else { 0 });
+
+        let bets = db
+            .get_player_bets(&bet.user.to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(bets.len(), 1);
+        assert_eq!(bets[0].won, won);
+        let link = bets[0].escrow.as_ref().unwrap();
+        assert_eq!(link.escrow_account, bet.escrow.to_string());
+        assert!(link.settle_signature.is_some());
+        assert!(db
+            .get_player_bets(&stale.user.to_string(), None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+// Static batch export for ZK Casino transparency drops
+// Writes a self-contained bundle for a range of confirmed batches: a manifest with
+// each batch's bets and settlement transaction, the Groth16 proof bytes and the
+// verifying keys those proofs were checked against. Anyone can verify the bundle
+// offline with the prover crate (`cargo run -p prover --example verify_bundle -- <dir>`)
+// and compare the key hashes with the one published at /v1/transparency.
+//
+// Bundle layout:
+//   manifest.json
+//   proofs/batch_<id>.bin   SerializableProof::to_bytes
+//   keys/<key hash>.bin     Compressed verifying key, named by its base58 SHA-256
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use tokio::fs;
+
+use crate::settlement_persistence::SettlementPersistence;
+use crate::SettlementItem;
+
+pub const EXPORT_FORMAT: &str = "zkcasino-batch-export-v1";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub format: String,
+    pub exported_at: DateTime<Utc>,
+    pub from_batch: u64,
+    pub to_batch: u64,
+    pub batches: Vec<ExportedBatch>,
+    pub verifying_keys: Vec<ExportedKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedBatch {
+    pub batch_id: u64,
+    pub confirmed_at: DateTime<Utc>,
+    pub transaction_signature: Option<String>,
+    pub proof_file: Option<String>, // None when settled with a placeholder proof
+    pub verifying_key_hash: Option<String>,
+    pub bets: Vec<SettlementItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedKey {
+    pub hash: String,
+    pub file: String,
+}
+
+/// Export confirmed batches with ids in [from, to] into `out_dir`
+pub async fn export_batches(
+    persistence: &SettlementPersistence,
+    from: u64,
+    to: u64,
+    out_dir: &Path,
+) -> Result<ExportManifest> {
+    if from > to {
+        return Err(anyhow!("Invalid batch range {}..={}", from, to));
+    }
+    fs::create_dir_all(out_dir.join("proofs")).await?;
+    fs::create_dir_all(out_dir.join("keys")).await?;
+
+    let mut batches = Vec::new();
+    let mut key_hashes = BTreeSet::new();
+    for batch in persistence.get_confirmed_batches_in_range(from, to).await? {
+        let proof_file = match &batch.proof_data {
+            Some(proof) => {
+                let file = format!("proofs/batch_{}.bin", batch.batch_id);
+                fs::write(out_dir.join(&file), proof).await?;
+                Some(file)
+            }
+            None => None,
+        };
+        if let Some(hash) = &batch.verifying_key_hash {
+            key_hashes.insert(hash.clone());
+        }
+        batches.push(ExportedBatch {
+            batch_id: batch.batch_id,
+            confirmed_at: batch.updated_at,
+            transaction_signature: batch.transaction_signature,
+            proof_file,
+            verifying_key_hash: batch.verifying_key_hash,
+            bets: batch.items,
+        });
+    }
+
+    let mut verifying_keys = Vec::new();
+    for hash in key_hashes {
+        let key = persistence
+            .get_verifying_key(&hash)
+            .await
+            .ok_or_else(|| anyhow!("Verifying key {} is not in the settlement file", hash))?;
+        let file = format!("keys/{}.bin", hash);
+        fs::write(out_dir.join(&file), key).await?;
+        verifying_keys.push(ExportedKey { hash, file });
+    }
+
+    let manifest = ExportManifest {
+        format: EXPORT_FORMAT.to_string(),
+        exported_at: Utc::now(),
+        from_batch: from,
+        to_batch: to,
+        batches,
+        verifying_keys,
+    };
+    fs::write(
+        out_dir.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )
+    .await?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement_persistence::SettlementBatchStatus;
+
+    #[tokio::test]
+    async fn test_export_batches() {
+        let persistence = SettlementPersistence::new("sqlite::memory:").await.unwrap();
+        let item = |bet_id: &str| SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            payout: 200,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        };
+        let confirm = |batch_id| {
+            persistence.update_batch_status(batch_id, SettlementBatchStatus::Confirmed, None)
+        };
+
+        let proven = persistence.create_batch(&[item("a")]).await.unwrap();
+        persistence
+            .store_proof(proven, &[7; 64], &[1; 32])
+            .await
+            .unwrap();
+        confirm(proven).await.unwrap();
+        let placeholder = persistence.create_batch(&[item("b")]).await.unwrap();
+        confirm(placeholder).await.unwrap();
+        let pending = persistence.create_batch(&[item("c")]).await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("zkcasino-export-{}", uuid::Uuid::new_v4()));
+        let manifest = export_batches(&persistence, proven, pending, &dir)
+            .await
+            .unwrap();
+
+        // Unconfirmed batches are left out; placeholder batches are listed without a proof
+        let ids: Vec<u64> = manifest.batches.iter().map(|b| b.batch_id).collect();
+        assert_eq!(ids, vec![proven, placeholder]);
+        assert_eq!(manifest.batches[1].proof_file, None);
+
+        let proof_file = manifest.batches[0].proof_file.as_ref().unwrap();
+        assert_eq!(std::fs::read(dir.join(proof_file)).unwrap(), vec![7; 64]);
+        let key_hash = solana_sdk::hash::hash(&[1; 32]).to_string();
+        assert_eq!(manifest.verifying_keys.len(), 1);
+        assert_eq!(manifest.verifying_keys[0].hash, key_hash);
+        assert_eq!(
+            std::fs::read(dir.join(&manifest.verifying_keys[0].file)).unwrap(),
+            vec![1; 32]
+        );
+
+        let written: ExportManifest =
+            serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+        assert_eq!(written.format, EXPORT_FORMAT);
+        assert_eq!(written.batches.len(), 2);
+
+        assert!(export_batches(&persistence, 5, 1, &dir).await.is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
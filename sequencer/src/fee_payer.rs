@@ -0,0 +1,473 @@
+// Sequencer fee-payer monitoring for ZK Casino
+// The sequencer key pays every settlement, staging and memo transaction. This watches
+// its balance: dropping below the alert threshold raises an alert (logged, kept for the
+// admin API and optionally posted to a webhook) once, until the balance recovers. With
+// a refill account configured the wallet is also topped back up to the refill target.
+// Before the settlement submitter starts, the balance must cover the estimated fees of
+// the batches still queued for submission, so it never begins a backlog it can't pay for.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use solana_sdk::signer::Signer;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{error, info, warn};
+
+use crate::settlement_persistence::{SettlementBatchStatus, SettlementPersistence};
+use crate::treasury::TreasuryLedger;
+
+/// Refills kept in memory for the admin API
+const MAX_RETAINED_REFILLS: usize = 100;
+
+pub struct FeePayerConfig {
+    pub alert_threshold_lamports: u64,
+    pub refill_source: Option<Arc<Keypair>>, // Treasury account refills are paid from
+    pub refill_target_lamports: u64,         // Refill back up to this
+    pub fee_per_batch_lamports: u64, // Fee estimate until settlement costs have been recorded
+    pub check_interval: Duration,
+    pub webhook_url: Option<String>,
+}
+
+impl Default for FeePayerConfig {
+    fn default() -> Self {
+        Self {
+            alert_threshold_lamports: 50_000_000, // 0.05 SOL
+            refill_source: None,
+            refill_target_lamports: 0,
+            fee_per_batch_lamports: 10_000,
+            check_interval: Duration::from_secs(60),
+            webhook_url: None,
+        }
+    }
+}
+
+impl FeePayerConfig {
+    /// Load from environment (FEE_PAYER_ALERT_THRESHOLD_LAMPORTS,
+    /// FEE_PAYER_REFILL_KEYPAIR_PATH, FEE_PAYER_REFILL_TARGET_LAMPORTS,
+    /// FEE_PAYER_FEE_PER_BATCH_LAMPORTS, FEE_PAYER_CHECK_INTERVAL_SECS,
+    /// FEE_PAYER_ALERT_WEBHOOK_URL); auto-refill is off unless a refill keypair is set
+    pub fn from_env() -> Result<Self> {
+        fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T>
+        where
+            T::Err: std::fmt::Display,
+        {
+            match std::env::var(name) {
+                Ok(v) => v.parse().map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(default),
+            }
+        }
+
+        let defaults = Self::default();
+        let refill_source = match std::env::var("FEE_PAYER_REFILL_KEYPAIR_PATH") {
+            Ok(path) => Some(Arc::new(read_keypair_file(&path).map_err(|e| {
+                anyhow!(
+                    "Failed to read fee payer refill keypair from {}: {}",
+                    path,
+                    e
+                )
+            })?)),
+            Err(_) => None,
+        };
+        let config = Self {
+            alert_threshold_lamports: env_or(
+                "FEE_PAYER_ALERT_THRESHOLD_LAMPORTS",
+                defaults.alert_threshold_lamports,
+            )?,
+            refill_source,
+            refill_target_lamports: env_or(
+                "FEE_PAYER_REFILL_TARGET_LAMPORTS",
+                defaults.refill_target_lamports,
+            )?,
+            fee_per_batch_lamports: env_or(
+                "FEE_PAYER_FEE_PER_BATCH_LAMPORTS",
+                defaults.fee_per_batch_lamports,
+            )?,
+            check_interval: Duration::from_secs(env_or(
+                "FEE_PAYER_CHECK_INTERVAL_SECS",
+                defaults.check_interval.as_secs(),
+            )?),
+            webhook_url: std::env::var("FEE_PAYER_ALERT_WEBHOOK_URL").ok(),
+        };
+        if config.refill_source.is_some()
+            && config.refill_target_lamports <= config.alert_threshold_lamports
+        {
+            return Err(anyhow!(
+                "FEE_PAYER_REFILL_TARGET_LAMPORTS must be above FEE_PAYER_ALERT_THRESHOLD_LAMPORTS"
+            ));
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FeePayerRefill {
+    pub from: String,
+    pub lamports: u64,
+    pub balance_before: u64,
+    pub transaction_signature: Option<String>,
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeePayerStatus {
+    pub fee_payer: String,
+    pub balance_lamports: Option<u64>, // None until the first check
+    pub alert_threshold_lamports: u64,
+    pub low: bool,
+    pub auto_refill: bool,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub refills: Vec<FeePayerRefill>, // Most recent first
+}
+
+#[derive(Default)]
+struct MonitorState {
+    balance: Option<u64>,
+    checked_at: Option<DateTime<Utc>>,
+    low: bool,
+    refills: VecDeque<FeePayerRefill>,
+}
+
+pub struct FeePayerMonitor {
+    config: FeePayerConfig,
+    ledger: Arc<dyn TreasuryLedger>,
+    fee_payer: Pubkey,
+    state: Mutex<MonitorState>,
+    client: reqwest::Client,
+}
+
+impl FeePayerMonitor {
+    pub fn new(config: FeePayerConfig, ledger: Arc<dyn TreasuryLedger>, fee_payer: Pubkey) -> Self {
+        Self {
+            config,
+            ledger,
+            fee_payer,
+            state: Mutex::new(MonitorState::default()),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Read the fee-payer balance, alert if it is low and refill it if configured
+    pub async fn check(&self) -> Result<FeePayerStatus> {
+        let mut balance = self.ledger.balance(&self.fee_payer).await?;
+
+        if balance < self.config.alert_threshold_lamports {
+            if let Some(source) = &self.config.refill_source {
+                let refill = self.refill(source, balance).await;
+                let refilled = refill.error.is_none();
+                self.record_refill(refill);
+                if refilled {
+                    balance = self.ledger.balance(&self.fee_payer).await?;
+                }
+            }
+        }
+
+        let low = balance < self.config.alert_threshold_lamports;
+        let newly_low = {
+            let mut state = self.state.lock();
+            let newly_low = low && !state.low;
+            if !low && state.low {
+                info!(
+                    "Fee payer {} balance recovered: {} lamports",
+                    self.fee_payer, balance
+                );
+            }
+            state.balance = Some(balance);
+            state.checked_at = Some(Utc::now());
+            state.low = low;
+            newly_low
+        };
+
+        let status = self.status();
+        if newly_low {
+            warn!(
+                "Fee payer {} balance {} lamports is below the alert threshold of {}",
+                self.fee_payer, balance, self.config.alert_threshold_lamports
+            );
+            self.notify(&status);
+        }
+        Ok(status)
+    }
+
+    async fn refill(&self, source: &Keypair, balance: u64) -> FeePayerRefill {
+        let lamports = self.config.refill_target_lamports.saturating_sub(balance);
+        let mut refill = FeePayerRefill {
+            from: source.pubkey().to_string(),
+            lamports,
+            balance_before: balance,
+            transaction_signature: None,
+            error: None,
+            at: Utc::now(),
+        };
+        match self
+            .ledger
+            .transfer(source, &self.fee_payer, lamports)
+            .await
+        {
+            Ok(signature) => {
+                info!(
+                    "Refilled fee payer {} with {} lamports from {}: {}",
+                    self.fee_payer, lamports, refill.from, signature
+                );
+                refill.transaction_signature = Some(signature);
+            }
+            Err(e) => {
+                error!("Failed to refill fee payer {}: {}", self.fee_payer, e);
+                refill.error = Some(e.to_string());
+            }
+        }
+        refill
+    }
+
+    fn record_refill(&self, refill: FeePayerRefill) {
+        let mut state = self.state.lock();
+        state.refills.push_front(refill);
+        state.refills.truncate(MAX_RETAINED_REFILLS);
+    }
+
+    /// Post a low-balance alert to the webhook, if configured, without blocking the caller
+    fn notify(&self, status: &FeePayerStatus) {
+        let Some(url) = self.config.webhook_url.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let request = self.client.post(url).json(status);
+        runtime.spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => warn!("Failed to deliver fee payer alert: {}", e),
+            }
+        });
+    }
+
+    pub fn status(&self) -> FeePayerStatus {
+        let state = self.state.lock();
+        FeePayerStatus {
+            fee_payer: self.fee_payer.to_string(),
+            balance_lamports: state.balance,
+            alert_threshold_lamports: self.config.alert_threshold_lamports,
+            low: state.low,
+            auto_refill: self.config.refill_source.is_some(),
+            checked_at: state.checked_at,
+            refills: state.refills.iter().cloned().collect(),
+        }
+    }
+
+    /// Estimated fees for every batch not yet submitted: the average recorded settlement
+    /// fee per batch, or the configured estimate before any cost has been recorded
+    pub async fn estimate_queued_fees(&self, persistence: &SettlementPersistence) -> Result<u64> {
+        let queued = persistence
+            .get_pending_batches()
+            .await?
+            .into_iter()
+            .filter(|batch| batch.status != SettlementBatchStatus::Submitted)
+            .count() as u64;
+        let costs = persistence.get_cost_summary().await?;
+        let per_batch = if costs.batches > 0 {
+            costs.avg_fee_per_batch_lamports.ceil() as u64
+        } else {
+            self.config.fee_per_batch_lamports
+        };
+        Ok(queued.saturating_mul(per_batch))
+    }
+
+    /// Refuse to start the settlement submitter unless the fee payer (after any refill)
+    /// can cover the estimated fees of the queued batches
+    pub async fn preflight(&self, persistence: &SettlementPersistence) -> Result<()> {
+        let balance = self.check().await?.balance_lamports.unwrap_or_default();
+        let required = self.estimate_queued_fees(persistence).await?;
+        if balance < required {
+            return Err(anyhow!(
+                "Fee payer {} holds {} lamports but queued batches need an estimated {}; \
+                 not starting the settlement submitter",
+                self.fee_payer,
+                balance,
+                required
+            ));
+        }
+        info!(
+            "Fee payer {} holds {} lamports ({} estimated for queued batches)",
+            self.fee_payer, balance, required
+        );
+        Ok(())
+    }
+
+    /// Start the periodic balance check (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Monitoring fee payer {} (alert below {} lamports, auto-refill {})",
+            self.fee_payer,
+            self.config.alert_threshold_lamports,
+            if self.config.refill_source.is_some() {
+                "on"
+            } else {
+                "off"
+            }
+        );
+
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.check_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.check().await {
+                    error!("Fee payer balance check failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settlement_persistence::SettlementCost;
+    use crate::SettlementItem;
+    use axum::async_trait;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MockLedger {
+        balances: Mutex<HashMap<Pubkey, u64>>,
+        fail_transfers: bool,
+    }
+
+    #[async_trait]
+    impl TreasuryLedger for MockLedger {
+        async fn balance(&self, account: &Pubkey) -> Result<u64> {
+            Ok(*self.balances.lock().get(account).unwrap_or(&0))
+        }
+
+        async fn transfer(&self, from: &Keypair, to: &Pubkey, lamports: u64) -> Result<String> {
+            if self.fail_transfers {
+                return Err(anyhow!("transfer rejected"));
+            }
+            let mut balances = self.balances.lock();
+            let source = balances.entry(from.pubkey()).or_default();
+            *source = source
+                .checked_sub(lamports)
+                .ok_or_else(|| anyhow!("insufficient funds"))?;
+            *balances.entry(*to).or_default() += lamports;
+            Ok("mock_signature".to_string())
+        }
+    }
+
+    fn config(refill_source: Option<Arc<Keypair>>) -> FeePayerConfig {
+        FeePayerConfig {
+            alert_threshold_lamports: 1_000,
+            refill_source,
+            refill_target_lamports: 5_000,
+            fee_per_batch_lamports: 400,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_low_balance_alert_and_refill() {
+        let fee_payer = Pubkey::new_unique();
+        let ledger = Arc::new(MockLedger::default());
+        ledger.balances.lock().insert(fee_payer, 800);
+
+        // Without a refill account the balance stays low
+        let monitor = FeePayerMonitor::new(config(None), ledger.clone(), fee_payer);
+        let status = monitor.check().await.unwrap();
+        assert!(status.low);
+        assert_eq!(status.balance_lamports, Some(800));
+        assert!(status.refills.is_empty());
+
+        // With one, it is topped back up to the target
+        let source = Arc::new(Keypair::new());
+        ledger.balances.lock().insert(source.pubkey(), 10_000);
+        let monitor = FeePayerMonitor::new(config(Some(source.clone())), ledger.clone(), fee_payer);
+        let status = monitor.check().await.unwrap();
+        assert!(!status.low);
+        assert_eq!(status.balance_lamports, Some(5_000));
+        assert_eq!(status.refills.len(), 1);
+        assert_eq!(status.refills[0].lamports, 4_200);
+        assert_eq!(ledger.balances.lock()[&source.pubkey()], 5_800);
+
+        // Above the threshold nothing moves
+        let status = monitor.check().await.unwrap();
+        assert_eq!(status.refills.len(), 1);
+
+        // A failed refill is recorded and the balance reported low
+        let failing = Arc::new(MockLedger {
+            fail_transfers: true,
+            ..Default::default()
+        });
+        failing.balances.lock().insert(fee_payer, 10);
+        let monitor = FeePayerMonitor::new(config(Some(source)), failing, fee_payer);
+        let status = monitor.check().await.unwrap();
+        assert!(status.low);
+        assert_eq!(
+            status.refills[0].error.as_deref(),
+            Some("transfer rejected")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preflight_covers_queued_batches() {
+        let persistence = SettlementPersistence::new("sqlite::memory:").await.unwrap();
+        let item = SettlementItem {
+            bet_id: "bet".to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            payout: 0,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        };
+        for _ in 0..3 {
+            persistence
+                .create_batch(std::slice::from_ref(&item))
+                .await
+                .unwrap();
+        }
+
+        let fee_payer = Pubkey::new_unique();
+        let ledger = Arc::new(MockLedger::default());
+        ledger.balances.lock().insert(fee_payer, 1_100);
+        let monitor = FeePayerMonitor::new(config(None), ledger.clone(), fee_payer);
+
+        // Three queued batches at the configured 400 lamports each
+        assert_eq!(
+            monitor.estimate_queued_fees(&persistence).await.unwrap(),
+            1_200
+        );
+        assert!(monitor.preflight(&persistence).await.is_err());
+
+        // Once costs are recorded their average replaces the configured estimate
+        let settled = persistence.create_batch(&[item]).await.unwrap();
+        persistence
+            .update_batch_status(settled, SettlementBatchStatus::Confirmed, None)
+            .await
+            .unwrap();
+        persistence
+            .store_cost(
+                settled,
+                SettlementCost {
+                    fee_lamports: 300,
+                    priority_fee_lamports: 0,
+                    compute_units_consumed: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            monitor.estimate_queued_fees(&persistence).await.unwrap(),
+            900
+        );
+        monitor.preflight(&persistence).await.unwrap();
+    }
+}
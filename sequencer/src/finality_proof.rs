@@ -0,0 +1,246 @@
+// Batch finality proofs for ZK Casino
+// Systems embedded elsewhere (a bridge, a partner's backend) may need to know a batch is
+// final on Solana without running an RPC node of their own. A finality proof packages
+// the batch's settlement transaction with the confirmation data an RPC node gives for
+// it: the block that included it and the chain of finalized blocks built on top of it,
+// each naming its parent's blockhash, at least FINALITY_DEPTH deep. The package is
+// signed with the sequencer key. A client checks the signature, that the transaction
+// carries the batch's signature, and that the blocks link up; matching any one of the
+// blockhashes against a hash it already trusts (a light client checkpoint, a second
+// RPC provider) then vouches for the whole chain. The transaction's inclusion in its
+// block remains the sequencer's statement: RPC nodes don't serve the PoH entries it
+// would take to prove it.
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::VersionedTransaction;
+use std::str::FromStr;
+
+/// Domain separator, so a proof signature can't pass for any other sequencer signature
+const DOMAIN: &[u8] = b"zkcasino-finality-v1:";
+
+/// Blocks on top of the including block before a proof is issued. With 32 confirmed
+/// descendants a block has reached the maximum vote lockout and is rooted.
+pub const FINALITY_DEPTH: usize = 32;
+
+/// One block of the confirmation chain, as returned by getBlock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockLink {
+    pub slot: u64,
+    pub parent_slot: u64,
+    pub blockhash: String,          // Base58
+    pub previous_blockhash: String, // Base58; the parent block's blockhash
+    pub block_time: Option<i64>,
+}
+
+/// A finalized settlement transaction with the blocks confirming it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettlementConfirmation {
+    pub slot: u64,
+    pub transaction: String,    // Base64 wire-format transaction
+    pub blocks: Vec<BlockLink>, // Including block first, then each child in turn
+}
+
+/// Proof contents covered by the signature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FinalityProofBody {
+    pub batch_id: u64,
+    pub transaction_signature: String,
+    pub transaction: String, // Base64 wire-format settlement transaction
+    pub slot: u64,           // Slot of the including block
+    pub blocks: Vec<BlockLink>,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl FinalityProofBody {
+    pub fn new(
+        batch_id: u64,
+        transaction_signature: &Signature,
+        confirmation: SettlementConfirmation,
+        generated_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            batch_id,
+            transaction_signature: transaction_signature.to_string(),
+            transaction: confirmation.transaction,
+            slot: confirmation.slot,
+            blocks: confirmation.blocks,
+            generated_at,
+        }
+    }
+
+    /// Check the transaction against its signature and the block chain's links and
+    /// depth. Trusts no blockhash; see contains_block for anchoring the chain.
+    pub fn verify_chain(&self) -> Result<bool> {
+        let transaction: VersionedTransaction = bincode::deserialize(
+            &BASE64
+                .decode(&self.transaction)
+                .map_err(|e| anyhow!("Invalid transaction encoding: {}", e))?,
+        )?;
+        let signature = Signature::from_str(&self.transaction_signature)
+            .map_err(|e| anyhow!("Invalid transaction signature: {}", e))?;
+        if transaction.signatures.first() != Some(&signature)
+            || !transaction.verify_with_results().iter().all(|valid| *valid)
+        {
+            return Ok(false);
+        }
+
+        let Some(first) = self.blocks.first() else {
+            return Ok(false);
+        };
+        Ok(first.slot == self.slot
+            && self.blocks.len() > FINALITY_DEPTH
+            && self.blocks.windows(2).all(|pair| {
+                pair[1].slot > pair[0].slot
+                    && pair[1].parent_slot == pair[0].slot
+                    && pair[1].previous_blockhash == pair[0].blockhash
+            }))
+    }
+
+    /// Whether the chain has `blockhash` at `slot`, to anchor it to a trusted hash
+    #[allow(dead_code)]
+    pub fn contains_block(&self, slot: u64, blockhash: &Hash) -> bool {
+        let blockhash = blockhash.to_string();
+        self.blocks
+            .iter()
+            .any(|block| block.slot == slot && block.blockhash == blockhash)
+    }
+
+    fn signed_message(&self) -> Result<Vec<u8>> {
+        let mut message = DOMAIN.to_vec();
+        message.extend(serde_json::to_vec(self)?);
+        Ok(message)
+    }
+}
+
+/// Signed finality proof (ed25519 over the domain and the JSON encoding of the body)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalityProof {
+    #[serde(flatten)]
+    pub body: FinalityProofBody,
+    pub signer: String,
+    pub signature: String,
+}
+
+impl FinalityProof {
+    /// Sign a proof body with the sequencer key
+    pub fn sign(body: FinalityProofBody, signer: &Keypair) -> Result<Self> {
+        let signature = signer.sign_message(&body.signed_message()?);
+        Ok(Self {
+            body,
+            signer: signer.pubkey().to_string(),
+            signature: signature.to_string(),
+        })
+    }
+
+    /// Check the signature against the embedded signer, then the transaction and chain
+    #[allow(dead_code)]
+    pub fn verify(&self) -> Result<bool> {
+        let signer =
+            Pubkey::from_str(&self.signer).map_err(|e| anyhow!("Invalid signer: {}", e))?;
+        let signature = Signature::from_str(&self.signature)
+            .map_err(|e| anyhow!("Invalid signature: {}", e))?;
+        Ok(
+            signature.verify(signer.as_ref(), &self.body.signed_message()?)
+                && self.body.verify_chain()?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::transaction::Transaction;
+
+    fn confirmation(payer: &Keypair, depth: usize) -> (Signature, SettlementConfirmation) {
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), b"settle", vec![]);
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[payer],
+            Hash::new_unique(),
+        );
+        let signature = transaction.signatures[0];
+        let transaction = VersionedTransaction::from(transaction);
+
+        let mut blocks: Vec<BlockLink> = Vec::new();
+        let mut slot = 1000;
+        for _ in 0..=depth {
+            let previous = blocks.last();
+            blocks.push(BlockLink {
+                slot,
+                parent_slot: previous.map_or(slot - 1, |block| block.slot),
+                blockhash: Hash::new_unique().to_string(),
+                previous_blockhash: previous
+                    .map_or_else(|| Hash::new_unique().to_string(), |b| b.blockhash.clone()),
+                block_time: None,
+            });
+            slot += 1 + slot % 2; // Skipped slots leave gaps
+        }
+        (
+            signature,
+            SettlementConfirmation {
+                slot: 1000,
+                transaction: BASE64.encode(bincode::serialize(&transaction).unwrap()),
+                blocks,
+            },
+        )
+    }
+
+    #[test]
+    fn test_finality_proof_verifies() {
+        let sequencer = Keypair::new();
+        let (signature, confirmation) = confirmation(&sequencer, FINALITY_DEPTH);
+        let anchor = confirmation.blocks[10].clone();
+        let body = FinalityProofBody::new(7, &signature, confirmation, Utc::now());
+        let proof = FinalityProof::sign(body, &sequencer).unwrap();
+        assert!(proof.verify().unwrap());
+        assert!(proof
+            .body
+            .contains_block(anchor.slot, &Hash::from_str(&anchor.blockhash).unwrap()));
+        assert!(!proof.body.contains_block(anchor.slot, &Hash::new_unique()));
+
+        // Survives the JSON round trip clients see
+        let json = serde_json::to_string(&proof).unwrap();
+        let decoded: FinalityProof = serde_json::from_str(&json).unwrap();
+        assert!(decoded.verify().unwrap());
+    }
+
+    #[test]
+    fn test_finality_proof_rejects_tampering() {
+        let sequencer = Keypair::new();
+        let (signature, confirmation) = confirmation(&sequencer, FINALITY_DEPTH);
+        let body = FinalityProofBody::new(7, &signature, confirmation.clone(), Utc::now());
+
+        // A broken link, even re-signed, fails the chain check
+        let mut broken = body.clone();
+        broken.blocks[5].previous_blockhash = Hash::new_unique().to_string();
+        assert!(!FinalityProof::sign(broken, &sequencer)
+            .unwrap()
+            .verify()
+            .unwrap());
+
+        // So does a chain too short to be final
+        let (signature, shallow) = self::confirmation(&sequencer, FINALITY_DEPTH - 1);
+        let shallow = FinalityProofBody::new(7, &signature, shallow, Utc::now());
+        assert!(!shallow.verify_chain().unwrap());
+
+        // Or a transaction other than the batch's
+        let mut other = body.clone();
+        other.transaction_signature = Signature::new_unique().to_string();
+        assert!(!other.verify_chain().unwrap());
+
+        // Edits after signing break the signature
+        let mut proof = FinalityProof::sign(body, &sequencer).unwrap();
+        proof.body.batch_id = 8;
+        assert!(!proof.verify().unwrap());
+    }
+}
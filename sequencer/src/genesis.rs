@@ -0,0 +1,302 @@
+// Rollup genesis manifest for ZK Casino
+// One JSON file (GENESIS_PATH) pins everything a deployment is made of: the program IDs,
+// the authority, sequencer and VRF keys, the house bankroll funded at launch, the
+// circuit's verifying key digest and every game's payout schedule. The sequencer
+// validates the manifest at startup, takes its program IDs and coinflip schedule from
+// it, and the startup self-check compares it against what is actually on chain, so a
+// deployment can be reproduced (or audited) from the manifest alone.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::{hash, Hash};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::solana::GAME_COINFLIP;
+use crate::tokens::{PayoutSchedule, BPS_DENOMINATOR};
+
+/// Same cap the verifier program puts on a game's house edge
+const MAX_HOUSE_EDGE_BPS: u64 = 1_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Genesis {
+    pub network: String, // Cluster the manifest deploys to (localnet, devnet, ...)
+    #[serde(with = "base58")]
+    pub vault_program_id: Pubkey,
+    #[serde(with = "base58")]
+    pub verifier_program_id: Pubkey,
+    pub authorities: GenesisAuthorities,
+    pub house_bankroll: GenesisBankroll,
+    #[serde(with = "base58")]
+    pub verifying_key_hash: Hash, // SHA-256 of the active on-chain verifying key bytes
+    pub games: Vec<GenesisGame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisAuthorities {
+    #[serde(with = "base58")]
+    pub vault: Pubkey, // VaultState authority
+    #[serde(with = "base58")]
+    pub verifier: Pubkey, // VerifierState authority
+    #[serde(with = "base58")]
+    pub sequencer: Pubkey, // Key the sequencer signs settlements with
+    #[serde(with = "base58")]
+    pub vrf: Pubkey, // Key bet outcomes are drawn with
+}
+
+/// Principal the admin funds the house vault with at launch, in base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisBankroll {
+    pub sol: u64,
+    pub usdc: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisGame {
+    pub game_id: u8,
+    pub payout_multiplier_bps: u64,
+    pub house_edge_bps: u64,
+}
+
+impl GenesisGame {
+    pub fn payout_schedule(&self) -> PayoutSchedule {
+        PayoutSchedule {
+            multiplier_bps: self.payout_multiplier_bps,
+            house_edge_bps: self.house_edge_bps,
+        }
+    }
+}
+
+impl Genesis {
+    /// Parse and validate a manifest
+    pub fn from_json(json: &str) -> Result<Self> {
+        let genesis: Self =
+            serde_json::from_str(json).map_err(|e| anyhow!("Invalid genesis manifest: {}", e))?;
+        genesis.validate()?;
+        Ok(genesis)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read genesis manifest {}", path.display()))?;
+        Self::from_json(&json)
+    }
+
+    /// Load the manifest at GENESIS_PATH; None when unset
+    pub fn from_env() -> Result<Option<Self>> {
+        std::env::var("GENESIS_PATH")
+            .ok()
+            .map(|path| {
+                let genesis = Self::load(&path)?;
+                genesis.check_env_program_ids()?;
+                Ok(genesis)
+            })
+            .transpose()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.network.is_empty() {
+            return Err(anyhow!("Genesis network must be set"));
+        }
+        if self.vault_program_id == self.verifier_program_id {
+            return Err(anyhow!(
+                "Genesis vault and verifier program IDs must differ"
+            ));
+        }
+        if self.authorities.sequencer == self.authorities.vrf {
+            return Err(anyhow!(
+                "Genesis VRF key must differ from the sequencer key"
+            ));
+        }
+
+        let mut game_ids = HashSet::new();
+        for game in &self.games {
+            if !game_ids.insert(game.game_id) {
+                return Err(anyhow!("Genesis lists game {} twice", game.game_id));
+            }
+            // Same bounds the verifier program enforces
+            if game.payout_multiplier_bps < BPS_DENOMINATOR
+                || game.payout_multiplier_bps > u32::MAX as u64
+            {
+                return Err(anyhow!(
+                    "Genesis game {} payout multiplier must be between {} and {} bps",
+                    game.game_id,
+                    BPS_DENOMINATOR,
+                    u32::MAX
+                ));
+            }
+            if game.house_edge_bps > MAX_HOUSE_EDGE_BPS {
+                return Err(anyhow!(
+                    "Genesis game {} house edge cannot exceed {} bps",
+                    game.game_id,
+                    MAX_HOUSE_EDGE_BPS
+                ));
+            }
+        }
+        if self.game(GAME_COINFLIP).is_none() {
+            return Err(anyhow!("Genesis must configure the coinflip game"));
+        }
+        Ok(())
+    }
+
+    /// VAULT_PROGRAM_ID / VERIFIER_PROGRAM_ID may still be set, but only to the manifest's
+    fn check_env_program_ids(&self) -> Result<()> {
+        for (var, program_id) in [
+            ("VAULT_PROGRAM_ID", self.vault_program_id),
+            ("VERIFIER_PROGRAM_ID", self.verifier_program_id),
+        ] {
+            if let Ok(value) = std::env::var(var) {
+                if value != program_id.to_string() {
+                    return Err(anyhow!(
+                        "{} {} conflicts with the genesis manifest's {}",
+                        var,
+                        value,
+                        program_id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn game(&self, game_id: u8) -> Option<&GenesisGame> {
+        self.games.iter().find(|game| game.game_id == game_id)
+    }
+
+    /// The coinflip schedule the sequencer pays out with
+    pub fn coinflip_payouts(&self) -> PayoutSchedule {
+        self.game(GAME_COINFLIP)
+            .expect("validated genesis has a coinflip game")
+            .payout_schedule()
+    }
+
+    /// Program IDs in the form `program_ids_from_env` returns them
+    pub fn program_ids(&self) -> (String, String) {
+        (
+            self.vault_program_id.to_string(),
+            self.verifier_program_id.to_string(),
+        )
+    }
+
+    /// SHA-256 of the manifest's canonical JSON, to tell deployments apart
+    pub fn digest(&self) -> Hash {
+        hash(&serde_json::to_vec(self).expect("genesis serializes"))
+    }
+}
+
+/// Pubkeys and hashes are written base58 in the manifest
+mod base58 {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|e| D::Error::custom(format!("invalid base58 value {}: {}", value, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn manifest() -> Value {
+        json!({
+            "network": "localnet",
+            "vault_program_id": Pubkey::new_unique().to_string(),
+            "verifier_program_id": Pubkey::new_unique().to_string(),
+            "authorities": {
+                "vault": Pubkey::new_unique().to_string(),
+                "verifier": Pubkey::new_unique().to_string(),
+                "sequencer": Pubkey::new_unique().to_string(),
+                "vrf": Pubkey::new_unique().to_string(),
+            },
+            "house_bankroll": { "sol": 100_000_000_000u64, "usdc": 0 },
+            "verifying_key_hash": hash(b"verifying key").to_string(),
+            "games": [
+                { "game_id": 0, "payout_multiplier_bps": 20_000, "house_edge_bps": 100 },
+            ],
+        })
+    }
+
+    fn parse(manifest: &Value) -> Result<Genesis> {
+        Genesis::from_json(&manifest.to_string())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let genesis = parse(&manifest()).unwrap();
+        assert_eq!(genesis.verifying_key_hash, hash(b"verifying key"));
+        assert_eq!(
+            genesis.coinflip_payouts(),
+            PayoutSchedule {
+                multiplier_bps: 20_000,
+                house_edge_bps: 100,
+            }
+        );
+
+        let json = serde_json::to_string(&genesis).unwrap();
+        let reparsed = Genesis::from_json(&json).unwrap();
+        assert_eq!(reparsed, genesis);
+        assert_eq!(reparsed.digest(), genesis.digest());
+    }
+
+    #[test]
+    fn test_validation() {
+        let rejects = |edit: &dyn Fn(&mut Value)| {
+            let mut manifest = manifest();
+            edit(&mut manifest);
+            parse(&manifest).is_err()
+        };
+
+        assert!(rejects(&|m| m["network"] = json!("")));
+        assert!(rejects(&|m| m["vault_program_id"] = json!("not-a-key")));
+        assert!(rejects(&|m| m["verifying_key_hash"] = json!("abc")));
+        assert!(rejects(&|m| m["extra"] = json!(1)));
+        assert!(rejects(&|m| {
+            m["verifier_program_id"] = m["vault_program_id"].clone();
+        }));
+        assert!(rejects(&|m| {
+            m["authorities"]["vrf"] = m["authorities"]["sequencer"].clone();
+        }));
+        assert!(rejects(&|m| m["games"][0]["game_id"] = json!(1)));
+        assert!(rejects(
+            &|m| m["games"][0]["payout_multiplier_bps"] = json!(9_999)
+        ));
+        assert!(rejects(&|m| m["games"][0]["house_edge_bps"] = json!(1_001)));
+        assert!(rejects(&|m| {
+            let game = m["games"][0].clone();
+            m["games"].as_array_mut().unwrap().push(game);
+        }));
+
+        // Further games are allowed alongside the coinflip
+        let mut manifest = manifest();
+        manifest["games"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "game_id": 1, "payout_multiplier_bps": 60_000, "house_edge_bps": 200 }));
+        assert_eq!(parse(&manifest).unwrap().games.len(), 2);
+    }
+}
@@ -0,0 +1,361 @@
+// Geo/IP policy enforcement for ZK Casino
+// Resolves client IPs to countries using a local GeoIP CSV database and applies
+// a configurable allowlist/blocklist to the betting endpoints.
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::{AppState, ErrorResponse};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoPolicyMode {
+    Disabled,  // No enforcement
+    Blocklist, // Reject listed countries
+    Allowlist, // Accept only listed countries
+}
+
+/// Local GeoIP database: sorted IPv4 ranges mapped to ISO country codes
+///
+/// Loaded from a CSV file with lines of `start_ip,end_ip,country_code`
+#[derive(Debug, Default)]
+pub struct GeoIpDatabase {
+    ranges: Vec<(u32, u32, String)>,
+}
+
+impl GeoIpDatabase {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_csv(&contents)
+    }
+
+    pub fn from_csv(contents: &str) -> Result<Self> {
+        let mut ranges = Vec::new();
+
+        for (line_no, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() != 3 {
+                return Err(anyhow!("Invalid GeoIP entry on line {}", line_no + 1));
+            }
+
+            let start: Ipv4Addr = fields[0]
+                .parse()
+                .map_err(|_| anyhow!("Invalid start IP on line {}", line_no + 1))?;
+            let end: Ipv4Addr = fields[1]
+                .parse()
+                .map_err(|_| anyhow!("Invalid end IP on line {}", line_no + 1))?;
+            ranges.push((u32::from(start), u32::from(end), fields[2].to_uppercase()));
+        }
+
+        ranges.sort_by_key(|(start, _, _)| *start);
+        Ok(Self { ranges })
+    }
+
+    /// Look up the country code for an IP (IPv4 only)
+    pub fn lookup(&self, ip: IpAddr) -> Option<&str> {
+        let ip = match ip {
+            IpAddr::V4(v4) => u32::from(v4),
+            IpAddr::V6(v6) => u32::from(v6.to_ipv4_mapped()?),
+        };
+
+        let idx = self.ranges.partition_point(|(start, _, _)| *start <= ip);
+        let (start, end, country) = self.ranges.get(idx.checked_sub(1)?)?;
+        (*start <= ip && ip <= *end).then_some(country.as_str())
+    }
+
+    pub fn range_count(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoPolicySnapshot {
+    pub mode: GeoPolicyMode,
+    pub countries: Vec<String>,
+    pub allow_unknown: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyDecision {
+    pub allowed: bool,
+    pub country: Option<String>,
+    pub reason: &'static str,
+}
+
+/// Country-based policy applied to betting endpoints
+pub struct GeoPolicy {
+    database: GeoIpDatabase,
+    state: RwLock<GeoPolicySnapshot>,
+}
+
+impl GeoPolicy {
+    pub fn new(database: GeoIpDatabase, snapshot: GeoPolicySnapshot) -> Self {
+        Self {
+            database,
+            state: RwLock::new(snapshot),
+        }
+    }
+
+    /// Disabled policy (default for tests and local development)
+    pub fn disabled() -> Self {
+        Self::new(
+            GeoIpDatabase::default(),
+            GeoPolicySnapshot {
+                mode: GeoPolicyMode::Disabled,
+                countries: Vec::new(),
+                allow_unknown: true,
+            },
+        )
+    }
+
+    /// Load policy from environment (GEO_POLICY_MODE, GEO_POLICY_COUNTRIES, GEOIP_DB_PATH, GEO_POLICY_ALLOW_UNKNOWN)
+    pub fn from_env() -> Result<Self> {
+        let mode = match std::env::var("GEO_POLICY_MODE")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "disabled" => GeoPolicyMode::Disabled,
+            "blocklist" => GeoPolicyMode::Blocklist,
+            "allowlist" => GeoPolicyMode::Allowlist,
+            other => return Err(anyhow!("Unknown GEO_POLICY_MODE: {}", other)),
+        };
+
+        let countries = std::env::var("GEO_POLICY_COUNTRIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|c| c.trim().to_uppercase())
+            .filter(|c| !c.is_empty())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let database = match std::env::var("GEOIP_DB_PATH") {
+            Ok(path) => GeoIpDatabase::load(Path::new(&path))?,
+            Err(_) => GeoIpDatabase::default(),
+        };
+
+        let allow_unknown =
+            std::env::var("GEO_POLICY_ALLOW_UNKNOWN").unwrap_or_default() != "false";
+
+        info!(
+            "Geo policy: {:?} ({} GeoIP ranges loaded)",
+            mode,
+            database.range_count()
+        );
+
+        Ok(Self::new(
+            database,
+            GeoPolicySnapshot {
+                mode,
+                countries,
+                allow_unknown,
+            },
+        ))
+    }
+
+    pub fn snapshot(&self) -> GeoPolicySnapshot {
+        self.state.read().clone()
+    }
+
+    pub fn set_mode(&self, mode: GeoPolicyMode, allow_unknown: bool) {
+        let mut state = self.state.write();
+        state.mode = mode;
+        state.allow_unknown = allow_unknown;
+        info!(
+            "Geo policy updated: mode={:?}, allow_unknown={}",
+            mode, allow_unknown
+        );
+    }
+
+    /// Add a country code to the list, returns false if already present
+    pub fn add_country(&self, country: &str) -> bool {
+        let country = country.trim().to_uppercase();
+        let mut state = self.state.write();
+        if state.countries.contains(&country) {
+            return false;
+        }
+        info!("Geo policy: added country {}", country);
+        state.countries.push(country);
+        state.countries.sort();
+        true
+    }
+
+    /// Remove a country code from the list, returns false if not present
+    pub fn remove_country(&self, country: &str) -> bool {
+        let country = country.trim().to_uppercase();
+        let mut state = self.state.write();
+        let before = state.countries.len();
+        state.countries.retain(|c| c != &country);
+        let removed = state.countries.len() != before;
+        if removed {
+            info!("Geo policy: removed country {}", country);
+        }
+        removed
+    }
+
+    pub fn evaluate(&self, ip: Option<IpAddr>) -> PolicyDecision {
+        let state = self.state.read();
+        if state.mode == GeoPolicyMode::Disabled {
+            return PolicyDecision {
+                allowed: true,
+                country: None,
+                reason: "policy disabled",
+            };
+        }
+
+        let country = ip
+            .and_then(|ip| self.database.lookup(ip))
+            .map(str::to_string);
+        let Some(code) = country.as_deref() else {
+            return PolicyDecision {
+                allowed: state.allow_unknown,
+                country: None,
+                reason: "country unknown",
+            };
+        };
+
+        let listed = state.countries.iter().any(|c| c == code);
+        let (allowed, reason) = match state.mode {
+            GeoPolicyMode::Blocklist if listed => (false, "country blocklisted"),
+            GeoPolicyMode::Allowlist if !listed => (false, "country not allowlisted"),
+            _ => (true, "country permitted"),
+        };
+
+        PolicyDecision {
+            allowed,
+            country,
+            reason,
+        }
+    }
+}
+
+/// Resolve the client IP from X-Forwarded-For or the socket address
+fn client_ip(headers: &HeaderMap, request: &Request) -> Option<IpAddr> {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .and_then(|v| v.trim().parse().ok())
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        })
+}
+
+/// Middleware enforcing the geo policy on betting endpoints
+pub async fn geo_policy_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(request.headers(), &request);
+    let decision = state.geo_policy.evaluate(ip);
+
+    if !decision.allowed {
+        warn!(
+            "Geo policy rejected {} {} from {:?} (country: {:?}): {}",
+            request.method(),
+            request.uri().path(),
+            ip,
+            decision.country,
+            decision.reason
+        );
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: format!("Betting not available in your region ({})", decision.reason),
+            }),
+        )
+            .into_response();
+    }
+
+    info!(
+        "Geo policy allowed {} from {:?} (country: {:?}): {}",
+        request.uri().path(),
+        ip,
+        decision.country,
+        decision.reason
+    );
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DB: &str = "\
+# start,end,country
+1.0.0.0,1.0.0.255,AU
+2.0.0.0,2.255.255.255,FR
+8.8.8.0,8.8.8.255,US
+";
+
+    fn policy(mode: GeoPolicyMode, countries: &[&str]) -> GeoPolicy {
+        GeoPolicy::new(
+            GeoIpDatabase::from_csv(TEST_DB).unwrap(),
+            GeoPolicySnapshot {
+                mode,
+                countries: countries.iter().map(|c| c.to_string()).collect(),
+                allow_unknown: false,
+            },
+        )
+    }
+
+    #[test]
+    fn test_geoip_lookup() {
+        let db = GeoIpDatabase::from_csv(TEST_DB).unwrap();
+        assert_eq!(db.range_count(), 3);
+        assert_eq!(db.lookup("1.0.0.7".parse().unwrap()), Some("AU"));
+        assert_eq!(db.lookup("2.10.0.1".parse().unwrap()), Some("FR"));
+        assert_eq!(db.lookup("8.8.8.8".parse().unwrap()), Some("US"));
+        assert_eq!(db.lookup("3.0.0.1".parse().unwrap()), None);
+        assert!(GeoIpDatabase::from_csv("not,a").is_err());
+    }
+
+    #[test]
+    fn test_blocklist_and_allowlist() {
+        let blocklist = policy(GeoPolicyMode::Blocklist, &["US"]);
+        assert!(!blocklist.evaluate(Some("8.8.8.8".parse().unwrap())).allowed);
+        assert!(blocklist.evaluate(Some("1.0.0.1".parse().unwrap())).allowed);
+        // Unknown country follows allow_unknown
+        assert!(!blocklist.evaluate(Some("3.0.0.1".parse().unwrap())).allowed);
+        assert!(!blocklist.evaluate(None).allowed);
+
+        let allowlist = policy(GeoPolicyMode::Allowlist, &["FR"]);
+        assert!(allowlist.evaluate(Some("2.1.1.1".parse().unwrap())).allowed);
+        assert!(!allowlist.evaluate(Some("1.0.0.1".parse().unwrap())).allowed);
+
+        assert!(GeoPolicy::disabled().evaluate(None).allowed);
+    }
+
+    #[test]
+    fn test_manage_countries() {
+        let policy = policy(GeoPolicyMode::Blocklist, &[]);
+        assert!(policy.add_country("us"));
+        assert!(!policy.add_country("US"));
+        assert!(!policy.evaluate(Some("8.8.8.8".parse().unwrap())).allowed);
+
+        assert!(policy.remove_country("US"));
+        assert!(!policy.remove_country("US"));
+        assert!(policy.evaluate(Some("8.8.8.8".parse().unwrap())).allowed);
+    }
+}
@@ -0,0 +1,348 @@
+// House betting limits for ZK Casino
+// The house's risk limits, per token: the largest accepted bet, the largest payout,
+// and how much the house may lose across all players over a rolling day (its
+// exposure). place_bet enforces them and /v1/limits reports the same numbers, so
+// clients can validate a bet before submitting it. Limits apply to every game; a
+// game's payout schedule turns the payout cap and the exposure headroom into the
+// largest stake it accepts. Before its outcome is drawn, a bet reserves its worst case
+// loss (payout less stake) against the exposure cap, and the reservation is replaced by
+// the actual result once known, so concurrent bets can't overrun the cap together.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use thiserror::Error;
+
+use crate::tokens::{parse_amount, PayoutSchedule, TokenConfig};
+use crate::{GameKind, SettlementToken};
+
+/// Window the house's net loss is capped over
+pub const EXPOSURE_WINDOW_HOURS: i64 = 24;
+
+/// Limits of one token, in its base units; None means no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenLimits {
+    pub max_bet: Option<u64>,
+    pub max_payout: Option<u64>,
+    pub max_exposure: Option<u64>, // Net house loss over EXPOSURE_WINDOW_HOURS
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HouseLimitConfig {
+    pub sol: TokenLimits,
+    pub usdc: TokenLimits,
+}
+
+impl HouseLimitConfig {
+    /// Load from environment: {SOL,USDC}_MAX_BET, {SOL,USDC}_MAX_PAYOUT and
+    /// {SOL,USDC}_MAX_HOUSE_EXPOSURE as decimal token amounts; unset limits don't apply
+    pub fn from_env(tokens: &TokenConfig) -> Result<Self> {
+        let limits = |prefix: &str, decimals: u8| -> Result<TokenLimits> {
+            let amount = |name: &str| -> Result<Option<u64>> {
+                let var = format!("{}_{}", prefix, name);
+                match std::env::var(&var) {
+                    Ok(v) => parse_amount(&v, decimals)
+                        .map(Some)
+                        .map_err(|e| anyhow!("Invalid {}: {}", var, e)),
+                    Err(_) => Ok(None),
+                }
+            };
+            Ok(TokenLimits {
+                max_bet: amount("MAX_BET")?,
+                max_payout: amount("MAX_PAYOUT")?,
+                max_exposure: amount("MAX_HOUSE_EXPOSURE")?,
+            })
+        };
+        Ok(Self {
+            sol: limits("SOL", tokens.sol.decimals)?,
+            usdc: limits("USDC", tokens.usdc.decimals)?,
+        })
+    }
+
+    pub fn limits(&self, token: SettlementToken) -> &TokenLimits {
+        match token {
+            SettlementToken::Sol => &self.sol,
+            SettlementToken::Usdc => &self.usdc,
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LimitError {
+    #[error("Bet amount exceeds the maximum of {max} base units")]
+    AboveMaxBet { max: u64 },
+    #[error("Payout of {payout} base units would exceed the maximum of {max}")]
+    AboveMaxPayout { payout: u64, max: u64 },
+    #[error("Bet exceeds the house's remaining exposure of {headroom} base units")]
+    ExposureExceeded { headroom: u64 },
+}
+
+/// A token's limits for one game as they stand, as reported to clients
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LimitsSnapshot {
+    pub token: SettlementToken,
+    pub game: GameKind,
+    pub min_bet: u64,
+    pub max_bet: Option<u64>, // Largest bet accepted right now, all limits considered
+    pub max_payout: Option<u64>,
+    pub max_exposure: Option<u64>,
+    pub exposure: u64, // Net house loss in the window, plus bets awaiting their outcome
+    pub exposure_headroom: Option<u64>,
+    pub window_hours: i64,
+}
+
+/// Held from the limit check until the bet's outcome is known; resolve or release it
+#[derive(Debug)]
+pub struct ExposureReservation {
+    token: SettlementToken,
+    worst_case: u64,
+}
+
+#[derive(Default)]
+struct Exposure {
+    results: VecDeque<(DateTime<Utc>, i64)>, // House loss (negative for a gain), oldest first
+    net: i64,                                // Sum of `results`
+    reserved: u64,                           // Worst cases of bets awaiting their outcome
+}
+
+impl Exposure {
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - ChronoDuration::hours(EXPOSURE_WINDOW_HOURS);
+        while let Some((at, loss)) = self.results.front().copied() {
+            if at > cutoff {
+                break;
+            }
+            self.net -= loss;
+            self.results.pop_front();
+        }
+    }
+
+    fn used(&self) -> u64 {
+        (self.net.max(0) as u64).saturating_add(self.reserved)
+    }
+}
+
+pub struct HouseLimits {
+    config: HouseLimitConfig,
+    exposure: Mutex<HashMap<SettlementToken, Exposure>>,
+}
+
+/// Worst case house loss of a bet: its winning payout less the stake
+fn worst_case(payouts: &PayoutSchedule, amount: u64) -> u64 {
+    payouts
+        .checked_win_payout(amount)
+        .map_or(u64::MAX, |payout| payout.saturating_sub(amount))
+}
+
+/// Largest stake satisfying `fits` (monotone: true up to some stake, false after);
+/// None when every stake fits
+fn largest_stake(fits: impl Fn(u64) -> bool) -> Option<u64> {
+    if fits(u64::MAX) {
+        return None;
+    }
+    let (mut low, mut high) = (0u64, u64::MAX); // fits(low) or low == 0; !fits(high)
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Some(low)
+}
+
+impl HouseLimits {
+    pub fn new(config: HouseLimitConfig) -> Self {
+        Self {
+            config,
+            exposure: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a bet against the bet and payout caps
+    pub fn check(
+        &self,
+        token: SettlementToken,
+        payouts: &PayoutSchedule,
+        amount: u64,
+    ) -> Result<(), LimitError> {
+        let limits = self.config.limits(token);
+        if let Some(max) = limits.max_bet.filter(|max| amount > *max) {
+            return Err(LimitError::AboveMaxBet { max });
+        }
+        let payout = payouts.checked_win_payout(amount).unwrap_or(u64::MAX);
+        if let Some(max) = limits.max_payout.filter(|max| payout > *max) {
+            return Err(LimitError::AboveMaxPayout { payout, max });
+        }
+        Ok(())
+    }
+
+    /// Check a bet against every limit and reserve its worst case loss
+    pub fn reserve(
+        &self,
+        token: SettlementToken,
+        payouts: &PayoutSchedule,
+        amount: u64,
+        now: DateTime<Utc>,
+    ) -> Result<ExposureReservation, LimitError> {
+        self.check(token, payouts, amount)?;
+        let worst_case = worst_case(payouts, amount);
+        let mut exposures = self.exposure.lock();
+        let exposure = exposures.entry(token).or_default();
+        exposure.prune(now);
+        if let Some(max) = self.config.limits(token).max_exposure {
+            let headroom = max.saturating_sub(exposure.used());
+            if worst_case > headroom {
+                return Err(LimitError::ExposureExceeded { headroom });
+            }
+        }
+        exposure.reserved += worst_case;
+        Ok(ExposureReservation { token, worst_case })
+    }
+
+    /// Replace a reservation with the bet's actual house loss (negative for a gain)
+    pub fn resolve(&self, reservation: ExposureReservation, loss: i64, now: DateTime<Utc>) {
+        let mut exposures = self.exposure.lock();
+        let exposure = exposures.entry(reservation.token).or_default();
+        exposure.reserved = exposure.reserved.saturating_sub(reservation.worst_case);
+        exposure.results.push_back((now, loss));
+        exposure.net += loss;
+    }
+
+    /// Drop a reservation for a bet that was never placed
+    pub fn release(&self, reservation: ExposureReservation) {
+        if let Some(exposure) = self.exposure.lock().get_mut(&reservation.token) {
+            exposure.reserved = exposure.reserved.saturating_sub(reservation.worst_case);
+        }
+    }
+
+    pub fn snapshot(
+        &self,
+        token: SettlementToken,
+        game: GameKind,
+        payouts: &PayoutSchedule,
+        min_bet: u64,
+        now: DateTime<Utc>,
+    ) -> LimitsSnapshot {
+        let limits = *self.config.limits(token);
+        let exposure = {
+            let mut exposures = self.exposure.lock();
+            let exposure = exposures.entry(token).or_default();
+            exposure.prune(now);
+            exposure.used()
+        };
+        let exposure_headroom = limits.max_exposure.map(|max| max.saturating_sub(exposure));
+
+        let max_bet = largest_stake(|stake| {
+            limits.max_bet.is_none_or(|max| stake <= max)
+                && limits
+                    .max_payout
+                    .is_none_or(|max| payouts.checked_win_payout(stake).is_some_and(|p| p <= max))
+                && exposure_headroom.is_none_or(|headroom| worst_case(payouts, stake) <= headroom)
+        });
+        LimitsSnapshot {
+            token,
+            game,
+            min_bet,
+            max_bet,
+            max_payout: limits.max_payout,
+            max_exposure: limits.max_exposure,
+            exposure,
+            exposure_headroom,
+            window_hours: EXPOSURE_WINDOW_HOURS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(sol: TokenLimits) -> HouseLimits {
+        HouseLimits::new(HouseLimitConfig {
+            sol,
+            usdc: TokenLimits::default(),
+        })
+    }
+
+    #[test]
+    fn test_bet_and_payout_caps() {
+        let payouts = PayoutSchedule::default(); // 2x
+        let house = limits(TokenLimits {
+            max_bet: Some(1_000),
+            max_payout: Some(1_500),
+            max_exposure: None,
+        });
+        let sol = SettlementToken::Sol;
+        assert_eq!(
+            house.check(sol, &payouts, 1_001),
+            Err(LimitError::AboveMaxBet { max: 1_000 })
+        );
+        assert_eq!(
+            house.check(sol, &payouts, 751),
+            Err(LimitError::AboveMaxPayout {
+                payout: 1_502,
+                max: 1_500
+            })
+        );
+        assert!(house.check(sol, &payouts, 750).is_ok());
+        // Other tokens are unlimited
+        assert!(house
+            .check(SettlementToken::Usdc, &payouts, u64::MAX / 4)
+            .is_ok());
+
+        // The reported max bet is the largest one check accepts
+        let snapshot = house.snapshot(sol, GameKind::Coinflip, &payouts, 10, Utc::now());
+        assert_eq!(snapshot.max_bet, Some(750));
+        assert_eq!(snapshot.exposure_headroom, None);
+        let unlimited = house.snapshot(
+            SettlementToken::Usdc,
+            GameKind::Coinflip,
+            &payouts,
+            10,
+            Utc::now(),
+        );
+        assert_eq!(unlimited.max_bet, None);
+    }
+
+    #[test]
+    fn test_exposure_reservations() {
+        let payouts = PayoutSchedule::default(); // Worst case loss equals the stake
+        let house = limits(TokenLimits {
+            max_bet: None,
+            max_payout: None,
+            max_exposure: Some(1_000),
+        });
+        let sol = SettlementToken::Sol;
+        let now = Utc::now();
+
+        // Pending bets count their worst case against the cap
+        let first = house.reserve(sol, &payouts, 600, now).unwrap();
+        assert_eq!(
+            house.reserve(sol, &payouts, 500, now).unwrap_err(),
+            LimitError::ExposureExceeded { headroom: 400 }
+        );
+        let snapshot = house.snapshot(sol, GameKind::Coinflip, &payouts, 1, now);
+        assert_eq!(snapshot.exposure, 600);
+        assert_eq!(snapshot.max_bet, Some(400));
+
+        // A house win frees more than the reservation held
+        house.resolve(first, -600, now);
+        let second = house.reserve(sol, &payouts, 1_000, now).unwrap();
+        house.resolve(second, 1_000, now);
+        let snapshot = house.snapshot(sol, GameKind::Coinflip, &payouts, 1, now);
+        assert_eq!(snapshot.exposure, 400);
+        assert_eq!(snapshot.exposure_headroom, Some(600));
+
+        // Released reservations don't count, and results age out of the window
+        let released = house.reserve(sol, &payouts, 600, now).unwrap();
+        house.release(released);
+        let later = now + ChronoDuration::hours(EXPOSURE_WINDOW_HOURS);
+        let snapshot = house.snapshot(sol, GameKind::Coinflip, &payouts, 1, later);
+        assert_eq!(snapshot.exposure, 0);
+        assert_eq!(snapshot.max_bet, Some(1_000));
+    }
+}
@@ -0,0 +1,194 @@
+// Historical signing key registry for ZK Casino
+// Every sequencer keypair, VRF key and Groth16 verifying key the sequencer has used is
+// recorded with the window it was in use and, once revoked, why. Receipts, VRF proofs and
+// ZK proofs only name their key, so this is what lets them be checked years later against
+// the key that was valid when they were made. Records live in the settlement file, next to
+// the batches signed and proved with them, and are never deleted.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    Sequencer,    // Signs receipts and settlement transactions
+    Vrf,          // Produces bet outcomes
+    VerifyingKey, // Groth16 key settlement proofs verify against
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyRevocation {
+    pub reason: String,
+    pub revoked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyRecord {
+    pub kind: KeyKind,
+    pub key: String, // Base58 public key, or base58 SHA-256 of a verifying key
+    pub valid_from: DateTime<Utc>,
+    pub valid_until: Option<DateTime<Utc>>, // None while the key is in use
+    pub revocation: Option<KeyRevocation>,
+}
+
+impl KeyRecord {
+    /// Whether something signed or proved with this key at `at` should be trusted:
+    /// inside the validity window and before any revocation
+    pub fn was_valid_at(&self, at: DateTime<Utc>) -> bool {
+        self.valid_from <= at
+            && self.valid_until.is_none_or(|until| at < until)
+            && self
+                .revocation
+                .as_ref()
+                .is_none_or(|revocation| at < revocation.revoked_at)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum KeyRegistryError {
+    #[error("No {kind:?} key {key} in the key registry")]
+    NotFound { kind: KeyKind, key: String },
+    #[error("{kind:?} key {key} has been revoked: {reason}")]
+    Revoked {
+        kind: KeyKind,
+        key: String,
+        reason: String,
+    },
+    #[error(transparent)]
+    Storage(#[from] anyhow::Error),
+}
+
+/// Record that `key` is the one in use for its kind from `now`, closing the window of
+/// the key it replaces. Returns whether the registry changed; a revoked key is refused.
+pub fn record_in_use(
+    records: &mut Vec<KeyRecord>,
+    kind: KeyKind,
+    key: &str,
+    now: DateTime<Utc>,
+) -> Result<bool, KeyRegistryError> {
+    let same_key = |record: &&KeyRecord| record.kind == kind && record.key == key;
+    if let Some(revocation) = records
+        .iter()
+        .filter(same_key)
+        .find_map(|record| record.revocation.clone())
+    {
+        return Err(KeyRegistryError::Revoked {
+            kind,
+            key: key.to_string(),
+            reason: revocation.reason,
+        });
+    }
+    if records
+        .iter()
+        .filter(same_key)
+        .any(|record| record.valid_until.is_none())
+    {
+        return Ok(false);
+    }
+
+    for record in records
+        .iter_mut()
+        .filter(|record| record.kind == kind && record.valid_until.is_none())
+    {
+        record.valid_until = Some(now);
+    }
+    records.push(KeyRecord {
+        kind,
+        key: key.to_string(),
+        valid_from: now,
+        valid_until: None,
+        revocation: None,
+    });
+    Ok(true)
+}
+
+/// Revoke every record of a key, ending its window if it is still in use. Returns the
+/// revoked records.
+pub fn revoke(
+    records: &mut [KeyRecord],
+    kind: KeyKind,
+    key: &str,
+    reason: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<KeyRecord>, KeyRegistryError> {
+    let mut matching: Vec<&mut KeyRecord> = records
+        .iter_mut()
+        .filter(|record| record.kind == kind && record.key == key)
+        .collect();
+    if matching.is_empty() {
+        return Err(KeyRegistryError::NotFound {
+            kind,
+            key: key.to_string(),
+        });
+    }
+    if let Some(revocation) = matching.iter().find_map(|record| record.revocation.clone()) {
+        return Err(KeyRegistryError::Revoked {
+            kind,
+            key: key.to_string(),
+            reason: revocation.reason,
+        });
+    }
+
+    for record in matching.iter_mut() {
+        record.valid_until.get_or_insert(now);
+        record.revocation = Some(KeyRevocation {
+            reason: reason.to_string(),
+            revoked_at: now,
+        });
+    }
+    Ok(matching.into_iter().map(|record| record.clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_key_rotation_and_revocation() {
+        let start = Utc::now();
+        let rotated = start + Duration::days(30);
+        let mut records = Vec::new();
+
+        assert!(record_in_use(&mut records, KeyKind::Vrf, "old", start).unwrap());
+        assert!(!record_in_use(&mut records, KeyKind::Vrf, "old", start).unwrap());
+        assert!(record_in_use(&mut records, KeyKind::Sequencer, "seq", start).unwrap());
+        assert!(record_in_use(&mut records, KeyKind::Vrf, "new", rotated).unwrap());
+
+        // Rotation closes the old window but leaves other kinds alone
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].valid_until, Some(rotated));
+        assert_eq!(records[1].valid_until, None);
+        assert!(records[0].was_valid_at(start + Duration::days(1)));
+        assert!(!records[0].was_valid_at(rotated));
+        assert!(records[2].was_valid_at(rotated));
+
+        // Revocation invalidates from the moment it happens; proofs before it still check
+        let revoked_at = rotated + Duration::days(1);
+        let revoked = revoke(&mut records, KeyKind::Vrf, "new", "leaked", revoked_at).unwrap();
+        assert_eq!(revoked.len(), 1);
+        assert!(records[2].was_valid_at(rotated));
+        assert!(!records[2].was_valid_at(revoked_at));
+        assert_eq!(records[2].valid_until, Some(revoked_at));
+
+        assert!(matches!(
+            revoke(&mut records, KeyKind::Vrf, "new", "again", revoked_at),
+            Err(KeyRegistryError::Revoked { .. })
+        ));
+        assert!(matches!(
+            revoke(
+                &mut records,
+                KeyKind::Sequencer,
+                "new",
+                "wrong kind",
+                revoked_at
+            ),
+            Err(KeyRegistryError::NotFound { .. })
+        ));
+        assert!(matches!(
+            record_in_use(&mut records, KeyKind::Vrf, "new", revoked_at),
+            Err(KeyRegistryError::Revoked { .. })
+        ));
+    }
+}
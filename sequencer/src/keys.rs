@@ -0,0 +1,236 @@
+// Key material management for ZK Casino sequencer
+// Loads the sequencer and VRF keypairs (or generates ephemeral ones for development)
+// and implements the `sequencer keys` inspection command.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use solana_sdk::signer::Signer;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::genesis::Genesis;
+use crate::key_registry::KeyKind;
+use crate::settlement_persistence::SettlementPersistence;
+use crate::solana::SolanaConfig;
+
+/// Default program IDs used when VAULT_PROGRAM_ID / VERIFIER_PROGRAM_ID are unset
+pub const DEFAULT_VAULT_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub const DEFAULT_VERIFIER_PROGRAM_ID: &str = "11111111111111111111111111111112";
+
+/// Load a Solana JSON keypair file from the path in `env_var`, or generate an ephemeral key
+fn load_or_generate_keypair(env_var: &str, label: &str) -> Result<Keypair> {
+    match std::env::var(env_var) {
+        Ok(path) => {
+            let keypair = read_keypair_file(&path)
+                .map_err(|e| anyhow!("Failed to read {} keypair from {}: {}", label, path, e))?;
+            info!("Loaded {} keypair from {}", label, path);
+            Ok(keypair)
+        }
+        Err(_) => {
+            warn!(
+                "{} not set, generating ephemeral {} keypair",
+                env_var, label
+            );
+            Ok(Keypair::new())
+        }
+    }
+}
+
+/// Signing keys held by the sequencer
+#[derive(Clone)]
+pub struct SequencerKeys {
+    pub sequencer: Arc<Keypair>, // Solana transactions, report signing
+    pub vrf: Arc<Keypair>,       // Randomness (VRF) signing key
+}
+
+impl SequencerKeys {
+    /// Load keys from environment (SEQUENCER_KEYPAIR_PATH, VRF_KEYPAIR_PATH)
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            sequencer: Arc::new(load_or_generate_keypair(
+                "SEQUENCER_KEYPAIR_PATH",
+                "sequencer",
+            )?),
+            vrf: Arc::new(load_or_generate_keypair("VRF_KEYPAIR_PATH", "VRF")?),
+        })
+    }
+
+    /// Record both keys in the key registry as the ones now in use; fails if either
+    /// was revoked
+    pub async fn record_in_registry(&self, persistence: &SettlementPersistence) -> Result<()> {
+        persistence
+            .record_signing_key(KeyKind::Sequencer, &self.sequencer.pubkey().to_string())
+            .await?;
+        persistence
+            .record_signing_key(KeyKind::Vrf, &self.vrf.pubkey().to_string())
+            .await
+    }
+}
+
+/// Key an on-chain sequencer rotation moves to (SEQUENCER_NEXT_KEYPAIR_PATH). Settlement
+/// switches to it once the verifier's rotation activates it; unset outside a rotation.
+pub fn next_sequencer_keypair_from_env() -> Result<Option<Keypair>> {
+    std::env::var("SEQUENCER_NEXT_KEYPAIR_PATH")
+        .ok()
+        .map(|path| {
+            let keypair = read_keypair_file(&path).map_err(|e| {
+                anyhow!("Failed to read next sequencer keypair from {}: {}", path, e)
+            })?;
+            info!(
+                "Loaded next sequencer keypair {} from {}",
+                keypair.pubkey(),
+                path
+            );
+            Ok(keypair)
+        })
+        .transpose()
+}
+
+/// Program IDs from environment (VAULT_PROGRAM_ID, VERIFIER_PROGRAM_ID)
+pub fn program_ids_from_env() -> (String, String) {
+    (
+        std::env::var("VAULT_PROGRAM_ID").unwrap_or_else(|_| DEFAULT_VAULT_PROGRAM_ID.to_string()),
+        std::env::var("VERIFIER_PROGRAM_ID")
+            .unwrap_or_else(|_| DEFAULT_VERIFIER_PROGRAM_ID.to_string()),
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectedAccount {
+    pub label: String,
+    pub address: Pubkey,
+    pub exists: Option<bool>, // None when the RPC lookup failed
+    pub lamports: Option<u64>,
+}
+
+impl InspectedAccount {
+    fn new(label: &str, address: Pubkey) -> Self {
+        Self {
+            label: label.to_string(),
+            address,
+            exists: None,
+            lamports: None,
+        }
+    }
+}
+
+/// Accounts of interest for debugging: keys, program PDAs and an optional user's vault
+pub fn derive_accounts(
+    keys: &SequencerKeys,
+    vault_program_id: &Pubkey,
+    verifier_program_id: &Pubkey,
+    user: Option<&Pubkey>,
+) -> Vec<InspectedAccount> {
+    let mut accounts = vec![
+        InspectedAccount::new("sequencer", keys.sequencer.pubkey()),
+        InspectedAccount::new("vrf", keys.vrf.pubkey()),
+        InspectedAccount::new(
+            "verifier_state",
+            Pubkey::find_program_address(&[b"verifier_state"], verifier_program_id).0,
+        ),
+        InspectedAccount::new(
+            "vault_state",
+            Pubkey::find_program_address(&[b"vault_state"], vault_program_id).0,
+        ),
+    ];
+
+    if let Some(user) = user {
+        accounts.push(InspectedAccount::new("user", *user));
+        accounts.push(InspectedAccount::new(
+            "user_vault",
+            Pubkey::find_program_address(&[b"user_vault", user.as_ref()], vault_program_id).0,
+        ));
+    }
+
+    accounts
+}
+
+/// `sequencer keys [--address <pubkey>]`: print keys, PDAs and their on-chain state
+pub async fn run_keys_command(address: Option<String>) -> Result<()> {
+    let keys = SequencerKeys::from_env()?;
+    let (vault_program_id, verifier_program_id) = match Genesis::from_env()? {
+        Some(genesis) => genesis.program_ids(),
+        None => program_ids_from_env(),
+    };
+    let vault_program_id = Pubkey::from_str(&vault_program_id)
+        .map_err(|e| anyhow!("Invalid vault program ID: {}", e))?;
+    let verifier_program_id = Pubkey::from_str(&verifier_program_id)
+        .map_err(|e| anyhow!("Invalid verifier program ID: {}", e))?;
+    let user = address
+        .map(|a| Pubkey::from_str(&a).map_err(|e| anyhow!("Invalid address: {}", e)))
+        .transpose()?;
+
+    let mut accounts = derive_accounts(
+        &keys,
+        &vault_program_id,
+        &verifier_program_id,
+        user.as_ref(),
+    );
+
+    let config = SolanaConfig::from_env();
+    let accounts_clone = accounts.clone();
+    let lookups = tokio::task::spawn_blocking(move || {
+        let client = RpcClient::new_with_commitment(config.rpc_url, config.commitment);
+        accounts_clone
+            .iter()
+            .map(|account| {
+                client
+                    .get_account_with_commitment(&account.address, config.commitment)
+                    .ok()
+                    .map(|response| response.value.map(|a| a.lamports))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    for (account, lookup) in accounts.iter_mut().zip(lookups) {
+        account.exists = lookup.map(|lamports| lamports.is_some());
+        account.lamports = lookup.flatten();
+    }
+
+    println!("Vault program:    {}", vault_program_id);
+    println!("Verifier program: {}", verifier_program_id);
+    println!();
+    for account in &accounts {
+        let state = match (account.exists, account.lamports) {
+            (None, _) => "rpc unavailable".to_string(),
+            (Some(false), _) => "not found".to_string(),
+            (Some(true), lamports) => format!("{} lamports", lamports.unwrap_or(0)),
+        };
+        println!("{:<16} {:<44} {}", account.label, account.address, state);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_accounts() {
+        let keys = SequencerKeys {
+            sequencer: Arc::new(Keypair::new()),
+            vrf: Arc::new(Keypair::new()),
+        };
+        let vault_program_id = Pubkey::new_unique();
+        let verifier_program_id = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+
+        let accounts = derive_accounts(&keys, &vault_program_id, &verifier_program_id, None);
+        assert_eq!(accounts.len(), 4);
+        assert_eq!(accounts[0].address, keys.sequencer.pubkey());
+        assert_eq!(accounts[1].address, keys.vrf.pubkey());
+
+        let accounts = derive_accounts(&keys, &vault_program_id, &verifier_program_id, Some(&user));
+        let user_vault = accounts.iter().find(|a| a.label == "user_vault").unwrap();
+        assert_eq!(
+            user_vault.address,
+            Pubkey::find_program_address(&[b"user_vault", user.as_ref()], &vault_program_id).0
+        );
+    }
+}
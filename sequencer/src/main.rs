@@ -1,41 +1,200 @@
 use anyhow::Result;
 use axum::{
     async_trait,
-    extract::{FromRequest, Path, Request, State},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        FromRequest, Path, Query, Request, State,
+    },
+    http::HeaderMap,
     http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    middleware,
+    response::{Json, Response},
+    routing::{delete, get, post, put},
     Router,
 };
 use chrono::{DateTime, Utc};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use parking_lot::Mutex;
-use rand::Rng;
 use serde::{Deserialize, Serialize};
 use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::mpsc;
-use tokio::time::{interval, Duration};
+use tokio::time::interval;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info, warn};
-use uuid::Uuid;
 
+mod activity;
+use activity::{ActivityError, ActivityPage};
 mod database;
-use database::{Bet, Database, DatabaseError, PlayerBalance};
+use database::{
+    Bet, BetSettlementLink, BetSettlementStatus, Database, DatabaseError, JournalEntry,
+    PlayerBalance, WithdrawalEntry,
+};
+
+mod persistence_crypto;
+use persistence_crypto::PersistenceCipher;
 
 mod settlement_persistence;
-use settlement_persistence::{SettlementBatchStatus, SettlementPersistence};
+use settlement_persistence::{
+    LatestSettlement, ProofStats, SettlementBatchStatus, SettlementCostSummary,
+    SettlementPersistence,
+};
 
 mod oracle;
 use oracle::{OracleClient, OracleConfig, OracleManager};
 
+mod rpc_router;
 mod solana;
-use solana::{BatchSettlementData, BetSettlement, SolanaClient, SolanaConfig};
+use solana::{SolanaClient, SolanaConfig};
 
 mod settlement_prover;
-use settlement_prover::{SettlementProver, SettlementProverConfig};
+use settlement_prover::{ProverReadiness, ProverStatus, SettlementProverConfig};
+
+mod reports;
+use reports::{FairnessReport, ReportConfig, ReportService};
+
+mod admin;
+use admin::AdminAuth;
+
+mod geo_policy;
+use geo_policy::{geo_policy_middleware, GeoPolicy, GeoPolicyMode, GeoPolicySnapshot};
+
+mod compliance;
+use compliance::{ComplianceAction, ComplianceHook};
+
+mod genesis;
+use genesis::Genesis;
+mod keys;
+use keys::{next_sequencer_keypair_from_env, program_ids_from_env, SequencerKeys};
+
+mod key_registry;
+use key_registry::{KeyKind, KeyRecord, KeyRegistryError};
+
+mod bet_id;
+use bet_id::BetIdGenerator;
+mod bet_verification;
+use bet_verification::{BatchInclusion, FairnessBundle};
+
+mod bet_nonces;
+use bet_nonces::{BetNonceCache, NonceReservation};
+
+mod admission;
+use admission::{admission_middleware, AdmissionConfig, AdmissionController, AdmissionDecision};
+
+mod batch_policy;
+use batch_policy::{BatchComposer, BatchKey, BatchPolicy, BatchSchedule};
+mod batch_tuning;
+use batch_tuning::TuningCostModel;
+mod net_positions;
+
+mod proof_cache;
+use proof_cache::{ProofCacheConfig, ProofCacheStats};
+mod prover_pool;
+use prover_pool::ProverPoolConfig;
+mod demo;
+mod deposits;
+mod dry_run;
+mod escrow;
+mod export;
+mod finality_proof;
+use demo::DemoConfig;
+use deposits::{DepositWatcher, DepositWatcherConfig};
+use dry_run::DryRunReport;
+use escrow::{EscrowConfig, EscrowWatcher};
+use finality_proof::{FinalityProof, FinalityProofBody, FINALITY_DEPTH};
+mod treasury;
+use treasury::{TreasuryConfig, TreasuryError, TreasuryManager, TreasuryMovement};
+mod fee_payer;
+use fee_payer::{FeePayerConfig, FeePayerMonitor, FeePayerStatus};
+mod vrf;
+use vrf::{CoinflipInput, VrfAnchor, VrfAnchorConfig, VrfSigner, VrfTranscript};
+mod vrf_journal;
+use vrf_journal::{VrfJournal, VrfJournalEntry};
+mod bet_recovery;
+use bet_recovery::{JournaledBet, RecoveryDecision, RecoveryLog, RecoveryOutcome};
+mod adjustments;
+mod backup;
+use adjustments::{
+    AdjustmentConfig, AdjustmentError, AdjustmentManager, BalanceAdjustment, ReasonCode,
+};
+use backup::{BackupConfig, BackupService};
+mod outcome_monitor;
+use outcome_monitor::{OutcomeMonitor, OutcomeMonitorConfig, OutcomeMonitorSnapshot};
+mod self_check;
+use self_check::{read_only_middleware, SelfCheckConfig, SelfCheckReport};
+mod rounds;
+use rounds::{RoundConfig, RoundFeed, RoundScheduler};
+mod settlement_queue;
+use settlement_queue::{BatchPhase, SettlementQueueMonitor, SettlementQueueSnapshot};
+mod response_signing;
+use response_signing::sign_response_middleware;
+mod co_signer;
+use co_signer::{CoSignerClient, CoSignerConfig, CoSignerService};
+mod state_root;
+mod submitter;
+mod upgrade;
+use submitter::{update_bet_settlements, SettlementSubmitter, SubmitterConfig, SubmitterMode};
+use upgrade::{UpgradeCoordinator, UpgradeError, UpgradePhase, UpgradePlan, UpgradeStatus};
+mod tokens;
+use tokens::{HouseFees, PayoutSchedule, TokenConfig};
+mod withdrawal_fees;
+use withdrawal_fees::{WithdrawalFeeConfig, DEFAULT_NETWORK_FEE_LAMPORTS};
+mod withdrawal_limits;
+mod withdrawal_proof;
+use withdrawal_limits::{
+    WithdrawalLimitConfig, WithdrawalLimitOverride, WithdrawalLimiter, WithdrawalLimitsSnapshot,
+};
+use withdrawal_proof::{WithdrawalStatement, WithdrawalStatementBody};
+mod game_controls;
+use game_controls::{GameControls, GameStatus};
+mod house_limits;
+use house_limits::{HouseLimitConfig, HouseLimits, LimitError, LimitsSnapshot};
+mod disputes;
+use disputes::{
+    check_evidence, Dispute, DisputeError, DisputeEvidence, DisputeOutcome, DisputeStore,
+};
+
+// Token a bet is settled in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettlementToken {
+    #[default]
+    Sol,
+    Usdc,
+}
+
+// Game circuit a bet is proven with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameKind {
+    #[default]
+    Coinflip,
+}
+
+impl GameKind {
+    pub const ALL: [GameKind; 1] = [GameKind::Coinflip];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameKind::Coinflip => "coinflip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|game| game.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Id of the game's payout schedule in the verifier program
+    pub fn game_id(&self) -> u8 {
+        match self {
+            GameKind::Coinflip => solana::GAME_COINFLIP,
+        }
+    }
+}
 
 // Settlement queue for ZK proof batching (VF Node pattern)
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -45,6 +204,12 @@ pub struct SettlementItem {
     pub amount: i64,
     pub payout: i64,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub token: SettlementToken,
+    #[serde(default)]
+    pub game: GameKind,
+    #[serde(default)]
+    pub vrf_proof: Option<String>, // VRF signature the outcome was derived from
 }
 
 // Oracle proof data structure (future integration)
@@ -62,6 +227,15 @@ pub struct SettlementStats {
     pub total_batches_processed: Arc<AtomicU64>,
     pub items_in_current_batch: Arc<AtomicU64>,
     pub last_batch_processed_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    pub avg_proving_ms: Arc<AtomicU64>, // Moving average per batch
+    pub avg_submission_ms: Arc<AtomicU64>, // Moving average per batch
+    pub queue: Arc<SettlementQueueMonitor>, // Unbatched items and the batch in progress
+}
+
+impl Default for SettlementStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SettlementStats {
@@ -71,10 +245,28 @@ impl SettlementStats {
             total_batches_processed: Arc::new(AtomicU64::new(0)),
             items_in_current_batch: Arc::new(AtomicU64::new(0)),
             last_batch_processed_at: Arc::new(Mutex::new(None)),
+            avg_proving_ms: Arc::new(AtomicU64::new(0)),
+            avg_submission_ms: Arc::new(AtomicU64::new(0)),
+            queue: Arc::new(SettlementQueueMonitor::new()),
         }
     }
+
+    /// Fold a new sample into a moving average (weight 1/4 for the newest sample)
+    pub fn record_duration(average: &AtomicU64, duration: std::time::Duration) {
+        let sample = duration.as_millis() as u64;
+        let previous = average.load(Ordering::Relaxed);
+        let updated = if previous == 0 {
+            sample
+        } else {
+            (previous * 3 + sample) / 4
+        };
+        average.store(updated, Ordering::Relaxed);
+    }
 }
 
+/// Maximum number of settlement items per batch
+pub const MAX_SETTLEMENT_BATCH_SIZE: usize = 50;
+
 // High-performance channels for background processing
 pub type SettlementSender = mpsc::UnboundedSender<SettlementItem>;
 pub type SettlementReceiver = mpsc::UnboundedReceiver<SettlementItem>;
@@ -88,6 +280,55 @@ pub struct Args {
 
     #[arg(short, long, default_value = "sqlite:zkcasino.db")]
     pub database_url: String,
+
+    /// Refuse to submit batches without a verified ZK proof (no placeholder proofs)
+    #[arg(long)]
+    pub strict_proofs: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Print sequencer/VRF keys, derived PDAs and their on-chain state
+    Keys {
+        /// Player address to derive the user vault PDA for
+        #[arg(short, long)]
+        address: Option<String>,
+    },
+    /// Verify a backup and restore it for --database-url (run with the sequencer stopped)
+    Restore {
+        /// Backup file written by the scheduled backup job
+        path: std::path::PathBuf,
+        /// Only check the backup's integrity; don't restore it
+        #[arg(long)]
+        verify_only: bool,
+    },
+    /// Export confirmed batches, their proofs and verifying keys as a static bundle
+    Export {
+        /// First batch id to include
+        #[arg(long)]
+        from: u64,
+        /// Last batch id to include
+        #[arg(long)]
+        to: u64,
+        /// Directory to write the bundle to
+        #[arg(long)]
+        out: std::path::PathBuf,
+    },
+    /// Run only the settlement submitter, for sequencers started with SETTLEMENT_SUBMITTER=external
+    Submitter,
+    /// Run as the settlement co-signer: re-verify batches and proofs and countersign
+    /// settlement transactions on --port (run on its own host, with its own key)
+    CoSigner,
+    /// Replay recorded bet arrivals under different batch sizes and batching windows,
+    /// and recommend the cheapest schedule meeting a latency target
+    TuneBatches {
+        /// 95th percentile settlement latency (arrival to confirmation) to stay within
+        #[arg(long, default_value_t = 30_000)]
+        target_p95_ms: u64,
+    },
 }
 
 #[derive(Clone)]
@@ -97,8 +338,41 @@ pub struct AppState {
     pub oracle_client: OracleClient,
     pub settlement_stats: SettlementStats,
     pub solana_client: Option<Arc<SolanaClient>>, // Optional for Phase 2 testing
-    pub settlement_prover: Option<Arc<SettlementProver>>, // Phase 3e: ZK proof generation
+    pub settlement_prover: Arc<ProverReadiness>,  // Phase 3e: ZK proof generation (warm-up aware)
     pub settlement_persistence: Arc<SettlementPersistence>, // Phase 3e: Crash-safe queue
+    pub report_service: Arc<ReportService>,       // Signed fairness reports
+    pub admin_auth: AdminAuth,
+    pub geo_policy: Arc<GeoPolicy>, // Country policy for betting endpoints
+    pub game_controls: Arc<GameControls>, // Per-game kill switches
+    pub compliance: Arc<ComplianceHook>, // KYC/allowlist checks
+    pub dev_mode: bool,             // Enables devnet onboarding helpers
+    pub demo: Option<DemoConfig>,   // Testnet play balances for new addresses
+    pub bet_ids: Arc<BetIdGenerator>, // Structured, chronologically sortable bet IDs
+    pub strict_proofs: bool,        // Never settle with placeholder proofs
+    pub bet_nonces: Arc<BetNonceCache>, // Exactly-once bet placement per (player, nonce)
+    pub admission: Arc<AdmissionController>, // Backpressure on bet intake
+    pub keys: SequencerKeys,        // Sequencer and VRF signing keys
+    pub vrf_signer: Arc<VrfSigner>, // Signs bet outcomes with keys.vrf off the runtime
+    pub treasury: Option<Arc<TreasuryManager>>, // House bankroll rebalancing
+    pub fee_payer: Option<Arc<FeePayerMonitor>>, // Sequencer fee-payer balance and refills
+    pub escrow: Option<EscrowConfig>, // High-value bets must be escrowed on-chain
+    pub vrf_transcript: Option<Arc<VrfTranscript>>, // Anchors issued VRF proofs on-chain
+    pub vrf_journal: Option<Arc<VrfJournal>>, // Write-ahead log of every VRF proof issued
+    pub bet_recovery: Arc<RecoveryLog>, // Startup decisions on journaled bets never recorded
+    pub disputes: Arc<DisputeStore>, // Player disputes against recorded bets
+    pub withdrawal_fees: WithdrawalFeeConfig,
+    pub tokens: TokenConfig, // Per-token decimals, units and dust thresholds
+    pub payouts: PayoutSchedule, // Must match the verifier's coinflip GameConfig
+    pub house_limits: Arc<HouseLimits>, // Bet, payout and exposure caps per token
+    pub adjustments: Option<Arc<AdjustmentManager>>, // Dual-control manual balance adjustments
+    pub backups: Option<Arc<BackupService>>, // Scheduled database and settlement backups
+    pub self_check: Arc<SelfCheckReport>, // Startup checks; read-only when they failed
+    pub outcome_monitor: Arc<OutcomeMonitor>, // Rolling statistical tests of VRF outcomes
+    pub rounds: Arc<RoundFeed>, // Round commitments and reveals pushed to sessions
+    pub withdrawal_limits: Arc<WithdrawalLimiter>, // Per-player and hot-wallet velocity caps
+    pub upgrade: Arc<UpgradeCoordinator>, // Coordinated program upgrades; stops intake
+    pub genesis: Option<Arc<Genesis>>, // Deployment manifest the sequencer was started with
+    pub signed_responses: bool, // Sign balance and bet lookups with the sequencer key
 }
 
 #[derive(Deserialize, Serialize)]
@@ -106,6 +380,8 @@ pub struct BetRequest {
     pub player_address: String,
     pub amount: u64,
     pub guess: bool, // true for heads, false for tails
+    #[serde(default)]
+    pub nonce: Option<String>, // Client-chosen; resends with the same nonce replay the original bet
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -118,6 +394,9 @@ pub struct BetResponse {
     pub won: bool,
     pub payout: u64,
     pub timestamp: DateTime<Utc>,
+    pub settlement: Option<BetSettlementLink>, // Batch, on-chain index and tx once settled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrf_proof: Option<String>, // VRF signature the outcome was derived from
 }
 
 #[derive(Serialize, Deserialize)]
@@ -132,6 +411,13 @@ pub struct WithdrawRequest {
     pub amount: u64,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct WithdrawResponse {
+    #[serde(flatten)]
+    pub balance: BalanceResponse,
+    pub withdrawal: WithdrawalEntry, // Itemized fees and net payout
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct BalanceResponse {
     pub player_address: String,
@@ -142,6 +428,32 @@ pub struct BalanceResponse {
     pub total_won: u64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub demo: bool, // Demo play balance: not withdrawable, never settled on-chain
+}
+
+/// Unsigned initialize_user_vault transaction (base64 wire format) for the player
+/// to sign and send, included while their vault PDA doesn't exist on-chain
+#[derive(Serialize, Deserialize)]
+pub struct UserVaultInitialization {
+    pub user_vault: String,
+    pub transaction: String,
+}
+
+/// Where a player can fund their vault with a plain wallet transfer
+#[derive(Serialize, Deserialize)]
+pub struct DepositAddressResponse {
+    pub player_address: String,
+    pub deposit_address: String,
+    pub user_vault: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DepositResponse {
+    #[serde(flatten)]
+    pub balance: BalanceResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_vault_initialization: Option<UserVaultInitialization>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -185,6 +497,7 @@ impl From<&PlayerBalance> for BalanceResponse {
             total_won: balance.total_won as u64,
             created_at: balance.created_at,
             updated_at: balance.updated_at,
+            demo: balance.demo,
         }
     }
 }
@@ -200,6 +513,8 @@ impl From<&Bet> for BetResponse {
             won: bet.won,
             payout: bet.payout as u64,
             timestamp: bet.timestamp,
+            settlement: bet.settlement.clone(),
+            vrf_proof: None,
         }
     }
 }
@@ -211,15 +526,111 @@ pub fn create_app(state: AppState) -> Router {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // Geo policy and admission control apply to betting endpoints only
+    let geo_layer = middleware::from_fn_with_state(state.clone(), geo_policy_middleware);
+    let admission_layer = middleware::from_fn_with_state(state.clone(), admission_middleware);
+    let read_only_layer = middleware::from_fn_with_state(state.clone(), read_only_middleware);
+    let signing_layer = middleware::from_fn_with_state(state.clone(), sign_response_middleware);
+
     Router::new()
         .route("/health", get(health_check))
-        .route("/v1/bet", post(bet_handler))
-        .route("/v1/balance/:address", get(get_balance))
+        .route("/health/ready", get(readiness_check))
+        .route("/health/self-check", get(get_self_check))
+        .route(
+            "/v1/bet",
+            post(bet_handler)
+                .route_layer(admission_layer)
+                .route_layer(geo_layer),
+        )
+        .route(
+            "/v1/bet/:bet_id",
+            get(get_bet).route_layer(signing_layer.clone()),
+        )
+        .route(
+            "/v1/bet/:bet_id/fairness",
+            get(get_bet_fairness).route_layer(signing_layer.clone()),
+        )
+        .route(
+            "/v1/balance/:address",
+            get(get_balance).route_layer(signing_layer),
+        )
         .route("/v1/deposit", post(deposit_handler))
+        .route("/v1/deposit-address/:address", get(get_deposit_address))
         .route("/v1/withdraw", post(withdraw_handler))
+        .route("/v1/withdrawals/:address", get(get_player_withdrawals))
+        .route("/v1/withdrawal-proof/:address", get(get_withdrawal_proof))
+        .route("/v1/activity/:address", get(get_player_activity))
         .route("/v1/bets/:address", get(get_player_bets))
+        .route(
+            "/v1/batches/:batch_id/finality-proof",
+            get(get_finality_proof),
+        )
         .route("/v1/recent-bets", get(get_recent_bets))
+        .route("/v1/recovery/bets", get(list_recovered_bets))
+        .route("/v1/recovery/bets/:bet_id", get(get_recovered_bet))
+        .route("/v1/limits", get(get_limits))
         .route("/v1/settlement-stats", get(get_settlement_stats))
+        .route("/v1/analytics/settlement-costs", get(get_settlement_costs))
+        .route("/v1/transparency", get(get_transparency))
+        .route("/v1/session/ws", get(session_websocket))
+        .route("/v1/reports", get(list_reports))
+        .route("/v1/reports/:report_id", get(get_report))
+        .route(
+            "/v1/admin/geo-policy",
+            get(get_geo_policy).put(update_geo_policy),
+        )
+        .route(
+            "/v1/admin/geo-policy/countries/:country",
+            post(add_geo_policy_country).delete(remove_geo_policy_country),
+        )
+        .route("/v1/admin/games", get(list_games))
+        .route("/v1/admin/games/:game", put(update_game))
+        .route("/v1/dev/faucet", post(faucet_handler))
+        .route("/v1/demo/faucet", post(demo_faucet_handler))
+        .route(
+            "/v1/admin/compliance/:address",
+            delete(invalidate_compliance_decision),
+        )
+        .route("/v1/disputes", post(file_dispute))
+        .route("/v1/disputes/:id", get(get_dispute))
+        .route("/v1/admin/disputes", get(list_disputes))
+        .route("/v1/admin/disputes/:id/review", post(review_dispute))
+        .route("/v1/admin/disputes/:id/resolve", post(resolve_dispute))
+        .route("/v1/admin/house-fees", get(get_house_fees))
+        .route("/v1/admin/queue", get(get_settlement_queue))
+        .route("/v1/admin/outcome-monitor", get(get_outcome_monitor))
+        .route("/v1/admin/withdrawal-limits", get(get_withdrawal_limits))
+        .route(
+            "/v1/admin/withdrawal-limits/overrides",
+            post(override_withdrawal_limits),
+        )
+        .route(
+            "/v1/admin/adjustments",
+            get(list_balance_adjustments).post(propose_balance_adjustment),
+        )
+        .route(
+            "/v1/admin/adjustments/:id/approve",
+            post(approve_balance_adjustment),
+        )
+        .route("/v1/admin/ledger", get(get_ledger))
+        .route("/v1/admin/backups", post(run_backup))
+        .route("/v1/admin/treasury/movements", get(list_treasury_movements))
+        .route("/v1/admin/fee-payer", get(get_fee_payer_status))
+        .route("/v1/admin/batches/:id/dry-run", post(dry_run_batch))
+        .route("/v1/keys", get(list_signing_keys))
+        .route("/v1/admin/keys/revoke", post(revoke_signing_key))
+        .route("/v1/admin/upgrade", get(get_upgrade_status))
+        .route("/v1/admin/upgrade/begin", post(begin_upgrade))
+        .route("/v1/admin/upgrade/snapshot", post(snapshot_upgrade))
+        .route("/v1/admin/upgrade/pause", post(confirm_upgrade_paused))
+        .route("/v1/admin/upgrade/verify", post(verify_upgrade))
+        .route("/v1/admin/upgrade/resume", post(resume_upgrade))
+        .route("/v1/admin/upgrade/abort", post(abort_upgrade))
+        .route(
+            "/v1/admin/treasury/movements/:id/approve",
+            post(approve_treasury_movement),
+        )
+        .layer(read_only_layer)
         .layer(cors)
         .with_state(state)
 }
@@ -228,15 +639,83 @@ pub async fn health_check() -> &'static str {
     "OK"
 }
 
+/// Results of the startup self-check
+pub async fn get_self_check(State(state): State<AppState>) -> Json<SelfCheckReport> {
+    Json((*state.self_check).clone())
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub prover: ProverStatus,
+    pub strict_proofs: bool,
+}
+
+/// Ready once the prover has finished warming up (or is disabled/failed and falls back)
+pub async fn readiness_check(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let prover = state.settlement_prover.status();
+    let ready = match prover {
+        ProverStatus::Ready { .. } => true,
+        ProverStatus::Disabled => !state.strict_proofs,
+        _ => false,
+    };
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            prover,
+            strict_proofs: state.strict_proofs,
+        }),
+    )
+}
+
+// Start proving the batch `key` while it is still filling (SPECULATIVE_PROVING=true)
+fn speculate_open_batch(
+    composer: &BatchComposer,
+    key: &BatchKey,
+    readiness: &ProverReadiness,
+    settlement_persistence: Arc<SettlementPersistence>,
+) {
+    let Some(prover) = readiness.prover() else {
+        return;
+    };
+    if !prover.speculative_proving() {
+        return;
+    }
+    let Some(items) = composer.open_batch(key) else {
+        return;
+    };
+    let items = items.to_vec();
+    tokio::spawn(async move {
+        let speculated = match settlement_persistence.settled_balances().await {
+            Ok(settled) => prover.speculate(&settled, &items).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = speculated {
+            tracing::debug!("Speculative proving skipped: {}", e);
+        }
+    });
+}
+
 // Settlement batch processor for ZK proof preparation (VF Node pattern)
 async fn process_settlement_batch(
     batch: &[SettlementItem],
     stats: &SettlementStats,
-    solana_client: Option<Arc<SolanaClient>>,
-    settlement_prover: Option<Arc<SettlementProver>>,
+    submitter: Option<&SettlementSubmitter>, // None when an external submitter takes over
+    settlement_prover: Arc<ProverReadiness>,
     settlement_persistence: Arc<SettlementPersistence>,
+    db: Arc<Database>,
+    strict_proofs: bool,
 ) {
     let start_time = std::time::Instant::now();
+    let in_progress = stats.queue.start_batch(batch);
 
     tracing::info!(
         "Processing settlement batch of {} items for ZK proof generation",
@@ -265,33 +744,77 @@ async fn process_settlement_batch(
         }
     };
 
+    in_progress.persisted(actual_batch_id);
+
+    // Link bets to the batch in on-chain order so each bet can report its settlement
+    let bet_ids: Vec<String> = batch.iter().map(|item| item.bet_id.clone()).collect();
+    if let Err(e) = db.link_bets_to_batch(actual_batch_id, &bet_ids).await {
+        error!("Failed to link bets to batch {}: {}", actual_batch_id, e);
+    }
+
     stats
         .items_in_current_batch
         .fetch_sub(batch.len() as u64, Ordering::Relaxed);
     *stats.last_batch_processed_at.lock() = Some(Utc::now());
 
-    // Phase 3e: Generate ZK proof if prover is available
-    let proof_data = if let Some(settlement_prover) = settlement_prover {
+    // Phase 3e: Generate ZK proof if prover is available (waits for warm-up, bets keep flowing)
+    in_progress.phase(BatchPhase::Proving);
+    let proving_started = std::time::Instant::now();
+    let proof_data = if let Some(settlement_prover) = settlement_prover.wait_for_prover().await {
         info!(
             "Generating ZK proof for batch {} with {} items",
             actual_batch_id,
             batch.len()
         );
 
-        match settlement_prover.generate_proof(batch).await {
+        // The proof moves the settled state root on, as the verifier will check it
+        let proof = match settlement_persistence.settled_balances().await {
+            Ok(settled) => {
+                settlement_prover
+                    .generate_proof(actual_batch_id, &settled, batch)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match proof {
             Ok(proof) => {
-                info!("ZK proof generated successfully for batch {}", actual_batch_id);
+                info!(
+                    "ZK proof generated successfully for batch {}",
+                    actual_batch_id
+                );
 
                 // Verify the proof for testing
                 match settlement_prover.verify_proof(&proof).await {
                     Ok(true) => {
-                        info!("ZK proof verified successfully for batch {}", actual_batch_id);
+                        info!(
+                            "ZK proof verified successfully for batch {}",
+                            actual_batch_id
+                        );
 
                         // Convert proof to bytes for Solana submission
                         match proof.to_bytes() {
-                            Ok(proof_bytes) => Some(proof_bytes),
+                            Ok(proof_bytes) => {
+                                // Keep the verified proof so its state root can be published
+                                if let Err(e) = settlement_persistence
+                                    .store_proof(
+                                        actual_batch_id,
+                                        &proof_bytes,
+                                        settlement_prover.verifying_key(),
+                                    )
+                                    .await
+                                {
+                                    error!(
+                                        "Failed to store proof for batch {}: {}",
+                                        actual_batch_id, e
+                                    );
+                                }
+                                Some(proof_bytes)
+                            }
                             Err(e) => {
-                                error!("Failed to serialize proof for batch {}: {}", actual_batch_id, e);
+                                error!(
+                                    "Failed to serialize proof for batch {}: {}",
+                                    actual_batch_id, e
+                                );
                                 None
                             }
                         }
@@ -301,66 +824,55 @@ async fn process_settlement_batch(
                         None
                     }
                     Err(e) => {
-                        error!("Error verifying ZK proof for batch {}: {}", actual_batch_id, e);
+                        error!(
+                            "Error verifying ZK proof for batch {}: {}",
+                            actual_batch_id, e
+                        );
                         None
                     }
                 }
             }
             Err(e) => {
-                error!("Failed to generate ZK proof for batch {}: {}", actual_batch_id, e);
+                error!(
+                    "Failed to generate ZK proof for batch {}: {}",
+                    actual_batch_id, e
+                );
                 None
             }
         }
+    } else if strict_proofs {
+        None
     } else {
         // Fallback to placeholder proof for Phase 2 compatibility
         info!(
-            "Using placeholder proof for batch {} (ZK prover not enabled)",
-            batch_id
+            "Using placeholder proof for batch {} (ZK prover {:?})",
+            batch_id,
+            settlement_prover.status()
         );
-        Some(vec![0u8; 64]) // 64 bytes of zeros
+        Some(submitter::placeholder_proof())
     };
 
-    // Submit to Solana if client is available
-    if let Some(solana_client) = solana_client {
-        if let Some(proof_bytes) = proof_data {
-            match submit_batch_to_solana_with_proof(&*solana_client, actual_batch_id, batch, &proof_bytes)
-                .await
-            {
-                Ok(signature) => {
-                    info!(
-                        "Batch {} submitted to Solana successfully with proof: {}",
-                        actual_batch_id, signature
-                    );
-                    
-                    // Store the transaction signature in settlement persistence
-                    if let Err(e) = settlement_persistence.store_transaction(actual_batch_id, &signature.to_string()).await {
-                        error!("Failed to store transaction signature for batch {}: {}", actual_batch_id, e);
-                    } else {
-                        info!("Transaction signature stored for batch {}: {}", actual_batch_id, signature);
-                    }
-                }
-                Err(e) => {
-                    error!(
-                        "Failed to submit batch {} to Solana: {}. Continuing with local processing.",
-                        actual_batch_id, e
-                    );
-                }
-            }
-        } else {
-            error!(
-                "No proof available for batch {}, skipping Solana submission",
-                actual_batch_id
-            );
-        }
-    } else {
-        // For testing: store a mock transaction signature when Solana is not available
-        info!("Solana not available, storing mock transaction signature for batch {}", actual_batch_id);
-        let mock_signature = format!("mock_tx_{}_confirmed", actual_batch_id);
-        if let Err(e) = settlement_persistence.store_transaction(actual_batch_id, &mock_signature).await {
-            error!("Failed to store mock transaction signature for batch {}: {}", actual_batch_id, e);
-        } else {
-            info!("Mock transaction signature stored for batch {}: {}", actual_batch_id, mock_signature);
+    SettlementStats::record_duration(&stats.avg_proving_ms, proving_started.elapsed());
+
+    // Strict mode: a batch without a verified proof must never reach the chain
+    if strict_proofs && proof_data.is_none() {
+        error!(
+            "STRICT PROOFS: batch {} has no verified ZK proof (prover {:?}); refusing to submit",
+            actual_batch_id,
+            settlement_prover.status()
+        );
+        if let Err(e) = settlement_persistence
+            .update_batch_status(
+                actual_batch_id,
+                SettlementBatchStatus::Failed,
+                Some("No verified ZK proof (strict proof mode)".to_string()),
+            )
+            .await
+        {
+            error!("Failed to mark batch {} as failed: {}", actual_batch_id, e);
         }
+        update_bet_settlements(&db, actual_batch_id, BetSettlementStatus::Failed, None).await;
+        return;
     }
 
     // Log batch details for debugging
@@ -381,11 +893,43 @@ async fn process_settlement_batch(
     .await
     .ok();
 
-    // Phase 3e: Mark batch as completed in persistent storage
-    let actual_batch_id_str = format!("batch_{}", actual_batch_id);
-    if let Err(e) = settlement_persistence.mark_completed(&actual_batch_id_str).await {
-        error!("Failed to mark batch {} as completed: {}", actual_batch_id, e);
-        // Continue anyway - the batch was processed successfully
+    in_progress.phase(BatchPhase::Submitting);
+    match (submitter, proof_data) {
+        (Some(submitter), Some(proof_bytes)) => {
+            let submission_started = std::time::Instant::now();
+            submitter.submit(actual_batch_id, batch, &proof_bytes).await;
+            SettlementStats::record_duration(
+                &stats.avg_submission_ms,
+                submission_started.elapsed(),
+            );
+        }
+        (None, Some(_)) => {
+            // External submitter: the batch is handed off through the settlement file.
+            // Placeholder proofs aren't stored, so mark those batches proved here.
+            if let Err(e) = settlement_persistence
+                .update_batch_status(actual_batch_id, SettlementBatchStatus::Proved, None)
+                .await
+            {
+                error!("Failed to mark batch {} as proved: {}", actual_batch_id, e);
+            }
+        }
+        (_, None) => {
+            error!(
+                "No proof available for batch {}, skipping Solana submission",
+                actual_batch_id
+            );
+            if let Err(e) = settlement_persistence
+                .update_batch_status(
+                    actual_batch_id,
+                    SettlementBatchStatus::Failed,
+                    Some("No ZK proof".to_string()),
+                )
+                .await
+            {
+                error!("Failed to mark batch {} as failed: {}", actual_batch_id, e);
+            }
+            update_bet_settlements(&db, actual_batch_id, BetSettlementStatus::Failed, None).await;
+        }
     }
 
     tracing::info!(
@@ -395,151 +939,333 @@ async fn process_settlement_batch(
     );
 }
 
-/// Submit settlement batch to Solana (Phase 2 implementation)
-async fn submit_batch_to_solana(
-    solana_client: &SolanaClient,
-    batch_id: u64,
-    batch: &[SettlementItem],
-) -> Result<solana_sdk::signature::Signature> {
-    use solana_sdk::pubkey::Pubkey;
-    use std::str::FromStr;
-
-    // Convert settlement items to Solana batch format
-    let bet_settlements: Vec<BetSettlement> = batch
-        .iter()
-        .enumerate()
-        .map(|(i, item)| {
-            // Parse user address (in real implementation, this would be validated)
-            let user =
-                Pubkey::from_str(&item.player_address).unwrap_or_else(|_| Pubkey::new_unique());
-
-            // Determine outcome and payout logic
-            let is_win = item.payout > item.amount.abs() as i64;
-            let bet_amount = item.amount.abs() as u64;
-            let payout = if is_win { item.payout as u64 } else { 0 };
-
-            BetSettlement {
-                bet_id: batch_id * 1000 + i as u64,
-                user,
-                bet_amount,
-                user_guess: if is_win { 1 } else { 0 }, // Simplified for Phase 2
-                outcome: if is_win { 1 } else { 0 },
-                payout,
-            }
+// Run the compliance hook for an action, mapping denials to 403
+async fn enforce_compliance(
+    state: &AppState,
+    address: &str,
+    action: ComplianceAction,
+    amount: u64,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    state
+        .compliance
+        .check(address, action, amount)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            (
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
         })
-        .collect();
+}
 
-    let batch_data = BatchSettlementData {
-        batch_id,
-        sequencer_nonce: batch_id,
-        bets: bet_settlements,
+pub async fn bet_handler(
+    State(state): State<AppState>,
+    CustomJson(bet_request): CustomJson<BetRequest>,
+) -> Result<Json<BetResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(nonce) = bet_request.nonce.clone() else {
+        return place_bet(state, bet_request).await;
     };
+    let player_address = bet_request.player_address.clone();
 
-    // Create placeholder proof for Phase 2
-    let proof = vec![0u8; 64]; // 64 bytes of zeros
+    // Resends of the same nonce replay the original bet instead of placing a new one
+    let conflict = |error: &str| {
+        Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: error.to_string(),
+            }),
+        ))
+    };
+    match state.bet_nonces.reserve(
+        &player_address,
+        &nonce,
+        bet_request.amount,
+        bet_request.guess,
+    ) {
+        NonceReservation::New => {}
+        NonceReservation::Duplicate(original) => {
+            info!(
+                "Replaying bet {} for resent nonce {} from {}",
+                original.bet_id, nonce, player_address
+            );
+            return Ok(Json(original));
+        }
+        NonceReservation::InFlight => {
+            return conflict("A bet with this nonce is still being processed")
+        }
+        NonceReservation::Mismatch => {
+            return conflict("Nonce was already used for a different bet")
+        }
+    }
 
-    // Submit to Solana
-    solana_client
-        .submit_settlement_batch(batch_data, proof)
-        .await
+    let bet_nonces = state.bet_nonces.clone();
+    let result = place_bet(state, bet_request).await;
+    match &result {
+        Ok(Json(response)) => bet_nonces.complete(&player_address, &nonce, response),
+        Err(_) => bet_nonces.release(&player_address, &nonce),
+    }
+    result
 }
 
-/// Submit settlement batch to Solana with ZK proof (Phase 3e implementation)
-async fn submit_batch_to_solana_with_proof(
-    solana_client: &SolanaClient,
-    batch_id: u64,
-    batch: &[SettlementItem],
-    proof_data: &[u8],
-) -> Result<solana_sdk::signature::Signature> {
-    use solana_sdk::pubkey::Pubkey;
-    use std::str::FromStr;
-
-    info!(
-        "Submitting batch {} with ZK proof to Solana (size: {} bytes)",
-        batch_id,
-        proof_data.len()
-    );
+async fn place_bet(
+    state: AppState,
+    mut bet_request: BetRequest,
+) -> Result<Json<BetResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let start_time = std::time::Instant::now();
 
-    // Convert settlement items to Solana batch format
-    let bet_settlements: Vec<BetSettlement> = batch
-        .iter()
-        .enumerate()
-        .map(|(i, item)| {
-            // Parse user address (in real implementation, this would be validated)
-            let user =
-                Pubkey::from_str(&item.player_address).unwrap_or_else(|_| Pubkey::new_unique());
+    if let Err(status) = state.game_controls.check(GameKind::Coinflip) {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: format!(
+                    "{} is temporarily disabled{}",
+                    status.game.name(),
+                    status
+                        .reason
+                        .map(|reason| format!(": {}", reason))
+                        .unwrap_or_default()
+                ),
+            }),
+        ));
+    }
 
-            // Convert bet_id string to u64 by hashing
-            let bet_id = i as u64; // Simple index-based ID for batch
+    // Validate bet amount against the dust threshold and round it down to the token's
+    // unit; the player is only ever debited the normalized amount
+    let sol = state.tokens.spec(SettlementToken::Sol);
+    bet_request.amount = match sol.normalize(bet_request.amount) {
+        Ok((amount, _)) => amount,
+        Err(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!(
+                        "Bet amount must be at least {} lamports",
+                        sol.dust_threshold
+                    ),
+                }),
+            ))
+        }
+    };
 
-            // Determine outcome from payout (won if payout > 0)
-            let won = item.payout > 0;
-            let user_guess = 1u8; // Default guess (in real system this would be stored)
-            let outcome = if won { user_guess } else { 1 - user_guess };
+    if let Some(escrow) = state
+        .escrow
+        .as_ref()
+        .filter(|escrow| escrow.requires_escrow(bet_request.amount))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!(
+                    "Bets of {} lamports or more must be placed through on-chain escrow",
+                    escrow.min_amount
+                ),
+            }),
+        ));
+    }
 
-            BetSettlement {
-                bet_id,
-                user,
-                bet_amount: item.amount as u64,
-                user_guess,
-                outcome,
-                payout: item.payout as u64,
-            }
-        })
-        .collect();
+    enforce_compliance(
+        &state,
+        &bet_request.player_address,
+        ComplianceAction::Bet,
+        bet_request.amount,
+    )
+    .await?;
 
-    let batch_data = BatchSettlementData {
-        batch_id,
-        sequencer_nonce: batch_id, // Use batch_id as nonce
-        bets: bet_settlements,
+    // Reject bets the player cannot cover before any randomness is drawn
+    let balance = state
+        .db
+        .get_player_balance(&bet_request.player_address)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+        })?;
+    let balance = match balance {
+        Some(balance) => Some(balance),
+        None => new_demo_balance(&state, &bet_request.player_address).await?,
     };
+    let demo = balance.as_ref().is_some_and(|balance| balance.demo);
+    match balance {
+        None => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Player not found".to_string(),
+                }),
+            ))
+        }
+        Some(balance) if balance.balance < bet_request.amount as i64 => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!(
+                        "Insufficient balance. Required: {}, Available: {}",
+                        bet_request.amount, balance.balance
+                    ),
+                }),
+            ))
+        }
+        Some(_) => {}
+    }
 
-    // Submit to Solana with real ZK proof
-    solana_client
-        .submit_settlement_batch(batch_data, proof_data.to_vec())
+    // A player's first settled bet takes a state tree leaf; turn new players away once
+    // the tree can't hold them rather than settle a batch that can't be proven
+    let queued_bets = state
+        .settlement_stats
+        .items_in_current_batch
+        .load(Ordering::Relaxed) as usize;
+    let admitted = state
+        .settlement_persistence
+        .admits_player(&bet_request.player_address, queued_bets)
         .await
-}
-
-pub async fn bet_handler(
-    State(state): State<AppState>,
-    CustomJson(bet_request): CustomJson<BetRequest>,
-) -> Result<Json<BetResponse>, StatusCode> {
-    let start_time = std::time::Instant::now();
-
-    // Validate bet amount (minimum 1000 lamports = 0.000001 SOL)
-    if bet_request.amount < 1000 {
-        return Err(StatusCode::BAD_REQUEST);
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Settlement state error: {}", e),
+                }),
+            )
+        })?;
+    if !admitted {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: "The settlement state tree is full; new players can't be settled"
+                    .to_string(),
+            }),
+        ));
     }
 
-    // CPU-intensive random generation in background thread (VF Node pattern)
-    let coin_result = tokio::task::spawn_blocking(move || {
-        let mut rng = rand::thread_rng();
-        rng.gen::<bool>()
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Generate unique bet ID
-    let bet_id = format!("bet_{}", Uuid::new_v4().simple());
-
-    // Determine if player won
-    let won = bet_request.guess == coin_result;
-
-    // Calculate payout (2x for winning, 0 for losing)
-    let payout = if won { bet_request.amount * 2 } else { 0 };
+    // Demo bets move no real funds, so they count against the caps but not the exposure
+    let limit_error = |e: LimitError| {
+        let status = match e {
+            LimitError::ExposureExceeded { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+    let reservation = if demo {
+        state
+            .house_limits
+            .check(SettlementToken::Sol, &state.payouts, bet_request.amount)
+            .map_err(limit_error)?;
+        None
+    } else {
+        Some(
+            state
+                .house_limits
+                .reserve(
+                    SettlementToken::Sol,
+                    &state.payouts,
+                    bet_request.amount,
+                    Utc::now(),
+                )
+                .map_err(limit_error)?,
+        )
+    };
 
-    // Create immediate response (VF Node instant response pattern)
-    let response = BetResponse {
-        bet_id: bet_id.clone(),
-        player_address: bet_request.player_address.clone(),
+    // Generate unique, chronologically sortable bet ID
+    let bet_id = state.bet_ids.next_id();
+    let timestamp = bet_id.timestamp();
+    let bet_id = bet_id.to_string();
+
+    // VRF proof over the bet; only the signature leaves this task, and concurrent bets
+    // share the signing thread's wake-ups. The proof is journaled before anything else
+    // sees it: an outcome that can't be journaled is never returned.
+    let input = CoinflipInput {
+        bet_id: &bet_id,
+        player: &bet_request.player_address,
         amount: bet_request.amount,
         guess: bet_request.guess,
-        result: coin_result,
-        won,
-        payout,
-        timestamp: Utc::now(),
     };
-
+    let proof = async {
+        let (coin_result, vrf_proof) = state
+            .vrf_signer
+            .prove_coinflip(&input)
+            .await
+            .map_err(|_| "Failed to generate bet outcome")?;
+        if let Some(journal) = &state.vrf_journal {
+            journal
+                .append(
+                    &bet_id,
+                    &input.message(),
+                    &state.vrf_signer.pubkey(),
+                    &vrf_proof,
+                )
+                .await
+                .map_err(|e| {
+                    tracing::error!("Bet {} not placed: {}", bet_id, e);
+                    "Failed to record bet outcome"
+                })?;
+        }
+        Ok::<_, &str>((coin_result, vrf_proof))
+    }
+    .await;
+    let (coin_result, vrf_proof) = match proof {
+        Ok(proof) => proof,
+        Err(error) => {
+            if let Some(reservation) = reservation {
+                state.house_limits.release(reservation);
+            }
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: error.to_string(),
+                }),
+            ));
+        }
+    };
+    if let Some(transcript) = &state.vrf_transcript {
+        transcript.record(&bet_id, &vrf_proof);
+    }
+    state
+        .outcome_monitor
+        .record(&state.vrf_signer.pubkey(), coin_result);
+
+    // Determine if player won
+    let won = bet_request.guess == coin_result;
+
+    // Calculate payout (the payout schedule for winning, 0 for losing), rounded down
+    // like the verifier program
+    let payout = if won {
+        state.payouts.win_payout(bet_request.amount)
+    } else {
+        0
+    };
+    if let Some(reservation) = reservation {
+        state.house_limits.resolve(
+            reservation,
+            payout as i64 - bet_request.amount as i64,
+            Utc::now(),
+        );
+    }
+
+    // Create immediate response (VF Node instant response pattern)
+    let response = BetResponse {
+        bet_id: bet_id.clone(),
+        player_address: bet_request.player_address.clone(),
+        amount: bet_request.amount,
+        guess: bet_request.guess,
+        result: coin_result,
+        won,
+        payout,
+        timestamp,
+        settlement: None,
+        vrf_proof: Some(vrf_proof.to_string()),
+    };
+
     // Background processing: Save bet and update balances (non-blocking)
     let state_clone = state.clone();
     let response_clone = response.clone();
@@ -556,6 +1282,8 @@ pub async fn bet_handler(
             won,
             payout: payout as i64,
             timestamp: response_clone.timestamp,
+            settlement: None,
+            escrow: None,
         };
 
         // Save bet to database (background)
@@ -580,6 +1308,16 @@ pub async fn bet_handler(
             );
         }
 
+        // Demo bets move no real funds, so there is nothing to settle on-chain
+        if demo {
+            tracing::info!(
+                "Demo bet {} processed in {}μs (background)",
+                bet.id,
+                processing_time.as_micros()
+            );
+            return;
+        }
+
         // Add to settlement queue for ZK proof batching (VF Node pattern)
         let settlement_item = SettlementItem {
             bet_id: bet_id.clone(),
@@ -587,21 +1325,12 @@ pub async fn bet_handler(
             amount: bet_request.amount as i64,
             payout: payout as i64,
             timestamp: response_clone.timestamp,
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: Some(vrf_proof.to_string()),
         };
 
-        // Update settlement statistics
-        state_clone
-            .settlement_stats
-            .total_items_queued
-            .fetch_add(1, Ordering::Relaxed);
-        state_clone
-            .settlement_stats
-            .items_in_current_batch
-            .fetch_add(1, Ordering::Relaxed);
-
-        if let Err(e) = state_clone.settlement_sender.send(settlement_item) {
-            tracing::error!("Failed to queue settlement item for bet {}: {}", bet_id, e);
-        }
+        queue_for_settlement(&state_clone, settlement_item);
 
         tracing::info!(
             "Bet {} processed in {}μs (background)",
@@ -614,6 +1343,163 @@ pub async fn bet_handler(
     Ok(Json(response))
 }
 
+/// Hand a bet to the settlement queue, keeping the settlement statistics in step
+fn queue_for_settlement(state: &AppState, settlement_item: SettlementItem) {
+    state
+        .settlement_stats
+        .total_items_queued
+        .fetch_add(1, Ordering::Relaxed);
+    state
+        .settlement_stats
+        .items_in_current_batch
+        .fetch_add(1, Ordering::Relaxed);
+
+    state.settlement_stats.queue.enqueued(&settlement_item);
+    let bet_id = settlement_item.bet_id.clone();
+    if let Err(e) = state.settlement_sender.send(settlement_item) {
+        tracing::error!("Failed to queue settlement item for bet {}: {}", bet_id, e);
+        state.settlement_stats.queue.discarded(&bet_id);
+    }
+}
+
+/// Decide every journaled bet that is in neither the database nor a settlement batch
+/// (see bet_recovery) and carry out the decision: a completed bet is recorded, applied
+/// to the balance and queued for settlement like a live one. Journal order keeps the
+/// balance checks deterministic.
+async fn recover_unrecorded_bets(state: &AppState, journal: &[VrfJournalEntry]) -> Result<()> {
+    let (mut completed, mut refunded) = (0, 0);
+    for entry in journal {
+        if state.bet_recovery.contains(&entry.nonce)
+            || state.db.get_bet(&entry.nonce).await?.is_some()
+            || state
+                .settlement_persistence
+                .is_bet_processed(&entry.nonce)
+                .await?
+        {
+            continue;
+        }
+        let bet = JournaledBet::from_entry(entry);
+        let balance = match &bet {
+            Some(bet) => state.db.get_player_balance(&bet.player_address).await?,
+            None => None,
+        };
+        let (outcome, proof) = bet_recovery::decide(
+            entry,
+            bet.as_ref(),
+            &state.keys.vrf,
+            balance.as_ref(),
+            &state.payouts,
+        );
+
+        if let (
+            RecoveryOutcome::Completed {
+                result,
+                won,
+                payout,
+            },
+            Some(bet),
+            Some(proof),
+        ) = (&outcome, &bet, proof)
+        {
+            let timestamp = bet
+                .bet_id
+                .parse::<bet_id::BetId>()
+                .map_or(entry.recorded_at, |id| id.timestamp());
+            state
+                .db
+                .save_bet(&Bet {
+                    id: bet.bet_id.clone(),
+                    player_address: bet.player_address.clone(),
+                    amount: bet.amount as i64,
+                    guess: bet.guess,
+                    result: *result,
+                    won: *won,
+                    payout: *payout as i64,
+                    timestamp,
+                    settlement: None,
+                    escrow: None,
+                })
+                .await?;
+            state
+                .db
+                .update_player_balance_after_bet(
+                    &bet.player_address,
+                    bet.amount as i64,
+                    *payout as i64,
+                )
+                .await?;
+            if !balance.as_ref().is_some_and(|balance| balance.demo) {
+                queue_for_settlement(
+                    state,
+                    SettlementItem {
+                        bet_id: bet.bet_id.clone(),
+                        player_address: bet.player_address.clone(),
+                        amount: bet.amount as i64,
+                        payout: *payout as i64,
+                        timestamp,
+                        token: SettlementToken::Sol,
+                        game: GameKind::Coinflip,
+                        vrf_proof: Some(proof.to_string()),
+                    },
+                );
+            }
+            completed += 1;
+        } else {
+            refunded += 1;
+        }
+        info!("Recovered bet {}: {:?}", entry.nonce, outcome);
+        state.bet_recovery.record(RecoveryDecision {
+            bet_id: entry.nonce.clone(),
+            journal_sequence: entry.sequence,
+            player_address: bet.as_ref().map(|bet| bet.player_address.clone()),
+            amount: bet.as_ref().map(|bet| bet.amount),
+            outcome,
+            decided_at: Utc::now(),
+        })?;
+    }
+    if completed + refunded > 0 {
+        info!(
+            "Bet recovery: {} interrupted bets completed, {} refunded",
+            completed, refunded
+        );
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct RecoveryQuery {
+    pub player: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryDecisionsResponse {
+    pub decisions: Vec<RecoveryDecision>,
+}
+
+/// Recovery decisions for bets interrupted by a shutdown, optionally for one player
+pub async fn list_recovered_bets(
+    State(state): State<AppState>,
+    Query(query): Query<RecoveryQuery>,
+) -> Json<RecoveryDecisionsResponse> {
+    Json(RecoveryDecisionsResponse {
+        decisions: state.bet_recovery.list(query.player.as_deref()),
+    })
+}
+
+pub async fn get_recovered_bet(
+    State(state): State<AppState>,
+    Path(bet_id): Path<String>,
+) -> Result<Json<RecoveryDecision>, (StatusCode, Json<ErrorResponse>)> {
+    state.bet_recovery.get(&bet_id).map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("No recovery decision for bet {}", bet_id),
+            }),
+        )
+    })
+}
+
 pub async fn get_balance(
     State(state): State<AppState>,
     Path(address): Path<String>,
@@ -627,6 +1513,10 @@ pub async fn get_balance(
         )
     })?;
 
+    let balance = match balance {
+        Some(balance) => Some(balance),
+        None => new_demo_balance(&state, &address).await?,
+    };
     match balance {
         Some(balance) => Ok(Json(BalanceResponse::from(&balance))),
         None => Err((
@@ -638,687 +1528,4775 @@ pub async fn get_balance(
     }
 }
 
-pub async fn deposit_handler(
+// In demo mode, the first lookup or bet of an unknown address grants it a play balance.
+// Only valid Solana addresses qualify, so typos don't turn into funded players.
+async fn new_demo_balance(
+    state: &AppState,
+    player_address: &str,
+) -> Result<Option<PlayerBalance>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(demo) = &state.demo else {
+        return Ok(None);
+    };
+    if player_address
+        .parse::<solana_sdk::pubkey::Pubkey>()
+        .is_err()
+    {
+        return Ok(None);
+    }
+    let (balance, _) = state
+        .db
+        .grant_demo_balance(player_address, demo.play_balance as i64)
+        .await
+        .map_err(demo_error)?;
+    info!(
+        "Demo balance of {} lamports granted to {}",
+        balance.balance, player_address
+    );
+    Ok(Some(balance))
+}
+
+fn demo_error(e: DatabaseError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        DatabaseError::DemoBalance(_) => StatusCode::FORBIDDEN,
+        DatabaseError::NotDemoBalance(_) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// Look up a single bet by ID
+pub async fn get_bet(
     State(state): State<AppState>,
-    CustomJson(deposit_request): CustomJson<DepositRequest>,
-) -> Result<Json<BalanceResponse>, (StatusCode, Json<ErrorResponse>)> {
-    if deposit_request.amount == 0 {
-        return Err((
-            StatusCode::BAD_REQUEST,
+    Path(bet_id): Path<String>,
+) -> Result<Json<BetResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bet = state.db.get_bet(&bet_id).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
-                error: "Deposit amount must be greater than 0".to_string(),
+                error: format!("Database error: {}", e),
             }),
-        ));
+        )
+    })?;
+
+    match bet {
+        Some(bet) => Ok(Json(BetResponse::from(&bet))),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Bet not found".to_string(),
+            }),
+        )),
     }
+}
 
-    let balance = state
+/// Fairness bundle of a batched bet: the bet with the VRF proof of its outcome and its
+/// leaf and path in the batch's bets tree, for clients checking it against the chain
+pub async fn get_bet_fairness(
+    State(state): State<AppState>,
+    Path(bet_id): Path<String>,
+) -> Result<Json<FairnessBundle>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, error: String| (status, Json(ErrorResponse { error }));
+
+    let bet = state
         .db
-        .deposit(
-            &deposit_request.player_address,
-            deposit_request.amount as i64,
-        )
+        .get_bet(&bet_id)
         .await
         .map_err(|e| {
-            (
+            error(
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to deposit: {}", e),
-                }),
+                format!("Database error: {}", e),
             )
-        })?;
+        })?
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "Bet not found".to_string()))?;
+    let not_batched = || {
+        error(
+            StatusCode::CONFLICT,
+            format!("Bet {} is not batched yet", bet_id),
+        )
+    };
+    let link = bet.settlement.clone().ok_or_else(not_batched)?;
+    let batch = state
+        .settlement_persistence
+        .get_batch(link.batch_id)
+        .await
+        .ok_or_else(not_batched)?;
 
-    Ok(Json(BalanceResponse::from(&balance)))
+    let settlements = submitter::bet_settlements(&batch.items)
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let inclusion = BatchInclusion::new(link.batch_id, &settlements, link.onchain_index)
+        .ok_or_else(|| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Bet {} is missing from batch {}", bet_id, link.batch_id),
+            )
+        })?;
+    let mut response = BetResponse::from(&bet);
+    response.vrf_proof = batch
+        .items
+        .get(link.onchain_index as usize)
+        .filter(|item| item.bet_id == bet.id)
+        .and_then(|item| item.vrf_proof.clone());
+    Ok(Json(FairnessBundle {
+        bet: response,
+        vrf_public_key: state.keys.vrf.pubkey().to_string(),
+        batch: inclusion,
+    }))
 }
 
-pub async fn withdraw_handler(
+/// Signed statement of a player's balance with a Merkle proof of their settled balance
+/// against the state root, for processors validating an off-platform payout
+pub async fn get_withdrawal_proof(
     State(state): State<AppState>,
-    CustomJson(withdraw_request): CustomJson<WithdrawRequest>,
-) -> Result<Json<BalanceResponse>, (StatusCode, Json<ErrorResponse>)> {
-    if withdraw_request.amount == 0 {
-        return Err((
-            StatusCode::BAD_REQUEST,
+    Path(address): Path<String>,
+) -> Result<Json<WithdrawalStatement>, (StatusCode, Json<ErrorResponse>)> {
+    let internal_error = |e: anyhow::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
-                error: "Withdrawal amount must be greater than 0".to_string(),
+                error: format!("Failed to build withdrawal proof: {}", e),
             }),
-        ));
-    }
+        )
+    };
+    let not_found = |error: &str| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: error.to_string(),
+            }),
+        )
+    };
 
     let balance = state
         .db
-        .withdraw(
-            &withdraw_request.player_address,
-            withdraw_request.amount as i64,
-        )
+        .get_player_balance(&address)
         .await
-        .map_err(|e| match e {
-            DatabaseError::PlayerNotFound(_) => (
-                StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: "Player not found".to_string(),
-                }),
-            ),
-            DatabaseError::InsufficientBalance {
-                required,
-                available,
-            } => (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: format!(
-                        "Insufficient balance. Required: {}, Available: {}",
-                        required, available
-                    ),
-                }),
-            ),
-            _ => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Failed to withdraw: {}", e),
-                }),
-            ),
-        })?;
-
-    Ok(Json(BalanceResponse::from(&balance)))
+        .map_err(|e| internal_error(e.into()))?
+        .ok_or_else(|| not_found("Player not found"))?;
+    let (settled, last_settled_batch) = state
+        .settlement_persistence
+        .settled_state()
+        .await
+        .map_err(internal_error)?;
+    let body = WithdrawalStatementBody::new(
+        &address,
+        balance.balance,
+        &settled,
+        last_settled_batch,
+        Utc::now(),
+    )
+    .ok_or_else(|| not_found("Player has no settled balance"))?;
+    WithdrawalStatement::sign(body, &state.keys.sequencer)
+        .map(Json)
+        .map_err(internal_error)
 }
 
-pub async fn get_player_bets(
+/// Signed proof that a batch's settlement transaction is finalized on Solana: the
+/// transaction with its including block and the chain of blocks finalized on top of it
+pub async fn get_finality_proof(
     State(state): State<AppState>,
-    Path(address): Path<String>,
-) -> Result<Json<BetsResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let bets = state
-        .db
-        .get_player_bets(&address, Some(50))
+    Path(batch_id): Path<u64>,
+) -> Result<Json<FinalityProof>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, error: String| (status, Json(ErrorResponse { error }));
+
+    let batch = state
+        .settlement_persistence
+        .get_batch(batch_id)
+        .await
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "Batch not found".to_string()))?;
+    let signature = match (&batch.status, &batch.transaction_signature) {
+        (SettlementBatchStatus::Confirmed, Some(signature)) => signature
+            .parse::<solana_sdk::signature::Signature>()
+            .map_err(|_| {
+                error(
+                    StatusCode::NOT_FOUND,
+                    "Batch has no on-chain settlement transaction".to_string(),
+                )
+            })?,
+        _ => {
+            return Err(error(
+                StatusCode::CONFLICT,
+                format!(
+                    "Batch {} is {}, not settled on-chain",
+                    batch_id, batch.status
+                ),
+            ))
+        }
+    };
+    let Some(solana_client) = &state.solana_client else {
+        return Err(error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Solana integration is disabled".to_string(),
+        ));
+    };
+
+    let confirmation = solana_client
+        .get_settlement_confirmation(&signature, FINALITY_DEPTH)
         .await
         .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Database error: {}", e),
-                }),
+            error(
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to fetch confirmation data: {}", e),
+            )
+        })?
+        .ok_or_else(|| {
+            error(
+                StatusCode::CONFLICT,
+                format!("Batch {} is not finalized yet", batch_id),
             )
         })?;
-
-    let bet_responses: Vec<BetResponse> = bets.iter().map(BetResponse::from).collect();
-
-    Ok(Json(BetsResponse {
-        total_count: bet_responses.len(),
-        bets: bet_responses,
-    }))
+    let body = FinalityProofBody::new(batch_id, &signature, confirmation, Utc::now());
+    FinalityProof::sign(body, &state.keys.sequencer)
+        .map(Json)
+        .map_err(|e| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to sign finality proof: {}", e),
+            )
+        })
 }
 
-pub async fn get_recent_bets(
+/// Deposit address of a player: SOL sent there is swept into their vault by any relayer
+/// and credited here once the deposit watcher sees the vault's DepositEvent
+pub async fn get_deposit_address(
     State(state): State<AppState>,
-) -> Result<Json<BetsResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let bets = state.db.get_recent_bets(Some(50)).await.map_err(|e| {
+    Path(address): Path<String>,
+) -> Result<Json<DepositAddressResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(solana_client) = &state.solana_client else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: "Solana integration is disabled".to_string(),
+            }),
+        ));
+    };
+    let player: solana_sdk::pubkey::Pubkey = address.parse().map_err(|_| {
         (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
-                error: format!("Database error: {}", e),
+                error: "Invalid player address".to_string(),
             }),
         )
     })?;
-
-    let bet_responses: Vec<BetResponse> = bets.iter().map(BetResponse::from).collect();
-
-    Ok(Json(BetsResponse {
-        total_count: bet_responses.len(),
-        bets: bet_responses,
+    Ok(Json(DepositAddressResponse {
+        player_address: address,
+        deposit_address: solana_client.deposit_address_pda(&player).to_string(),
+        user_vault: solana_client.user_vault_pda(&player).to_string(),
     }))
 }
 
-#[derive(Serialize)]
-pub struct SettlementStatsResponse {
-    pub total_items_queued: u64,
-    pub total_batches_processed: u64,
-    pub items_in_current_batch: u64,
-    pub last_batch_processed_at: Option<DateTime<Utc>>,
-    pub queue_status: String,
-}
-
-pub async fn get_settlement_stats(
+pub async fn deposit_handler(
     State(state): State<AppState>,
-) -> Result<Json<SettlementStatsResponse>, StatusCode> {
-    let stats = &state.settlement_stats;
+    CustomJson(deposit_request): CustomJson<DepositRequest>,
+) -> Result<Json<DepositResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if deposit_request.amount == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Deposit amount must be greater than 0".to_string(),
+            }),
+        ));
+    }
 
-    let response = SettlementStatsResponse {
-        total_items_queued: stats.total_items_queued.load(Ordering::Relaxed),
-        total_batches_processed: stats.total_batches_processed.load(Ordering::Relaxed),
-        items_in_current_batch: stats.items_in_current_batch.load(Ordering::Relaxed),
-        last_batch_processed_at: *stats.last_batch_processed_at.lock(),
-        queue_status: "active".to_string(),
-    };
+    // Only whole units are credited; the residue goes to the house fee bucket
+    let sol = state.tokens.spec(SettlementToken::Sol);
+    let (amount, residue) = sol.normalize(deposit_request.amount).map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!(
+                    "Deposit amount must be at least {} lamports",
+                    sol.dust_threshold
+                ),
+            }),
+        )
+    })?;
 
-    Ok(Json(response))
-}
+    enforce_compliance(
+        &state,
+        &deposit_request.player_address,
+        ComplianceAction::Deposit,
+        amount,
+    )
+    .await?;
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 8)]
-async fn main() -> Result<()> {
+    let deposit_error = |e: DatabaseError| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to deposit: {}", e),
+            }),
+        )
+    };
+    let balance = state
+        .db
+        .deposit(&deposit_request.player_address, amount as i64)
+        .await
+        .map_err(|e| match e {
+            DatabaseError::DemoBalance(_) => demo_error(e),
+            _ => deposit_error(e),
+        })?;
+    state
+        .db
+        .accrue_house_fees(SettlementToken::Sol, residue)
+        .await
+        .map_err(deposit_error)?;
+
+    Ok(Json(DepositResponse {
+        balance: BalanceResponse::from(&balance),
+        user_vault_initialization: user_vault_initialization(
+            &state,
+            &deposit_request.player_address,
+        )
+        .await,
+    }))
+}
+
+// First deposits onboard the player on-chain: if their vault PDA doesn't exist yet,
+// hand back the initialize_user_vault transaction for them to sign. The lookup is
+// repeated on every deposit until the vault shows up, so a dropped onboarding
+// transaction is simply offered again. Failures never fail the deposit itself.
+async fn user_vault_initialization(
+    state: &AppState,
+    player_address: &str,
+) -> Option<UserVaultInitialization> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    let solana_client = state.solana_client.as_ref()?;
+    let user = Pubkey::from_str(player_address).ok()?;
+    let transaction = match solana_client.user_vault_initialization(&user).await {
+        Ok(transaction) => transaction?,
+        Err(e) => {
+            warn!("Failed to check user vault for {}: {}", player_address, e);
+            return None;
+        }
+    };
+    let encoded = match bincode::serialize(&transaction) {
+        Ok(bytes) => BASE64.encode(bytes),
+        Err(e) => {
+            warn!("Failed to encode user vault initialization: {}", e);
+            return None;
+        }
+    };
+    info!("Returning user vault initialization for {}", player_address);
+    Some(UserVaultInitialization {
+        user_vault: solana_client.user_vault_pda(&user).to_string(),
+        transaction: encoded,
+    })
+}
+
+pub async fn withdraw_handler(
+    State(state): State<AppState>,
+    CustomJson(withdraw_request): CustomJson<WithdrawRequest>,
+) -> Result<Json<WithdrawResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if withdraw_request.amount == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Withdrawal amount must be greater than 0".to_string(),
+            }),
+        ));
+    }
+
+    enforce_compliance(
+        &state,
+        &withdraw_request.player_address,
+        ComplianceAction::Withdraw,
+        withdraw_request.amount,
+    )
+    .await?;
+
+    // Pass through what the payout transaction actually costs on the network
+    let network_fee = match (
+        &state.solana_client,
+        state.withdrawal_fees.pass_through_network_fee,
+    ) {
+        (Some(client), true) => client.estimate_transfer_fee().await.unwrap_or_else(|e| {
+            warn!("Failed to look up network fee, using default: {}", e);
+            DEFAULT_NETWORK_FEE_LAMPORTS
+        }),
+        _ => DEFAULT_NETWORK_FEE_LAMPORTS,
+    };
+    let mut fees = state
+        .withdrawal_fees
+        .fees(withdraw_request.amount, network_fee);
+    if fees.total() >= withdraw_request.amount {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!(
+                    "Withdrawal amount must exceed fees of {} lamports",
+                    fees.total()
+                ),
+            }),
+        ));
+    }
+
+    // Pay out whole units only; what's left over is charged as dust
+    let sol = state.tokens.spec(SettlementToken::Sol);
+    fees.dust = match sol.normalize(withdraw_request.amount - fees.total()) {
+        Ok((_, residue)) => residue,
+        Err(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!(
+                        "Withdrawal amount after fees must be at least {} lamports",
+                        sol.dust_threshold
+                    ),
+                }),
+            ))
+        }
+    };
+
+    // Velocity limits are checked (and the amount reserved) before any funds move
+    let reservation = state
+        .withdrawal_limits
+        .reserve(
+            &withdraw_request.player_address,
+            withdraw_request.amount,
+            withdraw_request.amount - fees.total(),
+            Utc::now(),
+        )
+        .map_err(|exceeded| {
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ErrorResponse {
+                    error: exceeded.to_string(),
+                }),
+            )
+        })?;
+
+    let withdrawn = state
+        .db
+        .withdraw(
+            &withdraw_request.player_address,
+            withdraw_request.amount as i64,
+            fees,
+        )
+        .await;
+    if withdrawn.is_err() {
+        state.withdrawal_limits.release(reservation);
+    }
+    let (balance, withdrawal) = withdrawn.map_err(|e| match e {
+        DatabaseError::PlayerNotFound(_) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Player not found".to_string(),
+            }),
+        ),
+        DatabaseError::InsufficientBalance {
+            required,
+            available,
+        } => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!(
+                    "Insufficient balance. Required: {}, Available: {}",
+                    required, available
+                ),
+            }),
+        ),
+        DatabaseError::DemoBalance(_) => demo_error(e),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Failed to withdraw: {}", e),
+            }),
+        ),
+    })?;
+
+    Ok(Json(WithdrawResponse {
+        balance: BalanceResponse::from(&balance),
+        withdrawal,
+    }))
+}
+
+/// Withdrawal ledger for a player, most recent first
+pub async fn get_player_withdrawals(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Result<Json<Vec<WithdrawalEntry>>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .db
+        .get_player_withdrawals(&address)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    pub cursor: Option<String>, // Id of the last event of the previous page
+    pub limit: Option<usize>,
+}
+
+/// A player's deposits, withdrawals, bets, bonuses, adjustments and settlement
+/// confirmations as one timeline, newest first
+pub async fn get_player_activity(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<Json<ActivityPage>, (StatusCode, Json<ErrorResponse>)> {
+    activity::player_activity(
+        &state.db,
+        &address,
+        query.cursor.as_deref(),
+        query.limit.unwrap_or(activity::DEFAULT_PAGE_SIZE),
+    )
+    .await
+    .map(Json)
+    .map_err(|e| {
+        let status = match e {
+            ActivityError::UnknownCursor(_) => StatusCode::BAD_REQUEST,
+            ActivityError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })
+}
+
+#[derive(Deserialize)]
+pub struct LimitsQuery {
+    pub token: Option<SettlementToken>,
+    pub game: Option<GameKind>,
+}
+
+#[derive(Serialize)]
+pub struct LimitsResponse {
+    #[serde(flatten)]
+    pub limits: LimitsSnapshot,
+    pub escrow_min_bet: Option<u64>, // Bets this large must be placed through escrow
+}
+
+/// Current bet limits of a token and game, as place_bet enforces them, so clients can
+/// validate a bet before submitting it
+pub async fn get_limits(
+    State(state): State<AppState>,
+    Query(query): Query<LimitsQuery>,
+) -> Json<LimitsResponse> {
+    let token = query.token.unwrap_or_default();
+    Json(LimitsResponse {
+        limits: state.house_limits.snapshot(
+            token,
+            query.game.unwrap_or_default(),
+            &state.payouts,
+            state.tokens.spec(token).dust_threshold,
+            Utc::now(),
+        ),
+        escrow_min_bet: state.escrow.as_ref().map(|escrow| escrow.min_amount),
+    })
+}
+
+pub async fn get_player_bets(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> Result<Json<BetsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bets = state
+        .db
+        .get_player_bets(&address, Some(50))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+        })?;
+
+    let bet_responses: Vec<BetResponse> = bets.iter().map(BetResponse::from).collect();
+
+    Ok(Json(BetsResponse {
+        total_count: bet_responses.len(),
+        bets: bet_responses,
+    }))
+}
+
+pub async fn get_recent_bets(
+    State(state): State<AppState>,
+) -> Result<Json<BetsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let bets = state.db.get_recent_bets(Some(50)).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+    })?;
+
+    let bet_responses: Vec<BetResponse> = bets.iter().map(BetResponse::from).collect();
+
+    Ok(Json(BetsResponse {
+        total_count: bet_responses.len(),
+        bets: bet_responses,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct SettlementStatsResponse {
+    pub total_items_queued: u64,
+    pub total_batches_processed: u64,
+    pub items_in_current_batch: u64,
+    pub last_batch_processed_at: Option<DateTime<Utc>>,
+    pub queue_status: String,
+    pub avg_proving_ms: u64,
+    pub avg_submission_ms: u64,
+    pub estimated_backlog_ms: u64,
+    pub proof_cache: Option<ProofCacheStats>, // None until the prover is ready
+}
+
+pub async fn get_settlement_stats(
+    State(state): State<AppState>,
+) -> Result<Json<SettlementStatsResponse>, StatusCode> {
+    let stats = &state.settlement_stats;
+    let admission = state.admission.snapshot(stats);
+    let queue_status = match state.admission.evaluate(stats) {
+        AdmissionDecision::Admit => "active",
+        AdmissionDecision::Delay(_) => "throttled",
+        AdmissionDecision::Shed(_) => "shedding",
+    };
+
+    let response = SettlementStatsResponse {
+        total_items_queued: stats.total_items_queued.load(Ordering::Relaxed),
+        total_batches_processed: stats.total_batches_processed.load(Ordering::Relaxed),
+        items_in_current_batch: admission.queue_depth,
+        last_batch_processed_at: *stats.last_batch_processed_at.lock(),
+        queue_status: queue_status.to_string(),
+        avg_proving_ms: admission.avg_proving_ms,
+        avg_submission_ms: admission.avg_submission_ms,
+        estimated_backlog_ms: admission.estimated_backlog_ms,
+        proof_cache: state
+            .settlement_prover
+            .prover()
+            .map(|prover| prover.proof_cache_stats()),
+    };
+
+    Ok(Json(response))
+}
+
+/// Aggregated on-chain fees and compute units per batch and per bet
+pub async fn get_settlement_costs(
+    State(state): State<AppState>,
+) -> Result<Json<SettlementCostSummary>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .settlement_persistence
+        .get_cost_summary()
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to compute settlement costs: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: "Failed to compute settlement costs".to_string(),
+                }),
+            )
+        })
+}
+
+/// Everything needed to independently check the casino's fairness and settlement claims
+#[derive(Serialize)]
+pub struct TransparencyResponse {
+    pub vrf_public_keys: Vec<String>,
+    pub sequencer_public_key: String,
+    pub verifying_key_hash: Option<String>, // None until the ZK prover is ready
+    pub vault_program_id: String,
+    pub verifier_program_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_digest: Option<String>, // SHA-256 of the genesis manifest, when deployed from one
+    pub latest_settlement: Option<LatestSettlement>,
+    pub settlement: ProofStats,
+    pub prover: ProverStatus,
+    pub strict_proofs: bool,
+    pub avg_proving_ms: u64,
+    pub vrf_anchors: Vec<VrfAnchor>, // On-chain commitments to the VRF transcript, newest first
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Public data backing the transparency page
+pub async fn get_transparency(
+    State(state): State<AppState>,
+) -> Result<Json<TransparencyResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to build transparency report: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Failed to build transparency report".to_string(),
+            }),
+        )
+    };
+
+    let verifying_key_hash = match state.settlement_prover.prover() {
+        Some(prover) => Some(prover.verifying_key_hash().await.map_err(internal_error)?),
+        None => None,
+    };
+    let (vault_program_id, verifier_program_id) = match &state.genesis {
+        Some(genesis) => genesis.program_ids(),
+        None => program_ids_from_env(),
+    };
+
+    Ok(Json(TransparencyResponse {
+        vrf_public_keys: vec![state.keys.vrf.pubkey().to_string()],
+        sequencer_public_key: state.keys.sequencer.pubkey().to_string(),
+        verifying_key_hash,
+        vault_program_id,
+        verifier_program_id,
+        genesis_digest: state
+            .genesis
+            .as_ref()
+            .map(|genesis| genesis.digest().to_string()),
+        latest_settlement: state
+            .settlement_persistence
+            .get_latest_settlement()
+            .await
+            .map_err(internal_error)?,
+        settlement: state
+            .settlement_persistence
+            .get_proof_stats()
+            .await
+            .map_err(internal_error)?,
+        prover: state.settlement_prover.status(),
+        strict_proofs: state.strict_proofs,
+        avg_proving_ms: state
+            .settlement_stats
+            .avg_proving_ms
+            .load(Ordering::Relaxed),
+        vrf_anchors: state
+            .vrf_transcript
+            .as_ref()
+            .map(|transcript| transcript.anchors())
+            .unwrap_or_default(),
+        generated_at: Utc::now(),
+    }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReportSummary {
+    pub report_id: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub total_bets: u64,
+    pub chi_square_passed: bool,
+}
+
+impl From<&FairnessReport> for ReportSummary {
+    fn from(report: &FairnessReport) -> Self {
+        Self {
+            report_id: report.body.report_id.clone(),
+            period_start: report.body.period_start,
+            period_end: report.body.period_end,
+            generated_at: report.body.generated_at,
+            total_bets: report.body.outcome_distribution.samples,
+            chi_square_passed: report.body.outcome_distribution.passed,
+        }
+    }
+}
+
+pub async fn list_reports(State(state): State<AppState>) -> Json<Vec<ReportSummary>> {
+    let summaries = state
+        .report_service
+        .list_reports()
+        .iter()
+        .map(ReportSummary::from)
+        .collect();
+    Json(summaries)
+}
+
+pub async fn get_report(
+    State(state): State<AppState>,
+    Path(report_id): Path<String>,
+) -> Result<Json<FairnessReport>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .report_service
+        .get_report(&report_id)
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Report not found".to_string(),
+                }),
+            )
+        })
+}
+
+pub async fn get_geo_policy(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<GeoPolicySnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.geo_policy.snapshot()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GeoPolicyUpdateRequest {
+    pub mode: GeoPolicyMode,
+    pub allow_unknown: bool,
+}
+
+pub async fn update_geo_policy(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    CustomJson(payload): CustomJson<GeoPolicyUpdateRequest>,
+) -> Result<Json<GeoPolicySnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state
+        .geo_policy
+        .set_mode(payload.mode, payload.allow_unknown);
+    Ok(Json(state.geo_policy.snapshot()))
+}
+
+pub async fn list_games(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<GameStatus>>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.game_controls.snapshot()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GameUpdateRequest {
+    pub enabled: bool,
+    pub reason: Option<String>,
+}
+
+/// Enable or disable a game; disabling stops new bets, placed bets still settle
+pub async fn update_game(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(game): Path<String>,
+    CustomJson(payload): CustomJson<GameUpdateRequest>,
+) -> Result<Json<GameStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let game = GameKind::from_name(&game).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("Unknown game: {}", game),
+            }),
+        )
+    })?;
+    Ok(Json(state.game_controls.set(
+        game,
+        payload.enabled,
+        payload.reason,
+    )))
+}
+
+pub async fn add_geo_policy_country(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(country): Path<String>,
+) -> Result<Json<GeoPolicySnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Country must be a two-letter ISO code".to_string(),
+            }),
+        ));
+    }
+    state.geo_policy.add_country(&country);
+    Ok(Json(state.geo_policy.snapshot()))
+}
+
+pub async fn remove_geo_policy_country(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(country): Path<String>,
+) -> Result<Json<GeoPolicySnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    if !state.geo_policy.remove_country(&country) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Country not in policy list".to_string(),
+            }),
+        ));
+    }
+    Ok(Json(state.geo_policy.snapshot()))
+}
+
+pub async fn invalidate_compliance_decision(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(address): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state.compliance.invalidate(&address);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn treasury_manager(
+    state: &AppState,
+) -> Result<&Arc<TreasuryManager>, (StatusCode, Json<ErrorResponse>)> {
+    state.treasury.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Treasury rebalancing is not enabled".to_string(),
+            }),
+        )
+    })
+}
+
+pub async fn list_treasury_movements(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<TreasuryMovement>>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(treasury_manager(&state)?.movements()))
+}
+
+/// Sequencer fee-payer balance, alert state and recent refills
+pub async fn get_fee_payer_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<FeePayerStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let monitor = state.fee_payer.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Fee payer is not monitored by this process".to_string(),
+            }),
+        )
+    })?;
+    Ok(Json(monitor.status()))
+}
+
+/// Replay a stored batch's settlement without sending it: rebuild its settlement data
+/// and witness, re-verify its proof and simulate the verifier transaction
+pub async fn dry_run_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(batch_id): Path<u64>,
+) -> Result<Json<DryRunReport>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let batch = state
+        .settlement_persistence
+        .get_batch(batch_id)
+        .await
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: format!("Batch {} not found", batch_id),
+                }),
+            )
+        })?;
+    let report = dry_run::dry_run(
+        &batch,
+        &state.settlement_persistence,
+        &state.settlement_prover,
+        state.solana_client.as_deref(),
+        state.keys.vrf.pubkey(),
+    )
+    .await;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+pub struct KeyQuery {
+    pub kind: Option<KeyKind>,
+    pub key: Option<String>,
+}
+
+/// Every signing key the sequencer has used, with validity windows and revocations, so
+/// old receipts and proofs can be checked against the key valid when they were made
+pub async fn list_signing_keys(
+    State(state): State<AppState>,
+    Query(query): Query<KeyQuery>,
+) -> Json<Vec<KeyRecord>> {
+    let mut records = state.settlement_persistence.signing_keys().await;
+    records.retain(|record| {
+        query.kind.is_none_or(|kind| record.kind == kind)
+            && query.key.as_ref().is_none_or(|key| &record.key == key)
+    });
+    Json(records)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RevokeKeyRequest {
+    pub kind: KeyKind,
+    pub key: String,
+    pub reason: String,
+}
+
+/// Revoke a signing key; anything it signed or proved after now is no longer trusted
+pub async fn revoke_signing_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    CustomJson(payload): CustomJson<RevokeKeyRequest>,
+) -> Result<Json<Vec<KeyRecord>>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    if payload.reason.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "A revocation reason is required".to_string(),
+            }),
+        ));
+    }
+    state
+        .settlement_persistence
+        .revoke_signing_key(payload.kind, &payload.key, &payload.reason)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                KeyRegistryError::NotFound { .. } => StatusCode::NOT_FOUND,
+                KeyRegistryError::Revoked { .. } => StatusCode::CONFLICT,
+                KeyRegistryError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })
+}
+
+fn upgrade_error(e: UpgradeError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        UpgradeError::NoSolana => StatusCode::SERVICE_UNAVAILABLE,
+        UpgradeError::Onchain(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::CONFLICT,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// Read the programs' on-chain state for the upgrade step taken from `phase`
+async fn upgrade_onchain_view(
+    state: &AppState,
+    phase: UpgradePhase,
+) -> Result<upgrade::OnchainView, UpgradeError> {
+    state.upgrade.require_phase(phase)?;
+    let solana_client = state.solana_client.as_ref().ok_or(UpgradeError::NoSolana)?;
+    Ok(upgrade::read_onchain(solana_client).await?)
+}
+
+/// Current step of a coordinated upgrade
+pub async fn get_upgrade_status(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.upgrade.status()))
+}
+
+/// Start an upgrade: stop intake and let pending batches settle
+pub async fn begin_upgrade(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    CustomJson(plan): CustomJson<UpgradePlan>,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state.upgrade.begin(plan).map(Json).map_err(upgrade_error)
+}
+
+/// Confirm settlement has drained and record the state roots
+pub async fn snapshot_upgrade(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state
+        .upgrade
+        .require_phase(UpgradePhase::Draining)
+        .map_err(upgrade_error)?;
+    let storage_error = |e: anyhow::Error| upgrade_error(UpgradeError::Onchain(e));
+    let pending_batches = state
+        .settlement_persistence
+        .get_pending_batches()
+        .await
+        .map_err(storage_error)?
+        .len();
+    let queued_bets = state
+        .settlement_stats
+        .items_in_current_batch
+        .load(Ordering::Relaxed);
+    let local_state_root = state
+        .settlement_persistence
+        .settled_root()
+        .await
+        .map_err(storage_error)?;
+    let onchain = match upgrade_onchain_view(&state, UpgradePhase::Draining).await {
+        Ok(onchain) => Some(onchain),
+        Err(UpgradeError::NoSolana) => None,
+        Err(e) => return Err(upgrade_error(e)),
+    };
+    state
+        .upgrade
+        .snapshot(
+            pending_batches,
+            queued_bets,
+            local_state_root,
+            onchain.as_ref(),
+        )
+        .map(Json)
+        .map_err(upgrade_error)
+}
+
+/// Confirm the programs are paused on-chain, so the deploy can start
+pub async fn confirm_upgrade_paused(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let onchain = upgrade_onchain_view(&state, UpgradePhase::Snapshotted)
+        .await
+        .map_err(upgrade_error)?;
+    state
+        .upgrade
+        .confirm_paused(&onchain)
+        .map(Json)
+        .map_err(upgrade_error)
+}
+
+/// Check the deployed programs and verifying key against the upgrade plan
+pub async fn verify_upgrade(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let onchain = upgrade_onchain_view(&state, UpgradePhase::Paused)
+        .await
+        .map_err(upgrade_error)?;
+    state
+        .upgrade
+        .verify(&onchain)
+        .map(Json)
+        .map_err(upgrade_error)
+}
+
+/// Reopen intake once the upgraded programs are unpaused
+pub async fn resume_upgrade(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let onchain = upgrade_onchain_view(&state, UpgradePhase::Verified)
+        .await
+        .map_err(upgrade_error)?;
+    state
+        .upgrade
+        .resume(&onchain)
+        .map(Json)
+        .map_err(upgrade_error)
+}
+
+/// Abandon an upgrade and reopen intake
+pub async fn abort_upgrade(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UpgradeStatus>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.upgrade.abort()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TreasuryApprovalRequest {
+    pub cosigner: String,  // Co-signer public key (base58)
+    pub signature: String, // Signature over the movement digest (base58)
+}
+
+/// Co-sign a pending treasury movement; the transfer runs once enough co-signers approve
+pub async fn approve_treasury_movement(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+    CustomJson(payload): CustomJson<TreasuryApprovalRequest>,
+) -> Result<Json<TreasuryMovement>, (StatusCode, Json<ErrorResponse>)> {
+    use std::str::FromStr;
+
+    state.admin_auth.authorize(&headers)?;
+    let treasury = treasury_manager(&state)?;
+
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+    let cosigner = solana_sdk::pubkey::Pubkey::from_str(&payload.cosigner)
+        .map_err(|e| bad_request(format!("Invalid co-signer: {}", e)))?;
+    let signature = solana_sdk::signature::Signature::from_str(&payload.signature)
+        .map_err(|e| bad_request(format!("Invalid signature: {}", e)))?;
+
+    treasury
+        .approve(id, cosigner, signature)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                TreasuryError::NotFound(_) => StatusCode::NOT_FOUND,
+                TreasuryError::NotPending(_) | TreasuryError::DuplicateApproval(_) => {
+                    StatusCode::CONFLICT
+                }
+                TreasuryError::UnknownCosigner(_) | TreasuryError::InvalidSignature => {
+                    StatusCode::FORBIDDEN
+                }
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })
+}
+
+fn adjustment_manager(
+    state: &AppState,
+) -> Result<&Arc<AdjustmentManager>, (StatusCode, Json<ErrorResponse>)> {
+    state.adjustments.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Balance adjustments are not enabled".to_string(),
+            }),
+        )
+    })
+}
+
+fn adjustment_error(e: AdjustmentError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        AdjustmentError::NotFound(_) => StatusCode::NOT_FOUND,
+        AdjustmentError::NotPending(_) | AdjustmentError::DuplicateApproval(_) => {
+            StatusCode::CONFLICT
+        }
+        AdjustmentError::UnknownApprover(_) | AdjustmentError::InvalidSignature => {
+            StatusCode::FORBIDDEN
+        }
+        AdjustmentError::ZeroAmount | AdjustmentError::NoteTooLong => StatusCode::BAD_REQUEST,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+pub async fn list_balance_adjustments(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<BalanceAdjustment>>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(adjustment_manager(&state)?.adjustments()))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ProposeAdjustmentRequest {
+    pub player_address: String,
+    pub amount: i64, // Positive credits the player, negative debits
+    pub reason_code: ReasonCode,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Propose a manual credit or debit; applied once two approvers sign off
+pub async fn propose_balance_adjustment(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    CustomJson(payload): CustomJson<ProposeAdjustmentRequest>,
+) -> Result<Json<BalanceAdjustment>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    adjustment_manager(&state)?
+        .propose(
+            payload.player_address,
+            payload.amount,
+            payload.reason_code,
+            payload.note,
+        )
+        .map(Json)
+        .map_err(adjustment_error)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AdjustmentApprovalRequest {
+    pub approver: String,  // Approver public key (base58)
+    pub signature: String, // Signature over the adjustment digest (base58)
+}
+
+pub async fn approve_balance_adjustment(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+    CustomJson(payload): CustomJson<AdjustmentApprovalRequest>,
+) -> Result<Json<BalanceAdjustment>, (StatusCode, Json<ErrorResponse>)> {
+    use std::str::FromStr;
+
+    state.admin_auth.authorize(&headers)?;
+    let adjustments = adjustment_manager(&state)?;
+
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+    let approver = solana_sdk::pubkey::Pubkey::from_str(&payload.approver)
+        .map_err(|e| bad_request(format!("Invalid approver: {}", e)))?;
+    let signature = solana_sdk::signature::Signature::from_str(&payload.signature)
+        .map_err(|e| bad_request(format!("Invalid signature: {}", e)))?;
+
+    adjustments
+        .approve(id, approver, signature)
+        .await
+        .map(Json)
+        .map_err(adjustment_error)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct BackupResponse {
+    pub path: String,
+}
+
+/// Take a backup now, outside the schedule
+pub async fn run_backup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BackupResponse>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let backups = state.backups.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Backups are not enabled".to_string(),
+            }),
+        )
+    })?;
+    let path = backups.run_once().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Backup failed: {}", e),
+            }),
+        )
+    })?;
+    Ok(Json(BackupResponse {
+        path: path.display().to_string(),
+    }))
+}
+
+/// Double-entry journal, most recent first
+pub async fn get_ledger(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<JournalEntry>>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state.db.get_journal().await.map(Json).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+    })
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FileDisputeRequest {
+    pub bet_id: String,
+    pub player_address: String,
+    pub reason: String,
+    #[serde(default)]
+    pub evidence: DisputeEvidence,
+}
+
+fn dispute_error(e: DisputeError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        DisputeError::NotFound(_) => StatusCode::NOT_FOUND,
+        DisputeError::AlreadyDisputed(_) | DisputeError::InvalidTransition(..) => {
+            StatusCode::CONFLICT
+        }
+        DisputeError::InvalidText => StatusCode::BAD_REQUEST,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// File a dispute against one of the player's bets
+pub async fn file_dispute(
+    State(state): State<AppState>,
+    CustomJson(payload): CustomJson<FileDisputeRequest>,
+) -> Result<Json<Dispute>, (StatusCode, Json<ErrorResponse>)> {
+    let bet = state
+        .db
+        .get_bet(&payload.bet_id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Database error: {}", e),
+                }),
+            )
+        })?
+        .filter(|bet| bet.player_address == payload.player_address)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Bet not found".to_string(),
+                }),
+            )
+        })?;
+
+    let evidence_check = check_evidence(&bet, &payload.evidence, &state.keys.vrf.pubkey());
+    let dispute = state
+        .disputes
+        .file(&bet, payload.reason, payload.evidence, evidence_check)
+        .map_err(dispute_error)?;
+
+    info!(
+        "Dispute {} filed for bet {} by {} (VRF proof valid: {:?})",
+        dispute.id, dispute.bet_id, dispute.player_address, dispute.evidence_check.vrf_proof_valid
+    );
+    Ok(Json(dispute))
+}
+
+pub async fn get_dispute(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Dispute>, (StatusCode, Json<ErrorResponse>)> {
+    state
+        .disputes
+        .get(id)
+        .map(Json)
+        .ok_or_else(|| dispute_error(DisputeError::NotFound(id)))
+}
+
+pub async fn get_house_fees(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<HouseFees>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state.db.get_house_fees().await.map(Json).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("Database error: {}", e),
+            }),
+        )
+    })
+}
+
+#[derive(Deserialize)]
+pub struct SessionQuery {
+    pub since: Option<u64>, // Last sequence number seen; retained later events are replayed
+}
+
+/// Session WebSocket pushing round commitments, open/close and VRF reveals
+pub async fn session_websocket(
+    State(state): State<AppState>,
+    Query(query): Query<SessionQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| stream_session(socket, state.rounds.clone(), query.since))
+}
+
+async fn stream_session(mut socket: WebSocket, feed: Arc<RoundFeed>, since: Option<u64>) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let (replay, mut receiver) = feed.subscribe(since);
+    for message in replay {
+        if send_session_message(&mut socket, &message).await.is_err() {
+            return;
+        }
+    }
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(message) => {
+                    if send_session_message(&mut socket, &message).await.is_err() {
+                        return;
+                    }
+                }
+                // A slow client skips ahead; the jump in sequence numbers tells it to
+                // reconnect with `since` to replay what it missed
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Session fell behind by {} round events", skipped);
+                }
+                Err(RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {} // Sessions are push-only
+            },
+        }
+    }
+}
+
+async fn send_session_message(
+    socket: &mut WebSocket,
+    message: &rounds::SessionMessage,
+) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).expect("session messages serialize");
+    socket.send(Message::Text(text)).await
+}
+
+/// Settlement items waiting to be batched and the batch being processed, for when
+/// settlements appear stuck
+pub async fn get_settlement_queue(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SettlementQueueSnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.settlement_stats.queue.snapshot(Utc::now())))
+}
+
+/// Rolling outcome distribution statistics per VRF key and recent alerts
+/// Withdrawal limit configuration, hot-wallet usage, active overrides and recent alerts
+pub async fn get_withdrawal_limits(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<WithdrawalLimitsSnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.withdrawal_limits.snapshot(Utc::now())))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WithdrawalLimitOverrideRequest {
+    pub player_address: String,
+    pub duration_secs: u64,
+}
+
+/// Lift a player's withdrawal limits for a while (admin override)
+pub async fn override_withdrawal_limits(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    CustomJson(payload): CustomJson<WithdrawalLimitOverrideRequest>,
+) -> Result<Json<WithdrawalLimitOverride>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let duration = i64::try_from(payload.duration_secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .filter(|duration| *duration > chrono::Duration::zero())
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "duration_secs must be a positive number of seconds".to_string(),
+                }),
+            )
+        })?;
+    Ok(Json(state.withdrawal_limits.grant_override(
+        &payload.player_address,
+        Utc::now() + duration,
+    )))
+}
+
+pub async fn get_outcome_monitor(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<OutcomeMonitorSnapshot>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.outcome_monitor.snapshot()))
+}
+
+pub async fn list_disputes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Dispute>>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    Ok(Json(state.disputes.list()))
+}
+
+pub async fn review_dispute(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+) -> Result<Json<Dispute>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    state
+        .disputes
+        .start_review(id)
+        .map(Json)
+        .map_err(dispute_error)
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ResolveDisputeRequest {
+    pub outcome: DisputeOutcome,
+    pub note: String,
+}
+
+pub async fn resolve_dispute(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+    CustomJson(payload): CustomJson<ResolveDisputeRequest>,
+) -> Result<Json<Dispute>, (StatusCode, Json<ErrorResponse>)> {
+    state.admin_auth.authorize(&headers)?;
+    let dispute = state
+        .disputes
+        .resolve(id, payload.outcome, payload.note)
+        .map_err(dispute_error)?;
+
+    info!("Dispute {} resolved: {:?}", id, payload.outcome);
+    Ok(Json(dispute))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FaucetRequest {
+    pub airdrop_lamports: Option<u64>,
+    pub deposit_lamports: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FaucetResponse {
+    pub player_address: String,
+    pub player_secret_key: String, // Dev mode only: base58 keypair for the demo player
+    pub user_vault: String,
+    pub airdrop_signature: String,
+    pub onboarding_signature: String,
+    pub balance: BalanceResponse,
+}
+
+// Dev-mode onboarding: airdrop SOL to a fresh player, create their user vault PDA
+// and make an initial on-chain deposit, mirrored in the sequencer balance
+pub async fn faucet_handler(
+    State(state): State<AppState>,
+    CustomJson(request): CustomJson<FaucetRequest>,
+) -> Result<Json<FaucetResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.dev_mode {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "Faucet is only available in dev mode".to_string(),
+            }),
+        ));
+    }
+
+    let Some(solana_client) = state.solana_client.clone() else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: "Solana integration is disabled".to_string(),
+            }),
+        ));
+    };
+
+    let airdrop_lamports = request.airdrop_lamports.unwrap_or(1_000_000_000); // 1 SOL
+    let deposit_lamports = request.deposit_lamports.unwrap_or(100_000_000); // 0.1 SOL
+    let chain_error = |e: anyhow::Error| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(ErrorResponse {
+                error: format!("Faucet failed: {}", e),
+            }),
+        )
+    };
+
+    let player = Keypair::new();
+    let player_pubkey = player.pubkey();
+    info!("Faucet onboarding new player {}", player_pubkey);
+
+    let airdrop_signature = solana_client
+        .request_airdrop(&player_pubkey, airdrop_lamports)
+        .await
+        .map_err(chain_error)?;
+    let onboarding_signature = solana_client
+        .initialize_user_vault_with_deposit(&player, deposit_lamports)
+        .await
+        .map_err(chain_error)?;
+
+    let balance = state
+        .db
+        .deposit(&player_pubkey.to_string(), deposit_lamports as i64)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Failed to deposit: {}", e),
+                }),
+            )
+        })?;
+
+    Ok(Json(FaucetResponse {
+        player_address: player_pubkey.to_string(),
+        player_secret_key: player.to_base58_string(),
+        user_vault: solana_client.user_vault_pda(&player_pubkey).to_string(),
+        airdrop_signature: airdrop_signature.to_string(),
+        onboarding_signature: onboarding_signature.to_string(),
+        balance: BalanceResponse::from(&balance),
+    }))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DemoFaucetRequest {
+    pub player_address: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DemoFaucetResponse {
+    #[serde(flatten)]
+    pub balance: BalanceResponse,
+    pub granted: u64, // Lamports added to reach the play balance cap; 0 when already there
+}
+
+/// Demo-mode faucet: tops a demo player's play balance back up to the cap, creating it
+/// for a new address. Real balances are refused.
+pub async fn demo_faucet_handler(
+    State(state): State<AppState>,
+    CustomJson(request): CustomJson<DemoFaucetRequest>,
+) -> Result<Json<DemoFaucetResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(demo) = &state.demo else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "Demo faucet is only available in demo mode".to_string(),
+            }),
+        ));
+    };
+    if request
+        .player_address
+        .parse::<solana_sdk::pubkey::Pubkey>()
+        .is_err()
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Invalid player address".to_string(),
+            }),
+        ));
+    }
+
+    let (balance, grant) = state
+        .db
+        .grant_demo_balance(&request.player_address, demo.play_balance as i64)
+        .await
+        .map_err(demo_error)?;
+    let granted = grant
+        .map(|entry| entry.postings[0].amount as u64)
+        .unwrap_or(0);
+    info!(
+        "Demo faucet granted {} lamports to {}",
+        granted, request.player_address
+    );
+
+    Ok(Json(DemoFaucetResponse {
+        balance: BalanceResponse::from(&balance),
+        granted,
+    }))
+}
+
+/// Connect to Solana when ENABLE_SOLANA=true; None runs without on-chain settlement
+async fn init_solana_client(
+    sequencer_keypair: &Keypair,
+    genesis: Option<&Genesis>,
+) -> Result<Option<Arc<SolanaClient>>> {
+    Ok(
+        if std::env::var("ENABLE_SOLANA").unwrap_or_default() == "true" {
+            info!("Initializing Solana client...");
+
+            let solana_keypair = Keypair::from_bytes(&sequencer_keypair.to_bytes())
+                .map_err(|e| anyhow::anyhow!("Failed to copy sequencer keypair: {}", e))?;
+
+            // Configure for local validator by default, switch to testnet with env var
+            let solana_config = SolanaConfig::from_env();
+
+            // Program IDs (these should match the deployed programs)
+            let (vault_program_id, verifier_program_id) = match genesis {
+                Some(genesis) => genesis.program_ids(),
+                None => program_ids_from_env(),
+            };
+            let next_keypair = next_sequencer_keypair_from_env()?;
+            let co_signer = CoSignerConfig::from_env()?
+                .map(CoSignerClient::new)
+                .transpose()?;
+
+            match SolanaClient::new(
+                solana_config,
+                solana_keypair,
+                &vault_program_id,
+                &verifier_program_id,
+            )
+            .map(|client| match next_keypair {
+                Some(keypair) => client.with_next_sequencer_keypair(keypair),
+                None => client,
+            })
+            .map(|client| match co_signer {
+                Some(co_signer) => {
+                    info!("Settlements are countersigned by {}", co_signer.pubkey());
+                    client.with_co_signer(co_signer)
+                }
+                None => client,
+            }) {
+                Ok(client) => {
+                    info!("Solana client initialized successfully");
+                    let router = client.rpc_router();
+                    if router.endpoint_count() > 1 {
+                        router.probe_all().await;
+                        router.start();
+                    }
+                    // Test connection
+                    if let Err(e) = client.health_check().await {
+                        warn!(
+                        "Solana health check failed: {}. Continuing without Solana integration.",
+                        e
+                    );
+                        None
+                    } else {
+                        Some(Arc::new(client))
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to initialize Solana client: {}. Continuing without Solana integration.", e);
+                    None
+                }
+            }
+        } else {
+            info!("Solana integration disabled. Set ENABLE_SOLANA=true to enable.");
+            None
+        },
+    )
+}
+
+#[tokio::main(flavor = "multi_thread", worker_threads = 8)]
+async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
     let args = Args::parse();
 
-    // Initialize database
-    let db = Database::new(&args.database_url)
+    match args.command {
+        Some(Command::Keys { address }) => return keys::run_keys_command(address).await,
+        Some(Command::Restore { path, verify_only }) => {
+            let cipher = PersistenceCipher::from_env()?;
+            if verify_only {
+                let contents = tokio::fs::read_to_string(&path).await?;
+                let (_, _, summary) = backup::verify_backup(&contents, cipher.as_ref())?;
+                println!("Backup OK: {}", serde_json::to_string_pretty(&summary)?);
+            } else {
+                let summary = backup::restore(&path, &args.database_url, cipher.as_ref()).await?;
+                println!("Restored: {}", serde_json::to_string_pretty(&summary)?);
+            }
+            return Ok(());
+        }
+        Some(Command::Export { from, to, out }) => {
+            let cipher = PersistenceCipher::from_env()?.map(Arc::new);
+            let persistence =
+                SettlementPersistence::with_cipher(&args.database_url, cipher).await?;
+            let manifest = export::export_batches(&persistence, from, to, &out).await?;
+            println!(
+                "Exported {} batches ({} verifying keys) to {}",
+                manifest.batches.len(),
+                manifest.verifying_keys.len(),
+                out.display()
+            );
+            return Ok(());
+        }
+        Some(Command::Submitter) => {
+            let db = Database::new(&args.database_url)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to connect to database: {}", e))?;
+            db.create_tables()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to create database tables: {}", e))?;
+            let cipher = PersistenceCipher::from_env()?.map(Arc::new);
+            let persistence = SettlementPersistence::with_cipher(&args.database_url, cipher)
+                .await?
+                .shared();
+            let keys = SequencerKeys::from_env()?;
+            info!("Sequencer public key: {}", keys.sequencer.pubkey());
+            keys.record_in_registry(&persistence).await?;
+            let genesis = Genesis::from_env()?;
+            let solana_client = init_solana_client(&keys.sequencer, genesis.as_ref()).await?;
+            if let Some(client) = &solana_client {
+                let fee_payer = Arc::new(FeePayerMonitor::new(
+                    FeePayerConfig::from_env()?,
+                    client.clone(),
+                    client.sequencer_pubkey(),
+                ));
+                fee_payer.preflight(&persistence).await?;
+                fee_payer.start();
+            }
+
+            let submitter = Arc::new(SettlementSubmitter::new(
+                solana_client,
+                Arc::new(persistence),
+                Arc::new(db),
+                keys.vrf.pubkey(),
+            ));
+            submitter
+                .start(SubmitterConfig::from_env()?.poll_interval)
+                .await?;
+            return Ok(());
+        }
+        Some(Command::CoSigner) => {
+            let verifier_program_id = match Genesis::from_env()? {
+                Some(genesis) => genesis.program_ids().1,
+                None => program_ids_from_env().1,
+            };
+            let service = CoSignerService::from_env(&verifier_program_id)?;
+            return co_signer::serve(Arc::new(service), args.port).await;
+        }
+        Some(Command::TuneBatches { target_p95_ms }) => {
+            let cipher = PersistenceCipher::from_env()?.map(Arc::new);
+            let persistence =
+                SettlementPersistence::with_cipher(&args.database_url, cipher).await?;
+            // Same capacity as the settlement loop would have
+            let proofs_enabled = std::env::var("ENABLE_ZK_PROOFS").unwrap_or_default() == "true";
+            let policy = if proofs_enabled {
+                BatchPolicy::for_prover(&SettlementProverConfig {
+                    net_positions: std::env::var("SETTLEMENT_NET_POSITIONS").unwrap_or_default()
+                        != "false",
+                    ..SettlementProverConfig::default()
+                })
+            } else {
+                BatchPolicy::unconstrained()
+            };
+            let report = batch_tuning::tune(
+                &persistence,
+                policy,
+                BatchSchedule::from_env()?,
+                TuningCostModel::from_env(proofs_enabled)?,
+                target_p95_ms,
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // Initialize database
+    let db = Database::new(&args.database_url)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to connect to database: {}", e))?;
+
+    db.create_tables()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to create database tables: {}", e))?;
+    backup::import_pending_restore(&db, &args.database_url).await?;
+
+    let vrf_journal_path = VrfJournal::path_from_env(&args.database_url);
+    let vrf_journal = match &vrf_journal_path {
+        Some(path) => {
+            Some(Arc::new(VrfJournal::open(path.clone()).map_err(|e| {
+                anyhow::anyhow!("Failed to open VRF journal: {}", e)
+            })?))
+        }
+        None => {
+            info!("VRF journal disabled for in-memory database. Set VRF_JOURNAL_PATH to enable.");
+            None
+        }
+    };
+    let bet_recovery = Arc::new(
+        RecoveryLog::open(vrf_journal_path.as_deref().map(RecoveryLog::path_for))
+            .map_err(|e| anyhow::anyhow!("Failed to open bet recovery log: {}", e))?,
+    );
+
+    // Initialize settlement persistence for crash-safe queue (Phase 3e requirement)
+    info!("Initializing settlement persistence for crash-safe queue...");
+    let persistence_cipher = PersistenceCipher::from_env()?.map(Arc::new);
+    let submitter_config = SubmitterConfig::from_env()?;
+    let settlement_persistence =
+        SettlementPersistence::with_cipher(&args.database_url, persistence_cipher)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to initialize settlement persistence: {}", e))?;
+    let settlement_persistence = Arc::new(match submitter_config.mode {
+        SubmitterMode::Embedded => settlement_persistence,
+        SubmitterMode::External => settlement_persistence.shared(),
+    });
+
+    // Phase 3e: Crash recovery - process any pending batches from previous runs
+    info!("Checking for pending settlement batches to recover...");
+    let pending_batches = settlement_persistence
+        .get_pending_batches()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to get pending batches: {}", e))?;
+
+    if !pending_batches.is_empty() {
+        info!(
+            "Found {} pending batches from previous run, starting recovery...",
+            pending_batches.len()
+        );
+
+        for batch in pending_batches {
+            info!(
+                "Recovering batch {} with status {:?}",
+                batch.batch_id, batch.status
+            );
+
+            // TODO: Implement actual recovery logic based on batch status
+            // For now, just log the recovery - full implementation would:
+            // - Re-process pending batches
+            // - Re-submit proved batches
+            // - Verify submitted batches on-chain
+            match batch.status {
+                SettlementBatchStatus::Pending => {
+                    info!("Batch {} needs re-processing", batch.batch_id);
+                }
+                SettlementBatchStatus::Proving => {
+                    info!("Batch {} was interrupted during proving", batch.batch_id);
+                }
+                SettlementBatchStatus::Proved => {
+                    info!("Batch {} has proof ready for submission", batch.batch_id);
+                }
+                SettlementBatchStatus::Submitted => {
+                    info!(
+                        "Batch {} was submitted, checking on-chain status",
+                        batch.batch_id
+                    );
+                }
+                _ => {}
+            }
+        }
+    } else {
+        info!("No pending batches found, starting fresh");
+    }
+
+    // Initialize settlement queue for ZK proof batching (VF Node pattern)
+    let (settlement_sender, settlement_receiver) = mpsc::unbounded_channel();
+    let settlement_stats = SettlementStats::new();
+
+    // Initialize oracle manager for proof fetching (as requested by user)
+    let oracle_config = OracleConfig::default();
+    let oracle_manager = OracleManager::new(oracle_config.clone());
+    let oracle_client = OracleClient::new(oracle_config);
+
+    // Start oracle proof fetching service
+    oracle_manager
+        .start_proof_service()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to start oracle service: {}", e))?;
+
+    // Load sequencer and VRF keypairs (ephemeral when no keypair files are configured)
+    let sequencer_keys = SequencerKeys::from_env()?;
+    let sequencer_keypair = sequencer_keys.sequencer.clone();
+    info!("Sequencer public key: {}", sequencer_keypair.pubkey());
+    info!("VRF public key: {}", sequencer_keys.vrf.pubkey());
+    sequencer_keys
+        .record_in_registry(&settlement_persistence)
+        .await?;
+
+    // Deployment manifest: pins program IDs, keys and game schedules when GENESIS_PATH is set
+    let genesis = Genesis::from_env()?;
+    if let Some(genesis) = &genesis {
+        info!(
+            "Loaded {} genesis manifest {}",
+            genesis.network,
+            genesis.digest()
+        );
+    }
+
+    // Initialize Solana client (Phase 2: localnet first, then testnet)
+    let solana_client = init_solana_client(&sequencer_keypair, genesis.as_ref()).await?;
+
+    // Pre-flight checks: refuse to start, or serve read-only, when the wiring is wrong
+    let payouts = match &genesis {
+        Some(genesis) => genesis.coinflip_payouts(),
+        None => PayoutSchedule::from_env()?,
+    };
+    let tokens = TokenConfig::from_env()?;
+    let house_limits = Arc::new(HouseLimits::new(HouseLimitConfig::from_env(&tokens)?));
+    let self_check = Arc::new(
+        self_check::run(
+            &SelfCheckConfig::from_env()?.with_genesis(genesis.clone())?,
+            &db,
+            &sequencer_keys,
+            &payouts,
+            solana_client.as_deref(),
+        )
+        .await?,
+    );
+
+    // Initialize Settlement Prover for Phase 3e (ZK proof generation)
+    // Strict proofs: no placeholder-proof fallback, so the prover must be enabled
+    let strict_proofs =
+        args.strict_proofs || std::env::var("STRICT_PROOFS").unwrap_or_default() == "true";
+    if strict_proofs {
+        if std::env::var("ENABLE_ZK_PROOFS").unwrap_or_default() != "true" {
+            return Err(anyhow::anyhow!(
+                "Strict proof mode requires ENABLE_ZK_PROOFS=true"
+            ));
+        }
+        info!(
+            "Strict proof mode enabled: batches without a verified ZK proof will not be submitted"
+        );
+    }
+
+    // Groth16 setup runs in the background; settlement submission waits for it, bet intake does not.
+    // Batches must fit the settlement circuit when proofs are enabled.
+    let (settlement_prover, batch_policy) = if std::env::var("ENABLE_ZK_PROOFS").unwrap_or_default()
+        == "true"
+    {
+        info!("Warming up Settlement Prover for ZK proof generation...");
+
+        let prover_config = SettlementProverConfig {
+            speculative_proving: std::env::var("SPECULATIVE_PROVING").unwrap_or_default() == "true",
+            net_positions: std::env::var("SETTLEMENT_NET_POSITIONS").unwrap_or_default() != "false",
+            proof_cache: ProofCacheConfig::from_env(),
+            pool: ProverPoolConfig::from_env()?,
+            ..SettlementProverConfig::default()
+        };
+        let batch_policy = BatchPolicy::for_prover(&prover_config);
+        let readiness = Arc::new(ProverReadiness::warming_up());
+        readiness.start_warm_up(prover_config);
+        (readiness, batch_policy)
+    } else {
+        info!("ZK proof generation disabled. Set ENABLE_ZK_PROOFS=true to enable real proof generation.");
+        (
+            Arc::new(ProverReadiness::disabled()),
+            BatchPolicy::unconstrained(),
+        )
+    };
+    let batch_schedule = BatchSchedule::from_env()?;
+    let batch_policy = batch_policy.capped(batch_schedule.max_bets);
+    info!(
+        "Settlement batch policy: {:?}, closing every {:?}",
+        batch_policy, batch_schedule.interval
+    );
+
+    let db = Arc::new(db);
+
+    // Watch the wallet that pays for settlements; whichever process submits owns it, so
+    // an external submitter's refills aren't duplicated here
+    let fee_payer = match (&solana_client, submitter_config.mode) {
+        (Some(client), SubmitterMode::Embedded) => Some(Arc::new(FeePayerMonitor::new(
+            FeePayerConfig::from_env()?,
+            client.clone(),
+            client.sequencer_pubkey(),
+        ))),
+        _ => None,
+    };
+
+    // Settlement submission runs in-process unless a separate submitter owns it
+    let submitter = match submitter_config.mode {
+        SubmitterMode::Embedded => {
+            if let Some(fee_payer) = &fee_payer {
+                fee_payer.preflight(&settlement_persistence).await?;
+            }
+            let submitter = Arc::new(SettlementSubmitter::new(
+                solana_client.clone(),
+                settlement_persistence.clone(),
+                db.clone(),
+                sequencer_keys.vrf.pubkey(),
+            ));
+            // Exactly-once submission: settle the fate of transactions sent before a
+            // crash, then submit batches that were proved but never sent
+            if let Err(e) = submitter.run_once().await {
+                warn!("Failed to resume settlement submissions: {}", e);
+            }
+            Some(submitter)
+        }
+        SubmitterMode::External => {
+            info!("Proved batches are submitted by the external settlement submitter");
+            submitter::follow_external_submitter(
+                settlement_persistence.clone(),
+                db.clone(),
+                submitter_config.poll_interval,
+            );
+            None
+        }
+    };
+
+    if let Some(fee_payer) = &fee_payer {
+        fee_payer.clone().start();
+    }
+
+    // Start periodic fairness report generation
+    let report_service = Arc::new(ReportService::new(
+        ReportConfig::from_env(),
+        db.clone(),
+        settlement_persistence.clone(),
+        sequencer_keypair.clone(),
+    ));
+    report_service.clone().start();
+
+    // House bankroll rebalancing needs the chain to read balances and move funds
+    let treasury = match (TreasuryConfig::from_env()?, &solana_client) {
+        (Some(config), Some(client)) => {
+            let manager = Arc::new(TreasuryManager::new(config, client.clone()));
+            manager.clone().start();
+            Some(manager)
+        }
+        (Some(_), None) => {
+            warn!("Treasury rebalancing configured but Solana is unavailable; disabled");
+            None
+        }
+        (None, _) => None,
+    };
+
+    let demo = DemoConfig::from_env()?;
+    if let Some(demo) = &demo {
+        warn!(
+            "Demo mode: new addresses get {} lamports of non-withdrawable play balance",
+            demo.play_balance
+        );
+    }
+
+    // Escrowed bets are picked up from and settled through the vault program
+    let bet_ids = Arc::new(BetIdGenerator::from_env());
+    let escrow = match (EscrowConfig::from_env(), &solana_client) {
+        (Some(config), Some(client)) => {
+            Arc::new(EscrowWatcher::new(
+                config.clone(),
+                client.clone(),
+                db.clone(),
+                bet_ids.clone(),
+            ))
+            .start();
+            Some(config)
+        }
+        (Some(_), None) => {
+            warn!("Escrow mode configured but Solana is unavailable; disabled");
+            None
+        }
+        (None, _) => None,
+    };
+
+    // Deposits relayed into user vaults are credited to off-chain balances
+    match (DepositWatcherConfig::from_env(), &solana_client) {
+        (Some(config), Some(client)) => {
+            Arc::new(DepositWatcher::new(config, client.clone(), db.clone())).start();
+        }
+        (Some(_), None) => warn!("Deposit watcher configured but Solana is unavailable; disabled"),
+        (None, _) => {}
+    }
+
+    // Periodically commit the VRF transcript on-chain
+    let vrf_transcript = solana_client.as_ref().map(|client| {
+        let transcript = Arc::new(VrfTranscript::new(
+            VrfAnchorConfig::from_env(),
+            client.clone(),
+        ));
+        transcript.clone().start();
+        transcript
+    });
+
+    // Manual balance adjustments need two distinct approvers
+    let adjustments = AdjustmentConfig::from_env()?
+        .map(|config| Arc::new(AdjustmentManager::new(config, db.clone())));
+
+    let backups = BackupConfig::from_env().map(|config| {
+        let service = Arc::new(BackupService::new(
+            config,
+            db.clone(),
+            settlement_persistence.clone(),
+        ));
+        service.clone().start();
+        service
+    });
+
+    // Round-based games publish their lifecycle to session WebSockets
+    let round_config = RoundConfig::from_env()?;
+    let rounds = Arc::new(RoundFeed::new(round_config.history));
+    if !round_config.games.is_empty() {
+        Arc::new(RoundScheduler::new(
+            round_config,
+            rounds.clone(),
+            sequencer_keys.vrf.clone(),
+        ))
+        .start();
+    }
+
+    let state = AppState {
+        db,
+        settlement_sender,
+        oracle_client,
+        settlement_stats: settlement_stats.clone(),
+        solana_client,
+        settlement_prover,
+        settlement_persistence: settlement_persistence.clone(),
+        report_service,
+        admin_auth: AdminAuth::from_env(),
+        geo_policy: Arc::new(GeoPolicy::from_env()?),
+        game_controls: Arc::new(GameControls::from_env()?),
+        compliance: Arc::new(ComplianceHook::from_env()),
+        dev_mode: std::env::var("DEV_MODE").unwrap_or_default() == "true",
+        demo,
+        signed_responses: std::env::var("SIGNED_RESPONSES").unwrap_or_default() == "true",
+        bet_ids,
+        strict_proofs,
+        bet_nonces: Arc::new(BetNonceCache::from_env()),
+        admission: Arc::new(AdmissionController::new(AdmissionConfig {
+            batch_size: batch_policy.max_bets,
+            ..AdmissionConfig::from_env()
+        })),
+        vrf_signer: Arc::new(VrfSigner::spawn(sequencer_keys.vrf.clone())?),
+        keys: sequencer_keys,
+        treasury,
+        fee_payer,
+        escrow,
+        vrf_transcript,
+        vrf_journal,
+        bet_recovery,
+        disputes: Arc::new(DisputeStore::new()),
+        withdrawal_fees: WithdrawalFeeConfig::from_env()?,
+        tokens,
+        payouts,
+        house_limits,
+        adjustments,
+        backups,
+        self_check,
+        outcome_monitor: Arc::new(OutcomeMonitor::new(OutcomeMonitorConfig::from_env()?)),
+        rounds,
+        withdrawal_limits: Arc::new(WithdrawalLimiter::new(WithdrawalLimitConfig::from_env()?)),
+        upgrade: Arc::new(UpgradeCoordinator::default()),
+        genesis: genesis.map(Arc::new),
+    };
+
+    // Bets interrupted by the last shutdown are settled one way or the other before any
+    // new bet is taken
+    if let Some(path) = &vrf_journal_path {
+        recover_unrecorded_bets(&state, &vrf_journal::entries(path)?).await?;
+    }
+
+    // Settlement processor for ZK proof batching (VF Node background pattern)
+    let stats_clone = settlement_stats.clone();
+    let settlement_prover_clone = state.settlement_prover.clone();
+    let settlement_persistence_clone = state.settlement_persistence.clone();
+    let db_clone = state.db.clone();
+    let strict_proofs = state.strict_proofs;
+    let _settlement_processor_handle = tokio::spawn(async move {
+        let mut settlement_receiver = settlement_receiver;
+        let mut composer = BatchComposer::new(batch_policy);
+        let mut interval = interval(batch_schedule.interval); // Batching window
+
+        loop {
+            tokio::select! {
+                // Receive settlement items
+                item = settlement_receiver.recv() => {
+                    match item {
+                        Some(settlement_item) => {
+                            // Phase 3e: Check for deduplication before adding to batch
+                            match settlement_persistence_clone.is_bet_processed(&settlement_item.bet_id).await {
+                                Ok(already_processed) => {
+                                    if already_processed {
+                                        warn!("Bet {} already processed, skipping to prevent double settlement", settlement_item.bet_id);
+                                        stats_clone.queue.discarded(&settlement_item.bet_id);
+                                        continue;
+                                    }
+
+                                    // Add to a compatible batch; settle any batch the policy closes
+                                    let key = BatchKey::for_item(&settlement_item);
+                                    for batch in composer.push(settlement_item) {
+                                        process_settlement_batch(&batch, &stats_clone, submitter.as_deref(), settlement_prover_clone.clone(), settlement_persistence_clone.clone(), db_clone.clone(), strict_proofs).await;
+                                    }
+                                    speculate_open_batch(&composer, &key, &settlement_prover_clone, settlement_persistence_clone.clone());
+                                }
+                                Err(e) => {
+                                    error!("Failed to check if bet {} is already processed: {}. Proceeding anyway.", settlement_item.bet_id, e);
+                                    // If deduplication check fails, proceed anyway to avoid blocking settlement
+                                    let key = BatchKey::for_item(&settlement_item);
+                                    for batch in composer.push(settlement_item) {
+                                        process_settlement_batch(&batch, &stats_clone, submitter.as_deref(), settlement_prover_clone.clone(), settlement_persistence_clone.clone(), db_clone.clone(), strict_proofs).await;
+                                    }
+                                    speculate_open_batch(&composer, &key, &settlement_prover_clone, settlement_persistence_clone.clone());
+                                }
+                            }
+                        }
+                        None => break, // Channel closed
+                    }
+                }
+
+                // Process batch on timer (ensure regular processing)
+                _ = interval.tick() => {
+                    for batch in composer.drain() {
+                        process_settlement_batch(&batch, &stats_clone, submitter.as_deref(), settlement_prover_clone.clone(), settlement_persistence_clone.clone(), db_clone.clone(), strict_proofs).await;
+                    }
+                }
+            }
+        }
+    });
+
+    let app = create_app(state);
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], args.port));
+    info!("Sequencer listening on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    // Connect info is needed for client IP resolution in the geo policy
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use tower::ServiceExt; // for `oneshot`
+
+    async fn setup_test_app() -> (Router, AppState) {
+        let db = Database::new("").await.unwrap();
+        db.create_tables().await.unwrap();
+
+        // Initialize test settlement persistence
+        let settlement_persistence = Arc::new(
+            SettlementPersistence::new("sqlite::memory:")
+                .await
+                .expect("Failed to initialize test settlement persistence"),
+        );
+
+        let (settlement_sender, _) = mpsc::unbounded_channel();
+        let oracle_config = OracleConfig::default();
+        let oracle_client = OracleClient::new(oracle_config);
+        let settlement_stats = SettlementStats::new();
+
+        let db = Arc::new(db);
+        let report_service = Arc::new(ReportService::new(
+            ReportConfig::default(),
+            db.clone(),
+            settlement_persistence.clone(),
+            Arc::new(Keypair::new()),
+        ));
+        let keys = SequencerKeys {
+            sequencer: Arc::new(Keypair::new()),
+            vrf: Arc::new(Keypair::new()),
+        };
+
+        let state = AppState {
+            db,
+            settlement_sender,
+            oracle_client,
+            settlement_stats,
+            solana_client: None, // No Solana client for tests
+            settlement_prover: Arc::new(ProverReadiness::disabled()), // No ZK prover for tests
+            settlement_persistence,
+            report_service,
+            admin_auth: AdminAuth::new(Some("test-admin-token".to_string())),
+            geo_policy: Arc::new(GeoPolicy::disabled()),
+            game_controls: Arc::new(GameControls::all_enabled()),
+            compliance: Arc::new(ComplianceHook::disabled()),
+            dev_mode: false,
+            demo: None,
+            bet_ids: Arc::new(BetIdGenerator::new(0)),
+            strict_proofs: false,
+            bet_nonces: Arc::new(BetNonceCache::from_env()),
+            admission: Arc::new(AdmissionController::new(AdmissionConfig::default())),
+            keys: keys.clone(),
+            vrf_signer: Arc::new(VrfSigner::spawn(keys.vrf.clone()).unwrap()),
+            treasury: None,
+            fee_payer: None,
+            escrow: None,
+            vrf_transcript: None,
+            vrf_journal: None,
+            bet_recovery: Arc::new(RecoveryLog::open(None).unwrap()),
+            disputes: Arc::new(DisputeStore::new()),
+            withdrawal_fees: WithdrawalFeeConfig::default(),
+            tokens: TokenConfig::default(),
+            payouts: PayoutSchedule::default(),
+            house_limits: Arc::new(HouseLimits::new(HouseLimitConfig::default())),
+            adjustments: None,
+            backups: None,
+            self_check: Arc::new(SelfCheckReport::default()),
+            outcome_monitor: Arc::new(OutcomeMonitor::new(OutcomeMonitorConfig::default())),
+            rounds: Arc::new(RoundFeed::new(100)),
+            withdrawal_limits: Arc::new(WithdrawalLimiter::new(WithdrawalLimitConfig::default())),
+            upgrade: Arc::new(UpgradeCoordinator::default()),
+            genesis: None,
+            signed_responses: false,
+        };
+
+        let app = create_app(state.clone());
+        (app, state)
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let (app, _state) = setup_test_app().await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"OK");
+    }
+
+    #[tokio::test]
+    async fn test_readiness_check() {
+        let (app, state) = setup_test_app().await;
+
+        let request = || {
+            Request::builder()
+                .uri("/health/ready")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Prover disabled: ready (placeholder proofs)
+        let response = app.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Prover warming up: not ready
+        let mut warming_state = state.clone();
+        warming_state.settlement_prover = Arc::new(ProverReadiness::warming_up());
+        let response = create_app(warming_state).oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let readiness: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(readiness["ready"], false);
+        assert_eq!(readiness["prover"]["state"], "warming_up");
+    }
+
+    #[tokio::test]
+    async fn test_read_only_after_failed_self_check() {
+        let (_app, state) = setup_test_app().await;
+        let app = create_app(AppState {
+            self_check: Arc::new(SelfCheckReport {
+                checks: vec![],
+                read_only: true,
+            }),
+            ..state.clone()
+        });
+
+        // Writes are rejected
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/deposit")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({"player_address": "player", "amount": 10000})
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Reads and the admin API still work
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/recent-bets")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/admin/backups")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health/self-check")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["read_only"], true);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_stops_intake() {
+        let (app, _state) = setup_test_app().await;
+        let admin_post = |uri: &str, body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let deposit = || {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/deposit")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({"player_address": "player", "amount": 10000})
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+        };
+
+        let response = admin_post(
+            "/v1/admin/upgrade/begin",
+            serde_json::json!({"verifying_key_hash": "new-key"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = deposit().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Steps run in order; with nothing pending the snapshot succeeds
+        let response = admin_post("/v1/admin/upgrade/verify", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = admin_post("/v1/admin/upgrade/snapshot", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status["phase"], "snapshotted");
+        assert!(status["snapshot"]["onchain_state_root"].is_null());
+
+        // Pausing has to be confirmed on-chain
+        let response = admin_post("/v1/admin/upgrade/pause", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = admin_post("/v1/admin/upgrade/abort", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = deposit().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_deposit_and_balance() {
+        let (app, _state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        // Test deposit
+        let deposit_request = DepositRequest {
+            player_address: player_address.to_string(),
+            amount: 10000,
+        };
+
+        let request_body = serde_json::to_string(&deposit_request).unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/deposit")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let deposit_response: DepositResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(deposit_response.balance.balance, 10000);
+        assert_eq!(deposit_response.balance.total_deposited, 10000);
+        // No Solana client, so no on-chain onboarding to offer
+        assert!(deposit_response.user_vault_initialization.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bet_outcome_journaled_before_response() {
+        let (_, state) = setup_test_app().await;
+        let path =
+            std::env::temp_dir().join(format!("zkcasino-vrf-{}.jsonl", uuid::Uuid::new_v4()));
+        let state = AppState {
+            vrf_journal: Some(Arc::new(VrfJournal::open(path.clone()).unwrap())),
+            ..state.clone()
+        };
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10000).await.unwrap();
+
+        let bet_request = serde_json::json!({
+            "player_address": player_address,
+            "amount": 5000,
+            "guess": true,
+        });
+        let response = create_app(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/bet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(bet_request.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let bet_response: BetResponse = serde_json::from_slice(&body).unwrap();
+
+        // On disk by the time the response is out, whatever happens to the bet record
+        let journal = std::fs::read_to_string(&path).unwrap();
+        let entry: vrf_journal::VrfJournalEntry = serde_json::from_str(journal.trim()).unwrap();
+        assert_eq!(entry.sequence, 0);
+        assert_eq!(entry.nonce, bet_response.bet_id);
+        assert_eq!(entry.key_version, state.keys.vrf.pubkey().to_string());
+        let proof: solana_sdk::signature::Signature =
+            bet_response.vrf_proof.unwrap().parse().unwrap();
+        assert_eq!(
+            entry.signature_hash,
+            solana_sdk::hash::hash(proof.as_ref()).to_string()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recover_unrecorded_bets() {
+        let (app, state) = setup_test_app().await;
+        let path =
+            std::env::temp_dir().join(format!("zkcasino-vrf-{}.jsonl", uuid::Uuid::new_v4()));
+        let journal = VrfJournal::open(path.clone()).unwrap();
+        let player = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let unknown = "3Kz9hBo8GkbLbvGJgRuSyNk8DkcQ8Xq6kF2Gj8NQEnWa";
+        state.db.deposit(player, 10_000).await.unwrap();
+
+        // Journaled, but the sequencer stopped before recording them
+        let ids = state.bet_ids.clone();
+        let (kept, lost) = (ids.next_id().to_string(), ids.next_id().to_string());
+        for (bet_id, player) in [(&kept, player), (&lost, unknown)] {
+            let input = CoinflipInput {
+                bet_id,
+                player,
+                amount: 4_000,
+                guess: true,
+            };
+            let (_, proof) = vrf::prove_coinflip(&state.keys.vrf, &input);
+            journal
+                .append(bet_id, &input.message(), &state.keys.vrf.pubkey(), &proof)
+                .await
+                .unwrap();
+        }
+        let entries = vrf_journal::entries(&path).unwrap();
+        recover_unrecorded_bets(&state, &entries).await.unwrap();
+
+        // The covered bet completes with the outcome the player was shown
+        let bet = state.db.get_bet(&kept).await.unwrap().unwrap();
+        let balance = state.db.get_player_balance(player).await.unwrap().unwrap();
+        assert_eq!(balance.balance, 10_000 - 4_000 + bet.payout);
+        assert_eq!(
+            state
+                .settlement_stats
+                .total_items_queued
+                .load(Ordering::Relaxed),
+            1
+        );
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (
+                    status,
+                    serde_json::from_slice::<serde_json::Value>(&body).unwrap_or_default(),
+                )
+            }
+        };
+        let (status, decision) = get(format!("/v1/recovery/bets/{}", kept)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(decision["decision"], "completed");
+        assert_eq!(decision["result"], bet.result);
+
+        // The other player's bet can't be settled and is refunded
+        assert!(state.db.get_bet(&lost).await.unwrap().is_none());
+        let (_, decision) = get(format!("/v1/recovery/bets/{}", lost)).await;
+        assert_eq!(decision["decision"], "refunded");
+        assert_eq!(decision["reason"], "player_not_found");
+        let (_, listed) = get(format!("/v1/recovery/bets?player={}", unknown)).await;
+        assert_eq!(listed["decisions"].as_array().unwrap().len(), 1);
+        let (status, _) = get("/v1/recovery/bets/bet_unknown".to_string()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Decided once: another pass changes nothing
+        recover_unrecorded_bets(&state, &entries).await.unwrap();
+        let again = state.db.get_player_balance(player).await.unwrap().unwrap();
+        assert_eq!(again.balance, balance.balance);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bet_with_balance() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        // First deposit funds
+        state.db.deposit(player_address, 10000).await.unwrap();
+
+        // Then place bet
+        let bet_request = BetRequest {
+            player_address: player_address.to_string(),
+            amount: 5000,
+            guess: true,
+            nonce: None,
+        };
+
+        let request_body = serde_json::to_string(&bet_request).unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/bet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let bet_response: BetResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(bet_response.player_address, player_address);
+        assert_eq!(bet_response.amount, 5000);
+        assert!(bet_response.guess);
+        assert!(bet_response.bet_id.starts_with("bet_"));
+        assert!(bet_response.bet_id.parse::<bet_id::BetId>().is_ok());
+
+        // Outcome is reproducible from the VRF proof
+        let proof = bet_response.vrf_proof.unwrap().parse().unwrap();
+        let input = CoinflipInput {
+            bet_id: &bet_response.bet_id,
+            player: player_address,
+            amount: 5000,
+            guess: true,
+        };
+        assert_eq!(
+            vrf::verify_coinflip(&state.keys.vrf.pubkey(), &input, &proof),
+            Some(bet_response.result)
+        );
+
+        // Check payout logic
+        if bet_response.won {
+            assert_eq!(bet_response.payout, 10000);
+        } else {
+            assert_eq!(bet_response.payout, 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bet_resend_with_same_nonce() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10000).await.unwrap();
+
+        let send = |amount: u64| {
+            let bet_request = BetRequest {
+                player_address: player_address.to_string(),
+                amount,
+                guess: true,
+                nonce: Some("nonce-1".to_string()),
+            };
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/bet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&bet_request).unwrap()))
+                    .unwrap(),
+            )
+        };
+
+        let first = send(5000).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let first: BetResponse = serde_json::from_slice(
+            &axum::body::to_bytes(first.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+
+        // Resend returns the original bet rather than placing a new one
+        let resend = send(5000).await.unwrap();
+        assert_eq!(resend.status(), StatusCode::OK);
+        let resend: BetResponse = serde_json::from_slice(
+            &axum::body::to_bytes(resend.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(resend.bet_id, first.bet_id);
+        assert_eq!(resend.result, first.result);
+
+        // Same nonce with a different payload is rejected
+        let conflicting = send(2000).await.unwrap();
+        assert_eq!(conflicting.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_bet_shed_under_backpressure() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10000).await.unwrap();
+
+        // Settlement queue far beyond the hard limit
+        state
+            .settlement_stats
+            .items_in_current_batch
+            .store(50_000, Ordering::Relaxed);
+
+        let bet_request = BetRequest {
+            player_address: player_address.to_string(),
+            amount: 5000,
+            guess: true,
+            nonce: None,
+        };
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/bet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&bet_request).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "5");
+
+        // Balance untouched: the bet was never placed
+        let balance = state.db.get_player_balance(player_address).await.unwrap();
+        assert_eq!(balance.unwrap().balance, 10000);
+    }
+
+    #[tokio::test]
+    async fn test_high_value_bets_require_escrow() {
+        let (_, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 1_000_000).await.unwrap();
+        let app = create_app(AppState {
+            escrow: Some(EscrowConfig {
+                min_amount: 100_000,
+                poll_interval: std::time::Duration::from_secs(2),
+            }),
+            ..state.clone()
+        });
+
+        let place = |amount: u64| {
+            let bet_request = BetRequest {
+                player_address: player_address.to_string(),
+                amount,
+                guess: true,
+                nonce: None,
+            };
+            Request::builder()
+                .method("POST")
+                .uri("/v1/bet")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_string(&bet_request).unwrap()))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(place(100_000)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error_response: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(error_response.error.contains("on-chain escrow"));
+
+        // Small bets stay off-chain
+        let response = app.oneshot(place(5000)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_bet_insufficient_balance() {
+        let (app, _state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        // Try to bet without depositing first
+        let bet_request = BetRequest {
+            player_address: player_address.to_string(),
+            amount: 5000,
+            guess: true,
+            nonce: None,
+        };
+
+        let request_body = serde_json::to_string(&bet_request).unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/bet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error_response: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(error_response.error.contains("Player not found"));
+    }
+
+    #[tokio::test]
+    async fn test_withdraw() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        // First deposit funds
+        state.db.deposit(player_address, 10000).await.unwrap();
+
+        // Then withdraw
+        let withdraw_request = WithdrawRequest {
+            player_address: player_address.to_string(),
+            amount: 3000,
+        };
+
+        let request_body = serde_json::to_string(&withdraw_request).unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/withdraw")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let balance_response: BalanceResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(balance_response.balance, 7000);
+        assert_eq!(balance_response.total_withdrawn, 3000);
+    }
+
+    #[tokio::test]
+    async fn test_withdraw_itemizes_fees() {
+        let (_, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 100_000).await.unwrap();
+        let app = create_app(AppState {
+            withdrawal_fees: WithdrawalFeeConfig {
+                flat_lamports: 1000,
+                percentage_bps: 100, // 1%
+                pass_through_network_fee: true,
+            },
+            ..state.clone()
+        });
+
+        let withdraw = |amount: u64| {
+            let request = WithdrawRequest {
+                player_address: player_address.to_string(),
+                amount,
+            };
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/withdraw")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&request).unwrap()))
+                    .unwrap(),
+            )
+        };
+
+        // Fees can't consume the whole withdrawal
+        let response = withdraw(6000).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = withdraw(50_000).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let withdrawn: WithdrawResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(withdrawn.balance.balance, 50_000);
+        assert_eq!(withdrawn.withdrawal.fees.flat, 1000);
+        assert_eq!(withdrawn.withdrawal.fees.percentage, 500);
+        assert_eq!(
+            withdrawn.withdrawal.fees.network,
+            DEFAULT_NETWORK_FEE_LAMPORTS
+        );
+        assert_eq!(withdrawn.withdrawal.net_amount, 50_000 - 1500 - 5000);
+
+        let ledger = state
+            .db
+            .get_player_withdrawals(player_address)
+            .await
+            .unwrap();
+        assert_eq!(ledger, vec![withdrawn.withdrawal]);
+    }
+
+    #[tokio::test]
+    async fn test_house_limits() {
+        let (_, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 1_000_000).await.unwrap();
+        let app = create_app(AppState {
+            house_limits: Arc::new(HouseLimits::new(HouseLimitConfig {
+                sol: house_limits::TokenLimits {
+                    max_bet: Some(100_000),
+                    max_payout: Some(150_000),
+                    max_exposure: None,
+                },
+                ..Default::default()
+            })),
+            ..state.clone()
+        });
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/limits?token=sol&game=coinflip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let limits: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        // A 2x payout caps the bet below max_bet
+        assert_eq!(limits["max_bet"], 75_000);
+        assert_eq!(limits["max_payout"], 150_000);
+        assert_eq!(limits["min_bet"], 1000);
+
+        let bet = |amount: u64| {
+            let bet_request = BetRequest {
+                player_address: player_address.to_string(),
+                amount,
+                guess: true,
+                nonce: None,
+            };
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/bet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_string(&bet_request).unwrap()))
+                    .unwrap(),
+            )
+        };
+        assert_eq!(bet(75_001).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(bet(75_000).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawal_velocity_limits() {
+        let (_, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 100_000).await.unwrap();
+        let app = create_app(AppState {
+            withdrawal_limits: Arc::new(WithdrawalLimiter::new(WithdrawalLimitConfig {
+                player_hourly_lamports: Some(10_000),
+                ..Default::default()
+            })),
+            ..state.clone()
+        });
+
+        let post = |uri: &str, body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let withdraw = |amount: u64| {
+            post(
+                "/v1/withdraw",
+                serde_json::json!({ "player_address": player_address, "amount": amount }),
+            )
+        };
+
+        assert_eq!(withdraw(8000).await.unwrap().status(), StatusCode::OK);
+        // Over the hourly limit: rejected before the balance is touched
+        assert_eq!(
+            withdraw(5000).await.unwrap().status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        let balance = state.db.get_player_balance(player_address).await.unwrap();
+        assert_eq!(balance.unwrap().balance, 92_000);
+
+        let response = post(
+            "/v1/admin/withdrawal-limits/overrides",
+            serde_json::json!({ "player_address": player_address, "duration_secs": 3600 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(withdraw(5000).await.unwrap().status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/admin/withdrawal-limits")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let snapshot: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(snapshot["alerts"].as_array().unwrap().len(), 1);
+        assert_eq!(snapshot["overrides"][0]["player_address"], player_address);
+    }
+
+    #[tokio::test]
+    async fn test_amounts_normalized_to_token_unit() {
+        let (_, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let mut tokens = TokenConfig::default();
+        tokens.sol.unit = 1000;
+        tokens.sol.dust_threshold = 5000;
+        let app = create_app(AppState {
+            tokens,
+            ..state.clone()
+        });
+
+        let post = |uri: &str, body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let deposit = |amount: u64| {
+            post(
+                "/v1/deposit",
+                serde_json::json!({ "player_address": player_address, "amount": amount }),
+            )
+        };
+
+        // Dust is rejected outright
+        let response = deposit(4999).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Sub-unit residue is not credited
+        let response = deposit(12_345).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let balance: BalanceResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(balance.balance, 12_000);
+
+        let response = post(
+            "/v1/bet",
+            serde_json::json!({ "player_address": player_address, "amount": 4500, "guess": true }),
+        )
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to connect to database: {}", e))?;
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-    db.create_tables()
+        // No withdrawal fees configured, so the dust is the only charge
+        let response = post(
+            "/v1/withdraw",
+            serde_json::json!({ "player_address": player_address, "amount": 6500 }),
+        )
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to create database tables: {}", e))?;
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let withdrawn: WithdrawResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(withdrawn.withdrawal.fees.dust, 500);
+        assert_eq!(withdrawn.withdrawal.net_amount, 6000);
 
-    // Initialize settlement persistence for crash-safe queue (Phase 3e requirement)
-    info!("Initializing settlement persistence for crash-safe queue...");
-    let settlement_persistence = Arc::new(
-        SettlementPersistence::new(&args.database_url)
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/admin/house-fees")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to initialize settlement persistence: {}", e))?,
-    );
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let house: HouseFees = serde_json::from_slice(&body).unwrap();
+        assert_eq!(house.sol, 845);
+    }
 
-    // Phase 3e: Crash recovery - process any pending batches from previous runs
-    info!("Checking for pending settlement batches to recover...");
-    let pending_batches = settlement_persistence
-        .get_pending_batches()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to get pending batches: {}", e))?;
+    #[tokio::test]
+    async fn test_withdrawal_proof() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10000).await.unwrap();
+        let request = |address: &str| {
+            Request::builder()
+                .uri(format!("/v1/withdrawal-proof/{}", address))
+                .body(Body::empty())
+                .unwrap()
+        };
 
-    if !pending_batches.is_empty() {
-        info!(
-            "Found {} pending batches from previous run, starting recovery...",
-            pending_batches.len()
+        // Nothing settled yet
+        let response = app.clone().oneshot(request(player_address)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let items: Vec<SettlementItem> = [(player_address, 2_000, 4_000), ("other", 1_000, 0)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (player, amount, payout))| SettlementItem {
+                bet_id: format!("bet_{}", i),
+                player_address: player.to_string(),
+                amount,
+                payout,
+                timestamp: Utc::now(),
+                token: SettlementToken::Sol,
+                game: GameKind::Coinflip,
+                vrf_proof: None,
+            })
+            .collect();
+        let batch_id = state
+            .settlement_persistence
+            .create_batch(&items)
+            .await
+            .unwrap();
+        // Pending batches are not part of the settled state
+        let response = app.clone().oneshot(request(player_address)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        state
+            .settlement_persistence
+            .update_batch_status(batch_id, SettlementBatchStatus::Confirmed, None)
+            .await
+            .unwrap();
+
+        let response = app.clone().oneshot(request(player_address)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let statement: WithdrawalStatement = serde_json::from_slice(&body).unwrap();
+        assert!(statement.verify().unwrap());
+        assert_eq!(statement.signer, state.keys.sequencer.pubkey().to_string());
+        assert_eq!(statement.body.balance, 10000);
+        assert_eq!(statement.body.settled_balance, 2_000);
+        assert_eq!(statement.body.last_settled_batch, Some(batch_id));
+        let settled = state
+            .settlement_persistence
+            .settled_balances()
+            .await
+            .unwrap();
+        assert_eq!(
+            statement.body.state_root,
+            solana_sdk::hash::Hash::new_from_array(state_root::state_root(&settled)).to_string()
         );
 
-        for batch in pending_batches {
-            info!(
-                "Recovering batch {} with status {:?}",
-                batch.batch_id, batch.status
-            );
+        let response = app.oneshot(request("unknown_player")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 
-            // TODO: Implement actual recovery logic based on batch status
-            // For now, just log the recovery - full implementation would:
-            // - Re-process pending batches
-            // - Re-submit proved batches
-            // - Verify submitted batches on-chain
-            match batch.status {
-                SettlementBatchStatus::Pending => {
-                    info!("Batch {} needs re-processing", batch.batch_id);
-                }
-                SettlementBatchStatus::Proving => {
-                    info!("Batch {} was interrupted during proving", batch.batch_id);
-                }
-                SettlementBatchStatus::Proved => {
-                    info!("Batch {} has proof ready for submission", batch.batch_id);
-                }
-                SettlementBatchStatus::Submitted => {
-                    info!(
-                        "Batch {} was submitted, checking on-chain status",
-                        batch.batch_id
-                    );
-                }
-                _ => {}
+    #[tokio::test]
+    async fn test_finality_proof_requires_settled_batch() {
+        let (app, state) = setup_test_app().await;
+        let request = |batch_id: u64| {
+            Request::builder()
+                .uri(format!("/v1/batches/{}/finality-proof", batch_id))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request(42)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Not settled on-chain yet
+        let batch_id = state
+            .settlement_persistence
+            .create_batch(&[])
+            .await
+            .unwrap();
+        let response = app.clone().oneshot(request(batch_id)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_player_activity() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10_000).await.unwrap();
+        state
+            .db
+            .withdraw(
+                player_address,
+                4_000,
+                withdrawal_fees::WithdrawalFees::default(),
+            )
+            .await
+            .unwrap();
+        let request = |query: &str| {
+            Request::builder()
+                .uri(format!("/v1/activity/{}?{}", player_address, query))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request("limit=1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["events"][0]["kind"], "withdrawal");
+        assert_eq!(page["events"][0]["amount"], -4_000);
+        let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+        let response = app
+            .clone()
+            .oneshot(request(&format!("cursor={}", cursor)))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(page["events"][0]["kind"], "deposit");
+        assert_eq!(page["events"][0]["amount"], 10_000);
+        assert!(page["next_cursor"].is_null());
+
+        let response = app.oneshot(request("cursor=bet:unknown")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_settlement_queue_endpoint() {
+        let (app, state) = setup_test_app().await;
+        state.settlement_stats.queue.enqueued(&SettlementItem {
+            bet_id: "bet_1".to_string(),
+            player_address: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+            amount: 1_000,
+            payout: 2_000,
+            timestamp: Utc::now() - chrono::Duration::seconds(90),
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
+        });
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri("/v1/admin/queue");
+            if let Some(auth) = auth {
+                builder = builder.header("authorization", auth);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .oneshot(request(Some("Bearer test-admin-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let snapshot: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(snapshot["unbatched"]["count"], 1);
+        assert!(snapshot["unbatched"]["oldest_age_secs"].as_u64().unwrap() >= 90);
+        assert_eq!(
+            snapshot["unbatched"]["players"][0]["total_payout"],
+            serde_json::json!(2_000)
+        );
+        assert!(snapshot["in_progress"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_balance_adjustment_dual_control() {
+        use adjustments::AdjustmentStatus;
+
+        let (_, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10_000).await.unwrap();
+        let approvers = [Keypair::new(), Keypair::new()];
+        let manager = AdjustmentManager::new(
+            AdjustmentConfig {
+                approvers: approvers.iter().map(|k| k.pubkey()).collect(),
+                audit_log: None,
+            },
+            state.db.clone(),
+        );
+        let app = create_app(AppState {
+            adjustments: Some(Arc::new(manager)),
+            ..state.clone()
+        });
+
+        let send = |uri: &str, body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let read = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<BalanceAdjustment>(&body).unwrap()
+        };
+
+        // A reason code is mandatory
+        let response = send(
+            "/v1/admin/adjustments",
+            serde_json::json!({ "player_address": player_address, "amount": -2500 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = send(
+            "/v1/admin/adjustments",
+            serde_json::json!({
+                "player_address": player_address,
+                "amount": -2500,
+                "reason_code": "error_correction",
+                "note": "Duplicate deposit credit",
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let adjustment = read(response).await;
+
+        for (i, approver) in approvers.iter().enumerate() {
+            let signature = approver.sign_message(adjustment.digest().as_bytes());
+            let response = send(
+                &format!("/v1/admin/adjustments/{}/approve", adjustment.id),
+                serde_json::json!({
+                    "approver": approver.pubkey().to_string(),
+                    "signature": signature.to_string(),
+                }),
+            )
+            .await
+            .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let status = read(response).await.status;
+            if i == 0 {
+                assert_eq!(status, AdjustmentStatus::PendingApproval);
+            } else {
+                assert_eq!(status, AdjustmentStatus::Applied);
             }
         }
-    } else {
-        info!("No pending batches found, starting fresh");
+
+        let balance = state.db.get_player_balance(player_address).await.unwrap();
+        assert_eq!(balance.unwrap().balance, 7500);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/admin/ledger")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let journal: Vec<JournalEntry> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(journal.len(), 1);
+        assert_eq!(journal[0].postings[0].amount, -2500);
+    }
+
+    #[tokio::test]
+    async fn test_get_balance() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        // First deposit funds
+        state.db.deposit(player_address, 5000).await.unwrap();
+
+        // Get balance
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/v1/balance/{}", player_address))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let balance_response: BalanceResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(balance_response.balance, 5000);
+        assert_eq!(balance_response.player_address, player_address);
+    }
+
+    #[tokio::test]
+    async fn test_signed_responses() {
+        let (_, state) = setup_test_app().await;
+        let app = create_app(AppState {
+            signed_responses: true,
+            ..state.clone()
+        });
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 10000).await.unwrap();
+        let bet = Bet {
+            id: "bet_1".to_string(),
+            player_address: player_address.to_string(),
+            amount: 5000,
+            guess: true,
+            result: true,
+            won: true,
+            payout: 10000,
+            timestamp: Utc::now(),
+            settlement: None,
+            escrow: None,
+        };
+        state.db.save_bet(&bet).await.unwrap();
+
+        for path in [
+            format!("/v1/bet/{}", bet.id),
+            format!("/v1/balance/{}", player_address),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(&path).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let signature =
+                response_signing::ResponseSignature::from_headers(response.headers()).unwrap();
+            assert_eq!(signature.signer, state.keys.sequencer.pubkey());
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(signature.verify(&path, &body));
+        }
+        // Errors are left unsigned
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/bet/bet_missing")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response
+            .headers()
+            .get(response_signing::SIGNATURE_HEADER)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_player_bets() {
+        let (app, state) = setup_test_app().await;
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+        // Create test bets
+        for i in 0..3 {
+            let bet = Bet {
+                id: format!("test_bet_{}", i),
+                player_address: player_address.to_string(),
+                amount: 1000,
+                guess: true,
+                result: false,
+                won: false,
+                payout: 0,
+                timestamp: Utc::now(),
+                settlement: None,
+                escrow: None,
+            };
+            state.db.save_bet(&bet).await.unwrap();
+        }
+
+        // Get player bets
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/v1/bets/{}", player_address))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let bets_response: BetsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(bets_response.total_count, 3);
+        assert!(bets_response
+            .bets
+            .iter()
+            .all(|bet| bet.settlement.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_settlement_batch_links_bets() {
+        let (_app, state) = setup_test_app().await;
+
+        let mut items = Vec::new();
+        for i in 0..2 {
+            let bet_id = state.bet_ids.next_id().to_string();
+            state
+                .db
+                .save_bet(&Bet {
+                    id: bet_id.clone(),
+                    player_address: "player".to_string(),
+                    amount: 1000,
+                    guess: true,
+                    result: i == 0,
+                    won: i == 0,
+                    payout: if i == 0 { 2000 } else { 0 },
+                    timestamp: Utc::now(),
+                    settlement: None,
+                    escrow: None,
+                })
+                .await
+                .unwrap();
+            items.push(SettlementItem {
+                bet_id,
+                player_address: "player".to_string(),
+                amount: 1000,
+                payout: if i == 0 { 2000 } else { 0 },
+                timestamp: Utc::now(),
+                token: SettlementToken::Sol,
+                game: GameKind::Coinflip,
+                vrf_proof: None,
+            });
+        }
+
+        let submitter = SettlementSubmitter::new(
+            None,
+            state.settlement_persistence.clone(),
+            state.db.clone(),
+            state.keys.vrf.pubkey(),
+        );
+        process_settlement_batch(
+            &items,
+            &state.settlement_stats,
+            Some(&submitter),
+            state.settlement_prover.clone(),
+            state.settlement_persistence.clone(),
+            state.db.clone(),
+            false,
+        )
+        .await;
+
+        let bet = state.db.get_bet(&items[1].bet_id).await.unwrap().unwrap();
+        let link = bet.settlement.unwrap();
+        assert_eq!(link.onchain_index, 1);
+        assert_eq!(link.status, BetSettlementStatus::Confirmed);
+        assert!(link.transaction_signature.unwrap().starts_with("mock_tx_"));
     }
 
-    // Initialize settlement queue for ZK proof batching (VF Node pattern)
-    let (settlement_sender, settlement_receiver) = mpsc::unbounded_channel();
-    let settlement_stats = SettlementStats::new();
+    /// Serves fairness bundles from the app and batch records from a fixed record
+    struct AppFairnessSource {
+        app: Router,
+        record: solana::OnchainBatchRecord,
+    }
 
-    // Initialize oracle manager for proof fetching (as requested by user)
-    let oracle_config = OracleConfig::default();
-    let oracle_manager = OracleManager::new(oracle_config.clone());
-    let oracle_client = OracleClient::new(oracle_config);
+    #[axum::async_trait]
+    impl bet_verification::FairnessSource for AppFairnessSource {
+        async fn fairness_bundle(
+            &self,
+            bet_id: &str,
+        ) -> anyhow::Result<(Vec<u8>, response_signing::ResponseSignature)> {
+            let response = self
+                .app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(bet_verification::fairness_path(bet_id))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await?;
+            anyhow::ensure!(response.status() == StatusCode::OK, "{}", response.status());
+            let signature = response_signing::ResponseSignature::from_headers(response.headers())?;
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            Ok((body.to_vec(), signature))
+        }
 
-    // Start oracle proof fetching service
-    oracle_manager
-        .start_proof_service()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to start oracle service: {}", e))?;
+        async fn batch_record(
+            &self,
+            batch_id: u64,
+        ) -> anyhow::Result<Option<solana::OnchainBatchRecord>> {
+            Ok((batch_id == self.record.batch_id).then(|| self.record.clone()))
+        }
+    }
 
-    // Initialize Solana client (Phase 2: localnet first, then testnet)
-    let solana_client = if std::env::var("ENABLE_SOLANA").unwrap_or_default() == "true" {
-        info!("Initializing Solana client...");
+    #[tokio::test]
+    async fn test_bet_fairness_verifies_end_to_end() {
+        let (_, state) = setup_test_app().await;
+        let app = create_app(AppState {
+            signed_responses: true,
+            ..state.clone()
+        });
+        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
 
-        // Generate or load sequencer keypair (in production, load from secure storage)
-        let sequencer_keypair = Keypair::new();
-        info!("Sequencer public key: {}", sequencer_keypair.pubkey());
+        let mut items = Vec::new();
+        for amount in [1000u64, 1500, 2500] {
+            let bet_id = state.bet_ids.next_id().to_string();
+            let input = CoinflipInput {
+                bet_id: &bet_id,
+                player: player_address,
+                amount,
+                guess: true,
+            };
+            let (result, proof) = vrf::prove_coinflip(&state.keys.vrf, &input);
+            let payout = if result {
+                state.payouts.win_payout(amount)
+            } else {
+                0
+            };
+            state
+                .db
+                .save_bet(&Bet {
+                    id: bet_id.clone(),
+                    player_address: player_address.to_string(),
+                    amount: amount as i64,
+                    guess: true,
+                    result,
+                    won: result,
+                    payout: payout as i64,
+                    timestamp: Utc::now(),
+                    settlement: None,
+                    escrow: None,
+                })
+                .await
+                .unwrap();
+            items.push(SettlementItem {
+                bet_id,
+                player_address: player_address.to_string(),
+                amount: amount as i64,
+                payout: payout as i64,
+                timestamp: Utc::now(),
+                token: SettlementToken::Sol,
+                game: GameKind::Coinflip,
+                vrf_proof: Some(proof.to_string()),
+            });
+        }
 
-        // Configure for local validator by default, switch to testnet with env var
-        let solana_config = if std::env::var("SOLANA_TESTNET").unwrap_or_default() == "true" {
-            SolanaConfig::testnet()
-        } else {
-            SolanaConfig::default() // Local validator
-        };
+        // Not batched yet
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(bet_verification::fairness_path(&items[0].bet_id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
 
-        // Program IDs (these should match the deployed programs)
-        let vault_program_id = std::env::var("VAULT_PROGRAM_ID")
-            .unwrap_or_else(|_| "11111111111111111111111111111111".to_string());
-        let verifier_program_id = std::env::var("VERIFIER_PROGRAM_ID")
-            .unwrap_or_else(|_| "11111111111111111111111111111112".to_string());
-
-        match SolanaClient::new(
-            solana_config,
-            sequencer_keypair,
-            &vault_program_id,
-            &verifier_program_id,
-        ) {
-            Ok(client) => {
-                info!("Solana client initialized successfully");
-                // Test connection
-                if let Err(e) = client.health_check().await {
-                    warn!(
-                        "Solana health check failed: {}. Continuing without Solana integration.",
-                        e
-                    );
-                    None
-                } else {
-                    Some(Arc::new(client))
+        let submitter = SettlementSubmitter::new(
+            None,
+            state.settlement_persistence.clone(),
+            state.db.clone(),
+            state.keys.vrf.pubkey(),
+        );
+        process_settlement_batch(
+            &items,
+            &state.settlement_stats,
+            Some(&submitter),
+            state.settlement_prover.clone(),
+            state.settlement_persistence.clone(),
+            state.db.clone(),
+            false,
+        )
+        .await;
+        let bet = state.db.get_bet(&items[0].bet_id).await.unwrap().unwrap();
+        let batch_id = bet.settlement.unwrap().batch_id;
+
+        // The batch record as the verifier program writes it
+        let mut house_delta = 0;
+        let leaves: Vec<[u8; 32]> = submitter::bet_settlements(&items)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, bet)| {
+                house_delta += bet.bet_amount as i64 - bet.payout as i64;
+                verifier::optimistic::BatchLeaf {
+                    index: index as u32,
+                    bet: bet.to_program(),
+                    house_delta,
                 }
-            }
-            Err(e) => {
-                warn!("Failed to initialize Solana client: {}. Continuing without Solana integration.", e);
-                None
-            }
-        }
-    } else {
-        info!("Solana integration disabled. Set ENABLE_SOLANA=true to enable.");
-        None
-    };
+                .hash()
+            })
+            .collect();
+        let record = solana::OnchainBatchRecord {
+            batch_id,
+            sequencer: state.keys.sequencer.pubkey(),
+            proof_hash: [0; 32],
+            state_root: [0; 32],
+            bet_count: items.len() as u32,
+            house_delta,
+            slot: 1,
+            status: verifier::BatchRecordStatus::Settled,
+            bets_root: verifier::optimistic::bets_root(&leaves),
+        };
 
-    // Initialize Settlement Prover for Phase 3e (ZK proof generation)
-    let settlement_prover = if std::env::var("ENABLE_ZK_PROOFS").unwrap_or_default() == "true" {
-        info!("Initializing Settlement Prover for ZK proof generation...");
-
-        let prover_config = SettlementProverConfig::default();
-        match SettlementProver::new(prover_config).await {
-            Ok(prover) => {
-                // Initialize some demo user balances for testing
-                prover.init_user_balance(100, 10000).await;
-                prover.init_user_balance(200, 5000).await;
-                prover.init_user_balance(300, 15000).await;
-
-                info!("Settlement Prover initialized successfully");
-                Some(Arc::new(prover))
-            }
-            Err(e) => {
-                warn!("Failed to initialize Settlement Prover: {}. Continuing with placeholder proofs.", e);
-                None
-            }
+        let verifier = bet_verification::BetVerifier::new(
+            state.keys.sequencer.pubkey(),
+            vec![state.keys.vrf.pubkey()],
+            Arc::new(AppFairnessSource { app, record }),
+        );
+        for item in &items {
+            let verified = verifier.verify_bet_end_to_end(&item.bet_id).await.unwrap();
+            assert_eq!(verified.batch_id, batch_id);
+            assert!(verified.settled);
+            assert_eq!(verified.bet.payout, item.payout as u64);
         }
-    } else {
-        info!("ZK proof generation disabled. Set ENABLE_ZK_PROOFS=true to enable real proof generation.");
-        None
-    };
-
-    let state = AppState {
-        db: Arc::new(db),
-        settlement_sender,
-        oracle_client,
-        settlement_stats: settlement_stats.clone(),
-        solana_client,
-        settlement_prover,
-        settlement_persistence: settlement_persistence.clone(),
-    };
+    }
 
-    // Settlement processor for ZK proof batching (VF Node background pattern)
-    let stats_clone = settlement_stats.clone();
-    let solana_client_clone = state.solana_client.clone();
-    let settlement_prover_clone = state.settlement_prover.clone();
-    let settlement_persistence_clone = state.settlement_persistence.clone();
-    let _settlement_processor_handle = tokio::spawn(async move {
-        let mut settlement_receiver = settlement_receiver;
-        let mut batch = Vec::new();
-        let mut interval = interval(Duration::from_millis(100)); // 100ms batching window
+    #[tokio::test]
+    async fn test_transparency_endpoint() {
+        let (app, state) = setup_test_app().await;
 
-        loop {
-            tokio::select! {
-                // Receive settlement items
-                item = settlement_receiver.recv() => {
-                    match item {
-                        Some(settlement_item) => {
-                            // Phase 3e: Check for deduplication before adding to batch
-                            match settlement_persistence_clone.is_bet_processed(&settlement_item.bet_id).await {
-                                Ok(already_processed) => {
-                                    if already_processed {
-                                        warn!("Bet {} already processed, skipping to prevent double settlement", settlement_item.bet_id);
-                                        continue;
-                                    }
+        let batch_id = state
+            .settlement_persistence
+            .save_batch(
+                "batch_1",
+                vec![SettlementItem {
+                    bet_id: "bet_1".to_string(),
+                    player_address: "player".to_string(),
+                    amount: 2500,
+                    payout: 0,
+                    timestamp: Utc::now(),
+                    token: SettlementToken::Sol,
+                    game: GameKind::Coinflip,
+                    vrf_proof: None,
+                }],
+            )
+            .await
+            .unwrap();
+        state
+            .settlement_persistence
+            .mark_completed(&format!("batch_{}", batch_id))
+            .await
+            .unwrap();
 
-                                    // Add to batch if not already processed
-                                    batch.push(settlement_item);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/transparency")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-                                    // Process batch when it reaches size limit (prepare for ZK rollup)
-                                    if batch.len() >= 50 {
-                                        process_settlement_batch(&batch, &stats_clone, solana_client_clone.clone(), settlement_prover_clone.clone(), settlement_persistence_clone.clone()).await;
-                                        batch.clear();
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to check if bet {} is already processed: {}. Proceeding anyway.", settlement_item.bet_id, e);
-                                    // If deduplication check fails, proceed anyway to avoid blocking settlement
-                                    batch.push(settlement_item);
-                                    if batch.len() >= 50 {
-                                        process_settlement_batch(&batch, &stats_clone, solana_client_clone.clone(), settlement_prover_clone.clone(), settlement_persistence_clone.clone()).await;
-                                        batch.clear();
-                                    }
-                                }
-                            }
-                        }
-                        None => break, // Channel closed
-                    }
-                }
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            report["vrf_public_keys"][0],
+            state.keys.vrf.pubkey().to_string()
+        );
+        assert!(report["verifying_key_hash"].is_null());
+        assert_eq!(report["prover"]["state"], "disabled");
+        assert_eq!(report["latest_settlement"]["batch_id"], batch_id);
+        assert!(report["latest_settlement"]["state_root"].is_null());
+        assert_eq!(report["settlement"]["settled_volume"], 2500);
+        assert_eq!(report["settlement"]["placeholder_batches"], 1);
+        assert_eq!(report["vrf_anchors"], serde_json::json!([]));
+    }
 
-                // Process batch on timer (ensure regular processing)
-                _ = interval.tick() => {
-                    if !batch.is_empty() {
-                        process_settlement_batch(&batch, &stats_clone, solana_client_clone.clone(), settlement_prover_clone.clone(), settlement_persistence_clone.clone()).await;
-                        batch.clear();
-                    }
-                }
+    #[tokio::test]
+    async fn test_dispute_filing_and_resolution() {
+        let (app, state) = setup_test_app().await;
+        let bet = Bet {
+            id: "bet_disputed".to_string(),
+            player_address: "player1".to_string(),
+            amount: 1000,
+            guess: true,
+            result: false,
+            won: false,
+            payout: 0,
+            timestamp: Utc::now(),
+            settlement: None,
+            escrow: None,
+        };
+        state.db.save_bet(&bet).await.unwrap();
+
+        let send = |uri: &str, auth: bool, body: serde_json::Value| {
+            let mut builder = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json");
+            if auth {
+                builder = builder.header("authorization", "Bearer test-admin-token");
             }
-        }
-    });
+            app.clone()
+                .oneshot(builder.body(Body::from(body.to_string())).unwrap())
+        };
+        let file = |player: &str| {
+            serde_json::json!({
+                "bet_id": "bet_disputed",
+                "player_address": player,
+                "reason": "I never saw this outcome",
+            })
+        };
 
-    let app = create_app(state);
+        // Only the bet's own player can dispute it
+        let response = send("/v1/disputes", false, file("player2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], args.port));
-    info!("Sequencer listening on {}", addr);
+        let response = send("/v1/disputes", false, file("player1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let dispute: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(dispute["status"], "open");
+        assert!(dispute["evidence_check"]["vrf_proof_valid"].is_null());
+        let id = dispute["id"].as_u64().unwrap();
+
+        let response = send("/v1/disputes", false, file("player1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let resolve = serde_json::json!({ "outcome": "rejected", "note": "Outcome verified" });
+        let uri = format!("/v1/admin/disputes/{}/resolve", id);
+        let response = send(&uri, false, resolve.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = send(&uri, true, resolve).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+        // Players track their dispute by id
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/v1/disputes/{}", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let dispute: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(dispute["status"], "resolved");
+        assert_eq!(dispute["resolution"]["outcome"], "rejected");
+    }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn test_treasury_endpoints_require_admin_and_config() {
+        let (app, _state) = setup_test_app().await;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::{
-        body::Body,
-        http::{Request, StatusCode},
-    };
-    use tower::ServiceExt; // for `oneshot`
+        let request = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri("/v1/admin/treasury/movements");
+            if let Some(token) = auth {
+                builder = builder.header("authorization", format!("Bearer {}", token));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
 
-    async fn setup_test_app() -> (Router, AppState) {
-        let db = Database::new("").await.unwrap();
-        db.create_tables().await.unwrap();
+        let response = app.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 
-        // Initialize test settlement persistence
-        let settlement_persistence = Arc::new(
-            SettlementPersistence::new("sqlite::memory:")
-                .await
-                .expect("Failed to initialize test settlement persistence"),
-        );
+        let response = app
+            .oneshot(request(Some("test-admin-token")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 
-        let (settlement_sender, _) = mpsc::unbounded_channel();
-        let oracle_config = OracleConfig::default();
-        let oracle_client = OracleClient::new(oracle_config);
-        let settlement_stats = SettlementStats::new();
+    #[tokio::test]
+    async fn test_settlement_costs_endpoint() {
+        use settlement_persistence::SettlementCost;
 
-        let state = AppState {
-            db: Arc::new(db),
-            settlement_sender,
-            oracle_client,
-            settlement_stats,
-            solana_client: None,     // No Solana client for tests
-            settlement_prover: None, // No ZK prover for tests
-            settlement_persistence,
+        let (app, state) = setup_test_app().await;
+
+        let item = |bet_id: &str| SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: "player".to_string(),
+            amount: 1000,
+            payout: 0,
+            timestamp: Utc::now(),
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
         };
+        let persistence = &state.settlement_persistence;
+        let small = persistence
+            .save_batch("batch_1", vec![item("a"), item("b")])
+            .await
+            .unwrap();
+        let large = persistence
+            .save_batch("batch_2", vec![item("c"), item("d"), item("e"), item("f")])
+            .await
+            .unwrap();
+        persistence
+            .store_cost(
+                small,
+                SettlementCost {
+                    fee_lamports: 10_000,
+                    priority_fee_lamports: 5_000,
+                    compute_units_consumed: Some(200_000),
+                },
+            )
+            .await
+            .unwrap();
+        persistence
+            .store_cost(
+                large,
+                SettlementCost {
+                    fee_lamports: 12_000,
+                    priority_fee_lamports: 7_000,
+                    compute_units_consumed: None,
+                },
+            )
+            .await
+            .unwrap();
 
-        let app = create_app(state.clone());
-        (app, state)
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/analytics/settlement-costs")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let summary: SettlementCostSummary = serde_json::from_slice(&body).unwrap();
+        assert_eq!(summary.batches, 2);
+        assert_eq!(summary.bets, 6);
+        assert_eq!(summary.total_fee_lamports, 22_000);
+        assert_eq!(summary.total_priority_fee_lamports, 12_000);
+        assert_eq!(summary.avg_fee_per_batch_lamports, 11_000.0);
+        // Only the metered batch contributes to compute units per bet
+        assert_eq!(summary.avg_compute_units_per_bet, Some(100_000.0));
+        assert_eq!(summary.by_batch_size.len(), 2);
+        assert_eq!(summary.by_batch_size[0].batch_size, 2);
+        assert_eq!(summary.by_batch_size[0].avg_fee_per_bet_lamports, 5_000.0);
+        assert_eq!(summary.by_batch_size[1].avg_fee_per_bet_lamports, 3_000.0);
     }
 
     #[tokio::test]
-    async fn test_health_check() {
-        let (app, _state) = setup_test_app().await;
+    async fn test_strict_proofs_refuses_placeholder() {
+        let (_app, state) = setup_test_app().await;
+
+        let bet_id = state.bet_ids.next_id().to_string();
+        state
+            .db
+            .save_bet(&Bet {
+                id: bet_id.clone(),
+                player_address: "player".to_string(),
+                amount: 1000,
+                guess: true,
+                result: true,
+                won: true,
+                payout: 2000,
+                timestamp: Utc::now(),
+                settlement: None,
+                escrow: None,
+            })
+            .await
+            .unwrap();
+        let items = vec![SettlementItem {
+            bet_id: bet_id.clone(),
+            player_address: "player".to_string(),
+            amount: 1000,
+            payout: 2000,
+            timestamp: Utc::now(),
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
+        }];
+
+        // Prover disabled and strict mode on: no placeholder proof, nothing submitted
+        let submitter = SettlementSubmitter::new(
+            None,
+            state.settlement_persistence.clone(),
+            state.db.clone(),
+            state.keys.vrf.pubkey(),
+        );
+        process_settlement_batch(
+            &items,
+            &state.settlement_stats,
+            Some(&submitter),
+            state.settlement_prover.clone(),
+            state.settlement_persistence.clone(),
+            state.db.clone(),
+            true,
+        )
+        .await;
+
+        let link = state
+            .db
+            .get_bet(&bet_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .settlement
+            .unwrap();
+        assert_eq!(link.status, BetSettlementStatus::Failed);
+        assert!(link.transaction_signature.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reports_endpoints() {
+        let (app, state) = setup_test_app().await;
+
+        let now = Utc::now();
+        let report = state
+            .report_service
+            .generate_report(now - chrono::Duration::hours(1), now)
+            .await
+            .unwrap();
 
         let response = app
+            .clone()
             .oneshot(
                 Request::builder()
-                    .uri("/health")
+                    .uri("/v1/reports")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
 
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        assert_eq!(&body[..], b"OK");
-    }
-
-    #[tokio::test]
-    async fn test_deposit_and_balance() {
-        let (app, _state) = setup_test_app().await;
-        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
-
-        // Test deposit
-        let deposit_request = DepositRequest {
-            player_address: player_address.to_string(),
-            amount: 10000,
-        };
-
-        let request_body = serde_json::to_string(&deposit_request).unwrap();
+        let summaries: Vec<ReportSummary> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].report_id, report.body.report_id);
 
         let response = app
+            .clone()
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/v1/deposit")
-                    .header("content-type", "application/json")
-                    .body(Body::from(request_body))
+                    .uri(format!("/v1/reports/{}", report.body.report_id))
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
 
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        let balance_response: BalanceResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(balance_response.balance, 10000);
-        assert_eq!(balance_response.total_deposited, 10000);
+        let downloaded: FairnessReport = serde_json::from_slice(&body).unwrap();
+        assert!(downloaded.verify_signature().unwrap());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/reports/unknown")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
     #[tokio::test]
-    async fn test_bet_with_balance() {
+    async fn test_disabled_game_refuses_bets() {
         let (app, state) = setup_test_app().await;
         let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        state.db.deposit(player_address, 100_000).await.unwrap();
 
-        // First deposit funds
-        state.db.deposit(player_address, 10000).await.unwrap();
-
-        // Then place bet
-        let bet_request = BetRequest {
-            player_address: player_address.to_string(),
-            amount: 5000,
-            guess: true,
+        let update_game = |game: &str, body: serde_json::Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/v1/admin/games/{}", game))
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
         };
-
-        let request_body = serde_json::to_string(&bet_request).unwrap();
-
-        let response = app
-            .oneshot(
+        let bet = || {
+            let bet_request = BetRequest {
+                player_address: player_address.to_string(),
+                amount: 5000,
+                guess: true,
+                nonce: None,
+            };
+            app.clone().oneshot(
                 Request::builder()
                     .method("POST")
                     .uri("/v1/bet")
                     .header("content-type", "application/json")
-                    .body(Body::from(request_body))
+                    .body(Body::from(serde_json::to_string(&bet_request).unwrap()))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+        };
 
+        let response = update_game(
+            "coinflip",
+            serde_json::json!({ "enabled": false, "reason": "payout bug" }),
+        )
+        .await
+        .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
+        let response = bet().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        let bet_response: BetResponse = serde_json::from_slice(&body).unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.error, "coinflip is temporarily disabled: payout bug");
+        let balance = state.db.get_player_balance(player_address).await.unwrap();
+        assert_eq!(balance.unwrap().balance, 100_000);
 
-        assert_eq!(bet_response.player_address, player_address);
-        assert_eq!(bet_response.amount, 5000);
-        assert_eq!(bet_response.guess, true);
-        assert!(bet_response.bet_id.starts_with("bet_"));
+        let response = update_game("roulette", serde_json::json!({ "enabled": false }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-        // Check payout logic
-        if bet_response.won {
-            assert_eq!(bet_response.payout, 10000);
-        } else {
-            assert_eq!(bet_response.payout, 0);
-        }
+        update_game("coinflip", serde_json::json!({ "enabled": true }))
+            .await
+            .unwrap();
+        assert_eq!(bet().await.unwrap().status(), StatusCode::OK);
     }
 
     #[tokio::test]
-    async fn test_bet_insufficient_balance() {
-        let (app, _state) = setup_test_app().await;
-        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    async fn test_signing_key_registry() {
+        let (app, state) = setup_test_app().await;
+        state
+            .keys
+            .record_in_registry(&state.settlement_persistence)
+            .await
+            .unwrap();
+        let vrf_key = state.keys.vrf.pubkey().to_string();
 
-        // Try to bet without depositing first
-        let bet_request = BetRequest {
-            player_address: player_address.to_string(),
-            amount: 5000,
-            guess: true,
+        let list_keys = |uri: &str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
         };
-
-        let request_body = serde_json::to_string(&bet_request).unwrap();
-
-        let response = app
-            .oneshot(
+        let revoke = |body: serde_json::Value| {
+            app.clone().oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/v1/bet")
+                    .uri("/v1/admin/keys/revoke")
                     .header("content-type", "application/json")
-                    .body(Body::from(request_body))
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::from(body.to_string()))
                     .unwrap(),
             )
-            .await
-            .unwrap();
+        };
+        let records = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Vec<KeyRecord>>(&body).unwrap()
+        };
 
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = list_keys("/v1/keys").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(records(response).await.len(), 2);
+        let vrf = records(list_keys("/v1/keys?kind=vrf").await.unwrap()).await;
+        assert_eq!(vrf.len(), 1);
+        assert_eq!(vrf[0].key, vrf_key);
+        assert!(vrf[0].valid_until.is_none());
+
+        let response = revoke(serde_json::json!({
+            "kind": "vrf",
+            "key": vrf_key,
+            "reason": "key file leaked",
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let revoked = records(response).await;
+        assert_eq!(
+            revoked[0].revocation.as_ref().unwrap().reason,
+            "key file leaked"
+        );
+        assert!(revoked[0].valid_until.is_some());
+
+        let again = serde_json::json!({ "kind": "vrf", "key": vrf_key, "reason": "again" });
+        assert_eq!(revoke(again).await.unwrap().status(), StatusCode::CONFLICT);
+        let unknown = serde_json::json!({ "kind": "vrf", "key": "unknown", "reason": "typo" });
+        assert_eq!(
+            revoke(unknown).await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        // A revoked key can't be put back into use
+        assert!(state
+            .keys
+            .record_in_registry(&state.settlement_persistence)
             .await
-            .unwrap();
-        let error_response: ErrorResponse = serde_json::from_slice(&body).unwrap();
-        assert!(error_response.error.contains("Player not found"));
+            .is_err());
     }
 
     #[tokio::test]
-    async fn test_withdraw() {
+    async fn test_geo_policy_blocks_bets() {
         let (app, state) = setup_test_app().await;
-        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
-
-        // First deposit funds
-        state.db.deposit(player_address, 10000).await.unwrap();
 
-        // Then withdraw
-        let withdraw_request = WithdrawRequest {
-            player_address: player_address.to_string(),
-            amount: 3000,
+        // Unknown countries are blocked once the policy is enforced
+        let update = GeoPolicyUpdateRequest {
+            mode: GeoPolicyMode::Blocklist,
+            allow_unknown: false,
         };
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/v1/admin/geo-policy")
+                    .header("content-type", "application/json")
+                    .header("authorization", "Bearer test-admin-token")
+                    .body(Body::from(serde_json::to_string(&update).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(state.geo_policy.snapshot().mode, GeoPolicyMode::Blocklist);
 
-        let request_body = serde_json::to_string(&withdraw_request).unwrap();
-
+        let bet_request = BetRequest {
+            player_address: "player".to_string(),
+            amount: 5000,
+            guess: true,
+            nonce: None,
+        };
         let response = app
+            .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/v1/withdraw")
+                    .uri("/v1/bet")
                     .header("content-type", "application/json")
-                    .body(Body::from(request_body))
+                    .header("x-forwarded-for", "203.0.113.7")
+                    .body(Body::from(serde_json::to_string(&bet_request).unwrap()))
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
+        // Non-betting endpoints are unaffected
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/recent-bets")
+                    .header("x-forwarded-for", "203.0.113.7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        // Admin API requires credentials
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/admin/geo-policy")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
             .unwrap();
-        let balance_response: BalanceResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(balance_response.balance, 7000);
-        assert_eq!(balance_response.total_withdrawn, 3000);
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
 
     #[tokio::test]
-    async fn test_get_balance() {
-        let (app, state) = setup_test_app().await;
-        let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
-
-        // First deposit funds
-        state.db.deposit(player_address, 5000).await.unwrap();
-
-        // Get balance
+    async fn test_deposit_address_requires_solana() {
+        let (app, _state) = setup_test_app().await;
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri(&format!("/v1/balance/{}", player_address))
+                    .uri(format!(
+                        "/v1/deposit-address/{}",
+                        solana_sdk::pubkey::Pubkey::new_unique()
+                    ))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
 
-        assert_eq!(response.status(), StatusCode::OK);
+    #[tokio::test]
+    async fn test_faucet_requires_dev_mode() {
+        let (app, mut state) = setup_test_app().await;
+        let request_body = serde_json::to_string(&FaucetRequest {
+            airdrop_lamports: None,
+            deposit_lamports: None,
+        })
+        .unwrap();
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/dev/faucet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body.clone()))
+                    .unwrap(),
+            )
             .await
             .unwrap();
-        let balance_response: BalanceResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(balance_response.balance, 5000);
-        assert_eq!(balance_response.player_address, player_address);
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Dev mode without Solana integration cannot onboard on-chain
+        state.dev_mode = true;
+        let response = create_app(state)
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/dev/faucet")
+                    .header("content-type", "application/json")
+                    .body(Body::from(request_body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
     }
 
     #[tokio::test]
-    async fn test_get_player_bets() {
-        let (app, state) = setup_test_app().await;
+    async fn test_demo_mode() {
+        let (_, state) = setup_test_app().await;
+        let app = create_app(AppState {
+            demo: Some(DemoConfig {
+                play_balance: 1_000_000,
+            }),
+            ..state.clone()
+        });
         let player_address = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let post = |uri: &str, body: String| {
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
 
-        // Create test bets
-        for i in 0..3 {
-            let bet = Bet {
-                id: format!("test_bet_{}", i),
-                player_address: player_address.to_string(),
-                amount: 1000,
-                guess: true,
-                result: false,
-                won: false,
-                payout: 0,
-                timestamp: Utc::now(),
-            };
-            state.db.save_bet(&bet).await.unwrap();
-        }
-
-        // Get player bets
+        // A new address shows up with a flagged play balance
         let response = app
+            .clone()
             .oneshot(
                 Request::builder()
-                    .uri(&format!("/v1/bets/{}", player_address))
+                    .uri(format!("/v1/balance/{}", player_address))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let balance: BalanceResponse = serde_json::from_slice(&body).unwrap();
+        assert!(balance.demo);
+        assert_eq!(balance.balance, 1_000_000);
+        assert_eq!(balance.total_deposited, 0);
 
+        // Demo bets update the play balance but are never queued for settlement
+        let bet = serde_json::to_string(&BetRequest {
+            player_address: player_address.to_string(),
+            amount: 100_000,
+            guess: true,
+            nonce: None,
+        })
+        .unwrap();
+        let response = app.clone().oneshot(post("/v1/bet", bet)).await.unwrap();
         assert_eq!(response.status(), StatusCode::OK);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_ne!(
+            state
+                .db
+                .get_player_balance(player_address)
+                .await
+                .unwrap()
+                .unwrap()
+                .balance,
+            1_000_000
+        );
+        assert_eq!(
+            state
+                .settlement_stats
+                .total_items_queued
+                .load(Ordering::Relaxed),
+            0
+        );
+
+        // Play money can't be withdrawn; the faucet tops it back up to the cap
+        let withdraw = serde_json::to_string(&WithdrawRequest {
+            player_address: player_address.to_string(),
+            amount: 50_000,
+        })
+        .unwrap();
+        let response = app
+            .clone()
+            .oneshot(post("/v1/withdraw", withdraw))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
+        let faucet = |address: &str| {
+            serde_json::to_string(&DemoFaucetRequest {
+                player_address: address.to_string(),
+            })
+            .unwrap()
+        };
+        let spent = state
+            .db
+            .update_player_balance_after_bet(player_address, 500_000, 0)
+            .await
+            .unwrap();
+        let response = app
+            .clone()
+            .oneshot(post("/v1/demo/faucet", faucet(player_address)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        let bets_response: BetsResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(bets_response.total_count, 3);
+        let topped_up: DemoFaucetResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(topped_up.balance.balance, 1_000_000);
+        assert_eq!(topped_up.granted, 1_000_000 - spent.balance as u64);
+
+        // Real balances are refused, and so is the faucet outside demo mode
+        let real_player = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+        state.db.deposit(real_player, 10_000).await.unwrap();
+        let response = app
+            .clone()
+            .oneshot(post("/v1/demo/faucet", faucet(real_player)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = create_app(state)
+            .oneshot(post("/v1/demo/faucet", faucet(player_address)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 
     #[test]
     fn test_args_parsing() {
-        let args = Args::parse_from(&[
+        let args = Args::parse_from([
             "sequencer",
             "--port",
             "8080",
@@ -1328,9 +6306,64 @@ mod tests {
         assert_eq!(args.port, 8080);
         assert_eq!(args.database_url, "sqlite:test.db");
 
-        let args = Args::parse_from(&["sequencer"]);
+        let args = Args::parse_from(["sequencer", "--strict-proofs"]);
+        assert!(args.strict_proofs);
+
+        let args = Args::parse_from(["sequencer"]);
+        assert!(!args.strict_proofs);
         assert_eq!(args.port, 3000); // default value
         assert_eq!(args.database_url, "sqlite:zkcasino.db"); // default value
+        assert_eq!(args.command, None);
+
+        let args = Args::parse_from(["sequencer", "restore", "backup.json", "--verify-only"]);
+        assert_eq!(
+            args.command,
+            Some(Command::Restore {
+                path: "backup.json".into(),
+                verify_only: true,
+            })
+        );
+
+        let args = Args::parse_from([
+            "sequencer",
+            "export",
+            "--from",
+            "3",
+            "--to",
+            "7",
+            "--out",
+            "drop",
+        ]);
+        assert_eq!(
+            args.command,
+            Some(Command::Export {
+                from: 3,
+                to: 7,
+                out: "drop".into(),
+            })
+        );
+
+        let args = Args::parse_from(["sequencer", "submitter"]);
+        assert_eq!(args.command, Some(Command::Submitter));
+
+        let args = Args::parse_from(["sequencer", "co-signer"]);
+        assert_eq!(args.command, Some(Command::CoSigner));
+
+        let args = Args::parse_from(["sequencer", "tune-batches"]);
+        assert_eq!(
+            args.command,
+            Some(Command::TuneBatches {
+                target_p95_ms: 30_000
+            })
+        );
+
+        let args = Args::parse_from(["sequencer", "keys", "--address", "player"]);
+        assert_eq!(
+            args.command,
+            Some(Command::Keys {
+                address: Some("player".to_string())
+            })
+        );
     }
 
     #[test]
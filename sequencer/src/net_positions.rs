@@ -0,0 +1,110 @@
+// Per-player net positions for ZK Casino settlement
+// A high-frequency player can place many bets that land in the same batch. Every bet
+// stays in the batch as its own record (persisted, and submitted on chain with its VRF
+// proof, since fairness is checked bet by bet), but the accounting circuit only needs
+// each player's net balance change. Netting a batch's bets per player before building
+// the witness means a batch needs one circuit bet per player rather than per bet, so
+// it is bounded by distinct players and can hold far more bets.
+
+use crate::SettlementItem;
+
+/// Net effect of one player's bets in a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetPosition {
+    pub player_address: String,
+    pub bet_ids: Vec<String>, // Bets netted into this position, in batch order
+    pub wagered: u64,
+    pub payout: u64,
+    pub delta: i64, // Balance change, summed the way the circuit applies each bet
+}
+
+impl NetPosition {
+    /// Won bets move the stake to the player, lost bets to the house
+    fn bet_delta(item: &SettlementItem) -> i64 {
+        let amount = item.amount.abs();
+        if item.payout > amount {
+            amount
+        } else {
+            -amount
+        }
+    }
+}
+
+/// Net the items of a batch per player, in order of each player's first bet.
+/// Batches never mix tokens, so the player alone identifies a position.
+pub fn net_positions(items: &[SettlementItem]) -> Vec<NetPosition> {
+    let mut positions: Vec<NetPosition> = Vec::new();
+    for item in items {
+        let index = match positions
+            .iter()
+            .position(|position| position.player_address == item.player_address)
+        {
+            Some(index) => index,
+            None => {
+                positions.push(NetPosition {
+                    player_address: item.player_address.clone(),
+                    bet_ids: Vec::new(),
+                    wagered: 0,
+                    payout: 0,
+                    delta: 0,
+                });
+                positions.len() - 1
+            }
+        };
+        let position = &mut positions[index];
+        position.bet_ids.push(item.bet_id.clone());
+        position.wagered += item.amount.unsigned_abs();
+        position.payout += item.payout.max(0) as u64;
+        position.delta += NetPosition::bet_delta(item);
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameKind, SettlementToken};
+    use chrono::Utc;
+
+    fn item(bet_id: &str, player: &str, amount: i64, payout: i64) -> SettlementItem {
+        SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: player.to_string(),
+            amount,
+            payout,
+            timestamp: Utc::now(),
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
+        }
+    }
+
+    #[test]
+    fn test_bets_net_per_player() {
+        let positions = net_positions(&[
+            item("bet1", "alice", -1000, 0),
+            item("bet2", "bob", 500, 1000),
+            item("bet3", "alice", 300, 600),
+            item("bet4", "alice", -200, 0),
+            item("bet5", "bob", -500, 0),
+        ]);
+
+        assert_eq!(positions.len(), 2);
+        assert_eq!(
+            positions[0],
+            NetPosition {
+                player_address: "alice".to_string(),
+                bet_ids: vec!["bet1".into(), "bet3".into(), "bet4".into()],
+                wagered: 1500,
+                payout: 600,
+                delta: -900,
+            }
+        );
+        // Offsetting bets net to no balance change
+        assert_eq!(positions[1].player_address, "bob");
+        assert_eq!(positions[1].bet_ids, vec!["bet2", "bet5"]);
+        assert_eq!(positions[1].delta, 0);
+
+        assert!(net_positions(&[]).is_empty());
+    }
+}
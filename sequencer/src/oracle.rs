@@ -50,6 +50,7 @@ impl Default for OracleConfig {
 
 // High-performance oracle client (VF Node pattern)
 #[derive(Clone)]
+#[allow(dead_code)]
 pub struct OracleClient {
     config: OracleConfig,
     client: reqwest::Client,
@@ -187,6 +188,7 @@ impl OracleManager {
     }
 
     // Get oracle client for on-demand requests
+    #[allow(dead_code)]
     pub fn client(&self) -> &OracleClient {
         &self.client
     }
@@ -0,0 +1,367 @@
+// VRF outcome distribution monitoring for ZK Casino
+// Keeps a rolling window of coinflip outcomes per VRF key and runs two tests on it: a
+// chi-square test of the heads/tails split and a Wald-Wolfowitz runs test for serial
+// dependence. A statistic beyond its threshold raises an alert (logged, kept for the
+// admin API and optionally posted to a webhook) once, until the window recovers. This
+// is an early warning for RNG bugs or key misuse, not a proof of fairness.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Alerts kept in memory for the admin API
+const MAX_RETAINED_ALERTS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct OutcomeMonitorConfig {
+    pub window: usize,      // Outcomes per key the tests run over
+    pub min_samples: usize, // No alerts until a key's window holds this many outcomes
+    pub chi_square_threshold: f64,
+    pub runs_z_threshold: f64,
+    pub webhook_url: Option<String>,
+}
+
+impl Default for OutcomeMonitorConfig {
+    fn default() -> Self {
+        Self {
+            window: 1000,
+            min_samples: 200,
+            chi_square_threshold: 10.83, // p = 0.001 at 1 degree of freedom
+            runs_z_threshold: 3.29,      // Two-sided p = 0.001
+            webhook_url: None,
+        }
+    }
+}
+
+impl OutcomeMonitorConfig {
+    /// Load from environment (OUTCOME_MONITOR_WINDOW, OUTCOME_MONITOR_MIN_SAMPLES,
+    /// OUTCOME_CHI_SQUARE_THRESHOLD, OUTCOME_RUNS_Z_THRESHOLD, OUTCOME_ALERT_WEBHOOK_URL)
+    pub fn from_env() -> Result<Self> {
+        fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T>
+        where
+            T::Err: std::fmt::Display,
+        {
+            match std::env::var(name) {
+                Ok(v) => v.parse().map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(default),
+            }
+        }
+
+        let defaults = Self::default();
+        let config = Self {
+            window: env_or("OUTCOME_MONITOR_WINDOW", defaults.window)?,
+            min_samples: env_or("OUTCOME_MONITOR_MIN_SAMPLES", defaults.min_samples)?,
+            chi_square_threshold: env_or(
+                "OUTCOME_CHI_SQUARE_THRESHOLD",
+                defaults.chi_square_threshold,
+            )?,
+            runs_z_threshold: env_or("OUTCOME_RUNS_Z_THRESHOLD", defaults.runs_z_threshold)?,
+            webhook_url: std::env::var("OUTCOME_ALERT_WEBHOOK_URL").ok(),
+        };
+        if config.min_samples < 2 || config.min_samples > config.window {
+            return Err(anyhow!(
+                "OUTCOME_MONITOR_MIN_SAMPLES must be between 2 and OUTCOME_MONITOR_WINDOW"
+            ));
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionTest {
+    ChiSquare, // Heads/tails split
+    Runs,      // Serial dependence between consecutive outcomes
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutcomeAlert {
+    pub vrf_key: String,
+    pub test: DistributionTest,
+    pub statistic: f64,
+    pub threshold: f64,
+    pub samples: usize,
+    pub raised_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyStats {
+    pub vrf_key: String,
+    pub samples: usize,
+    pub heads: usize,
+    pub chi_square: f64,
+    pub runs_z: f64,
+    pub deviating: Vec<DistributionTest>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutcomeMonitorSnapshot {
+    pub keys: Vec<KeyStats>,
+    pub alerts: Vec<OutcomeAlert>, // Most recent first
+}
+
+#[derive(Default)]
+struct KeyWindow {
+    outcomes: VecDeque<bool>,
+    deviating: Vec<DistributionTest>, // Tests currently beyond threshold; alerted once
+}
+
+/// Chi-square statistic of the heads/tails split against a fair coin (1 degree of freedom)
+pub fn chi_square(outcomes: &VecDeque<bool>) -> f64 {
+    let n = outcomes.len() as f64;
+    if n == 0.0 {
+        return 0.0;
+    }
+    let heads = outcomes.iter().filter(|&&heads| heads).count() as f64;
+    let tails = n - heads;
+    (heads - tails).powi(2) / n
+}
+
+/// Wald-Wolfowitz runs test z-score; 0 when every outcome is the same (the chi-square
+/// test catches that)
+pub fn runs_z(outcomes: &VecDeque<bool>) -> f64 {
+    let n = outcomes.len() as f64;
+    let n1 = outcomes.iter().filter(|&&heads| heads).count() as f64;
+    let n2 = n - n1;
+    if n1 == 0.0 || n2 == 0.0 {
+        return 0.0;
+    }
+    let runs = 1 + outcomes
+        .iter()
+        .zip(outcomes.iter().skip(1))
+        .filter(|(a, b)| a != b)
+        .count();
+    let mean = 2.0 * n1 * n2 / n + 1.0;
+    let variance = 2.0 * n1 * n2 * (2.0 * n1 * n2 - n) / (n * n * (n - 1.0));
+    if variance <= 0.0 {
+        return 0.0;
+    }
+    (runs as f64 - mean) / variance.sqrt()
+}
+
+pub struct OutcomeMonitor {
+    config: OutcomeMonitorConfig,
+    keys: Mutex<HashMap<String, KeyWindow>>,
+    alerts: Mutex<VecDeque<OutcomeAlert>>,
+    client: reqwest::Client,
+}
+
+impl OutcomeMonitor {
+    pub fn new(config: OutcomeMonitorConfig) -> Self {
+        Self {
+            config,
+            keys: Mutex::new(HashMap::new()),
+            alerts: Mutex::new(VecDeque::new()),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Record an outcome produced with `vrf_key`; returns any alerts it raised
+    pub fn record(&self, vrf_key: &Pubkey, heads: bool) -> Vec<OutcomeAlert> {
+        let vrf_key = vrf_key.to_string();
+        let raised = {
+            let mut keys = self.keys.lock();
+            let window = keys.entry(vrf_key.clone()).or_default();
+            window.outcomes.push_back(heads);
+            if window.outcomes.len() > self.config.window {
+                window.outcomes.pop_front();
+            }
+            self.evaluate(&vrf_key, window)
+        };
+
+        if !raised.is_empty() {
+            let mut alerts = self.alerts.lock();
+            for alert in &raised {
+                warn!(
+                    "VRF outcome distribution alert for key {}: {:?} statistic {:.2} exceeds {:.2} over {} outcomes",
+                    alert.vrf_key, alert.test, alert.statistic, alert.threshold, alert.samples
+                );
+                alerts.push_front(alert.clone());
+            }
+            alerts.truncate(MAX_RETAINED_ALERTS);
+            self.notify(&raised);
+        }
+        raised
+    }
+
+    /// Run both tests on a key's window, raising alerts for tests that just crossed
+    /// their threshold and clearing tests that recovered
+    fn evaluate(&self, vrf_key: &str, window: &mut KeyWindow) -> Vec<OutcomeAlert> {
+        let samples = window.outcomes.len();
+        if samples < self.config.min_samples {
+            return Vec::new();
+        }
+        let results = [
+            (
+                DistributionTest::ChiSquare,
+                chi_square(&window.outcomes),
+                self.config.chi_square_threshold,
+            ),
+            (
+                DistributionTest::Runs,
+                runs_z(&window.outcomes).abs(),
+                self.config.runs_z_threshold,
+            ),
+        ];
+
+        let mut raised = Vec::new();
+        for (test, statistic, threshold) in results {
+            let was_deviating = window.deviating.contains(&test);
+            if statistic > threshold && !was_deviating {
+                window.deviating.push(test);
+                raised.push(OutcomeAlert {
+                    vrf_key: vrf_key.to_string(),
+                    test,
+                    statistic,
+                    threshold,
+                    samples,
+                    raised_at: Utc::now(),
+                });
+            } else if statistic <= threshold && was_deviating {
+                window.deviating.retain(|t| *t != test);
+                info!(
+                    "VRF outcome distribution for key {} back within {:?} threshold",
+                    vrf_key, test
+                );
+            }
+        }
+        raised
+    }
+
+    /// Post alerts to the webhook, if configured, without blocking the caller
+    fn notify(&self, alerts: &[OutcomeAlert]) {
+        let Some(url) = self.config.webhook_url.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let request = self.client.post(url).json(alerts);
+        runtime.spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => warn!("Failed to deliver outcome distribution alert: {}", e),
+            }
+        });
+    }
+
+    pub fn snapshot(&self) -> OutcomeMonitorSnapshot {
+        let mut keys: Vec<KeyStats> = self
+            .keys
+            .lock()
+            .iter()
+            .map(|(vrf_key, window)| KeyStats {
+                vrf_key: vrf_key.clone(),
+                samples: window.outcomes.len(),
+                heads: window.outcomes.iter().filter(|&&heads| heads).count(),
+                chi_square: chi_square(&window.outcomes),
+                runs_z: runs_z(&window.outcomes),
+                deviating: window.deviating.clone(),
+            })
+            .collect();
+        keys.sort_by(|a, b| a.vrf_key.cmp(&b.vrf_key));
+        OutcomeMonitorSnapshot {
+            keys,
+            alerts: self.alerts.lock().iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic, well-mixed coin flips
+    fn fair_coin(i: u32) -> bool {
+        solana_sdk::hash::hash(&i.to_le_bytes()).to_bytes()[0] & 1 == 1
+    }
+
+    fn monitor() -> OutcomeMonitor {
+        OutcomeMonitor::new(OutcomeMonitorConfig {
+            window: 100,
+            min_samples: 50,
+            ..OutcomeMonitorConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_statistics() {
+        let alternating: VecDeque<bool> = (0..100).map(|i| i % 2 == 0).collect();
+        assert_eq!(chi_square(&alternating), 0.0);
+        // Perfect alternation has far too many runs
+        assert!(runs_z(&alternating) > 9.0);
+
+        let blocks: VecDeque<bool> = (0..100).map(|i| i < 50).collect();
+        assert_eq!(chi_square(&blocks), 0.0);
+        // Two long runs are far too few
+        assert!(runs_z(&blocks) < -9.0);
+
+        let biased: VecDeque<bool> = (0..100).map(|i| i % 4 != 0).collect();
+        assert_eq!(chi_square(&biased), 25.0);
+
+        assert_eq!(runs_z(&VecDeque::from(vec![true; 10])), 0.0);
+    }
+
+    #[test]
+    fn test_alerts_once_per_deviation() {
+        let monitor = monitor();
+        let key = Pubkey::new_unique();
+
+        // Below min_samples nothing is evaluated
+        for _ in 0..49 {
+            assert!(monitor.record(&key, true).is_empty());
+        }
+        let alerts = monitor.record(&key, true);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].test, DistributionTest::ChiSquare);
+        assert_eq!(alerts[0].vrf_key, key.to_string());
+
+        // Still deviating: no repeat alert
+        assert!(monitor.record(&key, true).is_empty());
+
+        // Other keys are tracked separately
+        let other = Pubkey::new_unique();
+        for i in 0u32..60 {
+            monitor.record(&other, fair_coin(i));
+        }
+
+        let snapshot = monitor.snapshot();
+        assert_eq!(snapshot.keys.len(), 2);
+        assert_eq!(snapshot.alerts.len(), 1);
+        let stats = snapshot
+            .keys
+            .iter()
+            .find(|k| k.vrf_key == key.to_string())
+            .unwrap();
+        assert_eq!(stats.samples, 51);
+        assert_eq!(stats.deviating, vec![DistributionTest::ChiSquare]);
+    }
+
+    #[test]
+    fn test_deviation_clears_and_can_alert_again() {
+        let monitor = monitor();
+        let key = Pubkey::new_unique();
+        for _ in 0..50 {
+            monitor.record(&key, true);
+        }
+        // Push the window back to a balanced, well-mixed sequence
+        for i in 0u32..100 {
+            monitor.record(&key, fair_coin(i));
+        }
+        assert!(monitor.snapshot().keys[0].deviating.is_empty());
+
+        let chi_square_alerts = (0..100)
+            .flat_map(|_| monitor.record(&key, false))
+            .filter(|alert| alert.test == DistributionTest::ChiSquare)
+            .count();
+        assert_eq!(chi_square_alerts, 1);
+    }
+}
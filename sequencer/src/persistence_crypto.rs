@@ -0,0 +1,216 @@
+// Encryption at rest for settlement persistence
+// Settlement files hold the full financial history, so they can optionally be sealed
+// with AES-256-GCM. Files are wrapped in a JSON envelope naming the key that sealed
+// them; several keys can be configured so old files stay readable after rotation.
+//
+// Keys come from SETTLEMENT_ENCRYPTION_KEYS ("id:base64key,..." - first is active)
+// or SETTLEMENT_ENCRYPTION_KEYS_FILE (same format, e.g. mounted by a KMS/secret manager).
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+const ALGORITHM: &str = "aes-256-gcm";
+const AAD_PREFIX: &[u8] = b"zkcasino-settlement:";
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedEnvelope {
+    algorithm: String,
+    key_id: String,
+    nonce: String,      // base64, 12 bytes
+    ciphertext: String, // base64, includes the GCM tag
+}
+
+struct EncryptionKey {
+    id: String,
+    cipher: Aes256Gcm,
+}
+
+/// AES-256-GCM sealing for persistence files with support for key rotation
+pub struct PersistenceCipher {
+    keys: Vec<EncryptionKey>, // keys[0] encrypts; all keys decrypt
+}
+
+impl PersistenceCipher {
+    /// Parse "id:base64key,id:base64key"; the first key is used for new writes
+    pub fn from_key_list(spec: &str) -> Result<Self> {
+        let keys = spec
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (id, key) = entry
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Encryption key entry must be 'id:base64key'"))?;
+                let bytes = BASE64
+                    .decode(key.trim())
+                    .map_err(|e| anyhow!("Encryption key '{}' is not valid base64: {}", id, e))?;
+                let cipher = Aes256Gcm::new_from_slice(&bytes)
+                    .map_err(|_| anyhow!("Encryption key '{}' must be 32 bytes", id))?;
+                Ok(EncryptionKey {
+                    id: id.trim().to_string(),
+                    cipher,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if keys.is_empty() {
+            return Err(anyhow!("No encryption keys configured"));
+        }
+        Ok(Self { keys })
+    }
+
+    /// Load keys from environment; None when encryption at rest is not configured
+    pub fn from_env() -> Result<Option<Self>> {
+        let spec = match (
+            std::env::var("SETTLEMENT_ENCRYPTION_KEYS"),
+            std::env::var("SETTLEMENT_ENCRYPTION_KEYS_FILE"),
+        ) {
+            (Ok(spec), _) => spec,
+            (Err(_), Ok(path)) => std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read encryption keys from {}: {}", path, e))?,
+            (Err(_), Err(_)) => {
+                info!("Settlement persistence encryption disabled. Set SETTLEMENT_ENCRYPTION_KEYS to enable.");
+                return Ok(None);
+            }
+        };
+
+        let cipher = Self::from_key_list(&spec)?;
+        info!(
+            "Settlement persistence encryption enabled (active key: {}, {} keys loaded)",
+            cipher.active_key_id(),
+            cipher.keys.len()
+        );
+        Ok(Some(cipher))
+    }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.keys[0].id
+    }
+
+    fn aad(key_id: &str) -> Vec<u8> {
+        [AAD_PREFIX, key_id.as_bytes()].concat()
+    }
+
+    /// Seal data with the active key into a JSON envelope
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+        let key = &self.keys[0];
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &Self::aad(&key.id),
+                },
+            )
+            .map_err(|_| anyhow!("Encryption failed"))?;
+
+        Ok(serde_json::to_string_pretty(&EncryptedEnvelope {
+            algorithm: ALGORITHM.to_string(),
+            key_id: key.id.clone(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })?)
+    }
+
+    /// Open an envelope, verifying its integrity; returns the plaintext and the sealing key ID
+    pub fn decrypt(&self, envelope: &str) -> Result<(Vec<u8>, String)> {
+        let envelope: EncryptedEnvelope = serde_json::from_str(envelope)
+            .map_err(|e| anyhow!("Invalid encrypted envelope: {}", e))?;
+        if envelope.algorithm != ALGORITHM {
+            return Err(anyhow!(
+                "Unsupported encryption algorithm: {}",
+                envelope.algorithm
+            ));
+        }
+
+        let key = self
+            .keys
+            .iter()
+            .find(|k| k.id == envelope.key_id)
+            .ok_or_else(|| anyhow!("Encryption key '{}' is not configured", envelope.key_id))?;
+        let nonce: [u8; 12] = BASE64
+            .decode(&envelope.nonce)
+            .ok()
+            .and_then(|n| n.try_into().ok())
+            .ok_or_else(|| anyhow!("Invalid nonce in encrypted envelope"))?;
+        let ciphertext = BASE64
+            .decode(&envelope.ciphertext)
+            .map_err(|e| anyhow!("Invalid ciphertext encoding: {}", e))?;
+
+        let plaintext = key
+            .cipher
+            .decrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &Self::aad(&key.id),
+                },
+            )
+            .map_err(|_| {
+                anyhow!(
+                    "Integrity check failed: data was tampered with or sealed by a different key '{}'",
+                    key.id
+                )
+            })?;
+        Ok((plaintext, envelope.key_id))
+    }
+}
+
+/// Whether file contents are an encrypted envelope (vs plaintext settlement JSON)
+pub fn is_encrypted(contents: &str) -> bool {
+    serde_json::from_str::<EncryptedEnvelope>(contents).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        BASE64.encode([byte; 32])
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_and_rotation() {
+        let old = PersistenceCipher::from_key_list(&format!("k1:{}", key(1))).unwrap();
+        let sealed = old.encrypt(b"settlement history").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!is_encrypted(r#"{"version": 1}"#));
+
+        // After rotation the new key encrypts, the old key still decrypts
+        let rotated =
+            PersistenceCipher::from_key_list(&format!("k2:{},k1:{}", key(2), key(1))).unwrap();
+        assert_eq!(rotated.active_key_id(), "k2");
+        let (plaintext, key_id) = rotated.decrypt(&sealed).unwrap();
+        assert_eq!(plaintext, b"settlement history");
+        assert_eq!(key_id, "k1");
+
+        // Files sealed with a removed key cannot be opened
+        let only_new = PersistenceCipher::from_key_list(&format!("k2:{}", key(2))).unwrap();
+        assert!(only_new.decrypt(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let cipher = PersistenceCipher::from_key_list(&format!("k1:{}", key(1))).unwrap();
+        let sealed = cipher.encrypt(b"balance: 100").unwrap();
+
+        let mut envelope: EncryptedEnvelope = serde_json::from_str(&sealed).unwrap();
+        let mut ciphertext = BASE64.decode(&envelope.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        envelope.ciphertext = BASE64.encode(ciphertext);
+        let tampered = serde_json::to_string(&envelope).unwrap();
+
+        let err = cipher.decrypt(&tampered).unwrap_err();
+        assert!(err.to_string().contains("Integrity check failed"));
+
+        assert!(PersistenceCipher::from_key_list("k1:c2hvcnQ=").is_err());
+        assert!(PersistenceCipher::from_key_list("").is_err());
+    }
+}
@@ -0,0 +1,371 @@
+// Content-addressed proof cache for ZK Casino
+// When a batch with identical contents is proved again (a retry, or a replay after a
+// crash), the cached Groth16 proof is reused instead of recomputed. Entries are keyed
+// by a canonical hash of the circuit inputs plus the verifying key, so proofs made
+// with different keys never collide. Optionally mirrored to disk to survive restarts.
+
+use parking_lot::Mutex;
+use prover::{
+    proof_generator::SerializableProof, state_tree::root_bytes, witness_generator::SettlementBatch,
+};
+use serde::Serialize;
+use solana_sdk::hash::{Hash, Hasher};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{info, warn};
+
+const PROOF_FILE_EXTENSION: &str = "proof";
+
+#[derive(Debug, Clone)]
+pub struct ProofCacheConfig {
+    pub max_entries: usize,   // 0 disables the cache
+    pub dir: Option<PathBuf>, // Persist proofs here so they survive restarts
+}
+
+impl Default for ProofCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 256,
+            dir: None,
+        }
+    }
+}
+
+impl ProofCacheConfig {
+    /// Load from environment (PROOF_CACHE_MAX_ENTRIES, PROOF_CACHE_DIR)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_entries: std::env::var("PROOF_CACHE_MAX_ENTRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_entries),
+            dir: std::env::var("PROOF_CACHE_DIR").ok().map(PathBuf::from),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProofCacheStats {
+    pub entries: usize,
+    pub max_entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Canonical hash of everything the circuit proves over.
+///
+/// The batch timestamp is not a circuit input and is left out. The players' leaf
+/// updates are hashed without their paths, which the starting root already fixes.
+pub fn batch_content_hash(namespace: &Hash, batch: &SettlementBatch) -> Hash {
+    let mut hasher = Hasher::default();
+    hasher.hash(namespace.as_ref());
+    hasher.hash(&batch.batch_id.to_le_bytes());
+    hasher.hash(&batch.house_initial_balance.to_le_bytes());
+
+    hasher.hash(&root_bytes(batch.prev_state_root));
+    hasher.hash(&(batch.players.len() as u64).to_le_bytes());
+    for player in &batch.players {
+        hasher.hash(&player.key);
+        hasher.hash(&player.index.to_le_bytes());
+        match player.initial_balance {
+            Some(balance) => hasher.hash(&[&[1][..], &balance.to_le_bytes()].concat()),
+            None => hasher.hash(&[0]),
+        }
+        hasher.hash(&player.final_balance.to_le_bytes());
+    }
+
+    hasher.hash(&(batch.bets.len() as u64).to_le_bytes());
+    for bet in &batch.bets {
+        hasher.hash(&bet.user_id.to_le_bytes());
+        hasher.hash(&bet.amount.to_le_bytes());
+        hasher.hash(&[bet.guess as u8, bet.outcome as u8]);
+        hasher.hash(&(bet.bet_id.len() as u64).to_le_bytes());
+        hasher.hash(bet.bet_id.as_bytes());
+    }
+
+    hasher.hash(&(batch.records.len() as u64).to_le_bytes());
+    for record in &batch.records {
+        hasher.hash(&record.bet_id.to_le_bytes());
+        hasher.hash(&record.user);
+        hasher.hash(&record.bet_amount.to_le_bytes());
+        hasher.hash(&[record.user_guess, record.outcome]);
+        hasher.hash(&record.payout.to_le_bytes());
+    }
+    hasher.result()
+}
+
+#[derive(Default)]
+struct Entries {
+    proofs: HashMap<Hash, SerializableProof>,
+    order: VecDeque<Hash>, // Least recently used first
+}
+
+impl Entries {
+    fn touch(&mut self, key: &Hash) {
+        if let Some(position) = self.order.iter().position(|k| k == key) {
+            self.order.remove(position);
+        }
+        self.order.push_back(*key);
+    }
+}
+
+/// Bounded LRU cache of proofs by batch content hash
+pub struct ProofCache {
+    config: ProofCacheConfig,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ProofCache {
+    /// Create the cache, loading any proofs previously persisted to `config.dir`
+    pub fn new(config: ProofCacheConfig) -> Self {
+        let cache = Self {
+            config,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        };
+        if let Some(dir) = cache.config.dir.clone() {
+            if let Err(e) = cache.load_dir(&dir) {
+                warn!("Failed to load proof cache from {}: {}", dir.display(), e);
+            }
+        }
+        cache
+    }
+
+    fn load_dir(&self, dir: &PathBuf) -> anyhow::Result<()> {
+        std::fs::create_dir_all(dir)?;
+
+        // Oldest first so the most recent proofs survive the size limit
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(PROOF_FILE_EXTENSION) {
+                continue;
+            }
+            let modified = std::fs::metadata(&path)?.modified()?;
+            files.push((modified, path));
+        }
+        files.sort();
+
+        let mut loaded = 0;
+        for (_, path) in files {
+            let key = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| Hash::from_str(s).ok());
+            let proof = std::fs::read(&path)
+                .ok()
+                .and_then(|bytes| SerializableProof::from_bytes(&bytes).ok());
+            match (key, proof) {
+                (Some(key), Some(proof)) => {
+                    self.insert_entry(key, proof);
+                    loaded += 1;
+                }
+                _ => warn!("Ignoring unreadable proof cache file {}", path.display()),
+            }
+        }
+        if loaded > 0 {
+            info!("Loaded {} cached proofs from {}", loaded, dir.display());
+        }
+        Ok(())
+    }
+
+    fn proof_path(&self, key: &Hash) -> Option<PathBuf> {
+        self.config
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.{}", key, PROOF_FILE_EXTENSION)))
+    }
+
+    /// Insert into memory, evicting the least recently used entries over the limit
+    fn insert_entry(&self, key: Hash, proof: SerializableProof) {
+        let mut entries = self.entries.lock();
+        entries.proofs.insert(key, proof);
+        entries.touch(&key);
+        while entries.order.len() > self.config.max_entries {
+            let Some(evicted) = entries.order.pop_front() else {
+                break;
+            };
+            entries.proofs.remove(&evicted);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            if let Some(path) = self.proof_path(&evicted) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    pub fn get(&self, key: &Hash) -> Option<SerializableProof> {
+        if self.config.max_entries == 0 {
+            return None;
+        }
+        let mut entries = self.entries.lock();
+        match entries.proofs.get(key).cloned() {
+            Some(proof) => {
+                entries.touch(key);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(proof)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: Hash, proof: &SerializableProof) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+        if let Some(path) = self.proof_path(&key) {
+            // The in-memory copy is still useful if persisting fails
+            match proof.to_bytes() {
+                Ok(bytes) => {
+                    if let Err(e) = std::fs::write(&path, bytes) {
+                        warn!("Failed to persist proof to {}: {}", path.display(), e);
+                    }
+                }
+                Err(e) => warn!("Failed to serialize proof for cache: {}", e),
+            }
+        }
+        self.insert_entry(key, proof.clone());
+    }
+
+    pub fn stats(&self) -> ProofCacheStats {
+        ProofCacheStats {
+            entries: self.entries.lock().proofs.len(),
+            max_entries: self.config.max_entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prover::state_tree::{user_key, StateTree};
+    use prover::witness_generator::SettlementBet;
+    use prover::{BetRecord, Bn254, Fr, Proof};
+
+    fn batch(batch_id: u32, bets: &[(&str, u64)]) -> SettlementBatch {
+        let mut tree = StateTree::new();
+        tree.update(user_key(0), 10_000).unwrap();
+        let prev_state_root = tree.root();
+        SettlementBatch {
+            batch_id,
+            bets: bets
+                .iter()
+                .map(|(id, amount)| SettlementBet::new(0, *amount, true, false, id.to_string()))
+                .collect(),
+            players: vec![tree.update(user_key(0), 9_700).unwrap()],
+            prev_state_root,
+            house_initial_balance: 1_000_000,
+            timestamp: 1,
+            records: Vec::new(),
+        }
+    }
+
+    fn proof(batch_id: u32) -> SerializableProof {
+        SerializableProof::new(Proof::<Bn254>::default(), vec![Fr::from(1u64)], batch_id)
+    }
+
+    #[test]
+    fn test_batch_content_hash_is_canonical() {
+        let namespace = Hash::new_unique();
+        let a = batch(1, &[("bet1", 100), ("bet2", 200)]);
+
+        // Timestamp is not a circuit input
+        let mut retried = a.clone();
+        retried.timestamp = 999;
+        assert_eq!(
+            batch_content_hash(&namespace, &a),
+            batch_content_hash(&namespace, &retried)
+        );
+
+        // The committed bet records are circuit inputs too
+        let mut with_records = a.clone();
+        with_records.records.push(BetRecord::default());
+
+        // So are the state the batch starts from and where it leaves the players
+        let mut other_state = a.clone();
+        other_state.prev_state_root = a.players[0].roots().1;
+        let mut other_update = a.clone();
+        other_update.players[0].final_balance += 1;
+
+        for different in [
+            batch(2, &[("bet1", 100), ("bet2", 200)]),
+            batch(1, &[("bet2", 200), ("bet1", 100)]),
+            batch(1, &[("bet1", 100), ("bet2", 201)]),
+            with_records,
+            other_state,
+            other_update,
+        ] {
+            assert_ne!(
+                batch_content_hash(&namespace, &a),
+                batch_content_hash(&namespace, &different)
+            );
+        }
+        // Proofs under other verifying keys never match
+        assert_ne!(
+            batch_content_hash(&namespace, &a),
+            batch_content_hash(&Hash::new_unique(), &a)
+        );
+    }
+
+    #[test]
+    fn test_lru_eviction_and_metrics() {
+        let cache = ProofCache::new(ProofCacheConfig {
+            max_entries: 2,
+            dir: None,
+        });
+        let (a, b, c) = (Hash::new_unique(), Hash::new_unique(), Hash::new_unique());
+
+        assert!(cache.get(&a).is_none());
+        cache.insert(a, &proof(1));
+        cache.insert(b, &proof(2));
+        assert_eq!(cache.get(&a).unwrap().batch_id, 1); // a is now most recent
+        cache.insert(c, &proof(3));
+
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&c).is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.evictions, 1);
+    }
+
+    #[test]
+    fn test_persisted_proofs_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("proof_cache_{}", uuid::Uuid::new_v4()));
+        let config = ProofCacheConfig {
+            max_entries: 2,
+            dir: Some(dir.clone()),
+        };
+        let (a, b, c) = (Hash::new_unique(), Hash::new_unique(), Hash::new_unique());
+
+        let cache = ProofCache::new(config.clone());
+        cache.insert(a, &proof(1));
+        cache.insert(b, &proof(2));
+        cache.insert(c, &proof(3)); // Evicts a from disk too
+        drop(cache);
+
+        let restarted = ProofCache::new(config);
+        assert!(restarted.get(&a).is_none());
+        assert_eq!(restarted.get(&b).unwrap().batch_id, 2);
+        assert_eq!(restarted.get(&c).unwrap().batch_id, 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+// Prover resource isolation for ZK Casino
+// Groth16 proving uses every core it can reach: arkworks parallelises on rayon, whose
+// global pool spans the whole machine, so on a shared host a 100-bet proof competes with
+// the API workers and bet latency spikes. Proving runs instead on a dedicated pool whose
+// threads can be pinned to a CPU subset (PROVER_CPUS, e.g. "4-7" or "2,3,6") and run at
+// lower scheduling priority (PROVER_NICE, 0-19), with PROVER_THREADS capping its size.
+// Pinning and priority use Linux scheduler calls; on other platforms they are skipped.
+
+use anyhow::{anyhow, Result};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+/// Highest nice value; lower priorities than this don't exist
+const MAX_NICE: i32 = 19;
+
+#[derive(Debug, Clone, Default)]
+pub struct ProverPoolConfig {
+    pub cpus: Option<Vec<usize>>, // Pin proving threads to these CPUs
+    pub nice: Option<i32>,        // Nice value for proving threads; higher yields more
+    pub threads: Option<usize>,   // Defaults to one per pinned CPU, else one per core
+}
+
+impl ProverPoolConfig {
+    /// Load from environment (PROVER_CPUS, PROVER_NICE, PROVER_THREADS)
+    pub fn from_env() -> Result<Self> {
+        let cpus = std::env::var("PROVER_CPUS")
+            .ok()
+            .map(|list| parse_cpu_list(&list))
+            .transpose()?;
+        let nice = std::env::var("PROVER_NICE")
+            .ok()
+            .map(|v| {
+                v.parse::<i32>()
+                    .ok()
+                    .filter(|nice| (0..=MAX_NICE).contains(nice))
+                    .ok_or_else(|| anyhow!("Invalid PROVER_NICE {}: expected 0-{}", v, MAX_NICE))
+            })
+            .transpose()?;
+        let threads = std::env::var("PROVER_THREADS")
+            .ok()
+            .map(|v| {
+                v.parse::<usize>()
+                    .ok()
+                    .filter(|&threads| threads > 0)
+                    .ok_or_else(|| anyhow!("Invalid PROVER_THREADS {}", v))
+            })
+            .transpose()?;
+        Ok(Self {
+            cpus,
+            nice,
+            threads,
+        })
+    }
+
+    fn thread_count(&self) -> usize {
+        self.threads.unwrap_or_else(|| match &self.cpus {
+            Some(cpus) => cpus.len(),
+            None => num_cpus::get(),
+        })
+    }
+}
+
+/// Parse a CPU list such as "0-3,6"
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>> {
+    let invalid = || anyhow!("Invalid PROVER_CPUS {}: expected e.g. 0-3,6", list);
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+        let first: usize = first.parse().map_err(|_| invalid())?;
+        let last: usize = last.parse().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    if cpus.is_empty() {
+        return Err(invalid());
+    }
+    Ok(cpus)
+}
+
+/// Pin the calling thread and lower its priority
+#[cfg(target_os = "linux")]
+fn isolate_current_thread(cpus: Option<&[usize]>, nice: Option<i32>) -> std::io::Result<()> {
+    if let Some(cpus) = cpus {
+        // SAFETY: cpu_set_t is plain data, and CPU_SET bounds-checks the index
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in cpus {
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        // Thread id 0 is the calling thread
+        if unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    if let Some(nice) = nice {
+        // On Linux, priority is per thread when given a thread id
+        let tid = unsafe { libc::gettid() };
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn isolate_current_thread(cpus: Option<&[usize]>, nice: Option<i32>) -> std::io::Result<()> {
+    if cpus.is_some() || nice.is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "CPU pinning and thread priority are only supported on Linux",
+        ));
+    }
+    Ok(())
+}
+
+/// Thread pool the prover's CPU-bound work runs on, off the async runtime
+pub struct ProverPool {
+    pool: rayon::ThreadPool,
+}
+
+impl ProverPool {
+    pub fn new(config: &ProverPoolConfig) -> Result<Self> {
+        let threads = config.thread_count();
+        if let Some(cpus) = &config.cpus {
+            let available = num_cpus::get();
+            if let Some(cpu) = cpus.iter().find(|&&cpu| cpu >= available) {
+                return Err(anyhow!(
+                    "PROVER_CPUS includes CPU {}, but this host has {} CPUs",
+                    cpu,
+                    available
+                ));
+            }
+        }
+
+        let (cpus, nice) = (config.cpus.clone(), config.nice);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("prover-{}", index))
+            .start_handler(move |index| {
+                if let Err(e) = isolate_current_thread(cpus.as_deref(), nice) {
+                    warn!("Prover thread {} runs unisolated: {}", index, e);
+                }
+            })
+            .panic_handler(|_| error!("Prover job panicked"))
+            .build()?;
+        info!(
+            "Prover pool: {} threads, CPUs {:?}, nice {:?}",
+            threads, config.cpus, config.nice
+        );
+        Ok(Self { pool })
+    }
+
+    /// Run CPU-bound work on the pool; parallel work inside it stays on the pool
+    pub async fn run<T: Send + 'static>(
+        &self,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T> {
+        let (result, receiver) = oneshot::channel();
+        self.pool.spawn(move || {
+            let _ = result.send(work());
+        });
+        receiver
+            .await
+            .map_err(|_| anyhow!("Prover job did not complete"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,6").unwrap(), vec![0, 1, 2, 3, 6]);
+        assert_eq!(parse_cpu_list(" 2 , 1,2 ").unwrap(), vec![1, 2]);
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        assert!(parse_cpu_list("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pool_runs_parallel_work_on_its_threads() {
+        use rayon::prelude::*;
+
+        let pool = ProverPool::new(&ProverPoolConfig {
+            nice: cfg!(target_os = "linux").then_some(MAX_NICE),
+            threads: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        let names = pool
+            .run(|| {
+                (0..64)
+                    .into_par_iter()
+                    .map(|_| std::thread::current().name().map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap();
+        assert!(names
+            .iter()
+            .all(|name| name.as_deref().is_some_and(|n| n.starts_with("prover-"))));
+
+        // A panicking job fails instead of hanging the caller
+        assert!(pool.run(|| -> u8 { panic!("boom") }).await.is_err());
+    }
+}
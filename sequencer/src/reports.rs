@@ -0,0 +1,421 @@
+// Fairness reporting module for ZK Casino
+// Produces signed periodic reports (RTP per game, outcome distribution, settlement latency)
+// for compliance and player trust. Reports are generated on a schedule and served via the API.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{error, info};
+
+use crate::database::{Bet, Database};
+use crate::settlement_persistence::SettlementPersistence;
+
+/// Chi-square critical value for 1 degree of freedom at alpha = 0.05
+pub const CHI_SQUARE_CRITICAL_1DF: f64 = 3.841;
+
+/// Theoretical RTP for the 2x coin flip (50% win chance * 2x payout)
+pub const COINFLIP_THEORETICAL_RTP: f64 = 1.0;
+
+// Report scheduling configuration
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    /// How often a report is generated (also the length of each report period)
+    pub interval: Duration,
+    /// Maximum number of reports retained in memory
+    pub max_retained: usize,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(3600), // Hourly reports
+            max_retained: 24 * 30,               // 30 days of hourly reports
+        }
+    }
+}
+
+impl ReportConfig {
+    /// Load configuration from environment (REPORT_INTERVAL_SECS)
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(secs) = std::env::var("REPORT_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&secs| secs > 0)
+        {
+            config.interval = Duration::from_secs(secs);
+        }
+        config
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameRtpReport {
+    pub game: String,
+    pub bets: u64,
+    pub total_wagered: u64,
+    pub total_paid_out: u64,
+    pub actual_rtp: f64,
+    pub theoretical_rtp: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutcomeDistribution {
+    pub samples: u64,
+    pub heads: u64,
+    pub tails: u64,
+    pub chi_square: f64,
+    pub critical_value: f64,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SettlementLatency {
+    pub samples: u64,
+    pub mean_ms: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Report contents covered by the signature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReportBody {
+    pub report_id: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub games: Vec<GameRtpReport>,
+    pub outcome_distribution: OutcomeDistribution,
+    pub settlement_latency: SettlementLatency,
+}
+
+/// Signed fairness report (ed25519 over the JSON encoding of the body)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FairnessReport {
+    #[serde(flatten)]
+    pub body: ReportBody,
+    pub signer: String,
+    pub signature: String,
+}
+
+impl FairnessReport {
+    /// Sign a report body with the sequencer key
+    pub fn sign(body: ReportBody, signer: &Keypair) -> Result<Self> {
+        let message = serde_json::to_vec(&body)?;
+        let signature = signer.sign_message(&message);
+        Ok(Self {
+            body,
+            signer: signer.pubkey().to_string(),
+            signature: signature.to_string(),
+        })
+    }
+
+    /// Check the signature against the embedded signer pubkey
+    pub fn verify_signature(&self) -> Result<bool> {
+        let signer =
+            Pubkey::from_str(&self.signer).map_err(|e| anyhow!("Invalid signer: {}", e))?;
+        let signature = Signature::from_str(&self.signature)
+            .map_err(|e| anyhow!("Invalid signature: {}", e))?;
+        let message = serde_json::to_vec(&self.body)?;
+        Ok(signature.verify(signer.as_ref(), &message))
+    }
+}
+
+/// Compute the chi-square statistic for heads/tails against a fair 50/50 split
+pub fn coinflip_chi_square(heads: u64, tails: u64) -> f64 {
+    let samples = heads + tails;
+    if samples == 0 {
+        return 0.0;
+    }
+    let expected = samples as f64 / 2.0;
+    let heads_dev = heads as f64 - expected;
+    let tails_dev = tails as f64 - expected;
+    (heads_dev * heads_dev + tails_dev * tails_dev) / expected
+}
+
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let idx = ((sorted.len() - 1) * pct) / 100;
+    sorted[idx]
+}
+
+fn build_coinflip_rtp(bets: &[Bet]) -> GameRtpReport {
+    let total_wagered: u64 = bets.iter().map(|b| b.amount.max(0) as u64).sum();
+    let total_paid_out: u64 = bets.iter().map(|b| b.payout.max(0) as u64).sum();
+    let actual_rtp = if total_wagered > 0 {
+        total_paid_out as f64 / total_wagered as f64
+    } else {
+        0.0
+    };
+
+    GameRtpReport {
+        game: "coinflip".to_string(),
+        bets: bets.len() as u64,
+        total_wagered,
+        total_paid_out,
+        actual_rtp,
+        theoretical_rtp: COINFLIP_THEORETICAL_RTP,
+    }
+}
+
+fn build_outcome_distribution(bets: &[Bet]) -> OutcomeDistribution {
+    let heads = bets.iter().filter(|b| b.result).count() as u64;
+    let tails = bets.len() as u64 - heads;
+    let chi_square = coinflip_chi_square(heads, tails);
+
+    OutcomeDistribution {
+        samples: heads + tails,
+        heads,
+        tails,
+        chi_square,
+        critical_value: CHI_SQUARE_CRITICAL_1DF,
+        passed: chi_square <= CHI_SQUARE_CRITICAL_1DF,
+    }
+}
+
+fn build_settlement_latency(mut latencies_ms: Vec<u64>) -> SettlementLatency {
+    latencies_ms.sort_unstable();
+    let samples = latencies_ms.len() as u64;
+    let mean_ms = latencies_ms
+        .iter()
+        .sum::<u64>()
+        .checked_div(samples)
+        .unwrap_or(0);
+
+    SettlementLatency {
+        samples,
+        mean_ms,
+        p50_ms: percentile(&latencies_ms, 50),
+        p95_ms: percentile(&latencies_ms, 95),
+        max_ms: latencies_ms.last().copied().unwrap_or(0),
+    }
+}
+
+/// Generates, signs and stores fairness reports
+pub struct ReportService {
+    config: ReportConfig,
+    db: Arc<Database>,
+    settlement_persistence: Arc<SettlementPersistence>,
+    signer: Arc<Keypair>,
+    reports: RwLock<Vec<FairnessReport>>,
+}
+
+impl ReportService {
+    pub fn new(
+        config: ReportConfig,
+        db: Arc<Database>,
+        settlement_persistence: Arc<SettlementPersistence>,
+        signer: Arc<Keypair>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            settlement_persistence,
+            signer,
+            reports: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Generate, sign and store a report for the period [start, end)
+    pub async fn generate_report(
+        &self,
+        period_start: DateTime<Utc>,
+        period_end: DateTime<Utc>,
+    ) -> Result<FairnessReport> {
+        let mut bets = self
+            .db
+            .get_bets_between(period_start, period_end)
+            .await
+            .map_err(|e| anyhow!("Failed to load bets: {}", e))?;
+        // Demo play moves no real funds and never settles; it doesn't count
+        bets.retain(|bet| !self.db.is_demo_player(&bet.player_address));
+
+        // Settlement latency: bet timestamp -> batch confirmation
+        let batches = self
+            .settlement_persistence
+            .get_confirmed_batches_between(period_start, period_end)
+            .await?;
+        let latencies_ms: Vec<u64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch.items.iter().map(move |item| {
+                    (batch.updated_at - item.timestamp)
+                        .num_milliseconds()
+                        .max(0) as u64
+                })
+            })
+            .collect();
+
+        let body = ReportBody {
+            report_id: format!("report_{}", period_end.timestamp()),
+            period_start,
+            period_end,
+            generated_at: Utc::now(),
+            games: vec![build_coinflip_rtp(&bets)],
+            outcome_distribution: build_outcome_distribution(&bets),
+            settlement_latency: build_settlement_latency(latencies_ms),
+        };
+
+        let report = FairnessReport::sign(body, &self.signer)?;
+
+        let mut reports = self.reports.write();
+        reports.push(report.clone());
+        if reports.len() > self.config.max_retained {
+            let excess = reports.len() - self.config.max_retained;
+            reports.drain(..excess);
+        }
+
+        Ok(report)
+    }
+
+    /// All stored reports, newest first
+    pub fn list_reports(&self) -> Vec<FairnessReport> {
+        self.reports.read().iter().rev().cloned().collect()
+    }
+
+    pub fn get_report(&self, report_id: &str) -> Option<FairnessReport> {
+        self.reports
+            .read()
+            .iter()
+            .find(|r| r.body.report_id == report_id)
+            .cloned()
+    }
+
+    /// Start the periodic report job (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting fairness report service (interval: {:?})",
+            self.config.interval
+        );
+
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.interval);
+            interval.tick().await; // First tick completes immediately
+            let mut period_start = Utc::now();
+
+            loop {
+                interval.tick().await;
+                let period_end = Utc::now();
+
+                match self.generate_report(period_start, period_end).await {
+                    Ok(report) => {
+                        info!(
+                            "Fairness report {} generated: {} bets, chi-square {:.3}",
+                            report.body.report_id,
+                            report.body.outcome_distribution.samples,
+                            report.body.outcome_distribution.chi_square
+                        );
+                    }
+                    Err(e) => {
+                        error!("Failed to generate fairness report: {}", e);
+                    }
+                }
+
+                period_start = period_end;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+
+    async fn setup_service() -> (ReportService, Arc<Database>) {
+        let db = Arc::new(Database::new("").await.unwrap());
+        let settlement_persistence =
+            Arc::new(SettlementPersistence::new("sqlite::memory:").await.unwrap());
+        let service = ReportService::new(
+            ReportConfig::default(),
+            db.clone(),
+            settlement_persistence,
+            Arc::new(Keypair::new()),
+        );
+        (service, db)
+    }
+
+    #[test]
+    fn test_coinflip_chi_square() {
+        assert_eq!(coinflip_chi_square(0, 0), 0.0);
+        assert_eq!(coinflip_chi_square(50, 50), 0.0);
+
+        // 60/40 over 100 samples: (10^2 + 10^2) / 50 = 4.0
+        let chi = coinflip_chi_square(60, 40);
+        assert!((chi - 4.0).abs() < f64::EPSILON);
+        assert!(chi > CHI_SQUARE_CRITICAL_1DF);
+    }
+
+    #[tokio::test]
+    async fn test_generate_signed_report() {
+        let (service, db) = setup_service().await;
+        let start = Utc::now() - ChronoDuration::minutes(1);
+
+        for i in 0..4 {
+            let won = i % 2 == 0;
+            db.save_bet(&Bet {
+                id: format!("bet_{}", i),
+                player_address: "player".to_string(),
+                amount: 1000,
+                guess: true,
+                result: won,
+                won,
+                payout: if won { 2000 } else { 0 },
+                timestamp: Utc::now(),
+                settlement: None,
+                escrow: None,
+            })
+            .await
+            .unwrap();
+        }
+        // Demo bets are left out
+        db.grant_demo_balance("demo_player", 1000).await.unwrap();
+        db.save_bet(&Bet {
+            id: "demo_bet".to_string(),
+            player_address: "demo_player".to_string(),
+            amount: 1000,
+            guess: true,
+            result: true,
+            won: true,
+            payout: 2000,
+            timestamp: Utc::now(),
+            settlement: None,
+            escrow: None,
+        })
+        .await
+        .unwrap();
+
+        let report = service
+            .generate_report(start, Utc::now() + ChronoDuration::seconds(1))
+            .await
+            .unwrap();
+
+        let coinflip = &report.body.games[0];
+        assert_eq!(coinflip.bets, 4);
+        assert_eq!(coinflip.total_wagered, 4000);
+        assert_eq!(coinflip.total_paid_out, 4000);
+        assert!((coinflip.actual_rtp - 1.0).abs() < f64::EPSILON);
+        assert_eq!(report.body.outcome_distribution.heads, 2);
+        assert!(report.body.outcome_distribution.passed);
+        assert!(report.verify_signature().unwrap());
+
+        // Tampering with the body invalidates the signature
+        let mut tampered = report.clone();
+        tampered.body.games[0].total_paid_out = 0;
+        assert!(!tampered.verify_signature().unwrap());
+
+        assert!(service.get_report(&report.body.report_id).is_some());
+        assert_eq!(service.list_reports().len(), 1);
+    }
+}
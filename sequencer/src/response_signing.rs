@@ -0,0 +1,165 @@
+// Signed API responses for ZK Casino
+// With SIGNED_RESPONSES=true, balance and bet lookups carry a detached Ed25519 signature
+// of the sequencer key in their headers, so services that cache or relay the data can
+// prove it came from the sequencer. The signature covers the request path, the signing
+// time and the exact body bytes; the body itself is left untouched.
+
+use anyhow::{anyhow, Result};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use tracing::error;
+
+use crate::AppState;
+
+/// Base58 Ed25519 signature over `signed_message`
+pub const SIGNATURE_HEADER: HeaderName = HeaderName::from_static("x-sequencer-signature");
+/// Base58 sequencer pubkey that produced the signature
+pub const SIGNER_HEADER: HeaderName = HeaderName::from_static("x-sequencer-signer");
+/// Unix time (seconds) the response was signed at
+pub const SIGNED_AT_HEADER: HeaderName = HeaderName::from_static("x-sequencer-signed-at");
+
+/// Domain separator, so a response signature can't pass for any other sequencer signature
+const DOMAIN: &str = "zkcasino-response-v1";
+
+/// Bytes a response signature covers
+pub fn signed_message(path: &str, signed_at: i64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}:{}:{}:", DOMAIN, path, signed_at).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+/// Detached signature of a response body
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseSignature {
+    pub signer: Pubkey,
+    pub signature: Signature,
+    pub signed_at: i64,
+}
+
+impl ResponseSignature {
+    pub fn sign(keypair: &Keypair, path: &str, signed_at: i64, body: &[u8]) -> Self {
+        Self {
+            signer: keypair.pubkey(),
+            signature: keypair.sign_message(&signed_message(path, signed_at, body)),
+            signed_at,
+        }
+    }
+
+    /// Read the signature headers of a response
+    #[allow(dead_code)]
+    pub fn from_headers(headers: &axum::http::HeaderMap) -> Result<Self> {
+        let header = |name: &HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| anyhow!("Missing {} header", name))
+        };
+        Ok(Self {
+            signer: header(&SIGNER_HEADER)?
+                .parse()
+                .map_err(|e| anyhow!("Invalid signer: {}", e))?,
+            signature: header(&SIGNATURE_HEADER)?
+                .parse()
+                .map_err(|e| anyhow!("Invalid signature: {}", e))?,
+            signed_at: header(&SIGNED_AT_HEADER)?
+                .parse()
+                .map_err(|e| anyhow!("Invalid signing time: {}", e))?,
+        })
+    }
+
+    /// Check the signature against a relayed response's path and body
+    #[allow(dead_code)]
+    pub fn verify(&self, path: &str, body: &[u8]) -> bool {
+        self.signature.verify(
+            self.signer.as_ref(),
+            &signed_message(path, self.signed_at, body),
+        )
+    }
+
+    fn append_to(&self, response: &mut Response) -> Result<()> {
+        let headers = response.headers_mut();
+        for (name, value) in [
+            (SIGNATURE_HEADER, self.signature.to_string()),
+            (SIGNER_HEADER, self.signer.to_string()),
+            (SIGNED_AT_HEADER, self.signed_at.to_string()),
+        ] {
+            headers.insert(name, HeaderValue::from_str(&value)?);
+        }
+        Ok(())
+    }
+}
+
+/// Middleware signing successful responses with the sequencer key when enabled
+pub async fn sign_response_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.signed_responses {
+        return next.run(request).await;
+    }
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to read response body for signing: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let signature = ResponseSignature::sign(
+        &state.keys.sequencer,
+        &path,
+        chrono::Utc::now().timestamp(),
+        &body,
+    );
+    let mut response = Response::from_parts(parts, Body::from(body));
+    if let Err(e) = signature.append_to(&mut response) {
+        error!("Failed to attach response signature: {}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_signature() {
+        let keypair = Keypair::new();
+        let body = br#"{"balance":1000}"#;
+        let signature = ResponseSignature::sign(&keypair, "/v1/balance/abc", 1_700_000_000, body);
+        assert!(signature.verify("/v1/balance/abc", body));
+
+        // Bound to the path, the body and the signing time
+        assert!(!signature.verify("/v1/balance/xyz", body));
+        assert!(!signature.verify("/v1/balance/abc", br#"{"balance":9000}"#));
+        let backdated = ResponseSignature {
+            signed_at: 1_600_000_000,
+            ..signature.clone()
+        };
+        assert!(!backdated.verify("/v1/balance/abc", body));
+
+        // Survives a round trip through the headers
+        let mut response = Response::new(Body::empty());
+        signature.append_to(&mut response).unwrap();
+        assert_eq!(
+            ResponseSignature::from_headers(response.headers()).unwrap(),
+            signature
+        );
+    }
+}
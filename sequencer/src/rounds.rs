@@ -0,0 +1,420 @@
+// Round-based games and their session feed for ZK Casino
+// Crash and roulette run in rounds shared by every player rather than per bet. Before a
+// round opens the sequencer publishes a commitment to the round's VRF proof (the hash
+// of the VRF key's signature over the round), so the outcome is fixed before any bet
+// is taken. After the round closes the proof itself is revealed: clients check it
+// hashes to the commitment, verify it against the VRF public key and recompute the
+// outcome. Every event carries a feed-wide sequence number; a jump in the numbers
+// tells a client it missed something and should reconnect with `since` to replay it.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::{hash, Hash};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+use tracing::info;
+
+/// Domain separator so round messages never collide with coin flip VRF messages
+const ROUND_MESSAGE_PREFIX: &str = "zkcasino-round-v1";
+
+/// Roulette wheel pockets (single zero)
+const ROULETTE_POCKETS: u64 = 37;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundGame {
+    Crash,
+    Roulette,
+}
+
+impl FromStr for RoundGame {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "crash" => Ok(RoundGame::Crash),
+            "roulette" => Ok(RoundGame::Roulette),
+            other => Err(anyhow!("Unknown round game: {}", other)),
+        }
+    }
+}
+
+impl RoundGame {
+    fn message(&self, round_id: u64) -> Vec<u8> {
+        let game = match self {
+            RoundGame::Crash => "crash",
+            RoundGame::Roulette => "roulette",
+        };
+        format!("{}:{}:{}", ROUND_MESSAGE_PREFIX, game, round_id).into_bytes()
+    }
+
+    /// Outcome the proof commits to: the crash point in hundredths (100 = 1.00x),
+    /// or the winning roulette pocket
+    pub fn outcome(&self, proof: &Signature) -> u64 {
+        let digest = hash(proof.as_ref()).to_bytes();
+        let value = u64::from_le_bytes(digest[..8].try_into().unwrap());
+        match self {
+            RoundGame::Crash => {
+                // One round in 33 crashes instantly (the house edge); otherwise the
+                // crash point follows 1 / (1 - x) over 52 uniform bits
+                let h = (value >> 12) as u128;
+                if h.is_multiple_of(33) {
+                    return 100;
+                }
+                let e = 1u128 << 52;
+                ((100 * e - h) / (e - h)) as u64
+            }
+            RoundGame::Roulette => value % ROULETTE_POCKETS,
+        }
+    }
+}
+
+/// Sign round `round_id` of `game`; returns the proof and the commitment to it
+pub fn prove_round(vrf: &Keypair, game: RoundGame, round_id: u64) -> (Signature, Hash) {
+    let proof = vrf.sign_message(&game.message(round_id));
+    (proof, hash(proof.as_ref()))
+}
+
+/// Check a revealed proof against its commitment and the VRF public key; returns the
+/// outcome it fixes. This is the check clients run on a reveal.
+#[allow(dead_code)]
+pub fn verify_round(
+    vrf: &Pubkey,
+    game: RoundGame,
+    round_id: u64,
+    commitment: &Hash,
+    proof: &Signature,
+) -> Option<u64> {
+    (hash(proof.as_ref()) == *commitment && proof.verify(vrf.as_ref(), &game.message(round_id)))
+        .then(|| game.outcome(proof))
+}
+
+/// Lifecycle of one round, in the order it is published
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoundEvent {
+    Commitment {
+        game: RoundGame,
+        round_id: u64,
+        commitment: String,
+        vrf_public_key: String,
+    },
+    Open {
+        game: RoundGame,
+        round_id: u64,
+        closes_at: DateTime<Utc>,
+    },
+    Close {
+        game: RoundGame,
+        round_id: u64,
+    },
+    Reveal {
+        game: RoundGame,
+        round_id: u64,
+        proof: String,
+        outcome: u64,
+    },
+}
+
+/// A round event as pushed to session clients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMessage {
+    pub sequence: u64,
+    pub emitted_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: RoundEvent,
+}
+
+struct FeedInner {
+    next_sequence: u64,
+    history: VecDeque<SessionMessage>,
+}
+
+/// Sequenced broadcast of round events, with recent history kept for replay
+pub struct RoundFeed {
+    capacity: usize,
+    inner: Mutex<FeedInner>,
+    sender: broadcast::Sender<SessionMessage>,
+}
+
+impl RoundFeed {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self {
+            capacity,
+            inner: Mutex::new(FeedInner {
+                next_sequence: 1,
+                history: VecDeque::new(),
+            }),
+            sender,
+        }
+    }
+
+    pub fn publish(&self, event: RoundEvent) -> SessionMessage {
+        // Sequence, record and send under one lock so subscribers see sequence order
+        let mut inner = self.inner.lock();
+        let message = SessionMessage {
+            sequence: inner.next_sequence,
+            emitted_at: Utc::now(),
+            event,
+        };
+        inner.next_sequence += 1;
+        inner.history.push_back(message.clone());
+        if inner.history.len() > self.capacity {
+            inner.history.pop_front();
+        }
+        let _ = self.sender.send(message.clone()); // No subscribers is fine
+        message
+    }
+
+    /// Retained messages after `since` plus a receiver for everything published
+    /// afterwards, with nothing missed or repeated in between. Messages older than the
+    /// retained history can't be replayed; the gap shows in the sequence numbers.
+    pub fn subscribe(
+        &self,
+        since: Option<u64>,
+    ) -> (Vec<SessionMessage>, broadcast::Receiver<SessionMessage>) {
+        let inner = self.inner.lock();
+        let replay = match since {
+            Some(since) => inner
+                .history
+                .iter()
+                .filter(|message| message.sequence > since)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        (replay, self.sender.subscribe())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoundConfig {
+    pub games: Vec<RoundGame>,
+    pub betting_window: Duration, // Between open and close
+    pub cooldown: Duration,       // Between a reveal and the next round's commitment
+    pub history: usize,           // Session messages kept for replay
+}
+
+impl Default for RoundConfig {
+    fn default() -> Self {
+        Self {
+            games: Vec::new(),
+            betting_window: Duration::from_secs(10),
+            cooldown: Duration::from_secs(5),
+            history: 500,
+        }
+    }
+}
+
+impl RoundConfig {
+    /// Load from environment; no rounds run unless ROUND_GAMES is set.
+    /// (ROUND_GAMES=crash,roulette, ROUND_BETTING_SECS, ROUND_COOLDOWN_SECS,
+    /// ROUND_HISTORY)
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        let env_u64 = |name: &str, default: u64| -> Result<u64> {
+            match std::env::var(name) {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(default),
+            }
+        };
+        let games = match std::env::var("ROUND_GAMES") {
+            Ok(value) => value
+                .split(',')
+                .filter(|game| !game.trim().is_empty())
+                .map(RoundGame::from_str)
+                .collect::<Result<_>>()?,
+            Err(_) => defaults.games,
+        };
+        Ok(Self {
+            games,
+            betting_window: Duration::from_secs(env_u64(
+                "ROUND_BETTING_SECS",
+                defaults.betting_window.as_secs(),
+            )?),
+            cooldown: Duration::from_secs(env_u64(
+                "ROUND_COOLDOWN_SECS",
+                defaults.cooldown.as_secs(),
+            )?),
+            history: env_u64("ROUND_HISTORY", defaults.history as u64)? as usize,
+        })
+    }
+}
+
+/// Runs the configured games' rounds back to back and publishes them to the feed
+pub struct RoundScheduler {
+    config: RoundConfig,
+    feed: Arc<RoundFeed>,
+    vrf: Arc<Keypair>,
+}
+
+impl RoundScheduler {
+    pub fn new(config: RoundConfig, feed: Arc<RoundFeed>, vrf: Arc<Keypair>) -> Self {
+        Self { config, feed, vrf }
+    }
+
+    /// Commit, open, close and reveal one round
+    pub async fn run_round(&self, game: RoundGame, round_id: u64) -> u64 {
+        let (proof, commitment) = prove_round(&self.vrf, game, round_id);
+        self.feed.publish(RoundEvent::Commitment {
+            game,
+            round_id,
+            commitment: commitment.to_string(),
+            vrf_public_key: self.vrf.pubkey().to_string(),
+        });
+
+        let closes_at =
+            Utc::now() + chrono::Duration::from_std(self.config.betting_window).unwrap_or_default();
+        self.feed.publish(RoundEvent::Open {
+            game,
+            round_id,
+            closes_at,
+        });
+        sleep(self.config.betting_window).await;
+        self.feed.publish(RoundEvent::Close { game, round_id });
+
+        let outcome = game.outcome(&proof);
+        self.feed.publish(RoundEvent::Reveal {
+            game,
+            round_id,
+            proof: proof.to_string(),
+            outcome,
+        });
+        outcome
+    }
+
+    /// Start one round loop per configured game (background tasks)
+    pub fn start(self: Arc<Self>) {
+        for game in self.config.games.clone() {
+            info!(
+                "Starting {:?} rounds (betting window: {:?})",
+                game, self.config.betting_window
+            );
+            let scheduler = self.clone();
+            tokio::spawn(async move {
+                // Round ids only need to be unique per VRF key and game
+                let mut round_id = Utc::now().timestamp_millis() as u64;
+                loop {
+                    scheduler.run_round(game, round_id).await;
+                    round_id += 1;
+                    sleep(scheduler.config.cooldown).await;
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_commitment_and_reveal() {
+        let vrf = Keypair::new();
+        let (proof, commitment) = prove_round(&vrf, RoundGame::Roulette, 7);
+        let outcome = verify_round(&vrf.pubkey(), RoundGame::Roulette, 7, &commitment, &proof);
+        assert!(outcome.is_some_and(|pocket| pocket < ROULETTE_POCKETS));
+
+        // A proof for another round, game or key doesn't open the commitment
+        let (other_proof, _) = prove_round(&vrf, RoundGame::Roulette, 8);
+        let verify = |game, round_id, proof: &Signature, key: &Pubkey| {
+            verify_round(key, game, round_id, &commitment, proof)
+        };
+        assert_eq!(
+            verify(RoundGame::Roulette, 7, &other_proof, &vrf.pubkey()),
+            None
+        );
+        assert_eq!(verify(RoundGame::Crash, 7, &proof, &vrf.pubkey()), None);
+        assert_eq!(verify(RoundGame::Roulette, 8, &proof, &vrf.pubkey()), None);
+        assert_eq!(
+            verify(RoundGame::Roulette, 7, &proof, &Pubkey::new_unique()),
+            None
+        );
+
+        // Crash points never go below 1.00x
+        let vrf = Keypair::new();
+        for round_id in 0..200 {
+            let (proof, _) = prove_round(&vrf, RoundGame::Crash, round_id);
+            assert!(RoundGame::Crash.outcome(&proof) >= 100);
+        }
+    }
+
+    #[test]
+    fn test_feed_replay() {
+        let feed = RoundFeed::new(3);
+        let close = |round_id| RoundEvent::Close {
+            game: RoundGame::Crash,
+            round_id,
+        };
+        for round_id in 0..5 {
+            feed.publish(close(round_id));
+        }
+
+        // Only the last three are retained; asking for older ones leaves a gap
+        let (replay, _) = feed.subscribe(Some(0));
+        let sequences: Vec<u64> = replay.iter().map(|m| m.sequence).collect();
+        assert_eq!(sequences, vec![3, 4, 5]);
+        let (replay, _) = feed.subscribe(Some(4));
+        assert_eq!(replay.len(), 1);
+        let (replay, mut receiver) = feed.subscribe(None);
+        assert!(replay.is_empty());
+
+        feed.publish(close(5));
+        assert_eq!(receiver.try_recv().unwrap().sequence, 6);
+    }
+
+    #[tokio::test]
+    async fn test_round_lifecycle() {
+        let feed = Arc::new(RoundFeed::new(10));
+        let vrf = Arc::new(Keypair::new());
+        let config = RoundConfig {
+            games: vec![RoundGame::Crash],
+            betting_window: Duration::ZERO,
+            ..RoundConfig::default()
+        };
+        let scheduler = RoundScheduler::new(config, feed.clone(), vrf.clone());
+        let (_, mut receiver) = feed.subscribe(None);
+
+        let outcome = scheduler.run_round(RoundGame::Crash, 42).await;
+        let messages: Vec<SessionMessage> = (0..4).map(|_| receiver.try_recv().unwrap()).collect();
+        assert_eq!(
+            messages.iter().map(|m| m.sequence).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+
+        let RoundEvent::Commitment { commitment, .. } = &messages[0].event else {
+            panic!("expected commitment first");
+        };
+        assert!(matches!(
+            messages[1].event,
+            RoundEvent::Open { round_id: 42, .. }
+        ));
+        assert!(matches!(
+            messages[2].event,
+            RoundEvent::Close { round_id: 42, .. }
+        ));
+        let RoundEvent::Reveal { proof, .. } = &messages[3].event else {
+            panic!("expected reveal last");
+        };
+        let verified = verify_round(
+            &vrf.pubkey(),
+            RoundGame::Crash,
+            42,
+            &Hash::from_str(commitment).unwrap(),
+            &Signature::from_str(proof).unwrap(),
+        );
+        assert_eq!(verified, Some(outcome));
+    }
+}
@@ -0,0 +1,369 @@
+// Latency-aware Solana RPC routing for ZK Casino
+// Continuously probes every configured RPC endpoint for latency and slot lag and
+// routes traffic to the best one, preferring endpoints in the sequencer's own region.
+// Routing is sticky: after a failover the client stays on the fallback until the
+// original endpoint has been healthy for several probes, so it never flaps mid-incident.
+
+use parking_lot::RwLock;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::interval;
+use tracing::{info, warn};
+
+/// Weight of the newest latency sample in the moving average
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcEndpoint {
+    pub url: String,
+    pub region: String,
+}
+
+impl RpcEndpoint {
+    /// Parse "region=url,region=url"
+    pub fn parse_list(spec: &str) -> Vec<Self> {
+        spec.split(',')
+            .map(str::trim)
+            .filter_map(|entry| entry.split_once('='))
+            .map(|(region, url)| Self {
+                url: url.trim().to_string(),
+                region: region.trim().to_string(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutingPolicy {
+    pub max_slot_lag: u64, // Endpoints further behind the best slot are skipped
+    pub failure_threshold: u32, // Consecutive failures before an endpoint is unhealthy
+    pub recovery_probes: u32, // Healthy probes required before failing back
+    pub switch_margin: f64, // Only switch for an endpoint this much faster (0.3 = 30%)
+    pub probe_interval: Duration,
+}
+
+impl Default for RoutingPolicy {
+    fn default() -> Self {
+        Self {
+            max_slot_lag: 50,
+            failure_threshold: 2,
+            recovery_probes: 3,
+            switch_margin: 0.3,
+            probe_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RoutingPolicy {
+    /// Load from environment (SOLANA_RPC_MAX_SLOT_LAG, SOLANA_RPC_PROBE_INTERVAL_SECS)
+    pub fn from_env() -> Self {
+        let env_u64 = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let defaults = Self::default();
+        Self {
+            max_slot_lag: env_u64("SOLANA_RPC_MAX_SLOT_LAG").unwrap_or(defaults.max_slot_lag),
+            probe_interval: env_u64("SOLANA_RPC_PROBE_INTERVAL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.probe_interval),
+            ..defaults
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EndpointHealth {
+    pub latency_ms: Option<f64>, // Moving average; None until first successful probe
+    pub slot: Option<u64>,
+    pub slot_lag: u64,
+    pub consecutive_failures: u32,
+    pub healthy_streak: u32,
+    pub last_error: Option<String>,
+}
+
+impl EndpointHealth {
+    fn is_usable(&self, policy: &RoutingPolicy) -> bool {
+        self.latency_ms.is_some()
+            && self.consecutive_failures < policy.failure_threshold
+            && self.slot_lag <= policy.max_slot_lag
+    }
+}
+
+/// Pick the endpoint to route to. Local-region endpoints win over remote ones, then
+/// lower latency; the current endpoint is kept unless it is unusable or clearly beaten.
+pub fn select_endpoint(
+    endpoints: &[RpcEndpoint],
+    health: &[EndpointHealth],
+    current: usize,
+    local_region: &str,
+    policy: &RoutingPolicy,
+) -> usize {
+    let remote = |i: usize| endpoints[i].region != local_region;
+    let latency = |i: usize| health[i].latency_ms.unwrap_or(f64::MAX);
+
+    let best = (0..endpoints.len())
+        .filter(|&i| health[i].is_usable(policy))
+        .min_by(|&a, &b| {
+            remote(a)
+                .cmp(&remote(b))
+                .then(latency(a).total_cmp(&latency(b)))
+        });
+    let Some(best) = best else {
+        return current; // Nothing better to go to
+    };
+    if !health[current].is_usable(policy) {
+        return best;
+    }
+
+    // A challenger must have proven itself stable before we leave a working endpoint
+    if health[best].healthy_streak < policy.recovery_probes {
+        return current;
+    }
+    let better_region = remote(current) && !remote(best);
+    let much_faster = remote(current) == remote(best)
+        && latency(best) * (1.0 + policy.switch_margin) < latency(current);
+    if better_region || much_faster {
+        best
+    } else {
+        current
+    }
+}
+
+/// Tracks endpoint health and the endpoint currently in use
+pub struct RpcRouter {
+    endpoints: Vec<RpcEndpoint>,
+    local_region: String,
+    policy: RoutingPolicy,
+    commitment: CommitmentConfig,
+    health: RwLock<Vec<EndpointHealth>>,
+    current: AtomicUsize,
+}
+
+impl RpcRouter {
+    /// `endpoints[0]` is the primary and is used until probes say otherwise
+    pub fn new(
+        endpoints: Vec<RpcEndpoint>,
+        local_region: String,
+        policy: RoutingPolicy,
+        commitment: CommitmentConfig,
+    ) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "RpcRouter needs at least one endpoint"
+        );
+        let health = vec![EndpointHealth::default(); endpoints.len()];
+        Self {
+            endpoints,
+            local_region,
+            policy,
+            commitment,
+            health: RwLock::new(health),
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn endpoint_count(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// URL of the endpoint traffic should currently go to
+    pub fn current_url(&self) -> String {
+        self.endpoints[self.current.load(Ordering::Relaxed)]
+            .url
+            .clone()
+    }
+
+    fn reselect(&self, health: &[EndpointHealth]) {
+        let current = self.current.load(Ordering::Relaxed);
+        let next = select_endpoint(
+            &self.endpoints,
+            health,
+            current,
+            &self.local_region,
+            &self.policy,
+        );
+        if next != current {
+            self.current.store(next, Ordering::Relaxed);
+            info!(
+                "RPC routing switched {} ({}) -> {} ({})",
+                self.endpoints[current].url,
+                self.endpoints[current].region,
+                self.endpoints[next].url,
+                self.endpoints[next].region
+            );
+        }
+    }
+
+    /// A request to `url` failed; fail over if the endpoint is now unhealthy
+    pub fn report_failure(&self, url: &str, error: &str) {
+        let Some(index) = self.endpoints.iter().position(|e| e.url == url) else {
+            return;
+        };
+        let mut health = self.health.write();
+        health[index].consecutive_failures += 1;
+        health[index].healthy_streak = 0;
+        health[index].last_error = Some(error.to_string());
+        if health[index].consecutive_failures >= self.policy.failure_threshold {
+            warn!("RPC endpoint {} marked unhealthy: {}", url, error);
+        }
+        self.reselect(&health);
+    }
+
+    /// Count transport-level failures (not transaction errors) against an endpoint
+    pub fn report_client_error(&self, url: &str, error: &ClientError) {
+        if matches!(
+            error.kind(),
+            ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+        ) {
+            self.report_failure(url, &error.to_string());
+        }
+    }
+
+    /// Fold one probe round (latency and slot per endpoint) into health and reselect
+    pub fn record_probes(&self, results: Vec<Result<(Duration, u64), String>>) {
+        let best_slot = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok().map(|(_, slot)| *slot))
+            .max()
+            .unwrap_or(0);
+
+        let mut health = self.health.write();
+        for (entry, result) in health.iter_mut().zip(results) {
+            match result {
+                Ok((latency, slot)) => {
+                    let sample = latency.as_secs_f64() * 1000.0;
+                    entry.latency_ms = Some(match entry.latency_ms {
+                        Some(avg) => avg + LATENCY_EWMA_ALPHA * (sample - avg),
+                        None => sample,
+                    });
+                    entry.slot = Some(slot);
+                    entry.slot_lag = best_slot.saturating_sub(slot);
+                    entry.consecutive_failures = 0;
+                    entry.healthy_streak += 1;
+                    entry.last_error = None;
+                }
+                Err(error) => {
+                    entry.consecutive_failures += 1;
+                    entry.healthy_streak = 0;
+                    entry.last_error = Some(error);
+                }
+            }
+        }
+        self.reselect(&health);
+    }
+
+    /// Measure latency and slot of every endpoint once
+    pub async fn probe_all(&self) {
+        // Spawn all probes first so they run concurrently
+        let probes: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let url = endpoint.url.clone();
+                let commitment = self.commitment;
+                tokio::task::spawn_blocking(move || {
+                    let client = RpcClient::new_with_timeout_and_commitment(
+                        url,
+                        Duration::from_secs(5),
+                        commitment,
+                    );
+                    let started = Instant::now();
+                    client
+                        .get_slot()
+                        .map(|slot| (started.elapsed(), slot))
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(probes.len());
+        for probe in probes {
+            results.push(probe.await.unwrap_or_else(|e| Err(e.to_string())));
+        }
+        self.record_probes(results);
+    }
+
+    /// Start continuous probing (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting RPC routing across {} endpoints (region: {}, probe interval: {:?})",
+            self.endpoints.len(),
+            self.local_region,
+            self.policy.probe_interval
+        );
+        tokio::spawn(async move {
+            let mut interval = interval(self.policy.probe_interval);
+            loop {
+                interval.tick().await;
+                self.probe_all().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router() -> RpcRouter {
+        RpcRouter::new(
+            RpcEndpoint::parse_list("us=http://us-1,us=http://us-2,eu=http://eu-1"),
+            "us".to_string(),
+            RoutingPolicy {
+                recovery_probes: 2,
+                ..RoutingPolicy::default()
+            },
+            CommitmentConfig::confirmed(),
+        )
+    }
+
+    fn probe(ms: u64, slot: u64) -> Result<(Duration, u64), String> {
+        Ok((Duration::from_millis(ms), slot))
+    }
+
+    #[test]
+    fn test_prefers_local_region_and_lowest_latency() {
+        let router = router();
+        assert_eq!(router.current_url(), "http://us-1");
+
+        // Remote endpoint is fastest, but local endpoints win; us-2 is much faster than us-1
+        for _ in 0..2 {
+            router.record_probes(vec![probe(200, 1000), probe(50, 1000), probe(10, 1000)]);
+        }
+        assert_eq!(router.current_url(), "http://us-2");
+
+        // A single fast sample doesn't outweigh the moving average
+        router.record_probes(vec![probe(1, 1000), probe(60, 1000), probe(10, 1000)]);
+        assert_eq!(router.current_url(), "http://us-2");
+    }
+
+    #[test]
+    fn test_slot_lag_and_sticky_failover() {
+        let router = router();
+        router.record_probes(vec![probe(20, 1000), probe(30, 1000), probe(80, 1000)]);
+        router.record_probes(vec![probe(20, 1000), probe(30, 1000), probe(80, 1000)]);
+        assert_eq!(router.current_url(), "http://us-1");
+
+        // us-1 falls behind: route to the next local endpoint
+        router.record_probes(vec![probe(20, 900), probe(30, 1000), probe(80, 1000)]);
+        assert_eq!(router.current_url(), "http://us-2");
+
+        // us-2 fails submissions: fail over to the remote region
+        router.report_failure("http://us-2", "timeout");
+        router.report_failure("http://us-2", "timeout");
+        router.record_probes(vec![
+            Err("down".into()),
+            Err("down".into()),
+            probe(80, 1000),
+        ]);
+        assert_eq!(router.current_url(), "http://eu-1");
+
+        // One good probe isn't enough to fail back
+        router.record_probes(vec![probe(20, 1000), Err("down".into()), probe(80, 1000)]);
+        assert_eq!(router.current_url(), "http://eu-1");
+        router.record_probes(vec![probe(20, 1000), Err("down".into()), probe(80, 1000)]);
+        assert_eq!(router.current_url(), "http://us-1");
+    }
+}
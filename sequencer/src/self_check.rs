@@ -0,0 +1,1022 @@
+// Startup self-check for ZK Casino
+// Before serving traffic the sequencer checks that it is wired up the way it expects: the
+// database schema is current, the VRF key signs and verifies, and, with Solana enabled,
+// the vault and verifier programs are deployed executables whose state PDAs are
+// initialized, whose on-chain verifying key matches the pinned circuit digest and
+// whose coinflip payout schedule matches the sequencer's. With a genesis manifest the
+// keys, authorities, house bankroll, sequencer registration and game schedules are
+// checked against it as well.
+// When a check fails the sequencer either refuses to start or comes up read-only
+// (SELF_CHECK_ON_FAILURE=refuse|read_only).
+
+use anchor_lang::AccountDeserialize;
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use solana_sdk::account::Account;
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use solana_sdk::{bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable};
+use tracing::{error, info, warn};
+
+use crate::database::{Database, SCHEMA_VERSION};
+use crate::genesis::Genesis;
+use crate::keys::SequencerKeys;
+use crate::solana::{SolanaClient, GAME_COINFLIP};
+use crate::tokens::PayoutSchedule;
+use crate::{AppState, ErrorResponse};
+
+const VRF_PROBE_MESSAGE: &[u8] = b"zkcasino-self-check";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureMode {
+    Refuse,   // Exit instead of serving
+    ReadOnly, // Serve reads; reject writes outside the admin API
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfCheckConfig {
+    pub on_failure: FailureMode,
+    pub expected_verifying_key_hash: Option<String>, // Base58 SHA-256 of the on-chain key bytes
+    pub genesis: Option<Genesis>,
+}
+
+impl SelfCheckConfig {
+    /// Load from environment (SELF_CHECK_ON_FAILURE, EXPECTED_VERIFYING_KEY_HASH)
+    pub fn from_env() -> Result<Self> {
+        let on_failure = match std::env::var("SELF_CHECK_ON_FAILURE").as_deref() {
+            Err(_) | Ok("refuse") => FailureMode::Refuse,
+            Ok("read_only") => FailureMode::ReadOnly,
+            Ok(other) => {
+                return Err(anyhow!(
+                    "Invalid SELF_CHECK_ON_FAILURE: {} (expected refuse or read_only)",
+                    other
+                ))
+            }
+        };
+        Ok(Self {
+            on_failure,
+            expected_verifying_key_hash: std::env::var("EXPECTED_VERIFYING_KEY_HASH").ok(),
+            genesis: None,
+        })
+    }
+
+    /// Check against a genesis manifest, whose verifying key digest is the expected one
+    /// unless EXPECTED_VERIFYING_KEY_HASH says otherwise
+    pub fn with_genesis(mut self, genesis: Option<Genesis>) -> Result<Self> {
+        if let Some(genesis) = &genesis {
+            let genesis_hash = genesis.verifying_key_hash.to_string();
+            match &self.expected_verifying_key_hash {
+                Some(expected) if *expected != genesis_hash => {
+                    return Err(anyhow!(
+                        "EXPECTED_VERIFYING_KEY_HASH {} conflicts with the genesis manifest's {}",
+                        expected,
+                        genesis_hash
+                    ))
+                }
+                _ => self.expected_verifying_key_hash = Some(genesis_hash),
+            }
+        }
+        self.genesis = genesis;
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfCheckReport {
+    pub checks: Vec<CheckResult>,
+    pub read_only: bool, // Checks failed and the sequencer is serving reads only
+}
+
+impl SelfCheckReport {
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+    }
+}
+
+/// Run every check; fails when a check fails and the config says to refuse to start
+pub async fn run(
+    config: &SelfCheckConfig,
+    db: &Database,
+    keys: &SequencerKeys,
+    payouts: &PayoutSchedule,
+    solana_client: Option<&SolanaClient>,
+) -> Result<SelfCheckReport> {
+    let mut checks = vec![check_database(db), check_vrf_keypair(keys)];
+    if let Some(genesis) = &config.genesis {
+        checks.push(check_genesis_keys(genesis, keys, payouts));
+    }
+
+    match solana_client {
+        Some(client) => {
+            checks.extend(check_onchain(config, payouts, client).await);
+            if let Some(genesis) = &config.genesis {
+                checks.extend(check_genesis_onchain(genesis, client).await);
+            }
+        }
+        None => {
+            let genesis_checks = config.genesis.iter().flat_map(genesis_onchain_checks);
+            for name in ONCHAIN_CHECKS
+                .map(String::from)
+                .into_iter()
+                .chain(genesis_checks)
+            {
+                checks.push(CheckResult::new(
+                    &name,
+                    CheckStatus::Skipped,
+                    "Solana integration unavailable",
+                ));
+            }
+        }
+    }
+
+    let mut report = SelfCheckReport {
+        checks,
+        read_only: false,
+    };
+    for check in &report.checks {
+        match check.status {
+            CheckStatus::Passed => info!("Self-check {} passed: {}", check.name, check.detail),
+            CheckStatus::Skipped => info!("Self-check {} skipped: {}", check.name, check.detail),
+            CheckStatus::Failed => error!("Self-check {} failed: {}", check.name, check.detail),
+        }
+    }
+
+    if !report.passed() {
+        let failed: Vec<&str> = report.failures().map(|check| check.name.as_str()).collect();
+        match config.on_failure {
+            FailureMode::Refuse => {
+                return Err(anyhow!("Startup self-check failed: {}", failed.join(", ")))
+            }
+            FailureMode::ReadOnly => {
+                warn!(
+                    "Startup self-check failed ({}); serving read-only",
+                    failed.join(", ")
+                );
+                report.read_only = true;
+            }
+        }
+    }
+    Ok(report)
+}
+
+const ONCHAIN_CHECKS: [&str; 9] = [
+    "vault_program",
+    "verifier_program",
+    "vault_state",
+    "house_vault",
+    "verifier_state",
+    "verifying_key",
+    "circuit_registry",
+    "verifier_stats",
+    "coinflip_game_config",
+];
+
+async fn check_onchain(
+    config: &SelfCheckConfig,
+    payouts: &PayoutSchedule,
+    client: &SolanaClient,
+) -> Vec<CheckResult> {
+    let vault_program_id = client.vault_program_id();
+    let verifier_program_id = client.verifier_program_id();
+    let addresses = [
+        vault_program_id,
+        verifier_program_id,
+        client.vault_state_pda(),
+        client.house_vault_pda(),
+        client.verifier_state_pda(),
+        client.verifying_key_pda(),
+        client.circuit_registry_pda(),
+        client.verifier_stats_pda(),
+        client.game_config_pda(GAME_COINFLIP),
+    ];
+
+    let mut checks = Vec::new();
+    for (name, address) in ONCHAIN_CHECKS.iter().zip(addresses) {
+        let account = match client.get_account(&address).await {
+            Ok(account) => account,
+            Err(e) => {
+                checks.push(CheckResult::new(
+                    name,
+                    CheckStatus::Failed,
+                    format!("Failed to fetch {}: {}", address, e),
+                ));
+                continue;
+            }
+        };
+        let account = account.as_ref();
+        checks.push(match *name {
+            "vault_program" | "verifier_program" => check_program(name, &address, account),
+            "vault_state" => {
+                check_state_pda(name, &address, &vault_program_id, "VaultState", account)
+            }
+            "house_vault" => {
+                check_state_pda(name, &address, &vault_program_id, "HouseVault", account)
+            }
+            "verifier_state" => check_state_pda(
+                name,
+                &address,
+                &verifier_program_id,
+                "VerifierState",
+                account,
+            ),
+            "verifying_key" => {
+                check_verifying_key(config.expected_verifying_key_hash.as_deref(), account)
+            }
+            "circuit_registry" => check_state_pda(
+                name,
+                &address,
+                &verifier_program_id,
+                "CircuitRegistry",
+                account,
+            ),
+            "verifier_stats" => check_state_pda(
+                name,
+                &address,
+                &verifier_program_id,
+                "VerifierStats",
+                account,
+            ),
+            _ => check_game_config(name, payouts, account),
+        });
+    }
+    checks
+}
+
+/// Genesis checks run against on-chain state: the authorities, house bankroll and
+/// sequencer registration, then each game other than the coinflip (checked above)
+fn genesis_onchain_checks(genesis: &Genesis) -> Vec<String> {
+    let mut names: Vec<String> = [
+        "genesis_authorities",
+        "genesis_house_bankroll",
+        "genesis_sequencer_registration",
+    ]
+    .map(String::from)
+    .into();
+    names.extend(
+        genesis
+            .games
+            .iter()
+            .filter(|game| game.game_id != GAME_COINFLIP)
+            .map(|game| format!("genesis_game_{}", game.game_id)),
+    );
+    names
+}
+
+async fn check_genesis_onchain(genesis: &Genesis, client: &SolanaClient) -> Vec<CheckResult> {
+    let fetch = |name: &str, address: Pubkey| {
+        let name = name.to_string();
+        async move {
+            client.get_account(&address).await.map_err(|e| {
+                CheckResult::new(
+                    &name,
+                    CheckStatus::Failed,
+                    format!("Failed to fetch {}: {}", address, e),
+                )
+            })
+        }
+    };
+
+    let mut checks = Vec::new();
+    let vault_state = fetch("genesis_authorities", client.vault_state_pda()).await;
+    let verifier_state = fetch("genesis_authorities", client.verifier_state_pda()).await;
+    checks.push(match (vault_state, verifier_state) {
+        (Ok(vault_state), Ok(verifier_state)) => {
+            check_genesis_authorities(genesis, vault_state.as_ref(), verifier_state.as_ref())
+        }
+        (Err(failed), _) | (_, Err(failed)) => failed,
+    });
+    checks.push(
+        match fetch("genesis_house_bankroll", client.house_vault_pda()).await {
+            Ok(account) => check_genesis_bankroll(genesis, account.as_ref()),
+            Err(failed) => failed,
+        },
+    );
+    checks.push(
+        match fetch(
+            "genesis_sequencer_registration",
+            client.sequencer_registration_pda(),
+        )
+        .await
+        {
+            Ok(account) => check_genesis_sequencer_registration(genesis, account.as_ref()),
+            Err(failed) => failed,
+        },
+    );
+    for game in &genesis.games {
+        if game.game_id == GAME_COINFLIP {
+            continue;
+        }
+        let name = format!("genesis_game_{}", game.game_id);
+        checks.push(
+            match fetch(&name, client.game_config_pda(game.game_id)).await {
+                Ok(account) => check_game_config(&name, &game.payout_schedule(), account.as_ref()),
+                Err(failed) => failed,
+            },
+        );
+    }
+    checks
+}
+
+pub fn check_database(db: &Database) -> CheckResult {
+    let version = db.schema_version();
+    if version == SCHEMA_VERSION {
+        CheckResult::new(
+            "database_schema",
+            CheckStatus::Passed,
+            format!("Schema version {}", version),
+        )
+    } else {
+        CheckResult::new(
+            "database_schema",
+            CheckStatus::Failed,
+            format!("Schema version {}, expected {}", version, SCHEMA_VERSION),
+        )
+    }
+}
+
+pub fn check_vrf_keypair(keys: &SequencerKeys) -> CheckResult {
+    let vrf_pubkey = keys.vrf.pubkey();
+    if vrf_pubkey == keys.sequencer.pubkey() {
+        return CheckResult::new(
+            "vrf_keypair",
+            CheckStatus::Failed,
+            "VRF key must differ from the sequencer key",
+        );
+    }
+    let signature = keys.vrf.sign_message(VRF_PROBE_MESSAGE);
+    if !signature.verify(vrf_pubkey.as_ref(), VRF_PROBE_MESSAGE) {
+        return CheckResult::new(
+            "vrf_keypair",
+            CheckStatus::Failed,
+            format!("VRF key {} does not verify its own signature", vrf_pubkey),
+        );
+    }
+    CheckResult::new("vrf_keypair", CheckStatus::Passed, vrf_pubkey.to_string())
+}
+
+/// The sequencer signs with the manifest's keys and pays out its coinflip schedule
+pub fn check_genesis_keys(
+    genesis: &Genesis,
+    keys: &SequencerKeys,
+    payouts: &PayoutSchedule,
+) -> CheckResult {
+    let failed = |detail: String| CheckResult::new("genesis_keys", CheckStatus::Failed, detail);
+    let authorities = &genesis.authorities;
+    if keys.sequencer.pubkey() != authorities.sequencer {
+        return failed(format!(
+            "Sequencer key {} is not the genesis sequencer {}",
+            keys.sequencer.pubkey(),
+            authorities.sequencer
+        ));
+    }
+    if keys.vrf.pubkey() != authorities.vrf {
+        return failed(format!(
+            "VRF key {} is not the genesis VRF key {}",
+            keys.vrf.pubkey(),
+            authorities.vrf
+        ));
+    }
+    if *payouts != genesis.coinflip_payouts() {
+        return failed("Coinflip payout schedule differs from genesis".to_string());
+    }
+    CheckResult::new(
+        "genesis_keys",
+        CheckStatus::Passed,
+        format!("{} genesis {}", genesis.network, genesis.digest()),
+    )
+}
+
+/// The vault and verifier are administered by the manifest's authorities, and the
+/// verifier settles into the manifest's vault program
+pub fn check_genesis_authorities(
+    genesis: &Genesis,
+    vault_state: Option<&Account>,
+    verifier_state: Option<&Account>,
+) -> CheckResult {
+    let failed =
+        |detail: String| CheckResult::new("genesis_authorities", CheckStatus::Failed, detail);
+    let Some(vault_state) = decode::<vault::VaultState>(vault_state) else {
+        return failed("Vault state is not initialized".to_string());
+    };
+    let Some(verifier_state) = decode::<verifier::VerifierState>(verifier_state) else {
+        return failed("Verifier state is not initialized".to_string());
+    };
+    let authorities = &genesis.authorities;
+    if vault_state.authority != authorities.vault {
+        return failed(format!(
+            "Vault authority {} is not the genesis authority {}",
+            vault_state.authority, authorities.vault
+        ));
+    }
+    if verifier_state.authority != authorities.verifier {
+        return failed(format!(
+            "Verifier authority {} is not the genesis authority {}",
+            verifier_state.authority, authorities.verifier
+        ));
+    }
+    if verifier_state.vault_program != genesis.vault_program_id {
+        return failed(format!(
+            "Verifier settles into {}, genesis vault program is {}",
+            verifier_state.vault_program, genesis.vault_program_id
+        ));
+    }
+    CheckResult::new(
+        "genesis_authorities",
+        CheckStatus::Passed,
+        format!(
+            "vault {}, verifier {}",
+            authorities.vault, authorities.verifier
+        ),
+    )
+}
+
+/// The house vault was funded with at least the manifest's principal
+pub fn check_genesis_bankroll(genesis: &Genesis, house_vault: Option<&Account>) -> CheckResult {
+    let failed =
+        |detail: String| CheckResult::new("genesis_house_bankroll", CheckStatus::Failed, detail);
+    let Some(house_vault) = decode::<vault::HouseVault>(house_vault) else {
+        return failed("House vault is not initialized".to_string());
+    };
+    let expected = &genesis.house_bankroll;
+    if house_vault.sol_principal < expected.sol || house_vault.usdc_principal < expected.usdc {
+        return failed(format!(
+            "House principal {} SOL / {} USDC base units is below genesis {} / {}",
+            house_vault.sol_principal, house_vault.usdc_principal, expected.sol, expected.usdc
+        ));
+    }
+    CheckResult::new(
+        "genesis_house_bankroll",
+        CheckStatus::Passed,
+        format!(
+            "{} SOL / {} USDC base units",
+            house_vault.sol_principal, house_vault.usdc_principal
+        ),
+    )
+}
+
+/// The manifest's sequencer is on the verifier's allowlist
+pub fn check_genesis_sequencer_registration(
+    genesis: &Genesis,
+    registration: Option<&Account>,
+) -> CheckResult {
+    let name = "genesis_sequencer_registration";
+    let sequencer = genesis.authorities.sequencer;
+    match decode::<verifier::SequencerRegistration>(registration) {
+        Some(registration) if registration.sequencer == sequencer => {
+            CheckResult::new(name, CheckStatus::Passed, sequencer.to_string())
+        }
+        _ => CheckResult::new(
+            name,
+            CheckStatus::Failed,
+            format!("Genesis sequencer {} is not registered", sequencer),
+        ),
+    }
+}
+
+/// Decode an Anchor account, checking its discriminator
+fn decode<T: AccountDeserialize>(account: Option<&Account>) -> Option<T> {
+    T::try_deserialize(&mut account?.data.as_slice()).ok()
+}
+
+/// The program account exists, is executable and is owned by a BPF loader
+pub fn check_program(name: &str, program_id: &Pubkey, account: Option<&Account>) -> CheckResult {
+    let failed = |detail: String| CheckResult::new(name, CheckStatus::Failed, detail);
+    let Some(account) = account else {
+        return failed(format!("Program {} not found", program_id));
+    };
+    if !account.executable {
+        return failed(format!("Program {} is not executable", program_id));
+    }
+    let loaders = [
+        bpf_loader_upgradeable::id(),
+        bpf_loader::id(),
+        bpf_loader_deprecated::id(),
+    ];
+    if !loaders.contains(&account.owner) {
+        return failed(format!(
+            "Program {} is owned by {}, not a BPF loader",
+            program_id, account.owner
+        ));
+    }
+    CheckResult::new(name, CheckStatus::Passed, program_id.to_string())
+}
+
+/// The PDA exists, is owned by `program_id` and holds an Anchor `account_type` account
+pub fn check_state_pda(
+    name: &str,
+    address: &Pubkey,
+    program_id: &Pubkey,
+    account_type: &str,
+    account: Option<&Account>,
+) -> CheckResult {
+    let failed = |detail: String| CheckResult::new(name, CheckStatus::Failed, detail);
+    let Some(account) = account else {
+        return failed(format!("{} {} is not initialized", account_type, address));
+    };
+    if account.owner != *program_id {
+        return failed(format!(
+            "{} {} is owned by {}, expected {}",
+            account_type, address, account.owner, program_id
+        ));
+    }
+    if !has_discriminator(&account.data, account_type) {
+        return failed(format!("{} is not a {} account", address, account_type));
+    }
+    CheckResult::new(name, CheckStatus::Passed, address.to_string())
+}
+
+/// The active on-chain verifying key hashes to the expected circuit digest
+pub fn check_verifying_key(expected_hash: Option<&str>, account: Option<&Account>) -> CheckResult {
+    let failed = |detail: String| CheckResult::new("verifying_key", CheckStatus::Failed, detail);
+    let Some(expected_hash) = expected_hash else {
+        return CheckResult::new(
+            "verifying_key",
+            CheckStatus::Skipped,
+            "EXPECTED_VERIFYING_KEY_HASH not set",
+        );
+    };
+    let Some(account) = account else {
+        return failed("Verifying key account is not initialized".to_string());
+    };
+    let Some(active) = active_verifying_key(&account.data) else {
+        return failed("Account is not a verifying key account".to_string());
+    };
+    let actual_hash = hash(active).to_string();
+    if actual_hash != expected_hash {
+        return failed(format!(
+            "On-chain verifying key hash {} does not match expected {}",
+            actual_hash, expected_hash
+        ));
+    }
+    CheckResult::new("verifying_key", CheckStatus::Passed, actual_hash)
+}
+
+/// The game's on-chain payout schedule is the one the sequencer pays out with
+pub fn check_game_config(
+    name: &str,
+    payouts: &PayoutSchedule,
+    account: Option<&Account>,
+) -> CheckResult {
+    let failed = |detail: String| CheckResult::new(name, CheckStatus::Failed, detail);
+    let Some(account) = account else {
+        return failed("Game config account is not initialized".to_string());
+    };
+    // Discriminator, game_id (u8), payout_multiplier_bps (u32), house_edge_bps (u16)
+    let data = &account.data;
+    if !has_discriminator(data, "GameConfig") || data.len() < 15 {
+        return failed("Account is not a game config account".to_string());
+    }
+    let multiplier_bps = u32::from_le_bytes(data[9..13].try_into().unwrap()) as u64;
+    let house_edge_bps = u16::from_le_bytes(data[13..15].try_into().unwrap()) as u64;
+    if multiplier_bps != payouts.multiplier_bps || house_edge_bps != payouts.house_edge_bps {
+        return failed(format!(
+            "On-chain schedule {} bps with {} bps edge, sequencer pays {} bps with {} bps edge",
+            multiplier_bps, house_edge_bps, payouts.multiplier_bps, payouts.house_edge_bps
+        ));
+    }
+    CheckResult::new(
+        name,
+        CheckStatus::Passed,
+        format!("{} bps, {} bps edge", multiplier_bps, house_edge_bps),
+    )
+}
+
+fn has_discriminator(data: &[u8], account_type: &str) -> bool {
+    let discriminator = hash(format!("account:{}", account_type).as_bytes());
+    data.len() >= 8 && data[..8] == discriminator.to_bytes()[..8]
+}
+
+/// Active key bytes of a VerifyingKeyAccount: discriminator, version (u32),
+/// staged_len (u32), then `active` as a length-prefixed Vec<u8>
+pub(crate) fn active_verifying_key(data: &[u8]) -> Option<&[u8]> {
+    if !has_discriminator(data, "VerifyingKeyAccount") || data.len() < 20 {
+        return None;
+    }
+    let len = u32::from_le_bytes(data[16..20].try_into().unwrap()) as usize;
+    data.get(20..20 + len)
+}
+
+/// Rejects writes outside the admin API while the sequencer is read-only or an upgrade
+/// has stopped intake
+pub async fn read_only_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_write = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let reason = if state.self_check.read_only {
+        Some("Sequencer is read-only: startup self-check failed")
+    } else if !state.upgrade.intake_open() {
+        Some("Sequencer is read-only: an upgrade is in progress")
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        if is_write && !request.uri().path().starts_with("/v1/admin/") {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorResponse {
+                    error: reason.to_string(),
+                }),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+    use std::sync::Arc;
+
+    fn account(owner: Pubkey, executable: bool, data: Vec<u8>) -> Account {
+        Account {
+            lamports: 1_000_000,
+            data,
+            owner,
+            executable,
+            rent_epoch: 0,
+        }
+    }
+
+    fn anchor_data(account_type: &str, body: &[u8]) -> Vec<u8> {
+        let mut data =
+            hash(format!("account:{}", account_type).as_bytes()).to_bytes()[..8].to_vec();
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_check_database() {
+        let db = Database::new("test").await.unwrap();
+        assert_eq!(check_database(&db).status, CheckStatus::Failed);
+        db.create_tables().await.unwrap();
+        assert_eq!(check_database(&db).status, CheckStatus::Passed);
+    }
+
+    #[test]
+    fn test_check_vrf_keypair() {
+        let sequencer = Arc::new(Keypair::new());
+        let keys = SequencerKeys {
+            sequencer: sequencer.clone(),
+            vrf: Arc::new(Keypair::new()),
+        };
+        assert_eq!(check_vrf_keypair(&keys).status, CheckStatus::Passed);
+
+        let shared = SequencerKeys {
+            sequencer: sequencer.clone(),
+            vrf: sequencer,
+        };
+        assert_eq!(check_vrf_keypair(&shared).status, CheckStatus::Failed);
+    }
+
+    #[test]
+    fn test_check_program() {
+        let program_id = Pubkey::new_unique();
+        let status = |account: Option<Account>| {
+            check_program("verifier_program", &program_id, account.as_ref()).status
+        };
+        assert_eq!(
+            status(Some(account(bpf_loader_upgradeable::id(), true, vec![]))),
+            CheckStatus::Passed
+        );
+        assert_eq!(status(None), CheckStatus::Failed);
+        assert_eq!(
+            status(Some(account(bpf_loader_upgradeable::id(), false, vec![]))),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            status(Some(account(Pubkey::new_unique(), true, vec![]))),
+            CheckStatus::Failed
+        );
+    }
+
+    #[test]
+    fn test_check_state_pda() {
+        let program_id = Pubkey::new_unique();
+        let address = Pubkey::new_unique();
+        let status = |account: Option<Account>| {
+            check_state_pda(
+                "verifier_state",
+                &address,
+                &program_id,
+                "VerifierState",
+                account.as_ref(),
+            )
+            .status
+        };
+        let data = anchor_data("VerifierState", &[0; 64]);
+        assert_eq!(
+            status(Some(account(program_id, false, data.clone()))),
+            CheckStatus::Passed
+        );
+        assert_eq!(status(None), CheckStatus::Failed);
+        assert_eq!(
+            status(Some(account(Pubkey::new_unique(), false, data))),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            status(Some(account(
+                program_id,
+                false,
+                anchor_data("VaultState", &[0; 64])
+            ))),
+            CheckStatus::Failed
+        );
+    }
+
+    #[test]
+    fn test_check_verifying_key() {
+        let key = vec![7u8; 40];
+        let mut body = Vec::new();
+        body.extend_from_slice(&1u32.to_le_bytes()); // version
+        body.extend_from_slice(&0u32.to_le_bytes()); // staged_len
+        body.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        body.extend_from_slice(&key);
+        body.extend_from_slice(&0u32.to_le_bytes()); // empty staged key
+        let vk_account = account(
+            Pubkey::new_unique(),
+            false,
+            anchor_data("VerifyingKeyAccount", &body),
+        );
+        let expected = hash(&key).to_string();
+
+        assert_eq!(
+            check_verifying_key(Some(&expected), Some(&vk_account)).status,
+            CheckStatus::Passed
+        );
+        assert_eq!(
+            check_verifying_key(Some(&hash(b"other").to_string()), Some(&vk_account)).status,
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            check_verifying_key(Some(&expected), None).status,
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            check_verifying_key(None, Some(&vk_account)).status,
+            CheckStatus::Skipped
+        );
+    }
+
+    #[test]
+    fn test_check_game_config() {
+        let payouts = PayoutSchedule {
+            multiplier_bps: 20_000,
+            house_edge_bps: 100,
+        };
+        let config_account = |multiplier_bps: u32, house_edge_bps: u16| {
+            let mut body = vec![GAME_COINFLIP];
+            body.extend_from_slice(&multiplier_bps.to_le_bytes());
+            body.extend_from_slice(&house_edge_bps.to_le_bytes());
+            body.extend_from_slice(&0i64.to_le_bytes()); // updated_at
+            account(
+                Pubkey::new_unique(),
+                false,
+                anchor_data("GameConfig", &body),
+            )
+        };
+        let status = |account: Option<Account>| {
+            check_game_config("coinflip_game_config", &payouts, account.as_ref()).status
+        };
+
+        assert_eq!(
+            status(Some(config_account(20_000, 100))),
+            CheckStatus::Passed
+        );
+        assert_eq!(status(Some(config_account(20_000, 0))), CheckStatus::Failed);
+        assert_eq!(status(None), CheckStatus::Failed);
+    }
+
+    #[test]
+    fn test_genesis_checks() {
+        let keys = SequencerKeys {
+            sequencer: Arc::new(Keypair::new()),
+            vrf: Arc::new(Keypair::new()),
+        };
+        let payouts = PayoutSchedule::default();
+        let genesis = Genesis::from_json(
+            &serde_json::json!({
+                "network": "localnet",
+                "vault_program_id": Pubkey::new_unique().to_string(),
+                "verifier_program_id": Pubkey::new_unique().to_string(),
+                "authorities": {
+                    "vault": Pubkey::new_unique().to_string(),
+                    "verifier": Pubkey::new_unique().to_string(),
+                    "sequencer": keys.sequencer.pubkey().to_string(),
+                    "vrf": keys.vrf.pubkey().to_string(),
+                },
+                "house_bankroll": { "sol": 1_000, "usdc": 0 },
+                "verifying_key_hash": hash(b"key").to_string(),
+                "games": [{
+                    "game_id": GAME_COINFLIP,
+                    "payout_multiplier_bps": payouts.multiplier_bps,
+                    "house_edge_bps": payouts.house_edge_bps,
+                }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let authorities = &genesis.authorities;
+
+        assert_eq!(
+            check_genesis_keys(&genesis, &keys, &payouts).status,
+            CheckStatus::Passed
+        );
+        let rotated = SequencerKeys {
+            sequencer: Arc::new(Keypair::new()),
+            vrf: keys.vrf.clone(),
+        };
+        assert_eq!(
+            check_genesis_keys(&genesis, &rotated, &payouts).status,
+            CheckStatus::Failed
+        );
+        let repriced = PayoutSchedule {
+            house_edge_bps: payouts.house_edge_bps + 1,
+            ..payouts
+        };
+        assert_eq!(
+            check_genesis_keys(&genesis, &keys, &repriced).status,
+            CheckStatus::Failed
+        );
+
+        // Leading fields of each account; zeroes decode as defaults for the rest
+        let state = |account_type: &str, fields: &[&[u8]]| {
+            let mut body = fields.concat();
+            body.resize(body.len() + 512, 0);
+            account(
+                Pubkey::new_unique(),
+                false,
+                anchor_data(account_type, &body),
+            )
+        };
+        let vault_state = state("VaultState", &[authorities.vault.as_ref()]);
+        let verifier_state = |authority: &Pubkey, vault_program: &Pubkey| {
+            state(
+                "VerifierState",
+                &[authority.as_ref(), vault_program.as_ref()],
+            )
+        };
+        let status = |verifier: Account| {
+            check_genesis_authorities(&genesis, Some(&vault_state), Some(&verifier)).status
+        };
+        assert_eq!(
+            status(verifier_state(
+                &authorities.verifier,
+                &genesis.vault_program_id
+            )),
+            CheckStatus::Passed
+        );
+        assert_eq!(
+            status(verifier_state(
+                &Pubkey::new_unique(),
+                &genesis.vault_program_id
+            )),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            status(verifier_state(&authorities.verifier, &Pubkey::new_unique())),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            check_genesis_authorities(&genesis, None, None).status,
+            CheckStatus::Failed
+        );
+
+        let house_vault = |sol_principal: u64| {
+            state(
+                "HouseVault",
+                &[
+                    &sol_principal.to_le_bytes(),
+                    &0u64.to_le_bytes(),
+                    &sol_principal.to_le_bytes(),
+                ],
+            )
+        };
+        assert_eq!(
+            check_genesis_bankroll(&genesis, Some(&house_vault(1_000))).status,
+            CheckStatus::Passed
+        );
+        assert_eq!(
+            check_genesis_bankroll(&genesis, Some(&house_vault(999))).status,
+            CheckStatus::Failed
+        );
+
+        let registration =
+            |sequencer: Pubkey| state("SequencerRegistration", &[sequencer.as_ref()]);
+        assert_eq!(
+            check_genesis_sequencer_registration(
+                &genesis,
+                Some(&registration(authorities.sequencer))
+            )
+            .status,
+            CheckStatus::Passed
+        );
+        assert_eq!(
+            check_genesis_sequencer_registration(
+                &genesis,
+                Some(&registration(Pubkey::new_unique()))
+            )
+            .status,
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            check_genesis_sequencer_registration(&genesis, None).status,
+            CheckStatus::Failed
+        );
+
+        // The manifest's verifying key digest becomes the expected one
+        let config = SelfCheckConfig {
+            on_failure: FailureMode::Refuse,
+            expected_verifying_key_hash: None,
+            genesis: None,
+        };
+        let config = config.with_genesis(Some(genesis.clone())).unwrap();
+        assert_eq!(
+            config.expected_verifying_key_hash,
+            Some(hash(b"key").to_string())
+        );
+        let mut conflicting = config.clone();
+        conflicting.expected_verifying_key_hash = Some(hash(b"other").to_string());
+        assert!(conflicting.with_genesis(Some(genesis)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_failure_modes() {
+        let db = Database::new("test").await.unwrap(); // Schema never created
+        let keys = SequencerKeys {
+            sequencer: Arc::new(Keypair::new()),
+            vrf: Arc::new(Keypair::new()),
+        };
+        let mut config = SelfCheckConfig {
+            on_failure: FailureMode::Refuse,
+            expected_verifying_key_hash: None,
+            genesis: None,
+        };
+        assert!(run(&config, &db, &keys, &PayoutSchedule::default(), None)
+            .await
+            .is_err());
+
+        config.on_failure = FailureMode::ReadOnly;
+        let report = run(&config, &db, &keys, &PayoutSchedule::default(), None)
+            .await
+            .unwrap();
+        assert!(report.read_only);
+        assert_eq!(report.failures().count(), 1);
+
+        db.create_tables().await.unwrap();
+        let report = run(&config, &db, &keys, &PayoutSchedule::default(), None)
+            .await
+            .unwrap();
+        assert!(report.passed());
+        assert!(!report.read_only);
+        assert!(report
+            .checks
+            .iter()
+            .filter(|check| ONCHAIN_CHECKS.contains(&check.name.as_str()))
+            .all(|check| check.status == CheckStatus::Skipped));
+    }
+}
@@ -4,11 +4,16 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, RwLockWriteGuard};
 
+use crate::key_registry::{self, KeyKind, KeyRecord, KeyRegistryError};
+use crate::persistence_crypto::{is_encrypted, PersistenceCipher};
+use crate::state_root::{apply_items, state_root, SettledBalances, StateRoot};
 use crate::SettlementItem;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -61,32 +66,478 @@ pub struct SettlementBatch {
     pub retry_count: u32,
     pub error_message: Option<String>,
     pub items: Vec<SettlementItem>,
+    #[serde(default)]
+    pub cost: Option<SettlementCost>,
+    #[serde(default)]
+    pub submission_intent: Option<SubmissionIntent>,
+    #[serde(default)]
+    pub verifying_key_hash: Option<String>, // Key the stored proof verified against
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// A signed settlement transaction recorded before it is sent, so crash recovery can
+/// look up whether it landed instead of resubmitting the batch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubmissionIntent {
+    pub signature: String,
+    pub message_hash: String,
+    pub last_valid_block_height: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// On-chain cost of a settlement transaction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SettlementCost {
+    pub fee_lamports: u64,
+    pub priority_fee_lamports: u64,
+    pub compute_units_consumed: Option<u64>,
+}
+
+/// Cost metrics for batches of a given size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchSizeCost {
+    pub batch_size: usize,
+    pub batches: u64,
+    pub avg_fee_per_bet_lamports: f64,
+    pub avg_compute_units_per_bet: Option<f64>,
+}
+
+/// Aggregate on-chain settlement costs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementCostSummary {
+    pub batches: u64,
+    pub bets: u64,
+    pub total_fee_lamports: u64,
+    pub total_priority_fee_lamports: u64,
+    pub total_compute_units: u64,
+    pub avg_fee_per_batch_lamports: f64,
+    pub avg_fee_per_bet_lamports: f64,
+    pub avg_compute_units_per_bet: Option<f64>,
+    pub by_batch_size: Vec<BatchSizeCost>,
+}
+
+/// Proof coverage and totals for settled batches
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProofStats {
+    pub total_batches: u64,
+    pub proven_batches: u64,      // Confirmed with a verified ZK proof
+    pub placeholder_batches: u64, // Confirmed without a ZK proof (placeholder mode)
+    pub failed_batches: u64,
+    pub settled_bets: u64,
+    pub settled_volume: u64, // Sum of wagered amounts in confirmed batches
+}
+
+/// The most recently confirmed settlement batch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatestSettlement {
+    pub batch_id: u64,
+    pub transaction_signature: Option<String>,
+    pub confirmed_at: DateTime<Utc>,
+    pub state_root: Option<String>, // Hash of the proof (committing to final balances) sent on-chain
+}
+
+/// Current on-disk schema version of the settlement JSON file.
+/// Bump this and add a migration to MIGRATIONS whenever PersistenceData changes shape.
+pub const SCHEMA_VERSION: u32 = 5;
+
+/// MIGRATIONS[n] upgrades a version-n document to version n + 1
+const MIGRATIONS: &[fn(&mut serde_json::Value) -> Result<()>] = &[
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+];
+
+/// v0 (unversioned) -> v1: batches gain an optional on-chain `cost`
+fn migrate_v0_to_v1(document: &mut serde_json::Value) -> Result<()> {
+    let batches = document
+        .get_mut("batches")
+        .and_then(|b| b.as_object_mut())
+        .ok_or_else(|| anyhow::anyhow!("v0 settlement file has no batches map"))?;
+    for batch in batches.values_mut() {
+        let batch = batch
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("v0 settlement batch is not an object"))?;
+        batch.entry("cost").or_insert(serde_json::Value::Null);
+    }
+    Ok(())
+}
+
+/// v1 -> v2: batches gain a `submission_intent`; items gain `token` and `game`
+fn migrate_v1_to_v2(document: &mut serde_json::Value) -> Result<()> {
+    let batches = document
+        .get_mut("batches")
+        .and_then(|b| b.as_object_mut())
+        .ok_or_else(|| anyhow::anyhow!("v1 settlement file has no batches map"))?;
+    for batch in batches.values_mut() {
+        let batch = batch
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("v1 settlement batch is not an object"))?;
+        batch
+            .entry("submission_intent")
+            .or_insert(serde_json::Value::Null);
+
+        let items = batch
+            .get_mut("items")
+            .and_then(|i| i.as_array_mut())
+            .ok_or_else(|| anyhow::anyhow!("v1 settlement batch has no items"))?;
+        for item in items {
+            let item = item
+                .as_object_mut()
+                .ok_or_else(|| anyhow::anyhow!("v1 settlement item is not an object"))?;
+            item.entry("token").or_insert(serde_json::json!("sol"));
+            item.entry("game").or_insert(serde_json::json!("coinflip"));
+        }
+    }
+    Ok(())
+}
+
+/// v2 -> v3: batches gain a `verifying_key_hash`; keys are kept in `verifying_keys`
+fn migrate_v2_to_v3(document: &mut serde_json::Value) -> Result<()> {
+    let root = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("v2 settlement file is not an object"))?;
+    root.entry("verifying_keys")
+        .or_insert(serde_json::json!({}));
+    let batches = root
+        .get_mut("batches")
+        .and_then(|b| b.as_object_mut())
+        .ok_or_else(|| anyhow::anyhow!("v2 settlement file has no batches map"))?;
+    for batch in batches.values_mut() {
+        let batch = batch
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("v2 settlement batch is not an object"))?;
+        batch
+            .entry("verifying_key_hash")
+            .or_insert(serde_json::Value::Null);
+    }
+    Ok(())
+}
+
+/// v3 -> v4: signing keys in use are recorded in `signing_keys`
+fn migrate_v3_to_v4(document: &mut serde_json::Value) -> Result<()> {
+    document
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("v3 settlement file is not an object"))?
+        .entry("signing_keys")
+        .or_insert(serde_json::json!([]));
+    Ok(())
+}
+
+/// v4 -> v5: batch records are checksummed in `batch_checksums`. Records written before
+/// checksums can't be verified, so they are checksummed as found.
+fn migrate_v4_to_v5(document: &mut serde_json::Value) -> Result<()> {
+    let checksums = batch_checksums(document)
+        .ok_or_else(|| anyhow::anyhow!("v4 settlement file has no batches map"))?;
+    document["batch_checksums"] = checksums.into();
+    Ok(())
+}
+
+/// Base58 SHA-256 of a batch record's JSON. serde_json keeps object keys sorted, so the
+/// checksum doesn't depend on how the record was laid out on disk.
+fn record_checksum(record: &serde_json::Value) -> String {
+    solana_sdk::hash::hash(record.to_string().as_bytes()).to_string()
+}
+
+/// Checksums of every batch record in a settlement document, keyed like `batches`
+fn batch_checksums(
+    document: &serde_json::Value,
+) -> Option<serde_json::Map<String, serde_json::Value>> {
+    let batches = document.get("batches")?.as_object()?;
+    Some(
+        batches
+            .iter()
+            .map(|(batch_id, record)| (batch_id.clone(), record_checksum(record).into()))
+            .collect(),
+    )
+}
+
+/// Settlement file JSON: the data plus a checksum of every batch record
+fn to_document(data: &PersistenceData) -> Result<String> {
+    let mut document = serde_json::to_value(data)?;
+    let checksums = batch_checksums(&document).unwrap_or_default();
+    document["batch_checksums"] = checksums.into();
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// A batch record set aside at load because it failed its checksum or no longer parses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedRecord {
+    pub batch_id: String, // Key of the record in the file
+    pub reason: String,
+    pub record: serde_json::Value,
+}
+
+/// Take corrupted batch records out of a current-version document, so one bad record
+/// doesn't make the whole file unloadable. Their bet ids stay in `processed_bet_ids`,
+/// so the bets can't be settled again while the records are investigated.
+fn quarantine_corrupt_records(document: &mut serde_json::Value) -> Result<Vec<QuarantinedRecord>> {
+    let checksums = match document
+        .as_object_mut()
+        .and_then(|root| root.remove("batch_checksums"))
+    {
+        Some(serde_json::Value::Object(checksums)) => checksums,
+        _ => return Err(anyhow::anyhow!("Settlement file has no batch checksums")),
+    };
+    let batches = document
+        .get_mut("batches")
+        .and_then(|b| b.as_object_mut())
+        .ok_or_else(|| anyhow::anyhow!("Settlement file has no batches map"))?;
+
+    let corrupt: Vec<(String, String)> = batches
+        .iter()
+        .filter_map(|(batch_id, record)| {
+            let reason = match checksums.get(batch_id).and_then(|c| c.as_str()) {
+                None => "no checksum".to_string(),
+                Some(checksum) if checksum != record_checksum(record) => {
+                    "checksum mismatch".to_string()
+                }
+                Some(_) => serde_json::from_value::<SettlementBatch>(record.clone())
+                    .err()?
+                    .to_string(),
+            };
+            Some((batch_id.clone(), reason))
+        })
+        .collect();
+    Ok(corrupt
+        .into_iter()
+        .filter_map(|(batch_id, reason)| {
+            let record = batches.remove(&batch_id)?;
+            Some(QuarantinedRecord {
+                batch_id,
+                reason,
+                record,
+            })
+        })
+        .collect())
+}
+
+/// Parse a settlement file, migrating older schema versions to SCHEMA_VERSION.
+/// Returns the data, the version it was stored with and any corrupted batch records,
+/// which are left out of the data.
+fn load_persistence_data(
+    json_data: &str,
+) -> Result<(PersistenceData, u32, Vec<QuarantinedRecord>)> {
+    let mut document: serde_json::Value = serde_json::from_str(json_data)
+        .map_err(|e| anyhow::anyhow!("Settlement file is not valid JSON: {}", e))?;
+
+    // Files written before versioning have no version field
+    let stored_version = match document.get("version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow::anyhow!("Invalid settlement schema version: {}", v))?,
+    };
+    if stored_version > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "Settlement file has schema version {}, but this sequencer supports up to {}. Refusing to load it.",
+            stored_version,
+            SCHEMA_VERSION
+        ));
+    }
+
+    for version in stored_version..SCHEMA_VERSION {
+        MIGRATIONS[version as usize](&mut document).map_err(|e| {
+            anyhow::anyhow!(
+                "Settlement schema migration v{} -> v{} failed: {}",
+                version,
+                version + 1,
+                e
+            )
+        })?;
+        document["version"] = serde_json::json!(version + 1);
+    }
+
+    let quarantined = quarantine_corrupt_records(&mut document)?;
+    let data = serde_json::from_value(document).map_err(|e| {
+        anyhow::anyhow!(
+            "Settlement file does not match schema version {}: {}",
+            SCHEMA_VERSION,
+            e
+        )
+    })?;
+    Ok((data, stored_version, quarantined))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct PersistenceData {
+    version: u32,
     batches: HashMap<u64, SettlementBatch>,
     processed_bet_ids: std::collections::HashSet<String>,
     last_batch_id: u64,
+    verifying_keys: HashMap<String, Vec<u8>>, // Compressed Groth16 keys by base58 SHA-256
+    signing_keys: Vec<KeyRecord>,             // Every signing key used, oldest first
+}
+
+/// Decrypt (when sealed) and parse settlement file contents. Corrupted records are an
+/// error here; only startup quarantines them.
+fn decode_contents(contents: &str, cipher: Option<&PersistenceCipher>) -> Result<PersistenceData> {
+    let json_data = match (cipher, is_encrypted(contents)) {
+        (Some(cipher), true) => String::from_utf8(cipher.decrypt(contents)?.0)?,
+        (None, true) => {
+            return Err(anyhow::anyhow!(
+                "Settlement contents are encrypted but no settlement encryption keys are configured"
+            ))
+        }
+        (_, false) => contents.to_string(),
+    };
+    let (data, _, quarantined) = load_persistence_data(&json_data)?;
+    if !quarantined.is_empty() {
+        let batch_ids: Vec<&str> = quarantined.iter().map(|r| r.batch_id.as_str()).collect();
+        return Err(anyhow::anyhow!(
+            "Corrupted settlement batch records: {}",
+            batch_ids.join(", ")
+        ));
+    }
+    Ok(data)
+}
+
+impl Default for PersistenceData {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            batches: HashMap::new(),
+            processed_bet_ids: Default::default(),
+            last_batch_id: 0,
+            verifying_keys: HashMap::new(),
+            signing_keys: Vec::new(),
+        }
+    }
+}
+
+/// Replace a file through a synced temporary file and a rename, so a crash mid-write
+/// leaves either the old contents or the new, never a torn file
+async fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let temp_path = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&temp_path).await?;
+    file.write_all(contents.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+    fs::rename(&temp_path, path).await?;
+    Ok(())
+}
+
+// Lock file guarding the settlement file while it is shared between processes.
+// Holders keep it for one read-modify-write, so an old lock was left by a crashed process.
+const FILE_LOCK_RETRY: Duration = Duration::from_millis(5);
+const FILE_LOCK_STALE_AFTER: Duration = Duration::from_secs(10);
+const FILE_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    async fn acquire(file_path: &Path) -> Result<Self> {
+        let path = file_path.with_extension("json.lock");
+        let started = std::time::Instant::now();
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(&path)
+                        .and_then(|meta| meta.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > FILE_LOCK_STALE_AFTER);
+                    if stale {
+                        tracing::warn!("Removing stale settlement lock {}", path.display());
+                        std::fs::remove_file(&path).ok();
+                        continue;
+                    }
+                    if started.elapsed() > FILE_LOCK_TIMEOUT {
+                        return Err(anyhow::anyhow!(
+                            "Timed out waiting for settlement lock {}",
+                            path.display()
+                        ));
+                    }
+                    tokio::time::sleep(FILE_LOCK_RETRY).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Settled balances over the confirmed batches applied so far, so each newly confirmed
+/// batch is applied once instead of replaying every batch on each read
+#[derive(Default)]
+struct SettledCache {
+    batches: Vec<u64>, // Confirmed batches applied, by id
+    balances: SettledBalances,
+}
+
+impl SettledCache {
+    /// Apply the confirmed batches not yet applied. Starts over if a batch it applied is
+    /// no longer confirmed or a batch confirmed ahead of one with a lower id.
+    fn catch_up(&mut self, data: &PersistenceData) -> Result<()> {
+        let mut confirmed: Vec<&SettlementBatch> = data
+            .batches
+            .values()
+            .filter(|batch| batch.status == SettlementBatchStatus::Confirmed)
+            .collect();
+        confirmed.sort_by_key(|batch| batch.batch_id);
+
+        let applied = self.batches.len();
+        if confirmed.len() < applied
+            || !confirmed[..applied]
+                .iter()
+                .map(|batch| batch.batch_id)
+                .eq(self.batches.iter().copied())
+        {
+            *self = Self::default();
+        }
+        for batch in &confirmed[self.batches.len()..] {
+            apply_items(&mut self.balances, &batch.items).map_err(|e| {
+                anyhow::anyhow!("Confirmed batch {} can't be settled: {}", batch.batch_id, e)
+            })?;
+            self.batches.push(batch.batch_id);
+        }
+        Ok(())
+    }
 }
 
 pub struct SettlementPersistence {
     data: RwLock<PersistenceData>,
-    file_path: PathBuf,
+    settled: parking_lot::Mutex<SettledCache>,
+    file_path: Option<PathBuf>,             // None for in-memory databases
+    cipher: Option<Arc<PersistenceCipher>>, // Encryption at rest when configured
+    shared: bool, // Another process (the external submitter) writes the same file
 }
 
 impl SettlementPersistence {
     /// Initialize file-based persistence for settlement tracking
     pub async fn new(database_url: &str) -> Result<Self> {
-        // Convert database URL to file path for our JSON persistence
-        let file_path = if database_url.starts_with("sqlite:") {
-            let db_path = &database_url[7..];
-            Path::new(db_path).with_extension("settlement.json")
-        } else if database_url == "sqlite::memory:" {
-            // For in-memory database, use a temp file
-            std::env::temp_dir().join("settlement_memory.json")
-        } else {
-            PathBuf::from(database_url).with_extension("settlement.json")
+        Self::with_cipher(database_url, None).await
+    }
+
+    /// Initialize persistence, sealing the file with the given cipher (if any)
+    pub async fn with_cipher(
+        database_url: &str,
+        cipher: Option<Arc<PersistenceCipher>>,
+    ) -> Result<Self> {
+        let Some(file_path) = Self::file_path_for(database_url) else {
+            // In-memory database: keep settlement state in memory only
+            return Ok(Self {
+                data: RwLock::new(PersistenceData::default()),
+                settled: Default::default(),
+                file_path: None,
+                cipher,
+                shared: false,
+            });
         };
 
         // Create directory if it doesn't exist
@@ -94,26 +545,195 @@ impl SettlementPersistence {
             fs::create_dir_all(parent).await?;
         }
 
-        // Load existing data or create new
+        // Load existing data (migrating older schemas) or create new.
+        // Unreadable or unknown-version files are a hard error, never a silent reset.
+        let mut needs_rewrite = false;
         let data = if file_path.exists() {
-            let json_data = fs::read_to_string(&file_path).await?;
-            serde_json::from_str(&json_data).unwrap_or_default()
+            let contents = fs::read_to_string(&file_path).await?;
+            let json_data = match (&cipher, is_encrypted(&contents)) {
+                (Some(cipher), true) => {
+                    let (plaintext, key_id) = cipher
+                        .decrypt(&contents)
+                        .map_err(|e| anyhow::anyhow!("{}: {}", file_path.display(), e))?;
+                    if key_id != cipher.active_key_id() {
+                        tracing::info!(
+                            "Re-encrypting settlement file {} from key '{}' to '{}'",
+                            file_path.display(),
+                            key_id,
+                            cipher.active_key_id()
+                        );
+                        needs_rewrite = true;
+                    }
+                    String::from_utf8(plaintext)?
+                }
+                (None, true) => {
+                    return Err(anyhow::anyhow!(
+                        "{} is encrypted but no settlement encryption keys are configured",
+                        file_path.display()
+                    ));
+                }
+                (Some(_), false) => {
+                    tracing::info!(
+                        "Encrypting previously plaintext settlement file {}",
+                        file_path.display()
+                    );
+                    needs_rewrite = true;
+                    contents
+                }
+                (None, false) => contents,
+            };
+
+            let (data, stored_version, quarantined) = load_persistence_data(&json_data)
+                .map_err(|e| anyhow::anyhow!("{}: {}", file_path.display(), e))?;
+            if !quarantined.is_empty() {
+                Self::write_quarantine(&file_path, &quarantined, cipher.as_deref()).await?;
+                needs_rewrite = true;
+            }
+            if stored_version < SCHEMA_VERSION {
+                // Keep the original file in case the migration needs to be inspected
+                let backup_path =
+                    file_path.with_extension(format!("settlement.v{}.bak", stored_version));
+                fs::copy(&file_path, &backup_path).await?;
+                tracing::info!(
+                    "Migrated settlement file {} from schema v{} to v{} (backup at {})",
+                    file_path.display(),
+                    stored_version,
+                    SCHEMA_VERSION,
+                    backup_path.display()
+                );
+                needs_rewrite = true;
+            }
+            data
         } else {
             PersistenceData::default()
         };
 
-        Ok(Self {
+        let persistence = Self {
             data: RwLock::new(data),
-            file_path,
+            settled: Default::default(),
+            file_path: Some(file_path),
+            cipher,
+            shared: false,
+        };
+        if needs_rewrite {
+            persistence.save_to_file().await?;
+        }
+        Ok(persistence)
+    }
+
+    /// Move corrupted records to a quarantine file next to the settlement file, sealed
+    /// like it, before they are dropped from the settlement file
+    async fn write_quarantine(
+        file_path: &Path,
+        quarantined: &[QuarantinedRecord],
+        cipher: Option<&PersistenceCipher>,
+    ) -> Result<()> {
+        let quarantine_path = file_path.with_extension(format!(
+            "quarantine-{}.json",
+            Utc::now().format("%Y%m%dT%H%M%S")
+        ));
+        for record in quarantined {
+            tracing::error!(
+                "Quarantining corrupted settlement batch {} ({}) to {}",
+                record.batch_id,
+                record.reason,
+                quarantine_path.display()
+            );
+        }
+        let json_data = serde_json::to_string_pretty(quarantined)?;
+        let contents = match cipher {
+            Some(cipher) => cipher.encrypt(json_data.as_bytes())?,
+            None => json_data,
+        };
+        write_atomically(&quarantine_path, &contents).await
+    }
+
+    /// Share the settlement file with another process: every write reloads the file
+    /// under a lock file first, so neither process overwrites the other's updates
+    pub fn shared(self) -> Self {
+        Self {
+            shared: true,
+            ..self
+        }
+    }
+
+    /// Write access to the data; when shared, holds the file lock and starts from the
+    /// file's current contents. Keep the lock until the change is saved.
+    async fn write_data(
+        &self,
+    ) -> Result<(RwLockWriteGuard<'_, PersistenceData>, Option<FileLock>)> {
+        let file_lock = match (&self.file_path, self.shared) {
+            (Some(file_path), true) => Some(FileLock::acquire(file_path).await?),
+            _ => None,
+        };
+        let mut data = self.data.write().await;
+        if file_lock.is_some() {
+            *data = self.read_file().await?;
+        }
+        Ok((data, file_lock))
+    }
+
+    /// Pick up changes made by the other process sharing the settlement file
+    pub async fn reload(&self) -> Result<()> {
+        if self.shared {
+            let (_data, _file_lock) = self.write_data().await?;
+        }
+        Ok(())
+    }
+
+    async fn read_file(&self) -> Result<PersistenceData> {
+        let Some(file_path) = &self.file_path else {
+            return Ok(PersistenceData::default());
+        };
+        if !file_path.exists() {
+            return Ok(PersistenceData::default());
+        }
+        let contents = fs::read_to_string(file_path).await?;
+        decode_contents(&contents, self.cipher.as_deref())
+            .map_err(|e| anyhow::anyhow!("{}: {}", file_path.display(), e))
+    }
+
+    /// Settlement file for a database URL; None for in-memory databases
+    pub fn file_path_for(database_url: &str) -> Option<PathBuf> {
+        // Convert database URL to file path for our JSON persistence
+        if database_url == "sqlite::memory:" {
+            None
+        } else if let Some(db_path) = database_url.strip_prefix("sqlite:") {
+            Some(Path::new(db_path).with_extension("settlement.json"))
+        } else {
+            Some(PathBuf::from(database_url).with_extension("settlement.json"))
+        }
+    }
+
+    /// Serialize (and seal, when a cipher is configured) the data as written to disk
+    fn encode(&self, data: &PersistenceData) -> Result<String> {
+        let json_data = to_document(data)?;
+        Ok(match &self.cipher {
+            Some(cipher) => cipher.encrypt(json_data.as_bytes())?,
+            None => json_data,
         })
     }
 
     /// Save data to file
     async fn save_to_file(&self) -> Result<()> {
+        let Some(file_path) = &self.file_path else {
+            return Ok(());
+        };
         let data = self.data.read().await;
-        let json_data = serde_json::to_string_pretty(&*data)?;
-        fs::write(&self.file_path, json_data).await?;
-        Ok(())
+        let contents = self.encode(&data)?;
+        write_atomically(file_path, &contents).await
+    }
+
+    /// Point-in-time copy of the settlement file contents, taken under the read lock so
+    /// no write lands halfway through
+    pub async fn export_contents(&self) -> Result<String> {
+        let data = self.data.read().await;
+        self.encode(&data)
+    }
+
+    /// Check that settlement file contents decrypt and parse; returns the batch count
+    pub fn verify_contents(contents: &str, cipher: Option<&PersistenceCipher>) -> Result<usize> {
+        Ok(decode_contents(contents, cipher)?.batches.len())
     }
 
     /// Save settlement batch for crash-safe processing (Phase 3e requirement)
@@ -153,7 +773,7 @@ impl SettlementPersistence {
     pub async fn create_batch(&self, items: &[SettlementItem]) -> Result<u64> {
         let now = Utc::now();
 
-        let mut data = self.data.write().await;
+        let (mut data, _file_lock) = self.write_data().await?;
         let batch_id = data.last_batch_id + 1;
         data.last_batch_id = batch_id;
 
@@ -167,6 +787,9 @@ impl SettlementPersistence {
             retry_count: 0,
             error_message: None,
             items: items.to_vec(),
+            cost: None,
+            submission_intent: None,
+            verifying_key_hash: None,
         };
 
         // Add all bet IDs to processed set for deduplication
@@ -188,11 +811,15 @@ impl SettlementPersistence {
     }
 
     /// Create a new settlement batch with a specific ID
-    pub async fn create_batch_with_id(&self, batch_id: u64, items: &[SettlementItem]) -> Result<u64> {
+    pub async fn create_batch_with_id(
+        &self,
+        batch_id: u64,
+        items: &[SettlementItem],
+    ) -> Result<u64> {
         let now = Utc::now();
 
-        let mut data = self.data.write().await;
-        
+        let (mut data, _file_lock) = self.write_data().await?;
+
         // Update last_batch_id if this ID is higher
         if batch_id > data.last_batch_id {
             data.last_batch_id = batch_id;
@@ -208,6 +835,9 @@ impl SettlementPersistence {
             retry_count: 0,
             error_message: None,
             items: items.to_vec(),
+            cost: None,
+            submission_intent: None,
+            verifying_key_hash: None,
         };
 
         // Add all bet IDs to processed set for deduplication
@@ -237,7 +867,7 @@ impl SettlementPersistence {
     ) -> Result<()> {
         let now = Utc::now();
 
-        let mut data = self.data.write().await;
+        let (mut data, _file_lock) = self.write_data().await?;
         if let Some(batch) = data.batches.get_mut(&batch_id) {
             batch.status = status.clone();
             batch.updated_at = now;
@@ -251,16 +881,33 @@ impl SettlementPersistence {
         Ok(())
     }
 
-    /// Store proof data for a batch
-    pub async fn store_proof(&self, batch_id: u64, proof_data: &[u8]) -> Result<()> {
+    /// Store proof data for a batch, with the verifying key it verified against so the
+    /// proof can still be checked after the prover's keys change
+    pub async fn store_proof(
+        &self,
+        batch_id: u64,
+        proof_data: &[u8],
+        verifying_key: &[u8],
+    ) -> Result<()> {
         let now = Utc::now();
+        let key_hash = solana_sdk::hash::hash(verifying_key).to_string();
 
-        let mut data = self.data.write().await;
+        let (mut data, _file_lock) = self.write_data().await?;
+        key_registry::record_in_use(
+            &mut data.signing_keys,
+            KeyKind::VerifyingKey,
+            &key_hash,
+            now,
+        )?;
         if let Some(batch) = data.batches.get_mut(&batch_id) {
             batch.status = SettlementBatchStatus::Proved;
             batch.proof_data = Some(proof_data.to_vec());
+            batch.verifying_key_hash = Some(key_hash.clone());
             batch.updated_at = now;
         }
+        data.verifying_keys
+            .entry(key_hash)
+            .or_insert_with(|| verifying_key.to_vec());
         drop(data);
 
         self.save_to_file().await?;
@@ -277,10 +924,11 @@ impl SettlementPersistence {
     pub async fn store_transaction(&self, batch_id: u64, signature: &str) -> Result<()> {
         let now = Utc::now();
 
-        let mut data = self.data.write().await;
+        let (mut data, _file_lock) = self.write_data().await?;
         if let Some(batch) = data.batches.get_mut(&batch_id) {
             batch.status = SettlementBatchStatus::Submitted;
             batch.transaction_signature = Some(signature.to_string());
+            batch.submission_intent = None; // Outcome is known, nothing left to recover
             batch.updated_at = now;
         }
         drop(data);
@@ -291,6 +939,179 @@ impl SettlementPersistence {
         Ok(())
     }
 
+    /// Persist a signed transaction before sending it (must complete before the send)
+    pub async fn store_submission_intent(
+        &self,
+        batch_id: u64,
+        intent: SubmissionIntent,
+    ) -> Result<()> {
+        let (mut data, _file_lock) = self.write_data().await?;
+        let batch = data
+            .batches
+            .get_mut(&batch_id)
+            .ok_or_else(|| anyhow::anyhow!("Batch {} not found", batch_id))?;
+        batch.submission_intent = Some(intent.clone());
+        batch.updated_at = Utc::now();
+        drop(data);
+
+        self.save_to_file().await?;
+
+        tracing::info!(
+            "Stored submission intent {} for batch {}",
+            intent.signature,
+            batch_id
+        );
+        Ok(())
+    }
+
+    /// Drop a submission intent whose transaction can no longer land
+    pub async fn clear_submission_intent(&self, batch_id: u64) -> Result<()> {
+        let (mut data, _file_lock) = self.write_data().await?;
+        if let Some(batch) = data.batches.get_mut(&batch_id) {
+            batch.submission_intent = None;
+            batch.updated_at = Utc::now();
+        }
+        drop(data);
+
+        self.save_to_file().await?;
+        Ok(())
+    }
+
+    /// Batches with a transaction that may have been sent but whose outcome was never recorded
+    pub async fn get_unresolved_submission_intents(&self) -> Result<Vec<SettlementBatch>> {
+        let data = self.data.read().await;
+        Ok(data
+            .batches
+            .values()
+            .filter(|batch| {
+                batch.submission_intent.is_some()
+                    && !matches!(
+                        batch.status,
+                        SettlementBatchStatus::Confirmed | SettlementBatchStatus::Failed
+                    )
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Batches changed after `since`, for following updates made by another process
+    pub async fn get_batches_updated_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SettlementBatch>> {
+        let data = self.data.read().await;
+        Ok(data
+            .batches
+            .values()
+            .filter(|batch| batch.updated_at > since)
+            .cloned()
+            .collect())
+    }
+
+    /// Proved batches waiting for their first submission, oldest first
+    pub async fn get_batches_ready_for_submission(&self) -> Result<Vec<SettlementBatch>> {
+        let data = self.data.read().await;
+        let mut batches: Vec<SettlementBatch> = data
+            .batches
+            .values()
+            .filter(|batch| {
+                batch.status == SettlementBatchStatus::Proved && batch.submission_intent.is_none()
+            })
+            .cloned()
+            .collect();
+        batches.sort_by_key(|batch| batch.batch_id);
+        Ok(batches)
+    }
+
+    /// Record the fee and compute units paid for a batch's settlement transaction
+    pub async fn store_cost(&self, batch_id: u64, cost: SettlementCost) -> Result<()> {
+        let (mut data, _file_lock) = self.write_data().await?;
+        if let Some(batch) = data.batches.get_mut(&batch_id) {
+            batch.cost = Some(cost.clone());
+            batch.updated_at = Utc::now();
+        }
+        drop(data);
+
+        self.save_to_file().await?;
+
+        tracing::info!(
+            "Stored cost for batch {}: {} lamports fee, {:?} compute units",
+            batch_id,
+            cost.fee_lamports,
+            cost.compute_units_consumed
+        );
+        Ok(())
+    }
+
+    /// Aggregate settlement costs overall and per batch size
+    pub async fn get_cost_summary(&self) -> Result<SettlementCostSummary> {
+        let data = self.data.read().await;
+
+        let mut summary = SettlementCostSummary {
+            batches: 0,
+            bets: 0,
+            total_fee_lamports: 0,
+            total_priority_fee_lamports: 0,
+            total_compute_units: 0,
+            avg_fee_per_batch_lamports: 0.0,
+            avg_fee_per_bet_lamports: 0.0,
+            avg_compute_units_per_bet: None,
+            by_batch_size: Vec::new(),
+        };
+        // batch size -> (batches, bets, fees, metered bets, compute units)
+        let mut by_size: HashMap<usize, (u64, u64, u64, u64, u64)> = HashMap::new();
+        let mut metered_bets = 0u64;
+
+        for batch in data.batches.values() {
+            let Some(cost) = &batch.cost else {
+                continue;
+            };
+            let bets = batch.items.len() as u64;
+            summary.batches += 1;
+            summary.bets += bets;
+            summary.total_fee_lamports += cost.fee_lamports;
+            summary.total_priority_fee_lamports += cost.priority_fee_lamports;
+
+            let entry = by_size.entry(batch.items.len()).or_default();
+            entry.0 += 1;
+            entry.1 += bets;
+            entry.2 += cost.fee_lamports;
+            if let Some(units) = cost.compute_units_consumed {
+                summary.total_compute_units += units;
+                metered_bets += bets;
+                entry.3 += bets;
+                entry.4 += units;
+            }
+        }
+
+        let per = |total: u64, count: u64| {
+            if count == 0 {
+                0.0
+            } else {
+                total as f64 / count as f64
+            }
+        };
+        summary.avg_fee_per_batch_lamports = per(summary.total_fee_lamports, summary.batches);
+        summary.avg_fee_per_bet_lamports = per(summary.total_fee_lamports, summary.bets);
+        summary.avg_compute_units_per_bet =
+            (metered_bets > 0).then(|| per(summary.total_compute_units, metered_bets));
+
+        summary.by_batch_size = by_size
+            .into_iter()
+            .map(
+                |(batch_size, (batches, bets, fees, metered, units))| BatchSizeCost {
+                    batch_size,
+                    batches,
+                    avg_fee_per_bet_lamports: per(fees, bets),
+                    avg_compute_units_per_bet: (metered > 0).then(|| per(units, metered)),
+                },
+            )
+            .collect();
+        summary.by_batch_size.sort_by_key(|c| c.batch_size);
+
+        Ok(summary)
+    }
+
     /// Get pending batches that need to be retried (crash recovery)
     pub async fn get_pending_batches(&self) -> Result<Vec<SettlementBatch>> {
         let data = self.data.read().await;
@@ -312,6 +1133,149 @@ impl SettlementPersistence {
         Ok(batches)
     }
 
+    /// Get confirmed batches whose last update falls in [start, end)
+    pub async fn get_confirmed_batches_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<SettlementBatch>> {
+        let data = self.data.read().await;
+        let batches: Vec<SettlementBatch> = data
+            .batches
+            .values()
+            .filter(|batch| {
+                batch.status == SettlementBatchStatus::Confirmed
+                    && batch.updated_at >= start
+                    && batch.updated_at < end
+            })
+            .cloned()
+            .collect();
+
+        Ok(batches)
+    }
+
+    /// Every stored batch, by id
+    pub async fn get_batches(&self) -> Result<Vec<SettlementBatch>> {
+        let data = self.data.read().await;
+        let mut batches: Vec<SettlementBatch> = data.batches.values().cloned().collect();
+        batches.sort_by_key(|batch| batch.batch_id);
+        Ok(batches)
+    }
+
+    /// Confirmed batches with ids in [from, to], by id
+    pub async fn get_confirmed_batches_in_range(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<SettlementBatch>> {
+        let data = self.data.read().await;
+        let mut batches: Vec<SettlementBatch> = data
+            .batches
+            .values()
+            .filter(|batch| {
+                batch.status == SettlementBatchStatus::Confirmed
+                    && (from..=to).contains(&batch.batch_id)
+            })
+            .cloned()
+            .collect();
+        batches.sort_by_key(|batch| batch.batch_id);
+        Ok(batches)
+    }
+
+    pub async fn get_batch(&self, batch_id: u64) -> Option<SettlementBatch> {
+        self.data.read().await.batches.get(&batch_id).cloned()
+    }
+
+    /// Compressed verifying key bytes by their base58 SHA-256
+    pub async fn get_verifying_key(&self, key_hash: &str) -> Option<Vec<u8>> {
+        self.data.read().await.verifying_keys.get(key_hash).cloned()
+    }
+
+    /// Record the key in use for its kind, closing the previous key's validity window
+    pub async fn record_signing_key(&self, kind: KeyKind, key: &str) -> Result<()> {
+        let (mut data, _file_lock) = self.write_data().await?;
+        let changed = key_registry::record_in_use(&mut data.signing_keys, kind, key, Utc::now())?;
+        drop(data);
+        if changed {
+            self.save_to_file().await?;
+            tracing::info!("Recorded {:?} key {} in the key registry", kind, key);
+        }
+        Ok(())
+    }
+
+    pub async fn revoke_signing_key(
+        &self,
+        kind: KeyKind,
+        key: &str,
+        reason: &str,
+    ) -> Result<Vec<KeyRecord>, KeyRegistryError> {
+        let (mut data, _file_lock) = self.write_data().await?;
+        let revoked = key_registry::revoke(&mut data.signing_keys, kind, key, reason, Utc::now())?;
+        drop(data);
+        self.save_to_file().await?;
+        tracing::warn!("Revoked {:?} key {}: {}", kind, key, reason);
+        Ok(revoked)
+    }
+
+    /// Key registry, oldest first
+    pub async fn signing_keys(&self) -> Vec<KeyRecord> {
+        self.data.read().await.signing_keys.clone()
+    }
+
+    /// Net settled balance per player over every confirmed batch
+    pub async fn settled_balances(&self) -> Result<SettledBalances> {
+        Ok(self.settled_state().await?.0)
+    }
+
+    /// Settled balances together with the last confirmed batch they include
+    pub async fn settled_state(&self) -> Result<(SettledBalances, Option<u64>)> {
+        let data = self.data.read().await;
+        let mut settled = self.settled.lock();
+        settled.catch_up(&data)?;
+        Ok((settled.balances.clone(), settled.batches.last().copied()))
+    }
+
+    /// State root of the settled balances
+    pub async fn settled_root(&self) -> Result<StateRoot> {
+        let data = self.data.read().await;
+        let mut settled = self.settled.lock();
+        settled.catch_up(&data)?;
+        Ok(state_root(&settled.balances))
+    }
+
+    /// Whether a bet from `player_address` can still be settled. A player gets a state
+    /// tree leaf when their first batch confirms, so once the tree is nearly full a new
+    /// player is only let in if there is room next to every new player of the batches in
+    /// flight and the `queued_bets` not yet batched.
+    pub async fn admits_player(&self, player_address: &str, queued_bets: usize) -> Result<bool> {
+        let data = self.data.read().await;
+        let mut settled = self.settled.lock();
+        settled.catch_up(&data)?;
+        let balances = &settled.balances;
+        if balances.get(player_address).is_some() {
+            return Ok(true);
+        }
+
+        let new_players: HashSet<&str> = data
+            .batches
+            .values()
+            .filter(|batch| {
+                matches!(
+                    batch.status,
+                    SettlementBatchStatus::Pending
+                        | SettlementBatchStatus::Proving
+                        | SettlementBatchStatus::Proved
+                        | SettlementBatchStatus::Submitted
+                )
+            })
+            .flat_map(|batch| &batch.items)
+            .map(|item| item.player_address.as_str())
+            .filter(|player| balances.get(player).is_none())
+            .collect();
+        Ok(new_players.contains(player_address)
+            || balances.has_room(new_players.len() + queued_bets + 1))
+    }
+
     /// Check if a bet is already included in any batch (deduplication)
     pub async fn is_bet_processed(&self, bet_id: &str) -> Result<bool> {
         let data = self.data.read().await;
@@ -322,7 +1286,7 @@ impl SettlementPersistence {
     pub async fn increment_retry_count(&self, batch_id: u64) -> Result<u32> {
         let now = Utc::now();
 
-        let mut data = self.data.write().await;
+        let (mut data, _file_lock) = self.write_data().await?;
         let retry_count = if let Some(batch) = data.batches.get_mut(&batch_id) {
             batch.retry_count += 1;
             batch.updated_at = now;
@@ -337,6 +1301,60 @@ impl SettlementPersistence {
         Ok(retry_count)
     }
 
+    /// Proof coverage and settled volume across all batches
+    pub async fn get_proof_stats(&self) -> Result<ProofStats> {
+        let data = self.data.read().await;
+
+        let mut stats = ProofStats {
+            total_batches: data.batches.len() as u64,
+            proven_batches: 0,
+            placeholder_batches: 0,
+            failed_batches: 0,
+            settled_bets: 0,
+            settled_volume: 0,
+        };
+        for batch in data.batches.values() {
+            match batch.status {
+                SettlementBatchStatus::Confirmed => {
+                    if batch.proof_data.is_some() {
+                        stats.proven_batches += 1;
+                    } else {
+                        stats.placeholder_batches += 1;
+                    }
+                    stats.settled_bets += batch.items.len() as u64;
+                    stats.settled_volume += batch
+                        .items
+                        .iter()
+                        .map(|item| item.amount.unsigned_abs())
+                        .sum::<u64>();
+                }
+                SettlementBatchStatus::Failed => stats.failed_batches += 1,
+                _ => {}
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Most recently confirmed batch, with the state root its proof committed to
+    pub async fn get_latest_settlement(&self) -> Result<Option<LatestSettlement>> {
+        let data = self.data.read().await;
+        Ok(data
+            .batches
+            .values()
+            .filter(|batch| batch.status == SettlementBatchStatus::Confirmed)
+            .max_by_key(|batch| (batch.updated_at, batch.batch_id))
+            .map(|batch| LatestSettlement {
+                batch_id: batch.batch_id,
+                transaction_signature: batch.transaction_signature.clone(),
+                confirmed_at: batch.updated_at,
+                state_root: batch
+                    .proof_data
+                    .as_ref()
+                    .map(|proof| solana_sdk::hash::hash(proof).to_string()),
+            }))
+    }
+
     /// Get settlement statistics
     pub async fn get_settlement_stats(&self) -> Result<SettlementStats> {
         let data = self.data.read().await;
@@ -375,3 +1393,354 @@ pub struct SettlementStats {
     pub failed_batches: u64,
     pub confirmed_items: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V0_FILE: &str = r#"{
+        "batches": {
+            "1": {
+                "batch_id": 1,
+                "status": "Confirmed",
+                "created_at": "2024-06-01T00:00:00Z",
+                "updated_at": "2024-06-01T00:00:01Z",
+                "proof_data": null,
+                "transaction_signature": "mock_tx_1_confirmed",
+                "retry_count": 0,
+                "error_message": null,
+                "items": [{
+                    "bet_id": "bet_1",
+                    "player_address": "player",
+                    "amount": 1000,
+                    "payout": 2000,
+                    "timestamp": "2024-06-01T00:00:00Z"
+                }]
+            }
+        },
+        "processed_bet_ids": ["bet_1"],
+        "last_batch_id": 1
+    }"#;
+
+    #[test]
+    fn test_schema_migration_from_v0() {
+        let (data, stored_version, quarantined) = load_persistence_data(V0_FILE).unwrap();
+        assert_eq!(stored_version, 0);
+        assert_eq!(data.version, SCHEMA_VERSION);
+        assert_eq!(data.last_batch_id, 1);
+        assert!(data.processed_bet_ids.contains("bet_1"));
+        assert_eq!(data.batches[&1].cost, None);
+        assert_eq!(data.batches[&1].submission_intent, None);
+        assert_eq!(data.batches[&1].items[0].token, crate::SettlementToken::Sol);
+        assert_eq!(data.batches[&1].verifying_key_hash, None);
+        assert!(data.verifying_keys.is_empty());
+        assert!(data.signing_keys.is_empty());
+        assert!(quarantined.is_empty());
+
+        // Current-version files round-trip unchanged
+        let current = to_document(&data).unwrap();
+        let (data, stored_version, quarantined) = load_persistence_data(&current).unwrap();
+        assert_eq!(stored_version, SCHEMA_VERSION);
+        assert!(data.batches.contains_key(&1));
+        assert!(quarantined.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_records_are_quarantined() {
+        let dir = std::env::temp_dir().join(format!("zkcasino-corrupt-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+        let file_path = dir.join("casino.settlement.json");
+
+        let persistence = SettlementPersistence::new(&url).await.unwrap();
+        let item = |bet_id: &str| SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            payout: 0,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        };
+        let intact = persistence.create_batch(&[item("bet_1")]).await.unwrap();
+        let tampered = persistence.create_batch(&[item("bet_2")]).await.unwrap();
+        drop(persistence);
+        assert!(!dir.join("casino.settlement.json.tmp").exists());
+
+        // Flip one record's payout on disk
+        let mut document: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&file_path).unwrap()).unwrap();
+        document["batches"][tampered.to_string()]["items"][0]["payout"] = 200.into();
+        let contents = serde_json::to_string(&document).unwrap();
+        std::fs::write(&file_path, &contents).unwrap();
+        assert!(SettlementPersistence::verify_contents(&contents, None).is_err());
+
+        // Startup keeps the intact record and sets the tampered one aside
+        let persistence = SettlementPersistence::new(&url).await.unwrap();
+        assert!(persistence.get_batch(intact).await.is_some());
+        assert!(persistence.get_batch(tampered).await.is_none());
+        assert!(persistence.is_bet_processed("bet_2").await.unwrap());
+        let quarantine_file = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.to_string_lossy().contains("quarantine-"))
+            .unwrap();
+        let quarantined: Vec<QuarantinedRecord> =
+            serde_json::from_str(&std::fs::read_to_string(quarantine_file).unwrap()).unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].batch_id, tampered.to_string());
+        assert_eq!(quarantined[0].reason, "checksum mismatch");
+
+        // The rewritten file loads cleanly
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(
+            SettlementPersistence::verify_contents(&contents, None).unwrap(),
+            1
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_persistence_file() {
+        use base64::Engine;
+        let key = |byte: u8| base64::engine::general_purpose::STANDARD.encode([byte; 32]);
+        let cipher =
+            |spec: String| Some(Arc::new(PersistenceCipher::from_key_list(&spec).unwrap()));
+
+        let dir = std::env::temp_dir().join(format!("zkcasino-crypto-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+        let file_path = dir.join("casino.settlement.json");
+
+        let persistence =
+            SettlementPersistence::with_cipher(&url, cipher(format!("k1:{}", key(1))))
+                .await
+                .unwrap();
+        let batch_id = persistence.create_batch(&[]).await.unwrap();
+        drop(persistence);
+
+        // Nothing readable in plaintext on disk
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        assert!(is_encrypted(&contents));
+        assert!(!contents.contains("batches"));
+
+        // Without keys the file is a hard error, not a silent reset
+        assert!(SettlementPersistence::new(&url).await.is_err());
+
+        // Rotating keys re-seals the file with the new active key
+        let rotated = format!("k2:{},k1:{}", key(2), key(1));
+        let persistence = SettlementPersistence::with_cipher(&url, cipher(rotated))
+            .await
+            .unwrap();
+        assert!(persistence
+            .data
+            .read()
+            .await
+            .batches
+            .contains_key(&batch_id));
+        drop(persistence);
+        let contents = std::fs::read_to_string(&file_path).unwrap();
+        let only_new = PersistenceCipher::from_key_list(&format!("k2:{}", key(2))).unwrap();
+        assert_eq!(only_new.decrypt(&contents).unwrap().1, "k2");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_submission_intent_lifecycle() {
+        let dir = std::env::temp_dir().join(format!("zkcasino-intent-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+
+        let persistence = SettlementPersistence::new(&url).await.unwrap();
+        let batch_id = persistence.create_batch(&[]).await.unwrap();
+        let intent = SubmissionIntent {
+            signature: "sig_1".to_string(),
+            message_hash: "hash_1".to_string(),
+            last_valid_block_height: 100,
+            created_at: Utc::now(),
+        };
+        persistence
+            .store_submission_intent(batch_id, intent.clone())
+            .await
+            .unwrap();
+        drop(persistence);
+
+        // The intent survives a restart and is reported for recovery
+        let persistence = SettlementPersistence::new(&url).await.unwrap();
+        let unresolved = persistence
+            .get_unresolved_submission_intents()
+            .await
+            .unwrap();
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(unresolved[0].submission_intent, Some(intent));
+
+        // Recording the transaction resolves it
+        persistence
+            .store_transaction(batch_id, "sig_1")
+            .await
+            .unwrap();
+        assert!(persistence
+            .get_unresolved_submission_intents()
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(persistence
+            .store_submission_intent(
+                999,
+                SubmissionIntent {
+                    signature: "sig_2".to_string(),
+                    message_hash: "hash_2".to_string(),
+                    last_valid_block_height: 100,
+                    created_at: Utc::now(),
+                }
+            )
+            .await
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_proof_stats_and_latest_settlement() {
+        let dir = std::env::temp_dir().join(format!("zkcasino-proofs-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+        let persistence = SettlementPersistence::new(&url).await.unwrap();
+
+        let item = |bet_id: &str, amount: i64| SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: "player".to_string(),
+            amount,
+            payout: 0,
+            timestamp: Utc::now(),
+            token: crate::SettlementToken::Sol,
+            game: crate::GameKind::Coinflip,
+            vrf_proof: None,
+        };
+        assert_eq!(persistence.get_latest_settlement().await.unwrap(), None);
+
+        let placeholder = persistence.create_batch(&[item("a", 100)]).await.unwrap();
+        persistence
+            .update_batch_status(placeholder, SettlementBatchStatus::Confirmed, None)
+            .await
+            .unwrap();
+        let proven = persistence
+            .create_batch(&[item("b", 200), item("c", 300)])
+            .await
+            .unwrap();
+        persistence
+            .store_proof(proven, &[7u8; 64], &[1u8; 32])
+            .await
+            .unwrap();
+        persistence.store_transaction(proven, "sig").await.unwrap();
+        persistence
+            .update_batch_status(proven, SettlementBatchStatus::Confirmed, None)
+            .await
+            .unwrap();
+        let failed = persistence.create_batch(&[item("d", 400)]).await.unwrap();
+        persistence
+            .update_batch_status(failed, SettlementBatchStatus::Failed, None)
+            .await
+            .unwrap();
+
+        let stats = persistence.get_proof_stats().await.unwrap();
+        assert_eq!(stats.total_batches, 3);
+        assert_eq!(stats.proven_batches, 1);
+        assert_eq!(stats.placeholder_batches, 1);
+        assert_eq!(stats.failed_batches, 1);
+        assert_eq!(stats.settled_bets, 3);
+        assert_eq!(stats.settled_volume, 600);
+
+        let latest = persistence.get_latest_settlement().await.unwrap().unwrap();
+        assert_eq!(latest.batch_id, proven);
+        assert_eq!(latest.transaction_signature.as_deref(), Some("sig"));
+        assert_eq!(
+            latest.state_root,
+            Some(solana_sdk::hash::hash(&[7u8; 64]).to_string())
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_schema_rejects_unknown_or_corrupt_files() {
+        let future = format!(
+            r#"{{"version": {}, "batches": {{}}, "processed_bet_ids": [], "last_batch_id": 0}}"#,
+            SCHEMA_VERSION + 1
+        );
+        let err = load_persistence_data(&future).unwrap_err();
+        assert!(err.to_string().contains("schema version"));
+
+        assert!(load_persistence_data("not json").is_err());
+        assert!(load_persistence_data(r#"{"version": 1, "batches": 5}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_settled_state_follows_confirmations() {
+        let persistence = SettlementPersistence::new("sqlite::memory:").await.unwrap();
+        let item = |bet_id: &str, player: &str, payout: i64| SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: player.to_string(),
+            amount: 100,
+            payout,
+            timestamp: Utc::now(),
+            token: crate::SettlementToken::Sol,
+            game: crate::GameKind::Coinflip,
+            vrf_proof: None,
+        };
+        for (batch_id, player) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+            persistence
+                .save_batch(
+                    &format!("batch_{}", batch_id),
+                    vec![item(&format!("bet_{}", batch_id), player, 300)],
+                )
+                .await
+                .unwrap();
+        }
+        let confirm = |batch_id: u64, status: SettlementBatchStatus| {
+            let persistence = &persistence;
+            async move {
+                persistence
+                    .update_batch_status(batch_id, status, None)
+                    .await
+                    .unwrap()
+            }
+        };
+        let settled_players = |settled: &SettledBalances| {
+            ["alice", "bob", "carol"]
+                .into_iter()
+                .filter(|player| settled.get(player).is_some())
+                .collect::<Vec<_>>()
+        };
+
+        let (settled, last) = persistence.settled_state().await.unwrap();
+        assert!(settled.is_empty());
+        assert_eq!(last, None);
+        assert!(persistence.admits_player("carol", 0).await.unwrap());
+
+        // Batches apply in id order, each once
+        confirm(1, SettlementBatchStatus::Confirmed).await;
+        let (settled, last) = persistence.settled_state().await.unwrap();
+        assert_eq!((settled.get("alice"), last), (Some(200), Some(1)));
+        confirm(3, SettlementBatchStatus::Confirmed).await;
+        confirm(2, SettlementBatchStatus::Confirmed).await;
+        let (settled, last) = persistence.settled_state().await.unwrap();
+        assert_eq!(settled_players(&settled), ["alice", "bob", "carol"]);
+        assert_eq!(last, Some(3));
+
+        let mut expected = SettledBalances::new();
+        for (bet_id, player) in [("bet_1", "alice"), ("bet_2", "bob"), ("bet_3", "carol")] {
+            apply_items(&mut expected, &[item(bet_id, player, 300)]).unwrap();
+        }
+        assert_eq!(
+            persistence.settled_root().await.unwrap(),
+            state_root(&expected)
+        );
+
+        // A batch that stops being confirmed rebuilds the balances without it
+        confirm(2, SettlementBatchStatus::Failed).await;
+        let (settled, last) = persistence.settled_state().await.unwrap();
+        assert_eq!(settled_players(&settled), ["alice", "carol"]);
+        assert_eq!(last, Some(3));
+        assert!(persistence.admits_player("bob", 0).await.unwrap());
+    }
+}
@@ -4,17 +4,25 @@
 /// Converts SettlementItem data into SettlementBatch format for the prover,
 /// generates Groth16 proofs, and handles the proving pipeline.
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use indexmap::IndexSet;
 use prover::{
     proof_generator::{ProofGenerator, SerializableProof},
+    state_tree::root_bytes,
     witness_generator::{SettlementBatch, SettlementBet},
 };
-use solana_sdk::pubkey::Pubkey;
-use std::collections::HashMap;
-use std::str::FromStr;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::{debug, info};
-
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, info, warn};
+
+use crate::net_positions::net_positions;
+use crate::proof_cache::{batch_content_hash, ProofCache, ProofCacheConfig, ProofCacheStats};
+use crate::prover_pool::{ProverPool, ProverPoolConfig};
+use crate::solana::BetSettlement;
+use crate::state_root::{self, SettledBalances};
+use crate::submitter::bet_settlements;
 use crate::SettlementItem;
 
 /// Settlement prover configuration
@@ -26,6 +34,16 @@ pub struct SettlementProverConfig {
     pub max_bets_per_batch: usize,
     /// Initial house balance for proof generation
     pub house_initial_balance: u64,
+    /// Prove open batches while they are still filling so the final proof is usually ready at close
+    pub speculative_proving: bool,
+    /// Net each player's bets into one circuit bet, so batches are bounded by players rather than bets
+    pub net_positions: bool,
+    /// Most bets per batch the proof commits to (the on-chain bet list it binds to)
+    pub max_records: usize,
+    /// Reuse proofs for batches whose contents were already proved
+    pub proof_cache: ProofCacheConfig,
+    /// CPUs and priority of the threads proofs run on
+    pub pool: ProverPoolConfig,
 }
 
 impl Default for SettlementProverConfig {
@@ -34,85 +52,284 @@ impl Default for SettlementProverConfig {
             max_users: 5,                     // Start small for testing
             max_bets_per_batch: 3,            // Match circuit constraints
             house_initial_balance: 1_000_000, // 1M units house bankroll
+            speculative_proving: false,
+            net_positions: true,
+            max_records: 8, // Each committed bet costs ~800 constraints
+            proof_cache: ProofCacheConfig::default(),
+            pool: ProverPoolConfig::default(),
+        }
+    }
+}
+
+/// Lifecycle of the prover's Groth16 setup
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ProverStatus {
+    /// ZK proofs not enabled; batches use placeholder proofs
+    Disabled,
+    /// Groth16 keys are being loaded/generated
+    WarmingUp { since: DateTime<Utc> },
+    /// Keys loaded, proofs can be generated
+    Ready { since: DateTime<Utc> },
+    /// Setup failed; batches fall back to placeholder proofs
+    Failed { error: String },
+}
+
+impl ProverStatus {
+    /// Whether settlement can proceed without waiting on the prover
+    pub fn is_settled(&self) -> bool {
+        !matches!(self, ProverStatus::WarmingUp { .. })
+    }
+}
+
+/// Tracks prover warm-up so settlement submission can wait for keys while bet intake continues
+pub struct ProverReadiness {
+    status: watch::Sender<ProverStatus>,
+    prover: parking_lot::RwLock<Option<Arc<SettlementProver>>>,
+}
+
+impl ProverReadiness {
+    fn with_status(status: ProverStatus) -> Self {
+        Self {
+            status: watch::Sender::new(status),
+            prover: parking_lot::RwLock::new(None),
+        }
+    }
+
+    /// ZK proofs disabled
+    pub fn disabled() -> Self {
+        Self::with_status(ProverStatus::Disabled)
+    }
+
+    /// Prover setup in progress
+    pub fn warming_up() -> Self {
+        Self::with_status(ProverStatus::WarmingUp { since: Utc::now() })
+    }
+
+    pub fn status(&self) -> ProverStatus {
+        self.status.borrow().clone()
+    }
+
+    pub fn set_ready(&self, prover: Arc<SettlementProver>) {
+        *self.prover.write() = Some(prover);
+        self.status
+            .send_replace(ProverStatus::Ready { since: Utc::now() });
+    }
+
+    pub fn set_failed(&self, error: String) {
+        self.status.send_replace(ProverStatus::Failed { error });
+    }
+
+    /// Run Groth16 setup in the background, updating readiness when it completes
+    pub fn start_warm_up(self: &Arc<Self>, config: SettlementProverConfig) {
+        let readiness = self.clone();
+        tokio::spawn(async move {
+            let started = std::time::Instant::now();
+            match SettlementProver::new(config).await {
+                Ok(prover) => {
+                    info!(
+                        "Settlement Prover ready after {:.1}s",
+                        started.elapsed().as_secs_f64()
+                    );
+                    readiness.set_ready(Arc::new(prover));
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to initialize Settlement Prover: {}. Continuing with placeholder proofs.",
+                        e
+                    );
+                    readiness.set_failed(e.to_string());
+                }
+            }
+        });
+    }
+
+    /// The prover if warm-up has completed, without waiting
+    pub fn prover(&self) -> Option<Arc<SettlementProver>> {
+        self.prover.read().clone()
+    }
+
+    /// Wait for warm-up to finish; returns the prover when it is ready
+    pub async fn wait_for_prover(&self) -> Option<Arc<SettlementProver>> {
+        let mut receiver = self.status.subscribe();
+        if !receiver.borrow().is_settled() {
+            info!("Settlement waiting for prover warm-up to complete");
+            // The sender lives as long as self, so this cannot fail
+            let _ = receiver.wait_for(ProverStatus::is_settled).await;
         }
+        self.prover.read().clone()
     }
 }
 
+/// Proof generated ahead of time for a batch that was still filling
+struct SpeculativeProof {
+    bet_ids: Vec<String>,
+    /// House balance epoch the witness was built against
+    epoch: u64,
+    settlement_batch: SettlementBatch,
+    proof: SerializableProof,
+}
+
 /// Settlement prover that bridges sequencer and ZK prover
 pub struct SettlementProver {
     /// Groth16 proof generator
     proof_generator: Arc<Mutex<ProofGenerator>>,
     /// Configuration parameters
     config: SettlementProverConfig,
-    /// User balance tracking (in real implementation, this would come from database)
-    user_balances: Arc<Mutex<HashMap<u32, u64>>>,
     /// House balance tracking
     house_balance: Arc<Mutex<u64>>,
-    /// Global batch counter for unique batch IDs
+    /// Id of the last batch proved; speculation expects the next batch to follow it
     batch_counter: Arc<Mutex<u32>>,
+    /// Bumped on every house balance change; a speculative witness is only valid for one epoch
+    balance_epoch: AtomicU64,
+    /// Latest speculative proof, consumed by the next matching generate_proof
+    speculative: parking_lot::Mutex<Option<SpeculativeProof>>,
+    /// A speculative proof is being generated
+    speculating: AtomicBool,
+    /// Proofs by batch content hash
+    proof_cache: ProofCache,
+    /// Verifying key hash; cached proofs are only valid for the keys that made them
+    cache_namespace: solana_sdk::hash::Hash,
+    /// Compressed verifying key, stored with each proof it verifies
+    verifying_key: Vec<u8>,
+    /// The same key in the on-chain verifier's encoding, as registered for settlement
+    verifier_verifying_key: Vec<u8>,
+    /// Where setup and proving run, isolated from the API workers
+    pool: ProverPool,
 }
 
 impl SettlementProver {
     /// Create new settlement prover with given configuration
     pub async fn new(config: SettlementProverConfig) -> Result<Self> {
-        let mut proof_generator = ProofGenerator::new(config.max_users, config.max_bets_per_batch);
+        let pool = ProverPool::new(&config.pool)?;
 
         // Initialize the proof generator (setup Groth16 parameters)
-        proof_generator
-            .setup()
-            .map_err(|e| anyhow!("Failed to setup proof generator: {}", e))?;
+        let (max_users, max_bets) = (config.max_users, config.max_bets_per_batch);
+        let (max_records, net_positions) = (config.max_records, config.net_positions);
+        let (proof_generator, verifying_key, verifier_verifying_key) = pool
+            .run(move || -> Result<_> {
+                // Settled balances are net winnings, which bets routinely exceed
+                let mut proof_generator = ProofGenerator::new(max_users, max_bets)
+                    .with_max_records(max_records)
+                    .with_net_balances();
+                if net_positions {
+                    proof_generator = proof_generator.with_net_positions();
+                }
+                proof_generator
+                    .setup()
+                    .map_err(|e| anyhow!("Failed to setup proof generator: {}", e))?;
+                let verifying_key = proof_generator
+                    .serialize_verifying_key()
+                    .map_err(|e| anyhow!("Failed to serialize verifying key: {}", e))?;
+                let verifier_verifying_key = proof_generator
+                    .verifier_verifying_key()
+                    .map_err(|e| anyhow!("Failed to serialize verifying key: {}", e))?;
+                Ok((proof_generator, verifying_key, verifier_verifying_key))
+            })
+            .await??;
 
         let prover = Self {
             proof_generator: Arc::new(Mutex::new(proof_generator)),
             config: config.clone(),
-            user_balances: Arc::new(Mutex::new(HashMap::new())),
             house_balance: Arc::new(Mutex::new(config.house_initial_balance)),
             batch_counter: Arc::new(Mutex::new(0)),
+            balance_epoch: AtomicU64::new(0),
+            speculative: parking_lot::Mutex::new(None),
+            speculating: AtomicBool::new(false),
+            proof_cache: ProofCache::new(config.proof_cache.clone()),
+            cache_namespace: solana_sdk::hash::hash(&verifying_key),
+            verifying_key,
+            verifier_verifying_key,
+            pool,
         };
 
         info!("SettlementProver initialized with config: {:?}", config);
         Ok(prover)
     }
 
-    /// Initialize user balance (for demo purposes)
-    pub async fn init_user_balance(&self, user_id: u32, balance: u64) {
-        let mut balances = self.user_balances.lock().await;
-        balances.insert(user_id, balance);
-        info!("Initialized user {} with balance {}", user_id, balance);
+    pub fn speculative_proving(&self) -> bool {
+        self.config.speculative_proving
     }
 
-    /// Convert SettlementItem array to SettlementBatch for proof generation
+    /// Convert SettlementItem array to SettlementBatch for proof generation, as batch
+    /// `batch_id`: the id the batch is submitted under, the proof's first public input
     async fn convert_to_settlement_batch(
         &self,
+        batch_id: u64,
+        settled: &SettledBalances,
         settlement_items: &[SettlementItem],
     ) -> Result<SettlementBatch> {
-        let mut batch_counter = self.batch_counter.lock().await;
-        *batch_counter += 1;
-        let batch_id = *batch_counter;
+        let batch_id = u32::try_from(batch_id)
+            .map_err(|_| anyhow!("Batch id {} exceeds the prover's range", batch_id))?;
+        *self.batch_counter.lock().await = batch_id;
 
-        // Get current balances (snapshot)
-        let initial_balances = self.user_balances.lock().await.clone();
+        self.build_settlement_batch(settled, settlement_items, batch_id)
+            .await
+    }
+
+    /// Build the circuit witness input for `settlement_items` on top of the settled
+    /// balances. User slot i is the i-th distinct player of the batch, the order the
+    /// state tree updates their leaves in.
+    async fn build_settlement_batch(
+        &self,
+        settled: &SettledBalances,
+        settlement_items: &[SettlementItem],
+        batch_id: u32,
+    ) -> Result<SettlementBatch> {
+        let slots: IndexSet<&str> = settlement_items
+            .iter()
+            .map(|item| item.player_address.as_str())
+            .collect();
+        if slots.len() > self.config.max_users {
+            return Err(anyhow!(
+                "Batch touches {} users, exceeds maximum {}",
+                slots.len(),
+                self.config.max_users
+            ));
+        }
+        let slot_of = |player_address: &str| {
+            slots
+                .get_index_of(player_address)
+                .expect("every batch user has a slot") as u32
+        };
+
+        let (prev_state_root, players) = state_root::transition_witness(settled, settlement_items)?;
         let house_initial_balance = *self.house_balance.lock().await;
 
         // Convert settlement items to settlement bets
         let mut bets = Vec::new();
-        for item in settlement_items {
-            // Parse user address to get user_id (simplified mapping)
-            let user_id = self.parse_user_id(&item.player_address)?;
-
-            // Determine bet outcome
-            let is_win = item.payout > item.amount.abs();
-            let amount = item.amount.abs() as u64;
-
-            // For coin flip: assume heads=true, tails=false
-            // In real implementation, this would come from bet data
-            let guess = true; // Simplified for demo
-            let outcome = is_win; // If they won, outcome matches guess
-
-            let settlement_bet =
-                SettlementBet::new(user_id, amount, guess, outcome, item.bet_id.clone());
-
-            bets.push(settlement_bet);
+        if self.config.net_positions {
+            // One bet per player carrying their net change, payouts minus stakes as the
+            // state tree settles it. A player who broke even gets a zero bet, so a batch
+            // of offsetting bets is still a valid witness.
+            for position in net_positions(settlement_items) {
+                let delta = position.payout as i64 - position.wagered as i64;
+                bets.push(SettlementBet::new(
+                    slot_of(&position.player_address),
+                    delta.unsigned_abs(),
+                    true,
+                    delta > 0,
+                    position.bet_ids.join(","),
+                ));
+            }
+        } else {
+            for item in settlement_items {
+                let slot = slot_of(&item.player_address);
+
+                // Determine bet outcome
+                let is_win = item.payout > item.amount.abs();
+                let amount = item.amount.unsigned_abs();
+
+                // For coin flip: assume heads=true, tails=false
+                // In real implementation, this would come from bet data
+                let guess = true; // Simplified for demo
+                let outcome = is_win; // If they won, outcome matches guess
+
+                let settlement_bet =
+                    SettlementBet::new(slot, amount, guess, outcome, item.bet_id.clone());
+
+                bets.push(settlement_bet);
+            }
         }
 
         // Validate batch size
@@ -123,52 +340,65 @@ impl SettlementProver {
                 self.config.max_bets_per_batch
             ));
         }
+        if settlement_items.len() > self.config.max_records {
+            return Err(anyhow!(
+                "Batch too large: {} bets exceeds the {} the proof commits to",
+                settlement_items.len(),
+                self.config.max_records
+            ));
+        }
+
+        // The proof commits to the bet list exactly as it is submitted on chain
+        let records = bet_settlements(settlement_items)?
+            .iter()
+            .map(BetSettlement::to_record)
+            .collect();
 
         let settlement_batch = SettlementBatch {
             batch_id,
             bets,
-            initial_balances,
+            players,
+            prev_state_root,
             house_initial_balance,
             timestamp: chrono::Utc::now().timestamp() as u64,
+            records,
         };
 
         debug!(
-            "Converted {} settlement items to batch {}",
+            "Converted {} settlement items to batch {} ({} circuit bets)",
             settlement_items.len(),
-            batch_id
+            batch_id,
+            settlement_batch.bets.len()
         );
         Ok(settlement_batch)
     }
 
-    /// Parse user address to get user_id (simplified mapping for demo)
-    fn parse_user_id(&self, player_address: &str) -> Result<u32> {
-        // Try to parse as Pubkey first
-        if let Ok(pubkey) = Pubkey::from_str(player_address) {
-            // Use first 4 bytes of pubkey as user_id (simplified)
-            let bytes = pubkey.to_bytes();
-            let user_id = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-            return Ok(user_id % 1000); // Keep it reasonable for demo
-        }
-
-        // Fallback: hash the string
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        player_address.hash(&mut hasher);
-        let hash = hasher.finish();
-        Ok((hash % 1000) as u32) // Keep user IDs under 1000
-    }
-
-    /// Generate ZK proof for settlement batch
+    /// Generate ZK proof for settlement batch `batch_id` on top of the settled balances.
+    /// The proof only verifies on chain for a batch submitted under that id.
     pub async fn generate_proof(
         &self,
+        batch_id: u64,
+        settled: &SettledBalances,
         settlement_items: &[SettlementItem],
     ) -> Result<SerializableProof> {
         let start_time = std::time::Instant::now();
 
+        if let Some(proof) = self
+            .take_speculative_proof(batch_id, settled, settlement_items)
+            .await?
+        {
+            info!(
+                "Reused speculative proof for batch {} ({:?} at close)",
+                proof.batch_id,
+                start_time.elapsed()
+            );
+            return Ok(proof);
+        }
+
         // Convert to settlement batch format
-        let settlement_batch = self.convert_to_settlement_batch(settlement_items).await?;
+        let settlement_batch = self
+            .convert_to_settlement_batch(batch_id, settled, settlement_items)
+            .await?;
 
         info!(
             "Generating proof for batch {} with {} bets",
@@ -176,11 +406,31 @@ impl SettlementProver {
             settlement_batch.bets.len()
         );
 
-        // Generate proof using the prover library
-        let proof_generator = self.proof_generator.lock().await;
-        let proof = proof_generator
-            .generate_proof(&settlement_batch)
-            .map_err(|e| anyhow!("Proof generation failed: {}", e))?;
+        // Identical contents were proved before (retry or replay after a restart)
+        let content_hash = batch_content_hash(&self.cache_namespace, &settlement_batch);
+        if let Some(proof) = self.proof_cache.get(&content_hash) {
+            info!(
+                "Reused cached proof for batch {} (content hash {})",
+                settlement_batch.batch_id, content_hash
+            );
+            self.update_balances(&settlement_batch).await?;
+            return Ok(proof);
+        }
+
+        // Generate proof using the prover library; proving is CPU-bound, so it runs on
+        // the prover pool
+        let proof_generator = self.proof_generator.clone();
+        let witness = settlement_batch.clone();
+        let proof = self
+            .pool
+            .run(move || {
+                proof_generator
+                    .blocking_lock()
+                    .generate_proof(&witness)
+                    .map_err(|e| anyhow!("Proof generation failed: {}", e))
+            })
+            .await??;
+        self.proof_cache.insert(content_hash, &proof);
 
         let generation_time = start_time.elapsed();
         info!(
@@ -188,46 +438,128 @@ impl SettlementProver {
             settlement_batch.batch_id, generation_time
         );
 
-        // Update balances based on settlement
+        // Update the house balance based on settlement
         self.update_balances(&settlement_batch).await?;
 
         Ok(proof)
     }
 
-    /// Update user and house balances after successful proof generation
-    async fn update_balances(&self, settlement_batch: &SettlementBatch) -> Result<()> {
-        let mut user_balances = self.user_balances.lock().await;
-        let mut house_balance = self.house_balance.lock().await;
-
-        let mut total_user_delta: i64 = 0;
+    /// Prove a batch that is still filling, without committing its balance changes.
+    ///
+    /// Called again as bets arrive; each call rebuilds the witness from the current
+    /// items. When the batch closes unchanged on the same settled state,
+    /// generate_proof reuses the result. Skipped while a previous speculation is
+    /// still running.
+    pub async fn speculate(
+        &self,
+        settled: &SettledBalances,
+        settlement_items: &[SettlementItem],
+    ) -> Result<()> {
+        if !self.config.speculative_proving || settlement_items.is_empty() {
+            return Ok(());
+        }
+        if self.speculating.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let result = self.speculate_inner(settled, settlement_items).await;
+        self.speculating.store(false, Ordering::SeqCst);
+        result
+    }
 
-        for bet in &settlement_batch.bets {
-            // Get current balance or initialize if new user
-            let current_balance = user_balances.get(&bet.user_id).copied().unwrap_or(0);
+    async fn speculate_inner(
+        &self,
+        settled: &SettledBalances,
+        settlement_items: &[SettlementItem],
+    ) -> Result<()> {
+        let start_time = std::time::Instant::now();
+        let epoch = self.balance_epoch.load(Ordering::SeqCst);
+        // Batch ids are sequential, so the open batch most likely closes as the next one
+        let batch_id = *self.batch_counter.lock().await + 1;
+
+        let settlement_batch = self
+            .build_settlement_batch(settled, settlement_items, batch_id)
+            .await?;
+
+        // Proving is CPU-bound; keep it off the async workers
+        let proof_generator = self.proof_generator.clone();
+        let witness = settlement_batch.clone();
+        let proof = self
+            .pool
+            .run(move || {
+                proof_generator
+                    .blocking_lock()
+                    .generate_proof(&witness)
+                    .map_err(|e| anyhow!("Speculative proof generation failed: {}", e))
+            })
+            .await??;
+
+        // Balances moved while proving: the witness is already stale
+        if self.balance_epoch.load(Ordering::SeqCst) != epoch {
+            debug!("Discarding stale speculative proof for batch {}", batch_id);
+            return Ok(());
+        }
 
-            // Calculate balance change
-            let balance_delta = if bet.outcome == bet.guess {
-                // Win: get bet amount back plus payout
-                bet.amount as i64
-            } else {
-                // Lose: lose bet amount
-                -(bet.amount as i64)
-            };
+        debug!(
+            "Speculative proof for batch {} ({} bets) ready in {:?}",
+            batch_id,
+            settlement_items.len(),
+            start_time.elapsed()
+        );
+        *self.speculative.lock() = Some(SpeculativeProof {
+            bet_ids: settlement_items.iter().map(|i| i.bet_id.clone()).collect(),
+            epoch,
+            settlement_batch,
+            proof,
+        });
+        Ok(())
+    }
 
-            let new_balance = (current_balance as i64 + balance_delta).max(0) as u64;
-            user_balances.insert(bet.user_id, new_balance);
-            total_user_delta += balance_delta;
+    /// Consume the speculative proof if it was built for exactly this batch id, items
+    /// and balances
+    async fn take_speculative_proof(
+        &self,
+        batch_id: u64,
+        settled: &SettledBalances,
+        settlement_items: &[SettlementItem],
+    ) -> Result<Option<SerializableProof>> {
+        let Some(speculative) = self.speculative.lock().take() else {
+            return Ok(None);
+        };
 
+        let mut batch_counter = self.batch_counter.lock().await;
+        let matches = speculative.epoch == self.balance_epoch.load(Ordering::SeqCst)
+            && root_bytes(speculative.settlement_batch.prev_state_root)
+                == state_root::state_root(settled)
+            && speculative.settlement_batch.batch_id as u64 == batch_id
+            && speculative
+                .bet_ids
+                .iter()
+                .eq(settlement_items.iter().map(|i| &i.bet_id));
+        if !matches {
             debug!(
-                "User {} balance: {} -> {} (delta: {})",
-                bet.user_id, current_balance, new_balance, balance_delta
+                "Speculative proof for batch {} no longer matches, proving from scratch",
+                speculative.settlement_batch.batch_id
             );
+            return Ok(None);
         }
 
-        // Update house balance (house gains what users lose, loses what users win)
+        *batch_counter = speculative.settlement_batch.batch_id;
+        drop(batch_counter);
+        self.update_balances(&speculative.settlement_batch).await?;
+        Ok(Some(speculative.proof))
+    }
+
+    /// Update the house balance after successful proof generation. Player balances
+    /// are the settled balances, which move when the batch is confirmed.
+    async fn update_balances(&self, settlement_batch: &SettlementBatch) -> Result<()> {
+        let mut house_balance = self.house_balance.lock().await;
+
+        // House gains what users lose, loses what users win
+        let total_user_delta: i64 = settlement_batch.bets.iter().map(SettlementBet::delta).sum();
         let house_delta = -total_user_delta;
         let new_house_balance = (*house_balance as i64 + house_delta).max(0) as u64;
         *house_balance = new_house_balance;
+        self.balance_epoch.fetch_add(1, Ordering::SeqCst);
 
         debug!(
             "House balance updated by {} to {}",
@@ -237,14 +569,9 @@ impl SettlementProver {
         Ok(())
     }
 
-    /// Get current user balance
-    pub async fn get_user_balance(&self, user_id: u32) -> u64 {
-        self.user_balances
-            .lock()
-            .await
-            .get(&user_id)
-            .copied()
-            .unwrap_or(0)
+    /// Proof cache size and hit metrics
+    pub fn proof_cache_stats(&self) -> ProofCacheStats {
+        self.proof_cache.stats()
     }
 
     /// Get current house balance
@@ -252,6 +579,41 @@ impl SettlementProver {
         *self.house_balance.lock().await
     }
 
+    /// Compressed Groth16 verifying key proofs are generated for
+    pub fn verifying_key(&self) -> &[u8] {
+        &self.verifying_key
+    }
+
+    /// The verifying key as the verifier program stores it (verify_batch_proof's key)
+    pub fn verifier_verifying_key(&self) -> &[u8] {
+        &self.verifier_verifying_key
+    }
+
+    /// SHA-256 of the compressed Groth16 verifying key, so anyone can check which circuit keys proofs verify against
+    pub async fn verifying_key_hash(&self) -> Result<String> {
+        let proof_generator = self.proof_generator.lock().await;
+        let verifying_key = proof_generator
+            .serialize_verifying_key()
+            .map_err(|e| anyhow!("Failed to serialize verifying key: {}", e))?;
+        Ok(solana_sdk::hash::hash(&verifying_key).to_string())
+    }
+
+    /// Rebuild the circuit witness for `settlement_items` on top of the settled balances
+    /// and check it satisfies the circuit, without proving or touching balances
+    pub async fn check_witness(
+        &self,
+        settled: &SettledBalances,
+        settlement_items: &[SettlementItem],
+    ) -> Result<()> {
+        let settlement_batch = self
+            .build_settlement_batch(settled, settlement_items, 0)
+            .await?;
+        let proof_generator = self.proof_generator.lock().await;
+        proof_generator
+            .validate_settlement_batch(&settlement_batch)
+            .map_err(|e| anyhow!("Invalid witness: {}", e))
+    }
+
     /// Verify a proof (for testing)
     pub async fn verify_proof(&self, proof: &SerializableProof) -> Result<bool> {
         let proof_generator = self.proof_generator.lock().await;
@@ -265,28 +627,47 @@ impl SettlementProver {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bet_id::BetId;
+    use crate::solana::BatchSettlementData;
+    use crate::submitter::verifier_proof;
+    use crate::{GameKind, SettlementToken};
     use chrono::Utc;
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
 
     #[tokio::test]
-    async fn test_settlement_prover_creation() {
-        let config = SettlementProverConfig::default();
-        let prover = SettlementProver::new(config).await;
-        assert!(prover.is_ok());
+    async fn test_prover_readiness() {
+        let disabled = ProverReadiness::disabled();
+        assert_eq!(disabled.status(), ProverStatus::Disabled);
+        assert!(disabled.wait_for_prover().await.is_none());
+
+        let readiness = Arc::new(ProverReadiness::warming_up());
+        assert!(!readiness.status().is_settled());
+
+        // Settlement blocks until warm-up completes
+        let waiter = {
+            let readiness = readiness.clone();
+            tokio::spawn(async move { readiness.wait_for_prover().await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        let prover = SettlementProver::new(SettlementProverConfig::default())
+            .await
+            .unwrap();
+        readiness.set_ready(Arc::new(prover));
+        assert!(waiter.await.unwrap().is_some());
+        assert!(matches!(readiness.status(), ProverStatus::Ready { .. }));
+
+        let failed = ProverReadiness::warming_up();
+        failed.set_failed("setup failed".to_string());
+        assert!(failed.wait_for_prover().await.is_none());
     }
 
     #[tokio::test]
-    async fn test_user_id_parsing() {
+    async fn test_settlement_prover_creation() {
         let config = SettlementProverConfig::default();
-        let prover = SettlementProver::new(config).await.unwrap();
-
-        // Test with valid pubkey
-        let pubkey = Pubkey::new_unique();
-        let user_id = prover.parse_user_id(&pubkey.to_string()).unwrap();
-        assert!(user_id < 1000);
-
-        // Test with arbitrary string
-        let user_id2 = prover.parse_user_id("test_user").unwrap();
-        assert!(user_id2 < 1000);
+        let prover = SettlementProver::new(config).await;
+        assert!(prover.is_ok());
     }
 
     #[tokio::test]
@@ -294,34 +675,58 @@ mod tests {
         let config = SettlementProverConfig::default();
         let prover = SettlementProver::new(config).await.unwrap();
 
-        // Initialize some user balances
-        prover.init_user_balance(100, 10000).await;
-        prover.init_user_balance(200, 5000).await;
+        // Players settled in earlier batches
+        let mut settled = SettledBalances::new();
+        state_root::apply_items(
+            &mut settled,
+            &[
+                coinflip(1, "user200", 1000, 2000),
+                coinflip(2, "user100", 500, 0),
+            ],
+        )
+        .unwrap();
 
         let settlement_items = vec![
-            SettlementItem {
-                bet_id: "bet1".to_string(),
-                player_address: "user100".to_string(),
-                amount: -1000, // Lost bet
-                payout: 0,
-                timestamp: Utc::now(),
-            },
-            SettlementItem {
-                bet_id: "bet2".to_string(),
-                player_address: "user200".to_string(),
-                amount: 500, // Won bet
-                payout: 1000,
-                timestamp: Utc::now(),
-            },
+            coinflip(3, "user100", 1000, 0),    // Lost bet
+            coinflip(4, "user200", 500, 1000),  // Won bet
+            coinflip(5, "user300", 2000, 4000), // Won bet, first settlement
         ];
 
         let batch = prover
-            .convert_to_settlement_batch(&settlement_items)
+            .convert_to_settlement_batch(1, &settled, &settlement_items)
             .await
             .unwrap();
-        assert_eq!(batch.bets.len(), 2);
+        assert_eq!(batch.bets.len(), 3);
         assert_eq!(batch.batch_id, 1);
-        assert!(batch.initial_balances.len() >= 2);
+
+        // One leaf update per player in batch order, from the settled state's root
+        assert_eq!(
+            root_bytes(batch.prev_state_root),
+            state_root::state_root(&settled)
+        );
+        let updates: Vec<_> = batch
+            .players
+            .iter()
+            .map(|update| (update.index, update.initial_balance, update.final_balance))
+            .collect();
+        assert_eq!(
+            updates,
+            [
+                (1, Some(-500), -1500),
+                (0, Some(1000), 1500),
+                (2, None, 2000)
+            ]
+        );
+        assert_eq!(
+            batch.bets.iter().map(|bet| bet.user_id).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            root_bytes(batch.players[2].roots().1),
+            state_root::transition(&settled, &settlement_items)
+                .unwrap()
+                .1
+        );
     }
 
     #[tokio::test]
@@ -329,25 +734,253 @@ mod tests {
         let config = SettlementProverConfig::default();
         let prover = SettlementProver::new(config).await.unwrap();
 
-        // Initialize user balance
-        prover.init_user_balance(100, 10000).await;
+        let settled = SettledBalances::new();
+        let settlement_items = vec![coinflip(1, "user100", 1000, 0)]; // Lost bet
 
-        let settlement_items = vec![SettlementItem {
-            bet_id: "bet1".to_string(),
-            player_address: "user100".to_string(),
-            amount: -1000, // Lost bet
-            payout: 0,
-            timestamp: Utc::now(),
-        }];
-
-        let result = prover.generate_proof(&settlement_items).await;
+        let result = prover.generate_proof(1, &settled, &settlement_items).await;
         assert!(result.is_ok());
 
         let proof = result.unwrap();
         assert_eq!(proof.batch_id, 1);
 
+        // The proof moves the state root the verifier holds to the batch's new one
+        let (prev_root, new_root) = state_root::transition(&settled, &settlement_items).unwrap();
+        assert_eq!(
+            [
+                root_bytes(proof.public_inputs[2]),
+                root_bytes(proof.public_inputs[3])
+            ],
+            [prev_root, new_root]
+        );
+
         // Verify the proof
         let is_valid = prover.verify_proof(&proof).await.unwrap();
         assert!(is_valid);
+        assert_eq!(prover.get_house_balance().await, 1_001_000);
+    }
+
+    #[tokio::test]
+    async fn test_identical_batch_reuses_cached_proof() {
+        let config = SettlementProverConfig::default();
+        let prover = SettlementProver::new(config.clone()).await.unwrap();
+        let settled = SettledBalances::new();
+
+        let items = vec![coinflip(1, "user100", 1000, 0)];
+        let proof = prover.generate_proof(1, &settled, &items).await.unwrap();
+        assert_eq!(prover.proof_cache_stats().misses, 1);
+
+        // Replaying the same batch from the same state hits the cache
+        *prover.house_balance.lock().await = config.house_initial_balance;
+        let replayed = prover.generate_proof(1, &settled, &items).await.unwrap();
+        assert_eq!(replayed.to_bytes().unwrap(), proof.to_bytes().unwrap());
+        assert_eq!(prover.get_house_balance().await, 1_001_000);
+
+        let stats = prover.proof_cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        // The same bets on top of another settled state are proved from scratch
+        *prover.house_balance.lock().await = config.house_initial_balance;
+        let mut moved = SettledBalances::new();
+        state_root::apply_items(&mut moved, &[coinflip(9, "user100", 100, 200)]).unwrap();
+        prover.generate_proof(1, &moved, &items).await.unwrap();
+        assert_eq!(prover.proof_cache_stats().misses, 2);
+
+        // Different contents, or the same under another batch id, are proved from scratch
+        prover
+            .generate_proof(1, &settled, &[coinflip(2, "user100", 1000, 0)])
+            .await
+            .unwrap();
+        assert_eq!(prover.proof_cache_stats().misses, 3);
+        prover.generate_proof(2, &settled, &items).await.unwrap();
+        assert_eq!(prover.proof_cache_stats().misses, 4);
+    }
+
+    #[tokio::test]
+    async fn test_proof_verifies_against_submitted_batch() {
+        let prover = SettlementProver::new(SettlementProverConfig::default())
+            .await
+            .unwrap();
+        let mut settled = SettledBalances::new();
+        state_root::apply_items(&mut settled, &[coinflip(1, "user200", 1000, 2000)]).unwrap();
+        let items = vec![
+            coinflip(2, "user100", 1000, 0),
+            coinflip(3, "user200", 500, 1000),
+        ];
+        let proof = prover.generate_proof(7, &settled, &items).await.unwrap();
+        let proof_bytes = verifier_proof(&proof.to_bytes().unwrap());
+
+        // The batch as the submitter builds it for verify_and_settle
+        let (prev_state_root, new_state_root) = state_root::transition(&settled, &items).unwrap();
+        let batch = BatchSettlementData {
+            batch_id: 7,
+            sequencer_nonce: 7,
+            game_id: GameKind::Coinflip.game_id(),
+            prev_state_root,
+            new_state_root,
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [0; 32],
+            bets: bet_settlements(&items).unwrap(),
+            sequencer_signature: Signature::default(),
+        };
+        let verify = |batch: &BatchSettlementData| {
+            verifier::verify_batch_proof(
+                prover.verifier_verifying_key(),
+                &batch.to_program(),
+                &proof_bytes,
+            )
+        };
+        assert!(verify(&batch).is_ok());
+
+        // Not under another batch id, nor for other bets or state roots
+        assert!(verify(&BatchSettlementData {
+            batch_id: 8,
+            ..batch.clone()
+        })
+        .is_err());
+        let mut other_bets = batch.clone();
+        other_bets.bets[1].payout = 0;
+        assert!(verify(&other_bets).is_err());
+        assert!(verify(&BatchSettlementData {
+            new_state_root: prev_state_root,
+            ..batch.clone()
+        })
+        .is_err());
+    }
+
+    /// A valid bet ID (the committed bet list carries them as u64s) for bet `n`
+    fn bet_id(n: u64) -> String {
+        (0..32)
+            .find_map(|checksum| BetId::from_u64(n << 5 | checksum).ok())
+            .unwrap()
+            .to_string()
+    }
+
+    fn coinflip(n: u64, player: &str, amount: i64, payout: i64) -> SettlementItem {
+        SettlementItem {
+            bet_id: bet_id(n),
+            player_address: player.to_string(),
+            amount,
+            payout,
+            timestamp: Utc::now(),
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bets_netted_per_player() {
+        let prover = SettlementProver::new(SettlementProverConfig::default())
+            .await
+            .unwrap();
+        let settled = SettledBalances::new();
+
+        // More bets than the circuit holds, from two players; user200 breaks even
+        let items = vec![
+            coinflip(1, "user100", 1000, 0),
+            coinflip(2, "user200", 500, 1000),
+            coinflip(3, "user100", 300, 600),
+            coinflip(4, "user100", 200, 0),
+            coinflip(5, "user200", 500, 0),
+        ];
+        let batch = prover
+            .build_settlement_batch(&settled, &items, 1)
+            .await
+            .unwrap();
+        assert_eq!(batch.bets.len(), 2);
+        assert_eq!(batch.bets[0].amount, 900);
+        assert_eq!(
+            batch.bets[0].bet_id,
+            [bet_id(1), bet_id(3), bet_id(4)].join(",")
+        );
+        assert_eq!(batch.bets[1].amount, 0);
+        assert_eq!(batch.players[0].final_balance, -900);
+        assert_eq!(batch.players[1].final_balance, 0);
+
+        let proof = prover.generate_proof(1, &settled, &items).await.unwrap();
+        assert!(prover.verify_proof(&proof).await.unwrap());
+        assert_eq!(prover.get_house_balance().await, 1_000_900);
+
+        // Without netting the same bets don't fit the circuit
+        let per_bet = SettlementProver::new(SettlementProverConfig {
+            net_positions: false,
+            ..SettlementProverConfig::default()
+        })
+        .await
+        .unwrap();
+        assert!(per_bet.generate_proof(1, &settled, &items).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_speculative_proof_reused_when_batch_unchanged() {
+        let prover = SettlementProver::new(SettlementProverConfig {
+            speculative_proving: true,
+            ..SettlementProverConfig::default()
+        })
+        .await
+        .unwrap();
+        let settled = SettledBalances::new();
+
+        // Batch fills one bet at a time; the last speculation covers both bets
+        let mut items = vec![coinflip(1, "user100", 1000, 0)];
+        prover.speculate(&settled, &items).await.unwrap();
+        items.push(coinflip(2, "user200", 500, 1000));
+        prover.speculate(&settled, &items).await.unwrap();
+        assert!(prover.speculative.lock().is_some());
+
+        let proof = prover.generate_proof(1, &settled, &items).await.unwrap();
+        assert_eq!(proof.batch_id, 1);
+        assert!(prover.speculative.lock().is_none());
+        assert!(prover.verify_proof(&proof).await.unwrap());
+
+        // Balances are committed exactly as for a non-speculative proof
+        assert_eq!(prover.get_house_balance().await, 1_000_500);
+    }
+
+    #[tokio::test]
+    async fn test_speculative_proof_discarded_when_stale() {
+        let prover = SettlementProver::new(SettlementProverConfig {
+            speculative_proving: true,
+            ..SettlementProverConfig::default()
+        })
+        .await
+        .unwrap();
+        let mut settled = SettledBalances::new();
+
+        // A different item set than the one that closed
+        prover
+            .speculate(&settled, &[coinflip(1, "user100", 1000, 0)])
+            .await
+            .unwrap();
+        let items = vec![
+            coinflip(1, "user100", 1000, 0),
+            coinflip(2, "user100", 1000, 0),
+        ];
+        let proof = prover.generate_proof(1, &settled, &items).await.unwrap();
+        assert_eq!(proof.batch_id, 1);
+        assert!(prover.verify_proof(&proof).await.unwrap());
+        assert_eq!(prover.get_house_balance().await, 1_002_000);
+
+        // A batch settled after speculating
+        let next = [coinflip(3, "user100", 1000, 0)];
+        prover.speculate(&settled, &next).await.unwrap();
+        state_root::apply_items(&mut settled, &items).unwrap();
+        let proof = prover.generate_proof(2, &settled, &next).await.unwrap();
+        assert_eq!(proof.batch_id, 2);
+        assert!(prover.verify_proof(&proof).await.unwrap());
+        assert_eq!(
+            root_bytes(proof.public_inputs[2]),
+            state_root::state_root(&settled)
+        );
+
+        // Disabled by default
+        let prover = SettlementProver::new(SettlementProverConfig::default())
+            .await
+            .unwrap();
+        prover
+            .speculate(&settled, &[coinflip(1, "user100", 1000, 0)])
+            .await
+            .unwrap();
+        assert!(prover.speculative.lock().is_none());
     }
 }
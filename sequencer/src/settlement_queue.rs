@@ -0,0 +1,282 @@
+// Settlement queue inspection for ZK Casino
+// Tracks what sits between bet intake and the chain: items queued for settlement but not
+// yet in a closed batch (whether still in the channel or in a filling batch), and the
+// batch the settlement processor is currently persisting, proving or submitting. The
+// admin queue endpoint reports it so operators can see what is waiting when settlements
+// appear stuck.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::{GameKind, SettlementItem, SettlementToken};
+
+/// Players listed in a queue snapshot, largest backlog first
+const MAX_LISTED_PLAYERS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchPhase {
+    Persisting,
+    Proving,
+    Submitting,
+}
+
+#[derive(Debug, Clone)]
+struct InProgressBatch {
+    batch_id: Option<u64>, // Assigned once persisted
+    phase: BatchPhase,
+    started_at: DateTime<Utc>,
+    items: Vec<SettlementItem>,
+}
+
+#[derive(Debug, Default)]
+pub struct SettlementQueueMonitor {
+    unbatched: Mutex<HashMap<String, SettlementItem>>, // By bet ID
+    in_progress: Mutex<Option<InProgressBatch>>,
+}
+
+impl SettlementQueueMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An item was handed to the settlement processor
+    pub fn enqueued(&self, item: &SettlementItem) {
+        self.unbatched
+            .lock()
+            .insert(item.bet_id.clone(), item.clone());
+    }
+
+    /// The processor dropped an item without batching it (e.g. already settled)
+    pub fn discarded(&self, bet_id: &str) {
+        self.unbatched.lock().remove(bet_id);
+    }
+
+    /// A closed batch starts processing; it is reported as in progress until the
+    /// returned guard is dropped
+    pub fn start_batch(&self, batch: &[SettlementItem]) -> InProgressGuard<'_> {
+        {
+            let mut unbatched = self.unbatched.lock();
+            for item in batch {
+                unbatched.remove(&item.bet_id);
+            }
+        }
+        *self.in_progress.lock() = Some(InProgressBatch {
+            batch_id: None,
+            phase: BatchPhase::Persisting,
+            started_at: Utc::now(),
+            items: batch.to_vec(),
+        });
+        InProgressGuard { monitor: self }
+    }
+
+    pub fn snapshot(&self, now: DateTime<Utc>) -> SettlementQueueSnapshot {
+        let unbatched: Vec<SettlementItem> = self.unbatched.lock().values().cloned().collect();
+        let in_progress = self.in_progress.lock().clone();
+        SettlementQueueSnapshot {
+            unbatched: QueueSummary::of(&unbatched, now),
+            in_progress: in_progress.map(|batch| InProgressSnapshot {
+                batch_id: batch.batch_id,
+                phase: batch.phase,
+                started_at: batch.started_at,
+                age_secs: age_secs(batch.started_at, now),
+                composition: QueueSummary::of(&batch.items, now),
+            }),
+            generated_at: now,
+        }
+    }
+}
+
+/// Marks the in-progress batch done when dropped
+pub struct InProgressGuard<'a> {
+    monitor: &'a SettlementQueueMonitor,
+}
+
+impl InProgressGuard<'_> {
+    pub fn persisted(&self, batch_id: u64) {
+        if let Some(batch) = self.monitor.in_progress.lock().as_mut() {
+            batch.batch_id = Some(batch_id);
+        }
+    }
+
+    pub fn phase(&self, phase: BatchPhase) {
+        if let Some(batch) = self.monitor.in_progress.lock().as_mut() {
+            batch.phase = phase;
+        }
+    }
+}
+
+impl Drop for InProgressGuard<'_> {
+    fn drop(&mut self) {
+        *self.monitor.in_progress.lock() = None;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementQueueSnapshot {
+    pub unbatched: QueueSummary, // Queued, not yet in a closed batch
+    pub in_progress: Option<InProgressSnapshot>,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InProgressSnapshot {
+    pub batch_id: Option<u64>, // None until the batch is persisted
+    pub phase: BatchPhase,
+    pub started_at: DateTime<Utc>,
+    pub age_secs: u64,
+    pub composition: QueueSummary,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueSummary {
+    pub count: usize,
+    pub total_amount: i64,
+    pub total_payout: i64,
+    pub oldest_bet_at: Option<DateTime<Utc>>,
+    pub oldest_age_secs: Option<u64>,
+    pub by_kind: Vec<KindQueue>,   // Items that may share a batch
+    pub players: Vec<PlayerQueue>, // Largest backlog first, at most MAX_LISTED_PLAYERS
+    pub players_total: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KindQueue {
+    pub token: SettlementToken,
+    pub game: GameKind,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlayerQueue {
+    pub player_address: String,
+    pub count: usize,
+    pub total_amount: i64,
+    pub total_payout: i64,
+    pub oldest_bet_at: DateTime<Utc>,
+}
+
+impl QueueSummary {
+    fn of(items: &[SettlementItem], now: DateTime<Utc>) -> Self {
+        let mut kinds: HashMap<(SettlementToken, GameKind), usize> = HashMap::new();
+        let mut players: HashMap<&str, PlayerQueue> = HashMap::new();
+        for item in items {
+            *kinds.entry((item.token, item.game)).or_default() += 1;
+            let player = players
+                .entry(item.player_address.as_str())
+                .or_insert_with(|| PlayerQueue {
+                    player_address: item.player_address.clone(),
+                    count: 0,
+                    total_amount: 0,
+                    total_payout: 0,
+                    oldest_bet_at: item.timestamp,
+                });
+            player.count += 1;
+            player.total_amount += item.amount;
+            player.total_payout += item.payout;
+            player.oldest_bet_at = player.oldest_bet_at.min(item.timestamp);
+        }
+
+        let mut by_kind: Vec<KindQueue> = kinds
+            .into_iter()
+            .map(|((token, game), count)| KindQueue { token, game, count })
+            .collect();
+        by_kind.sort_by_key(|kind| std::cmp::Reverse(kind.count));
+        let players_total = players.len();
+        let mut players: Vec<PlayerQueue> = players.into_values().collect();
+        players.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.oldest_bet_at.cmp(&b.oldest_bet_at))
+        });
+        players.truncate(MAX_LISTED_PLAYERS);
+
+        let oldest_bet_at = items.iter().map(|item| item.timestamp).min();
+        Self {
+            count: items.len(),
+            total_amount: items.iter().map(|item| item.amount).sum(),
+            total_payout: items.iter().map(|item| item.payout).sum(),
+            oldest_bet_at,
+            oldest_age_secs: oldest_bet_at.map(|at| age_secs(at, now)),
+            by_kind,
+            players,
+            players_total,
+        }
+    }
+}
+
+fn age_secs(since: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    (now - since).num_seconds().max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn item(bet_id: &str, player: &str, age_secs: i64, now: DateTime<Utc>) -> SettlementItem {
+        SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: player.to_string(),
+            amount: 1_000,
+            payout: 0,
+            timestamp: now - Duration::seconds(age_secs),
+            token: SettlementToken::Sol,
+            game: GameKind::Coinflip,
+            vrf_proof: None,
+        }
+    }
+
+    #[test]
+    fn test_queue_snapshot() {
+        let now = Utc::now();
+        let monitor = SettlementQueueMonitor::new();
+        let items = [
+            item("bet_1", "alice", 30, now),
+            item("bet_2", "bob", 20, now),
+            item("bet_3", "alice", 10, now),
+        ];
+        for item in &items {
+            monitor.enqueued(item);
+        }
+
+        let snapshot = monitor.snapshot(now);
+        assert_eq!(snapshot.unbatched.count, 3);
+        assert_eq!(snapshot.unbatched.oldest_age_secs, Some(30));
+        assert_eq!(snapshot.unbatched.total_amount, 3_000);
+        assert_eq!(snapshot.unbatched.players_total, 2);
+        assert_eq!(snapshot.unbatched.players[0].player_address, "alice");
+        assert_eq!(snapshot.unbatched.players[0].count, 2);
+        assert_eq!(
+            snapshot.unbatched.by_kind,
+            vec![KindQueue {
+                token: SettlementToken::Sol,
+                game: GameKind::Coinflip,
+                count: 3,
+            }]
+        );
+        assert!(snapshot.in_progress.is_none());
+
+        // A closed batch moves its items from the queue to the in-progress batch
+        {
+            let guard = monitor.start_batch(&items[..2]);
+            guard.persisted(7);
+            guard.phase(BatchPhase::Proving);
+
+            let snapshot = monitor.snapshot(now);
+            assert_eq!(snapshot.unbatched.count, 1);
+            assert_eq!(snapshot.unbatched.oldest_age_secs, Some(10));
+            let in_progress = snapshot.in_progress.unwrap();
+            assert_eq!(in_progress.batch_id, Some(7));
+            assert_eq!(in_progress.phase, BatchPhase::Proving);
+            assert_eq!(in_progress.composition.count, 2);
+            assert_eq!(in_progress.composition.players_total, 2);
+        }
+        assert!(monitor.snapshot(now).in_progress.is_none());
+
+        monitor.discarded("bet_3");
+        assert_eq!(monitor.snapshot(now).unbatched.count, 0);
+    }
+}
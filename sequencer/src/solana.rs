@@ -1,7 +1,13 @@
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
 use anyhow::{anyhow, Result};
+use dashmap::DashSet;
 use log::{info, warn};
+use parking_lot::RwLock;
+use prover::{BigInteger, PrimeField};
+use solana_client::client_error::ClientError;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
+    account::Account,
     commitment_config::CommitmentConfig,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
@@ -10,8 +16,15 @@ use solana_sdk::{
     transaction::Transaction,
 };
 use std::str::FromStr;
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 
+use crate::co_signer::CoSignerClient;
+use crate::rpc_router::{RoutingPolicy, RpcEndpoint, RpcRouter};
+
+/// SPL Memo program (v2)
+const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
 /// Solana client configuration
 #[derive(Debug, Clone)]
 pub struct SolanaConfig {
@@ -19,6 +32,9 @@ pub struct SolanaConfig {
     pub commitment: CommitmentConfig,
     pub retry_attempts: u32,
     pub retry_delay_ms: u64,
+    pub region: String, // Region of this sequencer; rpc_url is assumed local
+    pub endpoints: Vec<RpcEndpoint>, // Additional (e.g. other-region) RPC endpoints
+    pub routing: RoutingPolicy,
 }
 
 impl Default for SolanaConfig {
@@ -28,6 +44,9 @@ impl Default for SolanaConfig {
             commitment: CommitmentConfig::confirmed(),
             retry_attempts: 3,
             retry_delay_ms: 1000,
+            region: "default".to_string(),
+            endpoints: Vec::new(),
+            routing: RoutingPolicy::default(),
         }
     }
 }
@@ -40,7 +59,28 @@ impl SolanaConfig {
             commitment: CommitmentConfig::confirmed(),
             retry_attempts: 5,
             retry_delay_ms: 2000,
+            region: "default".to_string(),
+            endpoints: Vec::new(),
+            routing: RoutingPolicy::default(),
+        }
+    }
+
+    /// Local validator by default, testnet when SOLANA_TESTNET=true. Extra endpoints for
+    /// latency-aware routing come from SOLANA_RPC_ENDPOINTS ("region=url,...") and SEQUENCER_REGION.
+    pub fn from_env() -> Self {
+        let mut config = if std::env::var("SOLANA_TESTNET").unwrap_or_default() == "true" {
+            Self::testnet()
+        } else {
+            Self::default()
+        };
+        if let Ok(region) = std::env::var("SEQUENCER_REGION") {
+            config.region = region;
         }
+        if let Ok(endpoints) = std::env::var("SOLANA_RPC_ENDPOINTS") {
+            config.endpoints = RpcEndpoint::parse_list(&endpoints);
+        }
+        config.routing = RoutingPolicy::from_env();
+        config
     }
 
     /// Create config for Solana Devnet
@@ -50,17 +90,37 @@ impl SolanaConfig {
             commitment: CommitmentConfig::confirmed(),
             retry_attempts: 5,
             retry_delay_ms: 2000,
+            region: "default".to_string(),
+            endpoints: Vec::new(),
+            routing: RoutingPolicy::default(),
         }
     }
 }
 
+/// Base fee charged per transaction signature
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// Game id of coinflip in the verifier's GameConfig accounts
+pub const GAME_COINFLIP: u8 = 0;
+
+/// Verifier circuit id of the coinflip accounting circuit
+pub const CIRCUIT_ACCOUNTING: u8 = 0;
+
+/// Bets per append_batch_chunk transaction when a batch has to be staged
+const BETS_PER_CHUNK: usize = 16;
+
 /// Solana client for submitting settlement transactions
 pub struct SolanaClient {
+    #[allow(dead_code)]
     client: RpcClient,
     config: SolanaConfig,
-    sequencer_keypair: Keypair,
+    router: Arc<RpcRouter>,
+    sequencer_keypair: RwLock<Arc<Keypair>>, // Replaced by the next key once it activates
+    next_sequencer_keypair: Option<Arc<Keypair>>, // Key a rotation is moving to
+    co_signer: Option<Arc<CoSignerClient>>,  // Countersigns settlements when required
     vault_program_id: Pubkey,
     verifier_program_id: Pubkey,
+    known_user_vaults: DashSet<Pubkey>, // Users whose vault PDA was seen on-chain
 }
 
 impl SolanaClient {
@@ -78,24 +138,311 @@ impl SolanaClient {
         let verifier_program_id = Pubkey::from_str(verifier_program_id)
             .map_err(|e| anyhow!("Invalid verifier program ID: {}", e))?;
 
+        // The configured rpc_url is the primary endpoint, in the sequencer's own region
+        let mut endpoints = vec![RpcEndpoint {
+            url: config.rpc_url.clone(),
+            region: config.region.clone(),
+        }];
+        for endpoint in &config.endpoints {
+            if !endpoints.iter().any(|e| e.url == endpoint.url) {
+                endpoints.push(endpoint.clone());
+            }
+        }
+        let router = Arc::new(RpcRouter::new(
+            endpoints,
+            config.region.clone(),
+            config.routing.clone(),
+            config.commitment,
+        ));
+
         Ok(Self {
             client,
             config,
-            sequencer_keypair,
+            router,
+            sequencer_keypair: RwLock::new(Arc::new(sequencer_keypair)),
+            next_sequencer_keypair: None,
+            co_signer: None,
             vault_program_id,
             verifier_program_id,
+            known_user_vaults: DashSet::new(),
         })
     }
 
+    /// Latency-aware endpoint selection shared by all requests
+    pub fn rpc_router(&self) -> Arc<RpcRouter> {
+        self.router.clone()
+    }
+
+    /// Also hold the key an on-chain sequencer rotation moves to; settlements switch
+    /// to it as soon as its registration is active
+    pub fn with_next_sequencer_keypair(mut self, keypair: Keypair) -> Self {
+        self.next_sequencer_keypair = Some(Arc::new(keypair));
+        self
+    }
+
+    /// Have every settlement countersigned by a co-signer, for a verifier that
+    /// requires one
+    pub fn with_co_signer(mut self, co_signer: CoSignerClient) -> Self {
+        self.co_signer = Some(Arc::new(co_signer));
+        self
+    }
+
+    fn co_signer_pubkey(&self) -> Option<Pubkey> {
+        self.co_signer.as_ref().map(|co_signer| co_signer.pubkey())
+    }
+
+    /// Key settlements are currently signed with
+    fn signer(&self) -> Arc<Keypair> {
+        self.sequencer_keypair.read().clone()
+    }
+
     /// Get the sequencer's public key
     pub fn sequencer_pubkey(&self) -> Pubkey {
-        self.sequencer_keypair.pubkey()
+        self.signer().pubkey()
+    }
+
+    /// Switch to the next sequencer key once the verifier accepts it. Both keys are
+    /// valid through the rotation's overlap window, so switching at the start of a
+    /// settlement leaves batches prepared under the old key able to land. Returns
+    /// whether the key changed.
+    pub async fn activate_next_sequencer_key(&self) -> Result<bool> {
+        let Some(next) = self.next_sequencer_keypair.clone() else {
+            return Ok(false);
+        };
+        if next.pubkey() == self.sequencer_pubkey() {
+            return Ok(false);
+        }
+        let address = self.registration_pda_of(&next.pubkey());
+        let Some(account) = self.get_account(&address).await? else {
+            return Ok(false);
+        };
+        let registration = OnchainSequencerRegistration::from_account_data(&account.data)?;
+        if !registration.is_active(chrono::Utc::now().timestamp()) {
+            return Ok(false);
+        }
+
+        let previous = std::mem::replace(&mut *self.sequencer_keypair.write(), next.clone());
+        info!(
+            "Sequencer key rotated from {} to {}",
+            previous.pubkey(),
+            next.pubkey()
+        );
+        Ok(true)
+    }
+
+    pub fn vault_program_id(&self) -> Pubkey {
+        self.vault_program_id
+    }
+
+    pub fn verifier_program_id(&self) -> Pubkey {
+        self.verifier_program_id
+    }
+
+    /// Global verifier state PDA
+    pub fn verifier_state_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"verifier_state"], &self.verifier_program_id).0
+    }
+
+    /// Verifying key PDA of the accounting circuit
+    pub fn verifying_key_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"verifying_key"], &self.verifier_program_id).0
+    }
+
+    /// Verifying key PDA of a circuit; circuits registered after the accounting
+    /// circuit have their id in the seeds
+    pub fn circuit_verifying_key_pda(&self, circuit_id: u8) -> Pubkey {
+        if circuit_id == CIRCUIT_ACCOUNTING {
+            return self.verifying_key_pda();
+        }
+        Pubkey::find_program_address(
+            &[b"verifying_key", &[circuit_id]],
+            &self.verifier_program_id,
+        )
+        .0
+    }
+
+    /// Registry mapping circuit ids to their verifying key accounts
+    pub fn circuit_registry_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"circuit_registry"], &self.verifier_program_id).0
+    }
+
+    /// Rolling settlement stats PDA, updated by every proven settlement
+    pub fn verifier_stats_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"verifier_stats"], &self.verifier_program_id).0
+    }
+
+    /// Payout schedule PDA of a game
+    pub fn game_config_pda(&self, game_id: u8) -> Pubkey {
+        Pubkey::find_program_address(&[b"game_config", &[game_id]], &self.verifier_program_id).0
+    }
+
+    /// Global vault state PDA
+    pub fn vault_state_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault_state"], &self.vault_program_id).0
+    }
+
+    /// House bankroll PDA
+    pub fn house_vault_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"house_vault"], &self.vault_program_id).0
+    }
+
+    /// Protocol fee vault PDA
+    pub fn fee_vault_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"fee_vault"], &self.vault_program_id).0
+    }
+
+    /// Per-user vault PDA
+    pub fn user_vault_pda(&self, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"user_vault", user.as_ref()], &self.vault_program_id).0
+    }
+
+    /// A referrer's ReferralAccount PDA
+    pub fn referral_account_pda(&self, referrer: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[vault::REFERRAL_SEED, referrer.as_ref()],
+            &self.vault_program_id,
+        )
+        .0
+    }
+
+    /// Per-user deposit address: a system account the vault's deposit_for credits to
+    /// the user's vault, so a plain wallet transfer funds it
+    pub fn deposit_address_pda(&self, user: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"deposit_address", user.as_ref()], &self.vault_program_id).0
+    }
+
+    /// Request an airdrop (devnet/localnet only) and wait for confirmation
+    pub async fn request_airdrop(&self, pubkey: &Pubkey, lamports: u64) -> Result<Signature> {
+        let signature = tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            let pubkey = *pubkey;
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                client
+                    .request_airdrop(&pubkey, lamports)
+                    .map_err(anyhow::Error::from)
+            }
+        })
+        .await??;
+
+        for _ in 0..self.config.retry_attempts * 10 {
+            let confirmed = tokio::task::spawn_blocking({
+                let rpc_url = self.router.current_url();
+                let commitment = self.config.commitment;
+                move || {
+                    let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                    client
+                        .confirm_transaction(&signature)
+                        .map_err(anyhow::Error::from)
+                }
+            })
+            .await??;
+
+            if confirmed {
+                info!(
+                    "Airdropped {} lamports to {}: {}",
+                    lamports, pubkey, signature
+                );
+                return Ok(signature);
+            }
+            sleep(Duration::from_millis(500)).await;
+        }
+
+        Err(anyhow!("Airdrop {} was not confirmed", signature))
+    }
+
+    /// Create the user's vault PDA and make an initial SOL deposit in one transaction
+    pub async fn initialize_user_vault_with_deposit(
+        &self,
+        user: &Keypair,
+        deposit_amount: u64,
+    ) -> Result<Signature> {
+        let user_pubkey = user.pubkey();
+        let user_vault = self.user_vault_pda(&user_pubkey);
+        let vault_state = self.vault_state_pda();
+        let initialize = self.initialize_user_vault_instruction(&user_pubkey);
+
+        let deposit = anchor_instruction(
+            self.vault_program_id,
+            vault::accounts::DepositSol {
+                user_vault,
+                vault_state,
+                user: user_pubkey,
+                system_program: solana_sdk::system_program::id(),
+            },
+            vault::instruction::DepositSol {
+                amount: deposit_amount,
+            },
+        );
+
+        let payer = Keypair::from_bytes(&user.to_bytes())
+            .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
+        let signature = self
+            .send_transaction_as(vec![initialize, deposit], payer)
+            .await?;
+
+        info!(
+            "User vault {} initialized for {} with {} lamports",
+            user_vault, user_pubkey, deposit_amount
+        );
+        self.known_user_vaults.insert(user_pubkey);
+        Ok(signature)
+    }
+
+    /// initialize_user_vault for `user`, who signs and pays the PDA rent
+    pub fn initialize_user_vault_instruction(&self, user: &Pubkey) -> Instruction {
+        anchor_instruction(
+            self.vault_program_id,
+            vault::accounts::InitializeUserVault {
+                user_vault: self.user_vault_pda(user),
+                vault_state: self.vault_state_pda(),
+                referral_account: None,
+                user: *user,
+                system_program: solana_sdk::system_program::id(),
+            },
+            vault::instruction::InitializeUserVault { referrer: None },
+        )
+    }
+
+    /// Unsigned initialize_user_vault transaction for a user without a vault PDA yet,
+    /// or None if the vault already exists. The user is the fee payer and only
+    /// signer, so the transaction is handed back to them rather than submitted.
+    pub async fn user_vault_initialization(&self, user: &Pubkey) -> Result<Option<Transaction>> {
+        if self.known_user_vaults.contains(user) {
+            return Ok(None);
+        }
+        if self
+            .get_account(&self.user_vault_pda(user))
+            .await?
+            .is_some()
+        {
+            self.known_user_vaults.insert(*user);
+            return Ok(None);
+        }
+
+        let instruction = self.initialize_user_vault_instruction(user);
+        let payer = *user;
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            move || -> Result<Option<Transaction>> {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let message = solana_sdk::message::Message::new_with_blockhash(
+                    &[instruction],
+                    Some(&payer),
+                    &client.get_latest_blockhash()?,
+                );
+                Ok(Some(Transaction::new_unsigned(message)))
+            }
+        })
+        .await?
     }
 
     /// Check if the Solana connection is healthy
     pub async fn health_check(&self) -> Result<()> {
         tokio::task::spawn_blocking({
-            let rpc_url = self.config.rpc_url.clone();
+            let rpc_url = self.router.current_url();
             let commitment = self.config.commitment;
             move || {
                 let client = RpcClient::new_with_commitment(rpc_url, commitment);
@@ -114,18 +461,160 @@ impl SolanaClient {
     /// Get the sequencer's SOL balance
     pub async fn get_sequencer_balance(&self) -> Result<u64> {
         let balance = tokio::task::spawn_blocking({
-            let rpc_url = self.config.rpc_url.clone();
+            let rpc_url = self.router.current_url();
             let commitment = self.config.commitment;
             let pubkey = self.sequencer_pubkey();
             move || {
                 let client = RpcClient::new_with_commitment(rpc_url, commitment);
-                client.get_balance(&pubkey)
+                client.get_balance(&pubkey).map_err(anyhow::Error::from)
             }
         })
         .await??;
         Ok(balance)
     }
 
+    /// Get the SOL balance of any account
+    pub async fn get_balance(&self, account: &Pubkey) -> Result<u64> {
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            let account = *account;
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                client.get_balance(&account).map_err(anyhow::Error::from)
+            }
+        })
+        .await?
+    }
+
+    /// Transfer SOL from `from`, which signs and pays the fee
+    pub async fn transfer_lamports(
+        &self,
+        from: &Keypair,
+        to: &Pubkey,
+        lamports: u64,
+    ) -> Result<Signature> {
+        let instruction = solana_sdk::system_instruction::transfer(&from.pubkey(), to, lamports);
+        let payer = Keypair::from_bytes(&from.to_bytes())
+            .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
+        let signature = self.send_transaction_as(vec![instruction], payer).await?;
+
+        info!(
+            "Transferred {} lamports {} -> {}: {}",
+            lamports,
+            from.pubkey(),
+            to,
+            signature
+        );
+        Ok(signature)
+    }
+
+    /// Current network fee of a single SOL transfer, as charged for withdrawal payouts
+    pub async fn estimate_transfer_fee(&self) -> Result<u64> {
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            let payer = self.sequencer_pubkey();
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let instruction = solana_sdk::system_instruction::transfer(&payer, &payer, 1);
+                let message = solana_sdk::message::Message::new_with_blockhash(
+                    &[instruction],
+                    Some(&payer),
+                    &client.get_latest_blockhash()?,
+                );
+                Ok(client.get_fee_for_message(&message)?)
+            }
+        })
+        .await?
+    }
+
+    /// Successful vault program transactions after `until`, oldest first
+    pub async fn get_vault_signatures(&self, until: Option<Signature>) -> Result<Vec<Signature>> {
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            let program_id = self.vault_program_id;
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let config = solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config {
+                    until,
+                    commitment: Some(commitment),
+                    ..Default::default()
+                };
+                let statuses = client
+                    .get_signatures_for_address_with_config(&program_id, config)
+                    .map_err(anyhow::Error::from)?;
+
+                // RPC returns newest first
+                let mut signatures = Vec::with_capacity(statuses.len());
+                for status in statuses.into_iter().rev() {
+                    if status.err.is_none() {
+                        signatures.push(Signature::from_str(&status.signature)?);
+                    }
+                }
+                Ok(signatures)
+            }
+        })
+        .await?
+    }
+
+    /// Release (win) or claim (loss) an escrowed bet; the sequencer signs as vault authority
+    pub async fn settle_escrowed_bet(
+        &self,
+        owner: &Pubkey,
+        escrow: &Pubkey,
+        won: bool,
+        payout: u64,
+    ) -> Result<Signature> {
+        let mut instruction = anchor_instruction(
+            self.vault_program_id,
+            vault::accounts::SettleEscrowedBet {
+                bet_escrow: *escrow,
+                user_vault: self.user_vault_pda(owner),
+                vault_state: self.vault_state_pda(),
+                house_vault: self.house_vault_pda(),
+                fee_vault: self.fee_vault_pda(),
+                owner: *owner,
+                authority: self.sequencer_pubkey(),
+            },
+            vault::instruction::SettleEscrowedBet { won, payout },
+        );
+        // A referred user's win pays their referrer, whose account the vault needs
+        if let Some(referrer) = self.user_referrer(owner).await? {
+            instruction.accounts.push(AccountMeta::new(
+                self.referral_account_pda(&referrer),
+                false,
+            ));
+        }
+
+        let payer = Keypair::from_bytes(&self.signer().to_bytes())
+            .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
+        let signature = self.send_transaction_as(vec![instruction], payer).await?;
+
+        info!(
+            "Escrowed bet {} settled (won: {}, payout: {}): {}",
+            escrow, won, payout, signature
+        );
+        Ok(signature)
+    }
+
+    /// Post a memo signed by the sequencer, e.g. to anchor a commitment on-chain
+    pub async fn post_memo(&self, memo: &str) -> Result<Signature> {
+        let instruction = Instruction {
+            program_id: MEMO_PROGRAM_ID,
+            accounts: vec![AccountMeta::new_readonly(self.sequencer_pubkey(), true)],
+            data: memo.as_bytes().to_vec(),
+        };
+
+        let payer = Keypair::from_bytes(&self.signer().to_bytes())
+            .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
+        let signature = self.send_transaction_as(vec![instruction], payer).await?;
+
+        info!("Memo posted: {}", signature);
+        Ok(signature)
+    }
+
     /// Submit a settlement batch to the verifier program
     pub async fn submit_settlement_batch(
         &self,
@@ -138,97 +627,465 @@ impl SolanaClient {
             batch_data.bets.len()
         );
 
-        let instruction = self.create_verify_and_settle_instruction(batch_data, proof)?;
-
-        let signature = self.send_transaction_with_retry(vec![instruction]).await?;
+        let prepared = self
+            .prepare_settlement_transaction(batch_data, proof)
+            .await?;
+        let signature = self.send_prepared_transaction(&prepared).await?;
 
         info!("Settlement batch submitted successfully: {}", signature);
         Ok(signature)
     }
 
-    /// Create verify_and_settle instruction for the verifier program
-    fn create_verify_and_settle_instruction(
+    /// Build and sign a settlement transaction without sending it. The signature is
+    /// fixed from here on, so it can be persisted before the transaction hits the network.
+    ///
+    /// A batch too large for a single transaction is first staged on-chain in chunks
+    /// (begin_batch, append_batch_chunk); the prepared transaction is then the
+    /// finalize_and_verify call, which is the only one that settles anything.
+    pub async fn prepare_settlement_transaction(
         &self,
         batch_data: BatchSettlementData,
         proof: Vec<u8>,
-    ) -> Result<Instruction> {
-        // Derive verifier state PDA
-        let (verifier_state, _) =
-            Pubkey::find_program_address(&[b"verifier_state"], &self.verifier_program_id);
-
-        // Create instruction data
-        let mut instruction_data = Vec::new();
-
-        // Add instruction discriminator (8 bytes for verify_and_settle)
-        // This would be computed from the method name hash in a real implementation
-        instruction_data.extend_from_slice(&[0x12, 0x34, 0x56, 0x78, 0xab, 0xcd, 0xef, 0x90]);
+    ) -> Result<PreparedTransaction> {
+        if let Err(e) = self.activate_next_sequencer_key().await {
+            warn!(
+                "Could not check the next sequencer key's registration: {}",
+                e
+            );
+        }
+        // Every settlement path checks the sequencer's signature over the batch
+        let mut batch_data = batch_data;
+        batch_data.sign(&self.signer());
+        let proof_for_co_signer = proof.clone();
+        let instructions =
+            self.create_verify_and_settle_instructions(batch_data.clone(), proof.clone())?;
+        let instructions = if self.fits_in_transaction(&instructions) {
+            instructions
+        } else {
+            self.stage_batch(&batch_data).await?;
+            self.finalize_and_verify_instructions(&batch_data, proof)
+        };
+        let payer = Keypair::from_bytes(&self.signer().to_bytes())
+            .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
 
-        // Serialize batch data and proof (simplified for Phase 2)
-        let serialized_batch = serde_json::to_vec(&batch_data)
-            .map_err(|e| anyhow!("Failed to serialize batch data: {}", e))?;
-        instruction_data.extend_from_slice(&(serialized_batch.len() as u32).to_le_bytes());
-        instruction_data.extend_from_slice(&serialized_batch);
+        let (mut transaction, last_valid_block_height) = tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            move || -> Result<(Transaction, u64)> {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let (recent_blockhash, last_valid_block_height) =
+                    client.get_latest_blockhash_with_commitment(commitment)?;
 
-        instruction_data.extend_from_slice(&(proof.len() as u32).to_le_bytes());
-        instruction_data.extend_from_slice(&proof);
+                // A required co-signer signs separately
+                let mut transaction =
+                    Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+                transaction.partial_sign(&[&payer], recent_blockhash);
+                Ok((transaction, last_valid_block_height))
+            }
+        })
+        .await??;
 
-        let instruction = Instruction {
-            program_id: self.verifier_program_id,
-            accounts: vec![
-                AccountMeta::new(verifier_state, false),
-                AccountMeta::new_readonly(self.sequencer_pubkey(), true),
-                AccountMeta::new_readonly(solana_sdk::sysvar::instructions::id(), false),
-            ],
-            data: instruction_data,
-        };
+        if let Some(co_signer) = &self.co_signer {
+            // The co-signer checks the batch as the sequencer signed it
+            co_signer
+                .co_sign(&batch_data, &proof_for_co_signer, &mut transaction)
+                .await?;
+        }
 
-        Ok(instruction)
+        Ok(PreparedTransaction {
+            signature: transaction.signatures[0],
+            message_hash: solana_sdk::hash::hash(&transaction.message_data()),
+            last_valid_block_height,
+            transaction,
+        })
     }
 
-    /// Send transaction with retry logic
-    async fn send_transaction_with_retry(
+    /// Send a prepared transaction, resending the same signed bytes on retry so that
+    /// at most one copy can ever land
+    pub async fn send_prepared_transaction(
         &self,
-        instructions: Vec<Instruction>,
+        prepared: &PreparedTransaction,
     ) -> Result<Signature> {
         for attempt in 1..=self.config.retry_attempts {
-            match self.send_transaction(instructions.clone()).await {
-                Ok(signature) => return Ok(signature),
-                Err(e) => {
-                    if attempt == self.config.retry_attempts {
-                        return Err(e);
-                    }
-                    warn!("Transaction attempt {} failed: {}. Retrying...", attempt, e);
+            // Re-read the route each attempt so a resend goes to the failover endpoint
+            let result = tokio::task::spawn_blocking({
+                let router = self.router.clone();
+                let rpc_url = router.current_url();
+                let commitment = self.config.commitment;
+                let transaction = prepared.transaction.clone();
+                move || {
+                    let client = RpcClient::new_with_commitment(rpc_url.clone(), commitment);
+                    client
+                        .send_and_confirm_transaction(&transaction)
+                        .inspect_err(|e| router.report_client_error(&rpc_url, e))
+                        .map_err(anyhow::Error::from)
+                }
+            })
+            .await?;
+
+            let error = match result {
+                Ok(signature) => {
+                    info!("Transaction confirmed: {}", signature);
+                    return Ok(signature);
+                }
+                Err(e) => e,
+            };
+
+            // The send may have failed after the transaction reached the cluster
+            match self
+                .check_submission_status(&prepared.signature, prepared.last_valid_block_height)
+                .await
+            {
+                Ok(SubmissionStatus::Landed) => return Ok(prepared.signature),
+                Ok(SubmissionStatus::FailedOnChain(reason)) => {
+                    return Err(anyhow!(
+                        "Transaction {} failed: {}",
+                        prepared.signature,
+                        reason
+                    ))
+                }
+                Ok(SubmissionStatus::Expired) => {
+                    return Err(anyhow!(
+                        "Transaction {} expired without landing: {}",
+                        prepared.signature,
+                        error
+                    ))
+                }
+                Ok(SubmissionStatus::InFlight) | Err(_) if attempt < self.config.retry_attempts => {
+                    warn!(
+                        "Transaction attempt {} failed: {}. Resending...",
+                        attempt, error
+                    );
                     sleep(Duration::from_millis(self.config.retry_delay_ms)).await;
                 }
+                _ => return Err(error),
             }
         }
         unreachable!()
     }
 
-    /// Send a single transaction
-    async fn send_transaction(&self, instructions: Vec<Instruction>) -> Result<Signature> {
+    /// Look up whether a previously signed transaction landed, failed, or can no longer land
+    pub async fn check_submission_status(
+        &self,
+        signature: &Signature,
+        last_valid_block_height: u64,
+    ) -> Result<SubmissionStatus> {
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            let signature = *signature;
+            move || -> Result<SubmissionStatus> {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let status = client
+                    .get_signature_status_with_commitment_and_history(&signature, commitment, true)?
+                    .map(|result| result.map_err(|e| e.to_string()));
+                // Read the height after the status so a landing in between isn't reported as expired
+                let block_height = client.get_block_height()?;
+                Ok(classify_submission(
+                    status,
+                    block_height,
+                    last_valid_block_height,
+                ))
+            }
+        })
+        .await?
+    }
+
+    /// Simulate the transaction that would settle a batch, without signing for real or
+    /// sending anything. A batch too large for one transaction can't be finalized
+    /// before it is staged, so its begin_batch instruction is simulated instead; that
+    /// still checks registration, batch order and the starting state root.
+    pub async fn simulate_settlement(
+        &self,
+        batch_data: &BatchSettlementData,
+        proof: Vec<u8>,
+    ) -> Result<SettlementSimulation> {
+        let mut batch_data = batch_data.clone();
+        batch_data.sign(&self.signer());
+        let instructions = self.create_verify_and_settle_instructions(batch_data.clone(), proof)?;
+        let (instruction_name, instructions) = if self.fits_in_transaction(&instructions) {
+            ("verify_and_settle", instructions)
+        } else {
+            (
+                "begin_batch",
+                vec![self.begin_batch_instruction(&batch_data)],
+            )
+        };
+        // The settlement instruction comes last, after any signature check
+        let instruction_data_len = instructions.last().map_or(0, |i| i.data.len());
+        let payer = Keypair::from_bytes(&self.signer().to_bytes())
+            .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
+
+        let result = tokio::task::spawn_blocking({
+            let router = self.router.clone();
+            let rpc_url = router.current_url();
+            let commitment = self.config.commitment;
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url.clone(), commitment);
+                // The node swaps in a recent blockhash and skips signature checks, so a
+                // required co-signer's signature can be left out
+                let mut transaction =
+                    Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+                transaction.partial_sign(&[&payer], solana_sdk::hash::Hash::default());
+                let config = solana_client::rpc_config::RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(commitment),
+                    ..Default::default()
+                };
+                client
+                    .simulate_transaction_with_config(&transaction, config)
+                    .inspect_err(|e| router.report_client_error(&rpc_url, e))
+                    .map(|response| response.value)
+                    .map_err(anyhow::Error::from)
+            }
+        })
+        .await??;
+
+        Ok(SettlementSimulation {
+            instruction: instruction_name.to_string(),
+            instruction_data_len,
+            error: result.err.map(|e| e.to_string()),
+            logs: result.logs.unwrap_or_default(),
+            units_consumed: result.units_consumed,
+        })
+    }
+
+    /// Create verify_and_settle instruction for the verifier program, signing the batch
+    /// and preceded by the ed25519 check of that signature
+    fn create_verify_and_settle_instructions(
+        &self,
+        mut batch_data: BatchSettlementData,
+        proof: Vec<u8>,
+    ) -> Result<Vec<Instruction>> {
+        batch_data.sign(&self.signer());
+        let signature_instruction = ed25519_signature_instruction(
+            &self.sequencer_pubkey(),
+            &batch_data.sequencer_signature,
+            &batch_data.batch_hash(),
+        );
+
+        let circuit_id = batch_data.circuit_id();
+        let instruction = anchor_instruction(
+            self.verifier_program_id,
+            verifier::accounts::VerifyAndSettle {
+                verifier_state: self.verifier_state_pda(),
+                circuit_registry: self.circuit_registry_pda(),
+                verifying_key: self.circuit_verifying_key_pda(circuit_id),
+                game_config: self.game_config_pda(batch_data.game_id),
+                sequencer: self.sequencer_pubkey(), // Pays for the batch record
+                sequencer_registration: self.sequencer_registration_pda(),
+                batch_record: self.batch_record_address(batch_data.batch_id),
+                verifier_stats: self.verifier_stats_pda(),
+                vault_caller: self.vault_caller_pda(), // Commits the state root to the vault
+                vault_state: self.vault_state_pda(),
+                vault_program: self.vault_program_id,
+                instructions_sysvar: solana_sdk::sysvar::instructions::id(),
+                co_signer: self.co_signer_pubkey(),
+                system_program: solana_sdk::system_program::id(),
+            },
+            verifier::instruction::VerifyAndSettle {
+                circuit_id,
+                batch_data: batch_data.to_program(),
+                proof,
+            },
+        );
+
+        Ok(vec![signature_instruction, instruction])
+    }
+
+    /// Whether a transaction holding just these instructions fits in a packet
+    fn fits_in_transaction(&self, instructions: &[Instruction]) -> bool {
+        let transaction = Transaction::new_with_payer(instructions, Some(&self.sequencer_pubkey()));
+        // Signature count (compact-u16, one byte here), signatures, then the message
+        let size = 1
+            + 64 * transaction.message.header.num_required_signatures as usize
+            + transaction.message_data().len();
+        size <= solana_sdk::packet::PACKET_DATA_SIZE
+    }
+
+    /// Upload a batch's bets into the sequencer's staging PDA, discarding any batch
+    /// left staged by an earlier attempt
+    async fn stage_batch(&self, batch_data: &BatchSettlementData) -> Result<()> {
+        let payer = || {
+            Keypair::from_bytes(&self.signer().to_bytes())
+                .map_err(|e| anyhow!("Failed to clone keypair: {}", e))
+        };
+        if self.get_account(&self.batch_staging_pda()).await?.is_some() {
+            warn!(
+                "Discarding previously staged batch before staging batch {}",
+                batch_data.batch_id
+            );
+            self.send_transaction_as(vec![self.cancel_batch_instruction()], payer()?)
+                .await?;
+        }
+
+        self.send_transaction_as(vec![self.begin_batch_instruction(batch_data)], payer()?)
+            .await?;
+        for (index, chunk) in batch_data.bets.chunks(BETS_PER_CHUNK).enumerate() {
+            let offset = (index * BETS_PER_CHUNK) as u32;
+            self.send_transaction_as(
+                vec![self.append_batch_chunk_instruction(offset, chunk)],
+                payer()?,
+            )
+            .await?;
+        }
+        info!(
+            "Staged batch {} ({} bets) in {} chunks",
+            batch_data.batch_id,
+            batch_data.bets.len(),
+            batch_data.bets.len().div_ceil(BETS_PER_CHUNK)
+        );
+        Ok(())
+    }
+
+    /// Staging PDA for batches submitted in chunks; one per sequencer
+    pub fn batch_staging_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"batch_staging", self.sequencer_pubkey().as_ref()],
+            &self.verifier_program_id,
+        )
+        .0
+    }
+
+    pub fn sequencer_registration_pda(&self) -> Pubkey {
+        self.registration_pda_of(&self.sequencer_pubkey())
+    }
+
+    /// Allowlist registration PDA of a sequencer key
+    fn registration_pda_of(&self, sequencer: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"sequencer", sequencer.as_ref()],
+            &self.verifier_program_id,
+        )
+        .0
+    }
+
+    fn begin_batch_instruction(&self, batch_data: &BatchSettlementData) -> Instruction {
+        anchor_instruction(
+            self.verifier_program_id,
+            verifier::accounts::BeginBatch {
+                verifier_state: self.verifier_state_pda(),
+                sequencer: self.sequencer_pubkey(),
+                sequencer_registration: self.sequencer_registration_pda(),
+                batch_staging: self.batch_staging_pda(),
+                system_program: solana_sdk::system_program::id(),
+            },
+            verifier::instruction::BeginBatch {
+                batch_id: batch_data.batch_id,
+                sequencer_nonce: batch_data.sequencer_nonce,
+                game_id: batch_data.game_id,
+                prev_state_root: batch_data.prev_state_root,
+                new_state_root: batch_data.new_state_root,
+                vrf_pubkey: batch_data.vrf_pubkey,
+                vrf_outputs_root: batch_data.vrf_outputs_root,
+                bet_count: batch_data.bets.len() as u32,
+                sequencer_signature: batch_data.sequencer_signature.into(),
+            },
+        )
+    }
+
+    fn append_batch_chunk_instruction(&self, offset: u32, bets: &[BetSettlement]) -> Instruction {
+        anchor_instruction(
+            self.verifier_program_id,
+            verifier::accounts::AppendBatchChunk {
+                sequencer: self.sequencer_pubkey(),
+                batch_staging: self.batch_staging_pda(),
+            },
+            verifier::instruction::AppendBatchChunk {
+                offset,
+                bets: bets.iter().map(BetSettlement::to_program).collect(),
+            },
+        )
+    }
+
+    /// finalize_and_verify for a staged batch, preceded by the ed25519 check of the
+    /// signature it was staged with
+    fn finalize_and_verify_instructions(
+        &self,
+        batch_data: &BatchSettlementData,
+        proof: Vec<u8>,
+    ) -> Vec<Instruction> {
+        let signature_instruction = ed25519_signature_instruction(
+            &self.sequencer_pubkey(),
+            &batch_data.sequencer_signature,
+            &batch_data.batch_hash(),
+        );
+        let circuit_id = batch_data.circuit_id();
+        let instruction = anchor_instruction(
+            self.verifier_program_id,
+            verifier::accounts::FinalizeAndVerify {
+                verifier_state: self.verifier_state_pda(),
+                circuit_registry: self.circuit_registry_pda(),
+                verifying_key: self.circuit_verifying_key_pda(circuit_id),
+                game_config: self.game_config_pda(batch_data.game_id),
+                sequencer: self.sequencer_pubkey(), // Pays for the batch record
+                sequencer_registration: self.sequencer_registration_pda(),
+                batch_staging: self.batch_staging_pda(), // Closed to the sequencer
+                batch_record: self.batch_record_address(batch_data.batch_id),
+                verifier_stats: self.verifier_stats_pda(),
+                vault_caller: self.vault_caller_pda(), // Commits the state root to the vault
+                vault_state: self.vault_state_pda(),
+                vault_program: self.vault_program_id,
+                instructions_sysvar: solana_sdk::sysvar::instructions::id(),
+                co_signer: self.co_signer_pubkey(),
+                system_program: solana_sdk::system_program::id(),
+            },
+            verifier::instruction::FinalizeAndVerify { circuit_id, proof },
+        );
+        vec![signature_instruction, instruction]
+    }
+
+    fn cancel_batch_instruction(&self) -> Instruction {
+        anchor_instruction(
+            self.verifier_program_id,
+            verifier::accounts::CancelBatch {
+                sequencer: self.sequencer_pubkey(),
+                batch_staging: self.batch_staging_pda(),
+            },
+            verifier::instruction::CancelBatch {},
+        )
+    }
+
+    /// Verifier PDA that signs the verifier's vault calls
+    pub fn vault_caller_pda(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[verifier::vault_cpi::VAULT_CALLER_SEED],
+            &self.verifier_program_id,
+        )
+        .0
+    }
+
+    /// Send a single transaction signed and paid for by the given keypair
+    async fn send_transaction_as(
+        &self,
+        instructions: Vec<Instruction>,
+        payer: Keypair,
+    ) -> Result<Signature> {
         let (recent_blockhash, signature) = tokio::task::spawn_blocking({
-            let rpc_url = self.config.rpc_url.clone();
+            let router = self.router.clone();
+            let rpc_url = router.current_url();
             let commitment = self.config.commitment;
-            let sequencer_keypair = Keypair::from_bytes(&self.sequencer_keypair.to_bytes())
-                .map_err(|e| anyhow!("Failed to clone keypair: {}", e))?;
 
             move || -> Result<(solana_sdk::hash::Hash, Signature)> {
-                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let client = RpcClient::new_with_commitment(rpc_url.clone(), commitment);
+                let report = |e: &ClientError| router.report_client_error(&rpc_url, e);
 
                 // Get recent blockhash
-                let recent_blockhash = client.get_latest_blockhash()?;
+                let recent_blockhash = client.get_latest_blockhash().inspect_err(report)?;
 
                 // Create and sign transaction
                 let transaction = Transaction::new_signed_with_payer(
                     &instructions,
-                    Some(&sequencer_keypair.pubkey()),
-                    &[&sequencer_keypair],
+                    Some(&payer.pubkey()),
+                    &[&payer],
                     recent_blockhash,
                 );
 
                 // Send transaction
-                let signature = client.send_and_confirm_transaction(&transaction)?;
+                let signature = client
+                    .send_and_confirm_transaction(&transaction)
+                    .inspect_err(report)?;
                 Ok((recent_blockhash, signature))
             }
         })
@@ -249,6 +1106,11 @@ impl SolanaClient {
         let batch_data = BatchSettlementData {
             batch_id,
             sequencer_nonce: batch_id,
+            game_id: GAME_COINFLIP,
+            prev_state_root: [0; 32],
+            new_state_root: [0; 32],
+            vrf_pubkey: Pubkey::default(),
+            vrf_outputs_root: [0; 32],
             bets: vec![
                 BetSettlement {
                     bet_id: batch_id * 100 + 1,
@@ -267,6 +1129,7 @@ impl SolanaClient {
                     payout: 0, // Loss: 0x
                 },
             ],
+            sequencer_signature: Signature::default(),
         };
 
         // Create dummy proof
@@ -278,7 +1141,7 @@ impl SolanaClient {
     /// Get transaction status and logs
     pub async fn get_transaction_logs(&self, signature: &Signature) -> Result<Vec<String>> {
         let logs = tokio::task::spawn_blocking({
-            let rpc_url = self.config.rpc_url.clone();
+            let rpc_url = self.router.current_url();
             let commitment = self.config.commitment;
             let signature = *signature;
             move || {
@@ -316,6 +1179,162 @@ impl SolanaClient {
         Ok(logs)
     }
 
+    /// Fee, priority fee and compute units paid by a confirmed transaction
+    pub async fn get_transaction_cost(
+        &self,
+        signature: &Signature,
+    ) -> Result<crate::settlement_persistence::SettlementCost> {
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            let signature = *signature;
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                let config = solana_client::rpc_config::RpcTransactionConfig {
+                    encoding: Some(solana_transaction_status::UiTransactionEncoding::Json),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                };
+
+                let transaction = client.get_transaction_with_config(&signature, config)?;
+                let meta = transaction
+                    .transaction
+                    .meta
+                    .ok_or_else(|| anyhow!("Transaction {} has no metadata", signature))?;
+
+                let signatures = match &transaction.transaction.transaction {
+                    solana_transaction_status::EncodedTransaction::Json(tx) => {
+                        tx.signatures.len() as u64
+                    }
+                    _ => 1,
+                };
+                let base_fee = signatures * LAMPORTS_PER_SIGNATURE;
+
+                Ok(crate::settlement_persistence::SettlementCost {
+                    fee_lamports: meta.fee,
+                    priority_fee_lamports: meta.fee.saturating_sub(base_fee),
+                    compute_units_consumed: meta.compute_units_consumed.into(),
+                })
+            }
+        })
+        .await?
+    }
+
+    /// A finalized transaction with its including block and the `depth` blocks built on
+    /// top of it; None until the transaction is finalized and buried that deep
+    pub async fn get_settlement_confirmation(
+        &self,
+        signature: &Signature,
+        depth: usize,
+    ) -> Result<Option<crate::finality_proof::SettlementConfirmation>> {
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let signature = *signature;
+            move || {
+                let finalized = CommitmentConfig::finalized();
+                let client = RpcClient::new_with_commitment(rpc_url, finalized);
+                match client.get_signature_status_with_commitment(&signature, finalized)? {
+                    None => return Ok(None),
+                    Some(Err(e)) => return Err(anyhow!("Transaction {} failed: {}", signature, e)),
+                    Some(Ok(())) => {}
+                }
+
+                let config = solana_client::rpc_config::RpcTransactionConfig {
+                    encoding: Some(solana_transaction_status::UiTransactionEncoding::Base64),
+                    commitment: Some(finalized),
+                    max_supported_transaction_version: Some(0),
+                };
+                let transaction = client.get_transaction_with_config(&signature, config)?;
+                let slot = transaction.slot;
+                let encoded = match transaction.transaction.transaction {
+                    solana_transaction_status::EncodedTransaction::Binary(
+                        data,
+                        solana_transaction_status::TransactionBinaryEncoding::Base64,
+                    ) => data,
+                    _ => return Err(anyhow!("Transaction {} was not base64-encoded", signature)),
+                };
+
+                let slots =
+                    client.get_blocks_with_limit_and_commitment(slot, depth + 1, finalized)?;
+                if slots.len() <= depth {
+                    return Ok(None);
+                }
+                let block_config = solana_client::rpc_config::RpcBlockConfig {
+                    encoding: None,
+                    transaction_details: Some(solana_transaction_status::TransactionDetails::None),
+                    rewards: Some(false),
+                    commitment: Some(finalized),
+                    max_supported_transaction_version: Some(0),
+                };
+                let blocks = slots
+                    .into_iter()
+                    .map(|slot| {
+                        let block = client.get_block_with_config(slot, block_config)?;
+                        Ok(crate::finality_proof::BlockLink {
+                            slot,
+                            parent_slot: block.parent_slot,
+                            blockhash: block.blockhash,
+                            previous_blockhash: block.previous_blockhash,
+                            block_time: block.block_time,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Some(crate::finality_proof::SettlementConfirmation {
+                    slot,
+                    transaction: encoded,
+                    blocks,
+                }))
+            }
+        })
+        .await?
+    }
+
+    /// Batch record PDA the verifier creates when it settles a batch
+    pub fn batch_record_address(&self, batch_id: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"batch", &batch_id.to_le_bytes()],
+            &self.verifier_program_id,
+        )
+        .0
+    }
+
+    /// Fetch a batch's on-chain record; None until the batch has settled
+    pub async fn get_batch_record(&self, batch_id: u64) -> Result<Option<OnchainBatchRecord>> {
+        self.get_account(&self.batch_record_address(batch_id))
+            .await?
+            .map(|account| OnchainBatchRecord::from_account_data(&account.data))
+            .transpose()
+    }
+
+    /// The referrer recorded in a user's vault; None for unreferred users or users
+    /// without a vault
+    pub async fn user_referrer(&self, user: &Pubkey) -> Result<Option<Pubkey>> {
+        let Some(account) = self.get_account(&self.user_vault_pda(user)).await? else {
+            return Ok(None);
+        };
+        let user_vault = vault::UserVault::try_deserialize(&mut account.data.as_slice())
+            .map_err(|e| anyhow!("Failed to decode user vault of {}: {}", user, e))?;
+        Ok(user_vault.referrer)
+    }
+
+    /// Fetch an account; None if it does not exist
+    pub async fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        let address = *address;
+        tokio::task::spawn_blocking({
+            let rpc_url = self.router.current_url();
+            let commitment = self.config.commitment;
+            move || {
+                let client = RpcClient::new_with_commitment(rpc_url, commitment);
+                client
+                    .get_account_with_commitment(&address, commitment)
+                    .map(|response| response.value)
+                    .map_err(anyhow::Error::from)
+            }
+        })
+        .await?
+    }
+
     /// Reconcile off-chain database with on-chain ledger state (Phase 3e requirement)
     /// This ensures our local database matches the actual on-chain state
     pub async fn reconcile_with_onchain_state(
@@ -334,13 +1353,39 @@ impl SolanaClient {
         for batch in off_chain_batches {
             report.total_batches_checked += 1;
 
-            // For now, we'll check transaction signatures if they exist
+            // The batch record is the on-chain source of truth for settled batches
+            match self.get_batch_record(batch.batch_id).await {
+                Ok(Some(record)) => {
+                    report.onchain_confirmed += 1;
+                    if record.bet_count as usize != batch.items.len() {
+                        report.discrepancies.push(format!(
+                            "Batch {} settled {} bets on-chain but has {} off-chain",
+                            batch.batch_id,
+                            record.bet_count,
+                            batch.items.len()
+                        ));
+                    }
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    report.discrepancies.push(format!(
+                        "Failed to read batch record for batch {}: {}",
+                        batch.batch_id, e
+                    ));
+                    continue;
+                }
+            }
+
+            // No record: check transaction signatures if they exist
             if let Some(tx_sig) = &batch.transaction_signature {
                 match self.verify_transaction_status(tx_sig).await {
                     Ok(confirmed) => {
                         if confirmed {
-                            report.onchain_confirmed += 1;
-                            info!("Batch {} confirmed on-chain: {}", batch.batch_id, tx_sig);
+                            report.discrepancies.push(format!(
+                                "Batch {} transaction {} confirmed but no batch record exists",
+                                batch.batch_id, tx_sig
+                            ));
                         } else {
                             report.discrepancies.push(format!(
                                 "Batch {} transaction {} not confirmed on-chain",
@@ -373,7 +1418,7 @@ impl SolanaClient {
         let sig = Signature::from_str(signature)?;
 
         tokio::task::spawn_blocking({
-            let rpc_url = self.config.rpc_url.clone();
+            let rpc_url = self.router.current_url();
             let commitment = self.config.commitment;
             move || {
                 let client = RpcClient::new_with_commitment(rpc_url, commitment);
@@ -408,12 +1453,65 @@ impl SolanaClient {
     }
 }
 
+/// A signed transaction that has not necessarily been sent yet
+#[derive(Debug, Clone)]
+pub struct PreparedTransaction {
+    pub transaction: Transaction,
+    pub signature: Signature,
+    pub message_hash: solana_sdk::hash::Hash,
+    pub last_valid_block_height: u64, // Blockhash expires once the chain passes this height
+}
+
+/// Outcome of a previously signed transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubmissionStatus {
+    Landed,
+    FailedOnChain(String),
+    InFlight, // Not seen yet, but its blockhash is still valid
+    Expired,  // Not seen and its blockhash has expired: it can never land
+}
+
+/// Classify a signature status lookup against the transaction's blockhash expiry
+pub fn classify_submission(
+    status: Option<std::result::Result<(), String>>,
+    current_block_height: u64,
+    last_valid_block_height: u64,
+) -> SubmissionStatus {
+    match status {
+        Some(Ok(())) => SubmissionStatus::Landed,
+        Some(Err(reason)) => SubmissionStatus::FailedOnChain(reason),
+        None if current_block_height > last_valid_block_height => SubmissionStatus::Expired,
+        None => SubmissionStatus::InFlight,
+    }
+}
+
+/// Instruction for one of our Anchor programs, built from the account and argument
+/// types the program crate generates, so account order and argument layout are checked
+/// by the compiler against the program itself rather than kept in sync by hand
+fn anchor_instruction(
+    program_id: Pubkey,
+    accounts: impl ToAccountMetas,
+    args: impl InstructionData,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
 /// Batch settlement data structure (matches verifier program)
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BatchSettlementData {
     pub batch_id: u64,
     pub sequencer_nonce: u64,
+    pub game_id: u8, // Selects the verifier's GameConfig the payouts are checked against
+    pub prev_state_root: [u8; 32],
+    pub new_state_root: [u8; 32],
+    pub vrf_pubkey: Pubkey, // VRF key the bets' outcomes were derived with
+    pub vrf_outputs_root: [u8; 32], // vrf::merkle_root over the bets' transcript leaves
     pub bets: Vec<BetSettlement>,
+    pub sequencer_signature: Signature, // Over batch_hash(); set by sign() when submitting
 }
 
 /// Individual bet settlement (matches verifier program)
@@ -427,7 +1525,231 @@ pub struct BetSettlement {
     pub payout: u64,    // Calculated payout amount
 }
 
+impl BatchSettlementData {
+    /// Circuit the batch is proven with; every game settles through the accounting
+    /// circuit until games get circuits of their own
+    pub fn circuit_id(&self) -> u8 {
+        CIRCUIT_ACCOUNTING
+    }
+
+    /// Hash the proof's public input commits to (the verifier's compute_batch_hash),
+    /// also what the sequencer signs
+    pub fn batch_hash(&self) -> [u8; 32] {
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.batch_id.to_le_bytes());
+        data.extend_from_slice(&self.sequencer_nonce.to_le_bytes());
+        data.push(self.game_id);
+        data.extend_from_slice(&(self.bets.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.prev_state_root);
+        data.extend_from_slice(&self.new_state_root);
+        data.extend_from_slice(self.vrf_pubkey.as_ref());
+        data.extend_from_slice(&self.vrf_outputs_root);
+        for bet in &self.bets {
+            bet.encode(&mut data);
+        }
+        // Top bits cleared so it is a BN254 scalar
+        let mut hash = solana_sdk::hash::hash(&data).to_bytes();
+        hash[0] &= 0x1f;
+        hash
+    }
+
+    /// Poseidon commitment to the bet list the proof carries as a public input (the
+    /// verifier's compute_batch_commitment), big-endian
+    pub fn batch_commitment(&self) -> [u8; 32] {
+        let records: Vec<prover::BetRecord> =
+            self.bets.iter().map(BetSettlement::to_record).collect();
+        let bytes = prover::batch_commitment(&records)
+            .into_bigint()
+            .to_bytes_be();
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(&bytes);
+        commitment
+    }
+
+    pub fn sign(&mut self, keypair: &Keypair) {
+        self.sequencer_signature = keypair.sign_message(&self.batch_hash());
+    }
+
+    /// The verifier program's form of the batch, as verify_and_settle takes it
+    pub(crate) fn to_program(&self) -> verifier::BatchSettlementData {
+        verifier::BatchSettlementData {
+            batch_id: self.batch_id,
+            sequencer_nonce: self.sequencer_nonce,
+            game_id: self.game_id,
+            prev_state_root: self.prev_state_root,
+            new_state_root: self.new_state_root,
+            vrf_pubkey: self.vrf_pubkey,
+            vrf_outputs_root: self.vrf_outputs_root,
+            bets: self.bets.iter().map(BetSettlement::to_program).collect(),
+            sequencer_signature: self.sequencer_signature.into(),
+        }
+    }
+}
+
+/// ed25519 program instruction checking one signature, with the public key, signature
+/// and message all in its own data; the verifier looks for it right before
+/// verify_and_settle
+pub fn ed25519_signature_instruction(
+    signer: &Pubkey,
+    signature: &Signature,
+    message: &[u8],
+) -> Instruction {
+    use solana_sdk::ed25519_instruction::{DATA_START, PUBKEY_SERIALIZED_SIZE};
+    let this_instruction = u16::MAX;
+    let public_key_offset = DATA_START as u16;
+    let signature_offset = public_key_offset + PUBKEY_SERIALIZED_SIZE as u16;
+    let message_offset = signature_offset + signature.as_ref().len() as u16;
+
+    let mut data = vec![1, 0]; // One signature, padding
+    for offset in [
+        signature_offset,
+        this_instruction,
+        public_key_offset,
+        this_instruction,
+        message_offset,
+        message.len() as u16,
+        this_instruction,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature.as_ref());
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: solana_sdk::ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+impl BetSettlement {
+    pub(crate) fn to_program(&self) -> verifier::BetSettlement {
+        verifier::BetSettlement {
+            bet_id: self.bet_id,
+            user: self.user,
+            bet_amount: self.bet_amount,
+            user_guess: self.user_guess,
+            outcome: self.outcome,
+            payout: self.payout,
+        }
+    }
+
+    /// The bet as the accounting circuit commits to it
+    pub fn to_record(&self) -> prover::BetRecord {
+        prover::BetRecord {
+            bet_id: self.bet_id,
+            user: self.user.to_bytes(),
+            bet_amount: self.bet_amount,
+            user_guess: self.user_guess,
+            outcome: self.outcome,
+            payout: self.payout,
+        }
+    }
+
+    /// Borsh encoding, as the verifier program deserializes it
+    fn encode(&self, data: &mut Vec<u8>) {
+        data.extend_from_slice(&self.bet_id.to_le_bytes());
+        data.extend_from_slice(self.user.as_ref());
+        data.extend_from_slice(&self.bet_amount.to_le_bytes());
+        data.push(self.user_guess);
+        data.push(self.outcome);
+        data.extend_from_slice(&self.payout.to_le_bytes());
+    }
+}
+
+/// What the cluster made of a simulated settlement transaction
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SettlementSimulation {
+    pub instruction: String, // verify_and_settle, or begin_batch for chunked batches
+    pub instruction_data_len: usize,
+    pub error: Option<String>, // None when the simulated transaction succeeded
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+}
+
+/// Batch record written by verify_and_settle (matches verifier program)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnchainBatchRecord {
+    pub batch_id: u64,
+    pub sequencer: Pubkey,
+    pub proof_hash: [u8; 32],
+    pub state_root: [u8; 32],
+    pub bet_count: u32,
+    pub house_delta: i64,
+    pub slot: u64,
+    pub status: verifier::BatchRecordStatus,
+    pub bets_root: [u8; 32], // Root over the batch's bet leaves (verifier's optimistic::bets_root)
+}
+
+impl OnchainBatchRecord {
+    const LEN: usize = 8 + 8 + 32 + 32 + 32 + 4 + 8 + 8 + 1 + 32;
+
+    /// Decode Anchor account data: 8-byte discriminator, then the fields in order
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let discriminator = solana_sdk::hash::hash(b"account:BatchRecord");
+        if data.len() < Self::LEN || data[..8] != discriminator.to_bytes()[..8] {
+            return Err(anyhow!("Account is not a batch record"));
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        Ok(Self {
+            batch_id: u64_at(8),
+            sequencer: Pubkey::try_from(&data[16..48]).unwrap(),
+            proof_hash: data[48..80].try_into().unwrap(),
+            state_root: data[80..112].try_into().unwrap(),
+            bet_count: u32::from_le_bytes(data[112..116].try_into().unwrap()),
+            house_delta: u64_at(116) as i64,
+            slot: u64_at(124),
+            status: match data[132] {
+                0 => verifier::BatchRecordStatus::Settled,
+                1 => verifier::BatchRecordStatus::Optimistic,
+                2 => verifier::BatchRecordStatus::Challenged,
+                _ => return Err(anyhow!("Unknown batch record status {}", data[132])),
+            },
+            bets_root: data[133..165].try_into().unwrap(),
+        })
+    }
+}
+
+/// Sequencer allowlist entry as stored by the verifier program
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnchainSequencerRegistration {
+    pub sequencer: Pubkey,
+    pub registered_at: i64,
+    pub valid_from: i64,
+    pub valid_until: Option<i64>,
+}
+
+impl OnchainSequencerRegistration {
+    /// Decode Anchor account data: 8-byte discriminator, then the fields in order
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let discriminator = solana_sdk::hash::hash(b"account:SequencerRegistration");
+        if data.len() < 8 + 32 + 8 + 8 + 1 || data[..8] != discriminator.to_bytes()[..8] {
+            return Err(anyhow!("Account is not a sequencer registration"));
+        }
+        let i64_at = |offset: usize| {
+            data.get(offset..offset + 8)
+                .map(|bytes| i64::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(|| anyhow!("Sequencer registration is truncated"))
+        };
+        Ok(Self {
+            sequencer: Pubkey::try_from(&data[8..40]).unwrap(),
+            registered_at: i64_at(40)?,
+            valid_from: i64_at(48)?,
+            valid_until: match data[56] {
+                0 => None,
+                _ => Some(i64_at(57)?),
+            },
+        })
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.valid_from <= now && self.valid_until.is_none_or(|until| now < until)
+    }
+}
+
 /// Settlement transaction result
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct SettlementResult {
     pub signature: Signature,
@@ -468,11 +1790,105 @@ mod tests {
         assert_eq!(testnet_config.rpc_url, "https://api.testnet.solana.com");
     }
 
+    #[test]
+    fn test_sequencer_registration_decoding() {
+        let sequencer = Pubkey::new_unique();
+        let mut data =
+            solana_sdk::hash::hash(b"account:SequencerRegistration").to_bytes()[..8].to_vec();
+        data.extend_from_slice(sequencer.as_ref());
+        data.extend_from_slice(&100i64.to_le_bytes());
+        data.extend_from_slice(&200i64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&300i64.to_le_bytes());
+
+        let registration = OnchainSequencerRegistration::from_account_data(&data).unwrap();
+        assert_eq!(registration.sequencer, sequencer);
+        assert_eq!(registration.valid_until, Some(300));
+        assert!(!registration.is_active(199));
+        assert!(registration.is_active(200));
+        assert!(!registration.is_active(300));
+
+        // A key that was never rotated out has no end to its window
+        let mut open = data[..57].to_vec();
+        open[56] = 0;
+        let registration = OnchainSequencerRegistration::from_account_data(&open).unwrap();
+        assert_eq!(registration.valid_until, None);
+        assert!(OnchainSequencerRegistration::from_account_data(&data[..50]).is_err());
+    }
+
+    #[test]
+    fn test_user_vault_initialization_transaction() {
+        let client = SolanaClient::new(
+            SolanaConfig::default(),
+            Keypair::new(),
+            "E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx",
+            "11111111111111111111111111111112",
+        )
+        .unwrap();
+        let user = Pubkey::new_unique();
+
+        let instruction = client.initialize_user_vault_instruction(&user);
+        assert_eq!(instruction.accounts[0].pubkey, client.user_vault_pda(&user));
+        let signers: Vec<Pubkey> = instruction
+            .accounts
+            .iter()
+            .filter(|meta| meta.is_signer)
+            .map(|meta| meta.pubkey)
+            .collect();
+        assert_eq!(signers, vec![user]);
+
+        // The user pays and is the only signer, so the sequencer never has to sign
+        let message = solana_sdk::message::Message::new(&[instruction], Some(&user));
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(message.account_keys[0], user);
+    }
+
+    #[test]
+    fn test_decode_batch_record() {
+        let sequencer = Pubkey::new_unique();
+        let mut data = solana_sdk::hash::hash(b"account:BatchRecord").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.extend_from_slice(sequencer.as_ref());
+        data.extend_from_slice(&[9; 32]);
+        data.extend_from_slice(&[4; 32]);
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&(-1500i64).to_le_bytes());
+        data.extend_from_slice(&777u64.to_le_bytes());
+        data.push(0); // Settled
+        data.extend_from_slice(&[5; 32]);
+
+        let record = OnchainBatchRecord::from_account_data(&data).unwrap();
+        assert_eq!(
+            record,
+            OnchainBatchRecord {
+                batch_id: 42,
+                sequencer,
+                proof_hash: [9; 32],
+                state_root: [4; 32],
+                bet_count: 3,
+                house_delta: -1500,
+                slot: 777,
+                status: verifier::BatchRecordStatus::Settled,
+                bets_root: [5; 32],
+            }
+        );
+
+        // Wrong account type or truncated data
+        data[0] ^= 1;
+        assert!(OnchainBatchRecord::from_account_data(&data).is_err());
+        assert!(OnchainBatchRecord::from_account_data(&data[..40]).is_err());
+    }
+
     #[test]
     fn test_batch_settlement_data() {
         let batch = BatchSettlementData {
             batch_id: 123,
             sequencer_nonce: 456,
+            game_id: GAME_COINFLIP,
+            prev_state_root: [0; 32],
+            new_state_root: [1; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [2; 32],
             bets: vec![BetSettlement {
                 bet_id: 1,
                 user: Pubkey::new_unique(),
@@ -481,16 +1897,268 @@ mod tests {
                 outcome: 1,
                 payout: 2000,
             }],
+            sequencer_signature: Signature::default(),
         };
 
         assert_eq!(batch.batch_id, 123);
         assert_eq!(batch.bets.len(), 1);
     }
 
+    #[test]
+    fn test_batch_signature_instruction() {
+        let keypair = Keypair::new();
+        let mut batch = BatchSettlementData {
+            batch_id: 7,
+            sequencer_nonce: 7,
+            game_id: GAME_COINFLIP,
+            prev_state_root: [0; 32],
+            new_state_root: [1; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [2; 32],
+            bets: vec![BetSettlement {
+                bet_id: 1,
+                user: Pubkey::new_unique(),
+                bet_amount: 1000,
+                user_guess: 1,
+                outcome: 0,
+                payout: 0,
+            }],
+            sequencer_signature: Signature::default(),
+        };
+        batch.sign(&keypair);
+        let hash = batch.batch_hash();
+        assert!(hash[0] <= 0x1f);
+        assert!(batch
+            .sequencer_signature
+            .verify(keypair.pubkey().as_ref(), &hash));
+
+        // The ed25519 program accepts the instruction as built
+        let feature_set = solana_sdk::feature_set::FeatureSet::all_enabled();
+        let instruction =
+            ed25519_signature_instruction(&keypair.pubkey(), &batch.sequencer_signature, &hash);
+        let verify = |data: &[u8]| {
+            solana_sdk::ed25519_instruction::verify(data, &[data], &feature_set).is_ok()
+        };
+        assert!(verify(&instruction.data));
+
+        // Changing a bet changes what is signed
+        batch.bets[0].payout = 2000;
+        let tampered = ed25519_signature_instruction(
+            &keypair.pubkey(),
+            &batch.sequencer_signature,
+            &batch.batch_hash(),
+        );
+        assert!(!verify(&tampered.data));
+    }
+
+    #[test]
+    fn test_anchor_instruction() {
+        let client = SolanaClient::new(
+            SolanaConfig::default(),
+            Keypair::new(),
+            "E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx",
+            "11111111111111111111111111111112",
+        )
+        .unwrap();
+        let user = Pubkey::new_unique();
+
+        // Anchor discriminator of initialize_user_vault, then a None referrer
+        let instruction = client.initialize_user_vault_instruction(&user);
+        assert_eq!(instruction.data, [254, 30, 70, 19, 7, 242, 135, 245, 0]);
+
+        // Account order and flags follow the program's Accounts struct
+        let instruction = client.cancel_batch_instruction();
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(client.sequencer_pubkey(), true),
+                AccountMeta::new(client.batch_staging_pda(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vault_pdas() {
+        let client = SolanaClient::new(
+            SolanaConfig::default(),
+            Keypair::new(),
+            "E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx",
+            "11111111111111111111111111111112",
+        )
+        .unwrap();
+        let user = Pubkey::new_unique();
+
+        let (expected, _) = Pubkey::find_program_address(
+            &[b"user_vault", user.as_ref()],
+            &Pubkey::from_str("E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx").unwrap(),
+        );
+        assert_eq!(client.user_vault_pda(&user), expected);
+        assert_ne!(client.vault_state_pda(), expected);
+        assert_ne!(client.deposit_address_pda(&user), expected);
+        assert_ne!(client.referral_account_pda(&user), expected);
+    }
+
+    #[test]
+    fn test_chunked_batch_instructions_fit() {
+        let client = SolanaClient::new(
+            SolanaConfig::default(),
+            Keypair::new(),
+            "E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx",
+            "11111111111111111111111111111112",
+        )
+        .unwrap();
+        let bets: Vec<BetSettlement> = (0..100)
+            .map(|bet_id| BetSettlement {
+                bet_id,
+                user: Pubkey::new_unique(),
+                bet_amount: 1000,
+                user_guess: 1,
+                outcome: 0,
+                payout: 0,
+            })
+            .collect();
+        let batch_data = BatchSettlementData {
+            batch_id: 9,
+            sequencer_nonce: 9,
+            game_id: GAME_COINFLIP,
+            prev_state_root: [0; 32],
+            new_state_root: [1; 32],
+            vrf_pubkey: Pubkey::new_unique(),
+            vrf_outputs_root: [2; 32],
+            bets,
+            sequencer_signature: Signature::default(),
+        };
+        let proof = vec![0; 256]; // Compressed Groth16 proof
+
+        // A full batch does not fit in one verify_and_settle transaction...
+        let single = client
+            .create_verify_and_settle_instructions(batch_data.clone(), proof.clone())
+            .unwrap();
+        assert!(!client.fits_in_transaction(&single));
+        // Settling commits the batch's state root to the vault
+        let settle = &single[1].accounts;
+        assert!(settle.contains(&AccountMeta::new(client.vault_state_pda(), false)));
+        assert!(settle.contains(&AccountMeta::new_readonly(client.vault_caller_pda(), false)));
+
+        // ...but every staging and finalize transaction does
+        assert!(client.fits_in_transaction(&[client.begin_batch_instruction(&batch_data)]));
+        let chunk = &batch_data.bets[..BETS_PER_CHUNK];
+        let append = client.append_batch_chunk_instruction(0, chunk);
+        assert_eq!(append.data.len(), 8 + 4 + 4 + BETS_PER_CHUNK * 58);
+        assert!(client.fits_in_transaction(&[append]));
+        assert!(client
+            .fits_in_transaction(&client.finalize_and_verify_instructions(&batch_data, proof)));
+        assert!(client.fits_in_transaction(&[client.cancel_batch_instruction()]));
+    }
+
+    #[test]
+    fn test_classify_submission() {
+        assert_eq!(
+            classify_submission(Some(Ok(())), 200, 100),
+            SubmissionStatus::Landed
+        );
+        assert_eq!(
+            classify_submission(Some(Err("custom program error".to_string())), 50, 100),
+            SubmissionStatus::FailedOnChain("custom program error".to_string())
+        );
+        assert_eq!(
+            classify_submission(None, 100, 100),
+            SubmissionStatus::InFlight
+        );
+        assert_eq!(
+            classify_submission(None, 101, 100),
+            SubmissionStatus::Expired
+        );
+    }
+
     #[test]
     fn test_keypair_generation() {
         let keypair = Keypair::new();
         let pubkey = keypair.pubkey();
         assert_ne!(pubkey, Pubkey::default());
     }
+
+    #[test]
+    fn test_encodings_match_fixtures() {
+        let fixture = &fixtures::BATCH;
+        let bets: Vec<BetSettlement> = fixture
+            .bets
+            .iter()
+            .map(|bet| {
+                let user = Pubkey::new_from_array(bet.user);
+                assert_eq!(user.to_string(), bet.player);
+                BetSettlement {
+                    bet_id: bet.bet_id,
+                    user,
+                    bet_amount: bet.bet_amount,
+                    user_guess: bet.user_guess,
+                    outcome: bet.outcome,
+                    payout: bet.payout,
+                }
+            })
+            .collect();
+        let batch = BatchSettlementData {
+            batch_id: fixture.batch_id,
+            sequencer_nonce: fixture.sequencer_nonce,
+            game_id: fixture.game_id,
+            prev_state_root: fixtures::hex32(fixture.prev_state_root),
+            new_state_root: fixtures::hex32(fixture.new_state_root),
+            vrf_pubkey: Pubkey::new_from_array(fixtures::hex32(fixture.vrf_pubkey)),
+            vrf_outputs_root: fixtures::hex32(fixture.vrf_outputs_root),
+            bets,
+            sequencer_signature: Signature::default(),
+        };
+        assert_eq!(batch.batch_hash(), fixtures::hex32(fixtures::BATCH_HASH));
+        assert_eq!(
+            batch.batch_commitment(),
+            fixtures::hex32(fixtures::BATCH_COMMITMENT)
+        );
+
+        let client = SolanaClient::new(
+            SolanaConfig::default(),
+            Keypair::new(),
+            "E75Bj5yHoYv41CV98UU8wSGo3FqhavaBTviwzNNtkZx",
+            "11111111111111111111111111111112",
+        )
+        .unwrap();
+        assert_eq!(
+            client.begin_batch_instruction(&batch).data,
+            fixtures::hex(fixtures::BEGIN_BATCH)
+        );
+        assert_eq!(
+            client.append_batch_chunk_instruction(0, &batch.bets).data,
+            fixtures::hex(fixtures::APPEND_BATCH_CHUNK)
+        );
+
+        // Accounts as the verifier program writes them
+        let record =
+            OnchainBatchRecord::from_account_data(&fixtures::hex(fixtures::BATCH_RECORD)).unwrap();
+        assert_eq!(
+            record,
+            OnchainBatchRecord {
+                batch_id: fixture.batch_id,
+                sequencer: Pubkey::new_from_array(fixtures::SEQUENCER),
+                proof_hash: fixtures::PROOF_HASH,
+                state_root: batch.new_state_root,
+                bet_count: 2,
+                house_delta: fixture.house_delta(),
+                slot: fixtures::SETTLED_SLOT,
+                status: verifier::BatchRecordStatus::Settled,
+                bets_root: fixtures::hex32(fixtures::BATCH_BETS_ROOT),
+            }
+        );
+        let registration = OnchainSequencerRegistration::from_account_data(&fixtures::hex(
+            fixtures::SEQUENCER_REGISTRATION,
+        ))
+        .unwrap();
+        assert_eq!(
+            registration,
+            OnchainSequencerRegistration {
+                sequencer: Pubkey::new_from_array(fixtures::SEQUENCER),
+                registered_at: fixtures::REGISTERED_AT,
+                valid_from: fixtures::REGISTERED_AT,
+                valid_until: Some(fixtures::VALID_UNTIL),
+            }
+        );
+    }
 }
@@ -0,0 +1,380 @@
+// Balance state roots for ZK Casino
+// The verifier program tracks a root of settled user balances and only accepts a batch
+// whose proof moves that root on. A user's settled balance is the net of every bet of
+// theirs in a confirmed batch (payouts minus stakes); deposits and withdrawals are not
+// rolled up. The tree is the settlement circuit's Poseidon state tree (see the prover's
+// state_tree): players keep the leaf they first settled at, so balances are kept in
+// that order.
+
+use indexmap::IndexMap;
+use prover::state_tree::{
+    leaf_hash, root_bytes, LeafUpdate, StateTree, StateTreeError, STATE_TREE_CAPACITY,
+    STATE_TREE_DEPTH,
+};
+use prover::{poseidon::poseidon_hash, Fr, PrimeField};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::vrf::MerkleStep;
+use crate::SettlementItem;
+
+pub type StateRoot = [u8; 32];
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum StateRootError {
+    #[error("Settled balance of {0} overflows")]
+    Overflow(String),
+    #[error(transparent)]
+    Full(#[from] StateTreeError),
+}
+
+/// Net settled balance per player, keyed by address, in the order they first settled,
+/// with the state tree over them. The tree is updated as batches are applied, never
+/// rebuilt.
+#[derive(Clone, Debug)]
+pub struct SettledBalances {
+    balances: IndexMap<String, i64>,
+    tree: StateTree,
+}
+
+impl Default for SettledBalances {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SettledBalances {
+    pub fn new() -> Self {
+        Self {
+            balances: IndexMap::new(),
+            tree: StateTree::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.balances.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.balances.is_empty()
+    }
+
+    pub fn get(&self, player_address: &str) -> Option<i64> {
+        self.balances.get(player_address).copied()
+    }
+
+    /// Whether `new_players` players without a leaf can still each be given one
+    pub fn has_room(&self, new_players: usize) -> bool {
+        self.len() + new_players <= STATE_TREE_CAPACITY
+    }
+}
+
+/// Each batch player's settled balance after the batch, in the order they first appear
+/// in it. Fails without a leaf for every new player or on overflow.
+fn batch_balances(
+    balances: &SettledBalances,
+    items: &[SettlementItem],
+) -> Result<IndexMap<String, i64>, StateRootError> {
+    let mut batch: IndexMap<String, i64> = IndexMap::new();
+    for item in items {
+        let player = &item.player_address;
+        let balance = match batch.get(player) {
+            Some(balance) => *balance,
+            None => balances.get(player).unwrap_or(0),
+        };
+        let balance = item
+            .payout
+            .checked_sub(item.amount)
+            .and_then(|delta| balance.checked_add(delta))
+            .ok_or_else(|| StateRootError::Overflow(player.clone()))?;
+        batch.insert(player.clone(), balance);
+    }
+
+    let new_players = batch
+        .keys()
+        .filter(|player| !balances.balances.contains_key(*player))
+        .count();
+    if !balances.has_room(new_players) {
+        return Err(StateTreeError::Full.into());
+    }
+    Ok(batch)
+}
+
+/// Apply a batch's bets to the settled balances. A batch that fails leaves them unchanged.
+pub fn apply_items(
+    balances: &mut SettledBalances,
+    items: &[SettlementItem],
+) -> Result<(), StateRootError> {
+    for (player, balance) in batch_balances(balances, items)? {
+        balances
+            .tree
+            .update(player_key(&player).to_bytes(), balance)?;
+        balances.balances.insert(player, balance);
+    }
+    Ok(())
+}
+
+/// On-chain key of a player. Non-pubkey players (demo addresses) get a stable key
+/// derived from the address.
+pub fn player_key(player_address: &str) -> Pubkey {
+    Pubkey::from_str(player_address).unwrap_or_else(|_| {
+        Pubkey::new_from_array(solana_sdk::hash::hash(player_address.as_bytes()).to_bytes())
+    })
+}
+
+/// Root of the settled balances, as the verifier stores it (a big-endian scalar)
+pub fn state_root(balances: &SettledBalances) -> StateRoot {
+    root_bytes(balances.tree.root())
+}
+
+/// A player's settled balance with its inclusion proof against `state_root(balances)`
+pub fn balance_proof(
+    balances: &SettledBalances,
+    player_address: &str,
+) -> Option<(i64, Vec<MerkleStep>)> {
+    let (index, _, balance) = balances.balances.get_full(player_address)?;
+    let steps = balances
+        .tree
+        .path(index as u32)
+        .into_iter()
+        .enumerate()
+        .map(|(height, sibling)| MerkleStep {
+            sibling: Hash::new_from_array(root_bytes(sibling)),
+            sibling_is_left: index >> height & 1 == 1,
+        })
+        .collect();
+    Some((*balance, steps))
+}
+
+/// Check that `balance` is the player's leaf under `root`
+pub fn verify_balance_proof(
+    root: &StateRoot,
+    player_address: &str,
+    balance: i64,
+    steps: &[MerkleStep],
+) -> bool {
+    if steps.len() != STATE_TREE_DEPTH {
+        return false;
+    }
+    let leaf = leaf_hash(&player_key(player_address).to_bytes(), balance);
+    let implied = steps.iter().try_fold(leaf, |node, step| {
+        // Siblings must be canonical field elements
+        let sibling = Fr::from_be_bytes_mod_order(step.sibling.as_ref());
+        (root_bytes(sibling) == step.sibling.to_bytes()).then(|| {
+            if step.sibling_is_left {
+                poseidon_hash(&[sibling, node])
+            } else {
+                poseidon_hash(&[node, sibling])
+            }
+        })
+    });
+    implied.is_some_and(|implied| &root_bytes(implied) == root)
+}
+
+/// Roots before and after applying a batch on top of the settled balances
+pub fn transition(
+    balances: &SettledBalances,
+    items: &[SettlementItem],
+) -> Result<(StateRoot, StateRoot), StateRootError> {
+    let (prev_root, updates) = transition_witness(balances, items)?;
+    let new_root = updates.last().map_or(prev_root, |update| update.roots().1);
+    Ok((root_bytes(prev_root), root_bytes(new_root)))
+}
+
+/// The root a batch starts from and the leaf update of each of its players, in the
+/// order they first appear in the batch, for the settlement circuit to check
+pub fn transition_witness(
+    balances: &SettledBalances,
+    items: &[SettlementItem],
+) -> Result<(Fr, Vec<LeafUpdate>), StateRootError> {
+    let batch = batch_balances(balances, items)?;
+
+    let mut tree = balances.tree.clone();
+    let prev_root = tree.root();
+    let updates = batch
+        .into_iter()
+        .map(|(player, balance)| tree.update(player_key(&player).to_bytes(), balance))
+        .collect::<Result<_, _>>()?;
+    Ok((prev_root, updates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn item(player: &str, amount: i64, payout: i64) -> SettlementItem {
+        SettlementItem {
+            bet_id: "bet".to_string(),
+            player_address: player.to_string(),
+            amount,
+            payout,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        }
+    }
+
+    #[test]
+    fn test_empty_state_root() {
+        assert_eq!(
+            state_root(&SettledBalances::new()),
+            verifier::EMPTY_STATE_ROOT
+        );
+    }
+
+    #[test]
+    fn test_transition_chains() {
+        let mut balances = SettledBalances::new();
+        let first = [item("alice", 1000, 2000), item("bob", 500, 0)];
+        let (prev, new) = transition(&balances, &first).unwrap();
+        assert_eq!(prev, verifier::EMPTY_STATE_ROOT);
+        assert_ne!(new, prev);
+        assert!(new[0] < 0x31);
+
+        apply_items(&mut balances, &first).unwrap();
+        assert_eq!(balances.get("alice"), Some(1000));
+        assert_eq!(balances.get("bob"), Some(-500));
+        let (next_prev, _) = transition(&balances, &[item("bob", 500, 1000)]).unwrap();
+        assert_eq!(next_prev, new);
+
+        // Players keep the leaf they first settled at, so a batch settling them in
+        // another order reaches another root
+        let reordered = [item("bob", 500, 0), item("alice", 1000, 2000)];
+        assert_ne!(
+            transition(&SettledBalances::new(), &reordered).unwrap().1,
+            new
+        );
+
+        // The circuit's leaf updates chain between the same roots
+        let (witness_prev, updates) =
+            transition_witness(&balances, &[item("carol", 300, 600)]).unwrap();
+        assert_eq!(root_bytes(witness_prev), new);
+        assert_eq!(updates.len(), 1);
+        assert_eq!((updates[0].index, updates[0].initial_balance), (2, None));
+        let mut next = balances.clone();
+        apply_items(&mut next, &[item("carol", 300, 600)]).unwrap();
+        assert_eq!(root_bytes(updates[0].roots().1), state_root(&next));
+    }
+
+    #[test]
+    fn test_transition_witness_nets_players() {
+        let mut balances = SettledBalances::new();
+        apply_items(&mut balances, &[item("alice", 1000, 2000)]).unwrap();
+
+        let items = [
+            item("bob", 500, 0),
+            item("alice", 100, 0),
+            item("bob", 200, 400),
+        ];
+        let (prev_root, updates) = transition_witness(&balances, &items).unwrap();
+        assert_eq!(root_bytes(prev_root), state_root(&balances));
+
+        // One update per player, in the order they first appear in the batch
+        let settled: Vec<_> = updates
+            .iter()
+            .map(|update| (update.index, update.initial_balance, update.final_balance))
+            .collect();
+        assert_eq!(settled, [(1, None, -300), (0, Some(1000), 900)]);
+        assert_eq!(updates[0].roots().1, updates[1].roots().0);
+        assert_eq!(
+            root_bytes(updates[1].roots().1),
+            transition(&balances, &items).unwrap().1
+        );
+    }
+
+    #[test]
+    fn test_overflow_leaves_balances_unchanged() {
+        let mut balances = SettledBalances::new();
+        apply_items(&mut balances, &[item("alice", 0, i64::MAX)]).unwrap();
+        let root = state_root(&balances);
+
+        let items = [item("bob", 500, 0), item("alice", 0, 1)];
+        assert_eq!(
+            apply_items(&mut balances, &items),
+            Err(StateRootError::Overflow("alice".to_string()))
+        );
+        assert!(transition(&balances, &items).is_err());
+        assert_eq!(balances.len(), 1);
+        assert_eq!(state_root(&balances), root);
+        assert!(apply_items(&mut balances, &[item("alice", i64::MIN, 0)]).is_err());
+        assert_eq!(balances.get("alice"), Some(i64::MAX));
+    }
+
+    #[test]
+    fn test_balance_proof() {
+        let mut balances = SettledBalances::new();
+        apply_items(
+            &mut balances,
+            &[
+                item("alice", 1000, 2000),
+                item("bob", 500, 0),
+                item("carol", 300, 600),
+            ],
+        )
+        .unwrap();
+        let root = state_root(&balances);
+
+        let (balance, steps) = balance_proof(&balances, "bob").unwrap();
+        assert_eq!(balance, -500);
+        assert_eq!(steps.len(), STATE_TREE_DEPTH);
+        assert!(verify_balance_proof(&root, "bob", -500, &steps));
+        assert!(!verify_balance_proof(&root, "bob", 500, &steps));
+        assert!(!verify_balance_proof(&root, "alice", -500, &steps));
+        assert!(!verify_balance_proof(&root, "bob", -500, &steps[1..]));
+        assert!(balance_proof(&balances, "dave").is_none());
+
+        for player in ["alice", "carol"] {
+            let (balance, steps) = balance_proof(&balances, player).unwrap();
+            assert!(verify_balance_proof(&root, player, balance, &steps));
+        }
+    }
+
+    #[test]
+    fn test_vault_accepts_balance_proofs() {
+        // The vault's prove_pnl_inclusion rebuilds the same root from a proof
+        let players: Vec<solana_sdk::pubkey::Pubkey> = (0..5)
+            .map(|_| solana_sdk::pubkey::Pubkey::new_unique())
+            .collect();
+        let items: Vec<SettlementItem> = players
+            .iter()
+            .enumerate()
+            .map(|(i, player)| item(&player.to_string(), 1000, 500 * i as i64))
+            .collect();
+        let mut balances = SettledBalances::new();
+        apply_items(&mut balances, &items).unwrap();
+        let root = state_root(&balances);
+
+        for player in &players {
+            let (balance, steps) = balance_proof(&balances, &player.to_string()).unwrap();
+            let steps: Vec<vault::BalanceProofStep> = steps
+                .iter()
+                .map(|step| vault::BalanceProofStep {
+                    sibling: step.sibling.to_bytes(),
+                    sibling_is_left: step.sibling_is_left,
+                })
+                .collect();
+            assert_eq!(steps.len(), vault::BALANCE_TREE_DEPTH);
+            assert_eq!(
+                vault::pnl_root_from_proof(player, balance, &steps),
+                Some(root)
+            );
+        }
+    }
+
+    #[test]
+    fn test_state_root_matches_fixtures() {
+        let items: Vec<SettlementItem> = fixtures::BATCH
+            .bets
+            .iter()
+            .map(|bet| item(bet.player, bet.bet_amount as i64, bet.payout as i64))
+            .collect();
+        let (prev, new) = transition(&SettledBalances::new(), &items).unwrap();
+        assert_eq!(prev, fixtures::hex32(fixtures::BATCH.prev_state_root));
+        assert_eq!(new, fixtures::hex32(fixtures::BATCH.new_state_root));
+    }
+}
@@ -0,0 +1,720 @@
+// Settlement submitter for ZK Casino
+// Sends proved batches to the verifier program and records how they landed. By default
+// it runs inside the sequencer and takes each batch straight from the prover. With
+// SETTLEMENT_SUBMITTER=external the sequencer stops once a batch's proof is stored, and
+// a separate `sequencer submitter` process picks proved batches up from the shared
+// settlement file. That way the betting API and the prover can restart without
+// interrupting transactions that are already in flight.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use prover::proof_generator::SerializableProof;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use tracing::{error, info, warn};
+
+use crate::bet_id::BetId;
+use crate::database::{BetSettlementStatus, Database};
+use crate::settlement_persistence::{
+    SettlementBatchStatus, SettlementPersistence, SubmissionIntent,
+};
+use crate::solana::{BatchSettlementData, BetSettlement, SolanaClient, SubmissionStatus};
+use crate::state_root;
+use crate::vrf::{merkle_root, transcript_leaf};
+use crate::SettlementItem;
+
+// Proof sent for batches settled without the ZK prover (Phase 2 compatibility)
+const PLACEHOLDER_PROOF_LEN: usize = 64;
+
+pub fn placeholder_proof() -> Vec<u8> {
+    vec![0u8; PLACEHOLDER_PROOF_LEN]
+}
+
+/// Proof bytes as the verifier program takes them. Stored proofs are the prover's
+/// envelope (SerializableProof::to_bytes), whose points are re-encoded for the alt_bn128
+/// syscalls; anything else, like the placeholder, goes out as it is.
+pub fn verifier_proof(proof_data: &[u8]) -> Vec<u8> {
+    match SerializableProof::from_bytes(proof_data) {
+        Ok(proof) => proof.to_verifier_bytes(),
+        Err(_) => proof_data.to_vec(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubmitterMode {
+    Embedded, // The sequencer submits each batch as soon as it is proved
+    External, // A separate `sequencer submitter` process submits proved batches
+}
+
+impl FromStr for SubmitterMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "embedded" => Ok(Self::Embedded),
+            "external" => Ok(Self::External),
+            other => Err(anyhow!("Unknown settlement submitter mode: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmitterConfig {
+    pub mode: SubmitterMode,
+    pub poll_interval: Duration,
+}
+
+impl Default for SubmitterConfig {
+    fn default() -> Self {
+        Self {
+            mode: SubmitterMode::Embedded,
+            poll_interval: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl SubmitterConfig {
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        let mode = match std::env::var("SETTLEMENT_SUBMITTER") {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| anyhow!("Invalid SETTLEMENT_SUBMITTER: {}", e))?,
+            Err(_) => defaults.mode,
+        };
+        let poll_interval = match std::env::var("SUBMITTER_POLL_INTERVAL_MS") {
+            Ok(value) => Duration::from_millis(
+                value
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid SUBMITTER_POLL_INTERVAL_MS: {}", e))?,
+            ),
+            Err(_) => defaults.poll_interval,
+        };
+        Ok(Self {
+            mode,
+            poll_interval,
+        })
+    }
+}
+
+pub struct SettlementSubmitter {
+    solana_client: Option<Arc<SolanaClient>>, // None: record mock signatures (testing)
+    persistence: Arc<SettlementPersistence>,
+    db: Arc<Database>,
+    vrf_pubkey: Pubkey, // Published with each batch alongside its VRF outputs commitment
+}
+
+impl SettlementSubmitter {
+    pub fn new(
+        solana_client: Option<Arc<SolanaClient>>,
+        persistence: Arc<SettlementPersistence>,
+        db: Arc<Database>,
+        vrf_pubkey: Pubkey,
+    ) -> Self {
+        Self {
+            solana_client,
+            persistence,
+            db,
+            vrf_pubkey,
+        }
+    }
+
+    /// Submit a proved batch and record the outcome; returns whether it was submitted
+    pub async fn submit(&self, batch_id: u64, batch: &[SettlementItem], proof: &[u8]) -> bool {
+        let submitted = if let Some(solana_client) = &self.solana_client {
+            match submit_batch_to_solana_with_proof(
+                solana_client,
+                &self.persistence,
+                self.vrf_pubkey,
+                batch_id,
+                batch,
+                proof,
+            )
+            .await
+            {
+                Ok(signature) => {
+                    info!(
+                        "Batch {} submitted to Solana successfully with proof: {}",
+                        batch_id, signature
+                    );
+
+                    update_bet_settlements(
+                        &self.db,
+                        batch_id,
+                        BetSettlementStatus::Submitted,
+                        Some(&signature.to_string()),
+                    )
+                    .await;
+
+                    // Store the transaction signature in settlement persistence
+                    if let Err(e) = self
+                        .persistence
+                        .store_transaction(batch_id, &signature.to_string())
+                        .await
+                    {
+                        error!(
+                            "Failed to store transaction signature for batch {}: {}",
+                            batch_id, e
+                        );
+                    } else {
+                        info!(
+                            "Transaction signature stored for batch {}: {}",
+                            batch_id, signature
+                        );
+                    }
+
+                    // Record fees and compute units for cost-per-bet analytics
+                    match solana_client.get_transaction_cost(&signature).await {
+                        Ok(cost) => {
+                            if let Err(e) = self.persistence.store_cost(batch_id, cost).await {
+                                error!("Failed to store cost for batch {}: {}", batch_id, e);
+                            }
+                        }
+                        Err(e) => {
+                            warn!(
+                                "Failed to fetch transaction cost for batch {}: {}",
+                                batch_id, e
+                            );
+                        }
+                    }
+
+                    true
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to submit batch {} to Solana: {}. Continuing with local processing.",
+                        batch_id, e
+                    );
+                    update_bet_settlements(&self.db, batch_id, BetSettlementStatus::Failed, None)
+                        .await;
+                    false
+                }
+            }
+        } else {
+            // For testing: store a mock transaction signature when Solana is not available
+            info!(
+                "Solana not available, storing mock transaction signature for batch {}",
+                batch_id
+            );
+            let mock_signature = format!("mock_tx_{}_confirmed", batch_id);
+            update_bet_settlements(
+                &self.db,
+                batch_id,
+                BetSettlementStatus::Submitted,
+                Some(&mock_signature),
+            )
+            .await;
+            if let Err(e) = self
+                .persistence
+                .store_transaction(batch_id, &mock_signature)
+                .await
+            {
+                error!(
+                    "Failed to store mock transaction signature for batch {}: {}",
+                    batch_id, e
+                );
+            } else {
+                info!(
+                    "Mock transaction signature stored for batch {}: {}",
+                    batch_id, mock_signature
+                );
+            }
+            true
+        };
+
+        // Phase 3e: Mark batch as completed in persistent storage
+        if let Err(e) = self
+            .persistence
+            .mark_completed(&format!("batch_{}", batch_id))
+            .await
+        {
+            error!("Failed to mark batch {} as completed: {}", batch_id, e);
+            // Continue anyway - the batch was processed successfully
+        }
+
+        // Bets that reached the chain (or the mock) are now settled
+        if submitted {
+            update_bet_settlements(&self.db, batch_id, BetSettlementStatus::Confirmed, None).await;
+        }
+        submitted
+    }
+
+    /// Resolve earlier transactions, then submit every proved batch in the settlement file
+    pub async fn run_once(&self) -> Result<usize> {
+        self.persistence.reload().await?;
+        if let Some(solana_client) = &self.solana_client {
+            recover_submission_intents(solana_client, &self.persistence, &self.db).await?;
+        }
+
+        let batches = self.persistence.get_batches_ready_for_submission().await?;
+        for batch in &batches {
+            // Batches proved without the ZK prover carry no proof of their own
+            let proof = batch.proof_data.clone().unwrap_or_else(placeholder_proof);
+            self.submit(batch.batch_id, &batch.items, &proof).await;
+        }
+        Ok(batches.len())
+    }
+
+    pub fn start(self: Arc<Self>, poll_interval: Duration) -> JoinHandle<()> {
+        info!(
+            "Starting settlement submitter, polling every {}ms",
+            poll_interval.as_millis()
+        );
+
+        tokio::spawn(async move {
+            let mut interval = interval(poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_once().await {
+                    error!("Settlement submitter pass failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// Follow an external submitter: reload the shared settlement file so read endpoints
+/// stay current, and mirror batch outcomes onto the bets in this process's database
+pub fn follow_external_submitter(
+    persistence: Arc<SettlementPersistence>,
+    db: Arc<Database>,
+    poll_interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = interval(poll_interval);
+        let mut since = Utc::now();
+        loop {
+            interval.tick().await;
+            match sync_bet_settlements(&persistence, &db, since).await {
+                Ok(latest) => since = latest,
+                Err(e) => warn!("Failed to sync settlements from the submitter: {}", e),
+            }
+        }
+    })
+}
+
+/// Apply the status of batches updated after `since` to their bets; returns the latest
+/// update seen, to pass as `since` next time
+pub async fn sync_bet_settlements(
+    persistence: &SettlementPersistence,
+    db: &Database,
+    since: DateTime<Utc>,
+) -> Result<DateTime<Utc>> {
+    persistence.reload().await?;
+    let mut latest = since;
+    for batch in persistence.get_batches_updated_since(since).await? {
+        latest = latest.max(batch.updated_at);
+        let status = match batch.status {
+            SettlementBatchStatus::Submitted => BetSettlementStatus::Submitted,
+            SettlementBatchStatus::Confirmed => BetSettlementStatus::Confirmed,
+            SettlementBatchStatus::Failed => BetSettlementStatus::Failed,
+            _ => continue,
+        };
+        update_bet_settlements(
+            db,
+            batch.batch_id,
+            status,
+            batch.transaction_signature.as_deref(),
+        )
+        .await;
+    }
+    Ok(latest)
+}
+
+pub async fn update_bet_settlements(
+    db: &Database,
+    batch_id: u64,
+    status: BetSettlementStatus,
+    transaction_signature: Option<&str>,
+) {
+    if let Err(e) = db
+        .update_batch_settlement(batch_id, status, transaction_signature)
+        .await
+    {
+        error!(
+            "Failed to update settlement status for batch {}: {}",
+            batch_id, e
+        );
+    }
+}
+
+/// Submit settlement batch to Solana with ZK proof (Phase 3e implementation)
+async fn submit_batch_to_solana_with_proof(
+    solana_client: &SolanaClient,
+    settlement_persistence: &SettlementPersistence,
+    vrf_pubkey: Pubkey,
+    batch_id: u64,
+    batch: &[SettlementItem],
+    proof_data: &[u8],
+) -> Result<Signature> {
+    info!(
+        "Submitting batch {} with ZK proof to Solana (size: {} bytes)",
+        batch_id,
+        proof_data.len()
+    );
+    let batch_data = build_batch_data(settlement_persistence, vrf_pubkey, batch_id, batch).await?;
+
+    // Sign first and persist the intent before sending, so a crash mid-send is
+    // recovered by looking up the signature instead of submitting a second copy
+    let prepared = solana_client
+        .prepare_settlement_transaction(batch_data, verifier_proof(proof_data))
+        .await?;
+    settlement_persistence
+        .store_submission_intent(
+            batch_id,
+            SubmissionIntent {
+                signature: prepared.signature.to_string(),
+                message_hash: prepared.message_hash.to_string(),
+                last_valid_block_height: prepared.last_valid_block_height,
+                created_at: chrono::Utc::now(),
+            },
+        )
+        .await?;
+
+    // Submit to Solana with real ZK proof
+    solana_client.send_prepared_transaction(&prepared).await
+}
+
+/// The verifier's view of a batch: its bets, the balance state roots it moves between
+/// (on top of every confirmed batch) and its VRF commitment
+pub async fn build_batch_data(
+    settlement_persistence: &SettlementPersistence,
+    vrf_pubkey: Pubkey,
+    batch_id: u64,
+    batch: &[SettlementItem],
+) -> Result<BatchSettlementData> {
+    let bet_settlements = bet_settlements(batch)?;
+
+    // The verifier only accepts the batch on top of the state root of confirmed batches
+    let (prev_state_root, new_state_root) =
+        state_root::transition(&settlement_persistence.settled_balances().await?, batch)?;
+
+    Ok(BatchSettlementData {
+        batch_id,
+        sequencer_nonce: batch_id, // Use batch_id as nonce
+        game_id: batch
+            .first()
+            .map(|item| item.game)
+            .unwrap_or_default()
+            .game_id(),
+        prev_state_root,
+        new_state_root,
+        vrf_pubkey,
+        vrf_outputs_root: vrf_outputs_root(batch)?,
+        bets: bet_settlements,
+        sequencer_signature: Signature::default(), // Signed by the Solana client on submission
+    })
+}
+
+/// Convert settlement items to the on-chain bet list. Deterministic, since the proof
+/// commits to this list before the batch is submitted.
+pub fn bet_settlements(batch: &[SettlementItem]) -> Result<Vec<BetSettlement>> {
+    batch
+        .iter()
+        .map(|item| {
+            let user = state_root::player_key(&item.player_address);
+
+            // Structured bet IDs map losslessly onto the on-chain u64 bet_id
+            let bet_id = BetId::from_str(&item.bet_id)
+                .map_err(|e| anyhow!("Invalid bet ID {}: {}", item.bet_id, e))?
+                .as_u64();
+
+            // Determine outcome from payout (won if payout > 0)
+            let won = item.payout > 0;
+            let user_guess = 1u8; // Default guess (in real system this would be stored)
+            let outcome = if won { user_guess } else { 1 - user_guess };
+
+            Ok(BetSettlement {
+                bet_id,
+                user,
+                bet_amount: item.amount as u64,
+                user_guess,
+                outcome,
+                payout: item.payout as u64,
+            })
+        })
+        .collect()
+}
+
+/// Commitment to a batch's VRF outputs: the transcript Merkle root over the proofs of
+/// its bets, in batch order. Bets settled without a VRF proof are left out.
+pub fn vrf_outputs_root(batch: &[SettlementItem]) -> Result<[u8; 32]> {
+    let leaves = batch
+        .iter()
+        .filter_map(|item| Some((item, item.vrf_proof.as_deref()?)))
+        .map(|(item, proof)| {
+            let proof = Signature::from_str(proof)
+                .map_err(|e| anyhow!("Invalid VRF proof for bet {}: {}", item.bet_id, e))?;
+            Ok(transcript_leaf(&item.bet_id, &proof))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(merkle_root(&leaves).to_bytes())
+}
+
+/// Resolve settlement transactions that were signed and possibly sent before a crash
+pub async fn recover_submission_intents(
+    solana_client: &SolanaClient,
+    settlement_persistence: &SettlementPersistence,
+    db: &Database,
+) -> Result<()> {
+    for batch in settlement_persistence
+        .get_unresolved_submission_intents()
+        .await?
+    {
+        let Some(intent) = batch.submission_intent else {
+            continue;
+        };
+        let batch_id = batch.batch_id;
+        let signature = Signature::from_str(&intent.signature)
+            .map_err(|e| anyhow!("Invalid intent signature for batch {}: {}", batch_id, e))?;
+
+        match solana_client
+            .check_submission_status(&signature, intent.last_valid_block_height)
+            .await?
+        {
+            SubmissionStatus::Landed => {
+                info!(
+                    "Batch {} transaction {} landed before restart, marking confirmed",
+                    batch_id, signature
+                );
+                settlement_persistence
+                    .store_transaction(batch_id, &intent.signature)
+                    .await?;
+                settlement_persistence
+                    .mark_completed(&format!("batch_{}", batch_id))
+                    .await?;
+                update_bet_settlements(
+                    db,
+                    batch_id,
+                    BetSettlementStatus::Confirmed,
+                    Some(&intent.signature),
+                )
+                .await;
+            }
+            SubmissionStatus::FailedOnChain(reason) => {
+                warn!(
+                    "Batch {} transaction {} failed on-chain: {}",
+                    batch_id, signature, reason
+                );
+                settlement_persistence
+                    .clear_submission_intent(batch_id)
+                    .await?;
+                settlement_persistence
+                    .update_batch_status(batch_id, SettlementBatchStatus::Failed, Some(reason))
+                    .await?;
+                update_bet_settlements(db, batch_id, BetSettlementStatus::Failed, None).await;
+            }
+            SubmissionStatus::Expired => {
+                // The signed transaction can never land, so a fresh submission is safe
+                info!(
+                    "Batch {} transaction {} expired without landing, safe to resubmit",
+                    batch_id, signature
+                );
+                settlement_persistence
+                    .clear_submission_intent(batch_id)
+                    .await?;
+            }
+            SubmissionStatus::InFlight => {
+                info!(
+                    "Batch {} transaction {} may still land (valid until block height {})",
+                    batch_id, signature, intent.last_valid_block_height
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Bet;
+
+    #[tokio::test]
+    async fn test_external_submitter_shares_settlement_file() {
+        let dir = std::env::temp_dir().join(format!("zkcasino-submitter-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+        let item = SettlementItem {
+            bet_id: "bet_1".to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            payout: 200,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        };
+
+        // The sequencer proves batches; the submitter process has its own view of the file
+        let sequencer = SettlementPersistence::new(&url).await.unwrap().shared();
+        let submitter = SettlementSubmitter::new(
+            None,
+            Arc::new(SettlementPersistence::new(&url).await.unwrap().shared()),
+            Arc::new(Database::new("").await.unwrap()),
+            Pubkey::new_unique(),
+        );
+        let proved = sequencer
+            .create_batch(std::slice::from_ref(&item))
+            .await
+            .unwrap();
+        sequencer
+            .store_proof(proved, &[7; 64], &[1; 32])
+            .await
+            .unwrap();
+
+        assert_eq!(submitter.run_once().await.unwrap(), 1);
+        assert_eq!(submitter.run_once().await.unwrap(), 0); // Submitted exactly once
+
+        // The sequencer's next write builds on the submitter's update instead of undoing it
+        let pending = sequencer.create_batch(&[item]).await.unwrap();
+        let confirmed = sequencer
+            .get_confirmed_batches_in_range(proved, pending)
+            .await
+            .unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(
+            confirmed[0].transaction_signature.as_deref(),
+            Some(format!("mock_tx_{}_confirmed", proved).as_str())
+        );
+        assert_eq!(confirmed[0].proof_data.as_deref(), Some(&[7u8; 64][..]));
+
+        // Batches proved without a proof of their own go out with the placeholder
+        sequencer
+            .update_batch_status(pending, SettlementBatchStatus::Proved, None)
+            .await
+            .unwrap();
+        assert_eq!(submitter.run_once().await.unwrap(), 1);
+        sequencer.reload().await.unwrap();
+        assert_eq!(
+            sequencer
+                .get_confirmed_batches_in_range(pending, pending)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(!dir.join("casino.settlement.json.lock").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_vrf_outputs_root() {
+        let vrf = solana_sdk::signature::Keypair::new();
+        let item = |bet_id: &str, vrf_proof: Option<String>| SettlementItem {
+            bet_id: bet_id.to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            payout: 0,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof,
+        };
+        let proofs: Vec<Signature> = ["a", "b"]
+            .iter()
+            .map(|bet_id| {
+                let input = crate::vrf::CoinflipInput {
+                    bet_id,
+                    player: "player",
+                    amount: 100,
+                    guess: true,
+                };
+                crate::vrf::prove_coinflip(&vrf, &input).1
+            })
+            .collect();
+
+        let batch = vec![
+            item("a", Some(proofs[0].to_string())),
+            item("unproven", None),
+            item("b", Some(proofs[1].to_string())),
+        ];
+        let expected = merkle_root(&[
+            transcript_leaf("a", &proofs[0]),
+            transcript_leaf("b", &proofs[1]),
+        ]);
+        assert_eq!(vrf_outputs_root(&batch).unwrap(), expected.to_bytes());
+
+        // Order matters: the commitment fixes which output went to which bet
+        let swapped = vec![batch[2].clone(), batch[0].clone()];
+        assert_ne!(vrf_outputs_root(&swapped).unwrap(), expected.to_bytes());
+
+        assert!(vrf_outputs_root(&[item("c", Some("not a signature".to_string()))]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sync_bet_settlements_from_submitter() {
+        let dir = std::env::temp_dir().join(format!("zkcasino-sync-{}", uuid::Uuid::new_v4()));
+        let url = format!("sqlite:{}", dir.join("casino.db").display());
+        let sequencer = SettlementPersistence::new(&url).await.unwrap().shared();
+        let submitter_file = SettlementPersistence::new(&url).await.unwrap().shared();
+
+        let db = Database::new("").await.unwrap();
+        db.save_bet(&Bet {
+            id: "bet_1".to_string(),
+            player_address: "player".to_string(),
+            amount: 100,
+            guess: true,
+            result: true,
+            won: true,
+            payout: 200,
+            timestamp: Utc::now(),
+            settlement: None,
+            escrow: None,
+        })
+        .await
+        .unwrap();
+        let batch_id = sequencer.create_batch(&[]).await.unwrap();
+        db.link_bets_to_batch(batch_id, &["bet_1".to_string()])
+            .await
+            .unwrap();
+        let since = Utc::now();
+
+        // Nothing has happened to the batch yet
+        assert_eq!(
+            sync_bet_settlements(&sequencer, &db, since).await.unwrap(),
+            since
+        );
+
+        submitter_file
+            .store_transaction(batch_id, "sig_1")
+            .await
+            .unwrap();
+        submitter_file
+            .mark_completed(&format!("batch_{}", batch_id))
+            .await
+            .unwrap();
+        let latest = sync_bet_settlements(&sequencer, &db, since).await.unwrap();
+        assert!(latest > since);
+
+        let link = db
+            .get_bet("bet_1")
+            .await
+            .unwrap()
+            .unwrap()
+            .settlement
+            .unwrap();
+        assert_eq!(link.status, BetSettlementStatus::Confirmed);
+        assert_eq!(link.transaction_signature.as_deref(), Some("sig_1"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_submitter_mode_parsing() {
+        assert_eq!(
+            "External".parse::<SubmitterMode>().unwrap(),
+            SubmitterMode::External
+        );
+        assert_eq!(
+            "embedded".parse::<SubmitterMode>().unwrap(),
+            SubmitterMode::Embedded
+        );
+        assert!("remote".parse::<SubmitterMode>().is_err());
+    }
+}
@@ -0,0 +1,259 @@
+// Token amounts for ZK Casino
+// Each settlement token has its decimals, the smallest increment the casino accepts
+// (its unit) and a dust threshold below which amounts are rejected outright. Amounts
+// are validated and normalized at the API boundary: anything finer than the unit is
+// rounded down, and that residue goes to the house fee bucket rather than a player.
+//
+// Payouts round the same way everywhere: floor(stake * numerator / denominator). The
+// circuit and the verifier program check payouts with the same integer arithmetic, so
+// a fraction of a base unit is never paid out.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::SettlementToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenSpec {
+    pub decimals: u8,
+    pub unit: u64,           // Smallest accepted increment, in base units
+    pub dust_threshold: u64, // Smallest accepted amount, in base units
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AmountError {
+    #[error("Amount must be at least {threshold} base units")]
+    BelowDust { threshold: u64 },
+}
+
+impl TokenSpec {
+    /// Round `amount` down to the unit; returns the normalized amount and the residue
+    pub fn normalize(&self, amount: u64) -> Result<(u64, u64), AmountError> {
+        let residue = amount % self.unit;
+        let normalized = amount - residue;
+        if normalized < self.dust_threshold {
+            return Err(AmountError::BelowDust {
+                threshold: self.dust_threshold,
+            });
+        }
+        Ok((normalized, residue))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenConfig {
+    pub sol: TokenSpec,
+    pub usdc: TokenSpec,
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            sol: TokenSpec {
+                decimals: 9,
+                unit: 1,
+                dust_threshold: 1000, // 0.000001 SOL
+            },
+            usdc: TokenSpec {
+                decimals: 6,
+                unit: 1,
+                dust_threshold: 1000, // 0.001 USDC
+            },
+        }
+    }
+}
+
+impl TokenConfig {
+    /// Load from environment: {SOL,USDC}_DECIMALS, and {SOL,USDC}_AMOUNT_UNIT and
+    /// {SOL,USDC}_DUST_THRESHOLD as decimal token amounts (e.g. "0.000001")
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            sol: spec_from_env("SOL", defaults.sol)?,
+            usdc: spec_from_env("USDC", defaults.usdc)?,
+        })
+    }
+
+    pub fn spec(&self, token: SettlementToken) -> &TokenSpec {
+        match token {
+            SettlementToken::Sol => &self.sol,
+            SettlementToken::Usdc => &self.usdc,
+        }
+    }
+}
+
+fn spec_from_env(prefix: &str, default: TokenSpec) -> Result<TokenSpec> {
+    let decimals = match std::env::var(format!("{}_DECIMALS", prefix)) {
+        Ok(v) => v
+            .parse()
+            .map_err(|e| anyhow!("Invalid {}_DECIMALS: {}", prefix, e))?,
+        Err(_) => default.decimals,
+    };
+    if decimals > 18 {
+        return Err(anyhow!("{}_DECIMALS cannot exceed 18", prefix));
+    }
+    let amount = |name: &str, default: u64| -> Result<u64> {
+        let var = format!("{}_{}", prefix, name);
+        match std::env::var(&var) {
+            Ok(v) => parse_amount(&v, decimals).map_err(|e| anyhow!("Invalid {}: {}", var, e)),
+            Err(_) => Ok(default),
+        }
+    };
+    let spec = TokenSpec {
+        decimals,
+        unit: amount("AMOUNT_UNIT", default.unit)?,
+        dust_threshold: amount("DUST_THRESHOLD", default.dust_threshold)?,
+    };
+    if spec.unit == 0 {
+        return Err(anyhow!("{}_AMOUNT_UNIT must be greater than 0", prefix));
+    }
+    Ok(spec)
+}
+
+/// Parse a decimal token amount ("1.25") into base units
+pub fn parse_amount(value: &str, decimals: u8) -> Result<u64> {
+    let (whole, fraction) = value.trim().split_once('.').unwrap_or((value.trim(), ""));
+    if fraction.len() > decimals as usize {
+        return Err(anyhow!("more than {} decimal places", decimals));
+    }
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(anyhow!("empty amount"));
+    }
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(anyhow!("not a decimal amount"));
+    }
+    digits.parse().map_err(|e| anyhow!("{}", e))
+}
+
+/// Payout of `stake` at `numerator / denominator` odds, rounded down
+pub fn scaled_payout(stake: u64, numerator: u64, denominator: u64) -> u64 {
+    (stake as u128 * numerator as u128 / denominator as u128) as u64
+}
+
+pub(crate) const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Coinflip payout schedule, mirroring the verifier's GameConfig account: a win pays
+/// the multiplier less the house edge, both in basis points. The two must agree or
+/// settlement batches are rejected on-chain. The accounting circuit still proves 2x
+/// payouts, so a house edge only settles with proofs once the circuit takes the schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayoutSchedule {
+    pub multiplier_bps: u64,
+    pub house_edge_bps: u64,
+}
+
+impl Default for PayoutSchedule {
+    fn default() -> Self {
+        Self {
+            multiplier_bps: 20_000, // 2x
+            house_edge_bps: 0,
+        }
+    }
+}
+
+impl PayoutSchedule {
+    /// Load from environment (COINFLIP_PAYOUT_MULTIPLIER_BPS, COINFLIP_HOUSE_EDGE_BPS)
+    pub fn from_env() -> Result<Self> {
+        let env_or = |name: &str, default: u64| -> Result<u64> {
+            match std::env::var(name) {
+                Ok(v) => v.parse().map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(default),
+            }
+        };
+        let defaults = Self::default();
+        let schedule = Self {
+            multiplier_bps: env_or("COINFLIP_PAYOUT_MULTIPLIER_BPS", defaults.multiplier_bps)?,
+            house_edge_bps: env_or("COINFLIP_HOUSE_EDGE_BPS", defaults.house_edge_bps)?,
+        };
+        // Same bounds the verifier program enforces
+        if schedule.multiplier_bps < BPS_DENOMINATOR || schedule.multiplier_bps > u32::MAX as u64 {
+            return Err(anyhow!(
+                "COINFLIP_PAYOUT_MULTIPLIER_BPS must be between {} and {}",
+                BPS_DENOMINATOR,
+                u32::MAX
+            ));
+        }
+        if schedule.house_edge_bps > 1_000 {
+            return Err(anyhow!("COINFLIP_HOUSE_EDGE_BPS cannot exceed 1000 (10%)"));
+        }
+        Ok(schedule)
+    }
+
+    /// Payout of a winning bet, rounded down
+    pub fn win_payout(&self, stake: u64) -> u64 {
+        scaled_payout(
+            stake,
+            self.multiplier_bps * (BPS_DENOMINATOR - self.house_edge_bps),
+            BPS_DENOMINATOR * BPS_DENOMINATOR,
+        )
+    }
+
+    /// Payout of a winning bet, or None when it doesn't fit in a u64
+    pub fn checked_win_payout(&self, stake: u64) -> Option<u64> {
+        let payout = stake as u128
+            * (self.multiplier_bps * (BPS_DENOMINATOR - self.house_edge_bps)) as u128
+            / (BPS_DENOMINATOR * BPS_DENOMINATOR) as u128;
+        u64::try_from(payout).ok()
+    }
+}
+
+/// House fees accrued per token, in base units
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HouseFees {
+    pub sol: u64,
+    pub usdc: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_rounds_down_and_rejects_dust() {
+        let spec = TokenSpec {
+            decimals: 9,
+            unit: 1000,
+            dust_threshold: 5000,
+        };
+        assert_eq!(spec.normalize(12_345), Ok((12_000, 345)));
+        assert_eq!(spec.normalize(5000), Ok((5000, 0)));
+        assert_eq!(
+            spec.normalize(4999),
+            Err(AmountError::BelowDust { threshold: 5000 })
+        );
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("1", 9).unwrap(), 1_000_000_000);
+        assert_eq!(parse_amount("0.000001", 9).unwrap(), 1000);
+        assert_eq!(parse_amount("1.5", 6).unwrap(), 1_500_000);
+        assert_eq!(parse_amount(".25", 6).unwrap(), 250_000);
+        assert!(parse_amount("0.0000001", 6).is_err());
+        assert!(parse_amount("-1", 6).is_err());
+        assert!(parse_amount(".", 6).is_err());
+    }
+
+    #[test]
+    fn test_payout_schedule() {
+        let schedule = PayoutSchedule::default();
+        assert_eq!(schedule.win_payout(1001), 2002);
+
+        let schedule = PayoutSchedule {
+            multiplier_bps: 20_000,
+            house_edge_bps: 100, // 1.98x
+        };
+        assert_eq!(schedule.win_payout(1_000_000), 1_980_000);
+        assert_eq!(schedule.win_payout(1001), 1981);
+    }
+
+    #[test]
+    fn test_scaled_payout_rounds_down() {
+        assert_eq!(scaled_payout(1001, 2, 1), 2002);
+        // 1.5x of an odd stake drops the half base unit
+        assert_eq!(scaled_payout(1001, 3, 2), 1501);
+        assert_eq!(scaled_payout(u64::MAX / 2, 2, 1), u64::MAX - 1);
+    }
+}
@@ -0,0 +1,536 @@
+// House bankroll rebalancing for ZK Casino
+// Watches the on-chain house treasury: below the floor it proposes a top-up from the
+// reserve wallet, above the ceiling a sweep to cold storage. Movements only execute
+// once enough configured co-signers have signed the movement digest, and every
+// state change is logged (and appended to TREASURY_AUDIT_LOG when set).
+
+use anyhow::{anyhow, Result};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature};
+use solana_sdk::signer::Signer;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::interval;
+use tracing::{error, info, warn};
+
+use crate::solana::SolanaClient;
+
+/// Balance lookups and transfers, so the manager can run against a mock chain in tests
+#[async_trait]
+pub trait TreasuryLedger: Send + Sync {
+    async fn balance(&self, account: &Pubkey) -> Result<u64>;
+    async fn transfer(&self, from: &Keypair, to: &Pubkey, lamports: u64) -> Result<String>;
+}
+
+#[async_trait]
+impl TreasuryLedger for SolanaClient {
+    async fn balance(&self, account: &Pubkey) -> Result<u64> {
+        self.get_balance(account).await
+    }
+
+    async fn transfer(&self, from: &Keypair, to: &Pubkey, lamports: u64) -> Result<String> {
+        Ok(self
+            .transfer_lamports(from, to, lamports)
+            .await?
+            .to_string())
+    }
+}
+
+pub struct TreasuryConfig {
+    pub treasury: Arc<Keypair>, // House treasury paying out winnings
+    pub reserve: Arc<Keypair>,  // Funds top-ups
+    pub cold_storage: Pubkey,   // Receives sweeps
+    pub floor_lamports: u64,    // Top up when the treasury drops below this
+    pub target_lamports: u64,   // Rebalance back to this
+    pub ceiling_lamports: u64,  // Sweep when the treasury rises above this
+    pub check_interval: Duration,
+    pub cosigners: Vec<Pubkey>,
+    pub required_approvals: usize,
+    pub audit_log: Option<PathBuf>,
+}
+
+impl TreasuryConfig {
+    /// Load from environment; None when TREASURY_KEYPAIR_PATH is unset.
+    /// (TREASURY_RESERVE_KEYPAIR_PATH, TREASURY_COLD_STORAGE, TREASURY_FLOOR_LAMPORTS,
+    /// TREASURY_TARGET_LAMPORTS, TREASURY_CEILING_LAMPORTS, TREASURY_CHECK_INTERVAL_SECS,
+    /// TREASURY_COSIGNERS, TREASURY_REQUIRED_APPROVALS, TREASURY_AUDIT_LOG)
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(treasury_path) = std::env::var("TREASURY_KEYPAIR_PATH") else {
+            info!("Treasury rebalancing disabled. Set TREASURY_KEYPAIR_PATH to enable.");
+            return Ok(None);
+        };
+        let required = |name: &str| {
+            std::env::var(name).map_err(|_| anyhow!("Treasury rebalancing requires {}", name))
+        };
+        let lamports = |name: &str| -> Result<u64> {
+            required(name)?
+                .parse()
+                .map_err(|e| anyhow!("Invalid {}: {}", name, e))
+        };
+        let keypair = |path: &str, label: &str| {
+            read_keypair_file(path)
+                .map(Arc::new)
+                .map_err(|e| anyhow!("Failed to read {} keypair from {}: {}", label, path, e))
+        };
+
+        let cosigners = required("TREASURY_COSIGNERS")?
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| Pubkey::from_str(s).map_err(|e| anyhow!("Invalid co-signer {}: {}", s, e)))
+            .collect::<Result<Vec<_>>>()?;
+        let required_approvals = match std::env::var("TREASURY_REQUIRED_APPROVALS") {
+            Ok(v) => v
+                .parse()
+                .map_err(|e| anyhow!("Invalid TREASURY_REQUIRED_APPROVALS: {}", e))?,
+            Err(_) => cosigners.len().min(2),
+        };
+
+        let config = Self {
+            treasury: keypair(&treasury_path, "treasury")?,
+            reserve: keypair(&required("TREASURY_RESERVE_KEYPAIR_PATH")?, "reserve")?,
+            cold_storage: Pubkey::from_str(&required("TREASURY_COLD_STORAGE")?)
+                .map_err(|e| anyhow!("Invalid TREASURY_COLD_STORAGE: {}", e))?,
+            floor_lamports: lamports("TREASURY_FLOOR_LAMPORTS")?,
+            target_lamports: lamports("TREASURY_TARGET_LAMPORTS")?,
+            ceiling_lamports: lamports("TREASURY_CEILING_LAMPORTS")?,
+            check_interval: Duration::from_secs(
+                std::env::var("TREASURY_CHECK_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60),
+            ),
+            cosigners,
+            required_approvals,
+            audit_log: std::env::var("TREASURY_AUDIT_LOG").ok().map(PathBuf::from),
+        };
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.floor_lamports <= self.target_lamports
+            && self.target_lamports <= self.ceiling_lamports)
+        {
+            return Err(anyhow!(
+                "Treasury limits must satisfy floor <= target <= ceiling"
+            ));
+        }
+        if self.required_approvals == 0 || self.required_approvals > self.cosigners.len() {
+            return Err(anyhow!(
+                "Treasury co-signing needs 1..={} approvals, got {}",
+                self.cosigners.len(),
+                self.required_approvals
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementKind {
+    TopUp, // Reserve -> treasury
+    Sweep, // Treasury -> cold storage
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementStatus {
+    PendingApproval,
+    Executing, // Co-signing policy met, transfer in progress
+    Executed,
+    Failed,
+    Cancelled, // Balance returned to range before the movement was approved
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TreasuryMovement {
+    pub id: u64,
+    pub kind: MovementKind,
+    pub from: String,
+    pub to: String,
+    pub lamports: u64,
+    pub treasury_balance: u64, // Balance that triggered the movement
+    pub status: MovementStatus,
+    pub approvals: Vec<String>, // Co-signer public keys
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub transaction_signature: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TreasuryMovement {
+    /// Message co-signers sign to approve this movement
+    pub fn digest(&self) -> String {
+        format!(
+            "zkcasino-treasury-movement:{}:{:?}:{}:{}:{}",
+            self.id, self.kind, self.from, self.to, self.lamports
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TreasuryError {
+    #[error("Treasury movement {0} not found")]
+    NotFound(u64),
+    #[error("Treasury movement {0} is not awaiting approval")]
+    NotPending(u64),
+    #[error("{0} is not a treasury co-signer")]
+    UnknownCosigner(Pubkey),
+    #[error("Invalid co-signer signature")]
+    InvalidSignature,
+    #[error("Co-signer {0} already approved this movement")]
+    DuplicateApproval(Pubkey),
+}
+
+/// Movement needed to bring the treasury back to target, if it is out of range
+pub fn plan_rebalance(balance: u64, config: &TreasuryConfig) -> Option<(MovementKind, u64)> {
+    if balance < config.floor_lamports {
+        Some((MovementKind::TopUp, config.target_lamports - balance))
+    } else if balance > config.ceiling_lamports {
+        Some((MovementKind::Sweep, balance - config.target_lamports))
+    } else {
+        None
+    }
+}
+
+pub struct TreasuryManager {
+    config: TreasuryConfig,
+    ledger: Arc<dyn TreasuryLedger>,
+    movements: RwLock<Vec<TreasuryMovement>>,
+}
+
+impl TreasuryManager {
+    pub fn new(config: TreasuryConfig, ledger: Arc<dyn TreasuryLedger>) -> Self {
+        Self {
+            config,
+            ledger,
+            movements: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn treasury_pubkey(&self) -> Pubkey {
+        self.config.treasury.pubkey()
+    }
+
+    /// All movements, newest first
+    pub fn movements(&self) -> Vec<TreasuryMovement> {
+        self.movements.read().iter().rev().cloned().collect()
+    }
+
+    fn record(&self, movement: &TreasuryMovement) {
+        info!(
+            "Treasury movement {} ({:?}, {} lamports {} -> {}): {:?}",
+            movement.id,
+            movement.kind,
+            movement.lamports,
+            movement.from,
+            movement.to,
+            movement.status
+        );
+        let Some(path) = &self.config.audit_log else {
+            return;
+        };
+        let appended = serde_json::to_string(movement)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                writeln!(file, "{}", line)
+            });
+        if let Err(e) = appended {
+            error!(
+                "Failed to append treasury audit log {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// Check the treasury balance and propose (or cancel) a rebalancing movement
+    pub async fn check(&self) -> Result<Option<TreasuryMovement>> {
+        let balance = self.ledger.balance(&self.treasury_pubkey()).await?;
+        let plan = plan_rebalance(balance, &self.config);
+
+        let mut movements = self.movements.write();
+        if let Some(pending) = movements
+            .iter_mut()
+            .find(|m| m.status == MovementStatus::PendingApproval)
+        {
+            // One movement at a time; drop it if it no longer matches the balance
+            if plan.map(|(kind, _)| kind) != Some(pending.kind) {
+                pending.status = MovementStatus::Cancelled;
+                pending.updated_at = Utc::now();
+                let cancelled = pending.clone();
+                drop(movements);
+                self.record(&cancelled);
+            }
+            return Ok(None);
+        }
+
+        let Some((kind, lamports)) = plan else {
+            return Ok(None);
+        };
+        let (from, to) = match kind {
+            MovementKind::TopUp => (self.config.reserve.pubkey(), self.treasury_pubkey()),
+            MovementKind::Sweep => (self.treasury_pubkey(), self.config.cold_storage),
+        };
+        let now = Utc::now();
+        let movement = TreasuryMovement {
+            id: movements.len() as u64 + 1,
+            kind,
+            from: from.to_string(),
+            to: to.to_string(),
+            lamports,
+            treasury_balance: balance,
+            status: MovementStatus::PendingApproval,
+            approvals: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            transaction_signature: None,
+            error: None,
+        };
+        movements.push(movement.clone());
+        drop(movements);
+
+        self.record(&movement);
+        Ok(Some(movement))
+    }
+
+    /// Add a co-signer approval; executes the movement once the policy is met
+    pub async fn approve(
+        &self,
+        id: u64,
+        cosigner: Pubkey,
+        signature: Signature,
+    ) -> Result<TreasuryMovement, TreasuryError> {
+        if !self.config.cosigners.contains(&cosigner) {
+            return Err(TreasuryError::UnknownCosigner(cosigner));
+        }
+
+        let movement = {
+            let mut movements = self.movements.write();
+            let movement = movements
+                .iter_mut()
+                .find(|m| m.id == id)
+                .ok_or(TreasuryError::NotFound(id))?;
+            if movement.status != MovementStatus::PendingApproval {
+                return Err(TreasuryError::NotPending(id));
+            }
+            if !signature.verify(cosigner.as_ref(), movement.digest().as_bytes()) {
+                return Err(TreasuryError::InvalidSignature);
+            }
+            if movement.approvals.contains(&cosigner.to_string()) {
+                return Err(TreasuryError::DuplicateApproval(cosigner));
+            }
+            movement.approvals.push(cosigner.to_string());
+            movement.updated_at = Utc::now();
+            // Claim execution under the lock so concurrent approvals can't transfer twice
+            if movement.approvals.len() >= self.config.required_approvals {
+                movement.status = MovementStatus::Executing;
+            }
+            movement.clone()
+        };
+        info!(
+            "Treasury movement {} approved by {} ({}/{})",
+            id,
+            cosigner,
+            movement.approvals.len(),
+            self.config.required_approvals
+        );
+
+        if movement.status != MovementStatus::Executing {
+            return Ok(movement);
+        }
+        Ok(self.execute(movement).await)
+    }
+
+    async fn execute(&self, mut movement: TreasuryMovement) -> TreasuryMovement {
+        let (signer, to) = match movement.kind {
+            MovementKind::TopUp => (&self.config.reserve, self.treasury_pubkey()),
+            MovementKind::Sweep => (&self.config.treasury, self.config.cold_storage),
+        };
+        match self.ledger.transfer(signer, &to, movement.lamports).await {
+            Ok(signature) => {
+                movement.status = MovementStatus::Executed;
+                movement.transaction_signature = Some(signature);
+            }
+            Err(e) => {
+                warn!("Treasury movement {} failed: {}", movement.id, e);
+                movement.status = MovementStatus::Failed;
+                movement.error = Some(e.to_string());
+            }
+        }
+        movement.updated_at = Utc::now();
+
+        if let Some(stored) = self
+            .movements
+            .write()
+            .iter_mut()
+            .find(|m| m.id == movement.id)
+        {
+            *stored = movement.clone();
+        }
+        self.record(&movement);
+        movement
+    }
+
+    /// Start the periodic balance check (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting treasury rebalancing for {} (floor {}, target {}, ceiling {}, {}-of-{} co-signing)",
+            self.treasury_pubkey(),
+            self.config.floor_lamports,
+            self.config.target_lamports,
+            self.config.ceiling_lamports,
+            self.config.required_approvals,
+            self.config.cosigners.len()
+        );
+
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.check_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.check().await {
+                    error!("Treasury balance check failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MockLedger {
+        balances: parking_lot::Mutex<HashMap<Pubkey, u64>>,
+    }
+
+    #[async_trait]
+    impl TreasuryLedger for MockLedger {
+        async fn balance(&self, account: &Pubkey) -> Result<u64> {
+            Ok(self.balances.lock().get(account).copied().unwrap_or(0))
+        }
+
+        async fn transfer(&self, from: &Keypair, to: &Pubkey, lamports: u64) -> Result<String> {
+            let mut balances = self.balances.lock();
+            let source = balances.entry(from.pubkey()).or_default();
+            *source = source
+                .checked_sub(lamports)
+                .ok_or_else(|| anyhow!("insufficient funds"))?;
+            *balances.entry(*to).or_default() += lamports;
+            Ok("mock_signature".to_string())
+        }
+    }
+
+    fn manager(cosigners: &[&Keypair]) -> (TreasuryManager, Arc<MockLedger>) {
+        let ledger = Arc::new(MockLedger::default());
+        let config = TreasuryConfig {
+            treasury: Arc::new(Keypair::new()),
+            reserve: Arc::new(Keypair::new()),
+            cold_storage: Pubkey::new_unique(),
+            floor_lamports: 100,
+            target_lamports: 500,
+            ceiling_lamports: 1_000,
+            check_interval: Duration::from_secs(60),
+            cosigners: cosigners.iter().map(|k| k.pubkey()).collect(),
+            required_approvals: 2,
+            audit_log: None,
+        };
+        config.validate().unwrap();
+        ledger
+            .balances
+            .lock()
+            .insert(config.reserve.pubkey(), 10_000);
+        (TreasuryManager::new(config, ledger.clone()), ledger)
+    }
+
+    async fn approve(
+        manager: &TreasuryManager,
+        movement: &TreasuryMovement,
+        cosigner: &Keypair,
+    ) -> Result<TreasuryMovement, TreasuryError> {
+        let signature = cosigner.sign_message(movement.digest().as_bytes());
+        manager
+            .approve(movement.id, cosigner.pubkey(), signature)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_top_up_requires_cosigners() {
+        let (alice, bob, mallory) = (Keypair::new(), Keypair::new(), Keypair::new());
+        let (manager, ledger) = manager(&[&alice, &bob]);
+        let treasury = manager.treasury_pubkey();
+        ledger.balances.lock().insert(treasury, 50);
+
+        let movement = manager.check().await.unwrap().unwrap();
+        assert_eq!(movement.kind, MovementKind::TopUp);
+        assert_eq!(movement.lamports, 450);
+        // Only one movement in flight at a time
+        assert!(manager.check().await.unwrap().is_none());
+
+        assert!(matches!(
+            approve(&manager, &movement, &mallory).await,
+            Err(TreasuryError::UnknownCosigner(_))
+        ));
+        let forged = alice.sign_message(b"something else");
+        assert!(matches!(
+            manager.approve(movement.id, alice.pubkey(), forged).await,
+            Err(TreasuryError::InvalidSignature)
+        ));
+
+        let partial = approve(&manager, &movement, &alice).await.unwrap();
+        assert_eq!(partial.status, MovementStatus::PendingApproval);
+        assert!(matches!(
+            approve(&manager, &movement, &alice).await,
+            Err(TreasuryError::DuplicateApproval(_))
+        ));
+        assert_eq!(ledger.balance(&treasury).await.unwrap(), 50);
+
+        let executed = approve(&manager, &movement, &bob).await.unwrap();
+        assert_eq!(executed.status, MovementStatus::Executed);
+        assert_eq!(ledger.balance(&treasury).await.unwrap(), 500);
+        assert!(matches!(
+            approve(&manager, &movement, &bob).await,
+            Err(TreasuryError::NotPending(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sweep_and_cancellation() {
+        let (alice, bob) = (Keypair::new(), Keypair::new());
+        let (manager, ledger) = manager(&[&alice, &bob]);
+        let treasury = manager.treasury_pubkey();
+
+        ledger.balances.lock().insert(treasury, 700);
+        assert!(manager.check().await.unwrap().is_none());
+
+        ledger.balances.lock().insert(treasury, 1_500);
+        let sweep = manager.check().await.unwrap().unwrap();
+        assert_eq!(sweep.kind, MovementKind::Sweep);
+        assert_eq!(sweep.lamports, 1_000);
+
+        // Balance back in range before approval: the sweep is cancelled
+        ledger.balances.lock().insert(treasury, 800);
+        assert!(manager.check().await.unwrap().is_none());
+        assert_eq!(manager.movements()[0].status, MovementStatus::Cancelled);
+        assert!(matches!(
+            approve(&manager, &sweep, &alice).await,
+            Err(TreasuryError::NotPending(_))
+        ));
+    }
+}
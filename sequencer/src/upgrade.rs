@@ -0,0 +1,495 @@
+// Coordinated program and circuit upgrades for ZK Casino
+// The verifier only accepts proofs against its active verifying key, so a batch proved
+// under one circuit can't settle after the key changes, and new program code must start
+// from the state root the old code left. Upgrades therefore run as admin steps the
+// sequencer enforces in order:
+//   begin     stop taking bets and other player writes; settlement keeps running
+//   snapshot  once every batch has settled, record the local and on-chain state roots,
+//             which must agree
+//   pause     confirm both programs are paused on-chain
+//   verify    after deploying, check the program and verifying key hashes announced at
+//             begin, and that the on-chain state root is still the snapshotted one
+//   resume    once the programs are unpaused, reopen intake
+// The sequencer doesn't hold the program authority: pausing, deploying and unpausing are
+// done by the operator, and each step only proceeds once the chain shows them done.
+// abort gives up from any step and reopens intake.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::hash::{hash, Hash};
+use solana_sdk::pubkey::Pubkey;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::self_check::active_verifying_key;
+use crate::solana::SolanaClient;
+use crate::state_root::StateRoot;
+
+/// Verifier pause bits that stop every operation (PAUSE_ALL in the verifier program)
+const VERIFIER_PAUSE_ALL: u8 = 0b111;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpgradePhase {
+    Idle,        // Normal operation
+    Draining,    // Intake stopped; waiting for pending batches to settle
+    Snapshotted, // Drained, with state roots recorded
+    Paused,      // Programs paused on-chain; safe to deploy
+    Verified,    // Deployment matches the plan; waiting for the programs to unpause
+}
+
+/// What the operator is about to deploy. Hashes left out aren't checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UpgradePlan {
+    pub verifier_program_hash: Option<String>, // Base58 SHA-256 of the program's code
+    pub vault_program_hash: Option<String>,
+    pub verifying_key_hash: Option<String>, // Base58 SHA-256 of the active verifying key
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StateSnapshot {
+    pub local_state_root: String, // Base58 root of the settled balances
+    pub onchain_state_root: Option<String>, // None when Solana isn't configured
+    pub taken_at: DateTime<Utc>,
+}
+
+/// What the chain shows about the programs, read fresh for each step
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OnchainView {
+    pub verifier_paused: bool, // Every verifier operation is paused
+    pub vault_paused: bool,    // Every vault operation is paused, with no expiry slot
+    pub state_root: String,
+    pub verifier_program_hash: Option<String>, // None for programs not deployed upgradeable
+    pub vault_program_hash: Option<String>,
+    pub verifying_key_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpgradeStatus {
+    pub phase: UpgradePhase,
+    pub plan: Option<UpgradePlan>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub snapshot: Option<StateSnapshot>,
+    pub deployed: Option<OnchainView>, // What verify found
+}
+
+impl Default for UpgradeStatus {
+    fn default() -> Self {
+        Self {
+            phase: UpgradePhase::Idle,
+            plan: None,
+            started_at: None,
+            snapshot: None,
+            deployed: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UpgradeError {
+    #[error("This step needs the upgrade to be {expected:?}, but it is {actual:?}")]
+    WrongPhase {
+        expected: UpgradePhase,
+        actual: UpgradePhase,
+    },
+    #[error(
+        "Settlement has not drained: {pending_batches} batches and {queued_bets} bets pending"
+    )]
+    NotDrained {
+        pending_batches: usize,
+        queued_bets: u64,
+    },
+    #[error("State roots disagree: expected {expected}, on-chain {onchain}")]
+    StateRootMismatch { expected: String, onchain: String },
+    #[error("Programs are not paused on-chain (verifier: {verifier}, vault: {vault})")]
+    NotPaused { verifier: bool, vault: bool },
+    #[error("Programs are still paused on-chain")]
+    StillPaused,
+    #[error("Deployment does not match the upgrade plan: {0}")]
+    PlanMismatch(String),
+    #[error("Solana is not configured")]
+    NoSolana,
+    #[error(transparent)]
+    Onchain(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Default)]
+pub struct UpgradeCoordinator {
+    status: RwLock<UpgradeStatus>,
+}
+
+impl UpgradeCoordinator {
+    pub fn status(&self) -> UpgradeStatus {
+        self.status.read().clone()
+    }
+
+    /// Whether players may place bets and make other writes
+    pub fn intake_open(&self) -> bool {
+        self.status.read().phase == UpgradePhase::Idle
+    }
+
+    /// Fail early when a step is out of order, before reading the chain for it
+    pub fn require_phase(&self, phase: UpgradePhase) -> Result<(), UpgradeError> {
+        let actual = self.status.read().phase;
+        if actual != phase {
+            return Err(UpgradeError::WrongPhase {
+                expected: phase,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Move from `from` to `to`, applying `update` only when the phase matches
+    fn advance(
+        &self,
+        from: UpgradePhase,
+        to: UpgradePhase,
+        update: impl FnOnce(&mut UpgradeStatus) -> Result<(), UpgradeError>,
+    ) -> Result<UpgradeStatus, UpgradeError> {
+        let mut status = self.status.write();
+        if status.phase != from {
+            return Err(UpgradeError::WrongPhase {
+                expected: from,
+                actual: status.phase,
+            });
+        }
+        update(&mut status)?;
+        status.phase = to;
+        info!("Upgrade {:?} -> {:?}", from, to);
+        Ok(status.clone())
+    }
+
+    /// Stop intake for an upgrade
+    pub fn begin(&self, plan: UpgradePlan) -> Result<UpgradeStatus, UpgradeError> {
+        self.advance(UpgradePhase::Idle, UpgradePhase::Draining, |status| {
+            *status = UpgradeStatus {
+                plan: Some(plan),
+                started_at: Some(Utc::now()),
+                ..Default::default()
+            };
+            Ok(())
+        })
+    }
+
+    /// Record the state roots once nothing is left to settle
+    pub fn snapshot(
+        &self,
+        pending_batches: usize,
+        queued_bets: u64,
+        local_state_root: StateRoot,
+        onchain: Option<&OnchainView>,
+    ) -> Result<UpgradeStatus, UpgradeError> {
+        self.advance(
+            UpgradePhase::Draining,
+            UpgradePhase::Snapshotted,
+            |status| {
+                if pending_batches > 0 || queued_bets > 0 {
+                    return Err(UpgradeError::NotDrained {
+                        pending_batches,
+                        queued_bets,
+                    });
+                }
+                let local_state_root = Hash::new_from_array(local_state_root).to_string();
+                if let Some(onchain) = onchain {
+                    if onchain.state_root != local_state_root {
+                        return Err(UpgradeError::StateRootMismatch {
+                            expected: local_state_root,
+                            onchain: onchain.state_root.clone(),
+                        });
+                    }
+                }
+                status.snapshot = Some(StateSnapshot {
+                    local_state_root,
+                    onchain_state_root: onchain.map(|onchain| onchain.state_root.clone()),
+                    taken_at: Utc::now(),
+                });
+                Ok(())
+            },
+        )
+    }
+
+    /// Confirm the programs are paused, so the deploy can start
+    pub fn confirm_paused(&self, onchain: &OnchainView) -> Result<UpgradeStatus, UpgradeError> {
+        self.advance(UpgradePhase::Snapshotted, UpgradePhase::Paused, |_| {
+            if !onchain.verifier_paused || !onchain.vault_paused {
+                return Err(UpgradeError::NotPaused {
+                    verifier: onchain.verifier_paused,
+                    vault: onchain.vault_paused,
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Check the deployment against the plan and the snapshotted state root
+    pub fn verify(&self, onchain: &OnchainView) -> Result<UpgradeStatus, UpgradeError> {
+        self.advance(UpgradePhase::Paused, UpgradePhase::Verified, |status| {
+            let plan = status.plan.clone().unwrap_or_default();
+            let mut mismatches = Vec::new();
+            for (name, expected, deployed) in [
+                (
+                    "verifier program",
+                    &plan.verifier_program_hash,
+                    &onchain.verifier_program_hash,
+                ),
+                (
+                    "vault program",
+                    &plan.vault_program_hash,
+                    &onchain.vault_program_hash,
+                ),
+                (
+                    "verifying key",
+                    &plan.verifying_key_hash,
+                    &onchain.verifying_key_hash,
+                ),
+            ] {
+                if let Some(expected) = expected {
+                    if deployed.as_ref() != Some(expected) {
+                        mismatches.push(format!(
+                            "{} is {}, expected {}",
+                            name,
+                            deployed.as_deref().unwrap_or("unknown"),
+                            expected
+                        ));
+                    }
+                }
+            }
+            if !mismatches.is_empty() {
+                return Err(UpgradeError::PlanMismatch(mismatches.join("; ")));
+            }
+
+            let snapshot = status.snapshot.as_ref();
+            if let Some(expected) = snapshot.and_then(|s| s.onchain_state_root.as_ref()) {
+                if &onchain.state_root != expected {
+                    return Err(UpgradeError::StateRootMismatch {
+                        expected: expected.clone(),
+                        onchain: onchain.state_root.clone(),
+                    });
+                }
+            }
+            status.deployed = Some(onchain.clone());
+            Ok(())
+        })
+    }
+
+    /// Reopen intake once the upgraded programs are unpaused
+    pub fn resume(&self, onchain: &OnchainView) -> Result<UpgradeStatus, UpgradeError> {
+        self.advance(UpgradePhase::Verified, UpgradePhase::Idle, |_| {
+            if onchain.verifier_paused || onchain.vault_paused {
+                return Err(UpgradeError::StillPaused);
+            }
+            Ok(())
+        })
+    }
+
+    /// Give up on the upgrade and reopen intake
+    pub fn abort(&self) -> UpgradeStatus {
+        let mut status = self.status.write();
+        if status.phase != UpgradePhase::Idle {
+            warn!("Upgrade aborted while {:?}", status.phase);
+        }
+        *status = UpgradeStatus::default();
+        status.clone()
+    }
+}
+
+/// Pause bits and state root of a VerifierState account: discriminator, authority,
+/// vault program, two u64 counters, pause_flags (u8), two u64s, then state_root
+fn decode_verifier_state(data: &[u8]) -> Option<(u8, [u8; 32])> {
+    let pause_flags = *data.get(88)?;
+    let state_root = data.get(105..137)?.try_into().ok()?;
+    Some((pause_flags, state_root))
+}
+
+/// Whether a VaultState account pauses everything until cleared: discriminator,
+/// authority and three u64 totals come before pause_flags, then paused_until_slot. A
+/// pause that expires could lift in the middle of a deploy, so it doesn't count.
+fn decode_vault_paused(data: &[u8]) -> Option<bool> {
+    let pause_flags = *data.get(64)?;
+    let expires = *data.get(65)? != 0;
+    Some(pause_flags & vault::PAUSE_ALL == vault::PAUSE_ALL && !expires)
+}
+
+/// Hash of an upgradeable program's code, as `solana-verify get-program-hash` reports it
+/// (in base58): the programdata after its header, with the zero padding trimmed
+pub fn program_code_hash(programdata: &[u8]) -> Option<String> {
+    let code = programdata.get(UpgradeableLoaderState::size_of_programdata_metadata()..)?;
+    let end = code
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |i| i + 1);
+    Some(hash(&code[..end]).to_string())
+}
+
+/// Code hash of a deployed program; None unless it uses the upgradeable loader
+async fn deployed_program_hash(
+    client: &SolanaClient,
+    program_id: &Pubkey,
+) -> Result<Option<String>> {
+    let Some(program) = client.get_account(program_id).await? else {
+        return Ok(None);
+    };
+    if program.owner != bpf_loader_upgradeable::id() {
+        return Ok(None);
+    }
+    let Ok(UpgradeableLoaderState::Program {
+        programdata_address,
+    }) = bincode::deserialize(&program.data)
+    else {
+        return Ok(None);
+    };
+    Ok(client
+        .get_account(&programdata_address)
+        .await?
+        .and_then(|programdata| program_code_hash(&programdata.data)))
+}
+
+/// Read the programs' pause state, state root and deployed hashes
+pub async fn read_onchain(client: &SolanaClient) -> Result<OnchainView> {
+    let verifier_state = client
+        .get_account(&client.verifier_state_pda())
+        .await?
+        .ok_or_else(|| anyhow!("Verifier state account not found"))?;
+    let (pause_flags, state_root) = decode_verifier_state(&verifier_state.data)
+        .ok_or_else(|| anyhow!("Verifier state account is too short"))?;
+    let vault_state = client
+        .get_account(&client.vault_state_pda())
+        .await?
+        .ok_or_else(|| anyhow!("Vault state account not found"))?;
+    let vault_paused = decode_vault_paused(&vault_state.data)
+        .ok_or_else(|| anyhow!("Vault state account is too short"))?;
+    let verifying_key_hash = client
+        .get_account(&client.verifying_key_pda())
+        .await?
+        .and_then(|account| active_verifying_key(&account.data).map(|key| hash(key).to_string()));
+
+    Ok(OnchainView {
+        verifier_paused: pause_flags & VERIFIER_PAUSE_ALL == VERIFIER_PAUSE_ALL,
+        vault_paused,
+        state_root: Hash::new_from_array(state_root).to_string(),
+        verifier_program_hash: deployed_program_hash(client, &client.verifier_program_id()).await?,
+        vault_program_hash: deployed_program_hash(client, &client.vault_program_id()).await?,
+        verifying_key_hash,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn onchain(paused: bool, state_root: StateRoot) -> OnchainView {
+        OnchainView {
+            verifier_paused: paused,
+            vault_paused: paused,
+            state_root: Hash::new_from_array(state_root).to_string(),
+            verifier_program_hash: Some("verifier-v2".to_string()),
+            vault_program_hash: None,
+            verifying_key_hash: Some("vk-v2".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_steps_run_in_order() {
+        let coordinator = UpgradeCoordinator::default();
+        let root = [1; 32];
+        assert!(coordinator.intake_open());
+        assert!(matches!(
+            coordinator.confirm_paused(&onchain(true, root)),
+            Err(UpgradeError::WrongPhase { .. })
+        ));
+
+        coordinator
+            .begin(UpgradePlan {
+                verifier_program_hash: Some("verifier-v2".to_string()),
+                verifying_key_hash: Some("vk-v2".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!coordinator.intake_open());
+
+        // Nothing may be left to settle, and the roots must agree
+        assert!(matches!(
+            coordinator.snapshot(1, 0, root, None),
+            Err(UpgradeError::NotDrained { .. })
+        ));
+        assert!(matches!(
+            coordinator.snapshot(0, 0, root, Some(&onchain(false, [2; 32]))),
+            Err(UpgradeError::StateRootMismatch { .. })
+        ));
+        coordinator
+            .snapshot(0, 0, root, Some(&onchain(false, root)))
+            .unwrap();
+
+        assert!(matches!(
+            coordinator.confirm_paused(&onchain(false, root)),
+            Err(UpgradeError::NotPaused { .. })
+        ));
+        coordinator.confirm_paused(&onchain(true, root)).unwrap();
+
+        // The deployment must match the plan
+        let mut stale = onchain(true, root);
+        stale.verifying_key_hash = Some("vk-v1".to_string());
+        assert!(matches!(
+            coordinator.verify(&stale),
+            Err(UpgradeError::PlanMismatch(_))
+        ));
+        coordinator.verify(&onchain(true, root)).unwrap();
+
+        assert!(matches!(
+            coordinator.resume(&onchain(true, root)),
+            Err(UpgradeError::StillPaused)
+        ));
+        let status = coordinator.resume(&onchain(false, root)).unwrap();
+        assert_eq!(status.phase, UpgradePhase::Idle);
+        assert!(coordinator.intake_open());
+    }
+
+    #[test]
+    fn test_abort_reopens_intake() {
+        let coordinator = UpgradeCoordinator::default();
+        coordinator.begin(UpgradePlan::default()).unwrap();
+        assert!(matches!(
+            coordinator.begin(UpgradePlan::default()),
+            Err(UpgradeError::WrongPhase { .. })
+        ));
+        assert_eq!(coordinator.abort(), UpgradeStatus::default());
+        assert!(coordinator.intake_open());
+    }
+
+    #[test]
+    fn test_decode_onchain_accounts() {
+        let mut verifier_state = vec![0; 137];
+        verifier_state[88] = VERIFIER_PAUSE_ALL;
+        verifier_state[105..137].copy_from_slice(&[9; 32]);
+        assert_eq!(
+            decode_verifier_state(&verifier_state),
+            Some((VERIFIER_PAUSE_ALL, [9; 32]))
+        );
+        assert_eq!(decode_verifier_state(&verifier_state[..136]), None);
+
+        let mut vault_state = vec![0; 66];
+        assert_eq!(decode_vault_paused(&vault_state), Some(false));
+        vault_state[64] = vault::PAUSE_WITHDRAWALS;
+        assert_eq!(decode_vault_paused(&vault_state), Some(false));
+        vault_state[64] = vault::PAUSE_ALL;
+        assert_eq!(decode_vault_paused(&vault_state), Some(true));
+        vault_state[65] = 1; // Expiry slot set
+        assert_eq!(decode_vault_paused(&vault_state), Some(false));
+        assert_eq!(decode_vault_paused(&vault_state[..65]), None);
+
+        // Zero padding after the code doesn't change the hash
+        let header = UpgradeableLoaderState::size_of_programdata_metadata();
+        let mut programdata = vec![0; header];
+        programdata.extend_from_slice(b"\x7fELF code");
+        let hash = program_code_hash(&programdata).unwrap();
+        programdata.extend_from_slice(&[0; 64]);
+        assert_eq!(program_code_hash(&programdata), Some(hash));
+        assert_eq!(program_code_hash(&[0; 4]), None);
+    }
+}
@@ -0,0 +1,526 @@
+// Verifiable coin flips and on-chain transcript anchoring for ZK Casino
+// Each outcome is derived from the VRF key's ed25519 signature over the bet. Ed25519
+// signing is deterministic, so the signature fixes the outcome and anyone holding the
+// VRF public key can recompute it. Proofs issued since the last anchor are folded into
+// a Merkle root that is posted on-chain as a memo, so the sequencer cannot later
+// rewrite its randomness history without the published roots no longer matching.
+
+use anyhow::Result;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::{hash, hashv, Hash};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::interval;
+use tracing::{error, info};
+
+use crate::solana::SolanaClient;
+
+/// Domain separators so VRF messages and tree nodes never collide with other hashes
+const VRF_MESSAGE_PREFIX: &str = "zkcasino-vrf-v1";
+const LEAF_PREFIX: &[u8] = b"zkcasino-vrf-leaf";
+const NODE_PREFIX: &[u8] = b"zkcasino-vrf-node";
+const ANCHOR_MEMO_PREFIX: &str = "zkcasino-vrf-anchor:v1";
+
+/// A coin flip request, as covered by the VRF proof
+#[derive(Debug, Clone, Copy)]
+pub struct CoinflipInput<'a> {
+    pub bet_id: &'a str,
+    pub player: &'a str,
+    pub amount: u64,
+    pub guess: bool,
+}
+
+impl<'a> CoinflipInput<'a> {
+    /// Bytes the VRF key signs
+    pub fn message(&self) -> Vec<u8> {
+        format!(
+            "{}:{}:{}:{}:{}",
+            VRF_MESSAGE_PREFIX, self.bet_id, self.player, self.amount, self.guess
+        )
+        .into_bytes()
+    }
+
+    /// The input a message was built from; None for anything message() can't produce
+    pub fn from_message(message: &'a str) -> Option<Self> {
+        let fields: Vec<&str> = message
+            .strip_prefix(VRF_MESSAGE_PREFIX)?
+            .strip_prefix(':')?
+            .split(':')
+            .collect();
+        let [bet_id, player, amount, guess] = fields[..] else {
+            return None;
+        };
+        let input = Self {
+            bet_id,
+            player,
+            amount: amount.parse().ok()?,
+            guess: guess.parse().ok()?,
+        };
+        (input.message() == message.as_bytes()).then_some(input)
+    }
+}
+
+fn outcome_from_proof(proof: &Signature) -> bool {
+    hash(proof.as_ref()).to_bytes()[0] & 1 == 1
+}
+
+/// Flip a coin for `input` on the calling thread; returns the outcome (true = heads)
+/// and its proof. Bets go through VrfSigner, which gives the same result.
+#[allow(dead_code)]
+pub fn prove_coinflip(vrf: &Keypair, input: &CoinflipInput) -> (bool, Signature) {
+    let proof = vrf.sign_message(&input.message());
+    (outcome_from_proof(&proof), proof)
+}
+
+/// Check a proof against the VRF public key; returns the outcome it commits to
+pub fn verify_coinflip(vrf: &Pubkey, input: &CoinflipInput, proof: &Signature) -> Option<bool> {
+    proof
+        .verify(vrf.as_ref(), &input.message())
+        .then(|| outcome_from_proof(proof))
+}
+
+/// Most requests signed per wake-up of the signing thread before it checks back in
+const MAX_SIGNING_BATCH: usize = 256;
+
+struct SignRequest {
+    message: Vec<u8>,
+    reply: oneshot::Sender<Signature>,
+}
+
+/// Signs VRF messages on a dedicated thread, so bets never wait on the blocking pool.
+/// Callers build the message themselves and only the signing crosses threads; every
+/// request queued when the thread wakes is signed in one pass, so concurrent bets share
+/// a wake-up instead of paying for one each.
+pub struct VrfSigner {
+    pubkey: Pubkey,
+    requests: mpsc::Sender<SignRequest>,
+}
+
+impl VrfSigner {
+    pub fn spawn(vrf: Arc<Keypair>) -> Result<Self> {
+        let pubkey = vrf.pubkey();
+        let (requests, queue) = mpsc::channel::<SignRequest>();
+        std::thread::Builder::new()
+            .name("vrf-signer".to_string())
+            .spawn(move || {
+                while let Ok(first) = queue.recv() {
+                    let batch = std::iter::once(first).chain(queue.try_iter());
+                    for request in batch.take(MAX_SIGNING_BATCH) {
+                        // The bet may have been abandoned meanwhile; nothing to reply to
+                        let _ = request.reply.send(vrf.sign_message(&request.message));
+                    }
+                }
+            })?;
+        Ok(Self { pubkey, requests })
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    /// prove_coinflip on the signing thread
+    pub async fn prove_coinflip(&self, input: &CoinflipInput<'_>) -> Result<(bool, Signature)> {
+        let (reply, proof) = oneshot::channel();
+        self.requests
+            .send(SignRequest {
+                message: input.message(),
+                reply,
+            })
+            .map_err(|_| anyhow::anyhow!("VRF signing thread has stopped"))?;
+        let proof = proof
+            .await
+            .map_err(|_| anyhow::anyhow!("VRF signing thread dropped the request"))?;
+        Ok((outcome_from_proof(&proof), proof))
+    }
+}
+
+/// Transcript leaf for one issued proof
+pub fn transcript_leaf(bet_id: &str, proof: &Signature) -> Hash {
+    hashv(&[LEAF_PREFIX, bet_id.as_bytes(), proof.as_ref()])
+}
+
+/// Merkle root over leaves in issue order. An odd node is carried up unchanged
+/// rather than paired with itself, so no two leaf lists share a root.
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Hash::default();
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = parent_level(&level);
+    }
+    level[0]
+}
+
+fn parent_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hashv(&[NODE_PREFIX, left.as_ref(), right.as_ref()]),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// One hash on the path from a leaf up to the Merkle root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleStep {
+    pub sibling: Hash,
+    pub sibling_is_left: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct VrfAnchorConfig {
+    pub interval: Duration,
+    pub max_retained: usize, // Anchors kept in memory for the transparency endpoint
+}
+
+impl Default for VrfAnchorConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+            max_retained: 288, // One day of 5-minute anchors
+        }
+    }
+}
+
+impl VrfAnchorConfig {
+    /// Load from environment (VRF_ANCHOR_INTERVAL_SECS)
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(secs) = std::env::var("VRF_ANCHOR_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&secs| secs > 0)
+        {
+            config.interval = Duration::from_secs(secs);
+        }
+        config
+    }
+}
+
+/// A transcript segment committed on-chain
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VrfAnchor {
+    pub sequence: u64,
+    pub merkle_root: String,
+    pub proof_count: u64,
+    pub first_bet_id: String,
+    pub last_bet_id: String,
+    pub memo_signature: String, // Transaction carrying the root
+    pub anchored_at: DateTime<Utc>,
+}
+
+impl VrfAnchor {
+    fn memo(sequence: u64, proof_count: u64, root: &Hash) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            ANCHOR_MEMO_PREFIX, sequence, proof_count, root
+        )
+    }
+}
+
+/// Where anchors are posted, so the transcript can run against a mock chain in tests
+#[async_trait]
+pub trait AnchorChain: Send + Sync {
+    async fn post_memo(&self, memo: &str) -> Result<String>;
+}
+
+#[async_trait]
+impl AnchorChain for SolanaClient {
+    async fn post_memo(&self, memo: &str) -> Result<String> {
+        Ok(SolanaClient::post_memo(self, memo).await?.to_string())
+    }
+}
+
+/// Records issued VRF proofs and periodically anchors them on-chain
+pub struct VrfTranscript {
+    config: VrfAnchorConfig,
+    chain: Arc<dyn AnchorChain>,
+    pending: Mutex<Vec<(String, Hash)>>, // (bet_id, leaf) since the last anchor
+    anchors: RwLock<Vec<VrfAnchor>>,
+    next_sequence: Mutex<u64>,
+}
+
+impl VrfTranscript {
+    pub fn new(config: VrfAnchorConfig, chain: Arc<dyn AnchorChain>) -> Self {
+        Self {
+            config,
+            chain,
+            pending: Mutex::new(Vec::new()),
+            anchors: RwLock::new(Vec::new()),
+            next_sequence: Mutex::new(0),
+        }
+    }
+
+    pub fn record(&self, bet_id: &str, proof: &Signature) {
+        self.pending
+            .lock()
+            .push((bet_id.to_string(), transcript_leaf(bet_id, proof)));
+    }
+
+    /// Anchor every proof recorded so far. Proofs stay pending if posting fails, so
+    /// the next anchor covers them.
+    pub async fn anchor_once(&self) -> Result<Option<VrfAnchor>> {
+        // Only this task drains; proofs recorded meanwhile land after `count`
+        let (count, root, first_bet_id, last_bet_id) = {
+            let pending = self.pending.lock();
+            let (Some(first), Some(last)) = (pending.first(), pending.last()) else {
+                return Ok(None);
+            };
+            let leaves: Vec<Hash> = pending.iter().map(|(_, leaf)| *leaf).collect();
+            (
+                pending.len(),
+                merkle_root(&leaves),
+                first.0.clone(),
+                last.0.clone(),
+            )
+        };
+
+        let sequence = *self.next_sequence.lock();
+        let memo_signature = self
+            .chain
+            .post_memo(&VrfAnchor::memo(sequence, count as u64, &root))
+            .await?;
+
+        self.pending.lock().drain(..count);
+        *self.next_sequence.lock() = sequence + 1;
+        let anchor = VrfAnchor {
+            sequence,
+            merkle_root: root.to_string(),
+            proof_count: count as u64,
+            first_bet_id,
+            last_bet_id,
+            memo_signature,
+            anchored_at: Utc::now(),
+        };
+
+        let mut anchors = self.anchors.write();
+        anchors.push(anchor.clone());
+        if anchors.len() > self.config.max_retained {
+            let excess = anchors.len() - self.config.max_retained;
+            anchors.drain(..excess);
+        }
+        Ok(Some(anchor))
+    }
+
+    /// Retained anchors, newest first
+    pub fn anchors(&self) -> Vec<VrfAnchor> {
+        self.anchors.read().iter().rev().cloned().collect()
+    }
+
+    /// Start periodic anchoring (background task)
+    pub fn start(self: Arc<Self>) {
+        info!(
+            "Starting VRF transcript anchoring (interval: {:?})",
+            self.config.interval
+        );
+        tokio::spawn(async move {
+            let mut interval = interval(self.config.interval);
+            interval.tick().await; // First tick completes immediately
+            loop {
+                interval.tick().await;
+                match self.anchor_once().await {
+                    Ok(Some(anchor)) => info!(
+                        "Anchored {} VRF proofs (root {}): {}",
+                        anchor.proof_count, anchor.merkle_root, anchor.memo_signature
+                    ),
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to anchor VRF transcript: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Default)]
+    struct MockChain {
+        memos: Mutex<Vec<String>>,
+        down: AtomicBool,
+    }
+
+    #[async_trait]
+    impl AnchorChain for MockChain {
+        async fn post_memo(&self, memo: &str) -> Result<String> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(anyhow!("rpc unavailable"));
+            }
+            let mut memos = self.memos.lock();
+            memos.push(memo.to_string());
+            Ok(format!("sig{}", memos.len()))
+        }
+    }
+
+    fn input(bet_id: &str) -> CoinflipInput<'_> {
+        CoinflipInput {
+            bet_id,
+            player: "player1",
+            amount: 1000,
+            guess: true,
+        }
+    }
+
+    #[test]
+    fn test_coinflip_proof_verifies_and_fixes_outcome() {
+        let vrf = Keypair::new();
+        let (result, proof) = prove_coinflip(&vrf, &input("bet1"));
+
+        assert_eq!(prove_coinflip(&vrf, &input("bet1")), (result, proof));
+        assert_eq!(
+            verify_coinflip(&vrf.pubkey(), &input("bet1"), &proof),
+            Some(result)
+        );
+        // Proof is bound to the bet and the key
+        assert_eq!(verify_coinflip(&vrf.pubkey(), &input("bet2"), &proof), None);
+        assert_eq!(
+            verify_coinflip(&Keypair::new().pubkey(), &input("bet1"), &proof),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signer_matches_prove_coinflip() {
+        let vrf = Arc::new(Keypair::new());
+        let signer = Arc::new(VrfSigner::spawn(vrf.clone()).unwrap());
+        assert_eq!(signer.pubkey(), vrf.pubkey());
+
+        // Concurrent requests each get the proof for their own bet
+        let tasks: Vec<_> = (0..50)
+            .map(|i| {
+                let signer = signer.clone();
+                tokio::spawn(async move {
+                    let bet_id = format!("bet{}", i);
+                    let proof = signer.prove_coinflip(&input(&bet_id)).await.unwrap();
+                    (bet_id, proof)
+                })
+            })
+            .collect();
+        for task in tasks {
+            let (bet_id, proof) = task.await.unwrap();
+            assert_eq!(proof, prove_coinflip(&vrf, &input(&bet_id)));
+        }
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let leaves: Vec<Hash> = (0..5u8).map(|i| hash(&[i])).collect();
+        let node = |l: &Hash, r: &Hash| hashv(&[NODE_PREFIX, l.as_ref(), r.as_ref()]);
+
+        assert_eq!(merkle_root(&[]), Hash::default());
+        assert_eq!(merkle_root(&leaves[..1]), leaves[0]);
+        assert_eq!(
+            merkle_root(&leaves[..3]),
+            node(&node(&leaves[0], &leaves[1]), &leaves[2])
+        );
+        assert_eq!(
+            merkle_root(&leaves),
+            node(
+                &node(&node(&leaves[0], &leaves[1]), &node(&leaves[2], &leaves[3])),
+                &leaves[4]
+            )
+        );
+
+        // Order matters, and a repeated last leaf is not the same transcript
+        let mut swapped = leaves.clone();
+        swapped.swap(0, 1);
+        assert_ne!(merkle_root(&leaves), merkle_root(&swapped));
+        let mut repeated = leaves[..3].to_vec();
+        repeated.push(leaves[2]);
+        assert_ne!(merkle_root(&leaves[..3]), merkle_root(&repeated));
+    }
+
+    #[tokio::test]
+    async fn test_anchor_covers_proofs_since_last_anchor() {
+        let chain = Arc::new(MockChain::default());
+        let transcript = VrfTranscript::new(VrfAnchorConfig::default(), chain.clone());
+        let vrf = Keypair::new();
+        let record = |bet_id: &str| {
+            let (_, proof) = prove_coinflip(&vrf, &input(bet_id));
+            transcript.record(bet_id, &proof);
+            transcript_leaf(bet_id, &proof)
+        };
+        let first = [record("bet1"), record("bet2")];
+
+        // Nothing is lost while the chain is unreachable
+        chain.down.store(true, Ordering::Relaxed);
+        assert!(transcript.anchor_once().await.is_err());
+        chain.down.store(false, Ordering::Relaxed);
+
+        let anchor = transcript.anchor_once().await.unwrap().unwrap();
+        assert_eq!(anchor.sequence, 0);
+        assert_eq!(anchor.proof_count, 2);
+        assert_eq!(anchor.first_bet_id, "bet1");
+        assert_eq!(anchor.last_bet_id, "bet2");
+        assert_eq!(anchor.merkle_root, merkle_root(&first).to_string());
+        assert_eq!(
+            chain.memos.lock()[0],
+            format!("zkcasino-vrf-anchor:v1:0:2:{}", anchor.merkle_root)
+        );
+
+        // No new proofs, no anchor
+        assert!(transcript.anchor_once().await.unwrap().is_none());
+
+        let second = [record("bet3")];
+        let anchor = transcript.anchor_once().await.unwrap().unwrap();
+        assert_eq!(anchor.sequence, 1);
+        assert_eq!(anchor.merkle_root, merkle_root(&second).to_string());
+
+        let anchors = transcript.anchors();
+        assert_eq!(anchors.len(), 2);
+        assert_eq!(anchors[0].sequence, 1);
+        assert_eq!(anchors[0].memo_signature, "sig2");
+    }
+
+    #[test]
+    fn test_transcript_matches_fixtures() {
+        use solana_sdk::signer::keypair::keypair_from_seed;
+
+        let vrf = keypair_from_seed(&fixtures::VRF_SEED).unwrap();
+        assert_eq!(
+            vrf.pubkey().to_bytes(),
+            fixtures::hex32(fixtures::BATCH.vrf_pubkey)
+        );
+
+        let mut leaves = Vec::new();
+        for bet in fixtures::BATCH.bets {
+            let bet_id = bet.bet_id.to_string();
+            let input = CoinflipInput {
+                bet_id: &bet_id,
+                player: bet.player,
+                amount: bet.bet_amount,
+                guess: bet.user_guess == 1,
+            };
+            assert_eq!(input.message(), bet.vrf_message.as_bytes());
+
+            let (outcome, proof) = prove_coinflip(&vrf, &input);
+            assert_eq!(proof.as_ref(), fixtures::hex(bet.vrf_proof));
+            assert_eq!(outcome as u8, bet.outcome);
+            assert_eq!(
+                verify_coinflip(&vrf.pubkey(), &input, &proof),
+                Some(outcome)
+            );
+
+            let leaf = transcript_leaf(&bet_id, &proof);
+            assert_eq!(leaf.to_bytes(), fixtures::hex32(bet.vrf_leaf));
+            leaves.push(leaf);
+        }
+        assert_eq!(
+            merkle_root(&leaves).to_bytes(),
+            fixtures::hex32(fixtures::BATCH.vrf_outputs_root)
+        );
+    }
+}
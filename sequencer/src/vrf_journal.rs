@@ -0,0 +1,309 @@
+// Write-ahead journal of VRF usage for ZK Casino
+// Bet records are saved in the background after the bet response goes out, so a failed
+// insert can lose a bet whose outcome the player has already seen. To keep the
+// randomness transcript complete regardless, every coin flip proof is appended to the
+// journal and synced to disk before its response is returned: the exact message the VRF
+// key signed, its nonce (the bet id the message is bound to), the key that signed it
+// and the hash of the signature. Entries are numbered without gaps, and a scan of the
+// journal shows whether any nonce was ever signed twice, i.e. whether an outcome could
+// have been re-rolled. Appends go through a dedicated thread that writes every queued
+// entry with a single sync, so concurrent bets share the cost of the fsync.
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use tokio::sync::oneshot;
+use tracing::{error, info};
+
+/// Most entries written per sync before the journal thread checks back in
+const MAX_APPEND_BATCH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VrfJournalEntry {
+    pub sequence: u64,          // Position in the journal, counting from 0 without gaps
+    pub nonce: String,          // Bet id the signed message is bound to
+    pub message: String,        // Base64 of the exact bytes the VRF key signed
+    pub key_version: String,    // VRF public key, as listed in the key registry
+    pub signature_hash: String, // Base58 SHA-256 of the VRF signature
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Result of scanning a journal
+#[derive(Debug, Default, PartialEq)]
+pub struct JournalAudit {
+    pub entries: u64,
+    pub reused_nonces: Vec<String>, // Nonces signed more than once; empty for a sound journal
+}
+
+/// Read every entry of the journal at `path` (missing = empty), checking they are
+/// numbered without gaps
+pub fn entries(path: &Path) -> Result<Vec<VrfJournalEntry>> {
+    let mut entries = Vec::new();
+    if !path.exists() {
+        return Ok(entries);
+    }
+    for (line_number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let entry: VrfJournalEntry = serde_json::from_str(&line?)
+            .map_err(|e| anyhow!("{} line {}: {}", path.display(), line_number + 1, e))?;
+        if entry.sequence != entries.len() as u64 {
+            return Err(anyhow!(
+                "{} line {}: expected entry {}, found {}",
+                path.display(),
+                line_number + 1,
+                entries.len(),
+                entry.sequence
+            ));
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Scan the journal at `path` (see entries) and collect any nonce that appears more
+/// than once
+pub fn audit(path: &Path) -> Result<JournalAudit> {
+    let mut audit = JournalAudit::default();
+    let mut nonces = HashSet::new();
+    for entry in entries(path)? {
+        if !nonces.insert(entry.nonce.clone()) {
+            audit.reused_nonces.push(entry.nonce);
+        }
+        audit.entries += 1;
+    }
+    Ok(audit)
+}
+
+struct AppendRequest {
+    entry: VrfJournalEntry, // Sequence assigned by the journal thread
+    reply: oneshot::Sender<Result<u64, String>>,
+}
+
+pub struct VrfJournal {
+    requests: mpsc::Sender<AppendRequest>,
+}
+
+impl VrfJournal {
+    /// Journal next to the database, or at VRF_JOURNAL_PATH when set; None for
+    /// in-memory databases without an explicit path
+    pub fn path_from_env(database_url: &str) -> Option<PathBuf> {
+        if let Ok(path) = std::env::var("VRF_JOURNAL_PATH") {
+            return Some(PathBuf::from(path));
+        }
+        match database_url {
+            "sqlite::memory:" => None,
+            url => Some(
+                Path::new(url.strip_prefix("sqlite:").unwrap_or(url))
+                    .with_extension("vrf-journal.jsonl"),
+            ),
+        }
+    }
+
+    /// Audit the existing journal and start appending after its last entry. Refuses to
+    /// open a journal that doesn't scan cleanly, so numbering is never forked.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let audit = audit(&path)?;
+        if audit.reused_nonces.is_empty() {
+            info!(
+                "VRF journal {}: {} entries, no nonce reuse",
+                path.display(),
+                audit.entries
+            );
+        } else {
+            error!(
+                "VRF journal {}: nonces signed more than once: {}",
+                path.display(),
+                audit.reused_nonces.join(", ")
+            );
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let (requests, queue) = mpsc::channel::<AppendRequest>();
+        let mut next_sequence = audit.entries;
+        std::thread::Builder::new()
+            .name("vrf-journal".to_string())
+            .spawn(move || {
+                while let Ok(first) = queue.recv() {
+                    let batch: Vec<AppendRequest> = std::iter::once(first)
+                        .chain(queue.try_iter())
+                        .take(MAX_APPEND_BATCH)
+                        .collect();
+                    let mut lines = Vec::new();
+                    let mut sequence = next_sequence;
+                    for request in &batch {
+                        let entry = VrfJournalEntry {
+                            sequence,
+                            ..request.entry.clone()
+                        };
+                        // Serializing plain strings and numbers cannot fail
+                        serde_json::to_writer(&mut lines, &entry).expect("journal entry");
+                        lines.push(b'\n');
+                        sequence += 1;
+                    }
+                    // A failed write is cut back off, so no torn line is left to append after
+                    let written = file.metadata().and_then(|metadata| {
+                        file.write_all(&lines)
+                            .and_then(|_| file.sync_data())
+                            .inspect_err(|_| {
+                                let _ = file.set_len(metadata.len());
+                            })
+                    });
+                    match written {
+                        Ok(()) => {
+                            for (offset, request) in batch.into_iter().enumerate() {
+                                let _ = request.reply.send(Ok(next_sequence + offset as u64));
+                            }
+                            next_sequence = sequence;
+                        }
+                        Err(e) => {
+                            for request in batch {
+                                let _ = request.reply.send(Err(e.to_string()));
+                            }
+                        }
+                    }
+                }
+            })?;
+        Ok(Self { requests })
+    }
+
+    /// Append a proof and wait until it is on disk; returns its sequence number
+    pub async fn append(
+        &self,
+        nonce: &str,
+        message: &[u8],
+        key: &Pubkey,
+        proof: &Signature,
+    ) -> Result<u64> {
+        let (reply, appended) = oneshot::channel();
+        self.requests
+            .send(AppendRequest {
+                entry: VrfJournalEntry {
+                    sequence: 0,
+                    nonce: nonce.to_string(),
+                    message: BASE64.encode(message),
+                    key_version: key.to_string(),
+                    signature_hash: hash(proof.as_ref()).to_string(),
+                    recorded_at: Utc::now(),
+                },
+                reply,
+            })
+            .map_err(|_| anyhow!("VRF journal thread has stopped"))?;
+        appended
+            .await
+            .map_err(|_| anyhow!("VRF journal thread dropped the request"))?
+            .map_err(|e| anyhow!("Failed to append to VRF journal: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vrf::{prove_coinflip, CoinflipInput};
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use std::sync::Arc;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "zkcasino-vrf-journal-{}.jsonl",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    async fn append_bet(journal: &VrfJournal, vrf: &Keypair, bet_id: &str) -> u64 {
+        let input = CoinflipInput {
+            bet_id,
+            player: "player1",
+            amount: 1000,
+            guess: true,
+        };
+        let (_, proof) = prove_coinflip(vrf, &input);
+        journal
+            .append(bet_id, &input.message(), &vrf.pubkey(), &proof)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_journal_appends_in_order_and_resumes() {
+        let path = temp_path();
+        let vrf = Keypair::new();
+        let journal = Arc::new(VrfJournal::open(path.clone()).unwrap());
+
+        // Concurrent appends each get their own place in the journal
+        let vrf = Arc::new(vrf);
+        let tasks: Vec<_> = (0..20)
+            .map(|i| {
+                let (journal, vrf) = (journal.clone(), vrf.clone());
+                tokio::spawn(async move { append_bet(&journal, &vrf, &format!("bet{}", i)).await })
+            })
+            .collect();
+        let mut sequences = Vec::new();
+        for task in tasks {
+            sequences.push(task.await.unwrap());
+        }
+        sequences.sort();
+        assert_eq!(sequences, (0..20).collect::<Vec<u64>>());
+
+        let entries: Vec<VrfJournalEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let entry = entries.iter().find(|entry| entry.nonce == "bet3").unwrap();
+        let input = CoinflipInput {
+            bet_id: "bet3",
+            player: "player1",
+            amount: 1000,
+            guess: true,
+        };
+        let (_, proof) = prove_coinflip(&vrf, &input);
+        assert_eq!(BASE64.decode(&entry.message).unwrap(), input.message());
+        assert_eq!(entry.key_version, vrf.pubkey().to_string());
+        assert_eq!(entry.signature_hash, hash(proof.as_ref()).to_string());
+
+        // Reopening continues the numbering
+        drop(journal);
+        let journal = VrfJournal::open(path.clone()).unwrap();
+        assert_eq!(append_bet(&journal, &vrf, "bet20").await, 20);
+        assert_eq!(
+            audit(&path).unwrap(),
+            JournalAudit {
+                entries: 21,
+                reused_nonces: vec![],
+            }
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_audit_finds_reused_nonces_and_gaps() {
+        let path = temp_path();
+        let vrf = Keypair::new();
+        let journal = VrfJournal::open(path.clone()).unwrap();
+        append_bet(&journal, &vrf, "bet1").await;
+        append_bet(&journal, &vrf, "bet2").await;
+        append_bet(&journal, &vrf, "bet1").await;
+        assert_eq!(
+            audit(&path).unwrap().reused_nonces,
+            vec!["bet1".to_string()]
+        );
+
+        // A removed entry breaks the numbering, and the journal won't reopen
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(audit(&path).is_err());
+        assert!(VrfJournal::open(path.clone()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+// Withdrawal fees for ZK Casino
+// Withdrawals can carry a flat fee, a percentage fee and, optionally, the Solana network
+// fee of the payout transaction passed through to the player. Fees come out of the
+// requested amount: the player's balance is debited the full amount and receives the
+// rest, rounded down to the token's unit. Every fee component, including that rounding
+// dust, is itemized on the withdrawal's ledger entry.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Network fee assumed when the actual fee can't be looked up (one signature)
+pub const DEFAULT_NETWORK_FEE_LAMPORTS: u64 = 5000;
+
+#[derive(Debug, Clone, Default)]
+pub struct WithdrawalFeeConfig {
+    pub flat_lamports: u64,
+    pub percentage_bps: u64, // Basis points of the withdrawal amount
+    pub pass_through_network_fee: bool,
+}
+
+impl WithdrawalFeeConfig {
+    /// Load from environment (WITHDRAWAL_FEE_FLAT_LAMPORTS, WITHDRAWAL_FEE_BPS,
+    /// WITHDRAWAL_PASS_THROUGH_NETWORK_FEE)
+    pub fn from_env() -> Result<Self> {
+        let env_u64 = |name: &str| -> Result<u64> {
+            match std::env::var(name) {
+                Ok(v) => v.parse().map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(0),
+            }
+        };
+        let config = Self {
+            flat_lamports: env_u64("WITHDRAWAL_FEE_FLAT_LAMPORTS")?,
+            percentage_bps: env_u64("WITHDRAWAL_FEE_BPS")?,
+            pass_through_network_fee: std::env::var("WITHDRAWAL_PASS_THROUGH_NETWORK_FEE")
+                .unwrap_or_default()
+                == "true",
+        };
+        if config.percentage_bps > BPS_DENOMINATOR {
+            return Err(anyhow!(
+                "WITHDRAWAL_FEE_BPS cannot exceed {}",
+                BPS_DENOMINATOR
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Fees for withdrawing `amount`, given the network fee of the payout transaction
+    pub fn fees(&self, amount: u64, network_fee: u64) -> WithdrawalFees {
+        WithdrawalFees {
+            flat: self.flat_lamports,
+            percentage: (amount as u128 * self.percentage_bps as u128 / BPS_DENOMINATOR as u128)
+                as u64,
+            network: if self.pass_through_network_fee {
+                network_fee
+            } else {
+                0
+            },
+            dust: 0,
+        }
+    }
+}
+
+/// Itemized fees charged on a withdrawal, in lamports
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WithdrawalFees {
+    pub flat: u64,
+    pub percentage: u64,
+    pub network: u64,
+    #[serde(default)]
+    pub dust: u64, // Net amount finer than the token's unit
+}
+
+impl WithdrawalFees {
+    pub fn total(&self) -> u64 {
+        self.house_share().saturating_add(self.network)
+    }
+
+    /// Fees kept by the house; the network fee is paid on to the network
+    pub fn house_share(&self) -> u64 {
+        self.flat
+            .saturating_add(self.percentage)
+            .saturating_add(self.dust)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_components() {
+        let config = WithdrawalFeeConfig {
+            flat_lamports: 1000,
+            percentage_bps: 50, // 0.5%
+            pass_through_network_fee: false,
+        };
+        let fees = config.fees(1_000_000, 5000);
+        assert_eq!(
+            fees,
+            WithdrawalFees {
+                flat: 1000,
+                percentage: 5000,
+                network: 0,
+                dust: 0,
+            }
+        );
+        assert_eq!(fees.total(), 6000);
+
+        let config = WithdrawalFeeConfig {
+            pass_through_network_fee: true,
+            ..config
+        };
+        assert_eq!(config.fees(1_000_000, 5000).network, 5000);
+
+        // No fees configured
+        assert_eq!(
+            WithdrawalFeeConfig::default().fees(u64::MAX, 5000).total(),
+            0
+        );
+    }
+}
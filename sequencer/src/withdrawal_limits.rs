@@ -0,0 +1,453 @@
+// Withdrawal velocity limits for ZK Casino
+// Caps how much one player can withdraw per rolling hour and per rolling day, and how
+// much leaves the hot wallet across all players per rolling day. Limits are checked and
+// the withdrawal reserved in one step before the balance is debited, so concurrent
+// requests can't slip past a limit together. Player limits count the requested amount;
+// the hot-wallet cap counts the net payout that actually leaves the wallet. An admin can
+// exempt a player for a while (e.g. a verified large cash-out). A rejected withdrawal
+// raises an alert (logged, kept for the admin API and optionally posted to a webhook),
+// at most once an hour per player and limit.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Alerts kept in memory for the admin API
+const MAX_RETAINED_ALERTS: usize = 100;
+/// Repeat rejections of the same player and limit alert again after this long
+const ALERT_SUPPRESSION_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WithdrawalLimitConfig {
+    pub player_hourly_lamports: Option<u64>,
+    pub player_daily_lamports: Option<u64>,
+    pub global_daily_lamports: Option<u64>, // Hot-wallet outflow across all players
+    #[serde(skip)]
+    pub webhook_url: Option<String>,
+}
+
+impl WithdrawalLimitConfig {
+    /// Load from environment (WITHDRAWAL_PLAYER_HOURLY_LIMIT_LAMPORTS,
+    /// WITHDRAWAL_PLAYER_DAILY_LIMIT_LAMPORTS, WITHDRAWAL_GLOBAL_DAILY_LIMIT_LAMPORTS,
+    /// WITHDRAWAL_LIMIT_ALERT_WEBHOOK_URL); unset limits don't apply
+    pub fn from_env() -> Result<Self> {
+        let env_limit = |name: &str| -> Result<Option<u64>> {
+            match std::env::var(name) {
+                Ok(v) => v
+                    .parse()
+                    .map(Some)
+                    .map_err(|e| anyhow!("Invalid {}: {}", name, e)),
+                Err(_) => Ok(None),
+            }
+        };
+        Ok(Self {
+            player_hourly_lamports: env_limit("WITHDRAWAL_PLAYER_HOURLY_LIMIT_LAMPORTS")?,
+            player_daily_lamports: env_limit("WITHDRAWAL_PLAYER_DAILY_LIMIT_LAMPORTS")?,
+            global_daily_lamports: env_limit("WITHDRAWAL_GLOBAL_DAILY_LIMIT_LAMPORTS")?,
+            webhook_url: std::env::var("WITHDRAWAL_LIMIT_ALERT_WEBHOOK_URL").ok(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalLimit {
+    PlayerHourly,
+    PlayerDaily,
+    GlobalDaily,
+}
+
+impl WithdrawalLimit {
+    fn window(&self) -> ChronoDuration {
+        match self {
+            Self::PlayerHourly => ChronoDuration::hours(1),
+            Self::PlayerDaily | Self::GlobalDaily => ChronoDuration::days(1),
+        }
+    }
+}
+
+/// A withdrawal rejected by a limit; also the alert raised for it
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LimitExceeded {
+    pub limit: WithdrawalLimit,
+    pub player_address: String,
+    pub limit_lamports: u64,
+    pub used_lamports: u64, // Already withdrawn within the limit's window
+    pub requested_lamports: u64,
+    pub retry_at: Option<DateTime<Utc>>, // None when the request alone exceeds the limit
+    pub raised_at: DateTime<Utc>,
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Withdrawal exceeds the {:?} limit of {} lamports ({} already withdrawn)",
+            self.limit, self.limit_lamports, self.used_lamports
+        )?;
+        if let Some(retry_at) = self.retry_at {
+            write!(f, "; retry after {}", retry_at.to_rfc3339())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WithdrawalLimitOverride {
+    pub player_address: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WithdrawalLimitsSnapshot {
+    pub config: WithdrawalLimitConfig,
+    pub global_daily_used_lamports: u64,
+    pub overrides: Vec<WithdrawalLimitOverride>,
+    pub alerts: Vec<LimitExceeded>, // Most recent first
+}
+
+/// Held between the limit check and the debit; release it if the withdrawal fails
+#[derive(Debug)]
+pub struct WithdrawalReservation {
+    id: u64,
+    player_address: String,
+}
+
+struct Outflow {
+    id: u64,
+    at: DateTime<Utc>,
+    amount: u64,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    next_id: u64,
+    players: HashMap<String, VecDeque<Outflow>>, // Requested amounts, oldest first
+    global: VecDeque<Outflow>,                   // Net payouts, oldest first
+    overrides: HashMap<String, DateTime<Utc>>,
+}
+
+/// Sum of outflows within `window` of `now`, and when enough of them age out for
+/// `requested` to fit under `limit` (None if it never fits)
+fn window_usage(
+    outflows: &VecDeque<Outflow>,
+    window: ChronoDuration,
+    now: DateTime<Utc>,
+    limit: u64,
+    requested: u64,
+) -> (u64, Option<DateTime<Utc>>) {
+    let recent: Vec<&Outflow> = outflows.iter().filter(|o| o.at > now - window).collect();
+    let used: u64 = recent.iter().map(|o| o.amount).sum();
+    if requested > limit {
+        return (used, None);
+    }
+    let mut remaining = used;
+    for outflow in recent {
+        if remaining.saturating_add(requested) <= limit {
+            break;
+        }
+        remaining -= outflow.amount;
+        if remaining.saturating_add(requested) <= limit {
+            return (used, Some(outflow.at + window));
+        }
+    }
+    (used, Some(now))
+}
+
+pub struct WithdrawalLimiter {
+    config: WithdrawalLimitConfig,
+    state: Mutex<LimiterState>,
+    alerts: Mutex<VecDeque<LimitExceeded>>,
+    last_alerted: Mutex<HashMap<(String, WithdrawalLimit), DateTime<Utc>>>,
+    client: reqwest::Client,
+}
+
+impl WithdrawalLimiter {
+    pub fn new(config: WithdrawalLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(LimiterState::default()),
+            alerts: Mutex::new(VecDeque::new()),
+            last_alerted: Mutex::new(HashMap::new()),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Check a withdrawal of `amount` (paying out `net_amount`) against every limit and
+    /// record it if it fits
+    pub fn reserve(
+        &self,
+        player_address: &str,
+        amount: u64,
+        net_amount: u64,
+        now: DateTime<Utc>,
+    ) -> Result<WithdrawalReservation, LimitExceeded> {
+        let mut state = self.state.lock();
+        let day = ChronoDuration::days(1);
+        state.global.retain(|o| o.at > now - day);
+        if let Some(outflows) = state.players.get_mut(player_address) {
+            outflows.retain(|o| o.at > now - day);
+        }
+        state.overrides.retain(|_, expires_at| *expires_at > now);
+
+        if !state.overrides.contains_key(player_address) {
+            let empty = VecDeque::new();
+            let player_outflows = state.players.get(player_address).unwrap_or(&empty);
+            let checks = [
+                (
+                    WithdrawalLimit::PlayerHourly,
+                    self.config.player_hourly_lamports,
+                    player_outflows,
+                    amount,
+                ),
+                (
+                    WithdrawalLimit::PlayerDaily,
+                    self.config.player_daily_lamports,
+                    player_outflows,
+                    amount,
+                ),
+                (
+                    WithdrawalLimit::GlobalDaily,
+                    self.config.global_daily_lamports,
+                    &state.global,
+                    net_amount,
+                ),
+            ];
+            let exceeded =
+                checks
+                    .into_iter()
+                    .find_map(|(limit, limit_lamports, outflows, requested)| {
+                        let limit_lamports = limit_lamports?;
+                        let (used, retry_at) =
+                            window_usage(outflows, limit.window(), now, limit_lamports, requested);
+                        (used.saturating_add(requested) > limit_lamports).then(|| LimitExceeded {
+                            limit,
+                            player_address: player_address.to_string(),
+                            limit_lamports,
+                            used_lamports: used,
+                            requested_lamports: requested,
+                            retry_at,
+                            raised_at: now,
+                        })
+                    });
+            if let Some(exceeded) = exceeded {
+                drop(state);
+                self.alert(&exceeded);
+                return Err(exceeded);
+            }
+        }
+
+        state.next_id += 1;
+        let id = state.next_id;
+        state
+            .players
+            .entry(player_address.to_string())
+            .or_default()
+            .push_back(Outflow {
+                id,
+                at: now,
+                amount,
+            });
+        state.global.push_back(Outflow {
+            id,
+            at: now,
+            amount: net_amount,
+        });
+        Ok(WithdrawalReservation {
+            id,
+            player_address: player_address.to_string(),
+        })
+    }
+
+    /// Give back a reservation whose withdrawal did not go through
+    pub fn release(&self, reservation: WithdrawalReservation) {
+        let mut state = self.state.lock();
+        if let Some(outflows) = state.players.get_mut(&reservation.player_address) {
+            outflows.retain(|o| o.id != reservation.id);
+        }
+        state.global.retain(|o| o.id != reservation.id);
+    }
+
+    /// Exempt a player from all limits until `expires_at` (admin override)
+    pub fn grant_override(
+        &self,
+        player_address: &str,
+        expires_at: DateTime<Utc>,
+    ) -> WithdrawalLimitOverride {
+        self.state
+            .lock()
+            .overrides
+            .insert(player_address.to_string(), expires_at);
+        info!(
+            "Withdrawal limits lifted for {} until {}",
+            player_address, expires_at
+        );
+        WithdrawalLimitOverride {
+            player_address: player_address.to_string(),
+            expires_at,
+        }
+    }
+
+    /// Record an alert, unless the same player and limit alerted within the last hour
+    fn alert(&self, exceeded: &LimitExceeded) {
+        {
+            let mut last_alerted = self.last_alerted.lock();
+            let key = (exceeded.player_address.clone(), exceeded.limit);
+            if let Some(at) = last_alerted.get(&key) {
+                if exceeded.raised_at - *at < ChronoDuration::minutes(ALERT_SUPPRESSION_MINUTES) {
+                    return;
+                }
+            }
+            last_alerted.insert(key, exceeded.raised_at);
+        }
+
+        warn!(
+            "Withdrawal limit alert for {}: {}",
+            exceeded.player_address, exceeded
+        );
+        let mut alerts = self.alerts.lock();
+        alerts.push_front(exceeded.clone());
+        alerts.truncate(MAX_RETAINED_ALERTS);
+        drop(alerts);
+        self.notify(exceeded);
+    }
+
+    /// Post an alert to the webhook, if configured, without blocking the caller
+    fn notify(&self, exceeded: &LimitExceeded) {
+        let Some(url) = self.config.webhook_url.clone() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let request = self.client.post(url).json(exceeded);
+        runtime.spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => warn!("Failed to deliver withdrawal limit alert: {}", e),
+            }
+        });
+    }
+
+    pub fn snapshot(&self, now: DateTime<Utc>) -> WithdrawalLimitsSnapshot {
+        let state = self.state.lock();
+        let day = ChronoDuration::days(1);
+        let mut overrides: Vec<WithdrawalLimitOverride> = state
+            .overrides
+            .iter()
+            .filter(|(_, expires_at)| **expires_at > now)
+            .map(|(player_address, expires_at)| WithdrawalLimitOverride {
+                player_address: player_address.clone(),
+                expires_at: *expires_at,
+            })
+            .collect();
+        overrides.sort_by(|a, b| a.player_address.cmp(&b.player_address));
+        WithdrawalLimitsSnapshot {
+            config: self.config.clone(),
+            global_daily_used_lamports: state
+                .global
+                .iter()
+                .filter(|o| o.at > now - day)
+                .map(|o| o.amount)
+                .sum(),
+            overrides,
+            alerts: self.alerts.lock().iter().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> WithdrawalLimiter {
+        WithdrawalLimiter::new(WithdrawalLimitConfig {
+            player_hourly_lamports: Some(1_000),
+            player_daily_lamports: Some(3_000),
+            global_daily_lamports: Some(4_000),
+            webhook_url: None,
+        })
+    }
+
+    #[test]
+    fn test_player_and_global_limits() {
+        let limiter = limiter();
+        let start = Utc::now();
+        let minutes = |m: i64| start + ChronoDuration::minutes(m);
+
+        limiter.reserve("alice", 600, 600, start).unwrap();
+        let exceeded = limiter.reserve("alice", 500, 500, minutes(10)).unwrap_err();
+        assert_eq!(exceeded.limit, WithdrawalLimit::PlayerHourly);
+        assert_eq!(exceeded.used_lamports, 600);
+        assert_eq!(exceeded.retry_at, Some(minutes(60)));
+
+        // Another player has their own hourly allowance
+        limiter.reserve("bob", 1_000, 1_000, minutes(10)).unwrap();
+
+        // The hourly window rolls, the daily one doesn't
+        for hour in 1..=2 {
+            limiter
+                .reserve("alice", 1_000, 1_000, minutes(hour * 61))
+                .unwrap();
+        }
+        let exceeded = limiter
+            .reserve("alice", 500, 500, minutes(250))
+            .unwrap_err();
+        assert_eq!(exceeded.limit, WithdrawalLimit::PlayerDaily);
+        assert_eq!(exceeded.used_lamports, 2_600);
+
+        // 3_600 left the hot wallet today; only 400 more fits
+        let exceeded = limiter
+            .reserve("carol", 500, 500, minutes(250))
+            .unwrap_err();
+        assert_eq!(exceeded.limit, WithdrawalLimit::GlobalDaily);
+        assert_eq!(exceeded.retry_at, Some(start + ChronoDuration::days(1)));
+        limiter.reserve("carol", 500, 400, minutes(250)).unwrap();
+
+        // A request above the limit itself can never succeed
+        let exceeded = limiter.reserve("dave", 2_000, 0, minutes(250)).unwrap_err();
+        assert_eq!(exceeded.retry_at, None);
+
+        // Repeat rejections of the same player and limit alert once
+        let snapshot = limiter.snapshot(minutes(250));
+        assert_eq!(snapshot.global_daily_used_lamports, 4_000);
+        assert_eq!(snapshot.alerts.len(), 4);
+        limiter
+            .reserve("alice", 500, 500, minutes(251))
+            .unwrap_err();
+        assert_eq!(limiter.snapshot(minutes(251)).alerts.len(), 4);
+    }
+
+    #[test]
+    fn test_release_and_override() {
+        let limiter = limiter();
+        let now = Utc::now();
+
+        // A withdrawal that failed after reserving doesn't count
+        let reservation = limiter.reserve("alice", 1_000, 1_000, now).unwrap();
+        limiter.release(reservation);
+        let reservation = limiter.reserve("alice", 1_000, 1_000, now).unwrap();
+        assert!(limiter.reserve("alice", 1, 1, now).is_err());
+
+        // An override lifts every limit until it expires, but still counts the outflow
+        limiter.grant_override("alice", now + ChronoDuration::hours(1));
+        limiter.reserve("alice", 5_000, 5_000, now).unwrap();
+        assert_eq!(limiter.snapshot(now).overrides.len(), 1);
+        assert_eq!(limiter.snapshot(now).global_daily_used_lamports, 6_000);
+        assert!(limiter
+            .reserve("alice", 1, 1, now + ChronoDuration::hours(2))
+            .is_err());
+        assert!(limiter
+            .snapshot(now + ChronoDuration::hours(2))
+            .overrides
+            .is_empty());
+        limiter.release(reservation);
+    }
+}
@@ -0,0 +1,209 @@
+// Withdrawal proofs for ZK Casino
+// Payment processors and bridges that honor payouts off-platform need more than the
+// balance API's word for it. A withdrawal statement pins a player's balance to the
+// settled state: the player's leaf in the balance tree (see state_root), its Merkle proof
+// and the root it reaches, and the last confirmed batch that root includes. The whole
+// statement is signed with the sequencer key. A processor checks the signature and the
+// proof, then compares the root with the verifier program's on-chain state root.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::Signer;
+use std::str::FromStr;
+
+use crate::state_root::{self, SettledBalances};
+use crate::vrf::MerkleStep;
+
+/// Domain separator, so a statement signature can't pass for any other sequencer signature
+const DOMAIN: &[u8] = b"zkcasino-withdrawal-v1:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiblingPosition {
+    Left,
+    Right,
+}
+
+/// One Merkle proof step, hashed as Poseidon(left, right)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub sibling: String, // Base58
+    pub position: SiblingPosition,
+}
+
+/// Statement contents covered by the signature
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawalStatementBody {
+    pub player_address: String,
+    pub balance: i64,         // Off-chain balance when the statement was made
+    pub settled_balance: i64, // Net of the player's settled bets, the proven leaf
+    pub last_settled_batch: Option<u64>, // Last confirmed batch the root includes
+    pub state_root: String,   // Base58, as stored by the verifier program
+    pub proof: Vec<ProofStep>, // Leaf to root
+    pub generated_at: DateTime<Utc>,
+}
+
+impl WithdrawalStatementBody {
+    /// Build a statement for a player with a leaf in the settled balances
+    pub fn new(
+        player_address: &str,
+        balance: i64,
+        settled: &SettledBalances,
+        last_settled_batch: Option<u64>,
+        generated_at: DateTime<Utc>,
+    ) -> Option<Self> {
+        let (settled_balance, steps) = state_root::balance_proof(settled, player_address)?;
+        Some(Self {
+            player_address: player_address.to_string(),
+            balance,
+            settled_balance,
+            last_settled_batch,
+            state_root: Hash::new_from_array(state_root::state_root(settled)).to_string(),
+            proof: steps
+                .iter()
+                .map(|step| ProofStep {
+                    sibling: step.sibling.to_string(),
+                    position: if step.sibling_is_left {
+                        SiblingPosition::Left
+                    } else {
+                        SiblingPosition::Right
+                    },
+                })
+                .collect(),
+            generated_at,
+        })
+    }
+
+    /// Check the settled balance against the state root
+    #[allow(dead_code)]
+    pub fn verify_proof(&self) -> Result<bool> {
+        let root =
+            Hash::from_str(&self.state_root).map_err(|e| anyhow!("Invalid state root: {}", e))?;
+        let steps = self
+            .proof
+            .iter()
+            .map(|step| {
+                Ok(MerkleStep {
+                    sibling: Hash::from_str(&step.sibling)
+                        .map_err(|e| anyhow!("Invalid proof sibling: {}", e))?,
+                    sibling_is_left: step.position == SiblingPosition::Left,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(state_root::verify_balance_proof(
+            &root.to_bytes(),
+            &self.player_address,
+            self.settled_balance,
+            &steps,
+        ))
+    }
+
+    fn signed_message(&self) -> Result<Vec<u8>> {
+        let mut message = DOMAIN.to_vec();
+        message.extend(serde_json::to_vec(self)?);
+        Ok(message)
+    }
+}
+
+/// Signed withdrawal statement (ed25519 over the domain and the JSON encoding of the body)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalStatement {
+    #[serde(flatten)]
+    pub body: WithdrawalStatementBody,
+    pub signer: String,
+    pub signature: String,
+}
+
+impl WithdrawalStatement {
+    /// Sign a statement body with the sequencer key
+    pub fn sign(body: WithdrawalStatementBody, signer: &Keypair) -> Result<Self> {
+        let signature = signer.sign_message(&body.signed_message()?);
+        Ok(Self {
+            body,
+            signer: signer.pubkey().to_string(),
+            signature: signature.to_string(),
+        })
+    }
+
+    /// Check the signature against the embedded signer and the proof against the root
+    #[allow(dead_code)]
+    pub fn verify(&self) -> Result<bool> {
+        let signer =
+            Pubkey::from_str(&self.signer).map_err(|e| anyhow!("Invalid signer: {}", e))?;
+        let signature = Signature::from_str(&self.signature)
+            .map_err(|e| anyhow!("Invalid signature: {}", e))?;
+        Ok(
+            signature.verify(signer.as_ref(), &self.body.signed_message()?)
+                && self.body.verify_proof()?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settled() -> SettledBalances {
+        let items: Vec<crate::SettlementItem> = [
+            ("alice", 1_000, 2_000),
+            ("bob", 500, 0),
+            ("carol", 100, 400),
+        ]
+        .into_iter()
+        .map(|(player, amount, payout)| crate::SettlementItem {
+            bet_id: format!("{}-bet", player),
+            player_address: player.to_string(),
+            amount,
+            payout,
+            timestamp: Utc::now(),
+            token: Default::default(),
+            game: Default::default(),
+            vrf_proof: None,
+        })
+        .collect();
+        let mut settled = SettledBalances::new();
+        state_root::apply_items(&mut settled, &items).unwrap();
+        settled
+    }
+
+    #[test]
+    fn test_signed_withdrawal_statement() {
+        let keypair = Keypair::new();
+        let body =
+            WithdrawalStatementBody::new("carol", 4_300, &settled(), Some(7), Utc::now()).unwrap();
+        assert_eq!(body.settled_balance, 300);
+        assert_eq!(
+            body.state_root,
+            Hash::new_from_array(state_root::state_root(&settled())).to_string()
+        );
+        let statement = WithdrawalStatement::sign(body, &keypair).unwrap();
+        assert!(statement.verify().unwrap());
+
+        // Survives a JSON round trip
+        let json = serde_json::to_string(&statement).unwrap();
+        let decoded: WithdrawalStatement = serde_json::from_str(&json).unwrap();
+        assert!(decoded.verify().unwrap());
+
+        // A re-signed statement with an inflated leaf no longer matches the root
+        let mut inflated = statement.body.clone();
+        inflated.settled_balance = 30_000;
+        assert!(!inflated.verify_proof().unwrap());
+        assert!(!WithdrawalStatement::sign(inflated, &keypair)
+            .unwrap()
+            .verify()
+            .unwrap());
+
+        // Tampering with the signed balance invalidates the signature
+        let mut tampered = statement.clone();
+        tampered.body.balance = 40_000;
+        assert!(!tampered.verify().unwrap());
+
+        assert!(
+            WithdrawalStatementBody::new("dave", 100, &settled(), Some(7), Utc::now()).is_none()
+        );
+    }
+}